@@ -0,0 +1,699 @@
+use std::{collections::{HashMap, VecDeque}, rc::Rc};
+use xelis_bytecode::{Module, OpCode};
+use xelis_types::{Constant, Type, Value};
+
+use super::{
+    expr::{self, Expr},
+    solver::Condition,
+    AnalyzerError,
+    FindingKind
+};
+
+// Max count of values iterated from a known range, a bigger one is unknown
+const MAX_RANGE_VALUES: u64 = 1024;
+
+// Natives whose behavior is modeled
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NativeKind {
+    Panic,
+    Assert,
+    AssertEq,
+    // A controlled failure, the path ends without a finding
+    Revert,
+    // Its result is unknown and its instance may be updated
+    Other,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct Native {
+    pub kind: NativeKind,
+    pub returns: bool,
+}
+
+// What the instructions depending on the module and the environment do
+pub struct Program<'a> {
+    pub module: &'a Module,
+    pub natives: Vec<Native>,
+    // Count of fields of each struct by id, the environment ones first
+    pub structs: Vec<usize>,
+    // Count of fields of each variant of each enum by id
+    pub enums: HashMap<u16, Vec<usize>>,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct Limits {
+    // Instructions executed by a path, the loops are unrolled up to it
+    pub max_steps: usize,
+    // Paths started, each conditional jump on an argument starts a new one
+    pub max_paths: usize,
+    pub max_call_depth: usize,
+}
+
+// A failure reachable by a path
+#[derive(Debug)]
+pub struct Goal {
+    pub chunk: usize,
+    pub offset: usize,
+    pub kind: FindingKind,
+    // Conditions of the jumps taken, with their expected value
+    pub constraints: Vec<(Rc<Expr>, bool)>,
+    pub condition: Condition,
+}
+
+#[derive(Debug, Default)]
+pub struct Exploration {
+    pub goals: Vec<Goal>,
+    // Paths executed until their end
+    pub paths: usize,
+    // Paths stopped by a limit
+    pub truncated: usize,
+}
+
+// Value in the stack, with the register it references to assign it
+#[derive(Debug, Clone)]
+struct Slot {
+    expr: Rc<Expr>,
+    register: Option<(usize, usize)>,
+}
+
+impl Slot {
+    fn new(expr: Rc<Expr>) -> Self {
+        Self {
+            expr,
+            register: None
+        }
+    }
+
+    fn unknown() -> Self {
+        Self::new(Rc::new(Expr::Unknown))
+    }
+}
+
+#[derive(Debug, Clone)]
+enum Iter {
+    Values(VecDeque<Rc<Expr>>),
+    Unknown,
+}
+
+#[derive(Debug, Clone)]
+struct Frame {
+    chunk: usize,
+    pc: usize,
+    registers: Vec<Rc<Expr>>,
+    iterators: Vec<Iter>,
+}
+
+#[derive(Debug, Clone)]
+struct State {
+    stack: Vec<Slot>,
+    frames: Vec<Frame>,
+    constraints: Vec<(Rc<Expr>, bool)>,
+    steps: usize,
+}
+
+enum Flow {
+    Continue,
+    Done,
+    Truncated,
+}
+
+// Instruction being executed
+struct Instruction<'b> {
+    chunk: usize,
+    offset: usize,
+    next: usize,
+    args: &'b [u8],
+}
+
+impl Instruction<'_> {
+    fn u8(&self, index: usize) -> u8 {
+        self.args[index]
+    }
+
+    fn u16(&self, index: usize) -> usize {
+        u16::from_le_bytes([self.args[index], self.args[index + 1]]) as usize
+    }
+
+    fn u32(&self, index: usize) -> usize {
+        u32::from_le_bytes([self.args[index], self.args[index + 1], self.args[index + 2], self.args[index + 3]]) as usize
+    }
+
+    fn invalid(&self) -> AnalyzerError {
+        AnalyzerError::InvalidInstruction { chunk: self.chunk, offset: self.offset }
+    }
+}
+
+impl State {
+    fn frame(&mut self) -> &mut Frame {
+        self.frames.last_mut().expect("a path has a frame until its end")
+    }
+
+    fn pop(&mut self, instruction: &Instruction) -> Result<Slot, AnalyzerError> {
+        self.stack.pop().ok_or_else(|| instruction.invalid())
+    }
+
+    fn pop_n(&mut self, n: usize, instruction: &Instruction) -> Result<Vec<Slot>, AnalyzerError> {
+        let len = self.stack.len().checked_sub(n).ok_or_else(|| instruction.invalid())?;
+        Ok(self.stack.split_off(len))
+    }
+
+    fn push(&mut self, expr: Rc<Expr>) {
+        self.stack.push(Slot::new(expr));
+    }
+
+    fn load(&self, register: usize) -> Rc<Expr> {
+        self.frames.last()
+            .and_then(|frame| frame.registers.get(register).cloned())
+            .unwrap_or_else(|| Rc::new(Expr::Unknown))
+    }
+
+    fn store(&mut self, (frame, register): (usize, usize), expr: Rc<Expr>) {
+        if let Some(frame) = self.frames.get_mut(frame) {
+            if frame.registers.len() <= register {
+                frame.registers.resize(register + 1, Rc::new(Expr::Unknown));
+            }
+            frame.registers[register] = expr;
+        }
+    }
+}
+
+// Symbolic execution of a chunk with its arguments as symbols
+// Every path is followed until its end or a limit, both ways of a jump depending on the arguments are taken
+pub struct Executor<'a> {
+    program: &'a Program<'a>,
+    parameters: &'a [Type],
+    limits: Limits,
+}
+
+impl<'a> Executor<'a> {
+    pub fn new(program: &'a Program<'a>, parameters: &'a [Type], limits: Limits) -> Self {
+        Self {
+            program,
+            parameters,
+            limits
+        }
+    }
+
+    pub fn explore(&self, chunk: usize) -> Result<Exploration, AnalyzerError> {
+        // The first parameter is read from the top of the stack
+        let initial = State {
+            stack: (0..self.parameters.len()).rev().map(|i| Slot::new(Rc::new(Expr::Param(i)))).collect(),
+            frames: vec![Frame { chunk, pc: 0, registers: Vec::new(), iterators: Vec::new() }],
+            constraints: Vec::new(),
+            steps: 0
+        };
+
+        let mut exploration = Exploration::default();
+        let mut pending = vec![initial];
+        let mut started = 1;
+        while let Some(mut state) = pending.pop() {
+            loop {
+                if state.steps >= self.limits.max_steps {
+                    exploration.truncated += 1;
+                    break
+                }
+                state.steps += 1;
+
+                let before = pending.len();
+                let flow = self.step(&mut state, &mut pending, &mut exploration.goals)?;
+
+                // The new paths over the limit are dropped
+                let forks = pending.len() - before;
+                let allowed = self.limits.max_paths.saturating_sub(started).min(forks);
+                pending.truncate(before + allowed);
+                started += allowed;
+                exploration.truncated += forks - allowed;
+
+                match flow {
+                    Flow::Continue => {},
+                    Flow::Done => {
+                        exploration.paths += 1;
+                        break
+                    },
+                    Flow::Truncated => {
+                        exploration.truncated += 1;
+                        break
+                    }
+                }
+            }
+        }
+
+        Ok(exploration)
+    }
+
+    fn step(&self, state: &mut State, pending: &mut Vec<State>, goals: &mut Vec<Goal>) -> Result<Flow, AnalyzerError> {
+        let frame = state.frame();
+        let (chunk, offset) = (frame.chunk, frame.pc);
+        let bytes = self.program.module.get_chunk_at(chunk)
+            .ok_or(AnalyzerError::ChunkNotFound(chunk))?
+            .get_instructions();
+
+        // The end of a chunk returns
+        let Some(byte) = bytes.get(offset) else {
+            return Ok(self.return_frame(state))
+        };
+        let invalid = || AnalyzerError::InvalidInstruction { chunk, offset };
+        let opcode = OpCode::from_byte(*byte).ok_or_else(invalid)?;
+        let next = offset + 1 + opcode.arguments_bytes();
+        let args = bytes.get(offset + 1..next).ok_or_else(invalid)?;
+        let instruction = Instruction { chunk, offset, next, args };
+        state.frame().pc = next;
+
+        match opcode {
+            OpCode::Constant => {
+                let expr = match self.program.module.get_constant_at(instruction.u16(0)).ok_or_else(|| instruction.invalid())? {
+                    Constant::Default(value) => Expr::Value(value.clone()),
+                    _ => Expr::Unknown
+                };
+                state.push(Rc::new(expr));
+            },
+            OpCode::ChunkId => state.push(Rc::new(Expr::Value(Value::U16(instruction.u16(0) as u16)))),
+            OpCode::MemoryLoad => {
+                let register = instruction.u16(0);
+                let frame = state.frames.len() - 1;
+                state.stack.push(Slot { expr: state.load(register), register: Some((frame, register)) });
+            },
+            OpCode::MemorySet => {
+                let value = state.pop(&instruction)?;
+                let frame = state.frames.len() - 1;
+                state.store((frame, instruction.u16(0)), value.expr);
+            },
+            OpCode::Pop => {
+                state.pop(&instruction)?;
+            },
+            OpCode::PopN => {
+                state.pop_n(instruction.u8(0) as usize, &instruction)?;
+            },
+            OpCode::Copy => {
+                let value = state.stack.last().cloned().ok_or_else(|| instruction.invalid())?;
+                state.stack.push(value);
+            },
+            // The index is from the bottom of the stack
+            OpCode::CopyN => {
+                let value = state.stack.get(instruction.u8(0) as usize).cloned().ok_or_else(|| instruction.invalid())?;
+                state.stack.push(value);
+            },
+            OpCode::Swap | OpCode::Swap2 => {
+                let len = state.stack.len();
+                let (a, b) = match opcode {
+                    OpCode::Swap => (0, instruction.u8(0) as usize),
+                    _ => (instruction.u8(0) as usize, instruction.u8(1) as usize)
+                };
+                if len <= a || len <= b {
+                    return Err(instruction.invalid())
+                }
+                state.stack.swap(len - 1 - a, len - 1 - b);
+            },
+            OpCode::Jump => state.frame().pc = instruction.u32(0),
+            OpCode::JumpIfFalse => {
+                let condition = state.pop(&instruction)?.expr;
+                self.branch(state, pending, condition, instruction.u32(0));
+            },
+            OpCode::LtJumpIfFalse | OpCode::LteJumpIfFalse | OpCode::GtJumpIfFalse | OpCode::GteJumpIfFalse => {
+                let right = state.pop(&instruction)?.expr;
+                let left = state.pop(&instruction)?.expr;
+                let op = match opcode {
+                    OpCode::LtJumpIfFalse => OpCode::Lt,
+                    OpCode::LteJumpIfFalse => OpCode::Lte,
+                    OpCode::GtJumpIfFalse => OpCode::Gt,
+                    _ => OpCode::Gte
+                };
+                let condition = Expr::binary(op, left, right).unwrap_or_else(|_| Rc::new(Expr::Unknown));
+                self.branch(state, pending, condition, instruction.u32(0));
+            },
+            OpCode::JumpTable => self.jump_table(state, pending, &instruction)?,
+            OpCode::SubLoad
+            | OpCode::IterableLength => {
+                state.pop(&instruction)?;
+                state.stack.push(Slot::unknown());
+            },
+            OpCode::ArrayCall => {
+                state.pop_n(2, &instruction)?;
+                state.stack.push(Slot::unknown());
+            },
+            OpCode::IteratorBegin => {
+                let iterable = state.pop(&instruction)?.expr;
+                let iterator = match iterable.as_ref() {
+                    Expr::Value(Value::Range(start, end, _, inclusive)) => range_values(start, end, *inclusive),
+                    _ => None
+                };
+                state.frame().iterators.push(iterator.map_or(Iter::Unknown, Iter::Values));
+            },
+            OpCode::IteratorNext => {
+                let end = instruction.u32(0);
+                match state.frame().iterators.last_mut().ok_or_else(|| instruction.invalid())? {
+                    Iter::Values(values) => match values.pop_front() {
+                        Some(value) => state.push(value),
+                        None => state.frame().pc = end
+                    },
+                    Iter::Unknown => {
+                        let mut done = state.clone();
+                        done.frame().pc = end;
+                        pending.push(done);
+                        state.stack.push(Slot::unknown());
+                    }
+                }
+            },
+            OpCode::IteratorEnd => {
+                state.frame().iterators.pop().ok_or_else(|| instruction.invalid())?;
+            },
+            OpCode::Return => return Ok(self.return_frame(state)),
+            OpCode::Cast => {
+                let _type = Type::primitive_type_from_byte(instruction.u8(0)).ok_or_else(|| instruction.invalid())?;
+                let value = state.pop(&instruction)?.expr;
+                state.push(Expr::cast(value, _type));
+            },
+            OpCode::Neg => {
+                let value = state.pop(&instruction)?.expr;
+                state.push(Expr::not(value));
+            },
+            OpCode::InvokeChunk => {
+                if state.frames.len() >= self.limits.max_call_depth {
+                    return Ok(Flow::Truncated)
+                }
+
+                // The arguments are read in the reverse order by the callee
+                let count = instruction.u8(3) as usize + (instruction.u8(2) != 0) as usize;
+                let len = state.stack.len().checked_sub(count).ok_or_else(|| instruction.invalid())?;
+                state.stack[len..].reverse();
+                state.frames.push(Frame { chunk: instruction.u16(0), pc: 0, registers: Vec::new(), iterators: Vec::new() });
+            },
+            OpCode::SysCall => return self.syscall(state, goals, &instruction),
+            OpCode::ExternalCall => {
+                state.pop_n(instruction.u8(4) as usize, &instruction)?;
+                state.stack.push(Slot::unknown());
+            },
+            OpCode::CallPrecompile => {
+                state.pop(&instruction)?;
+                state.stack.push(Slot::unknown());
+            },
+            OpCode::NewArray | OpCode::NewMap => {
+                let count = instruction.u8(0) as usize * if opcode == OpCode::NewMap { 2 } else { 1 };
+                state.pop_n(count, &instruction)?;
+                state.stack.push(Slot::unknown());
+            },
+            OpCode::NewStruct => {
+                let fields = self.program.structs.get(instruction.u16(0)).ok_or_else(|| instruction.invalid())?;
+                state.pop_n(*fields, &instruction)?;
+                state.stack.push(Slot::unknown());
+            },
+            OpCode::NewEnum => {
+                let fields = self.program.enums.get(&(instruction.u16(0) as u16))
+                    .and_then(|variants| variants.get(instruction.u8(2) as usize))
+                    .ok_or_else(|| instruction.invalid())?;
+                state.pop_n(*fields, &instruction)?;
+                state.stack.push(Slot::unknown());
+            },
+            OpCode::NewRange | OpCode::NewRangeInclusive => {
+                let end = state.pop(&instruction)?.expr;
+                let start = state.pop(&instruction)?.expr;
+                let range = match (start.as_ref(), end.as_ref()) {
+                    (Expr::Value(start), Expr::Value(end)) => match start.get_type() {
+                        Ok(_type) if start.is_number() => Expr::Value(Value::Range(
+                            Box::new(start.clone()),
+                            Box::new(end.clone()),
+                            _type,
+                            opcode == OpCode::NewRangeInclusive
+                        )),
+                        _ => Expr::Unknown
+                    },
+                    _ => Expr::Unknown
+                };
+                state.push(Rc::new(range));
+            },
+            OpCode::Unpack => {
+                state.pop(&instruction)?;
+                for _ in 0..instruction.u8(0) {
+                    state.stack.push(Slot::unknown());
+                }
+            },
+            OpCode::Assign => {
+                let right = state.pop(&instruction)?;
+                let left = state.pop(&instruction)?;
+                if let Some(register) = left.register {
+                    state.store(register, right.expr);
+                }
+            },
+            OpCode::Inc | OpCode::Dec => {
+                let top = state.stack.last().cloned().ok_or_else(|| instruction.invalid())?;
+                let op = if opcode == OpCode::Inc { OpCode::Add } else { OpCode::Sub };
+                let Some(value) = self.operation(state, goals, &instruction, op, top.expr.clone(), self.one(&top.expr)) else {
+                    return Ok(Flow::Done)
+                };
+                if let Some(register) = top.register {
+                    state.store(register, value.clone());
+                }
+                if let Some(slot) = state.stack.last_mut() {
+                    slot.expr = value;
+                }
+            },
+            OpCode::MemoryAdd => {
+                let (left, right) = (state.load(instruction.u16(0)), state.load(instruction.u16(2)));
+                let Some(value) = self.operation(state, goals, &instruction, OpCode::Add, left, right) else {
+                    return Ok(Flow::Done)
+                };
+                state.push(value);
+            },
+            OpCode::MemoryCast => {
+                let _type = Type::primitive_type_from_byte(instruction.u8(2)).ok_or_else(|| instruction.invalid())?;
+                let value = state.load(instruction.u16(0));
+                state.push(Expr::cast(value, _type));
+            },
+            OpCode::MemoryInc => {
+                let register = instruction.u16(0);
+                let value = state.load(register);
+                let one = self.one(&value);
+                let Some(value) = self.operation(state, goals, &instruction, OpCode::Add, value, one) else {
+                    return Ok(Flow::Done)
+                };
+                let frame = state.frames.len() - 1;
+                state.store((frame, register), value);
+            },
+            _ => {
+                let right = state.pop(&instruction)?;
+                let left = state.pop(&instruction)?;
+                match assigned_operator(opcode) {
+                    Some(op) => {
+                        let Some(value) = self.operation(state, goals, &instruction, op, left.expr, right.expr) else {
+                            return Ok(Flow::Done)
+                        };
+                        if let Some(register) = left.register {
+                            state.store(register, value);
+                        }
+                    },
+                    None => {
+                        let Some(value) = self.operation(state, goals, &instruction, opcode, left.expr, right.expr) else {
+                            return Ok(Flow::Done)
+                        };
+                        state.push(value);
+                    }
+                }
+            }
+        }
+
+        Ok(Flow::Continue)
+    }
+
+    fn return_frame(&self, state: &mut State) -> Flow {
+        state.frames.pop();
+        if state.frames.is_empty() {
+            return Flow::Done
+        }
+
+        // The registers of the callee can't be assigned anymore
+        let frames = state.frames.len();
+        for slot in state.stack.iter_mut() {
+            if slot.register.is_some_and(|(frame, _)| frame >= frames) {
+                slot.register = None;
+            }
+        }
+
+        Flow::Continue
+    }
+
+    // Compute an operation, a failure it may raise is a goal
+    // None if it always fails: the path ends
+    fn operation(&self, state: &State, goals: &mut Vec<Goal>, instruction: &Instruction, op: OpCode, left: Rc<Expr>, right: Rc<Expr>) -> Option<Rc<Expr>> {
+        match Expr::binary(op, left, right) {
+            Ok(value) => {
+                if let Some(kind) = expr::trap_of(op) {
+                    if matches!(value.as_ref(), Expr::Binary(..)) && value.is_solvable() {
+                        goals.push(self.goal(state, instruction, kind, Condition::Traps(value.clone(), kind)));
+                    }
+                }
+                Some(value)
+            },
+            Err(kind) => {
+                goals.push(self.goal(state, instruction, kind, Condition::Always));
+                None
+            }
+        }
+    }
+
+    fn goal(&self, state: &State, instruction: &Instruction, kind: FindingKind, condition: Condition) -> Goal {
+        Goal {
+            chunk: instruction.chunk,
+            offset: instruction.offset,
+            kind,
+            constraints: state.constraints.clone(),
+            condition
+        }
+    }
+
+    // One of the type of a value, to increment it
+    fn one(&self, value: &Expr) -> Rc<Expr> {
+        let one = value.get_type(self.parameters)
+            .as_ref()
+            .and_then(expr::bits_of)
+            .map_or(Expr::Unknown, |bits| Expr::Value(expr::from_number(1u64.into(), bits)));
+        Rc::new(one)
+    }
+
+    // Jump to the target if the condition is false
+    fn branch(&self, state: &mut State, pending: &mut Vec<State>, condition: Rc<Expr>, target: usize) {
+        match condition.as_ref() {
+            Expr::Value(Value::Boolean(true)) => {},
+            Expr::Value(Value::Boolean(false)) => state.frame().pc = target,
+            _ => {
+                let mut jump = state.clone();
+                jump.frame().pc = target;
+                if condition.is_solvable() {
+                    jump.constraints.push((condition.clone(), false));
+                    state.constraints.push((condition, true));
+                }
+                pending.push(jump);
+            }
+        }
+    }
+
+    // The Jump of each case follows the table, ordered from its first value
+    fn jump_table(&self, state: &mut State, pending: &mut Vec<State>, instruction: &Instruction) -> Result<(), AnalyzerError> {
+        let first = match self.program.module.get_constant_at(instruction.u16(4)) {
+            Some(Constant::Default(value)) => value.clone(),
+            _ => return Err(instruction.invalid())
+        };
+        let len = instruction.u16(6);
+        let default = instruction.u32(0);
+        let case = |k: usize| instruction.next + k * (1 + OpCode::Jump.arguments_bytes());
+        let value = state.pop(instruction)?.expr;
+
+        // Value of each case with its target
+        let mut cases = Vec::with_capacity(len);
+        if let Some((first, bits)) = expr::number(&first) {
+            for k in 0..len {
+                match first.checked_add((k as u64).into()) {
+                    Some(n) if n <= expr::max_number(bits) => cases.push((Rc::new(Expr::Value(expr::from_number(n, bits))), case(k))),
+                    _ => break
+                }
+            }
+        }
+
+        match value.as_ref() {
+            Expr::Value(_) => {
+                let target = cases.iter()
+                    .find(|(v, _)| *v == value)
+                    .map_or(default, |(_, target)| *target);
+                state.frame().pc = target;
+            },
+            _ => {
+                let solvable = value.is_solvable();
+                let mut default_state = state.clone();
+                default_state.frame().pc = default;
+                for (case_value, target) in cases {
+                    let condition = Expr::binary(OpCode::Eq, value.clone(), case_value).unwrap_or_else(|_| Rc::new(Expr::Unknown));
+                    let mut branch = state.clone();
+                    branch.frame().pc = target;
+                    if solvable {
+                        branch.constraints.push((condition.clone(), true));
+                        default_state.constraints.push((condition, false));
+                    }
+                    pending.push(branch);
+                }
+                *state = default_state;
+            }
+        }
+
+        Ok(())
+    }
+
+    fn syscall(&self, state: &mut State, goals: &mut Vec<Goal>, instruction: &Instruction) -> Result<Flow, AnalyzerError> {
+        let native = self.program.natives.get(instruction.u16(0)).ok_or_else(|| instruction.invalid())?;
+        let arguments = state.pop_n(instruction.u8(3) as usize, instruction)?;
+        let instance = match instruction.u8(2) != 0 {
+            true => Some(state.pop(instruction)?),
+            false => None
+        };
+
+        let condition = match (native.kind, arguments.as_slice()) {
+            (NativeKind::Panic, _) => {
+                goals.push(self.goal(state, instruction, FindingKind::Panic, Condition::Always));
+                return Ok(Flow::Done)
+            },
+            (NativeKind::Revert, _) => return Ok(Flow::Done),
+            (NativeKind::Assert, [condition, ..]) => Some(condition.expr.clone()),
+            (NativeKind::AssertEq, [left, right]) => Some(Expr::binary(OpCode::Eq, left.expr.clone(), right.expr.clone())
+                .unwrap_or_else(|_| Rc::new(Expr::Unknown))),
+            _ => {
+                // The instance may be updated by the native
+                if let Some((frame, register)) = instance.and_then(|slot| slot.register) {
+                    state.store((frame, register), Rc::new(Expr::Unknown));
+                }
+                None
+            }
+        };
+
+        if let Some(condition) = condition {
+            match condition.as_ref() {
+                Expr::Value(Value::Boolean(false)) => {
+                    goals.push(self.goal(state, instruction, FindingKind::AssertionFailed, Condition::Always));
+                    return Ok(Flow::Done)
+                },
+                _ if condition.is_solvable() => {
+                    goals.push(self.goal(state, instruction, FindingKind::AssertionFailed, Condition::IsFalse(condition.clone())));
+                    state.constraints.push((condition, true));
+                },
+                _ => {}
+            }
+        }
+
+        if native.returns {
+            state.stack.push(Slot::unknown());
+        }
+
+        Ok(Flow::Continue)
+    }
+}
+
+// Operator applied by an assignment opcode
+fn assigned_operator(opcode: OpCode) -> Option<OpCode> {
+    Some(match opcode {
+        OpCode::AssignAdd => OpCode::Add,
+        OpCode::AssignSub => OpCode::Sub,
+        OpCode::AssignMul => OpCode::Mul,
+        OpCode::AssignDiv => OpCode::Div,
+        OpCode::AssignMod => OpCode::Mod,
+        OpCode::AssignPow => OpCode::Pow,
+        OpCode::AssignBitwiseAnd => OpCode::BitwiseAnd,
+        OpCode::AssignBitwiseOr => OpCode::BitwiseOr,
+        OpCode::AssignBitwiseXor => OpCode::BitwiseXor,
+        OpCode::AssignBitwiseShl => OpCode::BitwiseShl,
+        OpCode::AssignBitwiseShr => OpCode::BitwiseShr,
+        _ => return None
+    })
+}
+
+// Values of a known range, None if it is too big
+fn range_values(start: &Value, end: &Value, inclusive: bool) -> Option<VecDeque<Rc<Expr>>> {
+    let ((start, bits), (end, _)) = (expr::number(start)?, expr::number(end)?);
+    let mut count = end.checked_sub(start).unwrap_or_default().as_u64()?;
+    if inclusive && end >= start {
+        count += 1;
+    }
+    if count > MAX_RANGE_VALUES {
+        return None
+    }
+
+    Some((0..count)
+        .map(|i| Rc::new(Expr::Value(expr::from_number(start + i.into(), bits))))
+        .collect())
+}
@@ -0,0 +1,285 @@
+use std::rc::Rc;
+use xelis_bytecode::OpCode;
+use xelis_types::{Type, U256, Value};
+
+use super::FindingKind;
+
+// Why an expression can't be evaluated to a value
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Failure {
+    // The operation stops the execution
+    Trap(FindingKind),
+    // The operation isn't modeled
+    Unknown,
+}
+
+// Value computed by a path, depending on the arguments of the analyzed chunk
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Expr {
+    // Argument of the analyzed chunk by its index
+    Param(usize),
+    Value(Value),
+    // A value not modeled: structs, arrays, results of the natives
+    Unknown,
+    // Logical not of a bool, bitwise not of a number
+    Not(Rc<Expr>),
+    Cast(Rc<Expr>, Type),
+    // Operator with its two operands, by the opcode computing it
+    Binary(OpCode, Rc<Expr>, Rc<Expr>),
+}
+
+impl Expr {
+    // Build an operation, computed directly when its operands are known
+    // Err is returned when the known operands make it trap
+    pub fn binary(op: OpCode, left: Rc<Expr>, right: Rc<Expr>) -> Result<Rc<Expr>, FindingKind> {
+        match (left.as_ref(), right.as_ref()) {
+            (Expr::Unknown, _) | (_, Expr::Unknown) => Ok(Rc::new(Expr::Unknown)),
+            (Expr::Value(a), Expr::Value(b)) => match apply(op, a, b) {
+                Ok(value) => Ok(Rc::new(Expr::Value(value))),
+                Err(Failure::Trap(kind)) => Err(kind),
+                Err(Failure::Unknown) => Ok(Rc::new(Expr::Unknown))
+            },
+            _ => Ok(Rc::new(Expr::Binary(op, left, right)))
+        }
+    }
+
+    pub fn not(value: Rc<Expr>) -> Rc<Expr> {
+        match value.as_ref() {
+            Expr::Unknown => value,
+            Expr::Value(v) => Rc::new(not(v).map_or(Expr::Unknown, Expr::Value)),
+            _ => Rc::new(Expr::Not(value))
+        }
+    }
+
+    pub fn cast(value: Rc<Expr>, _type: Type) -> Rc<Expr> {
+        match value.as_ref() {
+            Expr::Unknown => value,
+            Expr::Value(v) => Rc::new(cast(v, &_type).map_or(Expr::Unknown, Expr::Value)),
+            _ => Rc::new(Expr::Cast(value, _type))
+        }
+    }
+
+    // Does the expression depend on the arguments only
+    // An unknown value can't be solved
+    pub fn is_solvable(&self) -> bool {
+        let mut has_param = false;
+        let mut stack = vec![self];
+        while let Some(expr) = stack.pop() {
+            match expr {
+                Expr::Param(_) => has_param = true,
+                Expr::Value(_) => {},
+                Expr::Unknown => return false,
+                Expr::Not(e) | Expr::Cast(e, _) => stack.push(e),
+                Expr::Binary(_, l, r) => stack.extend([l.as_ref(), r.as_ref()]),
+            }
+        }
+        has_param
+    }
+
+    // Type of the value, known for the numbers and bools
+    pub fn get_type(&self, parameters: &[Type]) -> Option<Type> {
+        match self {
+            Expr::Param(i) => parameters.get(*i).cloned(),
+            Expr::Value(v) => v.get_type().ok(),
+            Expr::Unknown => None,
+            Expr::Not(e) => e.get_type(parameters),
+            Expr::Cast(_, t) => Some(t.clone()),
+            Expr::Binary(op, l, _) => match op {
+                OpCode::Eq | OpCode::Gt | OpCode::Lt | OpCode::Gte | OpCode::Lte
+                | OpCode::And | OpCode::Or => Some(Type::Bool),
+                _ => l.get_type(parameters)
+            }
+        }
+    }
+
+    // Evaluate the expression with the arguments
+    pub fn eval(&self, arguments: &[Value]) -> Result<Value, Failure> {
+        match self {
+            Expr::Param(i) => arguments.get(*i).cloned().ok_or(Failure::Unknown),
+            Expr::Value(v) => Ok(v.clone()),
+            Expr::Unknown => Err(Failure::Unknown),
+            Expr::Not(e) => not(&e.eval(arguments)?).ok_or(Failure::Unknown),
+            Expr::Cast(e, t) => cast(&e.eval(arguments)?, t).ok_or(Failure::Unknown),
+            Expr::Binary(op, l, r) => apply(*op, &l.eval(arguments)?, &r.eval(arguments)?),
+        }
+    }
+
+    // Visit the expression and all its operands
+    pub fn visit<'a>(&'a self, f: &mut impl FnMut(&'a Expr)) {
+        f(self);
+        match self {
+            Expr::Not(e) | Expr::Cast(e, _) => e.visit(f),
+            Expr::Binary(_, l, r) => {
+                l.visit(f);
+                r.visit(f);
+            },
+            _ => {}
+        }
+    }
+}
+
+// Trap raised by an operator on invalid operands
+pub fn trap_of(op: OpCode) -> Option<FindingKind> {
+    match op {
+        OpCode::Add | OpCode::Sub | OpCode::Mul | OpCode::Pow => Some(FindingKind::Overflow),
+        OpCode::Div | OpCode::Mod => Some(FindingKind::DivisionByZero),
+        OpCode::BitwiseShl | OpCode::BitwiseShr => Some(FindingKind::ShiftOverflow),
+        _ => None
+    }
+}
+
+// Number with its count of bits
+pub fn number(value: &Value) -> Option<(U256, u32)> {
+    Some(match value {
+        Value::U8(v) => (U256::from(*v), 8),
+        Value::U16(v) => (U256::from(*v), 16),
+        Value::U32(v) => (U256::from(*v), 32),
+        Value::U64(v) => (U256::from(*v), 64),
+        Value::U128(v) => (U256::from(*v), 128),
+        Value::U256(v) => (*v, 256),
+        _ => return None
+    })
+}
+
+pub fn from_number(value: U256, bits: u32) -> Value {
+    match bits {
+        8 => Value::U8(value.low_u64() as u8),
+        16 => Value::U16(value.low_u64() as u16),
+        32 => Value::U32(value.low_u64() as u32),
+        64 => Value::U64(value.low_u64()),
+        128 => Value::U128(value.low_u128()),
+        _ => Value::U256(value)
+    }
+}
+
+// Biggest number of a count of bits
+pub fn max_number(bits: u32) -> U256 {
+    match bits {
+        256 => U256::MAX,
+        bits => (U256::ONE << bits) - U256::ONE
+    }
+}
+
+pub fn bits_of(_type: &Type) -> Option<u32> {
+    match _type {
+        Type::U8 => Some(8),
+        Type::U16 => Some(16),
+        Type::U32 => Some(32),
+        Type::U64 => Some(64),
+        Type::U128 => Some(128),
+        Type::U256 => Some(256),
+        _ => None
+    }
+}
+
+fn not(value: &Value) -> Option<Value> {
+    match value {
+        Value::Boolean(b) => Some(Value::Boolean(!b)),
+        v => number(v).map(|(n, bits)| from_number(!n & max_number(bits), bits))
+    }
+}
+
+// Casts between numbers truncate like the VM
+fn cast(value: &Value, _type: &Type) -> Option<Value> {
+    let bits = bits_of(_type)?;
+    let n = match value {
+        Value::Boolean(b) => U256::from(*b),
+        v => number(v)?.0
+    };
+    Some(from_number(n & max_number(bits), bits))
+}
+
+// Compute an operator with the semantics of the VM
+fn apply(op: OpCode, left: &Value, right: &Value) -> Result<Value, Failure> {
+    match (op, left, right) {
+        (OpCode::Eq, a, b) => return Ok(Value::Boolean(a == b)),
+        (OpCode::And, Value::Boolean(a), Value::Boolean(b)) => return Ok(Value::Boolean(*a && *b)),
+        (OpCode::Or, Value::Boolean(a), Value::Boolean(b)) => return Ok(Value::Boolean(*a || *b)),
+        (OpCode::Pow, a, Value::U32(exponent)) => {
+            let (a, bits) = number(a).ok_or(Failure::Unknown)?;
+            return a.checked_pow(*exponent)
+                .filter(|v| *v <= max_number(bits))
+                .map(|v| from_number(v, bits))
+                .ok_or(Failure::Trap(FindingKind::Overflow))
+        },
+        _ => {}
+    }
+
+    let ((a, bits), (b, other)) = match (number(left), number(right)) {
+        (Some(a), Some(b)) => (a, b),
+        _ => return Err(Failure::Unknown)
+    };
+    if bits != other {
+        return Err(Failure::Unknown)
+    }
+
+    let max = max_number(bits);
+    let overflow = Failure::Trap(FindingKind::Overflow);
+    let value = match op {
+        OpCode::Gt => return Ok(Value::Boolean(a > b)),
+        OpCode::Lt => return Ok(Value::Boolean(a < b)),
+        OpCode::Gte => return Ok(Value::Boolean(a >= b)),
+        OpCode::Lte => return Ok(Value::Boolean(a <= b)),
+        OpCode::Add => a.checked_add(b).filter(|v| *v <= max).ok_or(overflow)?,
+        OpCode::Sub => a.checked_sub(b).ok_or(overflow)?,
+        OpCode::Mul => a.checked_mul(b).filter(|v| *v <= max).ok_or(overflow)?,
+        OpCode::Div | OpCode::Mod if b.is_zero() => return Err(Failure::Trap(FindingKind::DivisionByZero)),
+        OpCode::Div => a / b,
+        OpCode::Mod => a % b,
+        OpCode::BitwiseAnd => a & b,
+        OpCode::BitwiseOr => a | b,
+        OpCode::BitwiseXor => a ^ b,
+        OpCode::BitwiseShl | OpCode::BitwiseShr => {
+            let shift = b.as_u64()
+                .filter(|shift| *shift < bits as u64)
+                .ok_or(Failure::Trap(FindingKind::ShiftOverflow))? as u32;
+            if op == OpCode::BitwiseShl {
+                (a << shift) & max
+            } else {
+                a >> shift
+            }
+        },
+        OpCode::SaturatingAdd => a.checked_add(b).filter(|v| *v <= max).unwrap_or(max),
+        OpCode::SaturatingSub => a.checked_sub(b).unwrap_or(U256::ZERO),
+        OpCode::SaturatingMul => a.checked_mul(b).filter(|v| *v <= max).unwrap_or(max),
+        OpCode::WrappingAdd => a.wrapping_add(b) & max,
+        OpCode::WrappingSub => a.wrapping_sub(b) & max,
+        OpCode::WrappingMul => a.wrapping_mul(b) & max,
+        _ => return Err(Failure::Unknown)
+    };
+
+    Ok(from_number(value, bits))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn value(op: OpCode, a: Value, b: Value) -> Result<Value, Failure> {
+        Expr::Binary(op, Rc::new(Expr::Value(a)), Rc::new(Expr::Param(0))).eval(&[b])
+    }
+
+    #[test]
+    fn test_eval_traps() {
+        assert_eq!(value(OpCode::Add, Value::U8(200), Value::U8(55)), Ok(Value::U8(255)));
+        assert_eq!(value(OpCode::Add, Value::U8(200), Value::U8(56)), Err(Failure::Trap(FindingKind::Overflow)));
+        assert_eq!(value(OpCode::Sub, Value::U64(1), Value::U64(2)), Err(Failure::Trap(FindingKind::Overflow)));
+        assert_eq!(value(OpCode::Div, Value::U32(1), Value::U32(0)), Err(Failure::Trap(FindingKind::DivisionByZero)));
+        assert_eq!(value(OpCode::BitwiseShl, Value::U16(1), Value::U16(16)), Err(Failure::Trap(FindingKind::ShiftOverflow)));
+        assert_eq!(value(OpCode::BitwiseShl, Value::U8(0x81), Value::U8(1)), Ok(Value::U8(2)));
+        assert_eq!(value(OpCode::WrappingAdd, Value::U8(255), Value::U8(2)), Ok(Value::U8(1)));
+        assert_eq!(value(OpCode::Lt, Value::U8(1), Value::U8(2)), Ok(Value::Boolean(true)));
+        // Operands of different types aren't modeled
+        assert_eq!(value(OpCode::Add, Value::U8(1), Value::U16(2)), Err(Failure::Unknown));
+    }
+
+    #[test]
+    fn test_fold_known_operands() {
+        let two = Rc::new(Expr::Value(Value::U64(2)));
+        assert_eq!(Expr::binary(OpCode::Mul, two.clone(), two.clone()), Ok(Rc::new(Expr::Value(Value::U64(4)))));
+        assert_eq!(Expr::binary(OpCode::Div, two.clone(), Rc::new(Expr::Value(Value::U64(0)))), Err(FindingKind::DivisionByZero));
+        assert_eq!(Expr::binary(OpCode::Add, two.clone(), Rc::new(Expr::Unknown)), Ok(Rc::new(Expr::Unknown)));
+        assert_eq!(Expr::cast(Rc::new(Expr::Value(Value::U64(257))), Type::U8).as_ref(), &Expr::Value(Value::U8(1)));
+        assert!(Expr::binary(OpCode::Add, two, Rc::new(Expr::Param(0))).unwrap().is_solvable());
+    }
+}
@@ -0,0 +1,320 @@
+// Symbolic execution of the chunks to find the arguments making them fail
+// The arguments of a chunk are symbols: each path is followed with the operations computed on them,
+// taking both ways of the jumps depending on them, until its end or the limits.
+// The division by zero, the overflows and the explicit panics and assertions reachable
+// are solved into concrete arguments, confirmed by executing the chunk with them in the VM.
+// The values not modeled (structs, arrays, maps, results of the natives) are unknown:
+// the jumps depending on them take both ways and the failures depending on them can't be solved.
+
+mod executor;
+mod expr;
+mod solver;
+
+#[cfg(test)]
+mod tests;
+
+use core::fmt;
+use thiserror::Error;
+use xelis_builder::EnvironmentBuilder;
+use xelis_bytecode::{Module, SourceSpan};
+use xelis_environment::EnvironmentError;
+use xelis_types::{Type, Value, ValueCell, ValueError};
+use xelis_vm::{ExecutionOutcome, VMError, VM};
+
+use executor::{Executor, Limits, Native, NativeKind, Program};
+use solver::Solver;
+
+#[derive(Debug, Error, PartialEq, Eq)]
+pub enum AnalyzerError {
+    #[error("chunk {0} not found")]
+    ChunkNotFound(usize),
+    #[error("entry {0} not found")]
+    EntryNotFound(String),
+    #[error("parameter {0} of type {1} is not supported, only the numbers and bools can be solved")]
+    UnsupportedParameter(usize, Type),
+    #[error("invalid instruction at {offset} in chunk {chunk}")]
+    InvalidInstruction {
+        chunk: usize,
+        offset: usize
+    },
+}
+
+// Failure raised by the VM
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum FindingKind {
+    DivisionByZero,
+    Overflow,
+    ShiftOverflow,
+    Panic,
+    AssertionFailed,
+}
+
+impl fmt::Display for FindingKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::DivisionByZero => write!(f, "division by zero"),
+            Self::Overflow => write!(f, "arithmetic overflow"),
+            Self::ShiftOverflow => write!(f, "shift overflow"),
+            Self::Panic => write!(f, "panic"),
+            Self::AssertionFailed => write!(f, "assertion failed"),
+        }
+    }
+}
+
+// A failure with the arguments reaching it
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Finding {
+    pub kind: FindingKind,
+    // Chunk and offset of the failing instruction, in a callee of the analyzed chunk maybe
+    pub chunk: usize,
+    pub offset: usize,
+    // Source location, when the module has debug info
+    pub span: Option<SourceSpan>,
+    // Arguments of the analyzed chunk, in the order of its parameters
+    pub arguments: Vec<Value>,
+}
+
+impl fmt::Display for Finding {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} at {} in chunk {}", self.kind, self.offset, self.chunk)?;
+        if let Some(span) = &self.span {
+            write!(f, " ({})", span)?;
+        }
+
+        let arguments = self.arguments.iter()
+            .map(ToString::to_string)
+            .collect::<Vec<_>>();
+        write!(f, " with arguments ({})", arguments.join(", "))
+    }
+}
+
+// Result of the analysis of a chunk
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AnalysisReport {
+    pub chunk: usize,
+    // Failures confirmed by the VM, one per instruction
+    pub findings: Vec<Finding>,
+    // Paths executed until their end
+    pub paths: usize,
+    // Paths stopped by a limit, the analysis is incomplete when some are
+    pub truncated: usize,
+}
+
+impl AnalysisReport {
+    #[inline]
+    pub fn is_complete(&self) -> bool {
+        self.truncated == 0
+    }
+}
+
+impl fmt::Display for AnalysisReport {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "chunk {}: {} paths, {} truncated", self.chunk, self.paths, self.truncated)?;
+        for finding in &self.findings {
+            writeln!(f, "    {}", finding)?;
+        }
+        Ok(())
+    }
+}
+
+// Find the arguments making the chunks of a module fail
+pub struct Analyzer<'a> {
+    module: &'a Module,
+    environment: &'a EnvironmentBuilder<'a>,
+    limits: Limits,
+    // Combinations of arguments tried for each failure
+    solver_budget: usize,
+    // Gas limit of the executions confirming the findings
+    max_gas: u64,
+}
+
+impl<'a> Analyzer<'a> {
+    // The environment provides the natives: `panic`, `assert`, `assert_eq` and `require` are modeled
+    pub fn new(module: &'a Module, environment: &'a EnvironmentBuilder<'a>) -> Self {
+        Self {
+            module,
+            environment,
+            limits: Limits {
+                max_steps: 10_000,
+                max_paths: 1_000,
+                max_call_depth: 16
+            },
+            solver_budget: 10_000,
+            max_gas: 10_000_000
+        }
+    }
+
+    // Max instructions executed by a path, the loops are unrolled up to it
+    pub fn with_max_steps(mut self, max_steps: usize) -> Self {
+        self.limits.max_steps = max_steps;
+        self
+    }
+
+    // Max paths explored by chunk
+    pub fn with_max_paths(mut self, max_paths: usize) -> Self {
+        self.limits.max_paths = max_paths;
+        self
+    }
+
+    // Max depth of the chunks called
+    pub fn with_max_call_depth(mut self, max_call_depth: usize) -> Self {
+        self.limits.max_call_depth = max_call_depth;
+        self
+    }
+
+    // Max combinations of arguments tried to reach a failure
+    pub fn with_solver_budget(mut self, solver_budget: usize) -> Self {
+        self.solver_budget = solver_budget;
+        self
+    }
+
+    // Gas limit of the VM executions confirming the findings
+    pub fn with_max_gas(mut self, max_gas: u64) -> Self {
+        self.max_gas = max_gas;
+        self
+    }
+
+    // Analyze the entries and the exported chunks, using their signature
+    pub fn analyze(&self) -> Result<Vec<AnalysisReport>, AnalyzerError> {
+        let mut ids = self.module.chunks_entry_ids()
+            .iter()
+            .chain(self.module.exports().values())
+            .copied()
+            .collect::<Vec<_>>();
+        ids.sort_unstable();
+        ids.dedup();
+
+        ids.into_iter()
+            .map(|id| {
+                let parameters = self.parameters_of(id);
+                self.analyze_chunk(id, &parameters)
+            })
+            .collect()
+    }
+
+    // Analyze an entry by its name
+    pub fn analyze_entry(&self, name: &str) -> Result<AnalysisReport, AnalyzerError> {
+        let id = self.module.entry_by_name(name)
+            .ok_or_else(|| AnalyzerError::EntryNotFound(name.to_owned()))?;
+        self.analyze_chunk(id, &self.parameters_of(id))
+    }
+
+    // Analyze a chunk with the types of its parameters
+    pub fn analyze_chunk(&self, id: usize, parameters: &[Type]) -> Result<AnalysisReport, AnalyzerError> {
+        if id >= self.module.chunks().len() {
+            return Err(AnalyzerError::ChunkNotFound(id))
+        }
+
+        if let Some((index, _type)) = parameters.iter().enumerate().find(|(_, t)| !t.is_primitive() || *t == &Type::String) {
+            return Err(AnalyzerError::UnsupportedParameter(index, _type.clone()))
+        }
+
+        let program = self.program();
+        let exploration = Executor::new(&program, parameters, self.limits).explore(id)?;
+
+        let solver = Solver::new(parameters, self.solver_budget);
+        let mut findings: Vec<Finding> = Vec::new();
+        for goal in exploration.goals {
+            if findings.iter().any(|f| f.chunk == goal.chunk && f.offset == goal.offset) {
+                continue
+            }
+
+            let arguments = solver.solve(&goal.constraints, &goal.condition, |arguments| self.confirm(id, arguments, goal.kind));
+            if let Some(arguments) = arguments {
+                findings.push(Finding {
+                    kind: goal.kind,
+                    chunk: goal.chunk,
+                    offset: goal.offset,
+                    span: self.module.debug_info().and_then(|info| info.get_span(goal.chunk, goal.offset)).copied(),
+                    arguments
+                });
+            }
+        }
+
+        Ok(AnalysisReport {
+            chunk: id,
+            findings,
+            paths: exploration.paths,
+            truncated: exploration.truncated
+        })
+    }
+
+    fn parameters_of(&self, id: usize) -> Vec<Type> {
+        self.module.get_signature(id)
+            .map(|signature| signature.parameters().iter().map(|(_, t)| t.clone()).collect())
+            .unwrap_or_default()
+    }
+
+    fn program(&self) -> Program<'a> {
+        let mapper = self.environment.get_functions_mapper();
+        let names = mapper.get_functions();
+        let natives = self.environment.get_functions()
+            .iter()
+            .enumerate()
+            .map(|(id, function)| {
+                let name = names.iter()
+                    .find(|(function_id, signature, _)| *function_id as usize == id && signature.get_on_type().is_none())
+                    .map(|(_, signature, _)| signature.get_name().as_str());
+                let kind = match name {
+                    Some("panic") => NativeKind::Panic,
+                    Some("assert" | "require") => NativeKind::Assert,
+                    Some("assert_eq") => NativeKind::AssertEq,
+                    Some("revert") => NativeKind::Revert,
+                    _ => NativeKind::Other
+                };
+                Native { kind, returns: function.return_type().is_some() }
+            })
+            .collect();
+
+        let environment = self.environment.environment();
+        let structs = environment.get_structures()
+            .iter()
+            .chain(self.module.structs())
+            .map(|s| s.fields().len())
+            .collect();
+        let enums = environment.get_enums()
+            .iter()
+            .chain(self.module.enums())
+            .map(|e| (e.id(), e.variants().iter().map(|v| v.fields().len()).collect()))
+            .collect();
+
+        Program {
+            module: self.module,
+            natives,
+            structs,
+            enums
+        }
+    }
+
+    // Execute the chunk with the arguments, it must fail as expected
+    fn confirm(&self, id: usize, arguments: &[Value], kind: FindingKind) -> bool {
+        let mut vm = VM::new(self.module, self.environment.environment());
+        vm.context_mut().set_gas_limit(self.max_gas);
+
+        // The first argument is read from the top of the stack
+        let arguments = arguments.iter().rev().cloned().map(ValueCell::from).collect::<Vec<_>>();
+        if vm.invoke_chunk_with_args(id as u16, arguments.into_iter()).is_err() {
+            return false
+        }
+
+        // A failing `require` reverts the execution
+        let error = match vm.run() {
+            Ok(ExecutionOutcome::Reverted(_)) => return matches!(kind, FindingKind::AssertionFailed),
+            Ok(ExecutionOutcome::Success(_)) => return false,
+            Err(error) => error
+        };
+        matches!(
+            (error.inner(), kind),
+            (VMError::DivisionByZero, FindingKind::DivisionByZero)
+            | (VMError::ArithmeticOverflow, FindingKind::Overflow)
+            | (VMError::ValueError(ValueError::Overflow), FindingKind::Overflow)
+            | (VMError::ShiftOverflow, FindingKind::ShiftOverflow)
+            | (VMError::EnvironmentError(EnvironmentError::Panic(_)), FindingKind::Panic)
+            | (VMError::EnvironmentError(
+                EnvironmentError::AssertionFailed
+                | EnvironmentError::AssertionFailedWith(_)
+                | EnvironmentError::AssertionNotEqual(..)
+            ), FindingKind::AssertionFailed)
+        )
+    }
+}
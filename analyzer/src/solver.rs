@@ -0,0 +1,210 @@
+use std::rc::Rc;
+use xelis_bytecode::OpCode;
+use xelis_types::{Type, U256, Value};
+
+use super::{
+    expr::{self, Expr, Failure},
+    FindingKind
+};
+
+// What the arguments must trigger at the end of the path
+#[derive(Debug, Clone)]
+pub enum Condition {
+    // The path itself fails, like a panic
+    Always,
+    // The top operation of the expression traps
+    Traps(Rc<Expr>, FindingKind),
+    // The bool is false, like an assertion
+    IsFalse(Rc<Expr>),
+}
+
+// Search of arguments following a path
+// Each argument is picked among values likely to flip the comparisons of the path:
+// the limits of its type, the constants of the path and their neighbours,
+// and the values solving the comparisons of a single argument, like `a * 2 == 200`
+pub struct Solver<'a> {
+    parameters: &'a [Type],
+    // Max count of combinations tried
+    budget: usize,
+}
+
+impl<'a> Solver<'a> {
+    pub fn new(parameters: &'a [Type], budget: usize) -> Self {
+        Self {
+            parameters,
+            budget
+        }
+    }
+
+    // Find arguments following the path and meeting the condition, accepted by the callback
+    pub fn solve(&self, constraints: &[(Rc<Expr>, bool)], condition: &Condition, mut accept: impl FnMut(&[Value]) -> bool) -> Option<Vec<Value>> {
+        let candidates = self.candidates(constraints, condition);
+        if candidates.iter().any(Vec::is_empty) {
+            return None
+        }
+
+        let mut indexes = vec![0; candidates.len()];
+        for _ in 0..self.budget {
+            let arguments = indexes.iter()
+                .zip(&candidates)
+                .map(|(i, values)| values[*i].clone())
+                .collect::<Vec<_>>();
+
+            if self.matches(&arguments, constraints, condition) && accept(&arguments) {
+                return Some(arguments)
+            }
+
+            // Next combination, the first argument changing the fastest
+            let mut position = 0;
+            loop {
+                let index = indexes.get_mut(position)?;
+                *index += 1;
+                if *index < candidates[position].len() {
+                    break
+                }
+                *index = 0;
+                position += 1;
+            }
+        }
+
+        None
+    }
+
+    fn matches(&self, arguments: &[Value], constraints: &[(Rc<Expr>, bool)], condition: &Condition) -> bool {
+        let follows = constraints.iter()
+            .all(|(expr, expected)| expr.eval(arguments) == Ok(Value::Boolean(*expected)));
+        if !follows {
+            return false
+        }
+
+        match condition {
+            Condition::Always => true,
+            Condition::IsFalse(expr) => expr.eval(arguments) == Ok(Value::Boolean(false)),
+            // The operands must be computed, only the operation traps
+            Condition::Traps(expr, kind) => match expr.as_ref() {
+                Expr::Binary(_, l, r) => l.eval(arguments).is_ok()
+                    && r.eval(arguments).is_ok()
+                    && expr.eval(arguments) == Err(Failure::Trap(*kind)),
+                _ => false
+            }
+        }
+    }
+
+    // Values tried for each argument, sorted
+    fn candidates(&self, constraints: &[(Rc<Expr>, bool)], condition: &Condition) -> Vec<Vec<Value>> {
+        let mut numbers: Vec<Vec<U256>> = vec![vec![U256::ZERO, U256::ONE, U256::from(2u64)]; self.parameters.len()];
+        let mut constants = Vec::new();
+
+        let exprs = constraints.iter()
+            .map(|(expr, _)| expr)
+            .chain(match condition {
+                Condition::Always => None,
+                Condition::Traps(expr, _) | Condition::IsFalse(expr) => Some(expr)
+            });
+        for expr in exprs {
+            expr.visit(&mut |e| match e {
+                Expr::Value(v) => constants.extend(expr::number(v).map(|(n, _)| n)),
+                Expr::Binary(OpCode::Eq | OpCode::Lt | OpCode::Gt | OpCode::Lte | OpCode::Gte, l, r) => {
+                    // Solve the side with arguments for the value of the known side
+                    for (side, other) in [(l, r), (r, l)] {
+                        if let Some((target, _)) = other.eval(&[]).ok().and_then(|v| expr::number(&v)) {
+                            invert(side, target, &mut numbers);
+                        }
+                    }
+                },
+                _ => {}
+            });
+        }
+
+        // Operands making the operation trap
+        if let Condition::Traps(expr, _) = condition {
+            if let Expr::Binary(op, l, r) = expr.as_ref() {
+                let known = |e: &Expr| e.eval(&[]).ok().and_then(|v| expr::number(&v));
+                match (op, known(l), known(r)) {
+                    (OpCode::Div | OpCode::Mod, _, _) => invert(r, U256::ZERO, &mut numbers),
+                    (OpCode::BitwiseShl | OpCode::BitwiseShr, _, Some((_, bits))) => invert(r, U256::from(bits as u64), &mut numbers),
+                    (OpCode::Add, _, Some((k, bits))) => invert(l, (expr::max_number(bits) - k).saturating_add(U256::ONE), &mut numbers),
+                    (OpCode::Add, Some((k, bits)), _) => invert(r, (expr::max_number(bits) - k).saturating_add(U256::ONE), &mut numbers),
+                    (OpCode::Mul, _, Some((k, bits))) if !k.is_zero() => invert(l, (expr::max_number(bits) / k).saturating_add(U256::ONE), &mut numbers),
+                    (OpCode::Mul, Some((k, bits)), _) if !k.is_zero() => invert(r, (expr::max_number(bits) / k).saturating_add(U256::ONE), &mut numbers),
+                    (OpCode::Sub, Some((k, _)), _) => invert(r, k.saturating_add(U256::ONE), &mut numbers),
+                    (OpCode::Sub, _, Some((k, _))) if !k.is_zero() => invert(l, k - U256::ONE, &mut numbers),
+                    _ => {}
+                }
+            }
+        }
+
+        self.parameters.iter()
+            .zip(numbers)
+            .map(|(_type, mut values)| {
+                if *_type == Type::Bool {
+                    return vec![Value::Boolean(false), Value::Boolean(true)]
+                }
+
+                let Some(bits) = expr::bits_of(_type) else {
+                    return Vec::new()
+                };
+                let max = expr::max_number(bits);
+                values.extend([max, max - U256::ONE, U256::ONE << (bits / 2)]);
+                for constant in &constants {
+                    values.extend([constant.saturating_sub(U256::ONE), *constant, constant.saturating_add(U256::ONE)]);
+                }
+
+                values.retain(|v| *v <= max);
+                values.sort_by(|a, b| a.partial_cmp(b).unwrap_or(core::cmp::Ordering::Equal));
+                values.dedup();
+                values.into_iter()
+                    .map(|v| expr::from_number(v, bits))
+                    .collect()
+            })
+            .collect()
+    }
+}
+
+// Values of the argument for which the expression gives the target and its neighbours
+// Only the operations with a known operand are inverted
+fn invert(expr: &Expr, target: U256, numbers: &mut [Vec<U256>]) {
+    match expr {
+        Expr::Param(i) => {
+            if let Some(values) = numbers.get_mut(*i) {
+                values.extend([target.saturating_sub(U256::ONE), target, target.saturating_add(U256::ONE)]);
+            }
+        },
+        Expr::Cast(e, _) => invert(e, target, numbers),
+        Expr::Binary(op, l, r) => {
+            let known = |e: &Expr| e.eval(&[]).ok().and_then(|v| expr::number(&v)).map(|(n, _)| n);
+            match (op, known(l), known(r)) {
+                (OpCode::Add, _, Some(k)) => invert(l, target.saturating_sub(k), numbers),
+                (OpCode::Add, Some(k), _) => invert(r, target.saturating_sub(k), numbers),
+                (OpCode::Sub, _, Some(k)) => invert(l, target.saturating_add(k), numbers),
+                (OpCode::Sub, Some(k), _) => invert(r, k.saturating_sub(target), numbers),
+                (OpCode::Mul, _, Some(k)) if !k.is_zero() => invert(l, target / k, numbers),
+                (OpCode::Mul, Some(k), _) if !k.is_zero() => invert(r, target / k, numbers),
+                (OpCode::Div, _, Some(k)) => invert(l, target.saturating_mul(k), numbers),
+                _ => {}
+            }
+        },
+        _ => {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_solve_inverted_comparison() {
+        // a * 3 + 1 == 100 with a: u8
+        let a = Rc::new(Expr::Param(0));
+        let product = Expr::binary(OpCode::Mul, a, Rc::new(Expr::Value(Value::U8(3)))).unwrap();
+        let sum = Expr::binary(OpCode::Add, product, Rc::new(Expr::Value(Value::U8(1)))).unwrap();
+        let eq = Expr::binary(OpCode::Eq, sum.clone(), Rc::new(Expr::Value(Value::U8(100)))).unwrap();
+
+        let solver = Solver::new(&[Type::U8], 1000);
+        assert_eq!(solver.solve(&[(eq.clone(), true)], &Condition::Always, |_| true), Some(vec![Value::U8(33)]));
+        // The operand a * 3 must not overflow first
+        assert_eq!(solver.solve(&[], &Condition::Traps(sum, FindingKind::Overflow), |_| true), Some(vec![Value::U8(85)]));
+        // Rejected by the callback
+        assert_eq!(solver.solve(&[(eq, true)], &Condition::Always, |_| false), None);
+    }
+}
@@ -0,0 +1,126 @@
+use xelis_builder::EnvironmentBuilder;
+use xelis_compiler::{Compiler, OptimizationLevel};
+use xelis_lexer::Lexer;
+use xelis_parser::Parser;
+use super::*;
+
+fn compile(code: &str, environment: &EnvironmentBuilder, level: OptimizationLevel) -> Module {
+    let tokens: Vec<_> = Lexer::new(code).collect::<Result<_, _>>().unwrap();
+    let (program, _) = Parser::with(tokens.into_iter(), environment).parse().unwrap();
+
+    let mut compiler = Compiler::new(&program, environment.environment())
+        .with_optimizations(level);
+    compiler.set_debug_info(true);
+    compiler.compile().unwrap()
+}
+
+const CODE: &str = r#"
+    fn ratio(a: u64, b: u64) -> u64 {
+        if (a > 10) && (b < 100) {
+            return a / (b - 5)
+        }
+        return a
+    }
+
+    entry check(x: u8) {
+        assert(x != 42);
+        let y: u8 = x * 2;
+        if y == 200 {
+            panic("bad")
+        }
+        return 0
+    }
+
+    pub fn divide(a: u64, b: u64) -> u64 {
+        let total: u64 = 0;
+        for i: u64 = 0; i < 3; i += 1 {
+            total += a
+        }
+        return ratio(total, b)
+    }
+
+    entry safe(x: u64) {
+        if x > 1000 {
+            return 0
+        }
+        return x * 2
+    }
+"#;
+
+fn finding(report: &AnalysisReport, kind: FindingKind) -> &Finding {
+    report.findings.iter()
+        .find(|f| f.kind == kind)
+        .unwrap_or_else(|| panic!("no {} in {}", kind, report))
+}
+
+#[test]
+fn test_analyze_entry() {
+    for level in [OptimizationLevel::None, OptimizationLevel::Full] {
+        let environment = EnvironmentBuilder::default();
+        let module = compile(CODE, &environment, level);
+        let analyzer = Analyzer::new(&module, &environment);
+
+        let report = analyzer.analyze_entry("check").unwrap();
+        assert!(report.is_complete());
+        assert_eq!(finding(&report, FindingKind::AssertionFailed).arguments, vec![Value::U8(42)]);
+        assert_eq!(finding(&report, FindingKind::Overflow).arguments, vec![Value::U8(128)]);
+        let panic = finding(&report, FindingKind::Panic);
+        assert_eq!(panic.arguments, vec![Value::U8(100)]);
+        assert_eq!(panic.span.map(|s| s.line), Some(13));
+
+        // Nothing fails with the bound checked
+        let report = analyzer.analyze_entry("safe").unwrap();
+        assert!(report.findings.is_empty());
+        assert_eq!(report.paths, 2);
+    }
+}
+
+#[test]
+fn test_analyze_calls_and_loops() {
+    let environment = EnvironmentBuilder::default();
+    let module = compile(CODE, &environment, OptimizationLevel::None);
+    let analyzer = Analyzer::new(&module, &environment);
+
+    let reports = analyzer.analyze().unwrap();
+    assert_eq!(reports.len(), 3);
+
+    // The division is in the callee, reached after the loop
+    let divide = module.get_export("divide").unwrap();
+    let report = reports.iter().find(|r| r.chunk == divide).unwrap();
+    let division = finding(report, FindingKind::DivisionByZero);
+    assert_eq!(division.chunk, 0);
+    assert_eq!(division.arguments[1], Value::U64(5));
+    assert!(division.to_string().starts_with("division by zero at"));
+
+    // total = a * 3 overflows before the call
+    let overflow = finding(report, FindingKind::Overflow);
+    assert_eq!(overflow.chunk, divide);
+}
+
+#[test]
+fn test_analyze_errors() {
+    let environment = EnvironmentBuilder::default();
+    let module = compile(CODE, &environment, OptimizationLevel::None);
+    let analyzer = Analyzer::new(&module, &environment);
+
+    assert_eq!(analyzer.analyze_entry("unknown"), Err(AnalyzerError::EntryNotFound("unknown".to_owned())));
+    assert_eq!(analyzer.analyze_chunk(10, &[]), Err(AnalyzerError::ChunkNotFound(10)));
+    assert_eq!(analyzer.analyze_chunk(0, &[Type::String]), Err(AnalyzerError::UnsupportedParameter(0, Type::String)));
+
+    // The loop depending on the argument is unrolled up to the limit
+    let module = compile(r#"
+        entry spin(n: u64) {
+            let i: u64 = 0;
+            while i < n {
+                i += 1
+            }
+            return 10 / (i - 3)
+        }
+    "#, &environment, OptimizationLevel::None);
+    let report = Analyzer::new(&module, &environment)
+        .with_max_paths(8)
+        .analyze_entry("spin")
+        .unwrap();
+    assert!(!report.is_complete());
+    assert_eq!(finding(&report, FindingKind::DivisionByZero).arguments, vec![Value::U64(3)]);
+}
@@ -0,0 +1,40 @@
+// Generate the sources of the test programs of xelis-aot with the transpiler
+// They are compiled with the tests and compared to the interpreter
+
+use std::{env, fs, path::Path};
+use xelis_aot::Transpiler;
+use xelis_builder::EnvironmentBuilder;
+use xelis_compiler::{Compiler, OptimizationLevel};
+use xelis_lexer::Lexer;
+use xelis_parser::Parser;
+
+// Programs of the `programs` directory of xelis-aot
+const PROGRAMS: [&str; 3] = ["points", "features", "overflow"];
+
+fn main() {
+    println!("cargo:rerun-if-changed=../programs");
+
+    let out_dir = env::var("OUT_DIR").expect("OUT_DIR is set by cargo");
+    for name in PROGRAMS {
+        let code = fs::read_to_string(format!("../programs/{name}.xel")).expect("program exists");
+        let tokens: Vec<_> = Lexer::new(&code).collect::<Result<_, _>>().expect("valid tokens");
+        let env = EnvironmentBuilder::default();
+        let (program, _) = Parser::with(tokens.into_iter(), &env).parse().expect("valid program");
+        let environment = env.build();
+
+        // The optimized version uses the fused instructions
+        for (suffix, level) in [("", OptimizationLevel::None), ("_optimized", OptimizationLevel::Full)] {
+            let module = Compiler::new(&program, &environment)
+                .with_optimizations(level)
+                .compile()
+                .expect("program compiles");
+
+            let source = Transpiler::new(&module, &environment)
+                .transpile()
+                .expect("module is transpiled");
+
+            fs::write(Path::new(&out_dir).join(format!("{name}{suffix}.rs")), source)
+                .expect("source is written");
+        }
+    }
+}
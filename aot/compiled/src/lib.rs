@@ -0,0 +1,6 @@
+// Tests of the chunks compiled ahead of time by xelis-aot
+// The build script transpiles the programs of xelis-aot,
+// their sources are compiled with the tests and executed against the interpreter
+
+#[cfg(test)]
+mod tests;
@@ -0,0 +1,183 @@
+use xelis_builder::EnvironmentBuilder;
+use xelis_bytecode::Module;
+use xelis_compiler::{Compiler, OptimizationLevel};
+use xelis_lexer::Lexer;
+use xelis_parser::Parser;
+use xelis_types::Value;
+use xelis_vm::{Environment, RunResult, Tracer, VMConfig, VMError, VM};
+
+// Sources generated from the programs by the build script
+mod points {
+    include!(concat!(env!("OUT_DIR"), "/points.rs"));
+}
+
+mod points_optimized {
+    include!(concat!(env!("OUT_DIR"), "/points_optimized.rs"));
+}
+
+mod features {
+    include!(concat!(env!("OUT_DIR"), "/features.rs"));
+}
+
+mod features_optimized {
+    include!(concat!(env!("OUT_DIR"), "/features_optimized.rs"));
+}
+
+mod overflow {
+    include!(concat!(env!("OUT_DIR"), "/overflow.rs"));
+}
+
+const POINTS: &str = include_str!("../../programs/points.xel");
+const FEATURES: &str = include_str!("../../programs/features.xel");
+const OVERFLOW: &str = include_str!("../../programs/overflow.xel");
+
+// Register function of a generated source
+type Register = for<'a> fn(&mut VM<'a>, &'a Module) -> bool;
+
+fn prepare_module(code: &str, level: OptimizationLevel) -> (Module, Environment) {
+    let tokens: Vec<_> = Lexer::new(code).collect::<Result<_, _>>().unwrap();
+    let env = EnvironmentBuilder::default();
+    let (program, _) = Parser::with(tokens.into_iter(), &env).parse().unwrap();
+
+    let env = env.build();
+    let module = Compiler::new(&program, &env)
+        .with_optimizations(level)
+        .compile()
+        .unwrap();
+
+    (module, env)
+}
+
+// Run the entry with a memory limit, compiled if a register function is given
+// Returns the value and the gas used
+fn run(module: &Module, environment: &Environment, register: Option<Register>, max_memory: usize) -> Result<(Value, u64), String> {
+    let config = VMConfig {
+        max_memory,
+        ..Default::default()
+    };
+    let mut vm = VM::with_config(module, environment, config);
+    if let Some(register) = register {
+        assert!(register(&mut vm, module));
+    }
+
+    vm.invoke_entry_chunk(module.chunks().len() as u16 - 1).unwrap();
+    let value = vm.run()
+        .map_err(|e| e.inner().to_string())?
+        .into_result()
+        .unwrap()
+        .into_value()
+        .unwrap();
+
+    Ok((value, vm.context().current_gas_usage()))
+}
+
+// The compiled chunks return the same value, use the same gas
+// and reach the same memory peak as the interpreter
+fn assert_same_execution(code: &str, level: OptimizationLevel, register: Register) -> Value {
+    let (module, environment) = prepare_module(code, level);
+    let expected = run(&module, &environment, None, usize::MAX).unwrap();
+    assert_eq!(run(&module, &environment, Some(register), usize::MAX).unwrap(), expected);
+
+    // Lowest memory limit allowing the interpreter to finish
+    let (mut low, mut high) = (0, 1 << 20);
+    while low < high {
+        let middle = (low + high) / 2;
+        if run(&module, &environment, None, middle).is_ok() {
+            high = middle;
+        } else {
+            low = middle + 1;
+        }
+    }
+
+    assert_eq!(run(&module, &environment, Some(register), low).unwrap(), expected);
+    assert_eq!(run(&module, &environment, Some(register), low - 1), Err(VMError::OutOfMemory.to_string()));
+
+    expected.0
+}
+
+#[test]
+fn test_compiled_chunks() {
+    assert_eq!(assert_same_execution(POINTS, OptimizationLevel::None, points::register), Value::U64(3715));
+    assert_eq!(assert_same_execution(POINTS, OptimizationLevel::Full, points_optimized::register), Value::U64(3715));
+}
+
+#[test]
+fn test_compiled_features() {
+    let value = assert_same_execution(FEATURES, OptimizationLevel::None, features::register);
+    assert_eq!(assert_same_execution(FEATURES, OptimizationLevel::Full, features_optimized::register), value);
+}
+
+#[test]
+fn test_compiled_errors() {
+    // The failing instruction is reported like in the interpreter
+    let (module, environment) = prepare_module(OVERFLOW, OptimizationLevel::None);
+    let error = |register: Option<Register>| {
+        let mut vm = VM::new(&module, &environment);
+        if let Some(register) = register {
+            assert!(register(&mut vm, &module));
+        }
+
+        vm.invoke_entry_chunk(1).unwrap();
+        vm.run().unwrap_err()
+    };
+
+    let expected = error(None);
+    assert_eq!(expected.inner().to_string(), VMError::ArithmeticOverflow.to_string());
+
+    let error = error(Some(overflow::register));
+    assert_eq!(error.to_string(), expected.to_string());
+    assert_eq!(error.backtrace(), expected.backtrace());
+}
+
+#[test]
+fn test_register_other_module() {
+    let (module, environment) = prepare_module(POINTS, OptimizationLevel::None);
+    let other = Module::new();
+    let mut vm = VM::new(&other, &environment);
+    assert!(!points::register(&mut vm, &other));
+
+    let mut vm = VM::new(&module, &environment);
+    assert!(points::register(&mut vm, &module));
+
+    // The optimized chunks are compiled from another module
+    let mut vm = VM::new(&module, &environment);
+    assert!(!points_optimized::register(&mut vm, &module));
+}
+
+#[test]
+fn test_resume_paused_chunk() {
+    let (module, environment) = prepare_module(POINTS, OptimizationLevel::None);
+    let mut vm = VM::new(&module, &environment);
+    assert!(points::register(&mut vm, &module));
+    vm.invoke_entry_chunk(1).unwrap();
+
+    // Paused in the middle of a block, the chunk is resumed by the interpreter
+    assert!(matches!(vm.run_for(7).unwrap(), RunResult::Paused));
+    let value = vm.resume().unwrap().into_result().unwrap().into_value().unwrap();
+    assert_eq!(value, Value::U64(3715));
+}
+
+#[derive(Default)]
+struct InstructionsCounter(usize);
+
+impl Tracer for InstructionsCounter {
+    fn on_instruction(&mut self, _: u16, _: usize, _: u8, _: u64) {
+        self.0 += 1;
+    }
+}
+
+#[test]
+fn test_interpreted_with_tracer() {
+    let (module, environment) = prepare_module(POINTS, OptimizationLevel::None);
+    let mut counter = InstructionsCounter::default();
+    let mut vm = VM::new(&module, &environment);
+    assert!(points::register(&mut vm, &module));
+    vm.context_mut().set_tracer(&mut counter);
+    vm.invoke_entry_chunk(1).unwrap();
+    let value = vm.run().unwrap().into_result().unwrap().into_value().unwrap();
+    assert_eq!(value, Value::U64(3715));
+    drop(vm);
+
+    // Every instruction was interpreted to be traced
+    assert!(counter.0 > 0);
+}
@@ -0,0 +1,745 @@
+// Ahead of time compilation of the modules
+// A validated module is translated into Rust source with a function per chunk.
+// Each chunk is split in blocks at the targets of its jumps and after its calls,
+// a block executes its instructions as Rust statements on the stack, the registers and the context
+// with their immediates inlined, and the natives are called through the environment.
+// Every instruction charges its cost from the table of the VM and the memory like the interpreter.
+// The host compiles the generated source with its binary and registers the chunks in the VM,
+// the bytecode stays the canonical form and is verified before registering them.
+
+#[cfg(test)]
+mod tests;
+
+use core::fmt::{self, Display, Write};
+use std::collections::BTreeSet;
+use thiserror::Error;
+use xelis_bytecode::{Module, OpCode, SerializerError};
+use xelis_vm::{ChunkReader, Environment, ModuleValidator, ValidatorError, VMError};
+use xelis_types::Type;
+
+// Items shared by the chunks, written at the top of the generated source
+const PRELUDE: &str = include_str!("prelude.rs");
+
+// Indentation of the instructions of a block
+const INDENT: &str = "                ";
+
+// Indentation of the statements of an instruction
+// Each instruction has its own scope so its values are dropped like in the interpreter
+const STATEMENT_INDENT: &str = "                    ";
+
+// Size of a Jump instruction, the cases of a JumpTable are Jumps following it
+const JUMP_SIZE: usize = 1 + 4;
+
+#[derive(Debug, Error)]
+pub enum AotError<'a> {
+    // The wrapped errors are boxed to not grow the size of the error
+    #[error("invalid module: {0}")]
+    Validator(Box<ValidatorError<'a>>),
+    #[error(transparent)]
+    VM(Box<VMError>),
+    #[error(transparent)]
+    Serializer(#[from] SerializerError),
+    #[error(transparent)]
+    Fmt(#[from] fmt::Error),
+}
+
+impl From<VMError> for AotError<'_> {
+    fn from(error: VMError) -> Self {
+        Self::VM(Box::new(error))
+    }
+}
+
+// How the execution continues after an instruction
+enum Flow {
+    // With the next instruction of the block
+    Next,
+    // The block is ended by the instruction
+    End,
+}
+
+// Translate a module into Rust source
+pub struct Transpiler<'a> {
+    module: &'a Module,
+    environment: &'a Environment,
+}
+
+impl<'a> Transpiler<'a> {
+    // Create a new transpiler for the module
+    pub fn new(module: &'a Module, environment: &'a Environment) -> Self {
+        Self {
+            module,
+            environment
+        }
+    }
+
+    // Generate the Rust source of the module
+    // It exposes a `register(vm, module)` function setting the compiled chunks in the VM,
+    // which returns false if the module isn't the one the source was generated from
+    // The crate compiling it must depend on `xelis-vm` and `xelis-types`
+    pub fn transpile(&self) -> Result<String, AotError<'a>> {
+        // Only a valid module can be translated: every jump lands on an instruction
+        ModuleValidator::new(self.module, self.environment)
+            .verify()
+            .map_err(|e| AotError::Validator(Box::new(e)))?;
+
+        let mut output = String::new();
+        writeln!(output, "// Generated by xelis-aot, do not edit")?;
+        writeln!(output)?;
+        writeln!(output, "#[allow(unused_imports)]")?;
+        writeln!(output, "use xelis_types::{{EnumValueType, IndexMap, Path, Value, ValueCell, ValueError}};")?;
+        writeln!(output, "#[allow(unused_imports)]")?;
+        writeln!(output, "use xelis_vm::{{Backend, ChunkManager, Context, EnvironmentError, InstructionResult, IteratorStep, Module, OpCode, Stack, VM, VMError, VALUE_MEMORY_USAGE}};")?;
+        writeln!(output)?;
+        // Not every chunk uses all of its items
+        writeln!(output, "#[macro_use]")?;
+        writeln!(output, "mod prelude {{")?;
+        writeln!(output, "#![allow(unused_macros, dead_code, clippy::all)]")?;
+        writeln!(output)?;
+        writeln!(output, "use super::*;")?;
+        writeln!(output)?;
+        output.push_str(PRELUDE);
+        writeln!(output, "}}")?;
+        writeln!(output)?;
+        writeln!(output, "#[allow(unused_imports)]")?;
+        writeln!(output, "use prelude::*;")?;
+        writeln!(output)?;
+
+        writeln!(output, "// Serialized module the chunks were compiled from")?;
+        writeln!(output, "const MODULE: &[u8] = &{:?};", self.module.to_bytes()?)?;
+        writeln!(output)?;
+
+        writeln!(output, "// Set the compiled chunks of the module in the VM")?;
+        writeln!(output, "// Returns false if the module isn't the one they were compiled from")?;
+        writeln!(output, "pub fn register<'a>(vm: &mut VM<'a>, module: &'a Module) -> bool {{")?;
+        writeln!(output, "    if module.to_bytes().ok().as_deref() != Some(MODULE) {{")?;
+        writeln!(output, "        return false")?;
+        writeln!(output, "    }}")?;
+        writeln!(output)?;
+        for id in 0..self.module.chunks().len() {
+            writeln!(output, "    vm.set_compiled_chunk(module, {id}, chunk_{id});")?;
+        }
+        writeln!(output, "    true")?;
+        writeln!(output, "}}")?;
+
+        for id in 0..self.module.chunks().len() {
+            writeln!(output)?;
+            self.transpile_chunk(&mut output, id)?;
+        }
+
+        Ok(output)
+    }
+
+    // Generate the function of a chunk
+    // It starts at the block of the current index: the beginning of the chunk
+    // or the instruction following the call it returned from
+    fn transpile_chunk(&self, output: &mut String, id: usize) -> Result<(), AotError<'a>> {
+        let chunk = &self.module.chunks()[id];
+        let len = chunk.get_instructions().len();
+
+        // Offsets of the instructions and of the ones starting a block
+        let mut reader = ChunkReader::new(chunk);
+        let mut instructions = Vec::new();
+        let mut leaders = BTreeSet::from([0, len]);
+        while reader.index() < len {
+            let offset = reader.index();
+            let opcode = reader.read_op_code()?;
+            match opcode {
+                OpCode::Jump
+                | OpCode::JumpIfFalse
+                | OpCode::IteratorNext
+                | OpCode::LtJumpIfFalse
+                | OpCode::LteJumpIfFalse
+                | OpCode::GtJumpIfFalse
+                | OpCode::GteJumpIfFalse => {
+                    leaders.insert(reader.read_u32()? as usize);
+                },
+                OpCode::JumpTable => {
+                    leaders.insert(reader.read_u32()? as usize);
+                    reader.read_u16()?;
+                    let cases = reader.read_u16()? as usize;
+                    for case in 0..cases {
+                        leaders.insert(reader.index() + case * JUMP_SIZE);
+                    }
+                },
+                opcode => reader.advance(opcode.arguments_bytes())?
+            }
+
+            // The instruction after a branch or a call starts a new block
+            if is_block_end(opcode) {
+                leaders.insert(reader.index());
+            }
+
+            instructions.push(offset);
+        }
+
+        writeln!(output, "#[allow(unused_variables, unused_mut, unused_assignments, unreachable_code, clippy::all)]")?;
+        writeln!(output, "fn chunk_{id}<'a>(backend: &Backend<'a>, stack: &mut Stack<'a>, manager: &mut ChunkManager<'a>, context: &mut Context<'a>) -> Result<InstructionResult, VMError> {{")?;
+        writeln!(output, "    let mut block = manager.index();")?;
+        writeln!(output, "    loop {{")?;
+        writeln!(output, "        block = match block {{")?;
+
+        let mut open = false;
+        for offset in instructions {
+            if leaders.contains(&offset) {
+                // The previous block continues with this one
+                if open {
+                    writeln!(output, "{INDENT}{offset}")?;
+                    writeln!(output, "            }},")?;
+                }
+
+                writeln!(output, "            {offset} => {{")?;
+                open = true;
+            } else if !open {
+                // Unreachable, no block leads to it
+                continue;
+            }
+
+            reader.set_index(offset)?;
+            let opcode = reader.read_op_code()?;
+            let next = offset + 1 + opcode.arguments_bytes();
+            writeln!(output, "{INDENT}// {opcode:?}")?;
+            writeln!(output, "{INDENT}{{")?;
+            writeln!(output, "{STATEMENT_INDENT}manager.set_index({})?;", offset + 1)?;
+            writeln!(output, "{STATEMENT_INDENT}context.increase_gas_usage(backend.get_instruction_cost(OpCode::{opcode:?}))?;")?;
+
+            let flow = self.transpile_instruction(output, &mut reader, opcode, next)?;
+            writeln!(output, "{INDENT}}}")?;
+            if let Flow::End = flow {
+                writeln!(output, "            }},")?;
+                open = false;
+            }
+        }
+
+        if open {
+            writeln!(output, "{INDENT}{len}")?;
+            writeln!(output, "            }},")?;
+        }
+
+        // Reaching the end of the chunk returns like the interpreter
+        writeln!(output, "            {len} => return Ok(InstructionResult::Break),")?;
+        writeln!(output, "            _ => return Err(VMError::OutOfBounds)")?;
+        writeln!(output, "        }};")?;
+        writeln!(output, "    }}")?;
+        writeln!(output, "}}")?;
+
+        Ok(())
+    }
+
+    // Generate the statements of an instruction
+    // The reader is after its opcode, `next` is the offset of the following instruction
+    fn transpile_instruction(&self, output: &mut String, reader: &mut ChunkReader, opcode: OpCode, next: usize) -> Result<Flow, AotError<'a>> {
+        let offset = next - 1 - opcode.arguments_bytes();
+        match opcode {
+            OpCode::Constant => emit(output, "
+let value: ValueCell = backend.get_constant_with_id($INDEX)?.clone().into();
+context.increase_value_memory_usage(&value)?;
+stack.push_stack(Path::Owned(value))?;", &[("INDEX", &reader.read_u16()?)])?,
+            OpCode::MemoryLoad => emit(output, "
+let value = manager.from_register($INDEX)?.shareable();
+stack.push_stack(value)?;", &[("INDEX", &reader.read_u16()?)])?,
+            OpCode::MemorySet => emit(output, "
+let mut value = stack.pop_stack()?;
+if !matches!(value, Path::Owned(_)) && matches!(value.as_ref().as_value(), ValueCell::Default(_)) {
+    context.increase_stored_memory_usage(&value)?;
+    value = Path::Owned(value.into_owned());
+}
+if let Some(previous) = manager.set_register($INDEX, value) {
+    context.decrease_value_memory_usage(&previous)?;
+}", &[("INDEX", &reader.read_u16()?)])?,
+            OpCode::SubLoad => emit(output, "
+let sub = stack.pop_stack()?.get_sub_variable($INDEX)?;
+stack.push_stack_unchecked(sub);", &[("INDEX", &reader.read_u8()?)])?,
+            OpCode::Pop => emit(output, "
+let value = stack.pop_stack()?;
+context.decrease_value_memory_usage(&value)?;", &[])?,
+            OpCode::PopN => emit(output, "
+for value in stack.pop_stack_n($COUNT)? {
+    context.decrease_value_memory_usage(&value)?;
+}", &[("COUNT", &reader.read_u8()?)])?,
+            OpCode::Copy => emit(output, "
+let value = stack.last_stack()?.clone();
+stack.push_stack(value)?;", &[])?,
+            OpCode::CopyN => emit(output, "
+let value = stack.get_stack_at($INDEX)?.clone();
+stack.push_stack(value)?;", &[("INDEX", &reader.read_u8()?)])?,
+            OpCode::Swap => emit(output, "
+stack.swap_stack($INDEX)?;", &[("INDEX", &reader.read_u8()?)])?,
+            OpCode::Swap2 => {
+                let a = reader.read_u8()?;
+                let b = reader.read_u8()?;
+                emit(output, "
+stack.swap_stack_both($A, $B)?;", &[("A", &a), ("B", &b)])?
+            },
+            OpCode::Jump => {
+                emit(output, "$ADDR", &[("ADDR", &reader.read_u32()?)])?;
+                return Ok(Flow::End)
+            },
+            OpCode::JumpIfFalse => {
+                emit(output, "
+if stack.pop_stack()?.as_bool()? { $NEXT } else { $ADDR }", &[("ADDR", &reader.read_u32()?), ("NEXT", &next)])?;
+                return Ok(Flow::End)
+            },
+            OpCode::IterableLength => emit(output, "
+let len = stack.pop_stack()?.as_ref().array_len()?;
+stack.push_stack_unchecked(Path::Owned(ValueCell::Default(Value::U32(len as u32))));", &[])?,
+            OpCode::IteratorBegin => emit(output, "
+let value = stack.pop_stack()?;
+manager.begin_iterator(value)?;", &[])?,
+            OpCode::IteratorNext => {
+                emit(output, "
+match manager.next_iterator($OFFSET, stack, context)? {
+    IteratorStep::Value(value) => {
+        stack.push_stack(value)?;
+        $NEXT
+    },
+    IteratorStep::Callback(chunk) => return Ok(InstructionResult::InvokeChunk(chunk)),
+    IteratorStep::End => $ADDR
+}", &[("OFFSET", &offset), ("ADDR", &reader.read_u32()?), ("NEXT", &next)])?;
+                return Ok(Flow::End)
+            },
+            OpCode::IteratorEnd => emit(output, "
+manager.pop_iterator()?;", &[])?,
+            OpCode::Return => {
+                emit(output, "
+return Ok(InstructionResult::Break)", &[])?;
+                return Ok(Flow::End)
+            },
+            OpCode::ArrayCall => emit(output, "
+let index = stack.pop_stack()?.into_owned().cast_to_u32()?;
+let sub = stack.pop_stack()?.get_sub_variable(index as usize)?;
+stack.push_stack_unchecked(sub);", &[])?,
+            OpCode::Cast => {
+                emit(output, "
+let current = stack.pop_stack()?;
+context.decrease_value_memory_usage(&current)?;", &[])?;
+                let Some(cast) = cast_expression(&reader.read_type()?) else {
+                    emit(output, "
+return Err(VMError::UnsupportedCastType)", &[])?;
+                    return Ok(Flow::End)
+                };
+                emit(output, "
+let value = $CAST;
+charge_string(&value, context)?;
+stack.push_stack(Path::Owned(value.into()))?;", &[("CAST", &cast)])?
+            },
+            OpCode::MemoryCast => {
+                emit(output, "
+let current = manager.from_register($INDEX)?.shareable();", &[("INDEX", &reader.read_u16()?)])?;
+                let Some(cast) = cast_expression(&reader.read_type()?) else {
+                    emit(output, "
+return Err(VMError::UnsupportedCastType)", &[])?;
+                    return Ok(Flow::End)
+                };
+                emit(output, "
+let value = $CAST;
+charge_string(&value, context)?;
+stack.push_stack(Path::Owned(value.into()))?;", &[("CAST", &cast)])?
+            },
+            OpCode::InvokeChunk => {
+                let id = reader.read_u16()?;
+                let on_value = reader.read_bool()?;
+                let args = reader.read_u8()? as usize + on_value as usize;
+                emit(output, "
+reverse_arguments(stack, $ARGS)?;
+manager.set_index($NEXT)?;
+return Ok(InstructionResult::InvokeChunk($ID))", &[("ARGS", &args), ("NEXT", &next), ("ID", &id)])?;
+                return Ok(Flow::End)
+            },
+            OpCode::ExternalCall => {
+                let module = reader.read_u16()?;
+                let export = reader.read_u16()?;
+                let args = reader.read_u8()?;
+                emit(output, "
+reverse_arguments(stack, $ARGS)?;
+manager.set_index($NEXT)?;
+return Ok(InstructionResult::InvokeExternalChunk($MODULE, $EXPORT, $ARGS))", &[("ARGS", &args), ("NEXT", &next), ("MODULE", &module), ("EXPORT", &export)])?;
+                return Ok(Flow::End)
+            },
+            OpCode::SysCall => {
+                let id = reader.read_u16()?;
+                let on_value = reader.read_bool()?;
+                let args = reader.read_u8()?;
+                self.transpile_syscall(output, offset, id, on_value, args)?
+            },
+            OpCode::ChunkId => emit(output, "
+stack.push_stack(Path::Owned(ValueCell::Default(Value::U16($ID))))?;", &[("ID", &reader.read_u16()?)])?,
+            OpCode::CallPrecompile => emit(output, "
+let precompile = backend.environment().get_precompile($ID)
+    .ok_or(VMError::UnknownPrecompile($ID))?;
+let input = precompile_input(&stack.pop_stack()?)?;
+let gas = precompile.base_gas()
+    .saturating_add(precompile.dynamic_gas(&input));
+context.increase_gas_usage(gas)?;
+let output = precompile.execute(&input)
+    .map_err(EnvironmentError::from)?;
+stack.push_stack(Path::Owned(ValueCell::TypedArray(output.into())))?;", &[("ID", &reader.read_u16()?)])?,
+            OpCode::NewArray => {
+                let length = reader.read_u8()?;
+                emit(output, "
+context.increase_memory_usage($LENGTH * VALUE_MEMORY_USAGE)?;", &[("LENGTH", &length)])?;
+                // The values are popped from the last one
+                for i in (0..length).rev() {
+                    emit(output, "
+let value_$I = stack.pop_stack()?;
+context.increase_stored_memory_usage(&value_$I)?;
+let value_$I = value_$I.into_owned();", &[("I", &i)])?;
+                }
+                let values = (0..length).map(|i| format!("value_{i}")).collect::<Vec<_>>().join(", ");
+                emit(output, "
+stack.push_stack(Path::Owned(ValueCell::new_array(vec![$VALUES])))?;", &[("VALUES", &values)])?
+            },
+            OpCode::NewStruct => emit(output, "
+let struct_type = backend.get_struct_with_id($ID)?;
+let fields_count = struct_type.fields().len();
+context.increase_memory_usage(fields_count * VALUE_MEMORY_USAGE)?;
+let mut fields = std::collections::VecDeque::with_capacity(fields_count);
+for _ in 0..fields_count {
+    let value = stack.pop_stack()?;
+    context.increase_stored_memory_usage(&value)?;
+    fields.push_front(value.into_owned().into());
+}
+stack.push_stack(Path::Owned(ValueCell::Struct(Vec::from(fields).into(), struct_type.clone())))?;", &[("ID", &reader.read_u16()?)])?,
+            OpCode::NewRange | OpCode::NewRangeInclusive => emit(output, "
+let end = stack.pop_stack()?.into_owned();
+let start = stack.pop_stack()?.into_owned();
+if !start.is_number() || !end.is_number() {
+    return Err(VMError::InvalidRangeType);
+}
+let start_type = start.as_value()?.get_type()?;
+if start_type != end.as_value()?.get_type()? {
+    return Err(VMError::InvalidRangeType);
+}
+let value = Value::Range(Box::new(start.into_value()?), Box::new(end.into_value()?), start_type, $INCLUSIVE);
+stack.push_stack_unchecked(Path::Owned(ValueCell::Default(value)));", &[("INCLUSIVE", &(opcode == OpCode::NewRangeInclusive))])?,
+            OpCode::NewMap => emit(output, "
+context.increase_memory_usage($LENGTH * 2 * VALUE_MEMORY_USAGE)?;
+let mut entries = Vec::with_capacity($LENGTH);
+for _ in 0..$LENGTH {
+    let value = stack.pop_stack()?;
+    let key = stack.pop_stack()?;
+    if key.as_ref().is_map() {
+        return Err(EnvironmentError::InvalidKeyType.into());
+    }
+    context.increase_stored_memory_usage(&key)?;
+    context.increase_stored_memory_usage(&value)?;
+    entries.push((key.into_owned(), value.into_owned().into()));
+}
+let map: IndexMap<_, _> = entries.into_iter().rev().collect();
+stack.push_stack_unchecked(Path::Owned(ValueCell::Map(map.into())));", &[("LENGTH", &reader.read_u8()?)])?,
+            OpCode::NewEnum => {
+                let id = reader.read_u16()?;
+                let variant = reader.read_u8()?;
+                emit(output, "
+let enum_type = backend.get_enum_with_id($ID)?;
+let fields_count = enum_type.get_variant($VARIANT)
+    .ok_or(VMError::InvalidEnumVariant)?
+    .fields()
+    .len();
+context.increase_memory_usage(fields_count * VALUE_MEMORY_USAGE)?;
+let mut values = std::collections::VecDeque::with_capacity(fields_count);
+for _ in 0..fields_count {
+    let value = stack.pop_stack()?;
+    context.increase_stored_memory_usage(&value)?;
+    values.push_front(value.into_owned().into());
+}
+stack.push_stack(Path::Owned(ValueCell::Enum(Vec::from(values).into(), EnumValueType::new(enum_type.clone(), $VARIANT))))?;", &[("ID", &id), ("VARIANT", &variant)])?
+            },
+            OpCode::Unpack => emit(output, "
+let values = stack.pop_stack()?.into_owned().to_vec()?;
+if values.len() != $LENGTH {
+    return Err(VMError::InvalidUnpackLength($LENGTH, values.len()));
+}
+for value in values.into_iter().rev() {
+    stack.push_stack(Path::Owned(value.into_owned()))?;
+}", &[("LENGTH", &reader.read_u8()?)])?,
+            OpCode::Add => emit(output, "
+let right = stack.pop_stack()?;
+let left = stack.pop_stack()?;
+let value = op_string!(left.as_ref(), right.as_ref());
+charge_string(&value, context)?;
+release_operands(context, &left, &right)?;
+stack.push_stack_unchecked(Path::Owned(value.into()));", &[])?,
+            OpCode::Sub => binary_op(output, "op_overflow!", "checked_sub")?,
+            OpCode::Mul => binary_op(output, "op_overflow!", "checked_mul")?,
+            OpCode::Div => binary_op(output, "op_div!", "checked_div")?,
+            OpCode::Mod => binary_op(output, "op_div!", "checked_rem")?,
+            OpCode::BitwiseAnd => binary_op(output, "op!", "&")?,
+            OpCode::BitwiseOr => binary_op(output, "op!", "|")?,
+            OpCode::BitwiseXor => binary_op(output, "op!", "^")?,
+            OpCode::BitwiseShl => binary_op(output, "op_shift!", "checked_shl")?,
+            OpCode::BitwiseShr => binary_op(output, "op_shift!", "checked_shr")?,
+            OpCode::Gt => binary_op(output, "op_bool!", ">")?,
+            OpCode::Lt => binary_op(output, "op_bool!", "<")?,
+            OpCode::Gte => binary_op(output, "op_bool!", ">=")?,
+            OpCode::Lte => binary_op(output, "op_bool!", "<=")?,
+            OpCode::CheckedAdd => binary_op(output, "op_checked!", "checked_add")?,
+            OpCode::CheckedSub => binary_op(output, "op_checked!", "checked_sub")?,
+            OpCode::CheckedMul => binary_op(output, "op_checked!", "checked_mul")?,
+            OpCode::SaturatingAdd => binary_op(output, "op_method!", "saturating_add")?,
+            OpCode::SaturatingSub => binary_op(output, "op_method!", "saturating_sub")?,
+            OpCode::SaturatingMul => binary_op(output, "op_method!", "saturating_mul")?,
+            OpCode::WrappingAdd => binary_op(output, "op_method!", "wrapping_add")?,
+            OpCode::WrappingSub => binary_op(output, "op_method!", "wrapping_sub")?,
+            OpCode::WrappingMul => binary_op(output, "op_method!", "wrapping_mul")?,
+            OpCode::Pow => emit(output, "
+let right = stack.pop_stack()?;
+let left = stack.pop_stack()?;
+let value = op_pow!(left.as_ref(), right.as_ref());
+stack.push_stack_unchecked(Path::Owned(value.into()));", &[])?,
+            OpCode::And => emit(output, "
+let left = stack.pop_stack()?.as_bool()?;
+let right = stack.pop_stack()?.as_bool()?;
+stack.push_stack_unchecked(Path::Owned(Value::Boolean(left && right).into()));", &[])?,
+            OpCode::Or => emit(output, "
+let right = stack.pop_stack()?;
+let left = stack.pop_stack()?;
+let value = left.as_bool()? || right.as_bool()?;
+stack.push_stack_unchecked(Path::Owned(Value::Boolean(value).into()));", &[])?,
+            OpCode::Eq => emit(output, "
+let right = stack.pop_stack()?;
+let left = stack.pop_stack()?;
+let value = match (left.as_ref().as_value(), right.as_ref().as_value()) {
+    (ValueCell::Default(a), ValueCell::Default(b)) if !a.is_null() && !b.is_null() => op_bool!(left.as_ref(), right.as_ref(), ==),
+    (a, b) => {
+        let (equal, compared) = a.deep_eq(b, context.max_value_depth())?;
+        context.increase_gas_usage(compared as u64)?;
+        Value::Boolean(equal)
+    }
+};
+release_operands(context, &left, &right)?;
+stack.push_stack_unchecked(Path::Owned(value.into()));", &[])?,
+            OpCode::Neg => emit(output, "
+let value = stack.pop_stack()?.as_ref().as_value().as_value()?.not()?;
+stack.push_stack_unchecked(Path::Owned(value.into()));", &[])?,
+            OpCode::Assign => emit(output, "
+let right = stack.pop_stack()?;
+let mut left = stack.pop_stack()?;
+right.as_ref().calculate_depth(context.max_value_depth())?;
+context.increase_stored_memory_usage(&right)?;
+let owned = right.into_owned();
+let previous = std::mem::replace(&mut *left.as_mut(), owned);
+let freed = context.owned_memory_usage(&previous)?;
+context.decrease_memory_usage(freed);", &[])?,
+            OpCode::AssignAdd => emit(output, "
+let right = stack.pop_stack()?;
+let mut left = stack.pop_stack()?;
+let value = op_string!(left.as_ref(), right.as_ref());
+charge_string(&value, context)?;
+let previous = context.value_memory_usage(left.as_ref().as_value())?;
+context.decrease_memory_usage(previous);
+context.decrease_value_memory_usage(&right)?;
+*left.as_mut() = value.into();", &[])?,
+            OpCode::AssignSub => assign_op(output, "op_overflow!", "checked_sub")?,
+            OpCode::AssignMul => assign_op(output, "op_overflow!", "checked_mul")?,
+            OpCode::AssignDiv => assign_op(output, "op_div!", "checked_div")?,
+            OpCode::AssignMod => assign_op(output, "op_div!", "checked_rem")?,
+            OpCode::AssignBitwiseAnd => assign_op(output, "op!", "&")?,
+            OpCode::AssignBitwiseOr => assign_op(output, "op!", "|")?,
+            OpCode::AssignBitwiseXor => assign_op(output, "op!", "^")?,
+            OpCode::AssignBitwiseShl => assign_op(output, "op_shift!", "checked_shl")?,
+            OpCode::AssignBitwiseShr => assign_op(output, "op_shift!", "checked_shr")?,
+            OpCode::AssignPow => emit(output, "
+let right = stack.pop_stack()?;
+let mut left = stack.pop_stack()?;
+let value = op_pow!(left.as_ref(), right.as_ref());
+*left.as_mut() = value.into();", &[])?,
+            OpCode::Inc => emit(output, "
+stack.last_mut_stack()?.as_mut().increment()?;", &[])?,
+            OpCode::Dec => emit(output, "
+stack.last_mut_stack()?.as_mut().decrement()?;", &[])?,
+            OpCode::MemoryAdd => {
+                let left = reader.read_u16()?;
+                let right = reader.read_u16()?;
+                emit(output, "
+let left = manager.from_register($LEFT)?.shareable();
+let right = manager.from_register($RIGHT)?.shareable();
+let value = op_string!(left.as_ref(), right.as_ref());
+charge_string(&value, context)?;
+stack.push_stack(Path::Owned(value.into()))?;", &[("LEFT", &left), ("RIGHT", &right)])?
+            },
+            OpCode::MemoryInc => emit(output, "
+manager.from_register($INDEX)?
+    .as_mut()
+    .increment()
+    .map_err(|e| match e {
+        ValueError::Overflow => VMError::ArithmeticOverflow,
+        e => e.into()
+    })?;", &[("INDEX", &reader.read_u16()?)])?,
+            OpCode::LtJumpIfFalse
+            | OpCode::LteJumpIfFalse
+            | OpCode::GtJumpIfFalse
+            | OpCode::GteJumpIfFalse => {
+                let op = match opcode {
+                    OpCode::LtJumpIfFalse => "<",
+                    OpCode::LteJumpIfFalse => "<=",
+                    OpCode::GtJumpIfFalse => ">",
+                    _ => ">=",
+                };
+                emit(output, "
+let right = stack.pop_stack()?;
+let left = stack.pop_stack()?;
+let value = op_bool!(left.as_ref(), right.as_ref(), $OP);
+release_operands(context, &left, &right)?;
+if let Value::Boolean(false) = value { $ADDR } else { $NEXT }", &[("OP", &op), ("ADDR", &reader.read_u32()?), ("NEXT", &next)])?;
+                return Ok(Flow::End)
+            },
+            OpCode::JumpTable => {
+                let addr = reader.read_u32()?;
+                let first = reader.read_u16()?;
+                let cases = reader.read_u16()?;
+                emit(output, "
+let first = backend.get_constant_with_id($FIRST)?.as_value()?;
+let value = stack.pop_stack()?.into_owned().into_value()?;
+if value.get_type()? != first.get_type()? {
+    return Err(VMError::UnexpectedType)
+}
+let index = value.cast_to_u256()?
+    .checked_sub(first.clone().cast_to_u256()?)
+    .and_then(|v| v.as_u64());
+match index {", &[("FIRST", &first)])?;
+                // Each case lands on its Jump, which is charged like in the interpreter
+                for case in 0..cases as usize {
+                    emit(output, "
+    Some($CASE) => $TARGET,", &[("CASE", &case), ("TARGET", &(next + case * JUMP_SIZE))])?;
+                }
+                emit(output, "
+    _ => $ADDR
+}", &[("ADDR", &addr)])?;
+                return Ok(Flow::End)
+            },
+        }
+
+        Ok(Flow::Next)
+    }
+
+    // Generate the call of a native function
+    // The arguments are popped and copied like the interpreter before borrowing the instance
+    // The values read from an iterator instance go through the chunks of its adapters first,
+    // the instruction is then interpreted again with the result of each chunk
+    fn transpile_syscall(&self, output: &mut String, offset: usize, id: u16, on_value: bool, args: u8) -> Result<(), AotError<'a>> {
+        emit(output, "
+let f = backend.environment().get_functions().get($ID)
+    .ok_or(VMError::UnknownSysCall)?;
+if backend.is_strict_determinism() && !f.is_deterministic() {
+    return Err(VMError::NonDeterministicOperation($ID));
+}", &[("ID", &id)])?;
+
+        if on_value {
+            emit(output, "
+if f.get_consumed_values() > 0 {
+    if let Some(chunk) = manager.resolve_native_instance($OFFSET, f.get_consumed_values(), $ARGS, stack, context)? {
+        return Ok(InstructionResult::InvokeChunk(chunk))
+    }
+}", &[("OFFSET", &offset), ("ARGS", &args)])?;
+        }
+
+        for i in (0..args).rev() {
+            emit(output, "
+let argument_$I = stack.pop_stack()?;", &[("I", &i)])?;
+        }
+        let arguments = (0..args).map(|i| format!("argument_{i}")).collect::<Vec<_>>().join(", ");
+        emit(output, "
+let mut arguments = vec![$ARGUMENTS];", &[("ARGUMENTS", &arguments)])?;
+
+        if on_value {
+            emit(output, "
+let mut instance = stack.pop_stack()?;
+copy_native_arguments(&mut arguments, Some(&instance), context)?;", &[])?;
+        } else {
+            emit(output, "
+copy_native_arguments(&mut arguments, None, context)?;", &[])?;
+        }
+
+        emit(output, "
+let gas = context.current_gas_usage();", &[])?;
+
+        if on_value {
+            emit(output, "
+let result = f.call_function(Some(&mut *instance.as_mut()), arguments, context);", &[])?;
+        } else {
+            emit(output, "
+let result = f.call_function(None, arguments, context);", &[])?;
+        }
+
+        emit(output, "
+context.trace_native_call($ID, context.current_gas_usage().saturating_sub(gas));
+match result {
+    Ok(Some(value)) => stack.push_stack(Path::Owned(value))?,
+    Ok(None) => {},
+    Err(EnvironmentError::Revert(value)) => return Ok(InstructionResult::Revert(value)),
+    Err(e) => return Err(e.into())
+}", &[("ID", &id)])?;
+
+        Ok(())
+    }
+}
+
+// Does the instruction end its block, by branching or by leaving the chunk
+fn is_block_end(opcode: OpCode) -> bool {
+    matches!(
+        opcode,
+        OpCode::Jump
+            | OpCode::JumpIfFalse
+            | OpCode::IteratorNext
+            | OpCode::LtJumpIfFalse
+            | OpCode::LteJumpIfFalse
+            | OpCode::GtJumpIfFalse
+            | OpCode::GteJumpIfFalse
+            | OpCode::JumpTable
+            | OpCode::InvokeChunk
+            | OpCode::ExternalCall
+            | OpCode::Return
+    )
+}
+
+// Expression casting the `current` path to a primitive type
+// None if the values can't be casted to the type
+fn cast_expression(_type: &Type) -> Option<String> {
+    let (variant, function) = match _type {
+        Type::U8 => ("U8", "cast_to_u8"),
+        Type::U16 => ("U16", "cast_to_u16"),
+        Type::U32 => ("U32", "cast_to_u32"),
+        Type::U64 => ("U64", "cast_to_u64"),
+        Type::U128 => ("U128", "cast_to_u128"),
+        Type::U256 => ("U256", "cast_to_u256"),
+        Type::String => ("String", "cast_to_string"),
+        _ => return None
+    };
+
+    Some(format!("Value::{variant}(current.into_owned().{function}()?)"))
+}
+
+// Operation consuming its two operands and pushing its result
+fn binary_op(output: &mut String, macr: &str, op: &str) -> fmt::Result {
+    emit(output, "
+let right = stack.pop_stack()?;
+let left = stack.pop_stack()?;
+let value = $MACRO(left.as_ref(), right.as_ref(), $OP);
+release_operands(context, &left, &right)?;
+stack.push_stack_unchecked(Path::Owned(value.into()));", &[("MACRO", &macr), ("OP", &op)])
+}
+
+// Operation storing its result in the left operand
+fn assign_op(output: &mut String, macr: &str, op: &str) -> fmt::Result {
+    emit(output, "
+let right = stack.pop_stack()?;
+let mut left = stack.pop_stack()?;
+let value = $MACRO(left.as_ref(), right.as_ref(), $OP);
+context.decrease_value_memory_usage(&right)?;
+*left.as_mut() = value.into();", &[("MACRO", &macr), ("OP", &op)])
+}
+
+// Write the lines of a template in the current instruction
+// Each `$NAME` of the template is replaced by its argument
+fn emit(output: &mut String, template: &str, args: &[(&str, &dyn Display)]) -> fmt::Result {
+    let mut code = template.trim_start_matches('\n').to_owned();
+    for (name, value) in args {
+        code = code.replace(&format!("${name}"), &value.to_string());
+    }
+
+    for line in code.lines() {
+        writeln!(output, "{STATEMENT_INDENT}{line}")?;
+    }
+
+    Ok(())
+}
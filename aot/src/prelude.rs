@@ -0,0 +1,268 @@
+// Items shared by the generated chunks
+// The transpiler writes them at the top of every generated source,
+// the operations are the same as the ones of the interpreter
+
+// Bitwise operation on two integers of the same type
+macro_rules! op {
+    ($a: expr, $b: expr, $op: tt) => {{
+        match ($a.as_value(), $b.as_value()) {
+            (ValueCell::Default(a), ValueCell::Default(b)) => match (a, b) {
+                (Value::U8(a), Value::U8(b)) => Value::U8(a $op b),
+                (Value::U16(a), Value::U16(b)) => Value::U16(a $op b),
+                (Value::U32(a), Value::U32(b)) => Value::U32(a $op b),
+                (Value::U64(a), Value::U64(b)) => Value::U64(a $op b),
+                (Value::U128(a), Value::U128(b)) => Value::U128(a $op b),
+                (Value::U256(a), Value::U256(b)) => Value::U256(*a $op *b),
+                _ => return Err(VMError::UnexpectedType)
+            }
+            _ => return Err(VMError::UnexpectedType)
+        }
+    }};
+}
+
+// Checked addition of numbers or concatenation of strings
+macro_rules! op_string {
+    ($a: expr, $b: expr) => {{
+        match ($a.as_value(), $b.as_value()) {
+            (ValueCell::Default(a), ValueCell::Default(b)) => match (a, b) {
+                (Value::U8(a), Value::U8(b)) => Value::U8(a.checked_add(*b).ok_or(VMError::ArithmeticOverflow)?),
+                (Value::U16(a), Value::U16(b)) => Value::U16(a.checked_add(*b).ok_or(VMError::ArithmeticOverflow)?),
+                (Value::U32(a), Value::U32(b)) => Value::U32(a.checked_add(*b).ok_or(VMError::ArithmeticOverflow)?),
+                (Value::U64(a), Value::U64(b)) => Value::U64(a.checked_add(*b).ok_or(VMError::ArithmeticOverflow)?),
+                (Value::U128(a), Value::U128(b)) => Value::U128(a.checked_add(*b).ok_or(VMError::ArithmeticOverflow)?),
+                (Value::U256(a), Value::U256(b)) => Value::U256(a.checked_add(*b).ok_or(VMError::ArithmeticOverflow)?),
+                (Value::String(a), Value::String(b)) => {
+                    // Verify the final len is less than u32::MAX
+                    if (a.len() as u32).checked_add(b.len() as u32).is_none() {
+                        return Err(VMError::StringTooLarge);
+                    }
+
+                    Value::String(a.to_owned() + b)
+                }
+                _ => {
+                    // One of the values may be a string
+                    if !a.is_string() && !b.is_string() {
+                        return Err(VMError::UnexpectedType)
+                    }
+
+                    let left = a.as_string_formatted()?.into_owned();
+                    let right = b.as_string_formatted()?;
+                    // Verify the final len is less than u32::MAX
+                    if (left.len() as u32).checked_add(right.len() as u32).is_none() {
+                        return Err(VMError::StringTooLarge);
+                    }
+
+                    Value::String(left + &right)
+                }
+            }
+            _ => return Err(VMError::UnexpectedType)
+        }
+    }};
+}
+
+// Comparison of two primitives of the same type
+macro_rules! op_bool {
+    ($a: expr, $b: expr, $op: tt) => {{
+        match ($a.as_value(), $b.as_value()) {
+            (ValueCell::Default(a), ValueCell::Default(b)) => match (a, b) {
+                (Value::Boolean(a), Value::Boolean(b)) => Value::Boolean(a $op b),
+                (Value::U8(a), Value::U8(b)) => Value::Boolean(a $op b),
+                (Value::U16(a), Value::U16(b)) => Value::Boolean(a $op b),
+                (Value::U32(a), Value::U32(b)) => Value::Boolean(a $op b),
+                (Value::U64(a), Value::U64(b)) => Value::Boolean(a $op b),
+                (Value::U128(a), Value::U128(b)) => Value::Boolean(a $op b),
+                (Value::U256(a), Value::U256(b)) => Value::Boolean(a $op b),
+                (Value::String(a), Value::String(b)) => Value::Boolean(a $op b),
+                _ => return Err(VMError::UnexpectedType)
+            }
+            _ => return Err(VMError::UnexpectedType)
+        }
+    }};
+}
+
+// Call an integer method like `saturating_add` on two values of the same type
+macro_rules! op_method {
+    ($a: expr, $b: expr, $fn: ident) => {{
+        match ($a.as_value(), $b.as_value()) {
+            (ValueCell::Default(a), ValueCell::Default(b)) => match (a, b) {
+                (Value::U8(a), Value::U8(b)) => Value::U8(a.$fn(*b)),
+                (Value::U16(a), Value::U16(b)) => Value::U16(a.$fn(*b)),
+                (Value::U32(a), Value::U32(b)) => Value::U32(a.$fn(*b)),
+                (Value::U64(a), Value::U64(b)) => Value::U64(a.$fn(*b)),
+                (Value::U128(a), Value::U128(b)) => Value::U128(a.$fn(*b)),
+                (Value::U256(a), Value::U256(b)) => Value::U256(a.$fn(*b)),
+                _ => return Err(VMError::UnexpectedType)
+            }
+            _ => return Err(VMError::UnexpectedType)
+        }
+    }};
+}
+
+// Call a checked integer method like `checked_add` on two values of the same type
+// The result is an optional, null if the operation overflowed
+macro_rules! op_checked {
+    ($a: expr, $b: expr, $fn: ident) => {{
+        let value = match ($a.as_value(), $b.as_value()) {
+            (ValueCell::Default(a), ValueCell::Default(b)) => match (a, b) {
+                (Value::U8(a), Value::U8(b)) => a.$fn(*b).map(Value::U8),
+                (Value::U16(a), Value::U16(b)) => a.$fn(*b).map(Value::U16),
+                (Value::U32(a), Value::U32(b)) => a.$fn(*b).map(Value::U32),
+                (Value::U64(a), Value::U64(b)) => a.$fn(*b).map(Value::U64),
+                (Value::U128(a), Value::U128(b)) => a.$fn(*b).map(Value::U128),
+                (Value::U256(a), Value::U256(b)) => a.$fn(*b).map(Value::U256),
+                _ => return Err(VMError::UnexpectedType)
+            }
+            _ => return Err(VMError::UnexpectedType)
+        };
+
+        ValueCell::Optional(value.map(Into::into))
+    }};
+}
+
+// Operation on numbers returning an error on overflow
+macro_rules! op_overflow {
+    ($a: expr, $b: expr, $fn: ident) => {{
+        match ($a.as_value(), $b.as_value()) {
+            (ValueCell::Default(a), ValueCell::Default(b)) => match (a, b) {
+                (Value::U8(a), Value::U8(b)) => a.$fn(*b).map(Value::U8),
+                (Value::U16(a), Value::U16(b)) => a.$fn(*b).map(Value::U16),
+                (Value::U32(a), Value::U32(b)) => a.$fn(*b).map(Value::U32),
+                (Value::U64(a), Value::U64(b)) => a.$fn(*b).map(Value::U64),
+                (Value::U128(a), Value::U128(b)) => a.$fn(*b).map(Value::U128),
+                (Value::U256(a), Value::U256(b)) => a.$fn(*b).map(Value::U256),
+                _ => return Err(VMError::UnexpectedType)
+            }
+            _ => return Err(VMError::UnexpectedType)
+        }.ok_or(VMError::ArithmeticOverflow)?
+    }};
+}
+
+// Division or remainder, dividing by zero is an error
+macro_rules! op_div {
+    ($a: expr, $b: expr, $fn: ident) => {{
+        match ($a.as_value(), $b.as_value()) {
+            (ValueCell::Default(a), ValueCell::Default(b)) => match (a, b) {
+                (Value::U8(a), Value::U8(b)) => a.$fn(*b).map(Value::U8),
+                (Value::U16(a), Value::U16(b)) => a.$fn(*b).map(Value::U16),
+                (Value::U32(a), Value::U32(b)) => a.$fn(*b).map(Value::U32),
+                (Value::U64(a), Value::U64(b)) => a.$fn(*b).map(Value::U64),
+                (Value::U128(a), Value::U128(b)) => a.$fn(*b).map(Value::U128),
+                (Value::U256(a), Value::U256(b)) => a.$fn(*b).map(Value::U256),
+                _ => return Err(VMError::UnexpectedType)
+            }
+            _ => return Err(VMError::UnexpectedType)
+        }.ok_or(VMError::DivisionByZero)?
+    }};
+}
+
+// Shift a value by an amount of the same type
+// Shifting by the number of bits of the type or more is an error
+macro_rules! op_shift {
+    ($a: expr, $b: expr, $fn: ident) => {{
+        let value = match ($a.as_value(), $b.as_value()) {
+            (ValueCell::Default(a), ValueCell::Default(b)) => match (a, b) {
+                (Value::U8(a), Value::U8(b)) => a.$fn(*b as u32).map(Value::U8),
+                (Value::U16(a), Value::U16(b)) => a.$fn(*b as u32).map(Value::U16),
+                (Value::U32(a), Value::U32(b)) => a.$fn(*b).map(Value::U32),
+                (Value::U64(a), Value::U64(b)) => u32::try_from(*b).ok().and_then(|b| a.$fn(b)).map(Value::U64),
+                (Value::U128(a), Value::U128(b)) => u32::try_from(*b).ok().and_then(|b| a.$fn(b)).map(Value::U128),
+                (Value::U256(a), Value::U256(b)) => b.as_u64()
+                    .and_then(|b| u32::try_from(b).ok())
+                    .and_then(|b| a.$fn(b))
+                    .map(Value::U256),
+                _ => return Err(VMError::UnexpectedType)
+            }
+            _ => return Err(VMError::UnexpectedType)
+        };
+
+        value.ok_or(VMError::ShiftOverflow)?
+    }};
+}
+
+// Power of an integer by an u32
+macro_rules! op_pow {
+    ($a: expr, $b: expr) => {{
+        match ($a.as_value(), $b.as_value()) {
+            (ValueCell::Default(a), ValueCell::Default(b)) => {
+                let pow_n = b.as_u32()?;
+                match a {
+                    Value::U8(a) => a.checked_pow(pow_n).map(Value::U8),
+                    Value::U16(a) => a.checked_pow(pow_n).map(Value::U16),
+                    Value::U32(a) => a.checked_pow(pow_n).map(Value::U32),
+                    Value::U64(a) => a.checked_pow(pow_n).map(Value::U64),
+                    Value::U128(a) => a.checked_pow(pow_n).map(Value::U128),
+                    Value::U256(a) => a.checked_pow(pow_n).map(Value::U256),
+                    _ => return Err(VMError::UnexpectedType)
+                }.ok_or(VMError::ArithmeticOverflow)?
+            }
+            _ => return Err(VMError::UnexpectedType)
+        }
+    }};
+}
+
+// A string built by the execution is charged as new memory
+pub fn charge_string(value: &Value, context: &mut Context) -> Result<(), VMError> {
+    if let Value::String(s) = value {
+        context.increase_memory_usage(s.len())?;
+    }
+
+    Ok(())
+}
+
+// The operands consumed by an operator are dropped
+pub fn release_operands(context: &mut Context, left: &Path, right: &Path) -> Result<(), VMError> {
+    context.decrease_value_memory_usage(left)?;
+    context.decrease_value_memory_usage(right)?;
+    Ok(())
+}
+
+// The arguments of a chunk are pushed in the reverse order
+pub fn reverse_arguments(stack: &mut Stack, args: usize) -> Result<(), VMError> {
+    let inner = stack.get_inner();
+    let len = inner.len();
+    if len < args {
+        return Err(VMError::NotEnoughArguments);
+    }
+
+    inner[len - args..len].reverse();
+    Ok(())
+}
+
+// Copy the arguments of a native which can't be borrowed during its call:
+// an inline element borrows its whole array and the instance is mutably borrowed
+pub fn copy_native_arguments(arguments: &mut [Path], instance: Option<&Path>, context: &Context) -> Result<(), VMError> {
+    for argument in arguments.iter_mut().filter(|v| matches!(v, Path::Element(..))) {
+        let value = core::mem::replace(argument, Path::Owned(ValueCell::default())).into_owned();
+        *argument = Path::Owned(value);
+    }
+
+    let instance_ptr = match instance {
+        Some(instance @ Path::Wrapper(_)) => instance.as_ref().as_value() as *const ValueCell,
+        Some(Path::Element(array, _)) => array.as_ptr(),
+        _ => return Ok(())
+    };
+
+    for argument in arguments.iter_mut() {
+        if argument.as_ref().as_value().contains_ptr(instance_ptr, context.max_value_depth())? {
+            let value = core::mem::replace(argument, Path::Owned(ValueCell::default())).into_owned();
+            *argument = Path::Owned(value);
+        }
+    }
+
+    Ok(())
+}
+
+// Bytes given to a precompile, from a blob or an array of u8
+pub fn precompile_input(input: &Path) -> Result<Vec<u8>, VMError> {
+    let input = match input.as_ref().as_value() {
+        ValueCell::TypedArray(array) => array.as_bytes()
+            .map(<[u8]>::to_vec)
+            .ok_or(EnvironmentError::InvalidParameter)?,
+        value => value.as_vec()?
+            .iter()
+            .map(|v| v.borrow().as_u8())
+            .collect::<Result<_, _>>()?
+    };
+
+    Ok(input)
+}
@@ -0,0 +1,84 @@
+use xelis_builder::EnvironmentBuilder;
+use xelis_bytecode::Module;
+use xelis_compiler::Compiler;
+use xelis_lexer::Lexer;
+use xelis_parser::Parser;
+use xelis_vm::Environment;
+use super::*;
+
+// The programs are compiled and executed by the tests of `xelis-aot-compiled`
+const OVERFLOW: &str = include_str!("../../programs/overflow.xel");
+
+fn prepare_module(code: &str) -> (Module, Environment) {
+    let tokens: Vec<_> = Lexer::new(code).collect::<Result<_, _>>().unwrap();
+    let env = EnvironmentBuilder::default();
+    let (program, _) = Parser::with(tokens.into_iter(), &env).parse().unwrap();
+
+    let env = env.build();
+    let module = Compiler::new(&program, &env)
+        .compile()
+        .unwrap();
+
+    (module, env)
+}
+
+// Chunks, blocks and instructions of a generated source, without their statements
+fn outline(source: &str) -> Vec<&str> {
+    source.lines()
+        .skip_while(|line| !line.starts_with("pub fn register"))
+        .filter(|line| line.starts_with("fn chunk_")
+            || line.starts_with("    vm.set_compiled_chunk")
+            || line.strip_prefix("            ").is_some_and(|line| line.starts_with(|c: char| c.is_ascii_digit()) && line.ends_with("=> {"))
+            || line.strip_prefix(INDENT).is_some_and(|line| line.starts_with("// ")))
+        .map(|line| line.trim().split('<').next().unwrap())
+        .collect()
+}
+
+#[test]
+fn test_transpile() {
+    let (module, environment) = prepare_module(OVERFLOW);
+    let source = Transpiler::new(&module, &environment)
+        .transpile()
+        .unwrap();
+
+    assert!(source.starts_with("// Generated by xelis-aot, do not edit"));
+    assert_eq!(outline(&source), [
+        "vm.set_compiled_chunk(module, 0, chunk_0);",
+        "vm.set_compiled_chunk(module, 1, chunk_1);",
+        // add
+        "fn chunk_0",
+        "0 => {",
+        "// MemorySet",
+        "// MemorySet",
+        "// MemoryLoad",
+        "// MemoryLoad",
+        "// Add",
+        "// Return",
+        // main, a block starts at each jump target and after each call
+        "fn chunk_1",
+        "0 => {",
+        "// Constant",
+        "// MemorySet",
+        "// Constant",
+        "// IteratorBegin",
+        "10 => {",
+        "// IteratorNext",
+        "15 => {",
+        "// MemorySet",
+        "// MemoryLoad",
+        "// MemoryLoad",
+        "// Constant",
+        "// InvokeChunk",
+        "32 => {",
+        "// Assign",
+        "// Jump",
+        "38 => {",
+        "// IteratorEnd",
+        "// MemoryLoad",
+        "// Cast",
+        "// Return",
+    ]);
+
+    // The same module gives the same source
+    assert_eq!(Transpiler::new(&module, &environment).transpile().unwrap(), source);
+}
@@ -188,6 +188,25 @@ pub enum OpCodeWithArgs {
     Inc,
     // --
     Dec,
+
+    // +?
+    CheckedAdd,
+    // -?
+    CheckedSub,
+    // *?
+    CheckedMul,
+    // +|
+    SaturatingAdd,
+    // -|
+    SaturatingSub,
+    // *|
+    SaturatingMul,
+    // +%
+    WrappingAdd,
+    // -%
+    WrappingSub,
+    // *%
+    WrappingMul,
 }
 
 impl OpCodeWithArgs {
@@ -260,6 +279,16 @@ impl OpCodeWithArgs {
 
             OpCodeWithArgs::Inc => OpCode::Inc,
             OpCodeWithArgs::Dec => OpCode::Dec,
+
+            OpCodeWithArgs::CheckedAdd => OpCode::CheckedAdd,
+            OpCodeWithArgs::CheckedSub => OpCode::CheckedSub,
+            OpCodeWithArgs::CheckedMul => OpCode::CheckedMul,
+            OpCodeWithArgs::SaturatingAdd => OpCode::SaturatingAdd,
+            OpCodeWithArgs::SaturatingSub => OpCode::SaturatingSub,
+            OpCodeWithArgs::SaturatingMul => OpCode::SaturatingMul,
+            OpCodeWithArgs::WrappingAdd => OpCode::WrappingAdd,
+            OpCodeWithArgs::WrappingSub => OpCode::WrappingSub,
+            OpCodeWithArgs::WrappingMul => OpCode::WrappingMul,
         }
     }
 
@@ -785,6 +814,69 @@ impl OpCodeWithArgs {
 
                 OpCodeWithArgs::Dec
             },
+            "CHECKEDADD" => {
+                if !args.is_empty() {
+                    return Err("Invalid args count");
+                }
+
+                OpCodeWithArgs::CheckedAdd
+            },
+            "CHECKEDSUB" => {
+                if !args.is_empty() {
+                    return Err("Invalid args count");
+                }
+
+                OpCodeWithArgs::CheckedSub
+            },
+            "CHECKEDMUL" => {
+                if !args.is_empty() {
+                    return Err("Invalid args count");
+                }
+
+                OpCodeWithArgs::CheckedMul
+            },
+            "SATURATINGADD" => {
+                if !args.is_empty() {
+                    return Err("Invalid args count");
+                }
+
+                OpCodeWithArgs::SaturatingAdd
+            },
+            "SATURATINGSUB" => {
+                if !args.is_empty() {
+                    return Err("Invalid args count");
+                }
+
+                OpCodeWithArgs::SaturatingSub
+            },
+            "SATURATINGMUL" => {
+                if !args.is_empty() {
+                    return Err("Invalid args count");
+                }
+
+                OpCodeWithArgs::SaturatingMul
+            },
+            "WRAPPINGADD" => {
+                if !args.is_empty() {
+                    return Err("Invalid args count");
+                }
+
+                OpCodeWithArgs::WrappingAdd
+            },
+            "WRAPPINGSUB" => {
+                if !args.is_empty() {
+                    return Err("Invalid args count");
+                }
+
+                OpCodeWithArgs::WrappingSub
+            },
+            "WRAPPINGMUL" => {
+                if !args.is_empty() {
+                    return Err("Invalid args count");
+                }
+
+                OpCodeWithArgs::WrappingMul
+            },
             _ => return Err("Invalid OpCode")
         })
     }
@@ -3,7 +3,8 @@ use xelis_types::{
     IdentifierType,
     StructType,
     Type,
-    Constant
+    Constant,
+    Value
 };
 
 use super::Operator;
@@ -13,10 +14,12 @@ pub enum Expression {
     FunctionCall(Option<Box<Expression>>, IdentifierType, Vec<Expression>), // path, function name, parameters
     ArrayCall(Box<Expression>, Box<Expression>), // expr, index
     ArrayConstructor(Vec<Expression>),
+    TupleConstructor(Vec<Expression>),
     StructConstructor(Vec<Expression>, StructType),
-    RangeConstructor(Box<Expression>, Box<Expression>), // start, end
+    RangeConstructor(Box<Expression>, Box<Expression>, bool), // start, end, end included
     MapConstructor(Vec<(Expression, Expression)>, Type, Type),
     EnumConstructor(Vec<Expression>, EnumValueType),
+    ResultConstructor(Box<Expression>, EnumValueType, Type), // Ok(value) or Err(error), result type
     Variable(IdentifierType), // variable name
     Constant(Constant), // hardcoded value
     Operator(Operator, Box<Expression>, Box<Expression>),
@@ -25,6 +28,8 @@ pub enum Expression {
     IsNot(Box<Expression>), // !expr (where expr is a bool)
     Ternary(Box<Expression>, Box<Expression>, Box<Expression>), // bool expr, if true expr, else expr
     Cast(Box<Expression>, Type), // expr, type
+    Try(Box<Expression>, IdentifierType), // expr?, id of the `is_err` function
+    FunctionReference(IdentifierType), // declared function given to a native by its chunk id
 }
 
 #[derive(Debug, Eq, PartialEq)]
@@ -39,6 +44,26 @@ pub enum Statement {
     Break,
     Continue,
     Variable(DeclarationStatement),
+    TupleDestructuring(Vec<IdentifierType>, Expression), // let (a, b) = tuple
+    Destructuring(Vec<Statement>), // let { a, b } = value, lowered to the declarations of its bindings
+    Switch(DeclarationStatement, Vec<SwitchCase>, Option<Vec<Statement>>), // value stored in a hidden variable, cases, default
+}
+
+// Case of a switch, executed when the value is one of its values
+#[derive(Debug, Eq, PartialEq)]
+pub struct SwitchCase {
+    pub values: Vec<Value>,
+    pub statements: Vec<Statement>,
+    // Continue with the statements of the next case
+    pub fallthrough: bool,
+}
+
+// Location of a statement in the source code
+#[derive(Debug, Clone, Copy, Hash, Eq, PartialEq)]
+pub struct Span {
+    pub line: usize,
+    pub column_start: usize,
+    pub column_end: usize,
 }
 
 #[derive(Debug, Hash, Eq, PartialEq)]
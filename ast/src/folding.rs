@@ -0,0 +1,265 @@
+use log::trace;
+use crate::{Expression, Operator};
+use xelis_types::{Constant, Type, Value};
+
+// Apply a checked operation on two values of the same number type
+// If the operation would overflow or divide by zero, nothing is folded
+// so the VM can report the error at runtime
+macro_rules! checked_op {
+    ($a: expr, $b: expr, $fn: ident) => {{
+        match ($a, $b) {
+            (Value::U8(a), Value::U8(b)) => Value::U8(a.$fn(*b)?),
+            (Value::U16(a), Value::U16(b)) => Value::U16(a.$fn(*b)?),
+            (Value::U32(a), Value::U32(b)) => Value::U32(a.$fn(*b)?),
+            (Value::U64(a), Value::U64(b)) => Value::U64(a.$fn(*b)?),
+            (Value::U128(a), Value::U128(b)) => Value::U128(a.$fn(*b)?),
+            (Value::U256(a), Value::U256(b)) => Value::U256(a.$fn(*b)?),
+            _ => return None
+        }
+    }};
+}
+
+// Apply an operation that can't fail, like `saturating_add`, on two values of the same number type
+macro_rules! method_op {
+    ($a: expr, $b: expr, $fn: ident) => {{
+        match ($a, $b) {
+            (Value::U8(a), Value::U8(b)) => Value::U8(a.$fn(*b)),
+            (Value::U16(a), Value::U16(b)) => Value::U16(a.$fn(*b)),
+            (Value::U32(a), Value::U32(b)) => Value::U32(a.$fn(*b)),
+            (Value::U64(a), Value::U64(b)) => Value::U64(a.$fn(*b)),
+            (Value::U128(a), Value::U128(b)) => Value::U128(a.$fn(*b)),
+            (Value::U256(a), Value::U256(b)) => Value::U256(a.$fn(*b)),
+            _ => return None
+        }
+    }};
+}
+
+// Apply a shift operation on two values of the same number type
+// Shifting by more than the bits available is not folded
+macro_rules! checked_shift {
+    ($a: expr, $b: expr, $fn: ident) => {{
+        match ($a, $b) {
+            (Value::U8(a), Value::U8(b)) => Value::U8(a.$fn(*b as u32)?),
+            (Value::U16(a), Value::U16(b)) => Value::U16(a.$fn(*b as u32)?),
+            (Value::U32(a), Value::U32(b)) => Value::U32(a.$fn(*b)?),
+            (Value::U64(a), Value::U64(b)) => Value::U64(a.$fn(u32::try_from(*b).ok()?)?),
+            (Value::U128(a), Value::U128(b)) => Value::U128(a.$fn(u32::try_from(*b).ok()?)?),
+            (Value::U256(a), Value::U256(b)) => Value::U256(a.$fn(u32::try_from(b.as_u64()?).ok()?)?),
+            _ => return None
+        }
+    }};
+}
+
+// Apply a bitwise operation on two values of the same number type
+macro_rules! bitwise_op {
+    ($a: expr, $b: expr, $op: tt) => {{
+        match ($a, $b) {
+            (Value::U8(a), Value::U8(b)) => Value::U8(a $op b),
+            (Value::U16(a), Value::U16(b)) => Value::U16(a $op b),
+            (Value::U32(a), Value::U32(b)) => Value::U32(a $op b),
+            (Value::U64(a), Value::U64(b)) => Value::U64(a $op b),
+            (Value::U128(a), Value::U128(b)) => Value::U128(a $op b),
+            (Value::U256(a), Value::U256(b)) => Value::U256(*a $op *b),
+            _ => return None
+        }
+    }};
+}
+
+// Compare two values of the same type
+macro_rules! compare_op {
+    ($a: expr, $b: expr, $op: tt) => {{
+        match ($a, $b) {
+            (Value::Boolean(a), Value::Boolean(b)) => Value::Boolean(a $op b),
+            (Value::U8(a), Value::U8(b)) => Value::Boolean(a $op b),
+            (Value::U16(a), Value::U16(b)) => Value::Boolean(a $op b),
+            (Value::U32(a), Value::U32(b)) => Value::Boolean(a $op b),
+            (Value::U64(a), Value::U64(b)) => Value::Boolean(a $op b),
+            (Value::U128(a), Value::U128(b)) => Value::Boolean(a $op b),
+            (Value::U256(a), Value::U256(b)) => Value::Boolean(a $op b),
+            (Value::String(a), Value::String(b)) => Value::Boolean(a $op b),
+            _ => return None
+        }
+    }};
+}
+
+// Concatenate two values as a string
+// Same behavior as the VM: at least one side must be a string
+fn concat(left: &Value, right: &Value) -> Option<Value> {
+    if !left.is_string() && !right.is_string() {
+        return None
+    }
+
+    let left = left.as_string_formatted().ok()?;
+    let right = right.as_string_formatted().ok()?;
+
+    // Verify the final len is less than u32::MAX
+    (left.len() as u32).checked_add(right.len() as u32)?;
+
+    Some(Value::String(format!("{}{}", left, right)))
+}
+
+// Execute the operator on two constant values
+// Returns None if the operation can't be done at compile time
+pub fn execute_operator(op: &Operator, left: &Value, right: &Value) -> Option<Value> {
+    Some(match op {
+        Operator::Add => match concat(left, right) {
+            Some(v) => v,
+            None => checked_op!(left, right, checked_add)
+        },
+        Operator::Sub => checked_op!(left, right, checked_sub),
+        Operator::Mul => checked_op!(left, right, checked_mul),
+        Operator::Div => checked_op!(left, right, checked_div),
+        Operator::Mod => checked_op!(left, right, checked_rem),
+        Operator::Pow => {
+            let pow_n = right.as_u32().ok()?;
+            match left {
+                Value::U8(v) => Value::U8(v.checked_pow(pow_n)?),
+                Value::U16(v) => Value::U16(v.checked_pow(pow_n)?),
+                Value::U32(v) => Value::U32(v.checked_pow(pow_n)?),
+                Value::U64(v) => Value::U64(v.checked_pow(pow_n)?),
+                Value::U128(v) => Value::U128(v.checked_pow(pow_n)?),
+                _ => return None
+            }
+        },
+
+        Operator::BitwiseXor => bitwise_op!(left, right, ^),
+        Operator::BitwiseAnd => bitwise_op!(left, right, &),
+        Operator::BitwiseOr => bitwise_op!(left, right, |),
+        Operator::BitwiseShl => checked_shift!(left, right, checked_shl),
+        Operator::BitwiseShr => checked_shift!(left, right, checked_shr),
+
+        Operator::SaturatingAdd => method_op!(left, right, saturating_add),
+        Operator::SaturatingSub => method_op!(left, right, saturating_sub),
+        Operator::SaturatingMul => method_op!(left, right, saturating_mul),
+        Operator::WrappingAdd => method_op!(left, right, wrapping_add),
+        Operator::WrappingSub => method_op!(left, right, wrapping_sub),
+        Operator::WrappingMul => method_op!(left, right, wrapping_mul),
+        // The result is an optional, which is not a value
+        Operator::CheckedAdd
+        | Operator::CheckedSub
+        | Operator::CheckedMul => return None,
+
+        Operator::Eq => compare_op!(left, right, ==),
+        Operator::Neq => compare_op!(left, right, !=),
+        Operator::Gte => compare_op!(left, right, >=),
+        Operator::Gt => compare_op!(left, right, >),
+        Operator::Lte => compare_op!(left, right, <=),
+        Operator::Lt => compare_op!(left, right, <),
+        Operator::And => Value::Boolean(left.as_bool().ok()? && right.as_bool().ok()?),
+        Operator::Or => Value::Boolean(left.as_bool().ok()? || right.as_bool().ok()?),
+        Operator::Assign(_) => return None,
+    })
+}
+
+// Cast a constant value to a primitive type like the OpCode::Cast would do
+pub fn execute_cast(value: Value, _type: &Type) -> Option<Value> {
+    Some(match _type {
+        Type::U8 => Value::U8(value.cast_to_u8().ok()?),
+        Type::U16 => Value::U16(value.cast_to_u16().ok()?),
+        Type::U32 => Value::U32(value.cast_to_u32().ok()?),
+        Type::U64 => Value::U64(value.cast_to_u64().ok()?),
+        Type::U128 => Value::U128(value.cast_to_u128().ok()?),
+        Type::U256 => Value::U256(value.cast_to_u256().ok()?),
+        Type::String => Value::String(value.cast_to_string().ok()?),
+        _ => return None
+    })
+}
+
+// Try to evaluate an expression at compile time
+// Only primitive values are folded: arithmetic, string concatenation,
+// comparisons, boolean logic and casts
+// Constants declared using `const` are already propagated by the parser
+// as Expression::Constant, so they are folded here too
+pub fn try_fold_expr(expr: &Expression) -> Option<Value> {
+    let value = match expr {
+        Expression::Constant(Constant::Default(v)) => v.clone(),
+        Expression::SubExpression(expr) => try_fold_expr(expr)?,
+        Expression::IsNot(expr) => try_fold_expr(expr)?.not().ok()?,
+        Expression::Cast(expr, _type) => execute_cast(try_fold_expr(expr)?, _type)?,
+        Expression::Operator(op, left, right) => {
+            let left = try_fold_expr(left)?;
+            // Short-circuit: the right side would never be executed
+            match (op, &left) {
+                (Operator::And, Value::Boolean(false)) => return Some(left),
+                (Operator::Or, Value::Boolean(true)) => return Some(left),
+                _ => {}
+            };
+
+            let right = try_fold_expr(right)?;
+            execute_operator(op, &left, &right)?
+        },
+        Expression::Ternary(condition, valid, invalid) => {
+            if try_fold_expr(condition)?.as_bool().ok()? {
+                try_fold_expr(valid)?
+            } else {
+                try_fold_expr(invalid)?
+            }
+        },
+        _ => return None
+    };
+
+    trace!("Folded expression {:?} into {:?}", expr, value);
+    Some(value)
+}
+
+#[cfg(test)]
+mod tests {
+    use xelis_types::U256;
+    use super::*;
+
+    fn constant(value: Value) -> Box<Expression> {
+        Box::new(Expression::Constant(value.into()))
+    }
+
+    #[test]
+    fn test_fold_arithmetic() {
+        // 2 + 3 * 4
+        let expr = Expression::Operator(
+            Operator::Add,
+            constant(Value::U64(2)),
+            Box::new(Expression::Operator(Operator::Mul, constant(Value::U64(3)), constant(Value::U64(4))))
+        );
+        assert_eq!(try_fold_expr(&expr), Some(Value::U64(14)));
+    }
+
+    #[test]
+    fn test_fold_string_concat() {
+        let expr = Expression::Operator(Operator::Add, constant(Value::String("a".to_owned())), constant(Value::U8(1)));
+        assert_eq!(try_fold_expr(&expr), Some(Value::String("a1".to_owned())));
+    }
+
+    #[test]
+    fn test_fold_boolean_logic() {
+        let expr = Expression::IsNot(Box::new(Expression::Operator(Operator::Or, constant(Value::Boolean(false)), constant(Value::Boolean(true)))));
+        assert_eq!(try_fold_expr(&expr), Some(Value::Boolean(false)));
+
+        // Right side is not constant but never executed
+        let expr = Expression::Operator(Operator::And, constant(Value::Boolean(false)), Box::new(Expression::Variable(0)));
+        assert_eq!(try_fold_expr(&expr), Some(Value::Boolean(false)));
+    }
+
+    #[test]
+    fn test_fold_bitwise() {
+        let expr = Expression::IsNot(constant(Value::U8(12)));
+        assert_eq!(try_fold_expr(&expr), Some(Value::U8(243)));
+
+        let expr = Expression::Operator(Operator::BitwiseShl, constant(Value::U256(1u64.into())), constant(Value::U256(255u64.into())));
+        assert_eq!(try_fold_expr(&expr), Some(Value::U256(U256::ONE << 255u32)));
+
+        // Shifting by the number of bits is reported by the VM
+        let expr = Expression::Operator(Operator::BitwiseShl, constant(Value::U256(1u64.into())), constant(Value::U256(256u64.into())));
+        assert_eq!(try_fold_expr(&expr), None);
+    }
+
+    #[test]
+    fn test_no_fold_on_runtime_error() {
+        let expr = Expression::Operator(Operator::Div, constant(Value::U64(1)), constant(Value::U64(0)));
+        assert_eq!(try_fold_expr(&expr), None);
+
+        let expr = Expression::Operator(Operator::Add, constant(Value::U8(255)), constant(Value::U8(1)));
+        assert_eq!(try_fold_expr(&expr), None);
+
+        let expr = Expression::Operator(Operator::Add, constant(Value::U64(1)), Box::new(Expression::Variable(0)));
+        assert_eq!(try_fold_expr(&expr), None);
+    }
+}
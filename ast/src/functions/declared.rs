@@ -1,13 +1,23 @@
 use xelis_types::{Type, IdentifierType};
-use crate::Statement;
+use crate::{Expression, Span, Statement};
 use super::Parameter;
 
-#[derive(Debug, PartialEq, Eq)]
+#[derive(Debug)]
 pub struct DeclaredFunction {
     for_type: Option<Type>,
     instance_name: Option<IdentifierType>,
     parameters: Vec<Parameter>,
     statements: Vec<Statement>,
+    // Spans of the statements in a depth-first order
+    statements_spans: Vec<Span>,
+    // Name of the function, only used for debug info
+    name: Option<String>,
+    // Names of the parameters, exposed in the signature of the public functions
+    parameters_names: Vec<String>,
+    // Public functions are exported by name in the module
+    public: bool,
+    // Checks of the `#[invariant(...)]` attributes, as calls to an assertion
+    invariants: Vec<Expression>,
     return_type: Option<Type>,
     variables_count: u16,
 }
@@ -19,6 +29,11 @@ impl DeclaredFunction {
             instance_name,
             parameters,
             statements,
+            statements_spans: Vec::new(),
+            name: None,
+            parameters_names: Vec::new(),
+            public: false,
+            invariants: Vec::new(),
             return_type,
             variables_count
         }
@@ -36,6 +51,15 @@ impl DeclaredFunction {
         &self.parameters
     }
 
+    // Get the names of the parameters, in the same order
+    pub fn get_parameters_names(&self) -> &[String] {
+        &self.parameters_names
+    }
+
+    pub fn set_parameters_names(&mut self, names: Vec<String>) {
+        self.parameters_names = names;
+    }
+
     pub fn get_instance_name(&self) -> Option<&IdentifierType> {
         self.instance_name.as_ref()
     }
@@ -52,15 +76,71 @@ impl DeclaredFunction {
         self.statements = statements;
     }
 
+    pub fn get_statements_spans(&self) -> &[Span] {
+        &self.statements_spans
+    }
+
+    pub fn set_statements_spans(&mut self, spans: Vec<Span>) {
+        self.statements_spans = spans;
+    }
+
+    pub fn get_name(&self) -> Option<&str> {
+        self.name.as_deref()
+    }
+
+    pub fn set_name(&mut self, name: String) {
+        self.name = Some(name);
+    }
+
     pub fn set_max_variables_count(&mut self, variables_count: u16) {
         self.variables_count = variables_count;
     }
+
+    pub fn is_public(&self) -> bool {
+        self.public
+    }
+
+    pub fn set_public(&mut self, public: bool) {
+        self.public = public;
+    }
+
+    pub fn get_invariants(&self) -> &[Expression] {
+        &self.invariants
+    }
+
+    pub fn set_invariants(&mut self, invariants: Vec<Expression>) {
+        self.invariants = invariants;
+    }
+}
+
+// Spans and name are only debug information and are ignored
+impl PartialEq for DeclaredFunction {
+    fn eq(&self, other: &Self) -> bool {
+        self.for_type == other.for_type
+            && self.instance_name == other.instance_name
+            && self.public == other.public
+            && self.parameters == other.parameters
+            && self.statements == other.statements
+            && self.invariants == other.invariants
+            && self.return_type == other.return_type
+            && self.variables_count == other.variables_count
+    }
 }
 
-#[derive(Debug, PartialEq, Eq)]
+impl Eq for DeclaredFunction {}
+
+#[derive(Debug)]
 pub struct EntryFunction {
     parameters: Vec<Parameter>,
     statements: Vec<Statement>,
+    // Spans of the statements in a depth-first order
+    statements_spans: Vec<Span>,
+    // Name of the function, only used for debug info
+    name: Option<String>,
+    // Names of the parameters, exposed in the ABI of the module
+    parameters_names: Vec<String>,
+    // Checks of the `#[invariant(...)]` attributes, as calls to an assertion
+    invariants: Vec<Expression>,
     variables_count: u16,
 }
 
@@ -70,6 +150,10 @@ impl EntryFunction {
         EntryFunction {
             parameters,
             statements,
+            statements_spans: Vec::new(),
+            name: None,
+            parameters_names: Vec::new(),
+            invariants: Vec::new(),
             variables_count
         }
     }
@@ -79,6 +163,15 @@ impl EntryFunction {
         &self.parameters
     }
 
+    // Get the names of the parameters, in the same order
+    pub fn get_parameters_names(&self) -> &[String] {
+        &self.parameters_names
+    }
+
+    pub fn set_parameters_names(&mut self, names: Vec<String>) {
+        self.parameters_names = names;
+    }
+
     // Get the statements of the function
     pub fn get_statements(&self) -> &Vec<Statement> {
         &self.statements
@@ -93,7 +186,43 @@ impl EntryFunction {
         self.statements = statements;
     }
 
+    pub fn get_statements_spans(&self) -> &[Span] {
+        &self.statements_spans
+    }
+
+    pub fn set_statements_spans(&mut self, spans: Vec<Span>) {
+        self.statements_spans = spans;
+    }
+
+    pub fn get_name(&self) -> Option<&str> {
+        self.name.as_deref()
+    }
+
+    pub fn set_name(&mut self, name: String) {
+        self.name = Some(name);
+    }
+
     pub fn set_max_variables_count(&mut self, variables_count: u16) {
         self.variables_count = variables_count;
     }
-}
\ No newline at end of file
+
+    pub fn get_invariants(&self) -> &[Expression] {
+        &self.invariants
+    }
+
+    pub fn set_invariants(&mut self, invariants: Vec<Expression>) {
+        self.invariants = invariants;
+    }
+}
+
+// Spans and names don't change the code and are ignored
+impl PartialEq for EntryFunction {
+    fn eq(&self, other: &Self) -> bool {
+        self.parameters == other.parameters
+            && self.statements == other.statements
+            && self.invariants == other.invariants
+            && self.variables_count == other.variables_count
+    }
+}
+
+impl Eq for EntryFunction {}
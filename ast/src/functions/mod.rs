@@ -1,7 +1,7 @@
 mod declared;
 
 use xelis_types::{Type, IdentifierType};
-use super::Statement;
+use super::{Expression, Span, Statement};
 
 pub use declared::{DeclaredFunction, EntryFunction};
 
@@ -76,11 +76,14 @@ impl Signature {
 }
 
 // Declared function type by a Program
-// They are separated in two types for better handling
+// They are separated in three types for better handling
 #[derive(Debug, PartialEq, Eq)]
 pub enum FunctionType {
     Declared(DeclaredFunction),
-    Entry(EntryFunction)
+    Entry(EntryFunction),
+    // Function qualified with `test`, without parameters and return type
+    // Only invoked by a test runner
+    Test(DeclaredFunction)
 }
 
 impl FunctionType {
@@ -93,11 +96,17 @@ impl FunctionType {
         }
     }
 
+    // Is this function a test function
+    #[inline(always)]
+    pub fn is_test(&self) -> bool {
+        matches!(self, FunctionType::Test(_))
+    }
+
     // Get the returned type of the function
     #[inline(always)]
     pub fn return_type(&self) -> &Option<Type> {
         match &self {
-            FunctionType::Declared(f) => f.get_return_type(),
+            FunctionType::Declared(f) | FunctionType::Test(f) => f.get_return_type(),
             FunctionType::Entry(_) => &Some(ENTRY_FN_RETURN_TYPE)
         }
     }
@@ -106,7 +115,7 @@ impl FunctionType {
     #[inline(always)]
     pub fn get_instance_name(&self) -> Option<&IdentifierType> {
         match self {
-            FunctionType::Declared(f) => f.get_instance_name(),
+            FunctionType::Declared(f) | FunctionType::Test(f) => f.get_instance_name(),
             _ => None
         }
     }
@@ -115,7 +124,7 @@ impl FunctionType {
     #[inline(always)]
     pub fn get_parameters(&self) -> &Vec<Parameter> {
         match self {
-            FunctionType::Declared(f) => f.get_parameters(),
+            FunctionType::Declared(f) | FunctionType::Test(f) => f.get_parameters(),
             FunctionType::Entry(f) => f.get_parameters()
         }
     }
@@ -124,15 +133,31 @@ impl FunctionType {
     #[inline(always)]
     pub fn get_statements(&self) -> &Vec<Statement> {
         match self {
-            FunctionType::Declared(f) => f.get_statements(),
+            FunctionType::Declared(f) | FunctionType::Test(f) => f.get_statements(),
             FunctionType::Entry(f) => f.get_statements()
         }
     }
 
+    // Get the checks of the invariants, run at the start of the function and before each return
+    pub fn get_invariants(&self) -> &[Expression] {
+        match self {
+            FunctionType::Declared(f) | FunctionType::Test(f) => f.get_invariants(),
+            FunctionType::Entry(f) => f.get_invariants()
+        }
+    }
+
+    // Set the checks of the invariants
+    pub fn set_invariants(&mut self, invariants: Vec<Expression>) {
+        match self {
+            FunctionType::Declared(f) | FunctionType::Test(f) => f.set_invariants(invariants),
+            FunctionType::Entry(f) => f.set_invariants(invariants)
+        }
+    }
+
     // Get the count of variables declared in the function
     pub fn get_variables_count(&self) -> u16 {
         match self {
-            FunctionType::Declared(f) => f.get_variables_count(),
+            FunctionType::Declared(f) | FunctionType::Test(f) => f.get_variables_count(),
             FunctionType::Entry(f) => f.get_variables_count()
         }
     }
@@ -140,15 +165,48 @@ impl FunctionType {
     // Set the statements of the function
     pub fn set_statements(&mut self, statements: Vec<Statement>) {
         match self {
-            FunctionType::Declared(f) => f.set_statements(statements),
+            FunctionType::Declared(f) | FunctionType::Test(f) => f.set_statements(statements),
             FunctionType::Entry(f) => f.set_statements(statements)
         }
     }
 
+    // Get the spans of the statements
+    // They are ordered like a depth-first walk of the statements
+    pub fn get_statements_spans(&self) -> &[Span] {
+        match self {
+            FunctionType::Declared(f) | FunctionType::Test(f) => f.get_statements_spans(),
+            FunctionType::Entry(f) => f.get_statements_spans()
+        }
+    }
+
+    // Set the spans of the statements
+    pub fn set_statements_spans(&mut self, spans: Vec<Span>) {
+        match self {
+            FunctionType::Declared(f) | FunctionType::Test(f) => f.set_statements_spans(spans),
+            FunctionType::Entry(f) => f.set_statements_spans(spans)
+        }
+    }
+
+    // Get the name of the function if it was set
+    pub fn get_name(&self) -> Option<&str> {
+        match self {
+            FunctionType::Declared(f) | FunctionType::Test(f) => f.get_name(),
+            FunctionType::Entry(f) => f.get_name()
+        }
+    }
+
+    // Set the name of the function
+    pub fn set_name(&mut self, name: String) {
+        match self {
+            FunctionType::Declared(f) | FunctionType::Test(f) => f.set_name(name),
+            FunctionType::Entry(f) => f.set_name(name)
+        }
+    }
+
     // Set the count of variables declared in the function
     pub fn set_max_variables_count(&mut self, count: u16) {
         match self {
-            FunctionType::Declared(f) => f.set_max_variables_count(count),
+            FunctionType::Declared(f) | FunctionType::Test(f) => f.set_max_variables_count(count),
             FunctionType::Entry(f) => f.set_max_variables_count(count)
         }
     }
@@ -3,9 +3,11 @@ mod operator;
 mod token;
 mod functions;
 mod program;
+mod folding;
 
 pub use expressions::*;
 pub use operator::Operator;
 pub use token::*;
 pub use functions::*;
-pub use program::Program;
\ No newline at end of file
+pub use program::Program;
+pub use folding::{try_fold_expr, execute_operator, execute_cast};
\ No newline at end of file
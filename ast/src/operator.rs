@@ -24,6 +24,16 @@ pub enum Operator {
     BitwiseShl, // <<
     BitwiseShr, // >>
 
+    CheckedAdd, // +?
+    CheckedSub, // -?
+    CheckedMul, // *?
+    SaturatingAdd, // +|
+    SaturatingSub, // -|
+    SaturatingMul, // *|
+    WrappingAdd, // +%
+    WrappingSub, // -%
+    WrappingMul, // *%
+
     Assign(Option<Box<Operator>>),
 }
 
@@ -52,6 +62,16 @@ impl Operator {
             Token::OperatorBitwiseShl => BitwiseShl,
             Token::OperatorBitwiseShr => BitwiseShr,
 
+            Token::OperatorCheckedPlus => CheckedAdd,
+            Token::OperatorCheckedMinus => CheckedSub,
+            Token::OperatorCheckedMultiply => CheckedMul,
+            Token::OperatorSaturatingPlus => SaturatingAdd,
+            Token::OperatorSaturatingMinus => SaturatingSub,
+            Token::OperatorSaturatingMultiply => SaturatingMul,
+            Token::OperatorWrappingPlus => WrappingAdd,
+            Token::OperatorWrappingMinus => WrappingSub,
+            Token::OperatorWrappingMultiply => WrappingMul,
+
             Token::OperatorAssign => Assign(None),
             Token::OperatorPlusAssign => Assign(Some(Box::new(Add))),
             Token::OperatorMinusAssign => Assign(Some(Box::new(Sub))),
@@ -92,6 +112,16 @@ impl Operator {
             | Operator::BitwiseShl
             | Operator::BitwiseShr
 
+            | Operator::CheckedAdd
+            | Operator::CheckedSub
+            | Operator::CheckedMul
+            | Operator::SaturatingAdd
+            | Operator::SaturatingSub
+            | Operator::SaturatingMul
+            | Operator::WrappingAdd
+            | Operator::WrappingSub
+            | Operator::WrappingMul
+
             | Operator::Gte
             | Operator::Gt
             | Operator::Lte
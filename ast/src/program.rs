@@ -1,5 +1,5 @@
-use indexmap::IndexSet;
-use xelis_types::{EnumType, StructType};
+use indexmap::{IndexMap, IndexSet};
+use xelis_types::{EnumType, StructType, Type};
 use crate::ConstantDeclaration;
 
 use super::FunctionType;
@@ -13,7 +13,9 @@ pub struct Program {
     // All enums declared
     enums: IndexSet<EnumType>,
     // All functions declared
-    functions: Vec<FunctionType>
+    functions: Vec<FunctionType>,
+    // Events emitted with a constant name, with the type of their data
+    events: IndexMap<String, Type>
 }
 
 impl Program {
@@ -23,7 +25,8 @@ impl Program {
             constants: IndexSet::new(),
             structures: IndexSet::new(),
             enums: IndexSet::new(),
-            functions: Vec::new()
+            functions: Vec::new(),
+            events: IndexMap::new()
         }
     }
 
@@ -33,7 +36,8 @@ impl Program {
             constants,
             structures,
             enums,
-            functions
+            functions,
+            events: IndexMap::new()
         }
     }
 
@@ -84,4 +88,21 @@ impl Program {
     pub fn functions(&self) -> &[FunctionType] {
         &self.functions
     }
-}
\ No newline at end of file
+
+    // Add an event emitted by the program
+    // An event emitted with different types of data is described with the `any` type
+    #[inline]
+    pub fn add_event(&mut self, name: String, data: Type) {
+        self.events.entry(name)
+            .and_modify(|t| if *t != data {
+                *t = Type::Any;
+            })
+            .or_insert(data);
+    }
+
+    // Get the events emitted by the program
+    #[inline]
+    pub fn events(&self) -> &IndexMap<String, Type> {
+        &self.events
+    }
+}
@@ -118,6 +118,16 @@ pub enum Token<'a> {
     OperatorBitwiseShl,
     OperatorBitwiseShr,
 
+    OperatorCheckedPlus,
+    OperatorCheckedMinus,
+    OperatorCheckedMultiply,
+    OperatorSaturatingPlus,
+    OperatorSaturatingMinus,
+    OperatorSaturatingMultiply,
+    OperatorWrappingPlus,
+    OperatorWrappingMinus,
+    OperatorWrappingMultiply,
+
     OperatorPlusAssign,
     OperatorMinusAssign,
     OperatorMultiplyAssign,
@@ -138,7 +148,15 @@ pub enum Token<'a> {
     As,
     ReturnType,
     Match,
+    Switch,
+    Case,
+    Default,
+    Fallthrough,
     FatArrow,
+    Impl,
+    Interface,
+    // Starts an attribute of a function: #[name(...)]
+    Hash,
 }
 
 impl Token<'_> {
@@ -176,6 +194,16 @@ impl Token<'_> {
             "<<" => OperatorBitwiseShl,
             ">>" => OperatorBitwiseShr,
 
+            "+?" => OperatorCheckedPlus,
+            "-?" => OperatorCheckedMinus,
+            "*?" => OperatorCheckedMultiply,
+            "+|" => OperatorSaturatingPlus,
+            "-|" => OperatorSaturatingMinus,
+            "*|" => OperatorSaturatingMultiply,
+            "+%" => OperatorWrappingPlus,
+            "-%" => OperatorWrappingMinus,
+            "*%" => OperatorWrappingMultiply,
+
             "+=" => OperatorPlusAssign,
             "-=" => OperatorMinusAssign,
             "*=" => OperatorMultiplyAssign,
@@ -194,6 +222,7 @@ impl Token<'_> {
             "." => Dot,
             "," => Comma,
             ":" => Colon,
+            "#" => Hash,
 
             "bool" => Bool,
             "blob" => Blob,
@@ -209,6 +238,8 @@ impl Token<'_> {
             "const" => Const,
             "entry" => Entry,
             "fn" => Function,
+            "impl" => Impl,
+            "interface" => Interface,
 
             "return" => Return,
             "if" => If,
@@ -230,6 +261,10 @@ impl Token<'_> {
             "as" => As,
             "->" => ReturnType,
             "match" => Match,
+            "switch" => Switch,
+            "case" => Case,
+            "default" => Default,
+            "fallthrough" => Fallthrough,
             "=>" => FatArrow,
 
             e => Number(NumberType::value_of(e)?),
@@ -273,6 +308,16 @@ impl Token<'_> {
             | OperatorBitwiseShl
             | OperatorBitwiseShr
 
+            | OperatorCheckedPlus
+            | OperatorCheckedMinus
+            | OperatorCheckedMultiply
+            | OperatorSaturatingPlus
+            | OperatorSaturatingMinus
+            | OperatorSaturatingMultiply
+            | OperatorWrappingPlus
+            | OperatorWrappingMinus
+            | OperatorWrappingMultiply
+
             | OperatorPlusAssign
             | OperatorMinusAssign
             | OperatorMultiplyAssign
@@ -1,10 +1,13 @@
 pub mod xstd;
+mod namespace;
 
-use std::{borrow::Cow, collections::HashMap};
+use std::{borrow::Cow, collections::{hash_map::DefaultHasher, HashMap, HashSet}, hash::{Hash, Hasher}, sync::Arc};
 use xelis_ast::Signature;
-use xelis_types::{Type, Constant};
-use xelis_environment::{Environment, NativeFunction, OnCallFn};
-use crate::{EnumManager, EnumVariantBuilder, FunctionMapper, StructManager};
+use xelis_types::{Type, Constant, EnumType, IdentifierType, OpaqueType, StructType, ENVIRONMENT_ENUM_ID_START};
+use xelis_environment::{Context, Environment, FnInstance, FnParams, FnReturnType, NativeFunction, Precompile};
+use crate::{Builder, BuilderError, EnumManager, EnumVariantBuilder, FunctionMapper, StructManager};
+
+pub use namespace::NamespaceBuilder;
 
 // EnvironmentBuilder is used to create an environment
 // it is used to register all the native functions and structures
@@ -13,7 +16,13 @@ pub struct EnvironmentBuilder<'a> {
     functions_mapper: FunctionMapper<'a>,
     struct_manager: StructManager<'a>,
     enum_manager: EnumManager<'a>,
+    // Names of the opaque types, their id is their index
+    opaques: Vec<&'a str>,
     constants: HashMap<Type, HashMap<&'a str, Constant>>,
+    // Full paths of the registered namespaces
+    namespaces: HashSet<String>,
+    // Function giving the values iterated by a foreach on a type
+    iterables: HashMap<Type, IdentifierType>,
     env: Environment
 }
 
@@ -23,18 +32,69 @@ impl<'a> EnvironmentBuilder<'a> {
         Self {
             functions_mapper: FunctionMapper::new(),
             struct_manager: StructManager::new(),
-            enum_manager: EnumManager::new(),
+            enum_manager: EnumManager::starting_at(ENVIRONMENT_ENUM_ID_START),
+            opaques: Vec::new(),
             constants: HashMap::new(),
+            namespaces: HashSet::new(),
+            iterables: HashMap::new(),
             env: Environment::new(),
         }
     }
 
     // Register a native function
+    // Returns the function registered to configure it further (like a dynamic cost)
     // Panic if the function signature is already registered
-    pub fn register_native_function(&mut self, name: &'a str, for_type: Option<Type>, parameters: Vec<(&'a str, Type)>, on_call: OnCallFn, cost: u64, return_type: Option<Type>) {
+    pub fn register_native_function(&mut self, name: &'a str, for_type: Option<Type>, parameters: Vec<(&'a str, Type)>, on_call: impl Fn(FnInstance, FnParams, &mut Context) -> FnReturnType + Send + Sync + 'static, cost: u64, return_type: Option<Type>) -> &mut NativeFunction {
         let params: Vec<_> = parameters.iter().map(|(_, t)| t.clone()).collect();
-        let _ = self.functions_mapper.register(name, for_type.clone(), parameters).unwrap();
+        let id = self.functions_mapper.register(name, for_type.clone(), parameters).unwrap();
         self.env.add_function(NativeFunction::new(for_type, params, on_call, cost, return_type));
+        self.env.get_function_by_id_mut(id as usize).unwrap()
+    }
+
+    // Register a variadic native function
+    // Any number of parameters of any type can be given after the declared ones
+    // Panic if the function signature is already registered
+    pub fn register_variadic_function(&mut self, name: &'a str, for_type: Option<Type>, parameters: Vec<(&'a str, Type)>, on_call: impl Fn(FnInstance, FnParams, &mut Context) -> FnReturnType + Send + Sync + 'static, cost: u64, return_type: Option<Type>) -> &mut NativeFunction {
+        let params: Vec<_> = parameters.iter().map(|(_, t)| t.clone()).collect();
+        let id = self.functions_mapper.register_variadic(name, for_type.clone(), parameters).unwrap();
+        let mut function = NativeFunction::new(for_type, params, on_call, cost, return_type);
+        function.set_variadic(true);
+        self.env.add_function(function);
+        self.env.get_function_by_id_mut(id as usize).unwrap()
+    }
+
+    // Register a static native function
+    // It is called on the type directly (`Type::name()`) and has no instance
+    // Panic if the function signature is already registered
+    pub fn register_static_function(&mut self, name: &'a str, for_type: Type, parameters: Vec<(&'a str, Type)>, on_call: impl Fn(FnInstance, FnParams, &mut Context) -> FnReturnType + Send + Sync + 'static, cost: u64, return_type: Option<Type>) -> &mut NativeFunction {
+        let params: Vec<_> = parameters.iter().map(|(_, t)| t.clone()).collect();
+        let id = self.functions_mapper.register(name, Some(for_type), parameters).unwrap();
+        self.env.add_function(NativeFunction::new(None, params, on_call, cost, return_type));
+        self.env.get_function_by_id_mut(id as usize).unwrap()
+    }
+
+    // Register native functions in a namespace
+    // They are called from the scripts as `namespace::name(...)`
+    // A namespace can be opened again to register more functions
+    // Panic if the name is already used by a structure or an enum
+    pub fn namespace(&mut self, name: &str, f: impl FnOnce(&mut NamespaceBuilder<'_, 'a>)) {
+        if self.is_type_name(name) {
+            panic!("{}: {}", BuilderError::NameAlreadyUsed, name);
+        }
+
+        self.namespaces.insert(name.to_owned());
+        f(&mut NamespaceBuilder::new(self, name.to_owned()));
+    }
+
+    // Check if a namespace is registered using its full path
+    pub fn has_namespace(&self, path: &str) -> bool {
+        self.namespaces.contains(path)
+    }
+
+    fn is_type_name(&self, name: &str) -> bool {
+        self.struct_manager.get_by_name(name).is_ok()
+            || self.enum_manager.get_by_name(name).is_ok()
+            || self.get_opaque_by_name(name).is_some()
     }
 
     // Get a function by its signature
@@ -46,17 +106,73 @@ impl<'a> EnvironmentBuilder<'a> {
     }
 
     // Register a structure in the environment
-    // Panic if the structure name is already used
-    pub fn register_structure(&mut self, name: &'a str, fields: Vec<(&'a str, Type)>) {
+    // Returns the structure type to register functions on it
+    // Panic if the structure name is already used by a type or a namespace
+    pub fn register_structure(&mut self, name: &'a str, fields: Vec<(&'a str, Type)>) -> StructType {
+        if self.has_namespace(name) || self.get_opaque_by_name(name).is_some() {
+            panic!("{}: {}", BuilderError::NameAlreadyUsed, name);
+        }
+
         let _type = self.struct_manager.build(Cow::Borrowed(name), fields).unwrap();
-        self.env.add_structure(_type);
+        self.env.add_structure(_type.clone());
+        _type
     }
 
     // Register an enum in the environment
-    // Panic if the enum name is already used
-    pub fn register_enum(&mut self, name: &'a str, variants: Vec<(&'a str, EnumVariantBuilder<'a>)>) {
+    // Returns the enum type to build its values
+    // Panic if the enum name is already used by a type or a namespace
+    pub fn register_enum(&mut self, name: &'a str, variants: Vec<(&'a str, EnumVariantBuilder<'a>)>) -> EnumType {
+        if self.has_namespace(name) || self.get_opaque_by_name(name).is_some() {
+            panic!("{}: {}", BuilderError::NameAlreadyUsed, name);
+        }
+
         let _type = self.enum_manager.build(Cow::Borrowed(name), variants).unwrap();
-        self.env.add_enum(_type);
+        self.env.add_enum(_type.clone());
+        _type
+    }
+
+    // Register an opaque type in the environment
+    // Its values can only be built and read by the natives registered on it
+    // Panic if the name is already used by a type or a namespace
+    pub fn register_opaque(&mut self, name: &'a str) -> OpaqueType {
+        if self.has_namespace(name) || self.is_type_name(name) {
+            panic!("{}: {}", BuilderError::NameAlreadyUsed, name);
+        }
+
+        let _type = OpaqueType::new(self.opaques.len() as IdentifierType);
+        self.opaques.push(name);
+        _type
+    }
+
+    // Get an opaque type by its name
+    pub fn get_opaque_by_name(&self, name: &str) -> Option<OpaqueType> {
+        self.opaques.iter()
+            .position(|n| *n == name)
+            .map(|id| OpaqueType::new(id as IdentifierType))
+    }
+
+    // Get the name of an opaque type
+    pub fn get_opaque_name(&self, _type: &OpaqueType) -> Option<&'a str> {
+        self.opaques.get(_type.id() as usize).copied()
+    }
+
+    // Make a type iterable by a foreach, like a collection struct
+    // The function is called on the value without parameters and returns the array of the values to iterate
+    // Panic if the function is not registered on the type or doesn't return an array
+    pub fn register_iterable(&mut self, _type: Type, function: &str) {
+        let id = self.functions_mapper.get(&Signature::new(function.to_owned(), Some(_type.clone()), Vec::new()))
+            .unwrap_or_else(|e| panic!("{}: {}", e, function));
+
+        if !matches!(self.env.get_functions()[id as usize].return_type(), Some(Type::Array(_))) {
+            panic!("iterable function {} must return an array", function);
+        }
+
+        self.iterables.insert(_type, id);
+    }
+
+    // Get the function giving the values iterated on a type
+    pub fn get_iterable_function(&self, _type: &Type) -> Option<IdentifierType> {
+        self.iterables.get(_type).copied()
     }
 
     // Register a constant in the environment
@@ -66,6 +182,15 @@ impl<'a> EnvironmentBuilder<'a> {
         constants.insert(name, value.clone());
     }
 
+    // Register a precompile called by its id from the bytecode
+    // Panic if the id is already used
+    pub fn register_precompile(&mut self, precompile: impl Precompile + 'static) {
+        let id = precompile.id();
+        if self.env.add_precompile(Arc::new(precompile)).is_some() {
+            panic!("precompile {} already registered", id);
+        }
+    }
+
     // Get a constant by name
     pub fn get_constant_by_name(&self, _type: &Type, name: &str) -> Option<&Constant> {
         self.constants.get(_type).and_then(|v| v.get(name))
@@ -101,6 +226,81 @@ impl<'a> EnvironmentBuilder<'a> {
         self.env
     }
 
+    // Fingerprint of everything visible by a program: the functions, types, constants and namespaces
+    // Two builders with the same fingerprint parse and compile a source the same way
+    // It is only stable for a build of the crate and must not be persisted
+    pub fn fingerprint(&self) -> u64 {
+        let mut hasher = DefaultHasher::new();
+
+        let mut functions = self.functions_mapper.get_functions();
+        functions.sort_by_key(|(id, _, _)| *id);
+        for (id, signature, function) in functions {
+            id.hash(&mut hasher);
+            signature.hash(&mut hasher);
+            function.parameters.hash(&mut hasher);
+            function.variadic.hash(&mut hasher);
+        }
+
+        for function in self.env.get_functions() {
+            function.return_type().hash(&mut hasher);
+            // The calls of the debug only functions may be removed by the compiler
+            function.is_debug_only().hash(&mut hasher);
+        }
+
+        for s in self.env.get_structures() {
+            s.id().hash(&mut hasher);
+            s.fields().hash(&mut hasher);
+        }
+
+        for e in self.env.get_enums() {
+            e.id().hash(&mut hasher);
+            for variant in e.variants() {
+                variant.fields().hash(&mut hasher);
+            }
+        }
+
+        // The names are in maps, sort them to not depend on their order
+        let mut names = self.struct_manager.names();
+        names.sort_unstable();
+        for name in names {
+            name.hash(&mut hasher);
+            if let Ok(builder) = self.struct_manager.get_by_name(name) {
+                builder.names().hash(&mut hasher);
+            }
+        }
+
+        let mut names = self.enum_manager.names();
+        names.sort_unstable();
+        for name in names {
+            name.hash(&mut hasher);
+            if let Ok(builder) = self.enum_manager.get_by_name(name) {
+                builder.names().hash(&mut hasher);
+            }
+        }
+
+        self.opaques.hash(&mut hasher);
+
+        let mut constants: Vec<_> = self.constants.iter()
+            .flat_map(|(_type, values)| values.iter().map(move |(name, value)| {
+                let mut hasher = DefaultHasher::new();
+                (_type, name, value).hash(&mut hasher);
+                hasher.finish()
+            }))
+            .collect();
+        constants.sort_unstable();
+        constants.hash(&mut hasher);
+
+        let mut namespaces: Vec<_> = self.namespaces.iter().collect();
+        namespaces.sort_unstable();
+        namespaces.hash(&mut hasher);
+
+        let mut iterables: Vec<_> = self.iterables.values().collect();
+        iterables.sort_unstable();
+        iterables.hash(&mut hasher);
+
+        hasher.finish()
+    }
+
     // Finalize the environment builder and return the environment and the function mapper
     pub fn finalize(self) -> (Environment, FunctionMapper<'a>) {
         (self.env, self.functions_mapper)
@@ -139,4 +339,63 @@ mod tests {
         let env = builder.build();
         assert_eq!(env.get_enums().len(), 1);
     }
-}
\ No newline at end of file
+
+    #[test]
+    pub fn test_namespace() {
+        let mut builder = EnvironmentBuilder::new();
+        builder.namespace("math", |ns| {
+            ns.register_native_function("zero", vec![], |_, _, _| Ok(None), 1, None);
+            ns.namespace("vec", |ns| {
+                ns.register_native_function("zero", vec![], |_, _, _| Ok(None), 1, None);
+            });
+        });
+
+        assert!(builder.has_namespace("math"));
+        assert!(builder.has_namespace("math::vec"));
+        assert!(!builder.has_namespace("vec"));
+
+        let mapper = builder.get_functions_mapper();
+        assert!(mapper.get(&Signature::new("math::zero".to_owned(), None, vec![])).is_ok());
+        assert!(mapper.get(&Signature::new("math::vec::zero".to_owned(), None, vec![])).is_ok());
+        assert!(mapper.get(&Signature::new("zero".to_owned(), None, vec![])).is_err());
+    }
+
+    #[test]
+    pub fn test_fingerprint() {
+        let builder = EnvironmentBuilder::default();
+        assert_eq!(builder.fingerprint(), EnvironmentBuilder::default().fingerprint());
+
+        let mut other = EnvironmentBuilder::default();
+        other.register_native_function("zero", None, vec![], |_, _, _| Ok(None), 1, Some(Type::U64));
+        assert_ne!(other.fingerprint(), builder.fingerprint());
+
+        let mut other = EnvironmentBuilder::default();
+        other.register_constant(Type::U64, "ZERO", Constant::Default(xelis_types::Value::U64(0)));
+        assert_ne!(other.fingerprint(), builder.fingerprint());
+
+        // The costs don't change the programs
+        let mut other = EnvironmentBuilder::default();
+        other.get_mut_function("println", None, vec![Type::Any]).set_cost(1000);
+        assert_eq!(other.fingerprint(), builder.fingerprint());
+    }
+
+    #[test]
+    #[should_panic]
+    pub fn test_namespace_duplicated_function() {
+        let mut builder = EnvironmentBuilder::new();
+        builder.namespace("math", |ns| {
+            ns.register_native_function("zero", vec![], |_, _, _| Ok(None), 1, None);
+        });
+        builder.namespace("math", |ns| {
+            ns.register_native_function("zero", vec![], |_, _, _| Ok(None), 1, None);
+        });
+    }
+
+    #[test]
+    #[should_panic]
+    pub fn test_namespace_type_collision() {
+        let mut builder = EnvironmentBuilder::new();
+        builder.register_structure("Math", vec![]);
+        builder.namespace("Math", |_| {});
+    }
+}
@@ -0,0 +1,54 @@
+use xelis_types::Type;
+use xelis_environment::{Context, FnInstance, FnParams, FnReturnType, NativeFunction};
+use super::EnvironmentBuilder;
+
+// NamespaceBuilder is used to register native functions in a namespace
+// They are called from the scripts using their path: `namespace::name(...)`
+// Namespaced functions are free functions, they can't be called on a type
+pub struct NamespaceBuilder<'b, 'a> {
+    builder: &'b mut EnvironmentBuilder<'a>,
+    path: String,
+}
+
+impl<'b, 'a> NamespaceBuilder<'b, 'a> {
+    pub(super) fn new(builder: &'b mut EnvironmentBuilder<'a>, path: String) -> Self {
+        Self {
+            builder,
+            path
+        }
+    }
+
+    // Full path of the namespace, like `math` or `math::vec`
+    pub fn path(&self) -> &str {
+        &self.path
+    }
+
+    // Register a native function in the namespace
+    // Panic if the function signature is already registered in this namespace
+    pub fn register_native_function(&mut self, name: &'a str, parameters: Vec<(&'a str, Type)>, on_call: impl Fn(FnInstance, FnParams, &mut Context) -> FnReturnType + Send + Sync + 'static, cost: u64, return_type: Option<Type>) -> &mut NativeFunction {
+        self.register_function(name, parameters, on_call, cost, return_type, false)
+    }
+
+    // Register a variadic native function in the namespace
+    // Any number of parameters of any type can be given after the declared ones
+    // Panic if the function signature is already registered in this namespace
+    pub fn register_variadic_function(&mut self, name: &'a str, parameters: Vec<(&'a str, Type)>, on_call: impl Fn(FnInstance, FnParams, &mut Context) -> FnReturnType + Send + Sync + 'static, cost: u64, return_type: Option<Type>) -> &mut NativeFunction {
+        self.register_function(name, parameters, on_call, cost, return_type, true)
+    }
+
+    // Register a nested namespace: `namespace::name::function(...)`
+    pub fn namespace(&mut self, name: &str, f: impl FnOnce(&mut NamespaceBuilder<'_, 'a>)) {
+        let path = format!("{}::{}", self.path, name);
+        self.builder.namespaces.insert(path.clone());
+        f(&mut NamespaceBuilder::new(self.builder, path));
+    }
+
+    fn register_function(&mut self, name: &'a str, parameters: Vec<(&'a str, Type)>, on_call: impl Fn(FnInstance, FnParams, &mut Context) -> FnReturnType + Send + Sync + 'static, cost: u64, return_type: Option<Type>, variadic: bool) -> &mut NativeFunction {
+        let params: Vec<_> = parameters.iter().map(|(_, t)| t.clone()).collect();
+        let id = self.builder.functions_mapper.register_in_namespace(&self.path, name, parameters, variadic).unwrap();
+        let mut function = NativeFunction::new(None, params, on_call, cost, return_type);
+        function.set_variadic(variadic);
+        self.builder.env.add_function(function);
+        self.builder.env.get_function_by_id_mut(id as usize).unwrap()
+    }
+}
@@ -1,5 +1,5 @@
 use xelis_types::{Type, Value, ValueCell};
-use xelis_environment::{Context, EnvironmentError, FnInstance, FnParams, FnReturnType};
+use xelis_environment::{Context, EnvironmentError, FnInstance, FnParams, FnReturnType, VALUE_MEMORY_USAGE};
 use super::EnvironmentBuilder;
 
 pub fn register(env: &mut EnvironmentBuilder) {
@@ -16,23 +16,27 @@ pub fn register(env: &mut EnvironmentBuilder) {
 
 // native functions
 fn len(zelf: FnInstance, _: FnParams, _: &mut Context) -> FnReturnType {
-    let len = zelf?.as_vec()?.len();
+    let len = zelf?.array_len()?;
     Ok(Some(Value::U32(len as u32).into()))
 }
 
 fn push(zelf: FnInstance, mut parameters: FnParams, context: &mut Context) -> FnReturnType {
-    let array =  zelf?.as_mut_vec()?;
-    if array.len() >= u32::MAX as usize {
+    let array = zelf?;
+    if array.array_len()? >= u32::MAX as usize {
         return Err(EnvironmentError::OutOfMemory)
     }
 
     let param = parameters.remove(0);
-    let value = param.into_owned();
-
     // Verify the depth of the value
-    value.calculate_depth(context.max_value_depth() - 1)?;
+    param.as_ref()
+        .calculate_depth(context.max_value_depth() - 1)?;
+
+    // The cell and the content of the value stored
+    context.increase_memory_usage(VALUE_MEMORY_USAGE)?;
+    context.increase_stored_memory_usage(&param)?;
+    let value = param.into_owned();
 
-    array.push(value.into());
+    array.push_array_element(value)?;
 
     Ok(None)
 }
@@ -40,21 +44,33 @@ fn push(zelf: FnInstance, mut parameters: FnParams, context: &mut Context) -> Fn
 fn remove(zelf: FnInstance, mut parameters: FnParams, context: &mut Context) -> FnReturnType {
     let index = parameters.remove(0).as_u32()? as usize;
 
-    let array = zelf?.as_mut_vec()?;
-    if index >= array.len() {
-        return Err(EnvironmentError::OutOfBounds(index, array.len()))
+    let zelf = zelf?;
+    let len = zelf.array_len()?;
+    if index >= len {
+        return Err(EnvironmentError::OutOfBounds(index, len))
     }
 
     // moving all elements after the index to the left is costly
-    context.increase_gas_usage((array.len() as u64) * 5)?;
+    context.increase_gas_usage((len as u64) * 5)?;
+    // Only the cell is freed, the value is returned
+    context.decrease_memory_usage(VALUE_MEMORY_USAGE);
+
+    if let ValueCell::TypedArray(array) = zelf {
+        return Ok(array.remove(index).map(Into::into))
+    }
 
-    Ok(Some(array.remove(index).into_owned()))
+    Ok(Some(zelf.as_mut_vec()?.remove(index).into_owned()))
 }
 
-fn pop(zelf: FnInstance, _: FnParams, _: &mut Context) -> FnReturnType {
-    let array = zelf?.as_mut_vec()?;
-    if let Some(value) = array.pop() {
-        Ok(Some(value.into_owned()))
+fn pop(zelf: FnInstance, _: FnParams, context: &mut Context) -> FnReturnType {
+    let value = match zelf? {
+        ValueCell::TypedArray(array) => array.pop().map(Into::into),
+        zelf => zelf.as_mut_vec()?.pop().map(|v| v.into_owned())
+    };
+
+    if let Some(value) = value {
+        context.decrease_memory_usage(VALUE_MEMORY_USAGE);
+        Ok(Some(value))
     } else {
         Ok(Some(ValueCell::Optional(None)))
     }
@@ -63,14 +79,18 @@ fn pop(zelf: FnInstance, _: FnParams, _: &mut Context) -> FnReturnType {
 fn slice(zelf: FnInstance, mut parameters: FnParams, context: &mut Context) -> FnReturnType {
     let param = parameters.remove(0);
     let range = param.as_ref();
-    let (start, end, _type) = range.as_range()?;
+    let (start, end, _type, inclusive) = range.as_range()?;
 
     if *_type != Type::U32 {
         return Err(EnvironmentError::InvalidParameter)
     }
 
     let start = start.as_u32()?;
-    let end = end.as_u32()?;
+    let mut end = end.as_u32()?;
+    if inclusive {
+        end = end.checked_add(1)
+            .ok_or(EnvironmentError::InvalidRange(start, end))?;
+    }
 
     let vec = zelf?.as_mut_vec()?;
     let len = vec.len() as u32;
@@ -80,6 +100,7 @@ fn slice(zelf: FnInstance, mut parameters: FnParams, context: &mut Context) -> F
 
     // we need to go through all elements in the slice, thus we increase the gas usage
     context.increase_gas_usage((vec.len() as u64) * 5)?;
+    context.increase_memory_usage((end - start) as usize * VALUE_MEMORY_USAGE)?;
 
     let mut slice = Vec::new();
     for i in start..end {
@@ -91,45 +112,51 @@ fn slice(zelf: FnInstance, mut parameters: FnParams, context: &mut Context) -> F
         slice.push(value);
     }
 
-    Ok(Some(ValueCell::Array(slice)))
+    Ok(Some(ValueCell::Array(slice.into())))
 }
 
 fn contains(zelf: FnInstance, mut parameters: FnParams, context: &mut Context) -> FnReturnType {
     let value = parameters.remove(0);
     let handle = value.as_ref();
     let expected = handle.as_value();
-    let vec = zelf?.as_vec()?;
+    let zelf = zelf?;
 
     // we need to go through all elements in the slice, thus we increase the gas usage
-    context.increase_gas_usage((vec.len() as u64) * 5)?;
+    context.increase_gas_usage((zelf.array_len()? as u64) * 5)?;
 
-    Ok(Some(Value::Boolean(vec.iter().find(|v| *v.borrow() == *expected).is_some()).into()))
+    let found = match (&*zelf, expected) {
+        (ValueCell::TypedArray(array), ValueCell::Default(v)) => array.contains(v),
+        (ValueCell::TypedArray(_), _) => false,
+        (zelf, _) => zelf.as_vec()?.iter().any(|v| *v.borrow() == *expected)
+    };
+
+    Ok(Some(Value::Boolean(found).into()))
 }
 
 fn get(zelf: FnInstance, mut parameters: FnParams, _: &mut Context) -> FnReturnType {
     let index = parameters.remove(0).as_u32()? as usize;
-    let vec = zelf?.as_vec()?;
-    if let Some(value) = vec.get(index) {
-        Ok(Some(ValueCell::Optional(Some(value.reference()))))
-    } else {
-        Ok(Some(ValueCell::Optional(None)))
-    }
+    get_at(zelf?, index)
 }
 
 fn first(zelf: FnInstance, _: FnParams, _: &mut Context) -> FnReturnType {
-    let vec = zelf?.as_vec()?;
-    if let Some(value) = vec.first() {
-        Ok(Some(ValueCell::Optional(Some(value.reference()))))
-    } else {
-        Ok(Some(ValueCell::Optional(None)))
-    }
+    get_at(zelf?, 0)
 }
 
 fn last(zelf: FnInstance, _: FnParams, _: &mut Context) -> FnReturnType {
-    let vec = zelf?.as_vec()?;
-    if let Some(value) = vec.last() {
-        Ok(Some(ValueCell::Optional(Some(value.reference()))))
-    } else {
-        Ok(Some(ValueCell::Optional(None)))
+    let zelf = zelf?;
+    match zelf.array_len()?.checked_sub(1) {
+        Some(index) => get_at(zelf, index),
+        None => Ok(Some(ValueCell::Optional(None)))
     }
+}
+
+// Optional value at the index, an inline value is copied
+// The values shared with a copy of the array are copied before one is referenced
+fn get_at(zelf: &mut ValueCell, index: usize) -> FnReturnType {
+    let value = match zelf {
+        ValueCell::TypedArray(array) => array.get(index).map(Into::into),
+        zelf => zelf.as_mut_vec()?.get(index).map(|v| v.reference())
+    };
+
+    Ok(Some(ValueCell::Optional(value)))
 }
\ No newline at end of file
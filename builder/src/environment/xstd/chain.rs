@@ -0,0 +1,54 @@
+use xelis_environment::{ChainInfo, Context, EnvironmentError};
+use xelis_types::{Type, Value};
+use super::{
+    bytes_to_value,
+    FnInstance,
+    FnParams,
+    FnReturnType,
+    EnvironmentBuilder
+};
+
+// Block and transaction data, provided by the host by inserting a `ChainInfo` in the Context
+pub fn register(env: &mut EnvironmentBuilder) {
+    env.namespace("chain", |ns| {
+        let hash_type = Type::Array(Box::new(Type::U8));
+
+        // Given by the host for each execution, a node replaying it may not set the same ones
+        ns.register_native_function("timestamp", vec![], timestamp, 5, Some(Type::U64))
+            .set_deterministic(false);
+        ns.register_native_function("block_height", vec![], block_height, 5, Some(Type::U64))
+            .set_deterministic(false);
+        ns.register_native_function("block_hash", vec![], block_hash, 5, Some(hash_type.clone()))
+            .set_deterministic(false);
+        ns.register_native_function("tx_hash", vec![], tx_hash, 5, Some(hash_type))
+            .set_deterministic(false);
+    });
+}
+
+fn timestamp(_: FnInstance, _: FnParams, context: &mut Context) -> FnReturnType {
+    let timestamp = context.try_get::<ChainInfo>()?.timestamp
+        .ok_or(EnvironmentError::MissingChainData("timestamp"))?;
+
+    Ok(Some(Value::U64(timestamp).into()))
+}
+
+fn block_height(_: FnInstance, _: FnParams, context: &mut Context) -> FnReturnType {
+    let height = context.try_get::<ChainInfo>()?.block_height
+        .ok_or(EnvironmentError::MissingChainData("block height"))?;
+
+    Ok(Some(Value::U64(height).into()))
+}
+
+fn block_hash(_: FnInstance, _: FnParams, context: &mut Context) -> FnReturnType {
+    let hash = context.try_get::<ChainInfo>()?.block_hash
+        .ok_or(EnvironmentError::MissingChainData("block hash"))?;
+
+    Ok(Some(bytes_to_value(&hash)))
+}
+
+fn tx_hash(_: FnInstance, _: FnParams, context: &mut Context) -> FnReturnType {
+    let hash = context.try_get::<ChainInfo>()?.tx_hash
+        .ok_or(EnvironmentError::MissingChainData("transaction hash"))?;
+
+    Ok(Some(bytes_to_value(&hash)))
+}
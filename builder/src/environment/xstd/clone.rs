@@ -0,0 +1,28 @@
+use xelis_types::Type;
+use xelis_environment::{
+    Context,
+    FnInstance,
+    FnParams,
+    FnReturnType
+};
+use super::EnvironmentBuilder;
+
+// Gas charged per copied byte
+const BYTES_PER_GAS: u64 = 16;
+
+// `clone()` is available on every type and returns a value of the type of its instance
+pub fn register(env: &mut EnvironmentBuilder) {
+    env.register_native_function("clone", Some(Type::Any), vec![], clone, 1, Some(Type::Any));
+}
+
+// Deep copy of the value, the copy shares no reference with it
+fn clone(zelf: FnInstance, _: FnParams, context: &mut Context) -> FnReturnType {
+    let zelf = zelf?;
+
+    // The size is computed before copying anything
+    let size = context.value_memory_usage(zelf)?;
+    context.increase_gas_usage(size as u64 / BYTES_PER_GAS)?;
+    context.increase_memory_usage(size)?;
+
+    Ok(Some(zelf.clone().into_owned()))
+}
@@ -0,0 +1,37 @@
+use xelis_types::{EnumType, EnumValueType, Type, ValueCell};
+use xelis_environment::{
+    Context,
+    FnParams,
+    FnReturnType
+};
+use super::EnvironmentBuilder;
+
+// `compare` returns a variant of the built-in `Ordering` enum
+// Values that can't be ordered, like maps or different structures, return null
+pub fn register(env: &mut EnvironmentBuilder) {
+    let _type = env.register_enum("Ordering", vec![
+        ("Less", vec![]),
+        ("Equal", vec![]),
+        ("Greater", vec![])
+    ]);
+
+    let return_type = Type::Optional(Box::new(Type::Enum(_type.clone())));
+    env.register_native_function("compare", None, vec![("left", Type::Any), ("right", Type::Any)], move |_, parameters, context| compare(&_type, parameters, context), 1, Some(return_type));
+}
+
+fn compare(_type: &EnumType, parameters: FnParams, context: &mut Context) -> FnReturnType {
+    let left = parameters[0].as_ref();
+    let right = parameters[1].as_ref();
+
+    // The depth is limited as a value may reference itself
+    let (ordering, compared) = left.as_value().deep_cmp(right.as_value(), context.max_value_depth())?;
+    context.increase_gas_usage(compared as u64)?;
+
+    let value = ordering.map(|ordering| {
+        // Variant ids follow the declaration: Less, Equal, Greater
+        let variant = (ordering as i8 + 1) as u8;
+        ValueCell::Enum(Default::default(), EnumValueType::new(_type.clone(), variant)).into()
+    });
+
+    Ok(Some(ValueCell::Optional(value)))
+}
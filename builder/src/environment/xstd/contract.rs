@@ -0,0 +1,28 @@
+use xelis_environment::{Context, EnvironmentError};
+use xelis_types::{Type, Value};
+use super::{
+    FnInstance,
+    FnParams,
+    FnReturnType,
+    EnvironmentBuilder
+};
+
+// Identities of the contracts, provided by the host and updated by the calls between contracts
+pub fn register(env: &mut EnvironmentBuilder) {
+    env.register_native_function("caller", None, vec![], caller, 5, Some(Type::String));
+    env.register_native_function("current_contract", None, vec![], current_contract, 5, Some(Type::String));
+}
+
+fn caller(_: FnInstance, _: FnParams, context: &mut Context) -> FnReturnType {
+    let caller = context.caller()
+        .ok_or(EnvironmentError::MissingCaller)?;
+
+    Ok(Some(Value::String(caller.to_owned()).into()))
+}
+
+fn current_contract(_: FnInstance, _: FnParams, context: &mut Context) -> FnReturnType {
+    let contract = context.current_contract()
+        .ok_or(EnvironmentError::MissingContract)?;
+
+    Ok(Some(Value::String(contract.to_owned()).into()))
+}
@@ -0,0 +1,186 @@
+use std::sync::LazyLock;
+use curve25519_dalek::{
+    constants::RISTRETTO_BASEPOINT_COMPRESSED,
+    ristretto::{CompressedRistretto, RistrettoPoint},
+    Scalar
+};
+use ed25519_dalek::{Signature, VerifyingKey};
+use sha2::Sha256;
+use sha3::{Digest, Keccak256, Sha3_512};
+use xelis_environment::{Context, EnvironmentError};
+use xelis_types::{Path, Type, Value, ValueCell};
+use super::{
+    read_bytes,
+    bytes_to_value,
+    FnInstance,
+    FnParams,
+    FnReturnType,
+    EnvironmentBuilder
+};
+
+// Bytes hashed for one unit of gas
+const BYTES_PER_GAS: u64 = 16;
+
+// Size of a public key
+const PUBLIC_KEY_LEN: usize = 32;
+// Size of a signature
+const SIGNATURE_LEN: usize = 64;
+
+// Generator of the XELIS keys, the public key is `private^-1 * H`
+static XELIS_GENERATOR: LazyLock<RistrettoPoint> = LazyLock::new(|| {
+    RistrettoPoint::hash_from_bytes::<Sha3_512>(RISTRETTO_BASEPOINT_COMPRESSED.as_bytes())
+});
+
+pub fn register(env: &mut EnvironmentBuilder) {
+    let bytes_type = Type::Array(Box::new(Type::U8));
+
+    env.register_native_function("blake3", None, vec![("bytes", bytes_type.clone())], blake3, 25, Some(bytes_type.clone()))
+        .set_cost_fn(Some(hash_cost));
+    env.register_native_function("sha256", None, vec![("bytes", bytes_type.clone())], sha256, 25, Some(bytes_type.clone()))
+        .set_cost_fn(Some(hash_cost));
+    env.register_native_function("keccak256", None, vec![("bytes", bytes_type.clone())], keccak256, 25, Some(bytes_type.clone()))
+        .set_cost_fn(Some(hash_cost));
+
+    // Keys and signatures must have the exact expected size
+    let signature_params = vec![
+        ("pubkey", bytes_type.clone()),
+        ("message", bytes_type.clone()),
+        ("signature", bytes_type)
+    ];
+    env.register_native_function("verify_ed25519", None, signature_params.clone(), verify_ed25519, 500, Some(Type::Bool))
+        .set_cost_fn(Some(signature_cost));
+    env.register_native_function("verify_schnorr", None, signature_params, verify_schnorr, 500, Some(Type::Bool))
+        .set_cost_fn(Some(signature_cost));
+}
+
+// Cost based on the size of the hashed bytes
+fn hash_cost(_: Option<&ValueCell>, parameters: &FnParams) -> u64 {
+    parameters.first()
+        .and_then(|p| p.as_ref().array_len().ok().map(|v| v as u64))
+        .unwrap_or(0) / BYTES_PER_GAS
+}
+
+// Cost based on the size of the signed message
+fn signature_cost(_: Option<&ValueCell>, parameters: &FnParams) -> u64 {
+    parameters.get(1)
+        .and_then(|p| p.as_ref().array_len().ok().map(|v| v as u64))
+        .unwrap_or(0) / BYTES_PER_GAS
+}
+
+// Read a u8[] parameter that must have exactly N bytes
+fn read_fixed_bytes<const N: usize>(param: &Path) -> Result<[u8; N], EnvironmentError> {
+    let bytes = read_bytes(param)?;
+    bytes.try_into()
+        .map_err(|bytes: Vec<u8>| EnvironmentError::InvalidLength {
+            expected: N,
+            actual: bytes.len()
+        })
+}
+
+fn blake3(_: FnInstance, parameters: FnParams, _: &mut Context) -> FnReturnType {
+    let bytes = read_bytes(&parameters[0])?;
+    let hash = blake3::hash(&bytes);
+    Ok(Some(bytes_to_value(hash.as_bytes())))
+}
+
+fn sha256(_: FnInstance, parameters: FnParams, _: &mut Context) -> FnReturnType {
+    let bytes = read_bytes(&parameters[0])?;
+    let hash = Sha256::digest(&bytes);
+    Ok(Some(bytes_to_value(&hash)))
+}
+
+fn keccak256(_: FnInstance, parameters: FnParams, _: &mut Context) -> FnReturnType {
+    let bytes = read_bytes(&parameters[0])?;
+    let hash = Keccak256::digest(&bytes);
+    Ok(Some(bytes_to_value(&hash)))
+}
+
+fn verify_ed25519(_: FnInstance, parameters: FnParams, _: &mut Context) -> FnReturnType {
+    let key = read_fixed_bytes::<PUBLIC_KEY_LEN>(&parameters[0])?;
+    let message = read_bytes(&parameters[1])?;
+    let signature = read_fixed_bytes::<SIGNATURE_LEN>(&parameters[2])?;
+
+    // An invalid key is a failed verification
+    let valid = VerifyingKey::from_bytes(&key)
+        .map(|key| key.verify_strict(&message, &Signature::from_bytes(&signature)).is_ok())
+        .unwrap_or(false);
+
+    Ok(Some(Value::Boolean(valid).into()))
+}
+
+fn verify_schnorr(_: FnInstance, parameters: FnParams, _: &mut Context) -> FnReturnType {
+    let key = read_fixed_bytes::<PUBLIC_KEY_LEN>(&parameters[0])?;
+    let message = read_bytes(&parameters[1])?;
+    let signature = read_fixed_bytes::<SIGNATURE_LEN>(&parameters[2])?;
+
+    let valid = schnorr_verify(&key, &message, &signature);
+    Ok(Some(Value::Boolean(valid).into()))
+}
+
+// Challenge of a XELIS signature: SHA3-512(key || message || R) reduced to a scalar
+fn schnorr_challenge(key: &[u8; PUBLIC_KEY_LEN], message: &[u8], point: &RistrettoPoint) -> Scalar {
+    let mut hasher = Sha3_512::new();
+    hasher.update(key);
+    hasher.update(message);
+    hasher.update(point.compress().as_bytes());
+
+    Scalar::from_bytes_mod_order_wide(&hasher.finalize().into())
+}
+
+// Verify a XELIS schnorr signature, encoded as `s || e`
+// R = s * H - e * P, the signature is valid if e == challenge(P, message, R)
+fn schnorr_verify(key: &[u8; PUBLIC_KEY_LEN], message: &[u8], signature: &[u8; SIGNATURE_LEN]) -> bool {
+    let Some(point) = CompressedRistretto(*key).decompress() else {
+        return false
+    };
+
+    let scalar = |bytes: &[u8]| -> Option<Scalar> {
+        Scalar::from_canonical_bytes(bytes.try_into().ok()?).into()
+    };
+    let (Some(s), Some(e)) = (scalar(&signature[..32]), scalar(&signature[32..])) else {
+        return false
+    };
+
+    let r = *XELIS_GENERATOR * s - point * e;
+    e == schnorr_challenge(key, message, &r)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Sign like a XELIS wallet, with a fixed nonce
+    fn schnorr_sign(private: &Scalar, message: &[u8], nonce: &Scalar) -> ([u8; PUBLIC_KEY_LEN], [u8; SIGNATURE_LEN]) {
+        let key = (*XELIS_GENERATOR * private.invert()).compress().to_bytes();
+        let r = *XELIS_GENERATOR * nonce;
+        let e = schnorr_challenge(&key, message, &r);
+        let s = private.invert() * e + nonce;
+
+        let mut signature = [0u8; SIGNATURE_LEN];
+        signature[..32].copy_from_slice(s.as_bytes());
+        signature[32..].copy_from_slice(e.as_bytes());
+        (key, signature)
+    }
+
+    #[test]
+    fn test_schnorr_verify() {
+        let private = Scalar::from(123456789u64);
+        let nonce = Scalar::from(987654321u64);
+        let (key, signature) = schnorr_sign(&private, b"hello", &nonce);
+
+        assert!(schnorr_verify(&key, b"hello", &signature));
+        assert!(!schnorr_verify(&key, b"hellO", &signature));
+
+        // Tampered signature
+        let mut tampered = signature;
+        tampered[0] ^= 1;
+        assert!(!schnorr_verify(&key, b"hello", &tampered));
+
+        // Another key
+        let (other, _) = schnorr_sign(&Scalar::from(42u64), b"hello", &nonce);
+        assert!(!schnorr_verify(&other, b"hello", &signature));
+
+        // Not a valid point
+        assert!(!schnorr_verify(&[0xff; PUBLIC_KEY_LEN], b"hello", &signature));
+    }
+}
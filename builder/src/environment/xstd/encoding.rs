@@ -0,0 +1,67 @@
+use base64::{engine::general_purpose::STANDARD, Engine};
+use xelis_environment::Context;
+use xelis_types::{Type, Value, ValueCell};
+use super::{
+    read_bytes,
+    bytes_to_value,
+    FnInstance,
+    FnParams,
+    FnReturnType,
+    EnvironmentBuilder
+};
+
+// Bytes encoded or decoded for one unit of gas
+const BYTES_PER_GAS: u64 = 32;
+
+// Decoding functions return null on an invalid input instead of stopping the execution
+pub fn register(env: &mut EnvironmentBuilder) {
+    let bytes_type = Type::Array(Box::new(Type::U8));
+    let optional_bytes_type = Type::Optional(Box::new(bytes_type.clone()));
+
+    env.register_native_function("hex_encode", None, vec![("bytes", bytes_type.clone())], hex_encode, 5, Some(Type::String))
+        .set_cost_fn(Some(input_len_cost));
+    env.register_native_function("hex_decode", None, vec![("value", Type::String)], hex_decode, 5, Some(optional_bytes_type.clone()))
+        .set_cost_fn(Some(input_len_cost));
+    env.register_native_function("base64_encode", None, vec![("bytes", bytes_type)], base64_encode, 5, Some(Type::String))
+        .set_cost_fn(Some(input_len_cost));
+    env.register_native_function("base64_decode", None, vec![("value", Type::String)], base64_decode, 5, Some(optional_bytes_type))
+        .set_cost_fn(Some(input_len_cost));
+}
+
+// Cost based on the size of the input, a byte array or a string
+fn input_len_cost(_: Option<&ValueCell>, parameters: &FnParams) -> u64 {
+    let len = parameters.first()
+        .map_or(0, |p| match p.as_ref().as_value() {
+            ValueCell::Default(Value::String(s)) => s.len(),
+            ValueCell::Array(values) => values.len(),
+            ValueCell::TypedArray(array) => array.len(),
+            _ => 0
+        });
+
+    len as u64 / BYTES_PER_GAS
+}
+
+// Wrap the decoded bytes in an optional
+fn decoded_to_value<E>(decoded: Result<Vec<u8>, E>) -> ValueCell {
+    ValueCell::Optional(decoded.ok().map(|bytes| bytes_to_value(&bytes).into()))
+}
+
+fn hex_encode(_: FnInstance, parameters: FnParams, _: &mut Context) -> FnReturnType {
+    let bytes = read_bytes(&parameters[0])?;
+    Ok(Some(Value::String(hex::encode(bytes)).into()))
+}
+
+fn hex_decode(_: FnInstance, parameters: FnParams, _: &mut Context) -> FnReturnType {
+    let value = parameters[0].as_ref();
+    Ok(Some(decoded_to_value(hex::decode(value.as_string()?))))
+}
+
+fn base64_encode(_: FnInstance, parameters: FnParams, _: &mut Context) -> FnReturnType {
+    let bytes = read_bytes(&parameters[0])?;
+    Ok(Some(Value::String(STANDARD.encode(bytes)).into()))
+}
+
+fn base64_decode(_: FnInstance, parameters: FnParams, _: &mut Context) -> FnReturnType {
+    let value = parameters[0].as_ref();
+    Ok(Some(decoded_to_value(STANDARD.decode(value.as_string()?))))
+}
@@ -0,0 +1,36 @@
+use xelis_environment::{Context, Event};
+use xelis_types::Type;
+use super::{
+    FnInstance,
+    FnParams,
+    FnReturnType,
+    EnvironmentBuilder
+};
+
+// Bytes of events for one unit of gas
+const BYTES_PER_GAS: u64 = 8;
+
+// Events are collected in the Context and retrieved by the host after the execution
+pub fn register(env: &mut EnvironmentBuilder) {
+    env.register_native_function("emit", None, vec![("name", Type::String), ("data", Type::Any)], emit, 50, None)
+        .set_mutating(true);
+}
+
+fn emit(_: FnInstance, parameters: FnParams, context: &mut Context) -> FnReturnType {
+    let name = parameters[0].as_ref().as_string()?.clone();
+
+    // The payload is copied so the script can't update it after its emission
+    let handle = parameters[1].as_ref();
+    let value = handle.as_value();
+    value.calculate_depth(context.max_value_depth())?;
+
+    let event = Event {
+        name,
+        data: value.clone().into_owned()
+    };
+
+    context.increase_gas_usage(event.size() as u64 / BYTES_PER_GAS)?;
+    context.push_event(event)?;
+
+    Ok(None)
+}
@@ -0,0 +1,23 @@
+use xelis_environment::Context;
+use xelis_types::{Type, Value};
+use super::{
+    FnInstance,
+    FnParams,
+    FnReturnType,
+    EnvironmentBuilder
+};
+
+// Gas introspection, so a script can adapt its work to its remaining budget
+// The cost of the call is charged before reading the gas
+pub fn register(env: &mut EnvironmentBuilder) {
+    env.register_native_function("gas_used", None, vec![], gas_used, 1, Some(Type::U64));
+    env.register_native_function("gas_remaining", None, vec![], gas_remaining, 1, Some(Type::U64));
+}
+
+fn gas_used(_: FnInstance, _: FnParams, context: &mut Context) -> FnReturnType {
+    Ok(Some(Value::U64(context.current_gas_usage()).into()))
+}
+
+fn gas_remaining(_: FnInstance, _: FnParams, context: &mut Context) -> FnReturnType {
+    Ok(Some(Value::U64(context.remaining_gas()).into()))
+}
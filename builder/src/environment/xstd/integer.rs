@@ -9,14 +9,24 @@ use paste::paste;
 
 use crate::EnvironmentBuilder;
 
+// Characters parsed or formatted for one unit of gas
+const CHARS_PER_GAS: u64 = 32;
+
 macro_rules! overflow_fn {
-    ($env: expr, $op: ident, $t: ident, $f: ident) => {
+    ($env: expr, $op: ident, $t: ident, $f: ident $(, $zero: ident)?) => {
         paste! {
             fn [<overflowing_ $op _ $f>](zelf: FnInstance, mut parameters: FnParams, _: &mut Context) -> FnReturnType {
                 // Extract and convert parameters
                 let other = parameters.remove(0).into_owned().[<as_ $f>]()?;
                 let value = zelf?.[<as_ $f>]()?;
                 
+                // A division by zero has no result, like an overflow
+                $(
+                    if other == $f::$zero {
+                        return Ok(Some(ValueCell::Optional(None)))
+                    }
+                )?
+
                 // Perform the operation with `overflowing_$op` as a method name
                 let (result, overflow) = value.[<overflowing_ $op>](other);
                 
@@ -50,8 +60,72 @@ macro_rules! register_overflows {
             overflow_fn!($env, add, $t, $f);
             overflow_fn!($env, sub, $t, $f);
             overflow_fn!($env, mul, $t, $f);
-            overflow_fn!($env, div, $t, $f);
-            overflow_fn!($env, rem, $t, $f);
+            overflow_fn!($env, div, $t, $f, MIN);
+            overflow_fn!($env, rem, $t, $f, MIN);
+        }
+    };
+}
+
+// Register a `$kind_$op` function like `saturating_add` returning the same type
+macro_rules! arithmetic_fn {
+    ($env: expr, $kind: ident, $op: ident, $t: ident, $f: ident) => {
+        paste! {
+            fn [<$kind _ $op _ $f>](zelf: FnInstance, mut parameters: FnParams, _: &mut Context) -> FnReturnType {
+                let other = parameters.remove(0).into_owned().[<as_ $f>]()?;
+                let value = zelf?.[<as_ $f>]()?;
+
+                Ok(Some(Value::$t(value.[<$kind _ $op>](other)).into()))
+            }
+
+            $env.register_native_function(
+                stringify!([<$kind _ $op>]),
+                Some(Type::$t),
+                vec![("other", Type::$t)],
+                [<$kind _ $op _ $f>],
+                2,
+                Some(Type::$t)
+            );
+        }
+    };
+}
+
+// Register a `checked_$op` function returning null on overflow
+macro_rules! checked_fn {
+    ($env: expr, $op: ident, $t: ident, $f: ident) => {
+        paste! {
+            fn [<checked_ $op _ $f>](zelf: FnInstance, mut parameters: FnParams, _: &mut Context) -> FnReturnType {
+                let other = parameters.remove(0).into_owned().[<as_ $f>]()?;
+                let value = zelf?.[<as_ $f>]()?;
+
+                let inner = value.[<checked_ $op>](other).map(|v| Value::$t(v).into());
+                Ok(Some(ValueCell::Optional(inner)))
+            }
+
+            $env.register_native_function(
+                stringify!([<checked_ $op>]),
+                Some(Type::$t),
+                vec![("other", Type::$t)],
+                [<checked_ $op _ $f>],
+                2,
+                Some(Type::Optional(Box::new(Type::$t)))
+            );
+        }
+    };
+}
+
+// macro to register the checked, saturating and wrapping operations for a specific type
+macro_rules! register_arithmetic {
+    ($env: expr, $t: ident, $f: ident) => {
+        {
+            checked_fn!($env, add, $t, $f);
+            checked_fn!($env, sub, $t, $f);
+            checked_fn!($env, mul, $t, $f);
+            arithmetic_fn!($env, saturating, add, $t, $f);
+            arithmetic_fn!($env, saturating, sub, $t, $f);
+            arithmetic_fn!($env, saturating, mul, $t, $f);
+            arithmetic_fn!($env, wrapping, add, $t, $f);
+            arithmetic_fn!($env, wrapping, sub, $t, $f);
+            arithmetic_fn!($env, wrapping, mul, $t, $f);
         }
     };
 }
@@ -62,8 +136,7 @@ macro_rules! to_endian_bytes {
             fn [<to_ $endian _bytes_ $f>](zelf: FnInstance, _: FnParams, _: &mut Context) -> FnReturnType {
                 let value = zelf?.[<as_ $f>]()?;
                 let bytes = value.[<to_ $endian _bytes>]();
-                let vec = bytes.iter().map(|b| Value::U8(*b).into()).collect();
-                Ok(Some(ValueCell::Array(vec)))
+                Ok(Some(ValueCell::TypedArray(bytes.to_vec().into())))
             }
 
             $env.register_native_function(
@@ -98,6 +171,56 @@ macro_rules! register_constants_min_max {
     };
 }
 
+// Register `parse_$f` returning null if the string isn't a valid number of the type
+macro_rules! parse_fn {
+    ($env: expr, $t: ident, $f: ident) => {
+        paste! {
+            fn [<parse_ $f>](_: FnInstance, parameters: FnParams, _: &mut Context) -> FnReturnType {
+                let handle = parameters[0].as_ref();
+                let inner = parse_digits(handle.as_string()?).map(|v| Value::$t(v).into());
+                Ok(Some(ValueCell::Optional(inner)))
+            }
+
+            $env.register_native_function(
+                stringify!([<parse_ $f>]),
+                None,
+                vec![("value", Type::String)],
+                [<parse_ $f>],
+                5,
+                Some(Type::Optional(Box::new(Type::$t)))
+            ).set_cost_fn(Some(input_len_cost));
+        }
+    };
+}
+
+// Register `to_hex`, `to_binary` and `to_string_padded` for a type
+macro_rules! format_fn {
+    ($env: expr, $t: ident, $f: ident) => {
+        paste! {
+            fn [<to_hex_ $f>](zelf: FnInstance, _: FnParams, _: &mut Context) -> FnReturnType {
+                let value = u256::from(zelf?.[<as_ $f>]()?);
+                Ok(Some(Value::String(format_radix(value, 16)).into()))
+            }
+
+            fn [<to_binary_ $f>](zelf: FnInstance, _: FnParams, _: &mut Context) -> FnReturnType {
+                let value = u256::from(zelf?.[<as_ $f>]()?);
+                Ok(Some(Value::String(format_radix(value, 2)).into()))
+            }
+
+            fn [<to_string_padded_ $f>](zelf: FnInstance, parameters: FnParams, _: &mut Context) -> FnReturnType {
+                let width = parameters[0].as_ref().as_u32()? as usize;
+                let value = u256::from(zelf?.[<as_ $f>]()?);
+                Ok(Some(Value::String(format!("{:0>width$}", format_radix(value, 10))).into()))
+            }
+
+            $env.register_native_function("to_hex", Some(Type::$t), vec![], [<to_hex_ $f>], 5, Some(Type::String));
+            $env.register_native_function("to_binary", Some(Type::$t), vec![], [<to_binary_ $f>], 10, Some(Type::String));
+            $env.register_native_function("to_string_padded", Some(Type::$t), vec![("width", Type::U32)], [<to_string_padded_ $f>], 5, Some(Type::String))
+                .set_cost_fn(Some(width_cost));
+        }
+    };
+}
+
 pub fn register(env: &mut EnvironmentBuilder) {
     // Register all operations with overflow checking
     register_overflows!(env, U8, u8);
@@ -121,4 +244,61 @@ pub fn register(env: &mut EnvironmentBuilder) {
     register_to_endian_bytes!(env, U64, u64);
     register_to_endian_bytes!(env, U128, u128);
     register_to_endian_bytes!(env, U256, u256);
-}
\ No newline at end of file
+}
+
+// Checked, saturating and wrapping operations for all types
+// Same behavior as the `+?`, `+|` and `+%` operators
+pub fn register_arithmetic(env: &mut EnvironmentBuilder) {
+    register_arithmetic!(env, U8, u8);
+    register_arithmetic!(env, U16, u16);
+    register_arithmetic!(env, U32, u32);
+    register_arithmetic!(env, U64, u64);
+    register_arithmetic!(env, U128, u128);
+    register_arithmetic!(env, U256, u256);
+}
+
+// Parsing from a decimal string and formatting in other bases for all types
+pub fn register_formatting(env: &mut EnvironmentBuilder) {
+    parse_fn!(env, U8, u8);
+    parse_fn!(env, U16, u16);
+    parse_fn!(env, U32, u32);
+    parse_fn!(env, U64, u64);
+    parse_fn!(env, U128, u128);
+    parse_fn!(env, U256, u256);
+
+    format_fn!(env, U8, u8);
+    format_fn!(env, U16, u16);
+    format_fn!(env, U32, u32);
+    format_fn!(env, U64, u64);
+    format_fn!(env, U128, u128);
+    format_fn!(env, U256, u256);
+}
+
+// Only decimal digits are accepted, without sign or separator
+fn parse_digits<T: core::str::FromStr>(value: &str) -> Option<T> {
+    if !value.bytes().all(|b| b.is_ascii_digit()) {
+        return None
+    }
+
+    value.parse().ok()
+}
+
+// Lowercase digits without prefix
+fn format_radix(value: u256, radix: u32) -> String {
+    value.to_str_radix(radix)
+        .expect("valid radix")
+}
+
+// Cost based on the size of the parsed string
+fn input_len_cost(_: Option<&ValueCell>, parameters: &FnParams) -> u64 {
+    parameters.first()
+        .and_then(|p| p.as_ref().as_string().ok().map(|s| s.len() as u64))
+        .unwrap_or(0) / CHARS_PER_GAS
+}
+
+// Cost based on the width of the padded string
+fn width_cost(_: Option<&ValueCell>, parameters: &FnParams) -> u64 {
+    parameters.first()
+        .and_then(|p| p.as_ref().as_u32().ok())
+        .map_or(0, |width| width as u64 / CHARS_PER_GAS)
+}
@@ -0,0 +1,186 @@
+use xelis_environment::{Context, EnvironmentError, VALUE_MEMORY_USAGE};
+use xelis_types::{LazyIterator, Type, Value, ValueCell, ValueError};
+use super::{
+    FnInstance,
+    FnParams,
+    FnReturnType,
+    EnvironmentBuilder
+};
+
+// Gas charged for each value produced by a consuming function
+const GAS_PER_VALUE: u64 = 2;
+
+// Add the values left to the first one, failing on overflow
+macro_rules! checked_sum {
+    ($iter: expr, $first: expr, $t: ident, $as: ident) => {
+        {
+            let mut total = $first;
+            while let Some(value) = $iter.next_value()? {
+                total = total.checked_add(value.borrow().$as()?)
+                    .ok_or(ValueError::Overflow)?;
+            }
+            Value::$t(total)
+        }
+    };
+}
+
+// Lazy iterators over arrays and ranges
+// The adapters return a copy of the iterator at its new position without producing
+// any value, the values are only produced by a foreach or the consuming functions
+// `map` and `filter` call a function of the program on each value produced
+pub fn register(env: &mut EnvironmentBuilder) {
+    let inner = Type::T(0);
+    let _type = Type::Iterator(Box::new(inner.clone()));
+
+    env.register_native_function("iter", Some(Type::Array(Box::new(inner.clone()))), vec![], iter_array, 5, Some(_type.clone()));
+    env.register_native_function("iter", Some(Type::Range(Box::new(inner.clone()))), vec![], iter_range, 5, Some(_type.clone()));
+
+    env.register_native_function("skip", Some(_type.clone()), vec![("n", Type::U32)], skip, 1, Some(_type.clone()));
+    env.register_native_function("take", Some(_type.clone()), vec![("n", Type::U32)], take, 1, Some(_type.clone()));
+    env.register_native_function("step_by", Some(_type.clone()), vec![("n", Type::U32)], step_by, 1, Some(_type.clone()));
+
+    // The function is given by its chunk id, the parser resolves its name
+    // An iterator of any value produces the values returned by the function
+    env.register_native_function("map", Some(_type.clone()), vec![("f", Type::U16)], map, 1, Some(Type::Iterator(Box::new(Type::Any))));
+    env.register_native_function("filter", Some(_type.clone()), vec![("f", Type::U16)], filter, 1, Some(_type.clone()));
+
+    // The VM calls the functions of the adapters on the values read
+    env.register_native_function("next", Some(_type.clone()), vec![], next, 1, Some(Type::Optional(Box::new(inner.clone()))))
+        .set_consumed_values(1);
+    env.register_native_function("count", Some(_type.clone()), vec![], count, 1, Some(Type::U64))
+        .set_consumed_values(u64::MAX);
+    env.register_native_function("sum", Some(_type.clone()), vec![], sum, 5, Some(Type::Optional(Box::new(inner.clone()))))
+        .set_consumed_values(u64::MAX);
+    env.register_native_function("collect", Some(_type.clone()), vec![], collect, 5, Some(Type::Array(Box::new(inner))))
+        .set_consumed_values(u64::MAX);
+
+    env.register_native_function("rev", Some(_type.clone()), vec![], rev, 1, Some(_type));
+}
+
+fn as_iterator(zelf: FnInstance<'_>) -> Result<&mut LazyIterator, EnvironmentError> {
+    match zelf? {
+        ValueCell::Iterator(iter) => Ok(iter),
+        v => Err(EnvironmentError::InvalidType(v.clone()))
+    }
+}
+
+// Charge the values about to be produced
+fn charge_values(iter: &LazyIterator, context: &mut Context) -> Result<(), EnvironmentError> {
+    context.increase_gas_usage(iter.len().saturating_mul(GAS_PER_VALUE))
+}
+
+// The values of a boxed array are referenced, a typed array is shared as is
+// The values shared with a copy of the array are copied before being referenced
+fn iter_array(zelf: FnInstance, _: FnParams, context: &mut Context) -> FnReturnType {
+    let iter = match zelf? {
+        ValueCell::TypedArray(array) => LazyIterator::from_typed_array(array.clone()),
+        ValueCell::Array(values) => {
+            context.increase_gas_usage(values.len() as u64)?;
+            LazyIterator::from_values(values.make_mut().iter().map(|v| v.reference()).collect())
+        },
+        v => return Err(EnvironmentError::InvalidType(v.clone()))
+    };
+
+    Ok(Some(ValueCell::Iterator(Box::new(iter))))
+}
+
+fn iter_range(zelf: FnInstance, _: FnParams, _: &mut Context) -> FnReturnType {
+    let (start, end, _type, inclusive) = zelf?.as_range()?;
+    let iter = LazyIterator::from_range(start, end, _type.clone(), inclusive)?;
+    Ok(Some(ValueCell::Iterator(Box::new(iter))))
+}
+
+fn skip(zelf: FnInstance, parameters: FnParams, _: &mut Context) -> FnReturnType {
+    let n = parameters[0].as_ref().as_u32()?;
+    let mut iter = as_iterator(zelf)?.clone();
+    iter.skip(n as u64);
+    Ok(Some(ValueCell::Iterator(Box::new(iter))))
+}
+
+fn take(zelf: FnInstance, parameters: FnParams, _: &mut Context) -> FnReturnType {
+    let n = parameters[0].as_ref().as_u32()?;
+    let mut iter = as_iterator(zelf)?.clone();
+    iter.take(n as u64);
+    Ok(Some(ValueCell::Iterator(Box::new(iter))))
+}
+
+fn step_by(zelf: FnInstance, parameters: FnParams, _: &mut Context) -> FnReturnType {
+    let n = parameters[0].as_ref().as_u32()?;
+    let mut iter = as_iterator(zelf)?.clone();
+    iter.step_by(n as u64)?;
+    Ok(Some(ValueCell::Iterator(Box::new(iter))))
+}
+
+fn rev(zelf: FnInstance, _: FnParams, _: &mut Context) -> FnReturnType {
+    let mut iter = as_iterator(zelf)?.clone();
+    iter.rev()?;
+    Ok(Some(ValueCell::Iterator(Box::new(iter))))
+}
+
+fn map(zelf: FnInstance, parameters: FnParams, _: &mut Context) -> FnReturnType {
+    let chunk = parameters[0].as_ref().as_u16()?;
+    let mut iter = as_iterator(zelf)?.clone();
+    iter.map(chunk);
+    Ok(Some(ValueCell::Iterator(Box::new(iter))))
+}
+
+fn filter(zelf: FnInstance, parameters: FnParams, _: &mut Context) -> FnReturnType {
+    let chunk = parameters[0].as_ref().as_u16()?;
+    let mut iter = as_iterator(zelf)?.clone();
+    iter.filter(chunk);
+    Ok(Some(ValueCell::Iterator(Box::new(iter))))
+}
+
+// Produce a copy of the next value, the iterator is advanced in place
+fn next(zelf: FnInstance, _: FnParams, context: &mut Context) -> FnReturnType {
+    let value = as_iterator(zelf)?.next_value()?;
+    if value.is_some() {
+        context.increase_gas_usage(GAS_PER_VALUE)?;
+    }
+
+    Ok(Some(ValueCell::Optional(value.map(|v| v.into_owned().into()))))
+}
+
+// Count of values left, none is produced
+fn count(zelf: FnInstance, _: FnParams, _: &mut Context) -> FnReturnType {
+    let len = as_iterator(zelf)?.len();
+    Ok(Some(Value::U64(len).into()))
+}
+
+// Sum of the values left, in the type of the values
+// An empty iterator returns null as the type of its values isn't known
+fn sum(zelf: FnInstance, _: FnParams, context: &mut Context) -> FnReturnType {
+    let mut iter = as_iterator(zelf)?.clone();
+    charge_values(&iter, context)?;
+
+    let Some(first) = iter.next_value()? else {
+        return Ok(Some(ValueCell::Optional(None)))
+    };
+
+    let first = first.borrow().as_value()?.clone();
+    let total = match first {
+        Value::U8(v) => checked_sum!(iter, v, U8, as_u8),
+        Value::U16(v) => checked_sum!(iter, v, U16, as_u16),
+        Value::U32(v) => checked_sum!(iter, v, U32, as_u32),
+        Value::U64(v) => checked_sum!(iter, v, U64, as_u64),
+        Value::U128(v) => checked_sum!(iter, v, U128, as_u128),
+        Value::U256(v) => checked_sum!(iter, v, U256, as_u256),
+        _ => return Err(ValueError::OperationNotNumberType.into())
+    };
+
+    Ok(Some(ValueCell::Optional(Some(total.into()))))
+}
+
+// Collect a copy of the values left into an array
+fn collect(zelf: FnInstance, _: FnParams, context: &mut Context) -> FnReturnType {
+    let mut iter = as_iterator(zelf)?.clone();
+    charge_values(&iter, context)?;
+    context.increase_memory_usage((iter.len() as usize).saturating_mul(VALUE_MEMORY_USAGE))?;
+
+    let mut values = Vec::with_capacity(iter.len() as usize);
+    while let Some(value) = iter.next_value()? {
+        values.push(value.into_owned().into());
+    }
+
+    Ok(Some(ValueCell::Array(values.into())))
+}
@@ -0,0 +1,258 @@
+use indexmap::IndexMap;
+use serde_json::Value as JsonValue;
+use xelis_environment::{Context, EnvironmentError};
+use xelis_types::{Type, Value, ValueCell};
+use super::{
+    FnInstance,
+    FnParams,
+    FnReturnType,
+    EnvironmentBuilder
+};
+
+// Bytes parsed or written for one unit of gas
+const BYTES_PER_GAS: u64 = 16;
+
+// Json is an opaque type for the scripts
+// Its value is the parsed document using the VM values:
+// null is an empty optional, a number is a u64, an array is an array and an object is a map with string keys
+// Only unsigned integers fitting in a u64 are supported as the VM has no signed or float types
+pub fn register(env: &mut EnvironmentBuilder) {
+    let _type = Type::Opaque(env.register_opaque("Json"));
+    let optional_type = Type::Optional(Box::new(_type.clone()));
+
+    env.register_native_function("json_parse", None, vec![("value", Type::String)], json_parse, 10, Some(optional_type.clone()))
+        .set_cost_fn(Some(parse_cost));
+    env.register_native_function("json_stringify", None, vec![("value", Type::Any)], json_stringify, 10, Some(Type::String));
+
+    env.register_native_function("kind", Some(_type.clone()), vec![], kind, 1, Some(Type::String));
+    env.register_native_function("is_null", Some(_type.clone()), vec![], is_null, 1, Some(Type::Bool));
+    env.register_native_function("as_bool", Some(_type.clone()), vec![], as_bool, 1, Some(Type::Optional(Box::new(Type::Bool))));
+    env.register_native_function("as_u64", Some(_type.clone()), vec![], as_u64, 1, Some(Type::Optional(Box::new(Type::U64))));
+    env.register_native_function("as_string", Some(_type.clone()), vec![], as_string, 1, Some(Type::Optional(Box::new(Type::String))));
+    env.register_native_function("get", Some(_type.clone()), vec![("key", Type::String)], get, 2, Some(optional_type.clone()));
+    env.register_native_function("at", Some(_type.clone()), vec![("index", Type::U32)], at, 1, Some(optional_type));
+    env.register_native_function("len", Some(_type.clone()), vec![], len, 1, Some(Type::Optional(Box::new(Type::U32))));
+    env.register_native_function("keys", Some(_type), vec![], keys, 5, Some(Type::Optional(Box::new(Type::Array(Box::new(Type::String))))));
+}
+
+// Cost based on the size of the parsed string
+fn parse_cost(_: Option<&ValueCell>, parameters: &FnParams) -> u64 {
+    parameters.first()
+        .and_then(|p| p.as_ref().as_string().ok().map(|s| s.len() as u64))
+        .unwrap_or(0) / BYTES_PER_GAS
+}
+
+// Convert a parsed document to a VM value
+// Returns None if a number is not supported
+fn from_json(value: JsonValue) -> Option<ValueCell> {
+    Some(match value {
+        JsonValue::Null => ValueCell::Optional(None),
+        JsonValue::Bool(v) => Value::Boolean(v).into(),
+        JsonValue::Number(n) => Value::U64(n.as_u64()?).into(),
+        JsonValue::String(s) => Value::String(s).into(),
+        JsonValue::Array(values) => {
+            let values = values.into_iter()
+                .map(|v| from_json(v).map(Into::into))
+                .collect::<Option<_>>()?;
+            ValueCell::Array(values)
+        },
+        JsonValue::Object(map) => {
+            let map = map.into_iter()
+                .map(|(k, v)| Some((Value::String(k).into(), from_json(v)?.into())))
+                .collect::<Option<IndexMap<_, _>>>()?;
+            ValueCell::Map(map.into())
+        }
+    })
+}
+
+// Write a VM value as JSON
+// Optionals are written as their inner value or null, structs as arrays of their fields
+// The depth is limited as a value may reference itself
+fn write_json(value: &ValueCell, out: &mut String, depth: usize, max_depth: usize) -> Result<(), EnvironmentError> {
+    if depth > max_depth {
+        return Err(EnvironmentError::InvalidType(value.clone()))
+    }
+
+    match value {
+        ValueCell::Default(v) => match v {
+            Value::Null => out.push_str("null"),
+            Value::U8(n) => out.push_str(&n.to_string()),
+            Value::U16(n) => out.push_str(&n.to_string()),
+            Value::U32(n) => out.push_str(&n.to_string()),
+            Value::U64(n) => out.push_str(&n.to_string()),
+            Value::U128(n) => out.push_str(&n.to_string()),
+            Value::U256(n) => out.push_str(&n.to_string()),
+            Value::String(s) => out.push_str(&JsonValue::from(s.as_str()).to_string()),
+            Value::Boolean(b) => out.push_str(if *b { "true" } else { "false" }),
+            Value::Blob(bytes) => {
+                out.push('[');
+                for (i, b) in bytes.iter().enumerate() {
+                    if i > 0 {
+                        out.push(',');
+                    }
+                    out.push_str(&b.to_string());
+                }
+                out.push(']');
+            },
+            Value::Range(..) => return Err(EnvironmentError::InvalidType(value.clone()))
+        },
+        ValueCell::Iterator(_) => return Err(EnvironmentError::InvalidType(value.clone())),
+        ValueCell::Array(values) | ValueCell::Struct(values, _) => {
+            out.push('[');
+            for (i, v) in values.iter().enumerate() {
+                if i > 0 {
+                    out.push(',');
+                }
+                write_json(&v.borrow(), out, depth + 1, max_depth)?;
+            }
+            out.push(']');
+        },
+        ValueCell::TypedArray(array) => {
+            out.push('[');
+            for (i, v) in array.iter().enumerate() {
+                if i > 0 {
+                    out.push(',');
+                }
+                write_json(&v.into(), out, depth + 1, max_depth)?;
+            }
+            out.push(']');
+        },
+        ValueCell::Optional(None) => out.push_str("null"),
+        ValueCell::Optional(Some(v)) => write_json(&v.borrow(), out, depth + 1, max_depth)?,
+        ValueCell::Map(map) => {
+            out.push('{');
+            for (i, (k, v)) in map.iter().enumerate() {
+                // Only string keys are allowed in an object
+                let key = k.as_string()
+                    .map_err(|_| EnvironmentError::InvalidKeyType)?;
+
+                if i > 0 {
+                    out.push(',');
+                }
+                out.push_str(&JsonValue::from(key.as_str()).to_string());
+                out.push(':');
+                write_json(&v.borrow(), out, depth + 1, max_depth)?;
+            }
+            out.push('}');
+        },
+        ValueCell::Enum(..) => return Err(EnvironmentError::InvalidType(value.clone()))
+    };
+
+    Ok(())
+}
+
+fn json_parse(_: FnInstance, parameters: FnParams, context: &mut Context) -> FnReturnType {
+    let handle = parameters[0].as_ref();
+    let value = serde_json::from_str(handle.as_string()?)
+        .ok()
+        .and_then(from_json);
+
+    // The document must respect the depth allowed for the VM values
+    if let Some(value) = &value {
+        value.calculate_depth(context.max_value_depth())?;
+    }
+
+    Ok(Some(ValueCell::Optional(value.map(Into::into))))
+}
+
+fn json_stringify(_: FnInstance, parameters: FnParams, context: &mut Context) -> FnReturnType {
+    let mut out = String::new();
+    write_json(parameters[0].as_ref().as_value(), &mut out, 0, context.max_value_depth())?;
+
+    // Paid once the size is known
+    context.increase_gas_usage(out.len() as u64 / BYTES_PER_GAS)?;
+    if out.len() > u32::MAX as usize {
+        return Err(EnvironmentError::OutOfMemory)
+    }
+
+    Ok(Some(Value::String(out).into()))
+}
+
+fn kind(zelf: FnInstance, _: FnParams, _: &mut Context) -> FnReturnType {
+    let kind = match zelf? {
+        ValueCell::Optional(None) => "null",
+        ValueCell::Default(Value::Boolean(_)) => "bool",
+        ValueCell::Default(Value::String(_)) => "string",
+        ValueCell::Default(_) => "number",
+        ValueCell::Array(_) | ValueCell::TypedArray(_) => "array",
+        ValueCell::Map(_) => "object",
+        v => return Err(EnvironmentError::InvalidType(v.clone()))
+    };
+
+    Ok(Some(Value::String(kind.to_owned()).into()))
+}
+
+fn is_null(zelf: FnInstance, _: FnParams, _: &mut Context) -> FnReturnType {
+    let null = matches!(zelf?, ValueCell::Optional(None));
+    Ok(Some(Value::Boolean(null).into()))
+}
+
+fn as_bool(zelf: FnInstance, _: FnParams, _: &mut Context) -> FnReturnType {
+    let value = zelf?.as_bool().ok()
+        .map(|v| Value::Boolean(v).into());
+    Ok(Some(ValueCell::Optional(value)))
+}
+
+fn as_u64(zelf: FnInstance, _: FnParams, _: &mut Context) -> FnReturnType {
+    let value = zelf?.as_u64().ok()
+        .map(|v| Value::U64(v).into());
+    Ok(Some(ValueCell::Optional(value)))
+}
+
+fn as_string(zelf: FnInstance, _: FnParams, _: &mut Context) -> FnReturnType {
+    let value = zelf?.as_string().ok()
+        .map(|v| Value::String(v.clone()).into());
+    Ok(Some(ValueCell::Optional(value)))
+}
+
+// Field of an object
+fn get(zelf: FnInstance, parameters: FnParams, _: &mut Context) -> FnReturnType {
+    let key = parameters[0].as_ref();
+    let value = match zelf? {
+        ValueCell::Map(map) => map.make_mut().get(key.as_value()).cloned(),
+        _ => None
+    };
+
+    Ok(Some(ValueCell::Optional(value)))
+}
+
+// Element of an array
+fn at(zelf: FnInstance, parameters: FnParams, _: &mut Context) -> FnReturnType {
+    let index = parameters[0].as_ref().as_u32()? as usize;
+    let value = match zelf? {
+        ValueCell::Array(values) => values.make_mut().get(index).map(|v| v.reference()),
+        ValueCell::TypedArray(array) => array.get(index).map(Into::into),
+        _ => None
+    };
+
+    Ok(Some(ValueCell::Optional(value)))
+}
+
+// Elements of an array or fields of an object
+fn len(zelf: FnInstance, _: FnParams, _: &mut Context) -> FnReturnType {
+    let len = match zelf? {
+        ValueCell::Array(values) => Some(values.len()),
+        ValueCell::TypedArray(array) => Some(array.len()),
+        ValueCell::Map(map) => Some(map.len()),
+        _ => None
+    };
+
+    Ok(Some(ValueCell::Optional(len.map(|v| Value::U32(v as u32).into()))))
+}
+
+fn keys(zelf: FnInstance, _: FnParams, context: &mut Context) -> FnReturnType {
+    let keys = match zelf? {
+        ValueCell::Map(map) => {
+            // we need to go through all elements, thus we increase the gas usage
+            context.increase_gas_usage((map.len() as u64) * 8)?;
+
+            let keys = map.keys()
+                .map(|key| key.clone().into())
+                .collect();
+            Some(ValueCell::Array(keys).into())
+        },
+        _ => None
+    };
+
+    Ok(Some(ValueCell::Optional(keys)))
+}
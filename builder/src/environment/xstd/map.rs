@@ -1,5 +1,5 @@
-use xelis_environment::{Context, EnvironmentError, FnInstance, FnParams, FnReturnType};
-use xelis_types::{Type, Value, ValueCell};
+use xelis_environment::{Context, EnvironmentError, FnInstance, FnParams, FnReturnType, VALUE_MEMORY_USAGE};
+use xelis_types::{IndexSet, SubValue, Type, Value, ValueCell};
 
 use crate::EnvironmentBuilder;
 
@@ -17,6 +17,18 @@ pub fn register(env: &mut EnvironmentBuilder) {
     env.register_native_function("values", Some(_type.clone()), vec![], values, 20, Some(Type::Array(Box::new(value_type.clone()))));
 }
 
+// The entries are always in the insertion order of their keys
+pub fn register_entries(env: &mut EnvironmentBuilder) {
+    let key_type = Type::T(0);
+    let value_type = Type::T(1);
+    let _type = Type::Map(Box::new(key_type.clone()), Box::new(value_type.clone()));
+    let entry_type = Type::Tuple(vec![key_type.clone(), value_type.clone()]);
+    env.register_native_function("entries", Some(_type.clone()), vec![], entries, 20, Some(Type::Array(Box::new(entry_type))));
+    env.register_native_function("merge", Some(_type.clone()), vec![("other", _type.clone())], merge, 30, None);
+    env.register_native_function("get_or_insert", Some(_type.clone()), vec![("key", key_type.clone()), ("default", value_type.clone())], get_or_insert, 30, Some(value_type));
+    env.register_native_function("retain", Some(_type), vec![("keys", Type::Array(Box::new(key_type)))], retain, 15, None);
+}
+
 fn len(zelf: FnInstance, _: FnParams, _: &mut Context) -> FnReturnType {
     let len = zelf?.as_map()?.len();
     Ok(Some(Value::U32(len as u32).into()))
@@ -29,7 +41,7 @@ fn contains_key(zelf: FnInstance, mut parameters: FnParams, _: &mut Context) ->
         return Err(EnvironmentError::InvalidKeyType);
     }
 
-    let contains = zelf?.as_map()?.contains_key(&k);
+    let contains = zelf?.as_map()?.contains_key(&*k);
     Ok(Some(Value::Boolean(contains).into()))
 }
 
@@ -40,13 +52,13 @@ fn get(zelf: FnInstance, mut parameters: FnParams, _: &mut Context) -> FnReturnT
         return Err(EnvironmentError::InvalidKeyType);
     }
 
-    let value = zelf?.as_map()?.get(&k).cloned();
+    let value = zelf?.as_mut_map()?.get(&*k).cloned();
     Ok(Some(ValueCell::Optional(value)))
 }
 
 fn insert(zelf: FnInstance, mut parameters: FnParams, context: &mut Context) -> FnReturnType {
-    let key = parameters.remove(0).into_owned();
-    if key.is_map() {
+    let key = parameters.remove(0);
+    if key.as_ref().is_map() {
         return Err(EnvironmentError::InvalidKeyType);
     }
 
@@ -57,19 +69,29 @@ fn insert(zelf: FnInstance, mut parameters: FnParams, context: &mut Context) ->
 
     let max_depth = context.max_value_depth() - 1;
     // Verify the depth of the key
-    key.calculate_depth(max_depth)?;
+    key.as_ref()
+        .calculate_depth(max_depth)?;
 
     let value = parameters.remove(0);
     // Verify the depth of the value
     value.as_ref()
         .calculate_depth(max_depth)?;
 
+    // Only a new entry allocates a key and a value,
+    // the key of an existing entry is kept
+    if !map.contains_key(key.as_ref().as_value()) {
+        context.increase_memory_usage(2 * VALUE_MEMORY_USAGE)?;
+        context.increase_stored_memory_usage(&key)?;
+    }
+    context.increase_stored_memory_usage(&value)?;
+
     let previous = map
-        .insert(key, value.into_owned().into());
+        .insert(key.into_owned(), value.into_owned().into());
+
     Ok(Some(ValueCell::Optional(previous)))
 }
 
-fn remove(zelf: FnInstance, mut parameters: FnParams, _: &mut Context) -> FnReturnType {
+fn remove(zelf: FnInstance, mut parameters: FnParams, context: &mut Context) -> FnReturnType {
     let key = parameters.remove(0);
 
     let k = key.as_ref();
@@ -77,13 +99,26 @@ fn remove(zelf: FnInstance, mut parameters: FnParams, _: &mut Context) -> FnRetu
         return Err(EnvironmentError::InvalidKeyType);
     }
 
-    let value = zelf?.as_mut_map()?
-        .remove(&k);
+    // Keep the insertion order of the remaining entries
+    let value = match zelf?.as_mut_map()?.shift_remove_full(&*k) {
+        Some((_, key, value)) => {
+            // The key is dropped, the value is returned
+            let freed = 2 * VALUE_MEMORY_USAGE + context.owned_memory_usage(&key)?;
+            context.decrease_memory_usage(freed);
+            Some(value)
+        },
+        None => None
+    };
+
     Ok(Some(ValueCell::Optional(value)))
 }
 
-fn clear(zelf: FnInstance, _: FnParams, _: &mut Context) -> FnReturnType {
-    zelf?.as_mut_map()?.clear();
+fn clear(zelf: FnInstance, _: FnParams, context: &mut Context) -> FnReturnType {
+    let zelf = zelf?;
+    let freed = context.owned_memory_usage(zelf)?;
+    zelf.as_mut_map()?.clear();
+    context.decrease_memory_usage(freed);
+
     Ok(None)
 }
 
@@ -92,12 +127,13 @@ fn keys(zelf: FnInstance, _: FnParams, context: &mut Context) -> FnReturnType {
 
     // we need to go through all elements, thus we increase the gas usage
     context.increase_gas_usage((map.len() as u64) * 8)?;
+    context.increase_memory_usage(map.len() * VALUE_MEMORY_USAGE)?;
 
     let keys = map.keys()
         .map(|key| key.clone().into())
         .collect::<Vec<_>>();
 
-    Ok(Some(ValueCell::Array(keys)))
+    Ok(Some(ValueCell::Array(keys.into())))
 }
 
 fn values(zelf: FnInstance, _: FnParams, context: &mut Context) -> FnReturnType {
@@ -105,10 +141,110 @@ fn values(zelf: FnInstance, _: FnParams, context: &mut Context) -> FnReturnType
 
     // we need to go through all elements, thus we increase the gas usage
     context.increase_gas_usage((map.len() as u64) * 5)?;
+    context.increase_memory_usage(map.len() * VALUE_MEMORY_USAGE)?;
 
     let values = map.values()
         .map(|v| v.reference())
         .collect::<Vec<_>>();
 
-    Ok(Some(ValueCell::Array(values)))
-}
\ No newline at end of file
+    Ok(Some(ValueCell::Array(values.into())))
+}
+// Each entry is a tuple of the key and its value
+fn entries(zelf: FnInstance, _: FnParams, context: &mut Context) -> FnReturnType {
+    let map = zelf?.as_mut_map()?;
+
+    context.increase_gas_usage((map.len() as u64) * 10)?;
+    // The tuple and the key copied for each entry
+    context.increase_memory_usage(map.len() * 2 * VALUE_MEMORY_USAGE)?;
+
+    let entries = map.iter()
+        .map(|(key, value)| ValueCell::Array(vec![key.clone().into(), value.reference()].into()).into())
+        .collect::<Vec<_>>();
+
+    Ok(Some(ValueCell::Array(entries.into())))
+}
+
+// Insert all the entries of the other map, its values replace the existing ones
+// The new keys are added in the order of the other map
+fn merge(zelf: FnInstance, mut parameters: FnParams, context: &mut Context) -> FnReturnType {
+    let handle = parameters.remove(0);
+    let other = handle.as_ref();
+    let other = other.as_map()?;
+    let map = zelf?.as_mut_map()?;
+
+    context.increase_gas_usage((other.len() as u64) * 10)?;
+    if map.len().saturating_add(other.len()) > u32::MAX as usize {
+        return Err(EnvironmentError::OutOfMemory)
+    }
+
+    // The values are copied like with insert
+    let mut added = 0;
+    for (key, value) in other {
+        if map.insert(key.clone(), value.borrow().clone().into()).is_none() {
+            added += 1;
+        }
+    }
+    context.increase_memory_usage(added * 2 * VALUE_MEMORY_USAGE)?;
+
+    Ok(None)
+}
+
+// Get a copy of the value of the key, the default is inserted if the key is missing
+fn get_or_insert(zelf: FnInstance, mut parameters: FnParams, context: &mut Context) -> FnReturnType {
+    let key = parameters.remove(0).into_owned();
+    if key.is_map() {
+        return Err(EnvironmentError::InvalidKeyType);
+    }
+
+    let map = zelf?.as_mut_map()?;
+    if let Some(value) = map.get(&key) {
+        return Ok(Some(value.borrow().clone()))
+    }
+
+    if map.len() >= u32::MAX as usize {
+        return Err(EnvironmentError::OutOfMemory)
+    }
+
+    let max_depth = context.max_value_depth() - 1;
+    key.calculate_depth(max_depth)?;
+
+    let default = parameters.remove(0).into_owned();
+    default.calculate_depth(max_depth)?;
+    context.increase_memory_usage(2 * VALUE_MEMORY_USAGE)?;
+
+    map.insert(key, default.clone().into());
+    Ok(Some(default))
+}
+
+// Keep only the entries whose key is in the array
+fn retain(zelf: FnInstance, mut parameters: FnParams, context: &mut Context) -> FnReturnType {
+    let handle = parameters.remove(0);
+    let array = handle.as_ref();
+    let len = array.array_len()?;
+    let map = zelf?.as_mut_map()?;
+
+    context.increase_gas_usage((len as u64 + map.len() as u64) * 5)?;
+    let keys = (0..len)
+        .filter_map(|i| array.get_array_element(i))
+        .collect::<IndexSet<_>>();
+
+    let mut freed = 0;
+    for (key, value) in map.iter().filter(|(key, _)| !keys.contains(*key)) {
+        freed += entry_memory_usage(context, key, value)?;
+    }
+
+    map.retain(|key, _| keys.contains(key));
+    context.decrease_memory_usage(freed);
+
+    Ok(None)
+}
+
+// Bytes freed by an entry dropped from a map
+fn entry_memory_usage(context: &Context, key: &ValueCell, value: &SubValue) -> Result<usize, EnvironmentError> {
+    let mut bytes = 2 * VALUE_MEMORY_USAGE + context.owned_memory_usage(key)?;
+    if !value.is_shared() {
+        bytes += context.owned_memory_usage(&value.borrow())?;
+    }
+
+    Ok(bytes)
+}
@@ -0,0 +1,113 @@
+use xelis_environment::{
+    FnInstance,
+    FnParams,
+    FnReturnType,
+    Context,
+};
+use xelis_types::{Type, Value, ValueCell, U256 as u256};
+
+use crate::EnvironmentBuilder;
+use super::{bytes_to_value, read_bytes};
+
+// Advanced operations on u256
+// Each operation that can overflow returns an optional instead
+pub fn register(env: &mut EnvironmentBuilder) {
+    let optional_u256 = Type::Optional(Box::new(Type::U256));
+
+    env.register_native_function("pow", Some(Type::U256), vec![("exp", Type::U32)], pow, 30, Some(optional_u256.clone()));
+    env.register_native_function("isqrt", Some(Type::U256), vec![], isqrt, 80, Some(Type::U256));
+    env.register_native_function("leading_zeros", Some(Type::U256), vec![], leading_zeros, 1, Some(Type::U32));
+    env.register_native_function("log2", Some(Type::U256), vec![], log2, 1, Some(Type::Optional(Box::new(Type::U32))));
+    env.register_native_function("mul_div", Some(Type::U256), vec![("mul", Type::U256), ("div", Type::U256)], mul_div, 60, Some(optional_u256));
+}
+
+// Conversions of u256 from and to bytes and text
+// Each parsing returns null on an invalid input
+pub fn register_conversions(env: &mut EnvironmentBuilder) {
+    let optional_u256 = Type::Optional(Box::new(Type::U256));
+    let bytes_type = Type::Array(Box::new(Type::U8));
+
+    env.register_static_function("from_bytes_be", Type::U256, vec![("bytes", bytes_type.clone())], from_bytes_be, 15, Some(optional_u256.clone()));
+    env.register_native_function("to_bytes_be", Some(Type::U256), vec![], to_bytes_be, 15, Some(bytes_type));
+    env.register_static_function("from_string", Type::U256, vec![("value", Type::String)], from_string, 25, Some(optional_u256));
+    env.register_native_function("to_string", Some(Type::U256), vec![("radix", Type::U32)], to_string, 25, Some(Type::Optional(Box::new(Type::String))));
+}
+
+fn pow(zelf: FnInstance, parameters: FnParams, _: &mut Context) -> FnReturnType {
+    let exp = parameters[0].as_ref().as_u32()?;
+    let value = zelf?.as_u256()?;
+
+    let inner = value.checked_pow(exp).map(|v| Value::U256(v).into());
+    Ok(Some(ValueCell::Optional(inner)))
+}
+
+fn isqrt(zelf: FnInstance, _: FnParams, _: &mut Context) -> FnReturnType {
+    let value = zelf?.as_u256()?;
+    Ok(Some(Value::U256(value.isqrt()).into()))
+}
+
+fn leading_zeros(zelf: FnInstance, _: FnParams, _: &mut Context) -> FnReturnType {
+    let value = zelf?.as_u256()?;
+    Ok(Some(Value::U32(value.leading_zeros()).into()))
+}
+
+// Base 2 logarithm rounded down, null for zero
+fn log2(zelf: FnInstance, _: FnParams, _: &mut Context) -> FnReturnType {
+    let value = zelf?.as_u256()?;
+
+    let inner = value.checked_ilog2().map(|v| Value::U32(v).into());
+    Ok(Some(ValueCell::Optional(inner)))
+}
+
+// Compute `self * mul / div` without overflow on the intermediate product
+// Returns null if the divisor is zero or if the result doesn't fit in a u256
+fn mul_div(zelf: FnInstance, parameters: FnParams, _: &mut Context) -> FnReturnType {
+    let mul = parameters[0].as_ref().as_u256()?;
+    let div = parameters[1].as_ref().as_u256()?;
+    let value = zelf?.as_u256()?;
+
+    let inner = value.mul_div(mul, div).map(|v| Value::U256(v).into());
+    Ok(Some(ValueCell::Optional(inner)))
+}
+
+// Big-endian bytes, shorter than 32 bytes are padded with leading zeros
+// Returns null above 32 bytes
+fn from_bytes_be(_: FnInstance, parameters: FnParams, _: &mut Context) -> FnReturnType {
+    let bytes = read_bytes(&parameters[0])?;
+    if bytes.len() > 32 {
+        return Ok(Some(ValueCell::Optional(None)))
+    }
+
+    let mut buffer = [0u8; 32];
+    buffer[32 - bytes.len()..].copy_from_slice(&bytes);
+
+    let inner = Value::U256(u256::from_be_bytes(buffer)).into();
+    Ok(Some(ValueCell::Optional(Some(inner))))
+}
+
+// Always 32 bytes, the most significant first
+fn to_bytes_be(zelf: FnInstance, _: FnParams, _: &mut Context) -> FnReturnType {
+    let value = zelf?.as_u256()?;
+    Ok(Some(bytes_to_value(&value.to_be_bytes())))
+}
+
+// Decimal digits only, without sign or separator
+fn from_string(_: FnInstance, parameters: FnParams, _: &mut Context) -> FnReturnType {
+    let value = parameters[0].as_ref().as_string()?
+        .parse::<u256>()
+        .ok()
+        .map(|v| Value::U256(v).into());
+
+    Ok(Some(ValueCell::Optional(value)))
+}
+
+// Lowercase digits in a radix from 2 to 36, null otherwise
+fn to_string(zelf: FnInstance, parameters: FnParams, _: &mut Context) -> FnReturnType {
+    let radix = parameters[0].as_ref().as_u32()?;
+    let value = zelf?.as_u256()?;
+
+    let inner = value.to_str_radix(radix)
+        .ok()
+        .map(|v| Value::String(v).into());
+    Ok(Some(ValueCell::Optional(inner)))
+}
@@ -1,11 +1,26 @@
 mod array;
 mod optional;
 mod string;
+mod string_builder;
 mod integer;
 mod range;
 mod map;
+mod math;
+mod crypto;
+mod random;
+mod encoding;
+mod json;
+mod storage;
+mod event;
+mod result;
+mod gas;
+mod iterator;
+mod contract;
+mod chain;
+mod compare;
+mod clone;
 
-use xelis_types::{Type, Value};
+use xelis_types::{Path, Type, Value, ValueCell};
 use xelis_environment::{
     EnvironmentError,
     FnInstance,
@@ -26,8 +41,47 @@ pub fn register(env: &mut EnvironmentBuilder) {
     env.register_native_function("println", None, vec![("value", Type::Any)], println, 1, None);
     env.register_native_function("debug", None, vec![("value", Type::Any)], debug, 1, None);
     env.register_native_function("panic", None, vec![("value", Type::Any)], panic, 1, Some(Type::Any));
-    env.register_native_function("assert", None, vec![("value", Type::Bool)], assert, 1, None);
-    env.register_native_function("is_same_ptr", None, vec![("value1", Type::Any), ("value2", Type::Any)], is_same_ptr, 5, Some(Type::Bool));
+    env.register_native_function("assert", None, vec![("value", Type::Bool)], assert, 1, None)
+        .set_debug_only(true);
+    // The sharing of the values is an implementation detail of the VM
+    env.register_native_function("is_same_ptr", None, vec![("value1", Type::Any), ("value2", Type::Any)], is_same_ptr, 5, Some(Type::Bool))
+        .set_deterministic(false);
+
+    // Registered last to keep the ids of the previous functions
+    string_builder::register(env);
+    string::register_regex(env);
+    string::register_format(env);
+    integer::register_arithmetic(env);
+    math::register(env);
+    crypto::register(env);
+    random::register(env);
+    encoding::register(env);
+    json::register(env);
+    storage::register(env);
+    event::register(env);
+    result::register(env);
+
+    env.register_native_function("assert", None, vec![("value", Type::Bool), ("message", Type::String)], assert_with_message, 1, None)
+        .set_debug_only(true);
+    env.register_native_function("assert_eq", None, vec![("left", Type::Any), ("right", Type::Any)], assert_eq, 5, None)
+        .set_debug_only(true);
+    gas::register(env);
+    map::register_entries(env);
+    iterator::register(env);
+    range::register_iterators(env);
+
+    // Unlike the assertions, always checked
+    env.register_native_function("require", None, vec![("condition", Type::Bool), ("message", Type::String)], require, 1, None);
+    // Stop the execution, roll back its state changes and return the value to the caller
+    env.register_native_function("revert", None, vec![("value", Type::Any)], revert, 1, Some(Type::Any));
+    contract::register(env);
+    math::register_conversions(env);
+    integer::register_formatting(env);
+    chain::register(env);
+    string::register_utf8(env);
+    string::register_padding(env);
+    compare::register(env);
+    clone::register(env);
 }
 
 fn println(_: FnInstance, parameters: FnParams, _: &mut Context) -> FnReturnType {
@@ -62,7 +116,69 @@ fn assert(_: FnInstance, parameters: FnParams, _: &mut Context) -> FnReturnType
     }
 }
 
+fn assert_with_message(_: FnInstance, parameters: FnParams, _: &mut Context) -> FnReturnType {
+    let value = parameters[0].as_ref().as_bool()?;
+    if value {
+        Ok(None)
+    } else {
+        let message = parameters[1].as_ref().as_string()?.clone();
+        Err(EnvironmentError::AssertionFailedWith(message))
+    }
+}
+
+fn require(_: FnInstance, parameters: FnParams, _: &mut Context) -> FnReturnType {
+    if parameters[0].as_ref().as_bool()? {
+        return Ok(None)
+    }
+
+    let message = parameters[1].as_ref().as_string()?.clone();
+    Err(EnvironmentError::Revert(Value::String(message).into()))
+}
+
+fn revert(_: FnInstance, mut parameters: FnParams, _: &mut Context) -> FnReturnType {
+    let param = parameters.remove(0);
+    let value = param.into_owned();
+
+    Err(EnvironmentError::Revert(value))
+}
+
+fn assert_eq(_: FnInstance, mut parameters: FnParams, context: &mut Context) -> FnReturnType {
+    // The depth is limited as a value may reference itself
+    let (equal, _) = parameters[0].as_ref().as_value()
+        .deep_eq(parameters[1].as_ref().as_value(), context.max_value_depth())?;
+    if equal {
+        return Ok(None)
+    }
+
+    let right = parameters.remove(1).into_owned();
+    let left = parameters.remove(0).into_owned();
+    Err(EnvironmentError::AssertionNotEqual(Box::new((left, right))))
+}
+
 fn is_same_ptr(_: FnInstance, parameters: FnParams, _: &mut Context) -> FnReturnType {
     let same = parameters[0].is_same_ptr(&parameters[1]);
     Ok(Some(Value::Boolean(same).into()))
-}
\ No newline at end of file
+}
+
+// Read a u8[] parameter
+fn read_bytes(param: &Path) -> Result<Vec<u8>, EnvironmentError> {
+    let handle = param.as_ref();
+    if let ValueCell::TypedArray(array) = handle.as_value() {
+        return array.as_bytes()
+            .map(<[u8]>::to_vec)
+            .ok_or(EnvironmentError::InvalidParameter)
+    }
+
+    let values = handle.as_vec()?;
+    let mut bytes = Vec::with_capacity(values.len());
+    for value in values {
+        bytes.push(value.borrow().as_u8()?);
+    }
+
+    Ok(bytes)
+}
+
+// Build a u8[] returned to the script
+fn bytes_to_value(bytes: &[u8]) -> ValueCell {
+    ValueCell::TypedArray(bytes.to_vec().into())
+}
@@ -0,0 +1,159 @@
+use xelis_environment::{Context, EnvironmentError};
+use xelis_types::{Type, Value, ValueCell, U256 as u256};
+use super::{
+    FnInstance,
+    FnParams,
+    FnReturnType,
+    EnvironmentBuilder
+};
+
+// Elements shuffled for one unit of gas
+const ELEMENTS_PER_GAS: u64 = 4;
+
+// Random is an opaque type for the scripts
+// Its value is the state of a xoshiro256** generator, derived from the seed set by the host
+// Nothing depends on the OS entropy, so an execution with the same seed is always reproducible
+pub fn register(env: &mut EnvironmentBuilder) {
+    let _type = Type::Opaque(env.register_opaque("Random"));
+
+    env.register_static_function("new", _type.clone(), vec![], new, 50, Some(_type.clone()));
+    env.register_native_function("next_u64", Some(_type.clone()), vec![], next_u64, 2, Some(Type::U64));
+    env.register_native_function("next_range", Some(_type.clone()), vec![("min", Type::U64), ("max", Type::U64)], next_range, 3, Some(Type::U64));
+    env.register_native_function("shuffle", Some(_type), vec![("array", Type::Array(Box::new(Type::Any)))], shuffle, 2, None)
+        .set_cost_fn(Some(shuffle_cost));
+}
+
+// Generators created during the execution
+// Each one has its own sequence even if they share the same seed
+struct RandomGenerators(u64);
+
+struct Xoshiro256 {
+    state: [u64; 4],
+}
+
+impl Xoshiro256 {
+    // The state must not be zero, which is the only fixed point of the generator
+    fn from_seed(seed: [u8; 32]) -> Self {
+        let mut state = [0u64; 4];
+        for (word, chunk) in state.iter_mut().zip(seed.chunks_exact(8)) {
+            *word = u64::from_le_bytes(chunk.try_into().unwrap());
+        }
+
+        if state == [0; 4] {
+            state[0] = 1;
+        }
+
+        Self { state }
+    }
+
+    fn from_value(value: &ValueCell) -> Result<Self, EnvironmentError> {
+        Ok(Self::from_seed(value.as_u256()?.to_le_bytes()))
+    }
+
+    fn to_value(&self) -> ValueCell {
+        let mut bytes = [0u8; 32];
+        for (chunk, word) in bytes.chunks_exact_mut(8).zip(self.state) {
+            chunk.copy_from_slice(&word.to_le_bytes());
+        }
+
+        Value::U256(u256::from_le_bytes(bytes)).into()
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        let s = &mut self.state;
+        let result = s[1].wrapping_mul(5).rotate_left(7).wrapping_mul(9);
+        let t = s[1] << 17;
+
+        s[2] ^= s[0];
+        s[3] ^= s[1];
+        s[1] ^= s[2];
+        s[0] ^= s[3];
+        s[2] ^= t;
+        s[3] = s[3].rotate_left(45);
+
+        result
+    }
+
+    // Uniform value in [0, bound)
+    // Values below the threshold are rejected to avoid a modulo bias
+    fn next_below(&mut self, bound: u64) -> u64 {
+        let threshold = bound.wrapping_neg() % bound;
+        loop {
+            let value = self.next_u64();
+            if value >= threshold {
+                return value % bound
+            }
+        }
+    }
+}
+
+// Cost based on the size of the shuffled array
+fn shuffle_cost(_: Option<&ValueCell>, parameters: &FnParams) -> u64 {
+    parameters.first()
+        .and_then(|p| p.as_ref().array_len().ok().map(|v| v as u64))
+        .unwrap_or(0) / ELEMENTS_PER_GAS
+}
+
+fn new(_: FnInstance, _: FnParams, context: &mut Context) -> FnReturnType {
+    let seed = *context.random_seed()
+        .ok_or(EnvironmentError::MissingRandomSeed)?;
+
+    let index = match context.get_mut::<RandomGenerators>() {
+        Some(generators) => {
+            generators.0 += 1;
+            generators.0
+        },
+        None => {
+            context.insert(RandomGenerators(0));
+            0
+        }
+    };
+
+    let mut hasher = blake3::Hasher::new();
+    hasher.update(&seed);
+    hasher.update(&index.to_le_bytes());
+
+    let generator = Xoshiro256::from_seed(*hasher.finalize().as_bytes());
+    Ok(Some(generator.to_value()))
+}
+
+fn next_u64(zelf: FnInstance, _: FnParams, _: &mut Context) -> FnReturnType {
+    let zelf = zelf?;
+    let mut generator = Xoshiro256::from_value(zelf)?;
+    let value = generator.next_u64();
+    *zelf = generator.to_value();
+
+    Ok(Some(Value::U64(value).into()))
+}
+
+// Value in [min, max)
+fn next_range(zelf: FnInstance, parameters: FnParams, _: &mut Context) -> FnReturnType {
+    let min = parameters[0].as_ref().as_u64()?;
+    let max = parameters[1].as_ref().as_u64()?;
+    if min >= max {
+        return Err(EnvironmentError::InvalidParameter)
+    }
+
+    let zelf = zelf?;
+    let mut generator = Xoshiro256::from_value(zelf)?;
+    let value = min + generator.next_below(max - min);
+    *zelf = generator.to_value();
+
+    Ok(Some(Value::U64(value).into()))
+}
+
+// Fisher-Yates shuffle of the array in place
+fn shuffle(zelf: FnInstance, mut parameters: FnParams, _: &mut Context) -> FnReturnType {
+    let zelf = zelf?;
+    let mut generator = Xoshiro256::from_value(zelf)?;
+
+    let mut handle = parameters[0].as_mut();
+    let array = handle.as_mut_vec()?;
+    for i in (1..array.len()).rev() {
+        let j = generator.next_below(i as u64 + 1) as usize;
+        array.swap(i, j);
+    }
+
+    *zelf = generator.to_value();
+    Ok(None)
+}
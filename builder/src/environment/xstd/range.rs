@@ -1,54 +1,40 @@
 use xelis_environment::{Context, EnvironmentError, FnInstance, FnParams, FnReturnType};
-use xelis_types::{Type, Value, ValueCell};
+use xelis_types::{LazyIterator, Type, Value, ValueCell, ValueError};
 use paste::paste;
 
 use crate::EnvironmentBuilder;
 
 macro_rules! contains {
-    ($t: ident, $start: expr, $end: expr, $value: expr) => {
+    ($t: ident, $start: expr, $end: expr, $value: expr, $inclusive: expr) => {
         paste! {
             {
                 let start = $start.[<as_ $t>]()?;
                 let end = $end.[<as_ $t>]()?;
                 let value = $value.[<as_ $t>]()?;
-                Value::Boolean((start..end).contains(&value)).into()
-            }
-        }
-    };
-}
-
-macro_rules! collect {
-    ($t: ident, $start: expr, $end: expr, $type: ident, $context: expr) => {
-        paste! {
-            {
-                let start = $start.[<as_ $type>]()?;
-                let end = $end.[<as_ $type>]()?;
-
-                if start >= end {
-                    ValueCell::Array(Vec::new())
+                let contains = if $inclusive {
+                    (start..=end).contains(&value)
                 } else {
-                    let diff = end - start;
-                    if diff > u32::MAX as _ {
-                        return Err(EnvironmentError::RangeTooLarge);
-                    }
-
-                    $context.increase_gas_usage(diff as u64 * 8)?;
-
-                    let vec = (start..end).map(|i| Value::$t(i).into()).collect();
-                    ValueCell::Array(vec)
-                }
+                    (start..end).contains(&value)
+                };
+                Value::Boolean(contains).into()
             }
         }
     };
 }
 
+// An inclusive range counting all the values of its type overflows it
 macro_rules! count {
-    ($t: ident, $start: expr, $end: expr, $type: ident) => {
+    ($t: ident, $start: expr, $end: expr, $type: ident, $inclusive: expr) => {
         paste! {
             {
                 let start = $start.[<as_ $type>]()?;
                 let end = $end.[<as_ $type>]()?;
-                let count = end.checked_sub(start).unwrap_or(Default::default());
+                let count = match end.checked_sub(start) {
+                    Some(count) if $inclusive => count.checked_add(1u8.into())
+                        .ok_or(ValueError::Overflow)?,
+                    Some(count) => count,
+                    None => Default::default()
+                };
                 Value::$t(count).into()
             }
         }
@@ -64,80 +50,96 @@ pub fn register(env: &mut EnvironmentBuilder) {
     env.register_native_function("count", Some(_type.clone()), vec![], count, 5, Some(Type::T(0)));
 }
 
+// Registered after the iterators to keep the ids of the previous functions
+pub fn register_iterators(env: &mut EnvironmentBuilder) {
+    let _type = Type::Range(Box::new(Type::T(0)));
+    let iterator = Type::Iterator(Box::new(Type::T(0)));
+    env.register_native_function("len", Some(_type.clone()), vec![], len, 5, Some(Type::U64));
+    env.register_native_function("step_by", Some(_type.clone()), vec![("n", Type::U32)], step_by, 5, Some(iterator.clone()));
+    env.register_native_function("rev", Some(_type), vec![], rev, 5, Some(iterator));
+}
+
+fn as_iterator(zelf: &ValueCell) -> Result<LazyIterator, EnvironmentError> {
+    let (start, end, _type, inclusive) = zelf.as_range()?;
+    Ok(LazyIterator::from_range(start, end, _type.clone(), inclusive)?)
+}
+
 fn contains(zelf: FnInstance, mut parameters: FnParams, _: &mut Context) -> FnReturnType {
     let value = parameters.remove(0);
     let zelf = zelf?;
-    let (start, end, _type) = zelf.as_range()?;
+    let (start, end, _type, inclusive) = zelf.as_range()?;
 
     let value = value.as_ref();
     Ok(Some(match _type {
-        Type::U8 => contains!(u8, start, end, value),
-        Type::U16 => contains!(u16, start, end, value),
-        Type::U32 => contains!(u32, start, end, value),
-        Type::U64 => contains!(u64, start, end, value),
-        Type::U128 => contains!(u128, start, end, value),
-        Type::U256 => contains!(u256, start, end, value),
+        Type::U8 => contains!(u8, start, end, value, inclusive),
+        Type::U16 => contains!(u16, start, end, value, inclusive),
+        Type::U32 => contains!(u32, start, end, value, inclusive),
+        Type::U64 => contains!(u64, start, end, value, inclusive),
+        Type::U128 => contains!(u128, start, end, value, inclusive),
+        Type::U256 => contains!(u256, start, end, value, inclusive),
         _ => return Err(EnvironmentError::InvalidType(zelf.clone()))
     }))
 }
 
 fn collect(zelf: FnInstance, _: FnParams, context: &mut Context) -> FnReturnType {
-    let zelf = zelf?;
-    let (start, end, _type) = zelf.as_range()?;
-    Ok(Some(match _type {
-        Type::U8 => collect!(U8, start, end, u8, context),
-        Type::U16 => collect!(U16, start, end, u16, context),
-        Type::U32 => collect!(U32, start, end, u32, context),
-        Type::U64 => collect!(U64, start, end, u64, context),
-        Type::U128 => collect!(U128, start, end, u128, context),
-        Type::U256 => {
-            let start = start.as_u256()?;
-            let end = end.as_u256()?;
-            let (diff, overflow) = end.overflowing_sub(start);
-
-            let mut vec = Vec::new();
-            if !overflow {
-                let diff = diff.as_u64().ok_or(EnvironmentError::InvalidParameter)?;
-                if diff > u32::MAX as u64 {
-                    return Err(EnvironmentError::RangeTooLarge);
-                }
-
-                context.increase_gas_usage(diff as u64 * 8)?;
-
-                for i in 0..diff {
-                    vec.push(Value::U256(i.into()).into());
-                }
-            }
+    let mut iter = as_iterator(zelf?)?;
+    if iter.len() > u32::MAX as u64 {
+        return Err(EnvironmentError::RangeTooLarge);
+    }
 
-            ValueCell::Array(vec)
-        }
-        _ => return Err(EnvironmentError::InvalidType(zelf.clone()))
-    }))
+    context.increase_gas_usage(iter.len() * 8)?;
+
+    let mut vec = Vec::with_capacity(iter.len() as usize);
+    while let Some(value) = iter.next_value()? {
+        vec.push(value);
+    }
+
+    Ok(Some(ValueCell::Array(vec.into())))
 }
 
 fn max(zelf: FnInstance, _: FnParams, _: &mut Context) -> FnReturnType {
     let zelf = zelf?;
-    let (_, end, _) = zelf.as_range()?;
+    let (_, end, _, _) = zelf.as_range()?;
     Ok(Some(end.clone().into()))
 }
 
 fn min(zelf: FnInstance, _: FnParams, _: &mut Context) -> FnReturnType {
     let zelf = zelf?;
-    let (start, _, _) = zelf.as_range()?;
+    let (start, _, _, _) = zelf.as_range()?;
     Ok(Some(start.clone().into()))
 }
 
 fn count(zelf: FnInstance, _: FnParams, _: &mut Context) -> FnReturnType {
     let zelf = zelf?;
-    let (start, end, _type) = zelf.as_range()?;
+    let (start, end, _type, inclusive) = zelf.as_range()?;
 
     Ok(Some(match _type {
-        Type::U8 => count!(U8, start, end, u8),
-        Type::U16 => count!(U16, start, end, u16),
-        Type::U32 => count!(U32, start, end, u32),
-        Type::U64 => count!(U64, start, end, u64),
-        Type::U128 => count!(U128, start, end, u128),
-        Type::U256 => count!(U256, start, end, u256),
+        Type::U8 => count!(U8, start, end, u8, inclusive),
+        Type::U16 => count!(U16, start, end, u16, inclusive),
+        Type::U32 => count!(U32, start, end, u32, inclusive),
+        Type::U64 => count!(U64, start, end, u64, inclusive),
+        Type::U128 => count!(U128, start, end, u128, inclusive),
+        Type::U256 => count!(U256, start, end, u256, inclusive),
         _ => return Err(EnvironmentError::InvalidType(zelf.clone()))
     }))
+}
+
+// Unlike count, the length doesn't depend on the range type
+fn len(zelf: FnInstance, _: FnParams, _: &mut Context) -> FnReturnType {
+    let len = as_iterator(zelf?)?.len();
+    Ok(Some(Value::U64(len).into()))
+}
+
+fn step_by(zelf: FnInstance, parameters: FnParams, _: &mut Context) -> FnReturnType {
+    let n = parameters[0].as_ref().as_u32()?;
+    let mut iter = as_iterator(zelf?)?;
+    iter.step_by(n as u64)?;
+    Ok(Some(ValueCell::Iterator(Box::new(iter))))
+}
+
+// Iterate from the end of the range to its start
+fn rev(zelf: FnInstance, _: FnParams, _: &mut Context) -> FnReturnType {
+    let mut iter = as_iterator(zelf?)?;
+    iter.rev()?;
+    Ok(Some(ValueCell::Iterator(Box::new(iter))))
 }
\ No newline at end of file
@@ -0,0 +1,74 @@
+use xelis_types::{Type, Value, ValueCell, ValueError};
+use xelis_environment::{
+    Context,
+    FnInstance,
+    FnParams,
+    FnReturnType
+};
+use super::EnvironmentBuilder;
+
+// Variant ids of the `Result` enum
+const OK_VARIANT: u8 = 0;
+const ERR_VARIANT: u8 = 1;
+
+// `Result<T, E>` is stored as the built-in `Result` enum at runtime
+// `Ok` holds the value as its only field, `Err` holds the error
+pub fn register(env: &mut EnvironmentBuilder) {
+    env.register_enum("Result", vec![
+        ("Ok", vec![("value", Type::T(0))]),
+        ("Err", vec![("error", Type::T(1))])
+    ]);
+
+    let _type = Type::Result(Box::new(Type::T(0)), Box::new(Type::T(1)));
+    env.register_native_function("is_ok", Some(_type.clone()), vec![], is_ok, 1, Some(Type::Bool));
+    env.register_native_function("is_err", Some(_type.clone()), vec![], is_err, 1, Some(Type::Bool));
+    env.register_native_function("unwrap", Some(_type.clone()), vec![], unwrap, 1, Some(Type::T(0)));
+    env.register_native_function("unwrap_err", Some(_type.clone()), vec![], unwrap_err, 1, Some(Type::T(1)));
+    env.register_native_function("unwrap_or", Some(_type), vec![("default", Type::T(0))], unwrap_or, 1, Some(Type::T(0)));
+}
+
+// Returns the variant id and its field
+// The result is not consumed, the field is copied
+fn read_variant(zelf: &ValueCell) -> Result<(u8, ValueCell), ValueError> {
+    match zelf {
+        ValueCell::Enum(fields, _type) if fields.len() == 1 => Ok((_type.variant_id(), fields[0].reference().into_owned())),
+        _ => Err(ValueError::InvalidValueCell(zelf.clone(), Type::Result(Box::new(Type::Any), Box::new(Type::Any))))
+    }
+}
+
+fn variant_id(zelf: &ValueCell) -> Result<u8, ValueError> {
+    match zelf {
+        ValueCell::Enum(_, _type) => Ok(_type.variant_id()),
+        _ => Err(ValueError::InvalidValueCell(zelf.clone(), Type::Result(Box::new(Type::Any), Box::new(Type::Any))))
+    }
+}
+
+fn is_ok(zelf: FnInstance, _: FnParams, _: &mut Context) -> FnReturnType {
+    Ok(Some(Value::Boolean(variant_id(zelf?)? == OK_VARIANT).into()))
+}
+
+fn is_err(zelf: FnInstance, _: FnParams, _: &mut Context) -> FnReturnType {
+    Ok(Some(Value::Boolean(variant_id(zelf?)? == ERR_VARIANT).into()))
+}
+
+fn unwrap(zelf: FnInstance, _: FnParams, _: &mut Context) -> FnReturnType {
+    match read_variant(zelf?)? {
+        (OK_VARIANT, value) => Ok(Some(value)),
+        _ => Err(ValueError::ResultIsErr.into())
+    }
+}
+
+fn unwrap_err(zelf: FnInstance, _: FnParams, _: &mut Context) -> FnReturnType {
+    match read_variant(zelf?)? {
+        (ERR_VARIANT, error) => Ok(Some(error)),
+        _ => Err(ValueError::ResultIsOk.into())
+    }
+}
+
+fn unwrap_or(zelf: FnInstance, mut parameters: FnParams, _: &mut Context) -> FnReturnType {
+    let default = parameters.remove(0);
+    match read_variant(zelf?)? {
+        (OK_VARIANT, value) => Ok(Some(value)),
+        _ => Ok(Some(default.into_owned()))
+    }
+}
@@ -0,0 +1,119 @@
+use xelis_environment::{Context, EnvironmentError, StorageProvider};
+use xelis_types::{SubValue, Type, Value, ValueCell, ValueError};
+use super::{
+    FnInstance,
+    FnParams,
+    FnReturnType,
+    EnvironmentBuilder
+};
+
+// Bytes read from the storage for one unit of gas
+const READ_BYTES_PER_GAS: u64 = 8;
+// Gas for each byte written in the storage
+const WRITE_GAS_PER_BYTE: u64 = 1;
+// Gas refunded for an entry deleted from the storage
+const DELETE_REFUND: u64 = 100;
+
+// Storage is an opaque type for the scripts, only its static functions are used
+// The entries are stored by the host through the StorageProvider set in the Context
+// Keys are strings stored as their UTF-8 bytes, values are encoded using `ValueCell::to_bytes`
+pub fn register(env: &mut EnvironmentBuilder) {
+    let _type = Type::Opaque(env.register_opaque("Storage"));
+
+    env.register_static_function("get", _type.clone(), vec![("key", Type::String)], get, 50, Some(Type::Optional(Box::new(Type::Any))));
+    env.register_static_function("has", _type.clone(), vec![("key", Type::String)], has, 50, Some(Type::Bool));
+    env.register_static_function("set", _type.clone(), vec![("key", Type::String), ("value", Type::Any)], set, 100, None)
+        .set_mutating(true);
+    env.register_static_function("delete", _type.clone(), vec![("key", Type::String)], delete, 50, Some(Type::Bool))
+        .set_mutating(true);
+    // The keys are iterated in the order of the host storage
+    env.register_static_function("keys", _type, vec![("prefix", Type::String)], keys, 50, Some(Type::Array(Box::new(Type::String))))
+        .set_deterministic(false);
+}
+
+fn read_cost(len: usize) -> u64 {
+    len as u64 / READ_BYTES_PER_GAS
+}
+
+fn get(_: FnInstance, parameters: FnParams, context: &mut Context) -> FnReturnType {
+    let handle = parameters[0].as_ref();
+    let key = handle.as_string()?;
+
+    let value = context.storage_mut()?.get(key.as_bytes())?;
+    let value = match value {
+        Some(bytes) => {
+            context.increase_gas_usage(read_cost(key.len() + bytes.len()))?;
+            Some(ValueCell::from_bytes(&bytes, context.max_value_depth())?.into())
+        },
+        None => {
+            context.increase_gas_usage(read_cost(key.len()))?;
+            None
+        }
+    };
+
+    Ok(Some(ValueCell::Optional(value)))
+}
+
+fn has(_: FnInstance, parameters: FnParams, context: &mut Context) -> FnReturnType {
+    let handle = parameters[0].as_ref();
+    let key = handle.as_string()?;
+
+    context.increase_gas_usage(read_cost(key.len()))?;
+    let found = context.storage_mut()?.get(key.as_bytes())?.is_some();
+
+    Ok(Some(Value::Boolean(found).into()))
+}
+
+// Paid before writing so a failed execution never touches the storage
+fn set(_: FnInstance, parameters: FnParams, context: &mut Context) -> FnReturnType {
+    let handle = parameters[0].as_ref();
+    let key = handle.as_string()?;
+    let value = parameters[1].as_ref()
+        .as_value()
+        .to_bytes(context.max_value_depth())?;
+
+    context.increase_gas_usage((key.len() + value.len()) as u64 * WRITE_GAS_PER_BYTE)?;
+    context.storage_set(key.as_bytes(), value)?;
+
+    Ok(None)
+}
+
+fn delete(_: FnInstance, parameters: FnParams, context: &mut Context) -> FnReturnType {
+    let handle = parameters[0].as_ref();
+    let key = handle.as_string()?;
+
+    context.increase_gas_usage(key.len() as u64 * WRITE_GAS_PER_BYTE)?;
+    let deleted = context.storage_delete(key.as_bytes())?;
+    if deleted {
+        context.refund_gas(DELETE_REFUND);
+    }
+
+    Ok(Some(Value::Boolean(deleted).into()))
+}
+
+// All the keys starting with the prefix, in the order given by the host
+fn keys(_: FnInstance, parameters: FnParams, context: &mut Context) -> FnReturnType {
+    let handle = parameters[0].as_ref();
+    let prefix = handle.as_string()?;
+
+    // The storage is taken out of the context to charge the gas while iterating
+    // It is always put back, even on error
+    let storage = context.take_storage()
+        .ok_or(EnvironmentError::MissingStorage)?;
+    let result = collect_keys(&*storage, prefix.as_bytes(), context);
+    context.set_storage(storage);
+
+    Ok(Some(ValueCell::Array(result?.into())))
+}
+
+fn collect_keys(storage: &dyn StorageProvider, prefix: &[u8], context: &mut Context) -> Result<Vec<SubValue>, EnvironmentError> {
+    let mut keys = Vec::new();
+    for (key, _) in storage.iterate_prefix(prefix)? {
+        context.increase_gas_usage(read_cost(key.len()) + 1)?;
+        let key = String::from_utf8(key)
+            .map_err(|_| ValueError::InvalidEncoding)?;
+        keys.push(Value::String(key).into());
+    }
+
+    Ok(keys)
+}
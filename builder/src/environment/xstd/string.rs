@@ -1,5 +1,7 @@
-use xelis_environment::Context;
-use xelis_types::{Type, Value, ValueCell};
+use std::collections::HashMap;
+use regex::{NoExpand, Regex, RegexBuilder};
+use xelis_environment::{Context, EnvironmentError, VALUE_MEMORY_USAGE};
+use xelis_types::{Path, Type, Value, ValueCell};
 use super::{
     FnInstance,
     FnParams,
@@ -7,29 +9,228 @@ use super::{
     EnvironmentBuilder
 };
 
+// Bytes processed for one unit of gas
+const BYTES_PER_GAS: u64 = 32;
+
+// Maximum size of a formatted primitive value, reached by the largest u256
+const MAX_FORMATTED_VALUE_LEN: u64 = 78;
+
+// Maximum size in bytes of a string built by padding or repetition
+const MAX_BUILT_STRING_LEN: usize = 64 * 1024;
+
+// Maximum length of a regex pattern
+const MAX_REGEX_PATTERN_LEN: usize = 256;
+// Maximum size in bytes of a compiled regex
+const MAX_REGEX_SIZE: usize = 64 * 1024;
+// Maximum size in bytes of the lazy DFA cache of a regex
+const MAX_REGEX_DFA_SIZE: usize = 256 * 1024;
+// Maximum nesting of groups and repetitions in a pattern
+const MAX_REGEX_NEST: u32 = 32;
+// Maximum compiled patterns kept in the cache
+const MAX_CACHED_REGEX: usize = 64;
+// Pattern bytes compiled for one unit of gas
+const REGEX_PATTERN_BYTES_PER_GAS: u64 = 4;
+
 pub fn register(env: &mut EnvironmentBuilder) {
     // String
     env.register_native_function("len", Some(Type::String), vec![], len, 1, Some(Type::U32));
     env.register_native_function("trim", Some(Type::String), vec![], trim, 1, Some(Type::String));
     env.register_native_function("contains", Some(Type::String), vec![("value", Type::String)], contains, 1, Some(Type::Bool));
     env.register_native_function("contains_ignore_case", Some(Type::String), vec![("value", Type::String)], contains_ignore_case, 1, Some(Type::Bool));
-    env.register_native_function("to_uppercase", Some(Type::String), vec![], to_uppercase, 1, Some(Type::String));
-    env.register_native_function("to_lowercase", Some(Type::String), vec![], to_lowercase, 1, Some(Type::String));
-    env.register_native_function("to_bytes", Some(Type::String), vec![], to_bytes, 5, Some(Type::Array(Box::new(Type::U8))));
+    env.register_native_function("to_uppercase", Some(Type::String), vec![], to_uppercase, 1, Some(Type::String))
+        .set_cost_fn(Some(instance_len_cost));
+    env.register_native_function("to_lowercase", Some(Type::String), vec![], to_lowercase, 1, Some(Type::String))
+        .set_cost_fn(Some(instance_len_cost));
+    env.register_native_function("to_bytes", Some(Type::String), vec![], to_bytes, 5, Some(Type::Array(Box::new(Type::U8))))
+        .set_cost_fn(Some(instance_len_cost));
     env.register_native_function("index_of", Some(Type::String), vec![("value", Type::String)], index_of, 3, Some(Type::Optional(Box::new(Type::U32))));
     env.register_native_function("last_index_of", Some(Type::String), vec![("value", Type::String)], last_index_of, 3, Some(Type::Optional(Box::new(Type::U32))));
-    env.register_native_function("replace", Some(Type::String), vec![("from", Type::String), ("to", Type::String)], replace, 5, Some(Type::String));
+    env.register_native_function("replace", Some(Type::String), vec![("from", Type::String), ("to", Type::String)], replace, 5, Some(Type::String))
+        .set_cost_fn(Some(replace_cost));
     env.register_native_function("starts_with", Some(Type::String), vec![("value", Type::String)], starts_with, 3, Some(Type::Bool));
     env.register_native_function("ends_with", Some(Type::String), vec![("value", Type::String)], ends_with, 3, Some(Type::Bool));
-    env.register_native_function("split", Some(Type::String), vec![("at", Type::String)], split, 5, Some(Type::Array(Box::new(Type::String))));
+    env.register_native_function("split", Some(Type::String), vec![("at", Type::String)], split, 5, Some(Type::Array(Box::new(Type::String))))
+        .set_cost_fn(Some(instance_len_cost));
     env.register_native_function("char_at", Some(Type::String), vec![("index", Type::U32)], char_at, 1, Some(Type::Optional(Box::new(Type::String))));
 
     env.register_native_function("is_empty", Some(Type::String), vec![], is_empty, 1, Some(Type::Bool));
-    env.register_native_function("matches", Some(Type::String), vec![("pattern", Type::String)], string_matches, 50, Some(Type::Array(Box::new(Type::String))));
+    env.register_native_function("matches", Some(Type::String), vec![("pattern", Type::String)], string_matches, 50, Some(Type::Array(Box::new(Type::String))))
+        .set_cost_fn(Some(instance_len_cost));
     env.register_native_function("substring", Some(Type::String), vec![("value", Type::U32)], string_substring, 3, Some(Type::Optional(Box::new(Type::String))));
     env.register_native_function("substring", Some(Type::String), vec![("value", Type::U32), ("value", Type::U32)], string_substring_range, 3, Some(Type::Optional(Box::new(Type::String))));
 }
 
+// Strings are UTF-8: `len`, `index_of` and `substring` count bytes while `char_at` counts chars
+// The functions below make the unit explicit, a char being a unicode scalar value and not a grapheme
+pub fn register_utf8(env: &mut EnvironmentBuilder) {
+    let optional_string = Type::Optional(Box::new(Type::String));
+
+    env.register_native_function("len_bytes", Some(Type::String), vec![], len, 1, Some(Type::U32));
+    env.register_native_function("len_chars", Some(Type::String), vec![], len_chars, 1, Some(Type::U32))
+        .set_cost_fn(Some(instance_len_cost));
+    env.register_native_function("chars", Some(Type::String), vec![], chars, 5, Some(Type::Array(Box::new(Type::String))))
+        .set_cost_fn(Some(instance_len_cost));
+    env.register_native_function("substring_chars", Some(Type::String), vec![("start", Type::U32), ("end", Type::U32)], substring_chars, 3, Some(optional_string))
+        .set_cost_fn(Some(instance_len_cost));
+}
+
+// Padding, repetition and trimming, registered after the UTF-8 functions to keep their ids
+// The padding lengths are in chars, the fill string is repeated and cut to reach it
+pub fn register_padding(env: &mut EnvironmentBuilder) {
+    let pair_type = Type::Tuple(vec![Type::String, Type::String]);
+
+    env.register_native_function("pad_start", Some(Type::String), vec![("len", Type::U32), ("fill", Type::String)], pad_start, 3, Some(Type::String))
+        .set_cost_fn(Some(pad_cost));
+    env.register_native_function("pad_end", Some(Type::String), vec![("len", Type::U32), ("fill", Type::String)], pad_end, 3, Some(Type::String))
+        .set_cost_fn(Some(pad_cost));
+    env.register_native_function("repeat", Some(Type::String), vec![("count", Type::U32)], repeat, 3, Some(Type::String))
+        .set_cost_fn(Some(repeat_cost));
+    env.register_native_function("trim_start", Some(Type::String), vec![], trim_start, 1, Some(Type::String));
+    env.register_native_function("trim_end", Some(Type::String), vec![], trim_end, 1, Some(Type::String));
+    env.register_native_function("split_once", Some(Type::String), vec![("at", Type::String)], split_once, 5, Some(Type::Optional(Box::new(pair_type))))
+        .set_cost_fn(Some(instance_len_cost));
+}
+
+// Regex functions are registered separately to keep the ids of the functions registered after the strings
+pub fn register_regex(env: &mut EnvironmentBuilder) {
+    env.register_native_function("regex_match", Some(Type::String), vec![("pattern", Type::String)], regex_match, 10, Some(Type::Bool))
+        .set_cost_fn(Some(regex_cost));
+    env.register_native_function("regex_find_all", Some(Type::String), vec![("pattern", Type::String)], regex_find_all, 10, Some(Type::Array(Box::new(Type::String))))
+        .set_cost_fn(Some(regex_cost));
+    env.register_native_function("regex_replace", Some(Type::String), vec![("pattern", Type::String), ("to", Type::String)], regex_replace, 10, Some(Type::String))
+        .set_cost_fn(Some(regex_replace_cost));
+}
+
+// format("{} has {} coins", name, amount)
+// Each `{}` is replaced by the next value, `{{` and `}}` are escaped braces
+pub fn register_format(env: &mut EnvironmentBuilder) {
+    env.register_variadic_function("format", None, vec![("format", Type::String)], format, 5, Some(Type::String))
+        .set_cost_fn(Some(format_cost));
+}
+
+// Cost based on the maximum size of the formatted string
+fn format_cost(_: Option<&ValueCell>, parameters: &FnParams) -> u64 {
+    let len = parameters.iter()
+        .map(|p| match p.as_ref().as_value() {
+            ValueCell::Default(Value::String(s)) => s.len() as u64,
+            _ => MAX_FORMATTED_VALUE_LEN
+        })
+        .fold(0u64, u64::saturating_add);
+
+    len / BYTES_PER_GAS
+}
+
+// Cost based on the maximum size of the padded string
+fn pad_cost(zelf: Option<&ValueCell>, parameters: &FnParams) -> u64 {
+    let len = zelf.and_then(|v| v.as_string().ok())
+        .map_or(0, |s| s.len() as u64);
+    let fill = parameters.get(1)
+        .and_then(|p| p.as_ref().as_string().ok().map(|s| s.len() as u64))
+        .unwrap_or(0);
+    let chars = parameters.first()
+        .and_then(|p| p.as_ref().as_u32().ok())
+        .unwrap_or(0) as u64;
+
+    len.saturating_add(chars.saturating_mul(fill)) / BYTES_PER_GAS
+}
+
+// Cost based on the size of the repeated string
+fn repeat_cost(zelf: Option<&ValueCell>, parameters: &FnParams) -> u64 {
+    let len = zelf.and_then(|v| v.as_string().ok())
+        .map_or(0, |s| s.len() as u64);
+    let count = parameters.first()
+        .and_then(|p| p.as_ref().as_u32().ok())
+        .unwrap_or(0) as u64;
+
+    len.saturating_mul(count) / BYTES_PER_GAS
+}
+
+// Cost based on the size of the string instance
+fn instance_len_cost(zelf: Option<&ValueCell>, _: &FnParams) -> u64 {
+    zelf.and_then(|v| v.as_string().ok())
+        .map_or(0, |s| s.len() as u64 / BYTES_PER_GAS)
+}
+
+// Cost based on the maximum size of the replaced string
+fn replace_cost(zelf: Option<&ValueCell>, parameters: &FnParams) -> u64 {
+    let len_of = |p: Option<&Path>| p.and_then(|p| p.as_ref().as_string().ok().map(|s| s.len() as u64)).unwrap_or(0);
+
+    let len = zelf.and_then(|v| v.as_string().ok())
+        .map_or(0, |s| s.len() as u64);
+    let from = len_of(parameters.first());
+    let to = len_of(parameters.get(1));
+
+    // An empty pattern matches between each byte
+    let matches = len.checked_div(from).unwrap_or(len + 1);
+    len.saturating_add(matches.saturating_mul(to)) / BYTES_PER_GAS
+}
+
+// Cost of compiling the pattern and scanning the string instance
+// The compilation is always paid, even if the pattern was cached,
+// so the gas used doesn't depend on the previous executions
+// Matching time is linear in the size of the string, scaled by the pattern size
+fn regex_cost(zelf: Option<&ValueCell>, parameters: &FnParams) -> u64 {
+    let len = zelf.and_then(|v| v.as_string().ok())
+        .map_or(0, |s| s.len() as u64);
+    let pattern = parameters.first()
+        .and_then(|p| p.as_ref().as_string().ok().map(|s| s.len() as u64))
+        .unwrap_or(0);
+
+    let scan = len.saturating_mul(1 + pattern / 16) / BYTES_PER_GAS;
+    scan.saturating_add(pattern / REGEX_PATTERN_BYTES_PER_GAS)
+}
+
+// Same as the regex cost plus the maximum size of the replaced string
+fn regex_replace_cost(zelf: Option<&ValueCell>, parameters: &FnParams) -> u64 {
+    let len = zelf.and_then(|v| v.as_string().ok())
+        .map_or(0, |s| s.len() as u64);
+    let to = parameters.get(1)
+        .and_then(|p| p.as_ref().as_string().ok().map(|s| s.len() as u64))
+        .unwrap_or(0);
+
+    // An empty match can happen between each byte
+    let output = len.saturating_add((len + 1).saturating_mul(to)) / BYTES_PER_GAS;
+    regex_cost(zelf, parameters).saturating_add(output)
+}
+
+// Compiled patterns of an execution, stored in the context
+#[derive(Default)]
+struct RegexCache {
+    patterns: HashMap<String, Regex>,
+}
+
+// Compile a pattern with the limits, or get it from the cache
+fn get_regex<'a>(context: &'a mut Context, pattern: &str) -> Result<&'a Regex, EnvironmentError> {
+    if pattern.len() > MAX_REGEX_PATTERN_LEN {
+        return Err(EnvironmentError::InvalidRegex(format!("pattern is longer than {} bytes", MAX_REGEX_PATTERN_LEN)))
+    }
+
+    if !context.contains::<RegexCache>() {
+        context.insert(RegexCache::default());
+    }
+
+    let cache = context.get_mut::<RegexCache>()
+        .ok_or(EnvironmentError::InvalidFnCall)?;
+
+    if !cache.patterns.contains_key(pattern) {
+        let regex = RegexBuilder::new(pattern)
+            .size_limit(MAX_REGEX_SIZE)
+            .dfa_size_limit(MAX_REGEX_DFA_SIZE)
+            .nest_limit(MAX_REGEX_NEST)
+            .build()
+            .map_err(|e| EnvironmentError::InvalidRegex(e.to_string()))?;
+
+        // Keep the memory bounded
+        if cache.patterns.len() >= MAX_CACHED_REGEX {
+            cache.patterns.clear();
+        }
+        cache.patterns.insert(pattern.to_owned(), regex);
+    }
+
+    cache.patterns.get(pattern)
+        .ok_or(EnvironmentError::InvalidFnCall)
+}
+
 fn len(zelf: FnInstance, _: FnParams, _: &mut Context) -> FnReturnType {
     let s: &String = zelf?.as_string()?;
     Ok(Some(Value::U32(s.len() as u32).into()))
@@ -56,25 +257,23 @@ fn contains_ignore_case(zelf: FnInstance, mut parameters: FnParams, _: &mut Cont
     Ok(Some(Value::Boolean(s.contains(&value)).into()))
 }
 
-fn to_uppercase(zelf: FnInstance, _: FnParams, _: &mut Context) -> FnReturnType {
+fn to_uppercase(zelf: FnInstance, _: FnParams, context: &mut Context) -> FnReturnType {
     let s: String = zelf?.as_string()?.to_uppercase();
+    context.increase_memory_usage(s.len())?;
     Ok(Some(Value::String(s).into()))
 }
 
-fn to_lowercase(zelf: FnInstance, _: FnParams, _: &mut Context) -> FnReturnType {
+fn to_lowercase(zelf: FnInstance, _: FnParams, context: &mut Context) -> FnReturnType {
     let s: String = zelf?.as_string()?.to_lowercase();
+    context.increase_memory_usage(s.len())?;
     Ok(Some(Value::String(s).into()))
 }
 
-fn to_bytes(zelf: FnInstance, _: FnParams, _: &mut Context) -> FnReturnType {
+fn to_bytes(zelf: FnInstance, _: FnParams, context: &mut Context) -> FnReturnType {
     let s: &String = zelf?.as_string()?;
+    context.increase_memory_usage(s.len() * VALUE_MEMORY_USAGE)?;
 
-    let mut bytes = Vec::new();
-    for b in s.as_bytes() {
-        bytes.push(Value::U8(*b).into());
-    }
-
-    Ok(Some(ValueCell::Array(bytes)))
+    Ok(Some(ValueCell::TypedArray(s.as_bytes().to_vec().into())))
 }
 
 fn index_of(zelf: FnInstance, mut parameters: FnParams, _: &mut Context) -> FnReturnType {
@@ -103,7 +302,7 @@ fn last_index_of(zelf: FnInstance, mut parameters: FnParams, _: &mut Context) ->
     }
 }
 
-fn replace(zelf: FnInstance, mut parameters: FnParams, _: &mut Context) -> FnReturnType {
+fn replace(zelf: FnInstance, mut parameters: FnParams, context: &mut Context) -> FnReturnType {
     let s: &String = zelf?.as_string()?;
     let param1 = parameters.remove(0);
     let param2 = parameters.remove(0);
@@ -112,6 +311,7 @@ fn replace(zelf: FnInstance, mut parameters: FnParams, _: &mut Context) -> FnRet
     let old = handle1.as_string()?;
     let new = handle2.as_string()?;
     let s = s.replace(old, new);
+    context.increase_memory_usage(s.len())?;
     Ok(Some(Value::String(s).into()))
 }
 
@@ -131,16 +331,19 @@ fn ends_with(zelf: FnInstance, mut parameters: FnParams, _: &mut Context) -> FnR
     Ok(Some(Value::Boolean(s.ends_with(value)).into()))
 }
 
-fn split(zelf: FnInstance, mut parameters: FnParams, _: &mut Context) -> FnReturnType {
+fn split(zelf: FnInstance, mut parameters: FnParams, context: &mut Context) -> FnReturnType {
     let s: &String = zelf?.as_string()?;
     let param = parameters.remove(0);
     let handle = param.as_ref();
     let value = handle.as_string()?;
-    let values = s.split(value)
+    let values: Vec<_> = s.split(value)
         .map(|s| Value::String(s.to_string()).into())
         .collect();
 
-    Ok(Some(ValueCell::Array(values)))
+    // Each part is copied in its own string
+    context.increase_memory_usage(values.len() * VALUE_MEMORY_USAGE + s.len())?;
+
+    Ok(Some(ValueCell::Array(values.into())))
 }
 
 fn char_at(zelf: FnInstance, mut parameters: FnParams, _: &mut Context) -> FnReturnType {
@@ -169,6 +372,7 @@ fn string_matches(zelf: FnInstance, mut parameters: FnParams, _: &mut Context) -
     Ok(Some(ValueCell::Array(m.map(|s| Value::String(s.to_string()).into()).collect())))
 }
 
+// The indexes are in bytes, null if one of them isn't on a char boundary or out of bounds
 fn string_substring(zelf: FnInstance, mut parameters: FnParams, _: &mut Context) -> FnReturnType {
     let s: &String = zelf?.as_string()?;
     let param = parameters.remove(0);
@@ -193,4 +397,181 @@ fn string_substring_range(zelf: FnInstance, mut parameters: FnParams, _: &mut Co
     } else {
         Ok(Some(ValueCell::Optional(None)))
     }
-}
\ No newline at end of file
+}
+
+fn len_chars(zelf: FnInstance, _: FnParams, _: &mut Context) -> FnReturnType {
+    let s: &String = zelf?.as_string()?;
+    Ok(Some(Value::U32(s.chars().count() as u32).into()))
+}
+
+fn chars(zelf: FnInstance, _: FnParams, context: &mut Context) -> FnReturnType {
+    let s: &String = zelf?.as_string()?;
+    let values: Vec<_> = s.chars()
+        .map(|c| Value::String(c.to_string()).into())
+        .collect();
+
+    // Each char is copied in its own string
+    context.increase_memory_usage(values.len() * VALUE_MEMORY_USAGE + s.len())?;
+
+    Ok(Some(ValueCell::Array(values.into())))
+}
+
+// The indexes are in chars, null if the range is out of bounds or reversed
+fn substring_chars(zelf: FnInstance, parameters: FnParams, _: &mut Context) -> FnReturnType {
+    let start = parameters[0].as_ref().as_u32()? as usize;
+    let end = parameters[1].as_ref().as_u32()? as usize;
+    let s: &String = zelf?.as_string()?;
+
+    if start > end {
+        return Ok(Some(ValueCell::Optional(None)))
+    }
+
+    // Byte offset of the nth char, the end of the string included
+    let offset = |n: usize| s.char_indices()
+        .map(|(i, _)| i)
+        .chain(std::iter::once(s.len()))
+        .nth(n);
+
+    let inner = match (offset(start), offset(end)) {
+        (Some(from), Some(to)) => Some(Value::String(s[from..to].to_owned()).into()),
+        _ => None
+    };
+
+    Ok(Some(ValueCell::Optional(inner)))
+}
+
+// Fill up to `len` chars, the string is returned as is if it's already long enough or the fill is empty
+fn padding(s: &str, len: usize, fill: &str, context: &mut Context) -> Result<String, EnvironmentError> {
+    let missing = len.saturating_sub(s.chars().count());
+    if missing == 0 || fill.is_empty() {
+        return Ok(String::new())
+    }
+
+    // Size of the padding, computed before building it
+    let fill_chars = fill.chars().count();
+    let rest: usize = fill.chars().take(missing % fill_chars).map(char::len_utf8).sum();
+    let actual = (missing / fill_chars).saturating_mul(fill.len())
+        .saturating_add(rest)
+        .saturating_add(s.len());
+    if actual > MAX_BUILT_STRING_LEN {
+        return Err(EnvironmentError::StringTooLarge { max: MAX_BUILT_STRING_LEN, actual });
+    }
+
+    context.increase_memory_usage(actual)?;
+    Ok(fill.chars().cycle().take(missing).collect())
+}
+
+fn pad_start(zelf: FnInstance, parameters: FnParams, context: &mut Context) -> FnReturnType {
+    let len = parameters[0].as_ref().as_u32()? as usize;
+    let handle = parameters[1].as_ref();
+    let s: &String = zelf?.as_string()?;
+
+    let padded = padding(s, len, handle.as_string()?, context)? + s;
+    Ok(Some(Value::String(padded).into()))
+}
+
+fn pad_end(zelf: FnInstance, parameters: FnParams, context: &mut Context) -> FnReturnType {
+    let len = parameters[0].as_ref().as_u32()? as usize;
+    let handle = parameters[1].as_ref();
+    let s: &String = zelf?.as_string()?;
+
+    let padded = s.clone() + &padding(s, len, handle.as_string()?, context)?;
+    Ok(Some(Value::String(padded).into()))
+}
+
+fn repeat(zelf: FnInstance, parameters: FnParams, context: &mut Context) -> FnReturnType {
+    let count = parameters[0].as_ref().as_u32()? as usize;
+    let s: &String = zelf?.as_string()?;
+
+    let actual = s.len().saturating_mul(count);
+    if actual > MAX_BUILT_STRING_LEN {
+        return Err(EnvironmentError::StringTooLarge { max: MAX_BUILT_STRING_LEN, actual });
+    }
+
+    context.increase_memory_usage(actual)?;
+    Ok(Some(Value::String(s.repeat(count)).into()))
+}
+
+fn trim_start(zelf: FnInstance, _: FnParams, _: &mut Context) -> FnReturnType {
+    let s = zelf?.as_string()?.trim_start().to_string();
+    Ok(Some(Value::String(s).into()))
+}
+
+fn trim_end(zelf: FnInstance, _: FnParams, _: &mut Context) -> FnReturnType {
+    let s = zelf?.as_string()?.trim_end().to_string();
+    Ok(Some(Value::String(s).into()))
+}
+
+// Split at the first occurrence, null if the separator isn't found
+fn split_once(zelf: FnInstance, parameters: FnParams, _: &mut Context) -> FnReturnType {
+    let handle = parameters[0].as_ref();
+    let at = handle.as_string()?;
+    let s: &String = zelf?.as_string()?;
+
+    let inner = s.split_once(at.as_str())
+        .map(|(left, right)| ValueCell::Array(vec![
+            Value::String(left.to_owned()).into(),
+            Value::String(right.to_owned()).into()
+        ].into()).into());
+
+    Ok(Some(ValueCell::Optional(inner)))
+}
+
+fn regex_match(zelf: FnInstance, parameters: FnParams, context: &mut Context) -> FnReturnType {
+    let s: &String = zelf?.as_string()?;
+    let handle = parameters[0].as_ref();
+    let regex = get_regex(context, handle.as_string()?)?;
+    Ok(Some(Value::Boolean(regex.is_match(s)).into()))
+}
+
+fn regex_find_all(zelf: FnInstance, parameters: FnParams, context: &mut Context) -> FnReturnType {
+    let s: &String = zelf?.as_string()?;
+    let handle = parameters[0].as_ref();
+    let regex = get_regex(context, handle.as_string()?)?;
+    let values = regex.find_iter(s)
+        .map(|m| Value::String(m.as_str().to_owned()).into())
+        .collect();
+
+    Ok(Some(ValueCell::Array(values)))
+}
+
+// The replacement is inserted as is, without expanding the capture groups
+fn regex_replace(zelf: FnInstance, parameters: FnParams, context: &mut Context) -> FnReturnType {
+    let s: &String = zelf?.as_string()?;
+    let pattern = parameters[0].as_ref();
+    let to = parameters[1].as_ref();
+    let regex = get_regex(context, pattern.as_string()?)?;
+    let s = regex.replace_all(s, NoExpand(to.as_string()?)).into_owned();
+    context.increase_memory_usage(s.len())?;
+    Ok(Some(Value::String(s).into()))
+}
+
+fn format(_: FnInstance, parameters: FnParams, _: &mut Context) -> FnReturnType {
+    let handle = parameters[0].as_ref();
+    let format = handle.as_string()?;
+    let mut values = parameters[1..].iter();
+
+    let mut result = String::with_capacity(format.len());
+    let mut chars = format.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '{' if chars.next_if_eq(&'{').is_some() => result.push('{'),
+            '}' if chars.next_if_eq(&'}').is_some() => result.push('}'),
+            '{' if chars.next_if_eq(&'}').is_some() => {
+                let value = values.next()
+                    .ok_or(EnvironmentError::InvalidFormat("missing value for placeholder"))?;
+                // Only primitive values are accepted to keep the output bounded
+                let value = value.as_ref();
+                result.push_str(&value.as_value().as_value()?.to_string());
+            },
+            '{' | '}' => return Err(EnvironmentError::InvalidFormat("unmatched brace")),
+            c => result.push(c)
+        }
+    }
+
+    if values.next().is_some() {
+        return Err(EnvironmentError::InvalidFormat("too many values"))
+    }
+
+    Ok(Some(Value::String(result).into()))
+}
@@ -0,0 +1,82 @@
+use xelis_environment::{Context, EnvironmentError};
+use xelis_types::{Type, Value, ValueCell};
+use super::{
+    FnInstance,
+    FnParams,
+    FnReturnType,
+    EnvironmentBuilder
+};
+
+// Bytes appended for one unit of gas
+const BYTES_PER_GAS: u64 = 32;
+
+// Maximum size of a string builder
+const MAX_SIZE: usize = u32::MAX as usize;
+
+// StringBuilder is an opaque type for the scripts
+// Its value is a string mutated in place instead of being copied on each append
+pub fn register(env: &mut EnvironmentBuilder) {
+    let _type = Type::Opaque(env.register_opaque("StringBuilder"));
+
+    env.register_static_function("new", _type.clone(), vec![], new, 1, Some(_type.clone()));
+    env.register_native_function("append", Some(_type.clone()), vec![("value", Type::String)], append, 1, None)
+        .set_cost_fn(Some(append_cost));
+    env.register_native_function("append_u64", Some(_type.clone()), vec![("value", Type::U64)], append_u64, 2, None);
+    env.register_native_function("len", Some(_type.clone()), vec![], len, 1, Some(Type::U32));
+    env.register_native_function("to_string", Some(_type.clone()), vec![], to_string, 1, Some(Type::String))
+        .set_cost_fn(Some(to_string_cost));
+}
+
+// Cost based on the size of the appended string
+fn append_cost(_: Option<&ValueCell>, parameters: &FnParams) -> u64 {
+    parameters.first()
+        .and_then(|p| p.as_ref().as_string().ok().map(|s| s.len() as u64))
+        .unwrap_or(0) / BYTES_PER_GAS
+}
+
+// Cost based on the size of the built string
+fn to_string_cost(zelf: Option<&ValueCell>, _: &FnParams) -> u64 {
+    zelf.and_then(|v| v.as_string().ok())
+        .map_or(0, |s| s.len() as u64 / BYTES_PER_GAS)
+}
+
+// Append a string and verify the builder size
+// The appended bytes are charged as new memory
+fn push(zelf: FnInstance, value: &str, context: &mut Context) -> Result<(), EnvironmentError> {
+    let s = zelf?.as_mut_string()?;
+    if s.len() + value.len() > MAX_SIZE {
+        return Err(EnvironmentError::OutOfMemory)
+    }
+
+    context.increase_memory_usage(value.len())?;
+
+    s.push_str(value);
+    Ok(())
+}
+
+fn new(_: FnInstance, _: FnParams, _: &mut Context) -> FnReturnType {
+    Ok(Some(Value::String(String::new()).into()))
+}
+
+fn append(zelf: FnInstance, parameters: FnParams, context: &mut Context) -> FnReturnType {
+    let param = &parameters[0];
+    push(zelf, param.as_ref().as_string()?, context)?;
+    Ok(None)
+}
+
+fn append_u64(zelf: FnInstance, parameters: FnParams, context: &mut Context) -> FnReturnType {
+    let value = parameters[0].as_ref().as_u64()?;
+    push(zelf, &value.to_string(), context)?;
+    Ok(None)
+}
+
+fn len(zelf: FnInstance, _: FnParams, _: &mut Context) -> FnReturnType {
+    let len = zelf?.as_string()?.len();
+    Ok(Some(Value::U32(len as u32).into()))
+}
+
+fn to_string(zelf: FnInstance, _: FnParams, context: &mut Context) -> FnReturnType {
+    let s = zelf?.as_string()?.clone();
+    context.increase_memory_usage(s.len())?;
+    Ok(Some(Value::String(s).into()))
+}
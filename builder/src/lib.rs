@@ -20,6 +20,10 @@ pub enum BuilderError {
     MappingExists,
     #[error("Signature already registered")]
     SignatureAlreadyRegistered,
+    #[error("Ambiguous function call, several overloads match")]
+    AmbiguousFunctionCall,
+    #[error("Name already used by a type or a namespace")]
+    NameAlreadyUsed,
     #[error(transparent)]
     ValueError(#[from] ValueError),
 }
\ No newline at end of file
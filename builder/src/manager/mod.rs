@@ -58,6 +58,15 @@ impl<'a, T: Builder<'a>> TypeManager<'a, T> {
         }
     }
 
+    // Create a new manager with its ids starting at a specific one
+    pub fn starting_at(id: IdentifierType) -> Self {
+        Self {
+            parent: None,
+            types: Vec::new(),
+            mapper: IdMapper::starting_at(id)
+        }
+    }
+
     pub fn with_parent(parent: &'a Self) -> Self {
         Self {
             parent: Some(parent),
@@ -66,6 +75,15 @@ impl<'a, T: Builder<'a>> TypeManager<'a, T> {
         }
     }
 
+    // Same as `with_parent` but its ids don't follow the parent ones
+    pub fn with_parent_starting_at(parent: &'a Self, id: IdentifierType) -> Self {
+        Self {
+            parent: Some(parent),
+            types: Vec::new(),
+            mapper: IdMapper::with_parent_starting_at(&parent.mapper, id),
+        }
+    }
+
     fn build_internal(&mut self, name: Cow<'a, str>, fields: Vec<(&'a str, T::Data)>) -> Result<T, BuilderError> {
         if self.mapper.has_variable(&name) {
             return Err(BuilderError::StructNameAlreadyUsed);
@@ -113,6 +131,19 @@ impl<'a, T: Builder<'a>> TypeManager<'a, T> {
         self.get_by_id(&id)
     }
 
+    // Get the name of a type by its identifier
+    pub fn get_name_by_id(&self, id: &IdentifierType) -> Option<&str> {
+        self.mapper.get_by_id(*id).map(|name| name.as_ref())
+    }
+
+    // Names of all the types registered, including the parent ones
+    pub fn names(&self) -> Vec<&str> {
+        self.mapper.names()
+            .into_iter()
+            .map(|name| name.as_ref())
+            .collect()
+    }
+
     pub fn get_by_ref(&self, _type: &T::Type) -> Result<&T, BuilderError> {
         if let Some(parent) = self.parent {
             if let Ok(s) = parent.get_by_ref(_type) {
@@ -10,6 +10,24 @@ use super::Mapper;
 pub struct Function<'a> {
     pub name: &'a str,
     pub parameters: Vec<(&'a str, Type)>,
+    // Any number of extra parameters can be given after the declared ones
+    pub variadic: bool,
+}
+
+// Rank of a compatible overload, the lowest is the best
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+struct Rank {
+    variadic: bool,
+    casts: usize,
+    generics: usize,
+}
+
+// Compatible overload with the casts of the hardcoded values it requires
+struct Candidate {
+    rank: Rank,
+    id: IdentifierType,
+    // Index of the argument and its casted expression
+    casts: Vec<(usize, Expression)>,
 }
 
 /// FunctionMapper is used to store the mapping between function signatures and their identifiers
@@ -46,8 +64,27 @@ impl<'a> FunctionMapper<'a> {
 
     // Register a function signature
     pub fn register(&mut self, name: &'a str, on_type: Option<Type>, parameters: Vec<(&'a str, Type)>) -> Result<IdentifierType, BuilderError> {
+        self.register_internal(name, on_type, parameters, false)
+    }
+
+    // Register a function signature accepting extra parameters of any type
+    pub fn register_variadic(&mut self, name: &'a str, on_type: Option<Type>, parameters: Vec<(&'a str, Type)>) -> Result<IdentifierType, BuilderError> {
+        self.register_internal(name, on_type, parameters, true)
+    }
+
+    // Register a function signature in a namespace
+    // Its signature name is the full path: `namespace::name`
+    pub fn register_in_namespace(&mut self, namespace: &str, name: &'a str, parameters: Vec<(&'a str, Type)>, variadic: bool) -> Result<IdentifierType, BuilderError> {
+        self.register_signature(format!("{}::{}", namespace, name), name, None, parameters, variadic)
+    }
+
+    fn register_internal(&mut self, name: &'a str, on_type: Option<Type>, parameters: Vec<(&'a str, Type)>, variadic: bool) -> Result<IdentifierType, BuilderError> {
+        self.register_signature(name.to_owned(), name, on_type, parameters, variadic)
+    }
+
+    fn register_signature(&mut self, signature_name: String, name: &'a str, on_type: Option<Type>, parameters: Vec<(&'a str, Type)>, variadic: bool) -> Result<IdentifierType, BuilderError> {
         let params: Vec<_> = parameters.iter().map(|(_, t)| t.clone()).collect();
-        let signature = Signature::new(name.to_owned(), on_type, params);
+        let signature = Signature::new(signature_name, on_type, params);
 
         if self.mapper.has_variable(&signature) {
             return Err(BuilderError::SignatureAlreadyRegistered);
@@ -58,7 +95,8 @@ impl<'a> FunctionMapper<'a> {
         // Register the mappings
         self.mappings.insert(id.clone(), Function {
             name,
-            parameters
+            parameters,
+            variadic
         });
 
         Ok(id)
@@ -69,6 +107,12 @@ impl<'a> FunctionMapper<'a> {
         self.mappings.get(id)
     }
 
+    // Find the function matching the call signature
+    // All the compatible overloads are ranked, the best one is selected:
+    // - a declared function is preferred over a variadic one
+    // - then the one requiring the fewest casts of hardcoded values
+    // - then the one with the most parameters of the exact same type
+    // If several overloads have the same rank, the call is ambiguous
     pub fn get_compatible(&self, key: Signature, expressions: &mut [Expression]) -> Result<IdentifierType, BuilderError> {
         // First check if we have the exact signature
         if let Ok(id) = self.mapper.get(&key) {
@@ -76,8 +120,19 @@ impl<'a> FunctionMapper<'a> {
         }
 
         // Lets find a compatible signature
-        'main: for (signature, id) in self.mapper.mappings.iter().filter(|(s, _)| s.get_name() == key.get_name() && s.get_parameters().len() == key.get_parameters().len()) {            
+        // Variadic functions accept more parameters than declared
+        let accept_len = |signature: &Signature, id: &IdentifierType| {
+            let len = signature.get_parameters().len();
+            let variadic = self.mappings.get(id).is_some_and(|f| f.variadic);
+            len == key.get_parameters().len() || (variadic && len < key.get_parameters().len())
+        };
+
+        let mut best: Option<Candidate> = None;
+        let mut ambiguous = false;
+        'main: for (signature, id) in self.mapper.mappings.iter().filter(|(s, id)| s.get_name() == key.get_name() && accept_len(s, id)) {
             let on_type = match (signature.get_on_type(), key.get_on_type()) {
+                // An array instance doesn't match the functions of its element type
+                (Some(s), Some(Type::Array(_))) if !matches!(s, Type::Array(_) | Type::Any | Type::T(_)) => false,
                 (Some(s), Some(k)) => s.is_compatible_with(k),
                 (None, None) => true,
                 _ => false
@@ -88,9 +143,12 @@ impl<'a> FunctionMapper<'a> {
             }
 
             let mut updated_expressions = Vec::new();
+            let mut exact = 0;
             for (i, (a, b)) in signature.get_parameters().iter().zip(key.get_parameters()).enumerate() {
+                // A generic parameter takes the inner type of the value it is called on
                 let mut cast_to_type = key.get_on_type()
                     .as_ref()
+                    .filter(|_| matches!(a, Type::T(_)))
                     .map(Type::get_inner_type)
                     .filter(|t| b.is_castable_to(t));
 
@@ -109,23 +167,48 @@ impl<'a> FunctionMapper<'a> {
                     if let Expression::Constant(value) = &expressions[i] {
                         let cloned = value.clone();
                         match cloned.checked_cast_to_primitive_type(a) {
-                            Ok(v) => {
-                                updated_expressions.push(Expression::Constant(v));
-                                continue;
-                            },
+                            Ok(v) => updated_expressions.push((i, Expression::Constant(v))),
                             Err(_) => continue 'main
                         };
                     } else {
                         continue 'main;
                     }
+                } else if a == b {
+                    exact += 1;
                 }
             }
 
-            for (i, expr) in updated_expressions.into_iter().enumerate() {
+            let variadic = self.mappings.get(id).is_some_and(|f| f.variadic);
+            let rank = Rank {
+                variadic,
+                casts: updated_expressions.len(),
+                generics: signature.get_parameters().len() - exact
+            };
+
+            match &best {
+                Some(candidate) if candidate.rank < rank => {},
+                Some(candidate) if candidate.rank == rank => ambiguous = true,
+                _ => {
+                    ambiguous = false;
+                    best = Some(Candidate {
+                        rank,
+                        id: *id,
+                        casts: updated_expressions
+                    });
+                }
+            }
+        }
+
+        if let Some(candidate) = best {
+            if ambiguous {
+                return Err(BuilderError::AmbiguousFunctionCall);
+            }
+
+            for (i, expr) in candidate.casts {
                 expressions[i] = expr;
             }
 
-            return Ok(id.clone());
+            return Ok(candidate.id);
         }
 
         if let Some(parent) = self.parent {
@@ -135,6 +218,29 @@ impl<'a> FunctionMapper<'a> {
         Err(BuilderError::MappingNotFound)
     }
 
+    // Names of the functions callable on this type, or of the free functions
+    pub fn get_names(&self, on_type: Option<&Type>) -> Vec<&str> {
+        self.mapper.names()
+            .into_iter()
+            .filter(|signature| match (signature.get_on_type(), on_type) {
+                (Some(s), Some(k)) => s.is_compatible_with(k),
+                (None, None) => true,
+                _ => false
+            })
+            .map(|signature| signature.get_name().as_str())
+            .collect()
+    }
+
+    // All the functions registered with their identifier and signature, including the parent ones
+    pub fn get_functions(&self) -> Vec<(IdentifierType, &Signature, &Function<'a>)> {
+        let mut functions = self.parent.map_or_else(Vec::new, |p| p.get_functions());
+        functions.extend(self.mapper.mappings.iter().filter_map(|(signature, id)| {
+            self.mappings.get(id).map(|f| (*id, signature, f))
+        }));
+
+        functions
+    }
+
     pub fn get_functions_for_type(&self, on_type: &Type) -> Vec<&Function<'a>> {
         let mut functions = Vec::new();
         if let Some(parent) = self.parent {
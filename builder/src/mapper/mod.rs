@@ -39,9 +39,23 @@ impl<'a, T: Clone + Eq + Hash + Debug> Mapper<'a, T> {
     }
 
     // Create a new VariableMapper starting at a specific id
+    pub fn starting_at(next_id: IdentifierType) -> Self {
+        Self {
+            parent: None,
+            next_id,
+            mappings: HashMap::new()
+        }
+    }
+
+    // Create a new VariableMapper starting after the ids of its parent
     pub fn with_parent(parent: &'a Self) -> Self {
+        Self::with_parent_starting_at(parent, parent.get_next_id())
+    }
+
+    // Create a new VariableMapper with its own ids, starting at a specific one
+    pub fn with_parent_starting_at(parent: &'a Self, next_id: IdentifierType) -> Self {
         Self {
-            next_id: parent.get_next_id(),
+            next_id,
             parent: Some(parent),
             mappings: HashMap::new()
         }
@@ -104,6 +118,13 @@ impl<'a, T: Clone + Eq + Hash + Debug> Mapper<'a, T> {
         Ok(id)
     }
 
+    // All the names registered, including the parent ones
+    pub fn names(&self) -> Vec<&T> {
+        let mut names = self.parent.map_or_else(Vec::new, |p| p.names());
+        names.extend(self.mappings.keys());
+        names
+    }
+
     pub fn count(&self) -> usize {
         self.parent.map_or(0, |p| p.count()) + self.mappings.len()
     }
@@ -0,0 +1,291 @@
+use alloc::{boxed::Box, string::String, vec, vec::Vec};
+use xelis_environment::Environment;
+use xelis_types::{EnumType, IndexSet, StructType, Type};
+
+use super::{ChunkSignature, Module};
+
+// Type of a value in the ABI
+// Structs and enums are referenced by their id, their fields are in the ABI
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize), serde(rename_all = "snake_case"))]
+pub enum AbiType {
+    Any,
+    T(u8),
+    U8,
+    U16,
+    U32,
+    U64,
+    U128,
+    U256,
+    String,
+    Bool,
+    Blob,
+    Array(Box<AbiType>),
+    Optional(Box<AbiType>),
+    Range(Box<AbiType>),
+    Map(Box<AbiType>, Box<AbiType>),
+    Tuple(Vec<AbiType>),
+    Result(Box<AbiType>, Box<AbiType>),
+    Iterator(Box<AbiType>),
+    Struct(u16),
+    Enum(u16),
+    // Type of the environment, its values can't be given from outside
+    Opaque(u16),
+}
+
+impl From<&Type> for AbiType {
+    fn from(value: &Type) -> Self {
+        match value {
+            Type::Any => AbiType::Any,
+            Type::T(id) => AbiType::T(*id),
+            Type::U8 => AbiType::U8,
+            Type::U16 => AbiType::U16,
+            Type::U32 => AbiType::U32,
+            Type::U64 => AbiType::U64,
+            Type::U128 => AbiType::U128,
+            Type::U256 => AbiType::U256,
+            Type::String => AbiType::String,
+            Type::Bool => AbiType::Bool,
+            Type::Blob => AbiType::Blob,
+            Type::Array(inner) => AbiType::Array(Box::new(inner.as_ref().into())),
+            Type::Optional(inner) => AbiType::Optional(Box::new(inner.as_ref().into())),
+            Type::Range(inner) => AbiType::Range(Box::new(inner.as_ref().into())),
+            Type::Map(key, value) => AbiType::Map(Box::new(key.as_ref().into()), Box::new(value.as_ref().into())),
+            Type::Tuple(types) => AbiType::Tuple(types.iter().map(Into::into).collect()),
+            Type::Result(value, error) => AbiType::Result(Box::new(value.as_ref().into()), Box::new(error.as_ref().into())),
+            Type::Iterator(inner) => AbiType::Iterator(Box::new(inner.as_ref().into())),
+            Type::Struct(s) => AbiType::Struct(s.id()),
+            Type::Enum(e) => AbiType::Enum(e.id()),
+            Type::Opaque(o) => AbiType::Opaque(o.id()),
+        }
+    }
+}
+
+// Named parameter of an entry
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct AbiParameter {
+    pub name: String,
+    #[cfg_attr(feature = "serde", serde(rename = "type"))]
+    pub _type: AbiType,
+}
+
+// Entry chunk callable by name
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct AbiEntry {
+    pub name: String,
+    pub chunk_id: u16,
+    pub parameters: Vec<AbiParameter>,
+    pub return_type: AbiType,
+}
+
+// Struct used by the module
+// A builtin struct is declared by the environment
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct AbiStruct {
+    pub id: u16,
+    pub fields: Vec<AbiType>,
+    pub builtin: bool,
+}
+
+// Enum used by the module, with the fields of each variant
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct AbiEnum {
+    pub id: u16,
+    pub variants: Vec<Vec<AbiType>>,
+    pub builtin: bool,
+}
+
+// Event emitted by the module
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct AbiEvent {
+    pub name: String,
+    pub data: AbiType,
+}
+
+// Description of the interface of a module
+// It lets the wallets and explorers build the calls and decode the events without the source
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Abi {
+    pub entries: Vec<AbiEntry>,
+    pub structs: Vec<AbiStruct>,
+    pub enums: Vec<AbiEnum>,
+    pub events: Vec<AbiEvent>,
+}
+
+// Structs and enums found in the types
+#[derive(Default)]
+struct UsedTypes {
+    structs: IndexSet<StructType>,
+    enums: IndexSet<EnumType>,
+}
+
+impl UsedTypes {
+    // Collect the structs and enums of a type, with the types of their fields
+    fn collect(&mut self, _type: &Type) {
+        let mut stack = vec![_type];
+        while let Some(_type) = stack.pop() {
+            match _type {
+                Type::Array(inner) | Type::Optional(inner) | Type::Range(inner) | Type::Iterator(inner) => stack.push(inner),
+                Type::Map(key, value) | Type::Result(key, value) => {
+                    stack.push(key);
+                    stack.push(value);
+                },
+                Type::Tuple(types) => stack.extend(types),
+                // A type already collected has its fields collected too
+                Type::Struct(s) if self.structs.insert(s.clone()) => stack.extend(s.fields()),
+                Type::Enum(e) if self.enums.insert(e.clone()) => stack.extend(e.variants().iter().flat_map(|v| v.fields())),
+                _ => {}
+            }
+        }
+    }
+}
+
+impl Module {
+    // Generate the ABI of the module
+    // Entries are described with the signatures set by the compiler,
+    // the structs and enums of the module are always included, and the ones
+    // of the environment only if they are used by the module
+    pub fn generate_abi(&self, environment: &Environment) -> Abi {
+        let mut used = UsedTypes::default();
+        for s in self.structs() {
+            used.collect(&Type::Struct(s.clone()));
+        }
+
+        for e in self.enums() {
+            used.collect(&Type::Enum(e.clone()));
+        }
+
+        // The public functions are only callable from other modules
+        let mut entries = Vec::new();
+        for (name, id) in self.exports().iter().filter(|(_, id)| self.is_entry_chunk(**id)) {
+            let parameters = self.get_signature(*id)
+                .map(ChunkSignature::parameters)
+                .unwrap_or_default()
+                .iter()
+                .map(|(name, _type)| {
+                    used.collect(_type);
+                    AbiParameter {
+                        name: name.clone(),
+                        _type: _type.into(),
+                    }
+                })
+                .collect();
+
+            // Entries always return a u64 value
+            entries.push(AbiEntry {
+                name: name.clone(),
+                chunk_id: *id as u16,
+                parameters,
+                return_type: AbiType::U64,
+            });
+        }
+
+        let events = self.events()
+            .iter()
+            .map(|(name, data)| {
+                used.collect(data);
+                AbiEvent {
+                    name: name.clone(),
+                    data: data.into(),
+                }
+            })
+            .collect();
+
+        let mut structs: Vec<_> = used.structs.iter()
+            .map(|s| AbiStruct {
+                id: s.id(),
+                fields: s.fields().iter().map(Into::into).collect(),
+                builtin: environment.get_structures().contains(s),
+            })
+            .collect();
+        structs.sort_unstable_by_key(|s| s.id);
+
+        let mut enums: Vec<_> = used.enums.iter()
+            .map(|e| AbiEnum {
+                id: e.id(),
+                variants: e.variants()
+                    .iter()
+                    .map(|v| v.fields().iter().map(Into::into).collect())
+                    .collect(),
+                builtin: environment.get_enums().contains(e),
+            })
+            .collect();
+        enums.sort_unstable_by_key(|e| e.id);
+
+        Abi {
+            entries,
+            structs,
+            enums,
+            events,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use xelis_types::EnumVariant;
+    use crate::Chunk;
+    use super::*;
+
+    #[test]
+    fn test_generate_abi() {
+        let mut environment = Environment::new();
+        let builtin = StructType::new(0, vec![Type::U64]);
+        environment.add_structure(builtin.clone());
+
+        let mut module = Module::new();
+        let point = StructType::new(1, vec![Type::U64, Type::U64]);
+        let shape = EnumType::new(2, vec![
+            EnumVariant::new(Vec::new()),
+            EnumVariant::new(vec![Type::Struct(point.clone())]),
+        ]);
+        module.add_struct(point.clone());
+        module.add_enum(shape.clone());
+
+        module.add_chunk(Chunk::new());
+        module.add_entry_chunk(Chunk::new());
+        module.add_export("draw", 1);
+        module.set_signature(1, ChunkSignature::new(vec![
+            ("shape".into(), Type::Enum(shape)),
+            ("sizes".into(), Type::Array(Box::new(Type::U8))),
+        ], Some(Type::U64)));
+        module.add_chunk(Chunk::new());
+        module.add_export("helper", 2);
+        module.add_event("drawn", Type::Struct(builtin));
+
+        let abi = module.generate_abi(&environment);
+        assert_eq!(abi.entries, vec![AbiEntry {
+            name: "draw".into(),
+            chunk_id: 1,
+            parameters: vec![
+                AbiParameter { name: "shape".into(), _type: AbiType::Enum(2) },
+                AbiParameter { name: "sizes".into(), _type: AbiType::Array(Box::new(AbiType::U8)) },
+            ],
+            return_type: AbiType::U64,
+        }]);
+        assert_eq!(abi.structs, vec![
+            AbiStruct { id: 0, fields: vec![AbiType::U64], builtin: true },
+            AbiStruct { id: 1, fields: vec![AbiType::U64, AbiType::U64], builtin: false },
+        ]);
+        assert_eq!(abi.enums, vec![AbiEnum { id: 2, variants: vec![vec![], vec![AbiType::Struct(1)]], builtin: false }]);
+        assert_eq!(abi.events, vec![AbiEvent { name: "drawn".into(), data: AbiType::Struct(0) }]);
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_serde_json() {
+        let parameter = AbiParameter {
+            name: "values".into(),
+            _type: AbiType::Map(Box::new(AbiType::String), Box::new(AbiType::Struct(1))),
+        };
+        let json = serde_json::to_string(&parameter).unwrap();
+        assert_eq!(json, r#"{"name":"values","type":{"map":["string",{"struct":1}]}}"#);
+        assert_eq!(serde_json::from_str::<AbiParameter>(&json).unwrap(), parameter);
+    }
+}
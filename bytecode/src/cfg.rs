@@ -0,0 +1,444 @@
+use alloc::{vec, vec::Vec};
+use thiserror::Error;
+
+use super::{Chunk, OpCode};
+
+#[derive(Debug, Error, PartialEq, Eq)]
+pub enum CfgError {
+    #[error("invalid opcode {byte} at {offset}")]
+    InvalidOpCode {
+        offset: usize,
+        byte: u8
+    },
+    #[error("invalid arguments for opcode {opcode:?} at {offset}")]
+    InvalidOpCodeArguments {
+        offset: usize,
+        opcode: OpCode
+    },
+    #[error("invalid jump to {target} at {offset}")]
+    InvalidJump {
+        offset: usize,
+        target: usize
+    },
+}
+
+// A sequence of instructions always executed from the first to the last one
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BasicBlock {
+    // Offset of its first instruction
+    pub start: usize,
+    // Offset after its last instruction
+    pub end: usize,
+    // Count of instructions
+    pub instructions: usize,
+    // Blocks executed after this one, the next block first when it can be reached
+    pub successors: Vec<usize>,
+    // Blocks executed before this one
+    pub predecessors: Vec<usize>,
+}
+
+// Control flow graph of a chunk with its dominator tree
+// Block 0 starts at the first instruction and is the entry of the chunk.
+// A block dominates another one if every path from the entry to it goes through it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ControlFlowGraph {
+    blocks: Vec<BasicBlock>,
+    // Reachable blocks in reverse postorder, the entry first
+    order: Vec<usize>,
+    // Immediate dominator of each block, None for the entry and the unreachable blocks
+    dominators: Vec<Option<usize>>,
+}
+
+// A decoded instruction with the offsets it can continue to
+struct Instruction {
+    offset: usize,
+    // Offset of the next instruction if it can be executed after this one
+    next: Option<usize>,
+    // Offsets jumped to
+    targets: Vec<usize>,
+}
+
+// Decode the instructions and their jumps
+fn decode(chunk: &Chunk) -> Result<Vec<Instruction>, CfgError> {
+    let bytes = chunk.get_instructions();
+    let read_u32 = |offset: usize| u32::from_le_bytes([bytes[offset], bytes[offset + 1], bytes[offset + 2], bytes[offset + 3]]) as usize;
+
+    let mut instructions = Vec::new();
+    let mut offset = 0;
+    while let Some(byte) = bytes.get(offset) {
+        let opcode = OpCode::from_byte(*byte)
+            .ok_or(CfgError::InvalidOpCode { offset, byte: *byte })?;
+        let next = offset + 1 + opcode.arguments_bytes();
+        if next > bytes.len() {
+            return Err(CfgError::InvalidOpCodeArguments { offset, opcode })
+        }
+
+        let targets = match opcode {
+            OpCode::JumpTable => {
+                // A jump per value follows the table
+                let len = u16::from_le_bytes([bytes[offset + 7], bytes[offset + 8]]) as usize;
+                let entry = 1 + OpCode::Jump.arguments_bytes();
+                let mut targets = vec![read_u32(offset + 1)];
+                targets.extend((0..len).map(|i| next + i * entry));
+                targets
+            },
+            opcode if opcode.is_jump() => vec![read_u32(offset + 1)],
+            _ => Vec::new()
+        };
+
+        let ends = matches!(opcode, OpCode::Return | OpCode::Jump | OpCode::JumpTable);
+        instructions.push(Instruction {
+            offset,
+            next: (!ends).then_some(next),
+            targets
+        });
+        offset = next;
+    }
+
+    Ok(instructions)
+}
+
+impl Chunk {
+    // Split the chunk in basic blocks linked by their jumps and compute its dominators
+    // A jump must land on an instruction or at the end of the chunk, which exits it
+    pub fn build_cfg(&self) -> Result<ControlFlowGraph, CfgError> {
+        let instructions = decode(self)?;
+        let size = self.get_instructions().len();
+        let index_of = |offset: usize| instructions.binary_search_by_key(&offset, |i| i.offset).ok();
+
+        // A block starts at the entry, at each jump target and after each jump
+        let mut leaders = vec![false; instructions.len()];
+        if let Some(first) = leaders.first_mut() {
+            *first = true;
+        }
+        for (index, instruction) in instructions.iter().enumerate() {
+            for target in instruction.targets.iter().copied() {
+                match index_of(target) {
+                    Some(i) => leaders[i] = true,
+                    None if target == size => {},
+                    None => return Err(CfgError::InvalidJump { offset: instruction.offset, target })
+                }
+            }
+
+            let jumps = !instruction.targets.is_empty() || instruction.next.is_none();
+            if jumps && index + 1 < instructions.len() {
+                leaders[index + 1] = true;
+            }
+        }
+
+        let mut blocks: Vec<BasicBlock> = Vec::new();
+        let mut exits = Vec::new();
+        for (index, instruction) in instructions.iter().enumerate() {
+            if leaders[index] {
+                blocks.push(BasicBlock {
+                    start: instruction.offset,
+                    end: instruction.offset,
+                    instructions: 0,
+                    successors: Vec::new(),
+                    predecessors: Vec::new()
+                });
+            }
+
+            let block = blocks.last_mut().expect("the first instruction starts a block");
+            block.instructions += 1;
+            block.end = instructions.get(index + 1).map_or(size, |i| i.offset);
+
+            // Offsets the last instruction of the block continues to
+            if instructions.get(index + 1).is_none_or(|_| leaders[index + 1]) {
+                exits.push(instruction.next.into_iter().chain(instruction.targets.iter().copied()).collect::<Vec<_>>());
+            }
+        }
+
+        let starts = blocks.iter().map(|b| b.start).collect::<Vec<_>>();
+        for (id, offsets) in exits.into_iter().enumerate() {
+            for successor in offsets.into_iter().filter_map(|offset| starts.binary_search(&offset).ok()) {
+                if !blocks[id].successors.contains(&successor) {
+                    blocks[id].successors.push(successor);
+                    blocks[successor].predecessors.push(id);
+                }
+            }
+        }
+
+        let order = reverse_postorder(&blocks);
+        let dominators = dominators(&blocks, &order);
+
+        Ok(ControlFlowGraph {
+            blocks,
+            order,
+            dominators
+        })
+    }
+}
+
+// Reachable blocks in reverse postorder
+// Depth-first, iterative so a long chunk can't overflow the stack
+fn reverse_postorder(blocks: &[BasicBlock]) -> Vec<usize> {
+    let mut order = Vec::with_capacity(blocks.len());
+    if blocks.is_empty() {
+        return order
+    }
+
+    let mut visited = vec![false; blocks.len()];
+    // Block with the position of the next successor to visit
+    let mut pending = vec![(0, 0)];
+    visited[0] = true;
+    while let Some((block, edge)) = pending.last_mut() {
+        let block = *block;
+        if let Some(successor) = blocks[block].successors.get(*edge).copied() {
+            *edge += 1;
+            if !visited[successor] {
+                visited[successor] = true;
+                pending.push((successor, 0));
+            }
+            continue
+        }
+
+        order.push(block);
+        pending.pop();
+    }
+
+    order.reverse();
+    order
+}
+
+// Immediate dominators by the iterative algorithm of Cooper, Harvey and Kennedy
+fn dominators(blocks: &[BasicBlock], order: &[usize]) -> Vec<Option<usize>> {
+    let mut position = vec![usize::MAX; blocks.len()];
+    for (i, block) in order.iter().enumerate() {
+        position[*block] = i;
+    }
+
+    // The entry is its own dominator while computing
+    let mut dominators = vec![None; blocks.len()];
+    if let Some(entry) = order.first() {
+        dominators[*entry] = Some(*entry);
+    }
+
+    let intersect = |dominators: &[Option<usize>], mut a: usize, mut b: usize| {
+        while a != b {
+            while position[a] > position[b] {
+                a = dominators[a].expect("processed block");
+            }
+            while position[b] > position[a] {
+                b = dominators[b].expect("processed block");
+            }
+        }
+        a
+    };
+
+    let mut changed = true;
+    while changed {
+        changed = false;
+        for block in order.iter().skip(1).copied() {
+            let dominator = blocks[block].predecessors.iter()
+                .copied()
+                .filter(|p| dominators[*p].is_some())
+                .reduce(|a, b| intersect(&dominators, a, b));
+
+            if dominator.is_some() && dominators[block] != dominator {
+                dominators[block] = dominator;
+                changed = true;
+            }
+        }
+    }
+
+    if let Some(entry) = order.first() {
+        dominators[*entry] = None;
+    }
+    dominators
+}
+
+impl ControlFlowGraph {
+    // All the blocks ordered by offset
+    #[inline]
+    pub fn blocks(&self) -> &[BasicBlock] {
+        &self.blocks
+    }
+
+    #[inline]
+    pub fn block(&self, id: usize) -> Option<&BasicBlock> {
+        self.blocks.get(id)
+    }
+
+    // Block containing the instruction at an offset
+    pub fn block_at(&self, offset: usize) -> Option<usize> {
+        let id = self.blocks.partition_point(|block| block.start <= offset).checked_sub(1)?;
+        (offset < self.blocks[id].end).then_some(id)
+    }
+
+    #[inline]
+    pub fn successors(&self, id: usize) -> &[usize] {
+        self.blocks.get(id).map_or(&[], |block| &block.successors)
+    }
+
+    #[inline]
+    pub fn predecessors(&self, id: usize) -> &[usize] {
+        self.blocks.get(id).map_or(&[], |block| &block.predecessors)
+    }
+
+    // Reachable blocks in reverse postorder: a block comes before its successors
+    // except through the back edges of the loops
+    #[inline]
+    pub fn reverse_postorder(&self) -> &[usize] {
+        &self.order
+    }
+
+    // Can the block be executed from the entry
+    pub fn is_reachable(&self, id: usize) -> bool {
+        (id == 0 && !self.blocks.is_empty()) || self.dominators.get(id).is_some_and(Option::is_some)
+    }
+
+    // Closest block dominating this one
+    // None for the entry and the unreachable blocks
+    #[inline]
+    pub fn immediate_dominator(&self, id: usize) -> Option<usize> {
+        self.dominators.get(id).copied().flatten()
+    }
+
+    // Does every path from the entry to the block `b` go through the block `a`
+    // A block dominates itself, an unreachable block is dominated by none
+    pub fn dominates(&self, a: usize, b: usize) -> bool {
+        if !self.is_reachable(b) {
+            return false
+        }
+
+        let mut current = Some(b);
+        while let Some(block) = current {
+            if block == a {
+                return true
+            }
+            current = self.immediate_dominator(block);
+        }
+        false
+    }
+
+    // Children of a block in the dominator tree
+    pub fn dominated(&self, id: usize) -> impl Iterator<Item = usize> + '_ {
+        self.dominators.iter()
+            .enumerate()
+            .filter(move |(_, dominator)| **dominator == Some(id))
+            .map(|(block, _)| block)
+    }
+
+    // Edges going back to a block dominating their source, one per loop head and jump back
+    pub fn back_edges(&self) -> Vec<(usize, usize)> {
+        self.order.iter()
+            .flat_map(|from| self.successors(*from).iter().map(move |to| (*from, *to)))
+            .filter(|(from, to)| self.dominates(*to, *from))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn jump(chunk: &mut Chunk, opcode: OpCode, target: u32) {
+        chunk.emit_opcode(opcode);
+        chunk.write_u32(target);
+    }
+
+    fn constant(chunk: &mut Chunk) {
+        chunk.emit_opcode(OpCode::Constant);
+        chunk.write_u16(0);
+    }
+
+    #[test]
+    fn test_if_else() {
+        // 0: if c { a } else { b } return
+        let mut chunk = Chunk::new();
+        constant(&mut chunk); // 0
+        jump(&mut chunk, OpCode::JumpIfFalse, 16); // 3
+        constant(&mut chunk); // 8
+        jump(&mut chunk, OpCode::Jump, 19); // 11
+        constant(&mut chunk); // 16
+        chunk.emit_opcode(OpCode::Return); // 19
+
+        let cfg = chunk.build_cfg().unwrap();
+        let starts = cfg.blocks().iter().map(|b| b.start).collect::<Vec<_>>();
+        assert_eq!(starts, [0, 8, 16, 19]);
+        assert_eq!(cfg.successors(0), [1, 2]);
+        assert_eq!(cfg.successors(1), [3]);
+        assert_eq!(cfg.successors(2), [3]);
+        assert_eq!(cfg.predecessors(3), [1, 2]);
+        assert_eq!(cfg.block(0).unwrap().instructions, 2);
+
+        assert_eq!(cfg.block_at(12), Some(1));
+        assert_eq!(cfg.block_at(20), None);
+
+        // The join is only dominated by the condition
+        assert_eq!(cfg.immediate_dominator(3), Some(0));
+        assert_eq!(cfg.immediate_dominator(0), None);
+        assert!(cfg.dominates(0, 3));
+        assert!(!cfg.dominates(1, 3));
+        assert!(cfg.dominates(3, 3));
+        assert_eq!(cfg.dominated(0).collect::<Vec<_>>(), [1, 2, 3]);
+        assert!(cfg.back_edges().is_empty());
+    }
+
+    #[test]
+    fn test_loop_and_unreachable() {
+        // 0: while c { a } return, then a dead block
+        let mut chunk = Chunk::new();
+        constant(&mut chunk); // 0
+        jump(&mut chunk, OpCode::JumpIfFalse, 16); // 3
+        constant(&mut chunk); // 8
+        jump(&mut chunk, OpCode::Jump, 0); // 11
+        chunk.emit_opcode(OpCode::Return); // 16
+        constant(&mut chunk); // 17
+        chunk.emit_opcode(OpCode::Return); // 20
+
+        let cfg = chunk.build_cfg().unwrap();
+        assert_eq!(cfg.blocks().len(), 4);
+        assert_eq!(cfg.successors(1), [0]);
+        assert_eq!(cfg.predecessors(0), [1]);
+        assert_eq!(cfg.back_edges(), [(1, 0)]);
+        assert_eq!(cfg.reverse_postorder(), [0, 2, 1]);
+
+        assert!(cfg.is_reachable(0));
+        assert!(!cfg.is_reachable(3));
+        assert!(!cfg.dominates(0, 3));
+        assert_eq!(cfg.immediate_dominator(3), None);
+    }
+
+    #[test]
+    fn test_jump_table() {
+        let mut chunk = Chunk::new();
+        constant(&mut chunk); // 0
+        chunk.emit_opcode(OpCode::JumpTable); // 3
+        chunk.write_u32(26);
+        chunk.write_u16(0);
+        chunk.write_u16(2);
+        jump(&mut chunk, OpCode::Jump, 22); // 12
+        jump(&mut chunk, OpCode::Jump, 26); // 17
+        constant(&mut chunk); // 22
+        chunk.emit_opcode(OpCode::Return); // 25
+        chunk.emit_opcode(OpCode::Return); // 26
+
+        let cfg = chunk.build_cfg().unwrap();
+        let starts = cfg.blocks().iter().map(|b| b.start).collect::<Vec<_>>();
+        assert_eq!(starts, [0, 12, 17, 22, 26]);
+        assert_eq!(cfg.successors(0), [4, 1, 2]);
+        assert_eq!(cfg.immediate_dominator(4), Some(0));
+        assert_eq!(cfg.immediate_dominator(3), Some(1));
+    }
+
+    #[test]
+    fn test_invalid_jump() {
+        let mut chunk = Chunk::new();
+        jump(&mut chunk, OpCode::Jump, 2);
+        assert_eq!(chunk.build_cfg(), Err(CfgError::InvalidJump { offset: 0, target: 2 }));
+
+        // A jump to the end exits the chunk
+        let mut chunk = Chunk::new();
+        jump(&mut chunk, OpCode::Jump, 5);
+        let cfg = chunk.build_cfg().unwrap();
+        assert!(cfg.successors(0).is_empty());
+
+        let mut chunk = Chunk::new();
+        chunk.emit_opcode(OpCode::Jump);
+        chunk.write_u16(0);
+        assert_eq!(chunk.build_cfg(), Err(CfgError::InvalidOpCodeArguments { offset: 0, opcode: OpCode::Jump }));
+        assert!(Chunk::new().build_cfg().unwrap().blocks().is_empty());
+    }
+}
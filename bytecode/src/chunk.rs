@@ -1,3 +1,4 @@
+use alloc::vec::Vec;
 use super::OpCode;
 
 // Each chunk is a collection of opcodes and constants
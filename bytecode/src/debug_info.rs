@@ -0,0 +1,139 @@
+use core::fmt;
+use alloc::{string::String, vec::Vec};
+use xelis_types::HashMap;
+
+// Location of a source code span
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct SourceSpan {
+    // line number in the source file
+    pub line: usize,
+    // column number where the span starts
+    pub column_start: usize,
+    // column number where the span ends
+    pub column_end: usize,
+}
+
+impl fmt::Display for SourceSpan {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "line {}, column {}", self.line, self.column_start)
+    }
+}
+
+// Optional debug section of a module
+// Map the chunks offsets to the source code that produced them
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct DebugInfo {
+    // Spans of each chunk, sorted by offset
+    // The span of an offset is the last one starting before it
+    spans: HashMap<usize, Vec<(usize, SourceSpan)>>,
+    // Name of the function compiled in each chunk
+    names: HashMap<usize, String>,
+}
+
+impl DebugInfo {
+    // Create an empty debug info
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // Add a span starting at the offset of the chunk
+    // Offsets must be added in increasing order for a chunk
+    pub fn add_span(&mut self, chunk_id: usize, offset: usize, span: SourceSpan) {
+        let spans = self.spans.entry(chunk_id).or_default();
+        match spans.last_mut() {
+            // Same offset, keep the most recent span
+            Some((last, value)) if *last == offset => *value = span,
+            _ => spans.push((offset, span))
+        }
+    }
+
+    // Get all the spans of a chunk
+    pub fn get_spans(&self, chunk_id: usize) -> Option<&[(usize, SourceSpan)]> {
+        self.spans.get(&chunk_id).map(Vec::as_slice)
+    }
+
+    // Get the span containing the instruction at the offset of the chunk
+    pub fn get_span(&self, chunk_id: usize, offset: usize) -> Option<&SourceSpan> {
+        let spans = self.spans.get(&chunk_id)?;
+        let index = spans.partition_point(|(start, _)| *start <= offset);
+        index.checked_sub(1)
+            .map(|i| &spans[i].1)
+    }
+
+    // Get the spans of every chunk, in no particular order
+    pub fn chunks_spans(&self) -> impl Iterator<Item = (usize, &[(usize, SourceSpan)])> {
+        self.spans.iter().map(|(id, spans)| (*id, spans.as_slice()))
+    }
+
+    // Get the function name of every chunk, in no particular order
+    pub fn chunks_names(&self) -> impl Iterator<Item = (usize, &str)> {
+        self.names.iter().map(|(id, name)| (*id, name.as_str()))
+    }
+
+    // Set the function name of a chunk
+    pub fn set_chunk_name(&mut self, chunk_id: usize, name: String) {
+        self.names.insert(chunk_id, name);
+    }
+
+    // Get the function name of a chunk
+    pub fn get_chunk_name(&self, chunk_id: usize) -> Option<&str> {
+        self.names.get(&chunk_id).map(String::as_str)
+    }
+
+    // Move the spans and name of each chunk to its new id
+    // The chunks without a new id are removed
+    pub fn remap_chunks(&mut self, new_id: impl Fn(usize) -> Option<usize>) {
+        self.spans = self.spans.drain()
+            .filter_map(|(id, spans)| Some((new_id(id)?, spans)))
+            .collect();
+        self.names = self.names.drain()
+            .filter_map(|(id, name)| Some((new_id(id)?, name)))
+            .collect();
+    }
+
+    // Is there any span or name registered
+    pub fn is_empty(&self) -> bool {
+        self.spans.is_empty() && self.names.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn span(line: usize) -> SourceSpan {
+        SourceSpan {
+            line,
+            column_start: 0,
+            column_end: 1
+        }
+    }
+
+    #[test]
+    fn test_get_span() {
+        let mut info = DebugInfo::new();
+        info.add_span(0, 2, span(1));
+        info.add_span(0, 10, span(2));
+        info.add_span(0, 10, span(3));
+
+        assert_eq!(info.get_span(0, 0), None);
+        assert_eq!(info.get_span(0, 2), Some(&span(1)));
+        assert_eq!(info.get_span(0, 9), Some(&span(1)));
+        assert_eq!(info.get_span(0, 10), Some(&span(3)));
+        assert_eq!(info.get_span(0, 100), Some(&span(3)));
+        assert_eq!(info.get_span(1, 0), None);
+    }
+
+    #[test]
+    fn test_remap_chunks() {
+        let mut info = DebugInfo::new();
+        info.add_span(0, 0, span(1));
+        info.add_span(2, 0, span(2));
+        info.set_chunk_name(2, "main".to_owned());
+
+        info.remap_chunks(|id| (id == 2).then_some(0));
+        assert_eq!(info.get_span(0, 0), Some(&span(2)));
+        assert_eq!(info.get_span(2, 0), None);
+        assert_eq!(info.get_chunk_name(0), Some("main"));
+    }
+}
@@ -0,0 +1,420 @@
+use core::fmt;
+use alloc::{string::String, vec::Vec};
+use xelis_types::Type;
+
+use super::Module;
+
+// Changes of a list of fields, compared by position
+// The values stored by the old module keep their fields at the same positions
+// as long as no field is removed or changes of type
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct FieldsDiff {
+    // Count of fields appended after the existing ones
+    pub added: usize,
+    // Positions of the fields removed from the end
+    pub removed: Vec<usize>,
+    // Positions of the fields whose type changed
+    pub changed: Vec<usize>,
+}
+
+impl FieldsDiff {
+    fn new(old: &[Type], new: &[Type]) -> Self {
+        Self {
+            added: new.len().saturating_sub(old.len()),
+            removed: (new.len()..old.len()).collect(),
+            changed: old.iter()
+                .zip(new)
+                .enumerate()
+                .filter(|(_, (old, new))| old != new)
+                .map(|(i, _)| i)
+                .collect(),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.added == 0 && self.removed.is_empty() && self.changed.is_empty()
+    }
+
+    // The stored values can't be read with the new fields
+    pub fn is_breaking(&self) -> bool {
+        !self.removed.is_empty() || !self.changed.is_empty()
+    }
+}
+
+impl fmt::Display for FieldsDiff {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mut parts = Vec::new();
+        if self.added > 0 {
+            parts.push(alloc::format!("{} added", self.added));
+        }
+        if !self.removed.is_empty() {
+            parts.push(alloc::format!("removed {:?}", self.removed));
+        }
+        if !self.changed.is_empty() {
+            parts.push(alloc::format!("changed {:?}", self.changed));
+        }
+
+        write!(f, "{}", parts.join(", "))
+    }
+}
+
+// Change of the fields of a struct kept by the new module
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StructDiff {
+    // Index of the struct in the modules
+    pub index: usize,
+    pub fields: FieldsDiff,
+}
+
+// Change of the variants of an enum kept by the new module
+// A variant is identified by its position like a field
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EnumDiff {
+    // Index of the enum in the modules
+    pub index: usize,
+    // Count of variants appended after the existing ones
+    pub added_variants: usize,
+    // Positions of the variants removed from the end
+    pub removed_variants: Vec<usize>,
+    // Variants whose fields changed by position
+    pub changed_variants: Vec<(usize, FieldsDiff)>,
+}
+
+impl EnumDiff {
+    pub fn is_breaking(&self) -> bool {
+        !self.removed_variants.is_empty() || self.changed_variants.iter().any(|(_, fields)| fields.is_breaking())
+    }
+}
+
+// Differences between two versions of a module
+// Chunks, structs and enums are compared by their index, constants by their value
+// and exports by their name, so an upgrade can be checked before being deployed
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct ModuleDiff {
+    // Ids of the chunks only in the new module
+    pub added_chunks: Vec<usize>,
+    // Ids of the chunks only in the old module
+    pub removed_chunks: Vec<usize>,
+    // Ids of the chunks whose instructions changed
+    pub changed_chunks: Vec<usize>,
+    // Indexes in the new module of the constants it added
+    pub added_constants: Vec<usize>,
+    // Indexes in the old module of the constants no longer used
+    pub removed_constants: Vec<usize>,
+    pub added_exports: Vec<String>,
+    pub removed_exports: Vec<String>,
+    // Exports whose signature changed
+    pub changed_exports: Vec<String>,
+    pub added_structs: Vec<usize>,
+    pub removed_structs: Vec<usize>,
+    pub changed_structs: Vec<StructDiff>,
+    // Indexes of the structs declared as migrated by the new module
+    pub migrated_structs: Vec<usize>,
+    pub added_enums: Vec<usize>,
+    pub removed_enums: Vec<usize>,
+    pub changed_enums: Vec<EnumDiff>,
+}
+
+impl ModuleDiff {
+    // Compare the old version of a module with the new one
+    pub fn new(old: &Module, new: &Module) -> Self {
+        let (added_chunks, removed_chunks) = added_removed(old.chunks().len(), new.chunks().len());
+        let changed_chunks = old.chunks()
+            .iter()
+            .zip(new.chunks())
+            .enumerate()
+            .filter(|(_, (old, new))| old.get_instructions() != new.get_instructions())
+            .map(|(id, _)| id)
+            .collect();
+
+        let added_constants = new.constants()
+            .iter()
+            .enumerate()
+            .filter(|(_, constant)| !old.constants().contains(*constant))
+            .map(|(i, _)| i)
+            .collect();
+        let removed_constants = old.constants()
+            .iter()
+            .enumerate()
+            .filter(|(_, constant)| !new.constants().contains(*constant))
+            .map(|(i, _)| i)
+            .collect();
+
+        let mut added_exports = Vec::new();
+        let mut changed_exports = Vec::new();
+        for (name, id) in new.exports() {
+            match old.get_export(name) {
+                Some(old_id) => if old.get_signature(old_id) != new.get_signature(*id) {
+                    changed_exports.push(name.clone());
+                },
+                None => added_exports.push(name.clone())
+            }
+        }
+        let removed_exports = old.exports()
+            .keys()
+            .filter(|name| new.get_export(name).is_none())
+            .cloned()
+            .collect();
+
+        let (added_structs, removed_structs) = added_removed(old.structs().len(), new.structs().len());
+        let changed_structs = old.structs()
+            .iter()
+            .zip(new.structs())
+            .enumerate()
+            .map(|(index, (old, new))| StructDiff { index, fields: FieldsDiff::new(old.fields(), new.fields()) })
+            .filter(|diff| !diff.fields.is_empty())
+            .collect();
+        let migrated_structs = new.migrations().keys().copied().collect();
+
+        let (added_enums, removed_enums) = added_removed(old.enums().len(), new.enums().len());
+        let changed_enums = old.enums()
+            .iter()
+            .zip(new.enums())
+            .enumerate()
+            .map(|(index, (old, new))| {
+                let (added, removed) = added_removed(old.variants().len(), new.variants().len());
+                EnumDiff {
+                    index,
+                    added_variants: added.len(),
+                    removed_variants: removed,
+                    changed_variants: old.variants()
+                        .iter()
+                        .zip(new.variants())
+                        .map(|(old, new)| FieldsDiff::new(old.fields(), new.fields()))
+                        .enumerate()
+                        .filter(|(_, fields)| !fields.is_empty())
+                        .collect(),
+                }
+            })
+            .filter(|diff| diff.added_variants > 0 || !diff.removed_variants.is_empty() || !diff.changed_variants.is_empty())
+            .collect();
+
+        Self {
+            added_chunks,
+            removed_chunks,
+            changed_chunks,
+            added_constants,
+            removed_constants,
+            added_exports,
+            removed_exports,
+            changed_exports,
+            added_structs,
+            removed_structs,
+            changed_structs,
+            migrated_structs,
+            added_enums,
+            removed_enums,
+            changed_enums,
+        }
+    }
+
+    // Both modules are identical
+    pub fn is_empty(&self) -> bool {
+        *self == Self::default()
+    }
+
+    // The values stored by the old module can't be read by the new one:
+    // a struct or an enum was removed, a field or a variant was removed or changed of type
+    // A struct changed with a migration is handled by the new module
+    pub fn breaks_storage_layout(&self) -> bool {
+        !self.removed_structs.is_empty()
+            || !self.removed_enums.is_empty()
+            || self.changed_structs.iter().any(|diff| diff.fields.is_breaking() && !self.migrated_structs.contains(&diff.index))
+            || self.changed_enums.iter().any(EnumDiff::is_breaking)
+    }
+
+    // The new module can replace the old one: its storage layout is compatible
+    // and the callers of its exports don't have to be updated
+    pub fn is_compatible(&self) -> bool {
+        !self.breaks_storage_layout() && self.removed_exports.is_empty() && self.changed_exports.is_empty()
+    }
+}
+
+// Indexes added and removed between two lists compared by position
+fn added_removed(old: usize, new: usize) -> (Vec<usize>, Vec<usize>) {
+    ((old..new).collect(), (new..old).collect())
+}
+
+impl fmt::Display for ModuleDiff {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.is_empty() {
+            return write!(f, "no changes")
+        }
+
+        for id in &self.added_chunks {
+            writeln!(f, "+ chunk {}", id)?;
+        }
+        for id in &self.removed_chunks {
+            writeln!(f, "- chunk {}", id)?;
+        }
+        for id in &self.changed_chunks {
+            writeln!(f, "~ chunk {}", id)?;
+        }
+        for index in &self.added_constants {
+            writeln!(f, "+ constant {}", index)?;
+        }
+        for index in &self.removed_constants {
+            writeln!(f, "- constant {}", index)?;
+        }
+        for name in &self.added_exports {
+            writeln!(f, "+ export {}", name)?;
+        }
+        for name in &self.removed_exports {
+            writeln!(f, "- export {} (breaking)", name)?;
+        }
+        for name in &self.changed_exports {
+            writeln!(f, "~ export {}: signature changed (breaking)", name)?;
+        }
+        for index in &self.added_structs {
+            writeln!(f, "+ struct {}", index)?;
+        }
+        for index in &self.removed_structs {
+            writeln!(f, "- struct {} (breaking)", index)?;
+        }
+        for diff in &self.changed_structs {
+            write!(f, "~ struct {}: fields {}", diff.index, diff.fields)?;
+            if self.migrated_structs.contains(&diff.index) {
+                write!(f, " (migrated)")?;
+            } else if diff.fields.is_breaking() {
+                write!(f, " (breaking)")?;
+            }
+            writeln!(f)?;
+        }
+        for index in &self.added_enums {
+            writeln!(f, "+ enum {}", index)?;
+        }
+        for index in &self.removed_enums {
+            writeln!(f, "- enum {} (breaking)", index)?;
+        }
+        for diff in &self.changed_enums {
+            write!(f, "~ enum {}:", diff.index)?;
+            if diff.added_variants > 0 {
+                write!(f, " {} variants added", diff.added_variants)?;
+            }
+            if !diff.removed_variants.is_empty() {
+                write!(f, " variants removed {:?}", diff.removed_variants)?;
+            }
+            for (variant, fields) in &diff.changed_variants {
+                write!(f, " variant {} fields {}", variant, fields)?;
+            }
+            if diff.is_breaking() {
+                write!(f, " (breaking)")?;
+            }
+            writeln!(f)?;
+        }
+
+        write!(f, "{}", if self.is_compatible() { "compatible" } else { "incompatible" })
+    }
+}
+
+impl Module {
+    // Compare this module with its new version
+    pub fn diff(&self, new: &Module) -> ModuleDiff {
+        ModuleDiff::new(self, new)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::vec;
+    use xelis_types::{EnumType, EnumVariant, StructType, Value};
+    use crate::{Chunk, ChunkSignature, OpCode};
+    use super::*;
+
+    fn chunk(constant: u16) -> Chunk {
+        let mut chunk = Chunk::new();
+        chunk.emit_opcode(OpCode::Constant);
+        chunk.write_u16(constant);
+        chunk.emit_opcode(OpCode::Return);
+        chunk
+    }
+
+    fn module(structs: Vec<Vec<Type>>) -> Module {
+        let mut module = Module::new();
+        module.add_constant(Value::U64(1));
+        module.add_chunk(chunk(0));
+        module.add_export("get", 0);
+        module.set_signature(0, ChunkSignature::new(vec![], Some(Type::U64)));
+        for (id, fields) in structs.into_iter().enumerate() {
+            module.add_struct(StructType::new(id as u16, fields));
+        }
+        module
+    }
+
+    #[test]
+    fn test_same_module() {
+        let old = module(vec![vec![Type::U64]]);
+        let diff = old.diff(&old.clone());
+        assert!(diff.is_empty());
+        assert!(diff.is_compatible());
+    }
+
+    #[test]
+    fn test_compatible_upgrade() {
+        let old = module(vec![vec![Type::U64]]);
+        let mut new = module(vec![vec![Type::U64, Type::String], vec![Type::Bool]]);
+        new.add_constant(Value::U64(2));
+        new.add_chunk(chunk(1));
+        new.add_export("get_two", 1);
+
+        let diff = old.diff(&new);
+        assert_eq!(diff.added_chunks, vec![1]);
+        assert_eq!(diff.added_constants, vec![1]);
+        assert_eq!(diff.added_exports, vec!["get_two"]);
+        assert_eq!(diff.added_structs, vec![1]);
+        assert_eq!(diff.changed_structs, vec![StructDiff { index: 0, fields: FieldsDiff { added: 1, ..Default::default() } }]);
+        assert!(!diff.breaks_storage_layout());
+        assert!(diff.is_compatible());
+    }
+
+    #[test]
+    fn test_breaking_upgrade() {
+        let old = module(vec![vec![Type::U64, Type::Bool], vec![Type::U8]]);
+        let mut new = module(vec![vec![Type::U32]]);
+        new.set_signature(0, ChunkSignature::new(vec![("n".into(), Type::U8)], Some(Type::U64)));
+
+        let diff = old.diff(&new);
+        assert_eq!(diff.removed_structs, vec![1]);
+        assert_eq!(diff.changed_structs[0].fields, FieldsDiff { added: 0, removed: vec![1], changed: vec![0] });
+        assert_eq!(diff.changed_exports, vec!["get"]);
+        assert!(diff.breaks_storage_layout());
+        assert!(!diff.is_compatible());
+
+        // The changed struct is migrated, the removed one can't be
+        new.add_migration(0, 0);
+        let diff = old.diff(&new);
+        assert_eq!(diff.migrated_structs, vec![0]);
+        assert!(diff.breaks_storage_layout());
+        let mut new = module(vec![vec![Type::U32], vec![Type::U8]]);
+        new.add_migration(0, 0);
+        assert!(!old.diff(&new).breaks_storage_layout());
+
+        // Only the chunk changed
+        let mut new = module(vec![vec![Type::U64, Type::Bool], vec![Type::U8]]);
+        new.add_constant(Value::U64(2));
+        *new.get_chunk_at_mut(0).unwrap() = chunk(1);
+        let diff = old.diff(&new);
+        assert_eq!(diff.changed_chunks, vec![0]);
+        assert_eq!(diff.added_constants, vec![1]);
+        assert!(diff.is_compatible());
+    }
+
+    #[test]
+    fn test_enum_variants() {
+        let mut old = Module::new();
+        old.add_enum(EnumType::new(0, vec![EnumVariant::new(vec![]), EnumVariant::new(vec![Type::U64])]));
+
+        let mut new = Module::new();
+        new.add_enum(EnumType::new(0, vec![EnumVariant::new(vec![]), EnumVariant::new(vec![Type::U64, Type::U8]), EnumVariant::new(vec![])]));
+        let diff = old.diff(&new);
+        assert_eq!(diff.changed_enums[0].added_variants, 1);
+        assert!(diff.is_compatible());
+
+        let mut new = Module::new();
+        new.add_enum(EnumType::new(0, vec![EnumVariant::new(vec![Type::U64])]));
+        let diff = old.diff(&new);
+        assert_eq!(diff.changed_enums[0].removed_variants, vec![1]);
+        assert_eq!(diff.changed_enums[0].changed_variants[0].1.added, 1);
+        assert!(diff.breaks_storage_layout());
+    }
+}
@@ -0,0 +1,330 @@
+use core::fmt::Write;
+use alloc::{format, string::{String, ToString}, vec::Vec};
+use thiserror::Error;
+use xelis_types::{Constant, HashMap, Type, Value};
+
+use super::{Chunk, Module, OpCode};
+
+#[derive(Debug, Error, PartialEq, Eq)]
+pub enum DisassemblerError {
+    #[error("chunk {0} not found")]
+    ChunkNotFound(usize),
+    #[error("invalid opcode {byte} at {offset} in chunk {chunk}")]
+    InvalidOpCode {
+        chunk: usize,
+        offset: usize,
+        byte: u8
+    },
+    #[error("invalid arguments for opcode {opcode:?} at {offset} in chunk {chunk}")]
+    InvalidOpCodeArguments {
+        chunk: usize,
+        offset: usize,
+        opcode: OpCode
+    },
+}
+
+// A decoded instruction with its arguments in a readable format
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DisassembledInstruction {
+    // Offset of the opcode in the chunk
+    pub offset: usize,
+    pub opcode: OpCode,
+    // Arguments decoded and resolved using the module
+    pub arguments: String,
+}
+
+// Produce human-readable listings of a module
+// Chunks are walked like the validator does: opcode then its arguments bytes
+pub struct Disassembler<'a> {
+    module: &'a Module,
+    // Optional names for the structs, by id
+    struct_names: HashMap<u16, String>,
+    // Optional names for the enums, by id
+    enum_names: HashMap<u16, String>,
+}
+
+// Little reader over the chunk arguments
+struct ArgumentsReader<'a> {
+    bytes: &'a [u8],
+    index: usize,
+}
+
+impl<'a> ArgumentsReader<'a> {
+    fn read_u8(&mut self) -> u8 {
+        let v = self.bytes[self.index];
+        self.index += 1;
+        v
+    }
+
+    fn read_bool(&mut self) -> bool {
+        self.read_u8() != 0
+    }
+
+    fn read_u16(&mut self) -> u16 {
+        u16::from_le_bytes([self.read_u8(), self.read_u8()])
+    }
+
+    fn read_u32(&mut self) -> u32 {
+        u32::from_le_bytes([self.read_u8(), self.read_u8(), self.read_u8(), self.read_u8()])
+    }
+}
+
+impl<'a> Disassembler<'a> {
+    // Create a new disassembler for the module
+    pub fn new(module: &'a Module) -> Self {
+        Self {
+            module,
+            struct_names: HashMap::default(),
+            enum_names: HashMap::default(),
+        }
+    }
+
+    // Set a name to display for a struct id
+    pub fn set_struct_name(&mut self, id: u16, name: impl Into<String>) {
+        self.struct_names.insert(id, name.into());
+    }
+
+    // Set a name to display for an enum id
+    pub fn set_enum_name(&mut self, id: u16, name: impl Into<String>) {
+        self.enum_names.insert(id, name.into());
+    }
+
+    // Get the name of a struct
+    fn struct_name(&self, id: u16) -> String {
+        match self.struct_names.get(&id) {
+            Some(name) => name.clone(),
+            None => format!("struct#{}", id)
+        }
+    }
+
+    // Get the name of an enum
+    fn enum_name(&self, id: u16) -> String {
+        match self.enum_names.get(&id) {
+            Some(name) => name.clone(),
+            None => format!("enum#{}", id)
+        }
+    }
+
+    // Get a string constant, used for the names
+    fn constant_string(&self, id: u16) -> String {
+        match self.module.get_constant_at(id as usize) {
+            Some(Constant::Default(Value::String(s))) => s.clone(),
+            _ => format!("<constant {}>", id)
+        }
+    }
+
+    // Decode the arguments of an opcode
+    fn decode_arguments(&self, opcode: OpCode, reader: &mut ArgumentsReader) -> String {
+        match opcode {
+            OpCode::Constant => {
+                let id = reader.read_u16();
+                match self.module.get_constant_at(id as usize) {
+                    Some(constant) => format!("{} ({})", id, constant),
+                    None => format!("{} (<missing>)", id)
+                }
+            },
+            OpCode::MemoryLoad | OpCode::MemorySet | OpCode::MemoryInc => reader.read_u16().to_string(),
+            OpCode::MemoryAdd => format!("{} {}", reader.read_u16(), reader.read_u16()),
+            OpCode::SubLoad
+            | OpCode::PopN
+            | OpCode::CopyN
+            | OpCode::Swap
+            | OpCode::NewArray
+            | OpCode::NewMap
+            | OpCode::Unpack => reader.read_u8().to_string(),
+            OpCode::Swap2 => format!("{} {}", reader.read_u8(), reader.read_u8()),
+            OpCode::Jump
+            | OpCode::JumpIfFalse
+            | OpCode::IteratorNext
+            | OpCode::LtJumpIfFalse
+            | OpCode::LteJumpIfFalse
+            | OpCode::GtJumpIfFalse
+            | OpCode::GteJumpIfFalse => format!("@{}", reader.read_u32()),
+            OpCode::JumpTable => {
+                let addr = reader.read_u32();
+                let first = reader.read_u16();
+                let len = reader.read_u16();
+                format!("@{} first={} len={}", addr, first, len)
+            },
+            OpCode::Cast => {
+                let byte = reader.read_u8();
+                match Type::primitive_type_from_byte(byte) {
+                    Some(t) => t.to_string(),
+                    None => format!("<invalid type {}>", byte)
+                }
+            },
+            OpCode::MemoryCast => {
+                let id = reader.read_u16();
+                let byte = reader.read_u8();
+                match Type::primitive_type_from_byte(byte) {
+                    Some(t) => format!("{} {}", id, t),
+                    None => format!("{} <invalid type {}>", id, byte)
+                }
+            },
+            OpCode::InvokeChunk | OpCode::SysCall => {
+                let id = reader.read_u16();
+                let on_value = reader.read_bool();
+                let args = reader.read_u8();
+                let kind = if opcode == OpCode::InvokeChunk { "chunk" } else { "syscall" };
+                format!("{}#{} on_value={} args={}", kind, id, on_value, args)
+            },
+            OpCode::ExternalCall => {
+                let module = self.constant_string(reader.read_u16());
+                let export = self.constant_string(reader.read_u16());
+                let args = reader.read_u8();
+                format!("{}::{} args={}", module, export, args)
+            },
+            OpCode::CallPrecompile => format!("precompile#{}", reader.read_u16()),
+            OpCode::ChunkId => format!("chunk#{}", reader.read_u16()),
+            OpCode::NewStruct => self.struct_name(reader.read_u16()),
+            OpCode::NewEnum => {
+                let id = reader.read_u16();
+                let variant = reader.read_u8();
+                format!("{}::{}", self.enum_name(id), variant)
+            },
+            _ => String::new()
+        }
+    }
+
+    // Decode all the instructions of a chunk
+    pub fn disassemble_instructions(&self, id: usize, chunk: &Chunk) -> Result<Vec<DisassembledInstruction>, DisassemblerError> {
+        let bytes = chunk.get_instructions();
+        let mut instructions = Vec::new();
+        let mut offset = 0;
+        while let Some(byte) = bytes.get(offset) {
+            let opcode = OpCode::from_byte(*byte)
+                .ok_or(DisassemblerError::InvalidOpCode { chunk: id, offset, byte: *byte })?;
+
+            let len = opcode.arguments_bytes();
+            let args = bytes.get(offset + 1..offset + 1 + len)
+                .ok_or(DisassemblerError::InvalidOpCodeArguments { chunk: id, offset, opcode })?;
+
+            let mut reader = ArgumentsReader { bytes: args, index: 0 };
+            instructions.push(DisassembledInstruction {
+                offset,
+                opcode,
+                arguments: self.decode_arguments(opcode, &mut reader)
+            });
+
+            offset += 1 + len;
+        }
+
+        Ok(instructions)
+    }
+
+    // Produce the listing of a chunk
+    pub fn disassemble_chunk(&self, id: usize) -> Result<String, DisassemblerError> {
+        let chunk = self.module.get_chunk_at(id)
+            .ok_or(DisassemblerError::ChunkNotFound(id))?;
+
+        let mut output = String::new();
+        let kind = if self.module.is_entry_chunk(id) {
+            "entry"
+        } else if self.module.is_test_chunk(id) {
+            "test"
+        } else {
+            "chunk"
+        };
+        let _ = writeln!(output, "{} {}:", kind, id);
+        for instruction in self.disassemble_instructions(id, chunk)? {
+            let name = format!("{:?}", instruction.opcode);
+            let _ = writeln!(output, "    {:04} {:<16} {}", instruction.offset, name, instruction.arguments);
+        }
+
+        Ok(output)
+    }
+
+    // Produce the listing of the whole module
+    pub fn disassemble(&self) -> Result<String, DisassemblerError> {
+        let mut output = String::new();
+
+        if !self.module.constants().is_empty() {
+            let _ = writeln!(output, "constants:");
+            for (i, constant) in self.module.constants().iter().enumerate() {
+                let _ = writeln!(output, "    {}: {}", i, constant.0);
+            }
+        }
+
+        if !self.module.structs().is_empty() {
+            let _ = writeln!(output, "structs:");
+            for s in self.module.structs() {
+                let fields = s.fields().iter().map(|f| f.to_string()).collect::<Vec<_>>();
+                let _ = writeln!(output, "    {} {{ {} }}", self.struct_name(s.id()), fields.join(", "));
+            }
+        }
+
+        if !self.module.enums().is_empty() {
+            let _ = writeln!(output, "enums:");
+            for e in self.module.enums() {
+                let variants = e.variants()
+                    .iter()
+                    .enumerate()
+                    .map(|(i, v)| {
+                        let fields = v.fields().iter().map(|f| f.to_string()).collect::<Vec<_>>();
+                        format!("{}({})", i, fields.join(", "))
+                    })
+                    .collect::<Vec<_>>();
+                let _ = writeln!(output, "    {} {{ {} }}", self.enum_name(e.id()), variants.join(", "));
+            }
+        }
+
+        for id in 0..self.module.chunks().len() {
+            output.push_str(&self.disassemble_chunk(id)?);
+        }
+
+        Ok(output)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use xelis_types::{StructType, Value};
+    use super::*;
+
+    #[test]
+    fn test_disassemble_chunk() {
+        let mut module = Module::new();
+        module.add_constant(Value::U64(10));
+        module.add_struct(StructType::new(0, vec![Type::U64]));
+
+        let mut chunk = Chunk::new();
+        chunk.emit_opcode(OpCode::Constant);
+        chunk.write_u16(0);
+        chunk.emit_opcode(OpCode::NewStruct);
+        chunk.write_u16(0);
+        chunk.emit_opcode(OpCode::Cast);
+        chunk.write_u8(Type::U8.primitive_byte().unwrap());
+        chunk.emit_opcode(OpCode::Return);
+        module.add_entry_chunk(chunk);
+
+        let mut disassembler = Disassembler::new(&module);
+        disassembler.set_struct_name(0, "Test");
+
+        let instructions = disassembler.disassemble_instructions(0, module.get_chunk_at(0).unwrap()).unwrap();
+        assert_eq!(instructions.len(), 4);
+        assert_eq!(instructions[0].arguments, "0 (10)");
+        assert_eq!(instructions[1].arguments, "Test");
+        assert_eq!(instructions[2].arguments, "u8");
+        assert_eq!(instructions[3].offset, 8);
+
+        let output = disassembler.disassemble().unwrap();
+        assert!(output.contains("Test { u64 }"));
+        assert!(output.contains("entry 0:"));
+        assert!(output.contains("0003 NewStruct"));
+    }
+
+    #[test]
+    fn test_disassemble_invalid() {
+        let mut module = Module::new();
+        let mut chunk = Chunk::new();
+        chunk.emit_opcode(OpCode::Jump);
+        chunk.write_u8(0);
+        module.add_chunk(chunk);
+
+        let disassembler = Disassembler::new(&module);
+        assert_eq!(
+            disassembler.disassemble(),
+            Err(DisassemblerError::InvalidOpCodeArguments { chunk: 0, offset: 0, opcode: OpCode::Jump })
+        );
+    }
+}
@@ -1,7 +1,27 @@
+#![cfg_attr(not(any(feature = "std", test)), no_std)]
+
+extern crate alloc;
+
 mod chunk;
 mod opcode;
 mod module;
+mod disassembler;
+mod serializer;
+mod debug_info;
+mod abi;
+mod version;
+mod stats;
+mod diff;
+mod cfg;
 
 pub use chunk::Chunk;
 pub use opcode::OpCode;
-pub use module::Module;
\ No newline at end of file
+pub use module::{Module, ChunkSignature};
+pub use disassembler::{Disassembler, DisassembledInstruction, DisassemblerError};
+pub use debug_info::{DebugInfo, SourceSpan};
+pub use abi::*;
+pub use version::{ModuleFeatures, ModuleVersion};
+pub use stats::{ChunkStats, ModuleStats};
+pub use diff::{ModuleDiff, StructDiff, EnumDiff, FieldsDiff};
+pub use cfg::{BasicBlock, CfgError, ControlFlowGraph};
+pub use serializer::{SerializerError, MODULE_MAGIC, MODULE_VERSION};
\ No newline at end of file
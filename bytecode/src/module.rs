@@ -1,36 +1,129 @@
+use core::fmt;
+use alloc::{string::String, vec::Vec};
+use xelis_types::{EnumType, StructType, Constant, ConstantWrapper, HashMap, HashSet, IndexMap, IndexSet, Type};
 
-use std::collections::HashSet;
-use indexmap::IndexSet;
-use xelis_types::{EnumType, StructType, Constant, ConstantWrapper};
+use super::{Chunk, DebugInfo, Disassembler, ModuleFeatures, ModuleVersion, OpCode};
 
-use super::Chunk;
+// Signature of an exported chunk with the names of its parameters
+// It describes the chunk to the callers without its source
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct ChunkSignature {
+    parameters: Vec<(String, Type)>,
+    return_type: Option<Type>,
+}
+
+impl ChunkSignature {
+    pub fn new(parameters: Vec<(String, Type)>, return_type: Option<Type>) -> Self {
+        Self {
+            parameters,
+            return_type
+        }
+    }
+
+    // Get the named parameters, in the order of the call
+    #[inline]
+    pub fn parameters(&self) -> &[(String, Type)] {
+        &self.parameters
+    }
+
+    // Get the type returned by the chunk
+    #[inline]
+    pub fn return_type(&self) -> Option<&Type> {
+        self.return_type.as_ref()
+    }
+}
 
 // A module is a collection of declared chunks, constants and types
 // It represents a program compiled in bytecode
 #[derive(Debug, Clone)]
 pub struct Module {
+    // Version of the bytecode targeted
+    version: ModuleVersion,
+    // Optional features required by the chunks
+    features: ModuleFeatures,
     // Set of constants used by the program
     constants: IndexSet<ConstantWrapper>,
     // Available chunks
     chunks: Vec<Chunk>,
     // Chunks callable from external programs
     entry_chunk_ids: HashSet<usize>,
+    // Chunks callable by name from other modules
+    exports: IndexMap<String, usize>,
+    // Signatures of the exported chunks, used to describe them in the ABI
+    signatures: HashMap<usize, ChunkSignature>,
+    // Events emitted by the chunks with the type of their data
+    events: IndexMap<String, Type>,
+    // Test chunks by their name, only invoked by a test runner
+    // This is not part of the canonical binary format
+    tests: IndexMap<String, usize>,
     // registered structs
     structs: IndexSet<StructType>,
     // registered enums
-    enums: IndexSet<EnumType>
+    enums: IndexSet<EnumType>,
+    // Structs whose layout changed from the previous version of the module
+    // by their index, with the entry chunk migrating their stored values
+    migrations: IndexMap<usize, usize>,
+    // Optional source mapping of the chunks
+    // This is not part of the canonical binary format
+    debug_info: Option<DebugInfo>
 }
 
 impl Module {
     // Create a new module
     pub fn new() -> Self {
         Self {
-            constants: IndexSet::new(),
+            version: ModuleVersion::CURRENT,
+            features: ModuleFeatures::NONE,
+            constants: IndexSet::default(),
             chunks: Vec::new(),
-            entry_chunk_ids: HashSet::new(),
-            structs: IndexSet::new(),
-            enums: IndexSet::new()
+            entry_chunk_ids: HashSet::default(),
+            exports: IndexMap::default(),
+            signatures: HashMap::default(),
+            events: IndexMap::default(),
+            tests: IndexMap::default(),
+            structs: IndexSet::default(),
+            enums: IndexSet::default(),
+            migrations: IndexMap::default(),
+            debug_info: None
+        }
+    }
+
+    // Get the version of the bytecode targeted by the module
+    #[inline]
+    pub fn version(&self) -> ModuleVersion {
+        self.version
+    }
+
+    #[inline]
+    pub fn set_version(&mut self, version: ModuleVersion) {
+        self.version = version;
+    }
+
+    // Get the features declared by the module
+    #[inline]
+    pub fn features(&self) -> ModuleFeatures {
+        self.features
+    }
+
+    #[inline]
+    pub fn set_features(&mut self, features: ModuleFeatures) {
+        self.features = features;
+    }
+
+    // Compute the features required by the instructions of the chunks
+    // The decoding stops at an invalid opcode, which is rejected by the validator
+    pub fn used_features(&self) -> ModuleFeatures {
+        let mut features = ModuleFeatures::NONE;
+        for chunk in self.chunks.iter() {
+            let instructions = chunk.get_instructions();
+            let mut index = 0;
+            while let Some(opcode) = instructions.get(index).and_then(|byte| OpCode::from_byte(*byte)) {
+                features.insert(opcode.required_feature());
+                index += 1 + opcode.arguments_bytes();
+            }
         }
+
+        features
     }
 
     // Get the constants declared in the module
@@ -45,6 +138,17 @@ impl Module {
         self.constants.insert_full(ConstantWrapper(value.into())).0
     }
 
+    // Keep only the constants at the indexes accepted, in their order
+    // The instructions reading them must be updated by the caller
+    pub fn retain_constants(&mut self, mut keep: impl FnMut(usize) -> bool) {
+        let mut index = 0;
+        self.constants.retain(|_| {
+            let kept = keep(index);
+            index += 1;
+            kept
+        });
+    }
+
     // Get a constant at a specific index
     #[inline]
     pub fn get_constant_at(&self, index: usize) -> Option<&Constant> {
@@ -95,6 +199,94 @@ impl Module {
         self.chunks.get_mut(index)
     }
 
+    // Get the chunks exported by name
+    #[inline]
+    pub fn exports(&self) -> &IndexMap<String, usize> {
+        &self.exports
+    }
+
+    // Export a chunk under a name so it can be called from another module
+    // Returns false if the name is already exported
+    #[inline]
+    pub fn add_export(&mut self, name: impl Into<String>, chunk_id: usize) -> bool {
+        match self.exports.entry(name.into()) {
+            indexmap::map::Entry::Occupied(_) => false,
+            indexmap::map::Entry::Vacant(entry) => {
+                entry.insert(chunk_id);
+                true
+            }
+        }
+    }
+
+    // Get the chunk id exported under a name
+    #[inline]
+    pub fn get_export(&self, name: &str) -> Option<usize> {
+        self.exports.get(name).copied()
+    }
+
+    // Check if a chunk is exported
+    #[inline]
+    pub fn is_exported_chunk(&self, index: usize) -> bool {
+        self.exports.values().any(|id| *id == index)
+    }
+
+    // Get the entry chunk exported under a name
+    // The public functions are also exported but can't be invoked as an entry
+    #[inline]
+    pub fn entry_by_name(&self, name: &str) -> Option<usize> {
+        self.get_export(name)
+            .filter(|id| self.is_entry_chunk(*id))
+    }
+
+    // Get the signature of an exported chunk
+    #[inline]
+    pub fn get_signature(&self, chunk_id: usize) -> Option<&ChunkSignature> {
+        self.signatures.get(&chunk_id)
+    }
+
+    // Set the signature of an exported chunk
+    #[inline]
+    pub fn set_signature(&mut self, chunk_id: usize, signature: ChunkSignature) {
+        self.signatures.insert(chunk_id, signature);
+    }
+
+    // Get the events emitted by the module with the type of their data
+    #[inline]
+    pub fn events(&self) -> &IndexMap<String, Type> {
+        &self.events
+    }
+
+    // Declare an event emitted by the module
+    // Returns false if the event is already declared
+    #[inline]
+    pub fn add_event(&mut self, name: impl Into<String>, data: Type) -> bool {
+        match self.events.entry(name.into()) {
+            indexmap::map::Entry::Occupied(_) => false,
+            indexmap::map::Entry::Vacant(entry) => {
+                entry.insert(data);
+                true
+            }
+        }
+    }
+
+    // Get the test chunks by their name
+    #[inline]
+    pub fn tests(&self) -> &IndexMap<String, usize> {
+        &self.tests
+    }
+
+    // Register a chunk as a test under its name
+    #[inline]
+    pub fn add_test(&mut self, name: impl Into<String>, chunk_id: usize) {
+        self.tests.insert(name.into(), chunk_id);
+    }
+
+    // Check if a chunk is a test
+    #[inline]
+    pub fn is_test_chunk(&self, index: usize) -> bool {
+        self.tests.values().any(|id| *id == index)
+    }
+
     // Get all the structs declared in the module
     #[inline]
     pub fn structs(&self) -> &IndexSet<StructType> {
@@ -130,4 +322,51 @@ impl Module {
     pub fn get_enum_at(&self, index: usize) -> Option<&EnumType> {
         self.enums.get_index(index)
     }
+
+    // Get the structs migrated from the previous version of the module
+    // with the chunk migrating their stored values
+    #[inline]
+    pub fn migrations(&self) -> &IndexMap<usize, usize> {
+        &self.migrations
+    }
+
+    // Declare the chunk migrating the stored values of a struct whose layout changed
+    // Returns false if the struct is already migrated
+    #[inline]
+    pub fn add_migration(&mut self, struct_index: usize, chunk_id: usize) -> bool {
+        match self.migrations.entry(struct_index) {
+            indexmap::map::Entry::Occupied(_) => false,
+            indexmap::map::Entry::Vacant(entry) => {
+                entry.insert(chunk_id);
+                true
+            }
+        }
+    }
+
+    // Get the chunk migrating the stored values of a struct
+    #[inline]
+    pub fn get_migration(&self, struct_index: usize) -> Option<usize> {
+        self.migrations.get(&struct_index).copied()
+    }
+
+    // Get the debug info of the module
+    #[inline]
+    pub fn debug_info(&self) -> Option<&DebugInfo> {
+        self.debug_info.as_ref()
+    }
+
+    // Set the debug info of the module
+    #[inline]
+    pub fn set_debug_info(&mut self, debug_info: Option<DebugInfo>) {
+        self.debug_info = debug_info;
+    }
+}
+
+impl fmt::Display for Module {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match Disassembler::new(self).disassemble() {
+            Ok(output) => write!(f, "{}", output),
+            Err(e) => write!(f, "<{}>", e)
+        }
+    }
 }
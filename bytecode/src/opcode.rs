@@ -1,4 +1,4 @@
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub enum OpCode {
     // load constant
     Constant,
@@ -49,6 +49,9 @@ pub enum OpCode {
     InvokeChunk,
     // Same as InvokeChunk, but for system calls
     SysCall,
+    // read module name constant u16, export name constant u16, args u8
+    // Invoke an exported chunk of a module linked in the VM
+    ExternalCall,
     // pop length, pop N values => create array with N values
     NewArray,
     // pop type id, pop N values => create struct
@@ -59,6 +62,9 @@ pub enum OpCode {
     NewMap,
     // read enum id u16, variant id u8, pop N values => create enum
     NewEnum,
+    // read length u8, pop array of N values => push N values
+    // The first value is pushed last to be on top of the stack
+    Unpack,
 
     // Operators
     // +
@@ -131,6 +137,66 @@ pub enum OpCode {
     Inc,
     // --
     Dec,
+
+    // Checked Operators
+    // pop right, pop left => push optional, null on overflow
+    // +?
+    CheckedAdd,
+    // -?
+    CheckedSub,
+    // *?
+    CheckedMul,
+
+    // Saturating Operators
+    // Clamp the result to the bounds of the type
+    // +|
+    SaturatingAdd,
+    // -|
+    SaturatingSub,
+    // *|
+    SaturatingMul,
+
+    // Wrapping Operators
+    // Wrap around the bounds of the type
+    // +%
+    WrappingAdd,
+    // -%
+    WrappingSub,
+    // *%
+    WrappingMul,
+
+    // Superinstructions
+    // Fused sequences emitted by the optimizer to reduce the dispatches in loops
+    // read u16 left, u16 right => push registers[left] + registers[right]
+    MemoryAdd,
+    // read u16 index => registers[index] += 1
+    MemoryInc,
+    // read u32 addr, pop right, pop left => jump if !(left < right)
+    LtJumpIfFalse,
+    // read u32 addr, pop right, pop left => jump if !(left <= right)
+    LteJumpIfFalse,
+    // read u32 addr, pop right, pop left => jump if !(left > right)
+    GtJumpIfFalse,
+    // read u32 addr, pop right, pop left => jump if !(left >= right)
+    GteJumpIfFalse,
+
+    // N..=Y
+    NewRangeInclusive,
+
+    // read u32 default addr, u16 constant id of the first case, u16 len, pop value
+    // => jump to the Jump at (value - first) in the len ones following, or to the default addr
+    JumpTable,
+
+    // read precompile id u16, pop input bytes => push output bytes
+    // Execute an operation of the host registered in the environment
+    CallPrecompile,
+
+    // read u16 index, u8 primitive type => push registers[index] as type
+    MemoryCast,
+
+    // read chunk id u16 => push it as an u16
+    // The chunk is given as a function to a native, like the iterator adapters
+    ChunkId,
 }
 
 impl OpCode {
@@ -210,6 +276,31 @@ impl OpCode {
 
             OpCode::Inc => 57,
             OpCode::Dec => 58,
+
+            OpCode::CheckedAdd => 59,
+            OpCode::CheckedSub => 60,
+            OpCode::CheckedMul => 61,
+            OpCode::SaturatingAdd => 62,
+            OpCode::SaturatingSub => 63,
+            OpCode::SaturatingMul => 64,
+            OpCode::WrappingAdd => 65,
+            OpCode::WrappingSub => 66,
+            OpCode::WrappingMul => 67,
+
+            OpCode::ExternalCall => 68,
+            OpCode::Unpack => 69,
+
+            OpCode::MemoryAdd => 70,
+            OpCode::MemoryInc => 71,
+            OpCode::LtJumpIfFalse => 72,
+            OpCode::LteJumpIfFalse => 73,
+            OpCode::GtJumpIfFalse => 74,
+            OpCode::GteJumpIfFalse => 75,
+            OpCode::NewRangeInclusive => 76,
+            OpCode::JumpTable => 77,
+            OpCode::CallPrecompile => 78,
+            OpCode::MemoryCast => 79,
+            OpCode::ChunkId => 80,
         }
     }
 
@@ -285,6 +376,31 @@ impl OpCode {
 
             57 => OpCode::Inc,
             58 => OpCode::Dec,
+
+            59 => OpCode::CheckedAdd,
+            60 => OpCode::CheckedSub,
+            61 => OpCode::CheckedMul,
+            62 => OpCode::SaturatingAdd,
+            63 => OpCode::SaturatingSub,
+            64 => OpCode::SaturatingMul,
+            65 => OpCode::WrappingAdd,
+            66 => OpCode::WrappingSub,
+            67 => OpCode::WrappingMul,
+
+            68 => OpCode::ExternalCall,
+            69 => OpCode::Unpack,
+
+            70 => OpCode::MemoryAdd,
+            71 => OpCode::MemoryInc,
+            72 => OpCode::LtJumpIfFalse,
+            73 => OpCode::LteJumpIfFalse,
+            74 => OpCode::GtJumpIfFalse,
+            75 => OpCode::GteJumpIfFalse,
+            76 => OpCode::NewRangeInclusive,
+            77 => OpCode::JumpTable,
+            78 => OpCode::CallPrecompile,
+            79 => OpCode::MemoryCast,
+            80 => OpCode::ChunkId,
             _ => return None,
         })
     }
@@ -326,17 +442,59 @@ impl OpCode {
             OpCode::Jump => 4, // u32 addr
             OpCode::JumpIfFalse => 4, // u32 addr
             OpCode::IteratorNext => 4, // u32 addr
-            OpCode::ArrayCall => 4, // index u32
             OpCode::Cast => 1, // primitive type id u8
             OpCode::InvokeChunk => 4, // id u16, on_value bool, args u8
             OpCode::SysCall => 4, // id u16, on_value bool, args u8
+            OpCode::ExternalCall => 5, // module constant u16, export constant u16, args u8
 
             OpCode::NewArray => 1, // u8 initial values
             OpCode::NewStruct => 2, // struct type id u16
             OpCode::NewRange => 0,
+            OpCode::NewRangeInclusive => 0,
             OpCode::NewMap => 1, // u8 initial values
+            OpCode::NewEnum => 3, // enum type id u16, variant id u8
+            OpCode::Unpack => 1, // u8 values
+
+            OpCode::MemoryAdd => 4, // u16 id, u16 id
+            OpCode::MemoryInc => 2, // u16 id
+            OpCode::LtJumpIfFalse
+            | OpCode::LteJumpIfFalse
+            | OpCode::GtJumpIfFalse
+            | OpCode::GteJumpIfFalse => 4, // u32 addr
+            OpCode::JumpTable => 8, // u32 addr, u16 constant id, u16 len
+            OpCode::CallPrecompile => 2, // u16 precompile id
+            OpCode::MemoryCast => 3, // u16 id, u8 primitive type
+            OpCode::ChunkId => 2, // u16 chunk id
 
             _ => 0,
         }
     }
+
+    // Is the OpCode a jump reading an u32 address
+    #[inline]
+    pub fn is_jump(&self) -> bool {
+        matches!(
+            self,
+            OpCode::Jump
+            | OpCode::JumpIfFalse
+            | OpCode::IteratorNext
+            | OpCode::LtJumpIfFalse
+            | OpCode::LteJumpIfFalse
+            | OpCode::GtJumpIfFalse
+            | OpCode::GteJumpIfFalse
+            | OpCode::JumpTable
+        )
+    }
+
+    // Get the comparison fused with a JumpIfFalse
+    #[inline]
+    pub const fn as_compare_jump(self) -> Option<Self> {
+        Some(match self {
+            OpCode::Lt => OpCode::LtJumpIfFalse,
+            OpCode::Lte => OpCode::LteJumpIfFalse,
+            OpCode::Gt => OpCode::GtJumpIfFalse,
+            OpCode::Gte => OpCode::GteJumpIfFalse,
+            _ => return None,
+        })
+    }
 }
\ No newline at end of file
@@ -0,0 +1,1406 @@
+use alloc::{borrow::ToOwned, boxed::Box, vec, vec::Vec};
+use thiserror::Error;
+use xelis_types::{
+    Constant,
+    EnumType,
+    EnumValueType,
+    EnumVariant,
+    IndexMap,
+    IndexSet,
+    OpaqueType,
+    StructType,
+    Type,
+    Value,
+    U256
+};
+
+use super::{Chunk, ChunkSignature, DebugInfo, Module, ModuleFeatures, ModuleVersion, SourceSpan};
+
+// Magic bytes at the start of each serialized module
+pub const MODULE_MAGIC: [u8; 4] = *b"XVMM";
+// Version of the binary format
+// Any change to the layout below must increase it
+pub const MODULE_VERSION: u8 = 1;
+
+// Maximum depth allowed for nested types and constants
+const MAX_DEPTH: usize = 64;
+
+// Tags used for the non primitive types
+// Primitive types are using Type::primitive_byte
+const TYPE_BLOB: u8 = 8;
+const TYPE_ARRAY: u8 = 9;
+const TYPE_OPTIONAL: u8 = 10;
+const TYPE_RANGE: u8 = 11;
+const TYPE_MAP: u8 = 12;
+const TYPE_STRUCT: u8 = 13;
+const TYPE_ENUM: u8 = 14;
+const TYPE_ANY: u8 = 15;
+const TYPE_T: u8 = 16;
+const TYPE_TUPLE: u8 = 17;
+const TYPE_RESULT: u8 = 18;
+const TYPE_ITERATOR: u8 = 19;
+const TYPE_OPAQUE: u8 = 20;
+
+// Tags used for the non primitive values
+const VALUE_NULL: u8 = 8;
+const VALUE_RANGE: u8 = 9;
+const VALUE_BLOB: u8 = 10;
+// Range with its end included, a separate id keeps the half-open ranges encoding
+const VALUE_RANGE_INCLUSIVE: u8 = 11;
+
+// Tags used for the constants
+const CONSTANT_DEFAULT: u8 = 0;
+const CONSTANT_STRUCT: u8 = 1;
+const CONSTANT_ARRAY: u8 = 2;
+const CONSTANT_OPTIONAL: u8 = 3;
+const CONSTANT_MAP: u8 = 4;
+const CONSTANT_ENUM: u8 = 5;
+
+#[derive(Debug, Error, PartialEq, Eq)]
+pub enum SerializerError {
+    #[error("unexpected end of input")]
+    UnexpectedEof,
+    #[error("invalid magic bytes")]
+    InvalidMagic,
+    #[error("unsupported version {0}")]
+    UnsupportedVersion(u8),
+    #[error("invalid type tag {0}")]
+    InvalidType(u8),
+    #[error("invalid value tag {0}")]
+    InvalidValue(u8),
+    #[error("invalid constant tag {0}")]
+    InvalidConstant(u8),
+    #[error("invalid bool byte {0}")]
+    InvalidBool(u8),
+    #[error("invalid utf-8 string")]
+    InvalidString,
+    #[error("struct {0} not found")]
+    StructNotFound(u16),
+    #[error("enum {0} not found")]
+    EnumNotFound(u16),
+    #[error("duplicated struct {0}")]
+    DuplicatedStruct(u16),
+    #[error("duplicated enum {0}")]
+    DuplicatedEnum(u16),
+    #[error("duplicated constant")]
+    DuplicatedConstant,
+    #[error("duplicated map key")]
+    DuplicatedMapKey,
+    #[error("max depth reached")]
+    MaxDepthReached,
+    #[error("duplicated export")]
+    DuplicatedExport,
+    #[error("exported chunk {0} not found")]
+    ExportChunkNotFound(u16),
+    #[error("chunk {0} is not exported")]
+    NotAnExportedChunk(u16),
+    #[error("duplicated signature for chunk {0}")]
+    DuplicatedSignature(u16),
+    #[error("duplicated event")]
+    DuplicatedEvent,
+    #[error("migrated struct {0} not found")]
+    MigrationStructNotFound(u16),
+    #[error("migration chunk {0} not found")]
+    MigrationChunkNotFound(u16),
+    #[error("duplicated migration for struct {0}")]
+    DuplicatedMigration(u16),
+    #[error("debug info of chunk {0} not found")]
+    DebugInfoChunkNotFound(u16),
+    #[error("invalid debug info of chunk {0}")]
+    InvalidDebugInfo(u16),
+    #[error("trailing bytes after the module")]
+    TrailingBytes,
+    #[error("length or index {0} doesn't fit in its encoding")]
+    LengthOverflow(usize),
+}
+
+// Writer of the binary format
+struct Writer {
+    bytes: Vec<u8>
+}
+
+impl Writer {
+    fn write_u8(&mut self, value: u8) {
+        self.bytes.push(value);
+    }
+
+    fn write_bool(&mut self, value: bool) {
+        self.bytes.push(value as u8);
+    }
+
+    fn write_u16(&mut self, value: u16) {
+        self.bytes.extend_from_slice(&value.to_le_bytes());
+    }
+
+    fn write_u32(&mut self, value: u32) {
+        self.bytes.extend_from_slice(&value.to_le_bytes());
+    }
+
+    fn write_u64(&mut self, value: u64) {
+        self.bytes.extend_from_slice(&value.to_le_bytes());
+    }
+
+    // Write a length or an index, rejected if it doesn't fit in a u8
+    fn write_u8_checked(&mut self, value: usize) -> Result<(), SerializerError> {
+        let value = u8::try_from(value).map_err(|_| SerializerError::LengthOverflow(value))?;
+        self.write_u8(value);
+        Ok(())
+    }
+
+    // Write a length or an index, rejected if it doesn't fit in a u16
+    fn write_u16_checked(&mut self, value: usize) -> Result<(), SerializerError> {
+        let value = u16::try_from(value).map_err(|_| SerializerError::LengthOverflow(value))?;
+        self.write_u16(value);
+        Ok(())
+    }
+
+    // Write a length or an offset, rejected if it doesn't fit in a u32
+    fn write_u32_checked(&mut self, value: usize) -> Result<(), SerializerError> {
+        let value = u32::try_from(value).map_err(|_| SerializerError::LengthOverflow(value))?;
+        self.write_u32(value);
+        Ok(())
+    }
+
+    fn write_bytes(&mut self, value: &[u8]) -> Result<(), SerializerError> {
+        self.write_u32_checked(value.len())?;
+        self.bytes.extend_from_slice(value);
+        Ok(())
+    }
+
+    fn write_type(&mut self, _type: &Type) -> Result<(), SerializerError> {
+        if let Some(byte) = _type.primitive_byte() {
+            self.write_u8(byte);
+            return Ok(())
+        }
+
+        match _type {
+            Type::Blob => self.write_u8(TYPE_BLOB),
+            Type::Array(inner) => {
+                self.write_u8(TYPE_ARRAY);
+                self.write_type(inner)?;
+            },
+            Type::Optional(inner) => {
+                self.write_u8(TYPE_OPTIONAL);
+                self.write_type(inner)?;
+            },
+            Type::Range(inner) => {
+                self.write_u8(TYPE_RANGE);
+                self.write_type(inner)?;
+            },
+            Type::Map(key, value) => {
+                self.write_u8(TYPE_MAP);
+                self.write_type(key)?;
+                self.write_type(value)?;
+            },
+            Type::Tuple(types) => {
+                self.write_u8(TYPE_TUPLE);
+                self.write_u8_checked(types.len())?;
+                for _type in types {
+                    self.write_type(_type)?;
+                }
+            },
+            Type::Result(value, error) => {
+                self.write_u8(TYPE_RESULT);
+                self.write_type(value)?;
+                self.write_type(error)?;
+            },
+            Type::Iterator(inner) => {
+                self.write_u8(TYPE_ITERATOR);
+                self.write_type(inner)?;
+            },
+            Type::Struct(s) => {
+                self.write_u8(TYPE_STRUCT);
+                self.write_u16(s.id());
+            },
+            Type::Enum(e) => {
+                self.write_u8(TYPE_ENUM);
+                self.write_u16(e.id());
+            },
+            Type::Opaque(o) => {
+                self.write_u8(TYPE_OPAQUE);
+                self.write_u16(o.id());
+            },
+            Type::Any => self.write_u8(TYPE_ANY),
+            Type::T(id) => {
+                self.write_u8(TYPE_T);
+                self.write_u8(*id);
+            },
+            // Primitive types are already handled
+            _ => unreachable!()
+        }
+
+        Ok(())
+    }
+
+    fn write_value(&mut self, value: &Value) -> Result<(), SerializerError> {
+        match value {
+            Value::Null => self.write_u8(VALUE_NULL),
+            Value::U8(v) => {
+                self.write_u8(0);
+                self.write_u8(*v);
+            },
+            Value::U16(v) => {
+                self.write_u8(1);
+                self.write_u16(*v);
+            },
+            Value::U32(v) => {
+                self.write_u8(2);
+                self.write_u32(*v);
+            },
+            Value::U64(v) => {
+                self.write_u8(3);
+                self.bytes.extend_from_slice(&v.to_le_bytes());
+            },
+            Value::U128(v) => {
+                self.write_u8(4);
+                self.bytes.extend_from_slice(&v.to_le_bytes());
+            },
+            Value::U256(v) => {
+                self.write_u8(5);
+                self.bytes.extend_from_slice(&v.to_le_bytes());
+            },
+            Value::Boolean(v) => {
+                self.write_u8(6);
+                self.write_bool(*v);
+            },
+            Value::String(v) => {
+                self.write_u8(7);
+                self.write_bytes(v.as_bytes())?;
+            },
+            Value::Range(start, end, _type, inclusive) => {
+                self.write_u8(if *inclusive { VALUE_RANGE_INCLUSIVE } else { VALUE_RANGE });
+                self.write_value(start)?;
+                self.write_value(end)?;
+                self.write_type(_type)?;
+            },
+            Value::Blob(v) => {
+                self.write_u8(VALUE_BLOB);
+                self.write_bytes(v)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    fn write_constant(&mut self, constant: &Constant) -> Result<(), SerializerError> {
+        match constant {
+            Constant::Default(v) => {
+                self.write_u8(CONSTANT_DEFAULT);
+                self.write_value(v)?;
+            },
+            Constant::Struct(fields, struct_type) => {
+                self.write_u8(CONSTANT_STRUCT);
+                self.write_u16(struct_type.id());
+                self.write_u8_checked(fields.len())?;
+                for field in fields {
+                    self.write_constant(field)?;
+                }
+            },
+            Constant::Array(values) => {
+                self.write_u8(CONSTANT_ARRAY);
+                self.write_u32_checked(values.len())?;
+                for value in values {
+                    self.write_constant(value)?;
+                }
+            },
+            Constant::Optional(value) => {
+                self.write_u8(CONSTANT_OPTIONAL);
+                self.write_bool(value.is_some());
+                if let Some(value) = value {
+                    self.write_constant(value)?;
+                }
+            },
+            Constant::Map(map) => {
+                self.write_u8(CONSTANT_MAP);
+                self.write_u32_checked(map.len())?;
+                // Entries are written in their insertion order
+                // which is part of the map value
+                for (key, value) in map {
+                    self.write_constant(key)?;
+                    self.write_constant(value)?;
+                }
+            },
+            Constant::Enum(fields, enum_type) => {
+                self.write_u8(CONSTANT_ENUM);
+                self.write_u16(enum_type.id());
+                self.write_u8(enum_type.variant_id());
+                self.write_u8_checked(fields.len())?;
+                for field in fields {
+                    self.write_constant(field)?;
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    // The chunks are sorted by id to stay canonical
+    fn write_debug_info(&mut self, debug_info: &DebugInfo) -> Result<(), SerializerError> {
+        let mut names: Vec<_> = debug_info.chunks_names().collect();
+        names.sort_unstable_by_key(|(id, _)| *id);
+        self.write_u16_checked(names.len())?;
+        for (id, name) in names {
+            self.write_u16_checked(id)?;
+            self.write_bytes(name.as_bytes())?;
+        }
+
+        let mut chunks: Vec<_> = debug_info.chunks_spans().collect();
+        chunks.sort_unstable_by_key(|(id, _)| *id);
+        self.write_u16_checked(chunks.len())?;
+        for (id, spans) in chunks {
+            self.write_u16_checked(id)?;
+            self.write_u32_checked(spans.len())?;
+            for (offset, span) in spans {
+                self.write_u32_checked(*offset)?;
+                self.write_u32_checked(span.line)?;
+                self.write_u32_checked(span.column_start)?;
+                self.write_u32_checked(span.column_end)?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+// Reader of the binary format
+// Structs and enums already decoded are used to resolve the types
+struct Reader<'a> {
+    bytes: &'a [u8],
+    index: usize,
+    structs: IndexSet<StructType>,
+    enums: IndexSet<EnumType>,
+}
+
+impl<'a> Reader<'a> {
+    fn read_slice(&mut self, len: usize) -> Result<&'a [u8], SerializerError> {
+        let end = self.index.checked_add(len)
+            .ok_or(SerializerError::UnexpectedEof)?;
+        let slice = self.bytes.get(self.index..end)
+            .ok_or(SerializerError::UnexpectedEof)?;
+        self.index = end;
+        Ok(slice)
+    }
+
+    fn read_array<const N: usize>(&mut self) -> Result<[u8; N], SerializerError> {
+        let mut array = [0; N];
+        array.copy_from_slice(self.read_slice(N)?);
+        Ok(array)
+    }
+
+    fn read_u8(&mut self) -> Result<u8, SerializerError> {
+        Ok(self.read_array::<1>()?[0])
+    }
+
+    fn read_bool(&mut self) -> Result<bool, SerializerError> {
+        match self.read_u8()? {
+            0 => Ok(false),
+            1 => Ok(true),
+            v => Err(SerializerError::InvalidBool(v))
+        }
+    }
+
+    fn read_u16(&mut self) -> Result<u16, SerializerError> {
+        Ok(u16::from_le_bytes(self.read_array()?))
+    }
+
+    fn read_u32(&mut self) -> Result<u32, SerializerError> {
+        Ok(u32::from_le_bytes(self.read_array()?))
+    }
+
+    fn read_u64(&mut self) -> Result<u64, SerializerError> {
+        Ok(u64::from_le_bytes(self.read_array()?))
+    }
+
+    fn read_bytes(&mut self) -> Result<&'a [u8], SerializerError> {
+        let len = self.read_u32()? as usize;
+        self.read_slice(len)
+    }
+
+    fn get_struct(&self, id: u16) -> Result<StructType, SerializerError> {
+        self.structs.iter()
+            .find(|s| s.id() == id)
+            .cloned()
+            .ok_or(SerializerError::StructNotFound(id))
+    }
+
+    fn get_enum(&self, id: u16) -> Result<EnumType, SerializerError> {
+        self.enums.iter()
+            .find(|e| e.id() == id)
+            .cloned()
+            .ok_or(SerializerError::EnumNotFound(id))
+    }
+
+    fn read_string(&mut self) -> Result<&'a str, SerializerError> {
+        core::str::from_utf8(self.read_bytes()?)
+            .map_err(|_| SerializerError::InvalidString)
+    }
+
+    // Read a type without resolving its structs and enums
+    // Used to find the definitions before their types can be resolved
+    fn skip_type(&mut self, depth: usize) -> Result<(), SerializerError> {
+        if depth > MAX_DEPTH {
+            return Err(SerializerError::MaxDepthReached)
+        }
+
+        let tag = self.read_u8()?;
+        if Type::primitive_type_from_byte(tag).is_some() {
+            return Ok(())
+        }
+
+        match tag {
+            TYPE_BLOB | TYPE_ANY => {},
+            TYPE_ARRAY | TYPE_OPTIONAL | TYPE_RANGE | TYPE_ITERATOR => self.skip_type(depth + 1)?,
+            TYPE_MAP | TYPE_RESULT => {
+                self.skip_type(depth + 1)?;
+                self.skip_type(depth + 1)?;
+            },
+            TYPE_TUPLE => {
+                let len = self.read_u8()?;
+                for _ in 0..len {
+                    self.skip_type(depth + 1)?;
+                }
+            },
+            TYPE_STRUCT | TYPE_ENUM | TYPE_OPAQUE => {
+                self.read_u16()?;
+            },
+            TYPE_T => {
+                self.read_u8()?;
+            },
+            v => return Err(SerializerError::InvalidType(v))
+        };
+
+        Ok(())
+    }
+
+    fn read_struct_fields(&mut self) -> Result<Vec<Type>, SerializerError> {
+        let len = self.read_u8()?;
+        let mut fields = Vec::with_capacity(len as usize);
+        for _ in 0..len {
+            fields.push(self.read_type(0)?);
+        }
+
+        Ok(fields)
+    }
+
+    fn read_enum_variants(&mut self) -> Result<Vec<EnumVariant>, SerializerError> {
+        let len = self.read_u8()?;
+        let mut variants = Vec::with_capacity(len as usize);
+        for _ in 0..len {
+            variants.push(EnumVariant::new(self.read_struct_fields()?));
+        }
+
+        Ok(variants)
+    }
+
+    // Read the structs and enums definitions
+    // A struct can have an enum field and an enum a struct field: the ids and the offsets
+    // of all the definitions are read first, then each type is built once its inner types are built
+    fn read_definitions(&mut self) -> Result<(), SerializerError> {
+        let mut structs = Vec::new();
+        let structs_len = self.read_u16()?;
+        for _ in 0..structs_len {
+            let id = self.read_u16()?;
+            if structs.iter().any(|(s, _)| *s == id) {
+                return Err(SerializerError::DuplicatedStruct(id))
+            }
+
+            structs.push((id, self.index));
+            let len = self.read_u8()?;
+            for _ in 0..len {
+                self.skip_type(0)?;
+            }
+        }
+
+        let mut enums = Vec::new();
+        let enums_len = self.read_u16()?;
+        for _ in 0..enums_len {
+            let id = self.read_u16()?;
+            if enums.iter().any(|(e, _)| *e == id) {
+                return Err(SerializerError::DuplicatedEnum(id))
+            }
+
+            enums.push((id, self.index));
+            let len = self.read_u8()?;
+            for _ in 0..len {
+                let fields_len = self.read_u8()?;
+                for _ in 0..fields_len {
+                    self.skip_type(0)?;
+                }
+            }
+        }
+
+        let end = self.index;
+        let mut built_structs = vec![None; structs.len()];
+        let mut built_enums = vec![None; enums.len()];
+        loop {
+            let mut progress = false;
+            let mut missing = None;
+            for (i, (id, offset)) in structs.iter().enumerate() {
+                if built_structs[i].is_some() {
+                    continue;
+                }
+
+                self.index = *offset;
+                match self.read_struct_fields() {
+                    Ok(fields) => {
+                        let _type = StructType::new(*id, fields);
+                        self.structs.insert(_type.clone());
+                        built_structs[i] = Some(_type);
+                        progress = true;
+                    },
+                    Err(e @ (SerializerError::StructNotFound(_) | SerializerError::EnumNotFound(_))) => missing = Some(e),
+                    Err(e) => return Err(e)
+                }
+            }
+
+            for (i, (id, offset)) in enums.iter().enumerate() {
+                if built_enums[i].is_some() {
+                    continue;
+                }
+
+                self.index = *offset;
+                match self.read_enum_variants() {
+                    Ok(variants) => {
+                        let _type = EnumType::new(*id, variants);
+                        self.enums.insert(_type.clone());
+                        built_enums[i] = Some(_type);
+                        progress = true;
+                    },
+                    Err(e @ (SerializerError::StructNotFound(_) | SerializerError::EnumNotFound(_))) => missing = Some(e),
+                    Err(e) => return Err(e)
+                }
+            }
+
+            match missing {
+                // Everything is built
+                None => break,
+                // A type is unknown or the types are recursive
+                Some(e) if !progress => return Err(e),
+                Some(_) => {}
+            }
+        }
+
+        // Keep the order of the definitions
+        self.structs = built_structs.into_iter().flatten().collect();
+        self.enums = built_enums.into_iter().flatten().collect();
+        self.index = end;
+
+        Ok(())
+    }
+
+    fn read_type(&mut self, depth: usize) -> Result<Type, SerializerError> {
+        if depth > MAX_DEPTH {
+            return Err(SerializerError::MaxDepthReached)
+        }
+
+        let tag = self.read_u8()?;
+        if let Some(_type) = Type::primitive_type_from_byte(tag) {
+            return Ok(_type)
+        }
+
+        Ok(match tag {
+            TYPE_BLOB => Type::Blob,
+            TYPE_ARRAY => Type::Array(Box::new(self.read_type(depth + 1)?)),
+            TYPE_OPTIONAL => Type::Optional(Box::new(self.read_type(depth + 1)?)),
+            TYPE_RANGE => Type::Range(Box::new(self.read_type(depth + 1)?)),
+            TYPE_ITERATOR => Type::Iterator(Box::new(self.read_type(depth + 1)?)),
+            TYPE_MAP => {
+                let key = self.read_type(depth + 1)?;
+                let value = self.read_type(depth + 1)?;
+                Type::Map(Box::new(key), Box::new(value))
+            },
+            TYPE_TUPLE => {
+                let len = self.read_u8()?;
+                let mut types = Vec::with_capacity(len as usize);
+                for _ in 0..len {
+                    types.push(self.read_type(depth + 1)?);
+                }
+                Type::Tuple(types)
+            },
+            TYPE_RESULT => {
+                let value = self.read_type(depth + 1)?;
+                let error = self.read_type(depth + 1)?;
+                Type::Result(Box::new(value), Box::new(error))
+            },
+            TYPE_STRUCT => {
+                let id = self.read_u16()?;
+                Type::Struct(self.get_struct(id)?)
+            },
+            TYPE_ENUM => {
+                let id = self.read_u16()?;
+                Type::Enum(self.get_enum(id)?)
+            },
+            TYPE_OPAQUE => Type::Opaque(OpaqueType::new(self.read_u16()?)),
+            TYPE_ANY => Type::Any,
+            TYPE_T => Type::T(self.read_u8()?),
+            v => return Err(SerializerError::InvalidType(v))
+        })
+    }
+
+    fn read_value(&mut self, depth: usize) -> Result<Value, SerializerError> {
+        if depth > MAX_DEPTH {
+            return Err(SerializerError::MaxDepthReached)
+        }
+
+        Ok(match self.read_u8()? {
+            0 => Value::U8(self.read_u8()?),
+            1 => Value::U16(self.read_u16()?),
+            2 => Value::U32(self.read_u32()?),
+            3 => Value::U64(u64::from_le_bytes(self.read_array()?)),
+            4 => Value::U128(u128::from_le_bytes(self.read_array()?)),
+            5 => Value::U256(U256::from_le_bytes(self.read_array()?)),
+            6 => Value::Boolean(self.read_bool()?),
+            7 => {
+                let bytes = self.read_bytes()?;
+                let s = core::str::from_utf8(bytes)
+                    .map_err(|_| SerializerError::InvalidString)?;
+                Value::String(s.to_owned())
+            },
+            VALUE_NULL => Value::Null,
+            id @ (VALUE_RANGE | VALUE_RANGE_INCLUSIVE) => {
+                let start = self.read_value(depth + 1)?;
+                let end = self.read_value(depth + 1)?;
+                let _type = self.read_type(depth + 1)?;
+                Value::Range(Box::new(start), Box::new(end), _type, id == VALUE_RANGE_INCLUSIVE)
+            },
+            VALUE_BLOB => Value::Blob(self.read_bytes()?.to_vec()),
+            v => return Err(SerializerError::InvalidValue(v))
+        })
+    }
+
+    fn read_constant(&mut self, depth: usize) -> Result<Constant, SerializerError> {
+        if depth > MAX_DEPTH {
+            return Err(SerializerError::MaxDepthReached)
+        }
+
+        Ok(match self.read_u8()? {
+            CONSTANT_DEFAULT => Constant::Default(self.read_value(depth + 1)?),
+            CONSTANT_STRUCT => {
+                let id = self.read_u16()?;
+                let struct_type = self.get_struct(id)?;
+                let len = self.read_u8()?;
+                let mut fields = Vec::with_capacity(len as usize);
+                for _ in 0..len {
+                    fields.push(self.read_constant(depth + 1)?);
+                }
+                Constant::Struct(fields, struct_type)
+            },
+            CONSTANT_ARRAY => {
+                let len = self.read_u32()?;
+                // Don't trust the len for the allocation
+                let mut values = Vec::new();
+                for _ in 0..len {
+                    values.push(self.read_constant(depth + 1)?);
+                }
+                Constant::Array(values)
+            },
+            CONSTANT_OPTIONAL => {
+                let value = if self.read_bool()? {
+                    Some(Box::new(self.read_constant(depth + 1)?))
+                } else {
+                    None
+                };
+                Constant::Optional(value)
+            },
+            CONSTANT_MAP => {
+                let len = self.read_u32()?;
+                let mut map = IndexMap::default();
+                for _ in 0..len {
+                    let key = self.read_constant(depth + 1)?;
+                    let value = self.read_constant(depth + 1)?;
+                    // Only one encoding is allowed for a map
+                    if map.insert(key, value).is_some() {
+                        return Err(SerializerError::DuplicatedMapKey);
+                    }
+                }
+                Constant::Map(map)
+            },
+            CONSTANT_ENUM => {
+                let id = self.read_u16()?;
+                let enum_type = self.get_enum(id)?;
+                let variant_id = self.read_u8()?;
+                let len = self.read_u8()?;
+                let mut fields = Vec::with_capacity(len as usize);
+                for _ in 0..len {
+                    fields.push(self.read_constant(depth + 1)?);
+                }
+                Constant::Enum(fields, EnumValueType::new(enum_type, variant_id))
+            },
+            v => return Err(SerializerError::InvalidConstant(v))
+        })
+    }
+
+    // The chunks must be in increasing order and the offsets of a chunk too
+    fn read_debug_info(&mut self, chunks_len: u16) -> Result<DebugInfo, SerializerError> {
+        let mut debug_info = DebugInfo::new();
+
+        let names_len = self.read_u16()?;
+        let mut previous = None;
+        for _ in 0..names_len {
+            let id = self.read_u16()?;
+            if id >= chunks_len {
+                return Err(SerializerError::DebugInfoChunkNotFound(id))
+            }
+
+            if previous.is_some_and(|previous| previous >= id) {
+                return Err(SerializerError::InvalidDebugInfo(id))
+            }
+            previous = Some(id);
+
+            debug_info.set_chunk_name(id as usize, self.read_string()?.to_owned());
+        }
+
+        let chunks_spans_len = self.read_u16()?;
+        let mut previous = None;
+        for _ in 0..chunks_spans_len {
+            let id = self.read_u16()?;
+            if id >= chunks_len {
+                return Err(SerializerError::DebugInfoChunkNotFound(id))
+            }
+
+            if previous.is_some_and(|previous| previous >= id) {
+                return Err(SerializerError::InvalidDebugInfo(id))
+            }
+            previous = Some(id);
+
+            let spans_len = self.read_u32()?;
+            let mut previous_offset = None;
+            for _ in 0..spans_len {
+                let offset = self.read_u32()?;
+                if previous_offset.is_some_and(|previous| previous >= offset) {
+                    return Err(SerializerError::InvalidDebugInfo(id))
+                }
+                previous_offset = Some(offset);
+
+                let span = SourceSpan {
+                    line: self.read_u32()? as usize,
+                    column_start: self.read_u32()? as usize,
+                    column_end: self.read_u32()? as usize,
+                };
+                debug_info.add_span(id as usize, offset as usize, span);
+            }
+        }
+
+        Ok(debug_info)
+    }
+}
+
+impl Module {
+    // Serialize the module in its canonical binary format
+    // Layout: magic, format version, semantic version, features, structs, enums, constants,
+    // chunks, exports, signatures, events, migrations, optional debug info
+    // A count or an index that doesn't fit in its encoding is rejected
+    pub fn to_bytes(&self) -> Result<Vec<u8>, SerializerError> {
+        let mut writer = Writer {
+            bytes: Vec::new()
+        };
+
+        writer.bytes.extend_from_slice(&MODULE_MAGIC);
+        writer.write_u8(MODULE_VERSION);
+
+        let version = self.version();
+        writer.write_u16(version.major);
+        writer.write_u16(version.minor);
+        writer.write_u16(version.patch);
+        writer.write_u64(self.features().bits());
+
+        writer.write_u16_checked(self.structs().len())?;
+        for s in self.structs() {
+            writer.write_u16(s.id());
+            writer.write_u8_checked(s.fields().len())?;
+            for field in s.fields() {
+                writer.write_type(field)?;
+            }
+        }
+
+        writer.write_u16_checked(self.enums().len())?;
+        for e in self.enums() {
+            writer.write_u16(e.id());
+            writer.write_u8_checked(e.variants().len())?;
+            for variant in e.variants() {
+                writer.write_u8_checked(variant.fields().len())?;
+                for field in variant.fields() {
+                    writer.write_type(field)?;
+                }
+            }
+        }
+
+        writer.write_u16_checked(self.constants().len())?;
+        for constant in self.constants() {
+            writer.write_constant(&constant.0)?;
+        }
+
+        writer.write_u16_checked(self.chunks().len())?;
+        for (i, chunk) in self.chunks().iter().enumerate() {
+            writer.write_bool(self.is_entry_chunk(i));
+            writer.write_bytes(chunk.get_instructions())?;
+        }
+
+        writer.write_u16_checked(self.exports().len())?;
+        for (name, id) in self.exports() {
+            writer.write_bytes(name.as_bytes())?;
+            writer.write_u16_checked(*id)?;
+        }
+
+        // Ordered by chunk id to stay canonical
+        let signatures: Vec<_> = (0..self.chunks().len())
+            .filter(|id| self.is_exported_chunk(*id))
+            .filter_map(|id| self.get_signature(id).map(|signature| (id, signature)))
+            .collect();
+
+        writer.write_u16_checked(signatures.len())?;
+        for (id, signature) in signatures {
+            writer.write_u16_checked(id)?;
+            writer.write_u8_checked(signature.parameters().len())?;
+            for (name, _type) in signature.parameters() {
+                writer.write_bytes(name.as_bytes())?;
+                writer.write_type(_type)?;
+            }
+
+            writer.write_bool(signature.return_type().is_some());
+            if let Some(return_type) = signature.return_type() {
+                writer.write_type(return_type)?;
+            }
+        }
+
+        writer.write_u16_checked(self.events().len())?;
+        for (name, data) in self.events() {
+            writer.write_bytes(name.as_bytes())?;
+            writer.write_type(data)?;
+        }
+
+        writer.write_u16_checked(self.migrations().len())?;
+        for (struct_index, chunk_id) in self.migrations() {
+            writer.write_u16_checked(*struct_index)?;
+            writer.write_u16_checked(*chunk_id)?;
+        }
+
+        writer.write_bool(self.debug_info().is_some());
+        if let Some(debug_info) = self.debug_info() {
+            writer.write_debug_info(debug_info)?;
+        }
+
+        Ok(writer.bytes)
+    }
+
+    // Size of the constants in the binary format
+    pub(crate) fn constants_size(&self) -> Result<usize, SerializerError> {
+        let mut writer = Writer {
+            bytes: Vec::new()
+        };
+
+        for constant in self.constants() {
+            writer.write_constant(&constant.0)?;
+        }
+
+        Ok(writer.bytes.len())
+    }
+
+    // Deserialize a module from its canonical binary format
+    // Chunks instructions are not verified, use the validator for it
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, SerializerError> {
+        let mut reader = Reader {
+            bytes,
+            index: 0,
+            structs: IndexSet::default(),
+            enums: IndexSet::default(),
+        };
+
+        if reader.read_array::<4>()? != MODULE_MAGIC {
+            return Err(SerializerError::InvalidMagic)
+        }
+
+        let version = reader.read_u8()?;
+        if version != MODULE_VERSION {
+            return Err(SerializerError::UnsupportedVersion(version))
+        }
+
+        let major = reader.read_u16()?;
+        let minor = reader.read_u16()?;
+        let patch = reader.read_u16()?;
+        let features = ModuleFeatures::from_bits(reader.read_u64()?);
+
+        reader.read_definitions()?;
+
+        let mut module = Module::new();
+        for s in reader.structs.iter() {
+            module.add_struct(s.clone());
+        }
+
+        for e in reader.enums.iter() {
+            module.add_enum(e.clone());
+        }
+
+        let constants_len = reader.read_u16()?;
+        for i in 0..constants_len {
+            let constant = reader.read_constant(0)?;
+            if module.add_constant(constant) != i as usize {
+                return Err(SerializerError::DuplicatedConstant)
+            }
+        }
+
+        let chunks_len = reader.read_u16()?;
+        for _ in 0..chunks_len {
+            let entry = reader.read_bool()?;
+            let instructions = reader.read_bytes()?;
+
+            let mut chunk = Chunk::new();
+            chunk.write_bytes(instructions);
+            if entry {
+                module.add_entry_chunk(chunk);
+            } else {
+                module.add_chunk(chunk);
+            }
+        }
+
+        let exports_len = reader.read_u16()?;
+        for _ in 0..exports_len {
+            let name = reader.read_string()?;
+            let id = reader.read_u16()?;
+            if id >= chunks_len {
+                return Err(SerializerError::ExportChunkNotFound(id))
+            }
+
+            if !module.add_export(name, id as usize) {
+                return Err(SerializerError::DuplicatedExport)
+            }
+        }
+
+        let signatures_len = reader.read_u16()?;
+        for _ in 0..signatures_len {
+            let id = reader.read_u16()?;
+            if !module.is_exported_chunk(id as usize) {
+                return Err(SerializerError::NotAnExportedChunk(id))
+            }
+
+            if module.get_signature(id as usize).is_some() {
+                return Err(SerializerError::DuplicatedSignature(id))
+            }
+
+            let len = reader.read_u8()?;
+            let mut parameters = Vec::with_capacity(len as usize);
+            for _ in 0..len {
+                let name = reader.read_string()?;
+                parameters.push((name.to_owned(), reader.read_type(0)?));
+            }
+
+            let return_type = if reader.read_bool()? {
+                Some(reader.read_type(0)?)
+            } else {
+                None
+            };
+            module.set_signature(id as usize, ChunkSignature::new(parameters, return_type));
+        }
+
+        let events_len = reader.read_u16()?;
+        for _ in 0..events_len {
+            let name = reader.read_string()?;
+            let data = reader.read_type(0)?;
+            if !module.add_event(name, data) {
+                return Err(SerializerError::DuplicatedEvent)
+            }
+        }
+
+        let migrations_len = reader.read_u16()?;
+        for _ in 0..migrations_len {
+            let struct_index = reader.read_u16()?;
+            if struct_index as usize >= module.structs().len() {
+                return Err(SerializerError::MigrationStructNotFound(struct_index))
+            }
+
+            let chunk_id = reader.read_u16()?;
+            if chunk_id >= chunks_len {
+                return Err(SerializerError::MigrationChunkNotFound(chunk_id))
+            }
+
+            if !module.add_migration(struct_index as usize, chunk_id as usize) {
+                return Err(SerializerError::DuplicatedMigration(struct_index))
+            }
+        }
+
+        if reader.read_bool()? {
+            module.set_debug_info(Some(reader.read_debug_info(chunks_len)?));
+        }
+
+        if reader.index != bytes.len() {
+            return Err(SerializerError::TrailingBytes)
+        }
+
+        module.set_version(ModuleVersion::new(major, minor, patch));
+        module.set_features(features);
+
+        Ok(module)
+    }
+}
+
+// With serde, the module is exchanged using its canonical binary format
+// so the same checks are applied when decoding it
+#[cfg(feature = "serde")]
+impl serde::Serialize for Module {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let bytes = self.to_bytes()
+            .map_err(serde::ser::Error::custom)?;
+        serializer.serialize_bytes(&bytes)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for Module {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct ModuleVisitor;
+
+        impl<'de> serde::de::Visitor<'de> for ModuleVisitor {
+            type Value = Module;
+
+            fn expecting(&self, formatter: &mut core::fmt::Formatter) -> core::fmt::Result {
+                formatter.write_str("a serialized module")
+            }
+
+            fn visit_bytes<E: serde::de::Error>(self, v: &[u8]) -> Result<Self::Value, E> {
+                Module::from_bytes(v).map_err(E::custom)
+            }
+
+            // Formats without a bytes type (like JSON) are using a sequence
+            fn visit_seq<A: serde::de::SeqAccess<'de>>(self, mut seq: A) -> Result<Self::Value, A::Error> {
+                let mut bytes = Vec::new();
+                while let Some(byte) = seq.next_element::<u8>()? {
+                    bytes.push(byte);
+                }
+
+                Module::from_bytes(&bytes).map_err(serde::de::Error::custom)
+            }
+        }
+
+        deserializer.deserialize_bytes(ModuleVisitor)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::OpCode;
+    use super::*;
+
+    // Size of the sections of the module built below
+    // count, chunk id, parameters count, each parameter name and type, then the return type
+    const SIGNATURES_LEN: usize = 2 + 2 + 1 + (4 + "point".len() + 3) + (4 + "amount".len() + 1) + (1 + 1);
+    // count, then the event name and type
+    const EVENTS_LEN: usize = 2 + (4 + "moved".len() + 3);
+    // count, then the struct index and the chunk id
+    const MIGRATIONS_LEN: usize = 2 + 2 + 2;
+    // no debug info
+    const DEBUG_INFO_LEN: usize = 1;
+    // semantic version, then the features
+    const HEADER_LEN: usize = 3 * 2 + 8;
+
+    fn build_module() -> Module {
+        let mut module = Module::new();
+        let struct_type = StructType::new(0, vec![Type::U64, Type::Array(Box::new(Type::String))]);
+        let enum_type = EnumType::new(1, vec![
+            EnumVariant::new(Vec::new()),
+            EnumVariant::new(vec![Type::Struct(struct_type.clone()), Type::Optional(Box::new(Type::U8))]),
+            EnumVariant::new(vec![Type::Tuple(vec![Type::U64, Type::String]), Type::Result(Box::new(Type::U8), Box::new(Type::String))])
+        ]);
+        module.add_struct(struct_type.clone());
+        module.add_enum(enum_type.clone());
+
+        module.add_constant(Value::U8(1));
+        module.add_constant(Value::U256(U256::from(u128::MAX)));
+        module.add_constant(Value::String("hello".to_owned()));
+        module.add_constant(Value::Range(Box::new(Value::U32(0)), Box::new(Value::U32(10)), Type::U32, false));
+        module.add_constant(Value::Range(Box::new(Value::U8(0)), Box::new(Value::U8(255)), Type::U8, true));
+        module.add_constant(Value::Blob(vec![1, 2, 3]));
+        module.add_constant(Value::Null);
+        module.add_constant(Constant::Struct(vec![
+            Constant::Default(Value::U64(5)),
+            Constant::Array(vec![Constant::Default(Value::String("a".to_owned()))])
+        ], struct_type.clone()));
+        module.add_constant(Constant::Enum(vec![], EnumValueType::new(enum_type.clone(), 0)));
+        module.add_constant(Constant::Optional(Some(Box::new(Constant::Default(Value::Boolean(true))))));
+        module.add_constant(Constant::Map((0..16u8).rev().map(|i| (Constant::Default(Value::U8(i)), Constant::Default(Value::U8(i)))).collect()));
+
+        let mut chunk = Chunk::new();
+        chunk.emit_opcode(OpCode::Constant);
+        chunk.write_u16(0);
+        chunk.emit_opcode(OpCode::CheckedAdd);
+        chunk.emit_opcode(OpCode::Return);
+        module.add_chunk(chunk.clone());
+        module.add_entry_chunk(chunk);
+        module.add_export("main", 1);
+        module.set_signature(1, ChunkSignature::new(
+            vec![("point".to_owned(), Type::Struct(struct_type)), ("amount".to_owned(), Type::U64)],
+            Some(Type::U64)
+        ));
+        module.add_event("moved", Type::Enum(enum_type));
+        module.add_migration(0, 1);
+        module.set_version(ModuleVersion::new(1, 2, 3));
+        module.set_features(module.used_features());
+
+        module
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let module = build_module();
+        let bytes = module.to_bytes().unwrap();
+        let decoded = Module::from_bytes(&bytes).unwrap();
+
+        // Compare in order: the insertion order of a map must be kept
+        assert!(decoded.constants().iter().eq(module.constants().iter()));
+        let map = decoded.get_constant_at(decoded.constants().len() - 1).unwrap().as_map().unwrap();
+        assert!(map.keys().eq((0..16u8).rev().map(|i| Constant::Default(Value::U8(i))).collect::<Vec<_>>().iter()));
+        assert_eq!(decoded.structs(), module.structs());
+        assert_eq!(decoded.enums(), module.enums());
+        assert_eq!(decoded.chunks_entry_ids(), module.chunks_entry_ids());
+        assert_eq!(decoded.exports(), module.exports());
+        assert_eq!(decoded.get_signature(1), module.get_signature(1));
+        assert_eq!(decoded.events(), module.events());
+        assert_eq!(decoded.migrations(), module.migrations());
+        assert_eq!(decoded.version(), ModuleVersion::new(1, 2, 3));
+        assert_eq!(decoded.features(), ModuleFeatures::CHECKED_ARITHMETIC);
+        for (a, b) in decoded.chunks().iter().zip(module.chunks()) {
+            assert_eq!(a.get_instructions(), b.get_instructions());
+        }
+
+        // Must be canonical
+        assert_eq!(decoded.to_bytes().unwrap(), bytes);
+    }
+
+    #[test]
+    fn test_counts_limit() {
+        // u16::MAX constants fit in the count
+        let mut module = Module::new();
+        for i in 0..u16::MAX as u64 {
+            module.add_constant(Value::U64(i));
+        }
+        let bytes = module.to_bytes().unwrap();
+        let decoded = Module::from_bytes(&bytes).unwrap();
+        assert_eq!(decoded.constants().len(), u16::MAX as usize);
+        assert_eq!(decoded.to_bytes().unwrap(), bytes);
+
+        // One more would be written as 0
+        module.add_constant(Value::U64(u16::MAX as u64));
+        assert_eq!(module.to_bytes().unwrap_err(), SerializerError::LengthOverflow(u16::MAX as usize + 1));
+
+        // u8::MAX struct fields fit in the count
+        let mut module = Module::new();
+        module.add_struct(StructType::new(0, vec![Type::U8; u8::MAX as usize]));
+        let decoded = Module::from_bytes(&module.to_bytes().unwrap()).unwrap();
+        assert_eq!(decoded.structs(), module.structs());
+
+        let mut module = Module::new();
+        module.add_struct(StructType::new(0, vec![Type::U8; u8::MAX as usize + 1]));
+        assert_eq!(module.to_bytes().unwrap_err(), SerializerError::LengthOverflow(u8::MAX as usize + 1));
+
+        // u8::MAX signature parameters fit in the count
+        let parameters = |len: usize| (0..len).map(|i| (format!("p{}", i), Type::U8)).collect::<Vec<_>>();
+        let mut module = build_module();
+        module.set_signature(1, ChunkSignature::new(parameters(u8::MAX as usize), None));
+        let decoded = Module::from_bytes(&module.to_bytes().unwrap()).unwrap();
+        assert_eq!(decoded.get_signature(1), module.get_signature(1));
+
+        module.set_signature(1, ChunkSignature::new(parameters(u8::MAX as usize + 1), None));
+        assert_eq!(module.to_bytes().unwrap_err(), SerializerError::LengthOverflow(u8::MAX as usize + 1));
+    }
+
+    #[test]
+    fn test_struct_with_enum_field() {
+        // The enum is written after the struct using it
+        // and has a field of another struct
+        let inner = StructType::new(0, vec![Type::U8]);
+        let enum_type = EnumType::new(0, vec![
+            EnumVariant::new(vec![]),
+            EnumVariant::new(vec![Type::Struct(inner.clone())])
+        ]);
+        let outer = StructType::new(1, vec![Type::Enum(enum_type.clone()), Type::Optional(Box::new(Type::Enum(enum_type.clone())))]);
+
+        let mut module = Module::new();
+        module.add_struct(outer.clone());
+        module.add_struct(inner);
+        module.add_enum(enum_type.clone());
+        module.add_constant(Constant::Struct(vec![
+            Constant::Enum(vec![], EnumValueType::new(enum_type, 0)),
+            Constant::Default(Value::Null)
+        ], outer));
+
+        let bytes = module.to_bytes().unwrap();
+        let decoded = Module::from_bytes(&bytes).unwrap();
+        assert_eq!(decoded.structs(), module.structs());
+        assert_eq!(decoded.enums(), module.enums());
+        assert!(decoded.constants().iter().eq(module.constants().iter()));
+        assert_eq!(decoded.to_bytes().unwrap(), bytes);
+
+        // A type referencing an unknown struct is still rejected
+        let mut module = Module::new();
+        module.add_struct(StructType::new(0, vec![Type::Struct(StructType::new(5, vec![]))]));
+        assert_eq!(Module::from_bytes(&module.to_bytes().unwrap()).unwrap_err(), SerializerError::StructNotFound(5));
+    }
+
+    #[test]
+    fn test_invalid_header() {
+        let mut bytes = build_module().to_bytes().unwrap();
+        bytes[0] = 0;
+        assert_eq!(Module::from_bytes(&bytes).unwrap_err(), SerializerError::InvalidMagic);
+
+        let mut bytes = build_module().to_bytes().unwrap();
+        bytes[4] = MODULE_VERSION + 1;
+        assert_eq!(Module::from_bytes(&bytes).unwrap_err(), SerializerError::UnsupportedVersion(MODULE_VERSION + 1));
+
+        let mut bytes = build_module().to_bytes().unwrap();
+        bytes[4] = 0;
+        assert_eq!(Module::from_bytes(&bytes).unwrap_err(), SerializerError::UnsupportedVersion(0));
+    }
+
+    #[test]
+    fn test_trailing_bytes() {
+        let mut bytes = build_module().to_bytes().unwrap();
+        bytes.push(0);
+        assert_eq!(Module::from_bytes(&bytes).unwrap_err(), SerializerError::TrailingBytes);
+    }
+
+    #[test]
+    fn test_invalid_export() {
+        let mut module = build_module();
+        module.add_export("missing", 2);
+        assert_eq!(Module::from_bytes(&module.to_bytes().unwrap()).unwrap_err(), SerializerError::ExportChunkNotFound(2));
+    }
+
+    #[test]
+    fn test_signatures() {
+        let mut module = build_module();
+        module.set_signature(0, ChunkSignature::default());
+        // Only the signatures of the exported chunks are kept
+        assert_eq!(Module::from_bytes(&module.to_bytes().unwrap()).unwrap().get_signature(0), None);
+
+        // A public function is exported without being an entry
+        module.add_export("helper", 0);
+        let decoded = Module::from_bytes(&module.to_bytes().unwrap()).unwrap();
+        assert_eq!(decoded.get_signature(0), Some(&ChunkSignature::default()));
+        assert_eq!(decoded.entry_by_name("helper"), None);
+        assert_eq!(decoded.entry_by_name("main"), Some(1));
+
+        // Point the signature to the chunk 0
+        let mut bytes = build_module().to_bytes().unwrap();
+        let index = bytes.len() - DEBUG_INFO_LEN - MIGRATIONS_LEN - EVENTS_LEN - SIGNATURES_LEN + 2;
+        assert_eq!(bytes[index..index + 2], [1, 0]);
+        bytes[index] = 0;
+        assert_eq!(Module::from_bytes(&bytes).unwrap_err(), SerializerError::NotAnExportedChunk(0));
+    }
+
+    #[test]
+    fn test_invalid_migration() {
+        let mut module = build_module();
+        module.add_migration(1, 0);
+        assert_eq!(Module::from_bytes(&module.to_bytes().unwrap()).unwrap_err(), SerializerError::MigrationStructNotFound(1));
+
+        let mut bytes = build_module().to_bytes().unwrap();
+        let len = bytes.len() - DEBUG_INFO_LEN;
+        bytes[len - 2] = 2;
+        assert_eq!(Module::from_bytes(&bytes).unwrap_err(), SerializerError::MigrationChunkNotFound(2));
+    }
+
+    fn span(line: usize) -> SourceSpan {
+        SourceSpan {
+            line,
+            column_start: 4,
+            column_end: 12
+        }
+    }
+
+    #[test]
+    fn test_debug_info() {
+        let mut module = build_module();
+        let mut debug_info = DebugInfo::new();
+        debug_info.set_chunk_name(1, "main".to_owned());
+        debug_info.set_chunk_name(0, "helper".to_owned());
+        debug_info.add_span(1, 0, span(3));
+        debug_info.add_span(1, 3, span(4));
+        debug_info.add_span(0, 0, span(10));
+        module.set_debug_info(Some(debug_info.clone()));
+
+        let bytes = module.to_bytes().unwrap();
+        let decoded = Module::from_bytes(&bytes).unwrap();
+        assert_eq!(decoded.debug_info(), Some(&debug_info));
+        assert_eq!(decoded.debug_info().unwrap().get_span(1, 4), Some(&span(4)));
+        assert_eq!(decoded.to_bytes().unwrap(), bytes);
+
+        // Only the section after the migrations differs without it
+        module.set_debug_info(None);
+        let stripped = module.to_bytes().unwrap();
+        assert_eq!(Module::from_bytes(&stripped).unwrap().debug_info(), None);
+        let len = stripped.len() - DEBUG_INFO_LEN;
+        assert_eq!(bytes[..len], stripped[..len]);
+    }
+
+    #[test]
+    fn test_invalid_debug_info() {
+        let mut module = build_module();
+        let mut debug_info = DebugInfo::new();
+        debug_info.set_chunk_name(2, "missing".to_owned());
+        module.set_debug_info(Some(debug_info));
+        assert_eq!(Module::from_bytes(&module.to_bytes().unwrap()).unwrap_err(), SerializerError::DebugInfoChunkNotFound(2));
+
+        // The offsets of a chunk must be increasing
+        let mut debug_info = DebugInfo::new();
+        debug_info.add_span(1, 0, span(1));
+        debug_info.add_span(1, 3, span(2));
+        module.set_debug_info(Some(debug_info));
+        let mut bytes = module.to_bytes().unwrap();
+        // flag, no names, one chunk, chunk id, spans count, then the spans
+        let second = bytes.len() - 16;
+        bytes[second..second + 4].copy_from_slice(&0u32.to_le_bytes());
+        assert_eq!(Module::from_bytes(&bytes).unwrap_err(), SerializerError::InvalidDebugInfo(1));
+    }
+
+    #[test]
+    fn test_truncated() {
+        let bytes = build_module().to_bytes().unwrap();
+        for i in 0..bytes.len() {
+            assert!(Module::from_bytes(&bytes[..i]).is_err());
+        }
+    }
+
+    #[test]
+    fn test_max_depth() {
+        let mut bytes = MODULE_MAGIC.to_vec();
+        bytes.push(MODULE_VERSION);
+        bytes.extend_from_slice(&[0; HEADER_LEN]);
+        // no structs, no enums
+        bytes.extend_from_slice(&[0, 0, 0, 0]);
+        // one constant
+        bytes.extend_from_slice(&[1, 0]);
+        for _ in 0..MAX_DEPTH * 2 {
+            bytes.push(CONSTANT_OPTIONAL);
+            bytes.push(1);
+        }
+
+        assert_eq!(Module::from_bytes(&bytes).unwrap_err(), SerializerError::MaxDepthReached);
+    }
+
+    #[test]
+    fn test_malformed_fuzz() {
+        let bytes = build_module().to_bytes().unwrap();
+        // Simple deterministic generator to mutate the bytes
+        let mut seed: u64 = 0x5EED;
+        for _ in 0..2000 {
+            seed = seed.wrapping_mul(6364136223846793005).wrapping_add(1442695040888963407);
+            let mut mutated = bytes.clone();
+            let index = (seed >> 33) as usize % mutated.len();
+            mutated[index] = (seed >> 17) as u8;
+
+            // It must never panic
+            let _ = Module::from_bytes(&mutated);
+        }
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_serde_json() {
+        let module = build_module();
+        let json = serde_json::to_string(&module).unwrap();
+        let decoded: Module = serde_json::from_str(&json).unwrap();
+        assert_eq!(decoded.to_bytes().unwrap(), module.to_bytes().unwrap());
+
+        // Invalid modules are rejected
+        assert!(serde_json::from_str::<Module>("[0, 1, 2]").is_err());
+    }
+}
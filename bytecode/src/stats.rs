@@ -0,0 +1,250 @@
+use core::fmt;
+use alloc::vec::Vec;
+
+use super::{Module, OpCode, SerializerError};
+
+// Size and complexity of a chunk
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ChunkStats {
+    // Count of instructions
+    pub instructions: usize,
+    // Size of the instructions in bytes
+    pub size: usize,
+    // Count of distinct chunks called
+    pub callees: usize,
+    // Longest chain of calls started by the chunk, itself included
+    // None if it can call itself, directly or not
+    pub max_call_depth: Option<usize>,
+}
+
+// Size and complexity of a module
+// Deploy tooling can compare them with the limits of the validator before submitting it
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ModuleStats {
+    // Stats of each chunk by id
+    pub chunks: Vec<ChunkStats>,
+    pub constants: usize,
+    // Size of the constants in the binary format
+    pub constants_size: usize,
+    pub structs: usize,
+    pub enums: usize,
+    // Size of the serialized module
+    pub size: usize,
+}
+
+impl ModuleStats {
+    // Count of instructions of all the chunks
+    pub fn instructions(&self) -> usize {
+        self.chunks.iter().map(|chunk| chunk.instructions).sum()
+    }
+
+    // Size of the biggest chunk in bytes
+    pub fn max_chunk_size(&self) -> usize {
+        self.chunks.iter().map(|chunk| chunk.size).max().unwrap_or(0)
+    }
+
+    // Longest chain of calls of the module
+    // None if a chunk can call itself
+    pub fn max_call_depth(&self) -> Option<usize> {
+        self.chunks.iter()
+            .try_fold(0, |max, chunk| chunk.max_call_depth.map(|depth| max.max(depth)))
+    }
+
+    // Ids of the chunks whose chain of calls is unbounded by a recursion
+    pub fn unbounded_chunks(&self) -> impl Iterator<Item = usize> + '_ {
+        self.chunks.iter()
+            .enumerate()
+            .filter(|(_, chunk)| chunk.max_call_depth.is_none())
+            .map(|(id, _)| id)
+    }
+}
+
+impl fmt::Display for ModuleStats {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "size: {} bytes", self.size)?;
+        writeln!(f, "constants: {} ({} bytes)", self.constants, self.constants_size)?;
+        writeln!(f, "structs: {}", self.structs)?;
+        writeln!(f, "enums: {}", self.enums)?;
+        writeln!(f, "instructions: {}", self.instructions())?;
+        match self.max_call_depth() {
+            Some(depth) => writeln!(f, "max call depth: {}", depth)?,
+            None => writeln!(f, "max call depth: unbounded (recursive)")?
+        };
+
+        write!(f, "chunks: {}", self.chunks.len())?;
+        for (id, chunk) in self.chunks.iter().enumerate() {
+            write!(f, "\n    chunk {}: {} instructions, {} bytes, {} callees, call depth ", id, chunk.instructions, chunk.size, chunk.callees)?;
+            match chunk.max_call_depth {
+                Some(depth) => write!(f, "{}", depth)?,
+                None => write!(f, "unbounded")?
+            }
+        }
+
+        Ok(())
+    }
+}
+
+// Longest chain of calls started by each chunk
+// Depth-first, iterative so a long chain of calls can't overflow the stack
+fn call_depths(graph: &[Vec<usize>]) -> Vec<Option<usize>> {
+    // Depth of each chunk once all its callees are known
+    let mut depths: Vec<Option<Option<usize>>> = alloc::vec![None; graph.len()];
+    let mut visiting = alloc::vec![false; graph.len()];
+
+    for root in 0..graph.len() {
+        if depths[root].is_some() {
+            continue
+        }
+
+        // Node with the position of the next callee to visit
+        let mut calls = alloc::vec![(root, 0)];
+        visiting[root] = true;
+        while let Some((node, edge)) = calls.last_mut() {
+            let node = *node;
+            if let Some(callee) = graph[node].get(*edge).copied() {
+                *edge += 1;
+                if depths[callee].is_none() && !visiting[callee] {
+                    visiting[callee] = true;
+                    calls.push((callee, 0));
+                }
+                continue
+            }
+
+            // A callee still visiting is calling this chunk back
+            let depth = graph[node].iter()
+                .try_fold(0, |max, callee| depths[*callee].flatten().map(|depth| max.max(depth)))
+                .map(|depth| depth + 1);
+
+            depths[node] = Some(depth);
+            visiting[node] = false;
+            calls.pop();
+        }
+    }
+
+    depths.into_iter().map(Option::flatten).collect()
+}
+
+impl Module {
+    // Compute the size and complexity of the module
+    // The decoding of a chunk stops at an invalid opcode, which is rejected by the validator
+    // Fails if the module can't be serialized
+    pub fn stats(&self) -> Result<ModuleStats, SerializerError> {
+        let mut chunks = Vec::with_capacity(self.chunks().len());
+        let mut graph = Vec::with_capacity(self.chunks().len());
+        for chunk in self.chunks() {
+            let instructions = chunk.get_instructions();
+            let mut callees = Vec::new();
+            let mut count = 0;
+            let mut index = 0;
+            while let Some(opcode) = instructions.get(index).and_then(|byte| OpCode::from_byte(*byte)) {
+                // A chunk given to a native is invoked by it
+                if matches!(opcode, OpCode::InvokeChunk | OpCode::ChunkId) {
+                    if let Some(bytes) = instructions.get(index + 1..index + 3) {
+                        let id = u16::from_le_bytes([bytes[0], bytes[1]]) as usize;
+                        // An unknown chunk is rejected by the validator
+                        if id < self.chunks().len() {
+                            callees.push(id);
+                        }
+                    }
+                }
+
+                count += 1;
+                index += 1 + opcode.arguments_bytes();
+            }
+
+            callees.sort_unstable();
+            callees.dedup();
+
+            chunks.push(ChunkStats {
+                instructions: count,
+                size: instructions.len(),
+                callees: callees.len(),
+                max_call_depth: None,
+            });
+            graph.push(callees);
+        }
+
+        for (chunk, depth) in chunks.iter_mut().zip(call_depths(&graph)) {
+            chunk.max_call_depth = depth;
+        }
+
+        Ok(ModuleStats {
+            chunks,
+            constants: self.constants().len(),
+            constants_size: self.constants_size()?,
+            structs: self.structs().len(),
+            enums: self.enums().len(),
+            size: self.to_bytes()?.len(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use xelis_types::Value;
+    use crate::Chunk;
+    use super::*;
+
+    fn call(chunk: &mut Chunk, id: u16) {
+        chunk.emit_opcode(OpCode::InvokeChunk);
+        chunk.write_u16(id);
+        chunk.write_bool(false);
+        chunk.write_u8(0);
+    }
+
+    #[test]
+    fn test_stats() {
+        let mut module = Module::new();
+        module.add_constant(Value::U64(10));
+
+        // 0 calls 1 which calls 2
+        let mut chunk = Chunk::new();
+        call(&mut chunk, 1);
+        call(&mut chunk, 1);
+        chunk.emit_opcode(OpCode::Return);
+        module.add_entry_chunk(chunk);
+
+        let mut chunk = Chunk::new();
+        call(&mut chunk, 2);
+        chunk.emit_opcode(OpCode::Return);
+        module.add_chunk(chunk);
+
+        let mut chunk = Chunk::new();
+        chunk.emit_opcode(OpCode::Constant);
+        chunk.write_u16(0);
+        chunk.emit_opcode(OpCode::Return);
+        module.add_chunk(chunk);
+
+        let stats = module.stats().unwrap();
+        assert_eq!(stats.chunks[0], ChunkStats { instructions: 3, size: 11, callees: 1, max_call_depth: Some(3) });
+        assert_eq!(stats.chunks[2], ChunkStats { instructions: 2, size: 4, callees: 0, max_call_depth: Some(1) });
+        assert_eq!(stats.instructions(), 7);
+        assert_eq!(stats.max_chunk_size(), 11);
+        assert_eq!(stats.max_call_depth(), Some(3));
+        assert_eq!(stats.constants, 1);
+        // tag of the constant and of the value, then the u64
+        assert_eq!(stats.constants_size, 10);
+        assert_eq!(stats.size, module.to_bytes().unwrap().len());
+    }
+
+    #[test]
+    fn test_recursive() {
+        let mut module = Module::new();
+
+        // 0 calls 1, 1 and 2 call each other, 3 calls itself
+        let calls: [&[u16]; 4] = [&[1], &[2], &[1], &[3]];
+        for callees in calls {
+            let mut chunk = Chunk::new();
+            for id in callees {
+                call(&mut chunk, *id);
+            }
+            chunk.emit_opcode(OpCode::Return);
+            module.add_chunk(chunk);
+        }
+
+        let stats = module.stats().unwrap();
+        assert!(stats.chunks.iter().all(|chunk| chunk.max_call_depth.is_none()));
+        assert_eq!(stats.max_call_depth(), None);
+        assert_eq!(stats.unbounded_chunks().count(), 4);
+    }
+}
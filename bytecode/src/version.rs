@@ -0,0 +1,171 @@
+use core::fmt;
+
+use super::OpCode;
+
+// Semantic version of the bytecode targeted by a module
+// A VM runs the modules of its major version up to its own version
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct ModuleVersion {
+    pub major: u16,
+    pub minor: u16,
+    pub patch: u16,
+}
+
+impl ModuleVersion {
+    // Version of the bytecode produced by this compiler
+    pub const CURRENT: Self = Self::new(1, 0, 0);
+
+    pub const fn new(major: u16, minor: u16, patch: u16) -> Self {
+        Self {
+            major,
+            minor,
+            patch
+        }
+    }
+
+    // Check if a module of this version can run on a VM supporting the given version
+    pub fn is_supported_by(&self, supported: &ModuleVersion) -> bool {
+        self.major == supported.major && self <= supported
+    }
+}
+
+impl Default for ModuleVersion {
+    fn default() -> Self {
+        Self::CURRENT
+    }
+}
+
+impl fmt::Display for ModuleVersion {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}.{}.{}", self.major, self.minor, self.patch)
+    }
+}
+
+// Set of the optional features used by a module
+// A new family of opcodes comes with its own feature, so a VM which doesn't know it
+// rejects the module at validation instead of mis-executing it
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub struct ModuleFeatures(u64);
+
+impl ModuleFeatures {
+    pub const NONE: Self = Self(0);
+    // Calls to the exports of the linked modules
+    pub const EXTERNAL_CALL: Self = Self(1 << 0);
+    // Checked, saturating and wrapping arithmetic
+    pub const CHECKED_ARITHMETIC: Self = Self(1 << 1);
+    // Instructions fused by the optimizer
+    pub const SUPERINSTRUCTIONS: Self = Self(1 << 2);
+    // Ranges including their end
+    pub const INCLUSIVE_RANGE: Self = Self(1 << 3);
+    // Dispatch of a switch through a table of jumps
+    pub const JUMP_TABLE: Self = Self(1 << 4);
+    // Calls to the precompiles of the host
+    pub const PRECOMPILE: Self = Self(1 << 5);
+    // Chunks given as functions to the natives
+    pub const CHUNK_REFERENCE: Self = Self(1 << 6);
+    // All the features known by this version
+    pub const ALL: Self = Self(Self::EXTERNAL_CALL.0 | Self::CHECKED_ARITHMETIC.0 | Self::SUPERINSTRUCTIONS.0 | Self::INCLUSIVE_RANGE.0 | Self::JUMP_TABLE.0 | Self::PRECOMPILE.0 | Self::CHUNK_REFERENCE.0);
+
+    #[inline]
+    pub const fn from_bits(bits: u64) -> Self {
+        Self(bits)
+    }
+
+    #[inline]
+    pub const fn bits(&self) -> u64 {
+        self.0
+    }
+
+    #[inline]
+    pub const fn is_empty(&self) -> bool {
+        self.0 == 0
+    }
+
+    // Check if all the features of other are in the set
+    #[inline]
+    pub const fn contains(&self, other: Self) -> bool {
+        self.0 & other.0 == other.0
+    }
+
+    #[inline]
+    pub fn insert(&mut self, other: Self) {
+        self.0 |= other.0;
+    }
+
+    #[inline]
+    pub const fn union(&self, other: Self) -> Self {
+        Self(self.0 | other.0)
+    }
+
+    // Features of the set missing from other
+    #[inline]
+    pub const fn difference(&self, other: Self) -> Self {
+        Self(self.0 & !other.0)
+    }
+}
+
+impl fmt::Display for ModuleFeatures {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:#x}", self.0)
+    }
+}
+
+impl OpCode {
+    // Feature required to execute the opcode
+    // The opcodes of the first version require none
+    pub fn required_feature(&self) -> ModuleFeatures {
+        match self {
+            OpCode::ExternalCall => ModuleFeatures::EXTERNAL_CALL,
+            OpCode::CheckedAdd
+            | OpCode::CheckedSub
+            | OpCode::CheckedMul
+            | OpCode::SaturatingAdd
+            | OpCode::SaturatingSub
+            | OpCode::SaturatingMul
+            | OpCode::WrappingAdd
+            | OpCode::WrappingSub
+            | OpCode::WrappingMul => ModuleFeatures::CHECKED_ARITHMETIC,
+            OpCode::MemoryAdd
+            | OpCode::MemoryInc
+            | OpCode::LtJumpIfFalse
+            | OpCode::LteJumpIfFalse
+            | OpCode::GtJumpIfFalse
+            | OpCode::GteJumpIfFalse
+            | OpCode::MemoryCast => ModuleFeatures::SUPERINSTRUCTIONS,
+            OpCode::NewRangeInclusive => ModuleFeatures::INCLUSIVE_RANGE,
+            OpCode::JumpTable => ModuleFeatures::JUMP_TABLE,
+            OpCode::CallPrecompile => ModuleFeatures::PRECOMPILE,
+            OpCode::ChunkId => ModuleFeatures::CHUNK_REFERENCE,
+            _ => ModuleFeatures::NONE
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_version_supported() {
+        let supported = ModuleVersion::new(1, 2, 0);
+        assert!(ModuleVersion::new(1, 0, 0).is_supported_by(&supported));
+        assert!(ModuleVersion::new(1, 2, 0).is_supported_by(&supported));
+        assert!(!ModuleVersion::new(1, 2, 1).is_supported_by(&supported));
+        assert!(!ModuleVersion::new(1, 3, 0).is_supported_by(&supported));
+        assert!(!ModuleVersion::new(0, 9, 0).is_supported_by(&supported));
+        assert!(!ModuleVersion::new(2, 0, 0).is_supported_by(&supported));
+    }
+
+    #[test]
+    fn test_features() {
+        let mut features = ModuleFeatures::NONE;
+        assert!(features.contains(ModuleFeatures::NONE));
+        features.insert(ModuleFeatures::EXTERNAL_CALL);
+        assert!(features.contains(ModuleFeatures::EXTERNAL_CALL));
+        assert!(!features.contains(ModuleFeatures::ALL));
+        assert!(ModuleFeatures::ALL.contains(features));
+
+        let unknown = ModuleFeatures::from_bits(1 << 63 | 1);
+        assert_eq!(unknown.difference(ModuleFeatures::ALL), ModuleFeatures::from_bits(1 << 63));
+    }
+}
@@ -0,0 +1,276 @@
+use std::{fs, path::Path};
+use xelis_analyzer::{AnalysisReport, Analyzer};
+use xelis_aot::Transpiler;
+use xelis_ast::Program;
+use xelis_builder::EnvironmentBuilder;
+use xelis_bytecode::{Disassembler, Module};
+use xelis_compiler::{Compiler, OptimizationLevel};
+use xelis_decompiler::Decompiler;
+use xelis_lexer::Lexer;
+use xelis_parser::Parser;
+use xelis_types::Constant;
+use xelis_vm::{ModuleValidator, VM};
+
+// Extension of the source files, any other file is read as a serialized module
+pub const SOURCE_EXTENSION: &str = "xel";
+// Extension of the serialized modules
+pub const MODULE_EXTENSION: &str = "xvm";
+
+// Options of the compilation
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CompileOptions {
+    pub level: OptimizationLevel,
+    pub debug_info: bool,
+    // Remove the functions, constants and types unused by the entries and exports
+    pub strip: bool,
+    // Remove the debug assertions and the invariants of the functions
+    pub release: bool,
+}
+
+impl Default for CompileOptions {
+    fn default() -> Self {
+        Self {
+            level: OptimizationLevel::Basic,
+            debug_info: false,
+            strip: false,
+            release: false
+        }
+    }
+}
+
+// Result of an execution
+#[derive(Debug)]
+pub struct Execution {
+    pub value: Constant,
+    // The value was given to a revert
+    pub reverted: bool,
+    pub gas_usage: u64,
+}
+
+// Parse a source code, the warnings found are returned with the program
+fn parse<'a>(source: &'a str, environment: &'a EnvironmentBuilder) -> Result<(Program, Vec<String>), String> {
+    let tokens = Lexer::new(source).collect::<Result<Vec<_>, _>>()
+        .map_err(|e| e.to_string())?;
+
+    let (program, _, warnings) = Parser::with(tokens.into_iter(), environment)
+        .parse_with_warnings()
+        .map_err(|e| e.with_source(source).to_string())?;
+
+    Ok((program, warnings.iter().map(ToString::to_string).collect()))
+}
+
+// Compile a source code into a module
+pub fn compile(source: &str, environment: &EnvironmentBuilder, options: CompileOptions) -> Result<Module, String> {
+    let (program, _) = parse(source, environment)?;
+    let mut compiler = Compiler::new(&program, environment.environment())
+        .with_optimizations(options.level);
+    compiler.set_debug_info(options.debug_info);
+    compiler.set_dead_code_elimination(options.strip);
+    compiler.set_debug_assertions(!options.release);
+
+    compiler.compile().map_err(|e| e.to_string())
+}
+
+// Verify a source code without running it: it is parsed, compiled and its module validated
+// The warnings of the parser are returned
+pub fn check(source: &str, environment: &EnvironmentBuilder) -> Result<Vec<String>, String> {
+    let (program, warnings) = parse(source, environment)?;
+    let module = Compiler::new(&program, environment.environment())
+        .compile()
+        .map_err(|e| e.to_string())?;
+
+    ModuleValidator::new(&module, environment.environment())
+        .verify()
+        .map_err(|e| e.to_string())?;
+
+    Ok(warnings)
+}
+
+// Load a module from a file: a source file is compiled, any other file is deserialized
+pub fn load_module(path: &Path, environment: &EnvironmentBuilder, options: CompileOptions) -> Result<Module, String> {
+    if path.extension().is_some_and(|ext| ext == SOURCE_EXTENSION) {
+        let source = read_source(path)?;
+        return compile(&source, environment, options)
+    }
+
+    let bytes = fs::read(path)
+        .map_err(|e| format!("failed to read {}: {}", path.display(), e))?;
+
+    Module::from_bytes(&bytes)
+        .map_err(|e| format!("invalid module {}: {}", path.display(), e))
+}
+
+// Read a source file
+pub fn read_source(path: &Path) -> Result<String, String> {
+    fs::read_to_string(path)
+        .map_err(|e| format!("failed to read {}: {}", path.display(), e))
+}
+
+// Run an entry of the module by its name
+// The module is validated first as it may come from an untrusted file
+pub fn run(module: &Module, environment: &EnvironmentBuilder, entry: &str, gas_limit: Option<u64>) -> Result<Execution, String> {
+    let environment = environment.environment();
+    ModuleValidator::new(module, environment)
+        .verify()
+        .map_err(|e| e.to_string())?;
+
+    let id = module.entry_by_name(entry)
+        .ok_or_else(|| format!("entry '{}' not found", entry))?;
+
+    let mut vm = VM::new(module, environment);
+    if let Some(gas_limit) = gas_limit {
+        vm.context_mut().set_gas_limit(gas_limit);
+    }
+
+    vm.invoke_entry_chunk(id as u16)
+        .map_err(|e| e.to_string())?;
+
+    let outcome = vm.run().map_err(|e| e.to_string())?;
+    Ok(Execution {
+        reverted: !outcome.is_success(),
+        value: outcome.into_result().unwrap_or_else(|value| value),
+        gas_usage: vm.context().current_gas_usage()
+    })
+}
+
+// Listing of all the chunks of the module
+pub fn disassemble(module: &Module) -> Result<String, String> {
+    Disassembler::new(module)
+        .disassemble()
+        .map_err(|e| e.to_string())
+}
+
+// Rust source of the module compiled ahead of time
+pub fn transpile(module: &Module, environment: &EnvironmentBuilder) -> Result<String, String> {
+    Transpiler::new(module, environment.environment())
+        .transpile()
+        .map_err(|e| e.to_string())
+}
+
+// Pseudo-source rebuilt from the module
+pub fn decompile(module: &Module, environment: &EnvironmentBuilder) -> Result<String, String> {
+    Decompiler::new(module, environment)
+        .decompile()
+        .map_err(|e| e.to_string())
+}
+
+// Failures reachable from the entries and the exports
+pub fn analyze(module: &Module, environment: &EnvironmentBuilder) -> Result<Vec<AnalysisReport>, String> {
+    Analyzer::new(module, environment)
+        .analyze()
+        .map_err(|e| e.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use xelis_types::Value;
+    use super::*;
+
+    const CODE: &str = "struct Point { x: u64, y: u64 }
+
+    entry main() {
+        let p: Point = Point { x: 10, y: 20 }
+        return p.x + p.y
+    }";
+
+    #[test]
+    fn test_compile_and_run_serialized() {
+        let environment = EnvironmentBuilder::default();
+        let module = compile(CODE, &environment, CompileOptions::default()).unwrap();
+        let module = Module::from_bytes(&module.to_bytes().unwrap()).unwrap();
+
+        let execution = run(&module, &environment, "main", None).unwrap();
+        assert_eq!(execution.value, Constant::Default(Value::U64(30)));
+        assert!(!execution.reverted);
+        assert!(execution.gas_usage > 0);
+
+        let error = run(&module, &environment, "other", None).unwrap_err();
+        assert_eq!(error, "entry 'other' not found");
+    }
+
+    #[test]
+    fn test_compile_serialized_debug_info() {
+        let environment = EnvironmentBuilder::default();
+        let options = CompileOptions {
+            debug_info: true,
+            ..Default::default()
+        };
+        let module = compile(CODE, &environment, options).unwrap();
+        let decoded = Module::from_bytes(&module.to_bytes().unwrap()).unwrap();
+
+        let debug_info = decoded.debug_info().unwrap();
+        assert_eq!(Some(debug_info), module.debug_info());
+        let id = decoded.entry_by_name("main").unwrap();
+        assert_eq!(debug_info.get_chunk_name(id), Some("main"));
+    }
+
+    #[test]
+    fn test_run_reverted() {
+        let environment = EnvironmentBuilder::default();
+        let code = "entry main() {
+            require(false, \"not allowed\")
+            return 0
+        }";
+        let module = compile(code, &environment, CompileOptions::default()).unwrap();
+
+        let execution = run(&module, &environment, "main", None).unwrap();
+        assert!(execution.reverted);
+        assert_eq!(execution.value, Constant::Default(Value::String("not allowed".to_owned())));
+    }
+
+    #[test]
+    fn test_run_out_of_gas() {
+        let environment = EnvironmentBuilder::default();
+        let module = compile(CODE, &environment, CompileOptions::default()).unwrap();
+        let execution = run(&module, &environment, "main", None).unwrap();
+
+        assert!(run(&module, &environment, "main", Some(execution.gas_usage)).is_ok());
+        assert!(run(&module, &environment, "main", Some(execution.gas_usage - 1)).is_err());
+    }
+
+    #[test]
+    fn test_check() {
+        let environment = EnvironmentBuilder::default();
+        let warnings = check("entry main() {\n    let unused: u64 = 1\n    return 0\n}", &environment).unwrap();
+        assert_eq!(warnings.len(), 1);
+        assert!(warnings[0].contains("unused_variable"));
+
+        let error = check("entry main() {\n    return value\n}", &environment).unwrap_err();
+        assert!(error.contains("2 |     return value"));
+    }
+
+    #[test]
+    fn test_disassemble() {
+        let environment = EnvironmentBuilder::default();
+        let module = compile(CODE, &environment, CompileOptions::default()).unwrap();
+        let listing = disassemble(&module).unwrap();
+        assert!(listing.contains("Return"));
+    }
+
+    #[test]
+    fn test_transpile() {
+        let environment = EnvironmentBuilder::default();
+        let module = compile(CODE, &environment, CompileOptions::default()).unwrap();
+        let source = transpile(&module, &environment).unwrap();
+        assert!(source.contains("pub fn register<'a>(vm: &mut VM<'a>, module: &'a Module) -> bool"));
+    }
+
+    #[test]
+    fn test_decompile() {
+        let environment = EnvironmentBuilder::default();
+        let module = compile(CODE, &environment, CompileOptions::default()).unwrap();
+        let source = decompile(&module, &environment).unwrap();
+        assert!(source.contains("entry main() {"));
+        assert!(source.contains("return (v0.field0 + v0.field1)"));
+    }
+
+    #[test]
+    fn test_analyze() {
+        let environment = EnvironmentBuilder::default();
+        let code = "entry main(a: u64) {\n    return 100 / a\n}";
+        let module = compile(code, &environment, CompileOptions::default()).unwrap();
+        let reports = analyze(&module, &environment).unwrap();
+        assert_eq!(reports.len(), 1);
+        assert_eq!(reports[0].findings[0].arguments, vec![Value::U64(0)]);
+    }
+}
@@ -0,0 +1,323 @@
+mod commands;
+
+use std::{
+    env,
+    fs,
+    path::PathBuf,
+    process::ExitCode
+};
+use xelis_builder::EnvironmentBuilder;
+use xelis_compiler::OptimizationLevel;
+use commands::{CompileOptions, MODULE_EXTENSION};
+
+const USAGE: &str = "Usage: xelis-vm <command> [options]
+Commands:
+    compile <file.xel> [-o <file.xvm>]    compile a source file into a serialized module
+    run <file> [--entry <name>] [--gas <limit>]
+                                          run an entry of a module or a source file, `main` by default
+    check <file.xel>                      parse, compile and validate a source file without running it
+    disasm <file>                         print the instructions of a module or a source file
+    stats <file>                          print the size and complexity of a module or a source file
+    aot <file> [-o <file.rs>]             translate a module or a source file into Rust source
+    decompile <file>                      print the pseudo-source rebuilt from a module or a source file
+    analyze <file>                        find the arguments of the entries and exports making them fail
+    diff <old> <new>                      compare two versions of a module, fails if the upgrade is incompatible
+Compilation options:
+    -O0, -O1, -O2                         no optimization, constant folding only (default), all the optimizations
+    --debug-info                          keep the source spans and function names in the module
+    --strip                               remove the functions, constants and types unused by the entries and exports
+    --release                             remove the assertions and the invariants, `require` is still checked";
+
+#[derive(Debug, PartialEq, Eq)]
+enum Command {
+    Compile {
+        input: PathBuf,
+        output: Option<PathBuf>,
+        options: CompileOptions,
+    },
+    Run {
+        input: PathBuf,
+        entry: String,
+        gas_limit: Option<u64>,
+        options: CompileOptions,
+    },
+    Check {
+        input: PathBuf,
+    },
+    Disasm {
+        input: PathBuf,
+        options: CompileOptions,
+    },
+    Aot {
+        input: PathBuf,
+        output: Option<PathBuf>,
+        options: CompileOptions,
+    },
+    Decompile {
+        input: PathBuf,
+        options: CompileOptions,
+    },
+    Stats {
+        input: PathBuf,
+        options: CompileOptions,
+    },
+    Analyze {
+        input: PathBuf,
+        options: CompileOptions,
+    },
+    Diff {
+        old: PathBuf,
+        new: PathBuf,
+        options: CompileOptions,
+    },
+    Help,
+}
+
+fn main() -> ExitCode {
+    let args: Vec<String> = env::args().skip(1).collect();
+    let result = parse_args(&args).and_then(execute);
+    match result {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("{}", e);
+            ExitCode::FAILURE
+        }
+    }
+}
+
+fn execute(command: Command) -> Result<(), String> {
+    let environment = EnvironmentBuilder::default();
+    match command {
+        Command::Compile { input, output, options } => {
+            let source = commands::read_source(&input)?;
+            let module = commands::compile(&source, &environment, options)?;
+            let output = output.unwrap_or_else(|| input.with_extension(MODULE_EXTENSION));
+            let bytes = module.to_bytes()
+                .map_err(|e| format!("failed to serialize the module: {}", e))?;
+            fs::write(&output, bytes)
+                .map_err(|e| format!("failed to write {}: {}", output.display(), e))?;
+        },
+        Command::Run { input, entry, gas_limit, options } => {
+            let module = commands::load_module(&input, &environment, options)?;
+            let execution = commands::run(&module, &environment, &entry, gas_limit)?;
+            if execution.reverted {
+                println!("gas used: {}", execution.gas_usage);
+                return Err(format!("reverted with {}", execution.value));
+            }
+
+            println!("{}", execution.value);
+            println!("gas used: {}", execution.gas_usage);
+        },
+        Command::Check { input } => {
+            let source = commands::read_source(&input)?;
+            for warning in commands::check(&source, &environment)? {
+                println!("{}: {}", input.display(), warning);
+            }
+        },
+        Command::Disasm { input, options } => {
+            let module = commands::load_module(&input, &environment, options)?;
+            print!("{}", commands::disassemble(&module)?);
+        },
+        Command::Aot { input, output, options } => {
+            let module = commands::load_module(&input, &environment, options)?;
+            let source = commands::transpile(&module, &environment)?;
+            match output {
+                Some(output) => fs::write(&output, source)
+                    .map_err(|e| format!("failed to write {}: {}", output.display(), e))?,
+                None => print!("{}", source)
+            }
+        },
+        Command::Decompile { input, options } => {
+            let module = commands::load_module(&input, &environment, options)?;
+            print!("{}", commands::decompile(&module, &environment)?);
+        },
+        Command::Stats { input, options } => {
+            // Instructions and call depth of each chunk and size of the constants,
+            // to compare with the limits of the validator before deploying
+            let module = commands::load_module(&input, &environment, options)?;
+            let stats = module.stats()
+                .map_err(|e| format!("failed to serialize the module: {}", e))?;
+            println!("{}", stats);
+        },
+        Command::Analyze { input, options } => {
+            let module = commands::load_module(&input, &environment, options)?;
+            let reports = commands::analyze(&module, &environment)?;
+            for report in &reports {
+                print!("{}", report);
+            }
+            let findings = reports.iter().map(|r| r.findings.len()).sum::<usize>();
+            if findings > 0 {
+                return Err(format!("{} failures found", findings))
+            }
+        },
+        Command::Diff { old, new, options } => {
+            let old = commands::load_module(&old, &environment, options)?;
+            let new = commands::load_module(&new, &environment, options)?;
+            let diff = old.diff(&new);
+            println!("{}", diff);
+            if !diff.is_compatible() {
+                return Err("the new module is incompatible with the old one".to_owned())
+            }
+        },
+        Command::Help => println!("{}", USAGE)
+    }
+
+    Ok(())
+}
+
+// Parse a number, `_` can be used as separator like in the scripts
+fn parse_number(value: &str) -> Result<u64, String> {
+    value.replace('_', "")
+        .parse()
+        .map_err(|_| format!("invalid number '{}'", value))
+}
+
+fn parse_args(args: &[String]) -> Result<Command, String> {
+    let Some((command, args)) = args.split_first() else {
+        return Err(USAGE.to_owned())
+    };
+
+    let mut input = None;
+    // Second input of a diff
+    let mut other = None;
+    let mut output = None;
+    let mut entry = None;
+    let mut gas_limit = None;
+    let mut options = CompileOptions::default();
+
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        let mut value = |name: &str| args.next()
+            .cloned()
+            .ok_or_else(|| format!("missing value for {}", name));
+
+        match arg.as_str() {
+            "-o" | "--output" => output = Some(PathBuf::from(value(arg)?)),
+            "--entry" => entry = Some(value(arg)?),
+            "--gas" => gas_limit = Some(parse_number(&value(arg)?)?),
+            "-O0" => options.level = OptimizationLevel::None,
+            "-O1" => options.level = OptimizationLevel::Basic,
+            "-O2" => options.level = OptimizationLevel::Full,
+            "--debug-info" => options.debug_info = true,
+            "--strip" => options.strip = true,
+            "--release" => options.release = true,
+            "-h" | "--help" => return Ok(Command::Help),
+            arg if arg.starts_with('-') => return Err(format!("unknown option {}", arg)),
+            arg if input.is_none() => input = Some(PathBuf::from(arg)),
+            arg if command == "diff" && other.is_none() => other = Some(PathBuf::from(arg)),
+            arg => return Err(format!("unexpected argument {}", arg))
+        }
+    }
+
+    if matches!(command.as_str(), "help" | "-h" | "--help") {
+        return Ok(Command::Help)
+    }
+
+    let input = input.ok_or_else(|| format!("missing the input file of {}", command))?;
+    Ok(match command.as_str() {
+        "compile" => Command::Compile { input, output, options },
+        "run" => Command::Run {
+            input,
+            entry: entry.unwrap_or_else(|| "main".to_owned()),
+            gas_limit,
+            options
+        },
+        "check" => Command::Check { input },
+        "disasm" => Command::Disasm { input, options },
+        "aot" => Command::Aot { input, output, options },
+        "decompile" => Command::Decompile { input, options },
+        "stats" => Command::Stats { input, options },
+        "analyze" => Command::Analyze { input, options },
+        "diff" => Command::Diff {
+            old: input,
+            new: other.ok_or("missing the new module of diff")?,
+            options
+        },
+        command => return Err(format!("unknown command {}\n{}", command, USAGE))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(args: &str) -> Result<Command, String> {
+        let args: Vec<String> = args.split_whitespace().map(ToOwned::to_owned).collect();
+        parse_args(&args)
+    }
+
+    #[test]
+    fn test_parse_args() {
+        assert_eq!(parse("compile file.xel -o out.xvm -O2 --debug-info --strip --release").unwrap(), Command::Compile {
+            input: PathBuf::from("file.xel"),
+            output: Some(PathBuf::from("out.xvm")),
+            options: CompileOptions {
+                level: OptimizationLevel::Full,
+                debug_info: true,
+                strip: true,
+                release: true
+            }
+        });
+
+        assert_eq!(parse("run file.xvm --entry start --gas 1_000_000").unwrap(), Command::Run {
+            input: PathBuf::from("file.xvm"),
+            entry: "start".to_owned(),
+            gas_limit: Some(1_000_000),
+            options: CompileOptions::default()
+        });
+
+        assert_eq!(parse("run file.xel").unwrap(), Command::Run {
+            input: PathBuf::from("file.xel"),
+            entry: "main".to_owned(),
+            gas_limit: None,
+            options: CompileOptions::default()
+        });
+
+        assert_eq!(parse("check file.xel").unwrap(), Command::Check { input: PathBuf::from("file.xel") });
+        assert_eq!(parse("aot file.xvm -o file.rs").unwrap(), Command::Aot {
+            input: PathBuf::from("file.xvm"),
+            output: Some(PathBuf::from("file.rs")),
+            options: CompileOptions::default()
+        });
+        assert_eq!(parse("decompile file.xel --debug-info").unwrap(), Command::Decompile {
+            input: PathBuf::from("file.xel"),
+            options: CompileOptions {
+                debug_info: true,
+                ..Default::default()
+            }
+        });
+        assert_eq!(parse("stats file.xel --strip").unwrap(), Command::Stats {
+            input: PathBuf::from("file.xel"),
+            options: CompileOptions {
+                strip: true,
+                ..Default::default()
+            }
+        });
+        assert_eq!(parse("analyze file.xvm").unwrap(), Command::Analyze {
+            input: PathBuf::from("file.xvm"),
+            options: CompileOptions::default()
+        });
+        assert_eq!(parse("diff old.xvm new.xel -O2").unwrap(), Command::Diff {
+            old: PathBuf::from("old.xvm"),
+            new: PathBuf::from("new.xel"),
+            options: CompileOptions {
+                level: OptimizationLevel::Full,
+                ..Default::default()
+            }
+        });
+        assert_eq!(parse("help").unwrap(), Command::Help);
+    }
+
+    #[test]
+    fn test_parse_invalid_args() {
+        assert!(parse("").is_err());
+        assert!(parse("run").is_err());
+        assert!(parse("run file.xvm --gas").is_err());
+        assert!(parse("run file.xvm --gas many").is_err());
+        assert!(parse("run file.xvm other.xvm").is_err());
+        assert!(parse("diff old.xvm").is_err());
+        assert!(parse("diff old.xvm new.xvm other.xvm").is_err());
+        assert!(parse("build file.xel").is_err());
+        assert!(parse("check file.xel --unknown").is_err());
+    }
+}
@@ -0,0 +1,216 @@
+use std::{
+    collections::{hash_map::{DefaultHasher, Entry}, HashMap},
+    hash::{Hash, Hasher}
+};
+use thiserror::Error;
+use xelis_ast::{Program, TokenResult};
+use xelis_builder::EnvironmentBuilder;
+use xelis_bytecode::Module;
+use xelis_lexer::{Lexer, LexerError};
+use xelis_parser::{Parser, ParserError};
+
+use crate::{Compiler, CompilerError, OptimizationLevel};
+
+#[derive(Debug, Error)]
+pub enum CacheError<'a> {
+    #[error(transparent)]
+    Lexer(#[from] LexerError),
+    // Boxed as the parser error is large
+    #[error(transparent)]
+    Parser(Box<ParserError<'a>>),
+    #[error(transparent)]
+    Compiler(#[from] CompilerError),
+}
+
+// Everything produced from a source
+struct CacheEntry<'a> {
+    // Kept to detect a collision of the hashes
+    source: &'a str,
+    tokens: Option<Vec<TokenResult<'a>>>,
+    // Programs by fingerprint of the environment
+    programs: HashMap<u64, Program>,
+    // Modules by fingerprint of the environment and optimization level
+    modules: HashMap<(u64, OptimizationLevel), Module>,
+}
+
+impl<'a> CacheEntry<'a> {
+    fn new(source: &'a str) -> Self {
+        Self {
+            source,
+            tokens: None,
+            programs: HashMap::new(),
+            modules: HashMap::new(),
+        }
+    }
+}
+
+// Memoize the tokens, programs and modules of the sources
+// Tooling re-compiling the same sources, like an editor or a test runner, only pays for the changes.
+// The entries are keyed by the hash of the source and by the fingerprint of the environment,
+// so an environment registering a new function doesn't reuse the previous results.
+// The tokens borrow the sources, which must outlive the cache.
+#[derive(Default)]
+pub struct CompilationCache<'a> {
+    entries: HashMap<u64, CacheEntry<'a>>,
+}
+
+impl<'a> CompilationCache<'a> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // Hash of a source used as key
+    pub fn hash_source(source: &str) -> u64 {
+        let mut hasher = DefaultHasher::new();
+        source.hash(&mut hasher);
+        hasher.finish()
+    }
+
+    // Get the entry of a source, a different source with the same hash replaces it
+    fn entry(&mut self, source: &'a str) -> &mut CacheEntry<'a> {
+        match self.entries.entry(Self::hash_source(source)) {
+            Entry::Occupied(entry) => {
+                let entry = entry.into_mut();
+                if entry.source != source {
+                    *entry = CacheEntry::new(source);
+                }
+                entry
+            },
+            Entry::Vacant(entry) => entry.insert(CacheEntry::new(source))
+        }
+    }
+
+    // Get the tokens of a source, it is only read the first time
+    pub fn tokens(&mut self, source: &'a str) -> Result<&[TokenResult<'a>], LexerError> {
+        let entry = self.entry(source);
+        let tokens = match entry.tokens.take() {
+            Some(tokens) => tokens,
+            None => Lexer::new(source).collect::<Result<Vec<_>, _>>()?
+        };
+
+        Ok(entry.tokens.insert(tokens))
+    }
+
+    // Get the program of a source parsed with the environment
+    pub fn program(&mut self, source: &'a str, environment: &'a EnvironmentBuilder) -> Result<&Program, CacheError<'a>> {
+        let fingerprint = environment.fingerprint();
+        if !self.entry(source).programs.contains_key(&fingerprint) {
+            // The parser consumes its tokens
+            let tokens = self.tokens(source)?.to_vec();
+            let (program, _) = Parser::with(tokens.into_iter(), environment)
+                .parse()
+                .map_err(|e| CacheError::Parser(Box::new(e)))?;
+
+            self.entry(source).programs.insert(fingerprint, program);
+        }
+
+        Ok(&self.entry(source).programs[&fingerprint])
+    }
+
+    // Get the module of a source compiled with the environment and the optimization level
+    pub fn module(&mut self, source: &'a str, environment: &'a EnvironmentBuilder, level: OptimizationLevel) -> Result<&Module, CacheError<'a>> {
+        let key = (environment.fingerprint(), level);
+        if !self.entry(source).modules.contains_key(&key) {
+            let program = self.program(source, environment)?;
+            let module = Compiler::new(program, environment.environment())
+                .with_optimizations(level)
+                .compile()?;
+
+            self.entry(source).modules.insert(key, module);
+        }
+
+        Ok(&self.entry(source).modules[&key])
+    }
+
+    // Forget everything produced from a source
+    pub fn invalidate(&mut self, source: &str) {
+        let hash = Self::hash_source(source);
+        if self.entries.get(&hash).is_some_and(|entry| entry.source == source) {
+            self.entries.remove(&hash);
+        }
+    }
+
+    // Forget the programs and modules produced with an environment
+    // The tokens don't depend on it and are kept
+    pub fn invalidate_environment(&mut self, environment: &EnvironmentBuilder) {
+        let fingerprint = environment.fingerprint();
+        for entry in self.entries.values_mut() {
+            entry.programs.remove(&fingerprint);
+            entry.modules.retain(|(f, _), _| *f != fingerprint);
+        }
+    }
+
+    // Forget everything
+    pub fn clear(&mut self) {
+        self.entries.clear();
+    }
+
+    // Count of sources in the cache
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use xelis_types::Type;
+    use super::*;
+
+    const CODE: &str = "entry main() {\n    return 10\n}";
+
+    #[test]
+    fn test_memoized() {
+        let environment = EnvironmentBuilder::default();
+        let mut cache = CompilationCache::new();
+
+        let tokens = cache.tokens(CODE).unwrap().as_ptr();
+        assert_eq!(cache.tokens(CODE).unwrap().as_ptr(), tokens);
+
+        let program: *const Program = cache.program(CODE, &environment).unwrap();
+        assert!(std::ptr::eq(cache.program(CODE, &environment).unwrap(), program));
+
+        let module: *const Module = cache.module(CODE, &environment, OptimizationLevel::Basic).unwrap();
+        assert!(std::ptr::eq(cache.module(CODE, &environment, OptimizationLevel::Basic).unwrap(), module));
+        assert_eq!(cache.module(CODE, &environment, OptimizationLevel::Full).unwrap().chunks().len(), 1);
+        assert_eq!(cache.len(), 1);
+    }
+
+    #[test]
+    fn test_environment_fingerprint() {
+        let environment = EnvironmentBuilder::default();
+        let mut other = EnvironmentBuilder::default();
+        other.register_native_function("ten", None, vec![], |_, _, _| Ok(None), 1, Some(Type::U64));
+
+        let mut cache = CompilationCache::new();
+        let code = "entry main() {\n    return ten()\n}";
+        assert!(matches!(cache.program(code, &environment), Err(CacheError::Parser(_))));
+        assert!(cache.module(code, &other, OptimizationLevel::Basic).is_ok());
+
+        // The tokens are kept
+        let tokens = cache.tokens(code).unwrap().as_ptr();
+        cache.invalidate_environment(&other);
+        assert_eq!(cache.tokens(code).unwrap().as_ptr(), tokens);
+        assert!(cache.module(code, &other, OptimizationLevel::Basic).is_ok());
+    }
+
+    #[test]
+    fn test_invalidate() {
+        let environment = EnvironmentBuilder::default();
+        let mut cache = CompilationCache::new();
+        let other = "entry main() {\n    return 20\n}";
+        cache.module(CODE, &environment, OptimizationLevel::Basic).unwrap();
+        cache.module(other, &environment, OptimizationLevel::Basic).unwrap();
+        assert_eq!(cache.len(), 2);
+
+        cache.invalidate(CODE);
+        assert_eq!(cache.len(), 1);
+        cache.clear();
+        assert!(cache.is_empty());
+
+        assert!(cache.tokens("entry main() { return \"a }").is_err());
+    }
+}
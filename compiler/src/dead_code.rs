@@ -0,0 +1,589 @@
+use std::collections::{HashMap, HashSet};
+use xelis_bytecode::{Chunk, ChunkSignature, Module, OpCode};
+use xelis_types::{Constant, EnumType, EnumValueType, EnumVariant, StructType, Type, Value};
+
+use crate::CompilerError;
+
+// Offsets in the arguments of the u16 constant ids read by an opcode
+fn constant_arguments(opcode: OpCode) -> &'static [usize] {
+    match opcode {
+        OpCode::Constant => &[0],
+        // module name and export name
+        OpCode::ExternalCall => &[0, 2],
+        // after the default address
+        OpCode::JumpTable => &[4],
+        _ => &[]
+    }
+}
+
+// Decode the offset of each instruction of a chunk
+fn decode_offsets(chunk: &Chunk) -> Result<Vec<(usize, OpCode)>, CompilerError> {
+    let bytes = chunk.get_instructions();
+    let mut instructions = Vec::new();
+    let mut offset = 0;
+    while offset < bytes.len() {
+        let byte = bytes[offset];
+        let opcode = OpCode::from_byte(byte)
+            .ok_or(CompilerError::InvalidOpCode(byte))?;
+
+        let next = offset + 1 + opcode.arguments_bytes();
+        if next > bytes.len() {
+            return Err(CompilerError::InvalidOpCodeArguments(opcode))
+        }
+
+        instructions.push((offset, opcode));
+        offset = next;
+    }
+
+    Ok(instructions)
+}
+
+fn read_u16(bytes: &[u8], offset: usize) -> u16 {
+    u16::from_le_bytes([bytes[offset], bytes[offset + 1]])
+}
+
+fn write_u16(bytes: &mut [u8], offset: usize, value: u16) {
+    bytes[offset..offset + 2].copy_from_slice(&value.to_le_bytes());
+}
+
+// Everything of the module reachable from its roots
+#[derive(Default)]
+struct Usage {
+    chunks: HashSet<usize>,
+    constants: HashSet<usize>,
+    // Ids of the structs and enums, those of the environment included
+    structs: HashSet<u16>,
+    enums: HashSet<u16>,
+}
+
+impl Usage {
+    fn mark_type(&mut self, _type: &Type) {
+        match _type {
+            Type::Array(inner)
+            | Type::Optional(inner)
+            | Type::Range(inner)
+            | Type::Iterator(inner) => self.mark_type(inner),
+            Type::Map(left, right)
+            | Type::Result(left, right) => {
+                self.mark_type(left);
+                self.mark_type(right);
+            },
+            Type::Tuple(types) => types.iter().for_each(|t| self.mark_type(t)),
+            Type::Struct(struct_type) => self.mark_struct(struct_type),
+            Type::Enum(enum_type) => self.mark_enum(enum_type),
+            _ => {}
+        }
+    }
+
+    fn mark_struct(&mut self, struct_type: &StructType) {
+        if self.structs.insert(struct_type.id()) {
+            struct_type.fields().iter().for_each(|t| self.mark_type(t));
+        }
+    }
+
+    fn mark_enum(&mut self, enum_type: &EnumType) {
+        if self.enums.insert(enum_type.id()) {
+            for variant in enum_type.variants() {
+                variant.fields().iter().for_each(|t| self.mark_type(t));
+            }
+        }
+    }
+
+    fn mark_constant(&mut self, constant: &Constant) {
+        match constant {
+            Constant::Default(Value::Range(_, _, _type, _)) => self.mark_type(_type),
+            Constant::Default(_) => {},
+            Constant::Struct(fields, struct_type) => {
+                self.mark_struct(struct_type);
+                fields.iter().for_each(|c| self.mark_constant(c));
+            },
+            Constant::Array(values) => values.iter().for_each(|c| self.mark_constant(c)),
+            Constant::Optional(value) => if let Some(value) = value {
+                self.mark_constant(value);
+            },
+            Constant::Map(map) => for (key, value) in map {
+                self.mark_constant(key);
+                self.mark_constant(value);
+            },
+            Constant::Enum(fields, enum_type) => {
+                self.mark_enum(enum_type.enum_type());
+                fields.iter().for_each(|c| self.mark_constant(c));
+            }
+        }
+    }
+}
+
+// Rebuild the types with the new ids of the structs and enums kept
+// The types of the environment are not changed
+#[derive(Default)]
+struct TypesRemap {
+    structs: HashMap<u16, u16>,
+    enums: HashMap<u16, u16>,
+    // Types already rebuilt by their old id, so they are shared like the original ones
+    built_structs: HashMap<u16, StructType>,
+    built_enums: HashMap<u16, EnumType>,
+}
+
+impl TypesRemap {
+    fn remap_type(&mut self, _type: &Type) -> Type {
+        match _type {
+            Type::Array(inner) => Type::Array(Box::new(self.remap_type(inner))),
+            Type::Optional(inner) => Type::Optional(Box::new(self.remap_type(inner))),
+            Type::Range(inner) => Type::Range(Box::new(self.remap_type(inner))),
+            Type::Iterator(inner) => Type::Iterator(Box::new(self.remap_type(inner))),
+            Type::Map(key, value) => Type::Map(Box::new(self.remap_type(key)), Box::new(self.remap_type(value))),
+            Type::Result(value, error) => Type::Result(Box::new(self.remap_type(value)), Box::new(self.remap_type(error))),
+            Type::Tuple(types) => Type::Tuple(types.iter().map(|t| self.remap_type(t)).collect()),
+            Type::Struct(struct_type) => Type::Struct(self.remap_struct(struct_type)),
+            Type::Enum(enum_type) => Type::Enum(self.remap_enum(enum_type)),
+            _type => _type.clone()
+        }
+    }
+
+    fn remap_struct(&mut self, struct_type: &StructType) -> StructType {
+        let id = struct_type.id();
+        let Some(new_id) = self.structs.get(&id).copied() else {
+            return struct_type.clone()
+        };
+
+        if let Some(built) = self.built_structs.get(&id) {
+            return built.clone()
+        }
+
+        let fields = struct_type.fields().iter().map(|t| self.remap_type(t)).collect();
+        let built = StructType::new(new_id, fields);
+        self.built_structs.insert(id, built.clone());
+        built
+    }
+
+    fn remap_enum(&mut self, enum_type: &EnumType) -> EnumType {
+        let id = enum_type.id();
+        let Some(new_id) = self.enums.get(&id).copied() else {
+            return enum_type.clone()
+        };
+
+        if let Some(built) = self.built_enums.get(&id) {
+            return built.clone()
+        }
+
+        let variants = enum_type.variants().iter()
+            .map(|variant| EnumVariant::new(variant.fields().iter().map(|t| self.remap_type(t)).collect()))
+            .collect();
+        let built = EnumType::new(new_id, variants);
+        self.built_enums.insert(id, built.clone());
+        built
+    }
+
+    fn remap_constant(&mut self, constant: &Constant) -> Constant {
+        match constant {
+            Constant::Default(Value::Range(start, end, _type, inclusive)) => {
+                Constant::Default(Value::Range(start.clone(), end.clone(), self.remap_type(_type), *inclusive))
+            },
+            Constant::Default(value) => Constant::Default(value.clone()),
+            Constant::Struct(fields, struct_type) => Constant::Struct(
+                fields.iter().map(|c| self.remap_constant(c)).collect(),
+                self.remap_struct(struct_type)
+            ),
+            Constant::Array(values) => Constant::Array(values.iter().map(|c| self.remap_constant(c)).collect()),
+            Constant::Optional(value) => Constant::Optional(value.as_ref().map(|c| Box::new(self.remap_constant(c)))),
+            Constant::Map(map) => Constant::Map(map.iter()
+                .map(|(key, value)| (self.remap_constant(key), self.remap_constant(value)))
+                .collect()
+            ),
+            Constant::Enum(fields, enum_type) => Constant::Enum(
+                fields.iter().map(|c| self.remap_constant(c)).collect(),
+                EnumValueType::new(self.remap_enum(enum_type.enum_type()), enum_type.variant_id())
+            )
+        }
+    }
+
+    fn remap_signature(&mut self, signature: &ChunkSignature) -> ChunkSignature {
+        let parameters = signature.parameters().iter()
+            .map(|(name, _type)| (name.clone(), self.remap_type(_type)))
+            .collect();
+        let return_type = signature.return_type().map(|t| self.remap_type(t));
+        ChunkSignature::new(parameters, return_type)
+    }
+}
+
+// New index of each kept index, in their order
+fn compact(len: usize, kept: impl Fn(usize) -> bool) -> HashMap<usize, usize> {
+    (0..len).filter(|i| kept(*i))
+        .enumerate()
+        .map(|(new, old)| (old, new))
+        .collect()
+}
+
+// Remove the constants no instruction reads anymore, like the ones replaced by the optimizer
+// The ids of the kept constants are rewritten in place, nothing else is renumbered
+pub fn remove_unused_constants(module: &mut Module) -> Result<(), CompilerError> {
+    let chunks = module.chunks().iter()
+        .map(decode_offsets)
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let mut used = HashSet::new();
+    for (chunk, instructions) in module.chunks().iter().zip(&chunks) {
+        let bytes = chunk.get_instructions();
+        for (offset, opcode) in instructions.iter().copied() {
+            for arg in constant_arguments(opcode) {
+                used.insert(read_u16(bytes, offset + 1 + arg) as usize);
+            }
+        }
+    }
+
+    let len = module.constants().len();
+    if (0..len).all(|id| used.contains(&id)) {
+        return Ok(())
+    }
+
+    let constant_ids = compact(len, |i| used.contains(&i));
+    for (id, instructions) in chunks.into_iter().enumerate() {
+        let Some(chunk) = module.get_chunk_at_mut(id) else {
+            return Err(CompilerError::ChunkNotFound(id))
+        };
+
+        let mut bytes = chunk.get_instructions().to_vec();
+        for (offset, opcode) in instructions {
+            for arg in constant_arguments(opcode) {
+                let id = read_u16(&bytes, offset + 1 + arg) as usize;
+                let new_id = constant_ids.get(&id)
+                    .ok_or(CompilerError::ConstantNotFound(id))?;
+                write_u16(&mut bytes, offset + 1 + arg, *new_id as u16);
+            }
+        }
+
+        let mut rewritten = Chunk::new();
+        rewritten.write_bytes(&bytes);
+        *chunk = rewritten;
+    }
+
+    module.retain_constants(|id| constant_ids.contains_key(&id));
+
+    Ok(())
+}
+
+// Remove the chunks, constants, structs and enums not used by the module
+// The roots are the entry and exported chunks with their signatures, the events and the migrations.
+// Everything reachable from them is kept in its order and renumbered,
+// the instructions are rewritten with the new ids.
+// The test chunks are removed too: strip a module only to deploy it.
+pub fn eliminate_dead_code(module: Module) -> Result<Module, CompilerError> {
+    let chunks = module.chunks().iter()
+        .map(decode_offsets)
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let module_structs: HashMap<u16, &StructType> = module.structs().iter().map(|s| (s.id(), s)).collect();
+    let module_enums: HashMap<u16, &EnumType> = module.enums().iter().map(|e| (e.id(), e)).collect();
+
+    let mut usage = Usage::default();
+    let mut pending: Vec<usize> = module.chunks_entry_ids().iter()
+        .chain(module.exports().values())
+        .chain(module.migrations().values())
+        .copied()
+        .collect();
+
+    // Follow the calls between the chunks
+    while let Some(id) = pending.pop() {
+        let (Some(chunk), Some(instructions)) = (module.get_chunk_at(id), chunks.get(id)) else {
+            return Err(CompilerError::ChunkNotFound(id))
+        };
+
+        if !usage.chunks.insert(id) {
+            continue;
+        }
+
+        let bytes = chunk.get_instructions();
+        for (offset, opcode) in instructions.iter().copied() {
+            let args = offset + 1;
+            match opcode {
+                OpCode::InvokeChunk | OpCode::ChunkId => pending.push(read_u16(bytes, args) as usize),
+                OpCode::NewStruct => if let Some(struct_type) = module_structs.get(&read_u16(bytes, args)) {
+                    usage.mark_struct(struct_type);
+                },
+                OpCode::NewEnum => if let Some(enum_type) = module_enums.get(&read_u16(bytes, args)) {
+                    usage.mark_enum(enum_type);
+                },
+                _ => for arg in constant_arguments(opcode) {
+                    usage.constants.insert(read_u16(bytes, args + arg) as usize);
+                }
+            }
+        }
+    }
+
+    for id in usage.constants.clone() {
+        let constant = module.get_constant_at(id)
+            .ok_or(CompilerError::ConstantNotFound(id))?;
+        usage.mark_constant(constant);
+    }
+
+    let signatures: Vec<_> = usage.chunks.iter()
+        .filter_map(|id| module.get_signature(*id))
+        .collect();
+    for signature in signatures {
+        signature.parameters().iter().for_each(|(_, t)| usage.mark_type(t));
+        if let Some(return_type) = signature.return_type() {
+            usage.mark_type(return_type);
+        }
+    }
+
+    module.events().values().for_each(|t| usage.mark_type(t));
+
+    // A migrated struct is kept for the upgrade checks even if no chunk uses it
+    for index in module.migrations().keys() {
+        if let Some(struct_type) = module.get_struct_at(*index) {
+            usage.mark_struct(struct_type);
+        }
+    }
+
+    // The kept types take the ids of the first ones of the module
+    let mut types = TypesRemap::default();
+    let struct_ids: Vec<u16> = module.structs().iter().map(StructType::id).collect();
+    for (new, old) in struct_ids.iter().filter(|id| usage.structs.contains(id)).enumerate() {
+        types.structs.insert(*old, struct_ids[new]);
+    }
+
+    let enum_ids: Vec<u16> = module.enums().iter().map(EnumType::id).collect();
+    for (new, old) in enum_ids.iter().filter(|id| usage.enums.contains(id)).enumerate() {
+        types.enums.insert(*old, enum_ids[new]);
+    }
+
+    let chunk_ids = compact(module.chunks().len(), |i| usage.chunks.contains(&i));
+    let constant_ids = compact(module.constants().len(), |i| usage.constants.contains(&i));
+    let struct_indexes = compact(struct_ids.len(), |i| usage.structs.contains(&struct_ids[i]));
+
+    let mut stripped = Module::new();
+    stripped.set_version(module.version());
+
+    for struct_type in module.structs().iter().filter(|s| usage.structs.contains(&s.id())) {
+        stripped.add_struct(types.remap_struct(struct_type));
+    }
+
+    for enum_type in module.enums().iter().filter(|e| usage.enums.contains(&e.id())) {
+        stripped.add_enum(types.remap_enum(enum_type));
+    }
+
+    for (id, constant) in module.constants().iter().enumerate() {
+        if constant_ids.contains_key(&id) {
+            stripped.add_constant(types.remap_constant(&constant.0));
+        }
+    }
+
+    for (id, (chunk, instructions)) in module.chunks().iter().zip(chunks).enumerate() {
+        if !chunk_ids.contains_key(&id) {
+            continue;
+        }
+
+        let mut bytes = chunk.get_instructions().to_vec();
+        for (offset, opcode) in instructions {
+            let args = offset + 1;
+            match opcode {
+                OpCode::InvokeChunk | OpCode::ChunkId => {
+                    let id = read_u16(&bytes, args) as usize;
+                    write_u16(&mut bytes, args, chunk_ids[&id] as u16);
+                },
+                OpCode::NewStruct => if let Some(id) = types.structs.get(&read_u16(&bytes, args)) {
+                    write_u16(&mut bytes, args, *id);
+                },
+                OpCode::NewEnum => if let Some(id) = types.enums.get(&read_u16(&bytes, args)) {
+                    write_u16(&mut bytes, args, *id);
+                },
+                _ => for arg in constant_arguments(opcode) {
+                    let id = read_u16(&bytes, args + arg) as usize;
+                    write_u16(&mut bytes, args + arg, constant_ids[&id] as u16);
+                }
+            }
+        }
+
+        let mut chunk = Chunk::new();
+        chunk.write_bytes(&bytes);
+        if module.is_entry_chunk(id) {
+            stripped.add_entry_chunk(chunk);
+        } else {
+            stripped.add_chunk(chunk);
+        }
+    }
+
+    for (name, id) in module.exports() {
+        let new_id = chunk_ids[id];
+        stripped.add_export(name.clone(), new_id);
+        if let Some(signature) = module.get_signature(*id) {
+            stripped.set_signature(new_id, types.remap_signature(signature));
+        }
+    }
+
+    for (name, data) in module.events() {
+        stripped.add_event(name.clone(), types.remap_type(data));
+    }
+
+    for (index, id) in module.migrations() {
+        if let Some(new_index) = struct_indexes.get(index) {
+            stripped.add_migration(*new_index, chunk_ids[id]);
+        }
+    }
+
+    if let Some(debug_info) = module.debug_info() {
+        let mut debug_info = debug_info.clone();
+        debug_info.remap_chunks(|id| chunk_ids.get(&id).copied());
+        stripped.set_debug_info(Some(debug_info));
+    }
+
+    stripped.set_features(stripped.used_features());
+
+    Ok(stripped)
+}
+
+#[cfg(test)]
+mod tests {
+    use xelis_builder::EnvironmentBuilder;
+    use xelis_lexer::Lexer;
+    use xelis_parser::Parser;
+    use crate::Compiler;
+    use super::*;
+
+    const CODE: &str = r#"
+        struct Unused {
+            a: u64
+        }
+
+        struct Point {
+            x: u64,
+            y: u64
+        }
+
+        enum Kind {
+            A,
+            B { p: Point }
+        }
+
+        fn unused_helper(a: u64) -> u64 {
+            let u: Unused = Unused { a: 12345 };
+            return u.a + a
+        }
+
+        fn double(a: u64) -> u64 {
+            return a * 2
+        }
+
+        pub fn area(p: Point) -> u64 {
+            return p.x * p.y
+        }
+
+        entry main() {
+            let p: Point = Point { x: 3, y: 777 };
+            return double(p.x) + area(p)
+        }
+    "#;
+
+    fn compile(code: &str, dead_code_elimination: bool) -> Module {
+        let tokens = Lexer::new(code).get().unwrap();
+        let environment = EnvironmentBuilder::default();
+        let (program, _) = Parser::new(tokens, &environment).parse().unwrap();
+        let mut compiler = Compiler::new(&program, environment.environment());
+        compiler.set_debug_info(true);
+        compiler.set_dead_code_elimination(dead_code_elimination);
+        compiler.compile().unwrap()
+    }
+
+    #[test]
+    fn test_unused_removed() {
+        let module = compile(CODE, false);
+        assert_eq!(module.chunks().len(), 4);
+        assert_eq!(module.constants().len(), 3);
+        assert_eq!(module.structs().len(), 2);
+        assert_eq!(module.enums().len(), 1);
+
+        let first_struct = module.structs()[0].id();
+        let stripped = compile(CODE, true);
+        assert_eq!(stripped.chunks().len(), 3);
+        assert_eq!(stripped.constants().len(), 2);
+        assert_eq!(stripped.enums().len(), 0);
+
+        // Point takes the id of the first struct
+        assert_eq!(stripped.structs().len(), 1);
+        let point = &stripped.structs()[0];
+        assert_eq!(point.id(), first_struct);
+        assert_eq!(point.fields(), &vec![Type::U64, Type::U64]);
+
+        // The calls and exports use the new chunk ids
+        assert_eq!(stripped.entry_by_name("main"), Some(2));
+        assert_eq!(stripped.get_export("area"), Some(1));
+        assert_eq!(stripped.get_signature(1).unwrap().parameters()[0].1, Type::Struct(point.clone()));
+        let entry = stripped.get_chunk_at(2).unwrap().get_instructions();
+        assert_eq!(&entry[0..3], &[OpCode::Constant.as_byte(), 1, 0]);
+        assert_eq!(&entry[11..14], &[OpCode::InvokeChunk.as_byte(), 0, 0]);
+        assert!(matches!(stripped.get_constant_at(1), Some(Constant::Struct(_, s)) if s.id() == first_struct));
+
+        let debug_info = stripped.debug_info().unwrap();
+        assert_eq!(debug_info.get_chunk_name(0), Some("double"));
+        assert_eq!(debug_info.get_chunk_name(2), Some("main"));
+    }
+
+    #[test]
+    fn test_unused_constants_removed() {
+        let mut module = Module::new();
+        module.add_constant(Value::U64(10));
+        module.add_constant(Value::U8(10));
+        module.add_constant(Value::U64(20));
+        module.add_constant(Value::U16(20));
+
+        // The u64 constants were replaced by their casted values
+        let mut chunk = Chunk::new();
+        chunk.emit_opcode(OpCode::Constant);
+        chunk.write_u16(1);
+        chunk.emit_opcode(OpCode::Constant);
+        chunk.write_u16(3);
+        chunk.emit_opcode(OpCode::Return);
+        module.add_entry_chunk(chunk);
+
+        remove_unused_constants(&mut module).unwrap();
+        let constants: Vec<_> = module.constants().iter().map(|c| c.0.clone()).collect();
+        assert_eq!(constants, vec![Value::U8(10).into(), Value::U16(20).into()]);
+        assert_eq!(module.get_chunk_at(0).unwrap().get_instructions(), &[
+            OpCode::Constant.as_byte(), 0, 0,
+            OpCode::Constant.as_byte(), 1, 0,
+            OpCode::Return.as_byte()
+        ]);
+    }
+
+    #[test]
+    fn test_types_reachable() {
+        // Kind is only used by the signature, and Point by one of its variants
+        let code = r#"
+            struct Point {
+                x: u64
+            }
+
+            enum Kind {
+                A,
+                B { p: Point }
+            }
+
+            fn unused() -> u64 {
+                return 5
+            }
+
+            pub fn is_a(kind: Kind) -> bool {
+                return kind == Kind::A
+            }
+        "#;
+
+        let stripped = compile(code, true);
+        assert_eq!(stripped.chunks().len(), 1);
+        assert_eq!(stripped.structs().len(), 1);
+        assert_eq!(stripped.enums().len(), 1);
+        assert_eq!(stripped.get_export("is_a"), Some(0));
+    }
+
+    #[test]
+    fn test_migrations_kept() {
+        let mut module = compile(CODE, false);
+        // Unused is migrated by main
+        assert!(module.add_migration(0, 3));
+
+        let stripped = eliminate_dead_code(module).unwrap();
+        assert_eq!(stripped.structs().len(), 2);
+        assert_eq!(stripped.structs()[0].fields(), &vec![Type::U64]);
+        assert_eq!(stripped.get_migration(0), Some(2));
+        assert_eq!(stripped.entry_by_name("main"), Some(2));
+    }
+}
@@ -1,5 +1,6 @@
 use thiserror::Error;
 use xelis_ast::Operator;
+use xelis_bytecode::OpCode;
 
 #[derive(Debug, Error)]
 pub enum CompilerError {
@@ -11,6 +12,8 @@ pub enum CompilerError {
     DuplicatedStruct(u16),
     #[error("duplicated enum {0}")]
     DuplicatedEnum(u16),
+    #[error("duplicated export '{0}'")]
+    DuplicatedExport(String),
     #[error("expected a 'break' statement")]
     ExpectedBreak,
     #[error("expected a 'continue' statement")]
@@ -43,4 +46,16 @@ pub enum CompilerError {
     TooMuchDanglingValueOnStack,
     #[error("expected a memory scope")]
     ExpectedMemoryScope,
+    #[error("invalid opcode {0}")]
+    InvalidOpCode(u8),
+    #[error("invalid arguments for opcode {0:?}")]
+    InvalidOpCodeArguments(OpCode),
+    #[error("invalid jump address {0}")]
+    InvalidJumpAddress(u32),
+    #[error("invalid jump target {0}")]
+    InvalidJumpTarget(usize),
+    #[error("chunk {0} not found")]
+    ChunkNotFound(usize),
+    #[error("constant {0} not found")]
+    ConstantNotFound(usize),
 }
\ No newline at end of file
@@ -1,4 +1,8 @@
 mod error;
+mod optimizer;
+mod cache;
+mod project;
+mod dead_code;
 
 use std::iter;
 use log::{trace, warn};
@@ -6,17 +10,31 @@ use xelis_ast::{
     Expression,
     FunctionType,
     Operator,
+    Parameter,
+    Span,
     Statement,
+    SwitchCase,
     Program
 };
-use xelis_environment::Environment;
-use xelis_bytecode::{Chunk, Module, OpCode};
+use xelis_environment::{Environment, NativeFunction};
+use xelis_bytecode::{Chunk, ChunkSignature, DebugInfo, Module, OpCode, SourceSpan};
+use xelis_types::{Constant, Type, Value, U256};
 
 pub use error::CompilerError;
+pub use cache::{CacheError, CompilationCache};
+pub use dead_code::{eliminate_dead_code, remove_unused_constants};
+pub use project::{ProjectBuilder, ProjectError};
+// Kept for the users of the compiler, the evaluation is shared with the parser
+pub use xelis_ast::try_fold_expr;
+pub use optimizer::*;
 
 // Temporary invalid address to patch jumps
 const INVALID_ADDR: u32 = 0xDEADBEEF;
 
+// Minimum count of values for a switch to use a jump table
+// Below it, comparing the value with each case is as fast
+const JUMP_TABLE_MIN_VALUES: usize = 3;
+
 pub struct Compiler<'a> {
     // Program to compile
     program: &'a Program,
@@ -36,6 +54,24 @@ pub struct Compiler<'a> {
     // and prevent any dangling values
     // Each element is a scope, where its elements are the index of each
     values_on_stack: Vec<Vec<usize>>,
+    // Evaluate constant expressions at compile time
+    constant_folding: bool,
+    // Passes applied on each compiled chunk
+    optimizer: Optimizer,
+    // Source mapping of the chunks, if enabled
+    debug_info: Option<DebugInfo>,
+    // Remove what is not reachable from the entries and exports
+    dead_code_elimination: bool,
+    // Spans of the statements of the current function
+    statements_spans: &'a [Span],
+    // Index of the next statement span to use
+    next_span: usize,
+    // Offset and span of each statement compiled in the current chunk
+    chunk_spans: Vec<(usize, Span)>,
+    // Keep the calls of the debug only functions, like the assertions
+    debug_assertions: bool,
+    // Checks of the invariants of the current function
+    invariants: &'a [Expression],
 }
 
 impl<'a> Compiler<'a> {
@@ -49,9 +85,62 @@ impl<'a> Compiler<'a> {
             loop_continue_patch: Vec::new(),
             memstore_ids: Vec::new(),
             values_on_stack: Vec::new(),
+            constant_folding: true,
+            optimizer: Optimizer::new(),
+            debug_info: None,
+            dead_code_elimination: false,
+            statements_spans: &[],
+            next_span: 0,
+            chunk_spans: Vec::new(),
+            debug_assertions: true,
+            invariants: &[],
         }
     }
 
+    // Set the optimizations level used during the compilation
+    // By default, only the constant folding is enabled
+    pub fn with_optimizations(mut self, level: OptimizationLevel) -> Self {
+        self.constant_folding = level >= OptimizationLevel::Basic;
+        self.optimizer = Optimizer::with_level(level);
+        self
+    }
+
+    // Get the optimizer to register custom passes
+    pub fn optimizer_mut(&mut self) -> &mut Optimizer {
+        &mut self.optimizer
+    }
+
+    // Enable or disable the constant folding pass
+    // When enabled, expressions like `2 + 3 * 4` are evaluated
+    // during the compilation and emitted as a single constant
+    pub fn set_constant_folding(&mut self, value: bool) {
+        self.constant_folding = value;
+    }
+
+    // Enable or disable the debug info generation
+    // When enabled, the module contains the source span of each statement
+    pub fn set_debug_info(&mut self, value: bool) {
+        self.debug_info = if value {
+            Some(DebugInfo::new())
+        } else {
+            None
+        };
+    }
+
+    // Enable or disable the dead code elimination
+    // When enabled, the functions, constants and types unused by the entries
+    // and exported functions are removed, the remaining chunks are renumbered
+    pub fn set_dead_code_elimination(&mut self, value: bool) {
+        self.dead_code_elimination = value;
+    }
+
+    // Enable or disable the debug assertions, enabled by default
+    // When disabled, the calls of the debug only functions like `assert` and the invariants
+    // of the functions are removed with their arguments, so they cost no gas in production
+    pub fn set_debug_assertions(&mut self, value: bool) {
+        self.debug_assertions = value;
+    }
+
     // Map the operator to the opcode
     fn map_operator_to_opcode(op: &Operator) -> Result<OpCode, CompilerError> {
         trace!("Mapping operator to opcode: {:?}", op);
@@ -63,12 +152,22 @@ impl<'a> Compiler<'a> {
             Operator::Mod => OpCode::Mod,
             Operator::Pow => OpCode::Pow,
 
-            Operator::BitwiseAnd => OpCode::And,
+            Operator::BitwiseAnd => OpCode::BitwiseAnd,
             Operator::BitwiseOr => OpCode::BitwiseOr,
             Operator::BitwiseXor => OpCode::BitwiseXor,
             Operator::BitwiseShl => OpCode::BitwiseShl,
             Operator::BitwiseShr => OpCode::BitwiseShr,
 
+            Operator::CheckedAdd => OpCode::CheckedAdd,
+            Operator::CheckedSub => OpCode::CheckedSub,
+            Operator::CheckedMul => OpCode::CheckedMul,
+            Operator::SaturatingAdd => OpCode::SaturatingAdd,
+            Operator::SaturatingSub => OpCode::SaturatingSub,
+            Operator::SaturatingMul => OpCode::SaturatingMul,
+            Operator::WrappingAdd => OpCode::WrappingAdd,
+            Operator::WrappingSub => OpCode::WrappingSub,
+            Operator::WrappingMul => OpCode::WrappingMul,
+
             Operator::Eq => OpCode::Eq,
             Operator::Neq => OpCode::Neg,
             Operator::Gt => OpCode::Gt,
@@ -131,19 +230,31 @@ impl<'a> Compiler<'a> {
         Ok(())
     }
 
+    // Emit a constant, same constants are shared in the module
+    fn emit_constant(&mut self, chunk: &mut Chunk, value: impl Into<Constant>) -> Result<(), CompilerError> {
+        let index = self.module.add_constant(value);
+        chunk.emit_opcode(OpCode::Constant);
+        chunk.write_u16(index as u16);
+
+        self.add_value_on_stack(chunk.last_index())
+    }
+
     // Compile the expression
     fn compile_expr(&mut self, chunk: &mut Chunk, expr: &Expression) -> Result<(), CompilerError> {
         trace!("Compiling expression: {:?}", expr);
+        if self.constant_folding && !matches!(expr, Expression::Constant(_)) {
+            if let Some(value) = try_fold_expr(expr) {
+                return self.emit_constant(chunk, value);
+            }
+        }
+
         match expr {
             Expression::Constant(v) => {
                 // Compile the value
-                let index = self.module.add_constant(v.clone());
-                chunk.emit_opcode(OpCode::Constant);
-                chunk.write_u16(index as u16);
-
-                self.add_value_on_stack(chunk.last_index())?;
+                self.emit_constant(chunk, v.clone())?;
             },
-            Expression::ArrayConstructor(exprs) => {
+            // A tuple is stored as an array
+            Expression::ArrayConstructor(exprs) | Expression::TupleConstructor(exprs) => {
                 if exprs.len() > u8::MAX as usize {
                     return Err(CompilerError::TooManyArrayValues(exprs.len()));
                 }
@@ -170,10 +281,10 @@ impl<'a> Compiler<'a> {
                 self.decrease_values_on_stack_by(exprs.len())?;
                 self.add_value_on_stack(chunk.last_index())?;
             },
-            Expression::RangeConstructor(min, max) => {
+            Expression::RangeConstructor(min, max, inclusive) => {
                 self.compile_expr(chunk, min)?;
                 self.compile_expr(chunk, max)?;
-                chunk.emit_opcode(OpCode::NewRange);
+                chunk.emit_opcode(if *inclusive { OpCode::NewRangeInclusive } else { OpCode::NewRange });
 
                 // Decrease and mark the last value
                 self.decrease_values_on_stack_by(2)?;
@@ -208,7 +319,56 @@ impl<'a> Compiler<'a> {
 
                 self.decrease_values_on_stack_by(exprs.len())?;
                 self.add_value_on_stack(chunk.last_index())?;
-            }
+            },
+            Expression::ResultConstructor(expr, enum_type, _) => {
+                self.compile_expr(chunk, expr)?;
+
+                chunk.emit_opcode(OpCode::NewEnum);
+                chunk.write_u16(enum_type.id());
+                chunk.write_u8(enum_type.variant_id());
+
+                self.decrease_values_on_stack()?;
+                self.add_value_on_stack(chunk.last_index())?;
+            },
+            Expression::Try(expr, is_err) => {
+                self.compile_expr(chunk, expr)?;
+
+                // Keep the result on the stack to return it or read its value
+                chunk.emit_opcode(OpCode::Copy);
+                chunk.emit_opcode(OpCode::SysCall);
+                chunk.write_u16(*is_err);
+                chunk.write_bool(true);
+                chunk.write_u8(0);
+
+                chunk.emit_opcode(OpCode::JumpIfFalse);
+                chunk.write_u32(INVALID_ADDR);
+                let jump_addr = chunk.last_index();
+
+                // Remove the values pushed before the result in the function
+                // so the error is the only value left for the caller
+                let below = self.values_on_stack.iter()
+                    .map(Vec::len)
+                    .sum::<usize>()
+                    .checked_sub(1)
+                    .ok_or(CompilerError::ExpectedValueOnStack)?;
+                for _ in 0..below {
+                    chunk.emit_opcode(OpCode::Swap);
+                    chunk.write_u8(1);
+                    chunk.emit_opcode(OpCode::Pop);
+                }
+                chunk.emit_opcode(OpCode::Return);
+
+                // Patch the jump if false
+                let jump_ok_addr = chunk.index();
+                chunk.patch_jump(jump_addr, jump_ok_addr as u32);
+
+                // The value is the only field of the Ok variant
+                chunk.emit_opcode(OpCode::SubLoad);
+                chunk.write_u8(0);
+
+                self.decrease_values_on_stack()?;
+                self.add_value_on_stack(chunk.last_index())?;
+            },
             Expression::Path(left, right) => {
                 // Compile the path
                 self.compile_expr(chunk, left)?;
@@ -227,6 +387,15 @@ impl<'a> Compiler<'a> {
 
                 self.add_value_on_stack(chunk.last_index())?;
             },
+            // The function is given to a native by its chunk id
+            Expression::FunctionReference(id) => {
+                let id = (*id as usize).checked_sub(self.environment.get_functions().len())
+                    .ok_or(CompilerError::ExpectedVariable)?;
+                chunk.emit_opcode(OpCode::ChunkId);
+                chunk.write_u16(id as u16);
+
+                self.add_value_on_stack(chunk.last_index())?;
+            },
             Expression::IsNot(expr) => {
                 self.compile_expr(chunk, expr)?;
                 chunk.emit_opcode(OpCode::Neg);
@@ -254,6 +423,9 @@ impl<'a> Compiler<'a> {
                 chunk.write_u32(INVALID_ADDR);
                 let jump_addr = chunk.last_index();
 
+                // One is used for the jump if false
+                self.decrease_values_on_stack()?;
+
                 // Compile the valid condition
                 self.compile_expr(chunk, valid)?;
 
@@ -262,6 +434,9 @@ impl<'a> Compiler<'a> {
                 chunk.write_u32(INVALID_ADDR);
                 let jump_valid_index = chunk.last_index();
 
+                // Only one of the values is pushed
+                self.decrease_values_on_stack()?;
+
                 // Patch the jump if false
                 let jump_false_addr = chunk.index();
                 chunk.patch_jump(jump_addr, jump_false_addr as u32);
@@ -273,8 +448,7 @@ impl<'a> Compiler<'a> {
                 let jump_valid_addr = chunk.index();
                 chunk.patch_jump(jump_valid_index, jump_valid_addr as u32);
 
-                // 1 for the condition, 1 for the valid, 1 for the invalid
-                self.decrease_values_on_stack_by(3)?;
+                self.decrease_values_on_stack()?;
                 self.add_value_on_stack(chunk.last_index())?;
             },
             Expression::Cast(expr, primitive_type) => {
@@ -447,7 +621,7 @@ impl<'a> Compiler<'a> {
 
                 // Reverse it, otherwise it will be shifted
                 for index in on_stack.into_iter().take(dangling).rev() {
-                    chunk.inject_opcode_at(OpCode::Pop, index + 1);
+                    self.inject_opcode_at(chunk, OpCode::Pop, index + 1)?;
                 }
             }
         }
@@ -455,6 +629,47 @@ impl<'a> Compiler<'a> {
         Ok(())
     }
 
+    // Inject an opcode in the chunk
+    // All the jumps addresses after the index are shifted
+    // and the jumps not yet patched are moved
+    fn inject_opcode_at(&mut self, chunk: &mut Chunk, op: OpCode, index: usize) -> Result<(), CompilerError> {
+        let mut patches = Vec::new();
+        let mut offset = 0;
+        while let Some(byte) = chunk.get_instruction_at(offset) {
+            let opcode = OpCode::from_byte(*byte)
+                .ok_or(CompilerError::InvalidOpCode(*byte))?;
+            let len = opcode.arguments_bytes();
+
+            if opcode.is_jump() {
+                let bytes = chunk.get_instructions_at(offset + 1, 4)
+                    .ok_or(CompilerError::InvalidOpCodeArguments(opcode))?;
+                let addr = u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]);
+                if addr != INVALID_ADDR && addr as usize >= index {
+                    patches.push((offset + 4, addr + 1));
+                }
+            }
+
+            offset += 1 + len;
+        }
+
+        for (last_index, addr) in patches {
+            chunk.patch_jump(last_index, addr);
+        }
+
+        for jumps in self.loop_break_patch.iter_mut().chain(self.loop_continue_patch.iter_mut()) {
+            for jump in jumps.iter_mut().filter(|v| **v >= index) {
+                *jump += 1;
+            }
+        }
+
+        for (offset, _) in self.chunk_spans.iter_mut().filter(|(v, _)| *v >= index) {
+            *offset += 1;
+        }
+
+        chunk.inject_opcode_at(op, index);
+        Ok(())
+    }
+
     // Pop the next register store id
     fn pop_mem_scope(&mut self, chunk: &mut Chunk) -> Result<(), CompilerError> {
         trace!("Popping memory scope");
@@ -489,14 +704,101 @@ impl<'a> Compiler<'a> {
         Ok(())
     }
 
+    // Get the first value and the index of the case of each value for a jump table
+    // The values of the cases must be contiguous integers
+    fn switch_jump_table(cases: &[SwitchCase]) -> Option<(&Value, Vec<usize>)> {
+        let mut values = cases.iter()
+            .enumerate()
+            .flat_map(|(i, case)| case.values.iter().map(move |v| (i, v)))
+            .map(|(i, v)| Some((v.clone().cast_to_u256().ok()?, v, i)))
+            .collect::<Option<Vec<_>>>()?;
+
+        if values.len() < JUMP_TABLE_MIN_VALUES || values.len() > u16::MAX as usize {
+            return None
+        }
+
+        // The values are unique, they are contiguous if they cover the distance between the bounds
+        values.sort_by_key(|(n, _, _)| *n);
+        let (first, value, _) = values[0];
+        let (last, _, _) = values[values.len() - 1];
+        if last.checked_sub(first)? != U256::from(values.len() as u64 - 1) {
+            return None
+        }
+
+        Some((value, values.into_iter().map(|(_, _, i)| i).collect()))
+    }
+
+    // Register the span of the next statement at the current chunk offset
+    fn record_statement_span(&mut self, chunk: &Chunk) {
+        if self.debug_info.is_none() {
+            return;
+        }
+
+        if let Some(span) = self.statements_spans.get(self.next_span) {
+            self.chunk_spans.push((chunk.index(), *span));
+        }
+        self.next_span += 1;
+    }
+
+    // Save the spans of the compiled chunk in the debug info
+    // Origins are used to remap the offsets changed by the optimizer
+    fn save_chunk_spans(&mut self, chunk_id: usize, origins: Option<Vec<(usize, usize)>>) {
+        let Some(debug_info) = self.debug_info.as_mut() else {
+            return;
+        };
+
+        for (offset, span) in self.chunk_spans.drain(..) {
+            let offset = match &origins {
+                // The statement starts at the first instruction kept after its offset
+                Some(origins) => match origins.iter().find(|(_, origin)| *origin >= offset) {
+                    Some((new_offset, _)) => *new_offset,
+                    None => continue
+                },
+                None => offset
+            };
+
+            debug_info.add_span(chunk_id, offset, SourceSpan {
+                line: span.line,
+                column_start: span.column_start,
+                column_end: span.column_end
+            });
+        }
+    }
+
+    // Is the expression a call of a debug only function removed without the debug assertions
+    fn is_removed_check(&self, expr: &Expression) -> bool {
+        !self.debug_assertions && matches!(
+            expr,
+            Expression::FunctionCall(_, id, _) if self.environment.get_functions()
+                .get(*id as usize)
+                .is_some_and(NativeFunction::is_debug_only)
+        )
+    }
+
+    // Check the invariants of the current function
+    fn compile_invariants(&mut self, chunk: &mut Chunk) -> Result<(), CompilerError> {
+        let invariants = self.invariants;
+        for invariant in invariants {
+            if !self.is_removed_check(invariant) {
+                self.compile_expr(chunk, invariant)?;
+            }
+        }
+
+        Ok(())
+    }
+
     // Compile the statements
     fn compile_statements(&mut self, chunk: &mut Chunk, statements: &[Statement]) -> Result<(), CompilerError> {
         trace!("Compiling statements: {:?}", statements);
         // Compile the statements
         for statement in statements {
+            self.record_statement_span(chunk);
             match statement {
-                Statement::Expression(expr) => self.compile_expr(chunk, expr)?,
+                Statement::Expression(expr) => if !self.is_removed_check(expr) {
+                    self.compile_expr(chunk, expr)?
+                },
                 Statement::Return(expr) => {
+                    self.compile_invariants(chunk)?;
                     if let Some(expr) = expr {
                         self.compile_expr(chunk, expr)?;
                         self.decrease_values_on_stack()?;
@@ -508,6 +810,22 @@ impl<'a> Compiler<'a> {
                     self.compile_expr(chunk, &declaration.value)?;
                     self.memstore(chunk)?;
                 },
+                Statement::TupleDestructuring(ids, expr) => {
+                    self.compile_expr(chunk, expr)?;
+                    chunk.emit_opcode(OpCode::Unpack);
+                    chunk.write_u8(ids.len() as u8);
+
+                    // The first value is on top of the stack, stored in the declared order
+                    self.decrease_values_on_stack()?;
+                    for _ in ids {
+                        self.add_value_on_stack(chunk.last_index())?;
+                    }
+                    for _ in ids {
+                        self.memstore(chunk)?;
+                    }
+                },
+                // The bindings are visible after the statement
+                Statement::Destructuring(statements) => self.compile_statements(chunk, statements)?,
                 Statement::Scope(statements) => {
                     self.push_mem_scope();
                     self.compile_statements(chunk, statements)?;
@@ -571,9 +889,12 @@ impl<'a> Compiler<'a> {
                     // One is used for the jump if false
                     self.decrease_values_on_stack()?;
 
+                    // Variables declared in the body are dropped after the loop
+                    self.push_mem_scope();
                     self.start_loop();
                     // Compile the valid condition
                     self.compile_statements(chunk, statements)?;
+                    self.pop_mem_scope(chunk)?;
 
                     // Jump back to the start
                     chunk.emit_opcode(OpCode::Jump);
@@ -662,6 +983,91 @@ impl<'a> Compiler<'a> {
 
                     self.end_loop(chunk, continue_index, jump_false_addr)?;
                 },
+                Statement::Switch(declaration, cases, default) => {
+                    self.push_mem_scope();
+                    self.compile_expr(chunk, &declaration.value)?;
+                    self.memstore(chunk)?;
+                    let value = Expression::Variable(declaration.id);
+
+                    // Jumps to patch with the address of each case
+                    let mut case_jumps = vec![Vec::new(); cases.len()];
+                    let default_jump = if let Some((first, table)) = Self::switch_jump_table(cases) {
+                        self.compile_expr(chunk, &value)?;
+                        chunk.emit_opcode(OpCode::JumpTable);
+                        chunk.write_u32(INVALID_ADDR);
+                        let default_jump = chunk.last_index();
+                        chunk.write_u16(self.module.add_constant(first.clone()) as u16);
+                        chunk.write_u16(table.len() as u16);
+
+                        // One is used for the jump table
+                        self.decrease_values_on_stack()?;
+
+                        // A jump per value, in the order of the values
+                        for case in table {
+                            chunk.emit_opcode(OpCode::Jump);
+                            chunk.write_u32(INVALID_ADDR);
+                            case_jumps[case].push(chunk.last_index());
+                        }
+
+                        default_jump
+                    } else {
+                        for (case, jumps) in cases.iter().zip(case_jumps.iter_mut()) {
+                            for v in case.values.iter() {
+                                // Jump to the case if the value is equal
+                                let condition = Expression::IsNot(Box::new(Expression::Operator(
+                                    Operator::Eq,
+                                    Box::new(value.clone()),
+                                    Box::new(Expression::Constant(v.clone().into()))
+                                )));
+                                self.compile_expr(chunk, &condition)?;
+                                chunk.emit_opcode(OpCode::JumpIfFalse);
+                                chunk.write_u32(INVALID_ADDR);
+                                jumps.push(chunk.last_index());
+
+                                // One is used for the jump if false
+                                self.decrease_values_on_stack()?;
+                            }
+                        }
+
+                        chunk.emit_opcode(OpCode::Jump);
+                        chunk.write_u32(INVALID_ADDR);
+                        chunk.last_index()
+                    };
+
+                    // The statements are written in the order of the cases
+                    // so a case without a jump at its end continues in the next one
+                    let mut end_jumps = Vec::new();
+                    for (case, jumps) in cases.iter().zip(case_jumps) {
+                        let addr = chunk.index() as u32;
+                        for jump in jumps {
+                            chunk.patch_jump(jump, addr);
+                        }
+
+                        self.push_mem_scope();
+                        self.compile_statements(chunk, &case.statements)?;
+                        self.pop_mem_scope(chunk)?;
+
+                        if !case.fallthrough {
+                            chunk.emit_opcode(OpCode::Jump);
+                            chunk.write_u32(INVALID_ADDR);
+                            end_jumps.push(chunk.last_index());
+                        }
+                    }
+
+                    chunk.patch_jump(default_jump, chunk.index() as u32);
+                    if let Some(statements) = default {
+                        self.push_mem_scope();
+                        self.compile_statements(chunk, statements)?;
+                        self.pop_mem_scope(chunk)?;
+                    }
+
+                    let end_addr = chunk.index() as u32;
+                    for jump in end_jumps {
+                        chunk.patch_jump(jump, end_addr);
+                    }
+
+                    self.pop_mem_scope(chunk)?;
+                },
                 Statement::Break => {
                     chunk.emit_opcode(OpCode::Jump);
                     chunk.write_u32(INVALID_ADDR);
@@ -685,10 +1091,14 @@ impl<'a> Compiler<'a> {
     }
 
     // Compile the function
-    fn compile_function(&mut self, function: &FunctionType) -> Result<(), CompilerError> {
+    fn compile_function(&mut self, function: &'a FunctionType) -> Result<(), CompilerError> {
         trace!("Compiling function: {:?}", function);
         let mut chunk = Chunk::new();
 
+        self.statements_spans = function.get_statements_spans();
+        self.next_span = 0;
+        self.chunk_spans.clear();
+
         // Push the new scope for ids
         self.push_mem_scope();
 
@@ -709,21 +1119,70 @@ impl<'a> Compiler<'a> {
             self.memstore(&mut chunk)?;
         }
 
+        self.invariants = function.get_invariants();
+        self.compile_invariants(&mut chunk)?;
         self.compile_statements(&mut chunk, function.get_statements())?;
+        // The end of the function is also a return
+        if !matches!(function.get_statements().last(), Some(Statement::Return(_))) {
+            self.compile_invariants(&mut chunk)?;
+        }
 
         // Pop the scope for ids
         self.pop_mem_scope(&mut chunk)?;
 
+        // Apply the peephole passes
+        let origins = self.optimizer.optimize_with_origins(&mut chunk, &mut self.module)?;
+        self.save_chunk_spans(self.module.chunks().len(), origins);
+        if let (Some(debug_info), Some(name)) = (self.debug_info.as_mut(), function.get_name()) {
+            debug_info.set_chunk_name(self.module.chunks().len(), name.to_owned());
+        }
+
         // Add the chunk to the module
-        if function.is_entry() {
-            self.module.add_entry_chunk(chunk);
-        } else {
-            self.module.add_chunk(chunk);
+        // Entry and public functions are also exported by name with their signature
+        let id = self.module.chunks().len();
+        match function {
+            FunctionType::Entry(entry) => {
+                let parameters = Self::named_parameters(entry.get_parameters_names(), entry.get_parameters());
+                self.export_chunk(entry.get_name(), id, ChunkSignature::new(parameters, Some(Type::U64)))?;
+                self.module.add_entry_chunk(chunk);
+            },
+            FunctionType::Declared(declared) if declared.is_public() => {
+                let parameters = Self::named_parameters(declared.get_parameters_names(), declared.get_parameters());
+                self.export_chunk(declared.get_name(), id, ChunkSignature::new(parameters, declared.get_return_type().clone()))?;
+                self.module.add_chunk(chunk);
+            },
+            _ => {
+                if function.is_test() {
+                    if let Some(name) = function.get_name() {
+                        self.module.add_test(name, id);
+                    }
+                }
+                self.module.add_chunk(chunk);
+            }
         }
 
         Ok(())
     }
 
+    // Pair the parameters with their names for a signature
+    fn named_parameters(names: &[String], parameters: &[Parameter]) -> Vec<(String, Type)> {
+        names.iter()
+            .zip(parameters)
+            .map(|(name, param)| (name.clone(), param.get_type().clone()))
+            .collect()
+    }
+
+    // Export a chunk by its name with its signature
+    fn export_chunk(&mut self, name: Option<&str>, id: usize, signature: ChunkSignature) -> Result<(), CompilerError> {
+        if let Some(name) = name {
+            if !self.module.add_export(name, id) {
+                return Err(CompilerError::DuplicatedExport(name.to_owned()));
+            }
+        }
+        self.module.set_signature(id, signature);
+        Ok(())
+    }
+
     // Compile the program
     pub fn compile(mut self) -> Result<Module, CompilerError> {
         // Include the structs created
@@ -741,6 +1200,10 @@ impl<'a> Compiler<'a> {
             }
         }
 
+        for (name, data) in self.program.events() {
+            self.module.add_event(name.clone(), data.clone());
+        }
+
         // Compile the program
         for function in self.program.functions() {
             self.compile_function(function)?;
@@ -763,6 +1226,19 @@ impl<'a> Compiler<'a> {
             return Err(CompilerError::MemoryStoreNotEmpty);
         }
 
+        // The passes may have replaced some constants
+        if !self.optimizer.is_empty() {
+            remove_unused_constants(&mut self.module)?;
+        }
+
+        self.module.set_debug_info(self.debug_info);
+        // Declare the features of the instructions emitted
+        self.module.set_features(self.module.used_features());
+
+        if self.dead_code_elimination {
+            return eliminate_dead_code(self.module)
+        }
+
         // Return the module
         Ok(self.module)
     }
@@ -878,7 +1354,8 @@ mod tests {
     #[test]
     fn test_simple_expression() {
         let (program, environment) = prepare_program("entry main() { return 1 + 2 }");
-        let compiler = Compiler::new(&program, &environment);
+        let mut compiler = Compiler::new(&program, &environment);
+        compiler.set_constant_folding(false);
         let module = compiler.compile().unwrap();
         assert_eq!(module.chunks().len(), 1);
         assert!(module.is_entry_chunk(0));
@@ -906,6 +1383,154 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_constant_folding() {
+        let (program, environment) = prepare_program("entry main() { return 2 + 3 * 4 }");
+        let compiler = Compiler::new(&program, &environment);
+        let module = compiler.compile().unwrap();
+        assert_eq!(module.constants().len(), 1);
+
+        assert_eq!(
+            module.get_constant_at(0),
+            Some(&Value::U64(14).into())
+        );
+
+        let chunk = module.get_chunk_at(0).unwrap();
+        assert_eq!(
+            chunk.get_instructions(),
+            &[
+                OpCode::Constant.as_byte(), 0, 0,
+                OpCode::Return.as_byte()
+            ]
+        );
+    }
+
+    #[test]
+    fn test_constant_folding_string_and_bool() {
+        let (program, environment) = prepare_program(r#"entry main() { let s: string = "a" + "b"; let b: bool = !(true && false) || false; return 0 }"#);
+        let compiler = Compiler::new(&program, &environment);
+        let module = compiler.compile().unwrap();
+
+        assert_eq!(
+            module.get_constant_at(0),
+            Some(&Value::String("ab".to_owned()).into())
+        );
+
+        assert_eq!(
+            module.get_constant_at(1),
+            Some(&Value::Boolean(true).into())
+        );
+    }
+
+    #[test]
+    fn test_constant_folding_dedup() {
+        let (program, environment) = prepare_program("entry main() { let a: u64 = 1 + 1; let b: u64 = 2; let c: u64 = a + (4 - 2); return a + b + c }");
+        let compiler = Compiler::new(&program, &environment);
+        let module = compiler.compile().unwrap();
+
+        // Only the value 2 is stored
+        assert_eq!(module.constants().len(), 1);
+    }
+
+    #[test]
+    fn test_constant_folding_runtime_error() {
+        // Division by zero must still happen at runtime
+        let (program, environment) = prepare_program("entry main() { return 1 / 0 }");
+        let compiler = Compiler::new(&program, &environment);
+        let module = compiler.compile().unwrap();
+
+        let chunk = module.get_chunk_at(0).unwrap();
+        assert_eq!(
+            chunk.get_instructions(),
+            &[
+                OpCode::Constant.as_byte(), 0, 0,
+                OpCode::Constant.as_byte(), 1, 0,
+                OpCode::Div.as_byte(),
+                OpCode::Return.as_byte()
+            ]
+        );
+    }
+
+    #[test]
+    fn test_constant_folding_overflow_operators() {
+        let (program, environment) = prepare_program("entry main() { let a: u8 = 250u8 +| 10u8; let b: u8 = 250u8 +% 10u8; return 0 }");
+        let compiler = Compiler::new(&program, &environment);
+        let module = compiler.compile().unwrap();
+
+        assert_eq!(module.get_constant_at(0), Some(&Value::U8(255).into()));
+        assert_eq!(module.get_constant_at(1), Some(&Value::U8(4).into()));
+
+        // Checked operators produce an optional at runtime
+        let (program, environment) = prepare_program("entry main() { let a: optional<u8> = 250u8 +? 10u8; return 0 }");
+        let compiler = Compiler::new(&program, &environment);
+        let module = compiler.compile().unwrap();
+
+        let chunk = module.get_chunk_at(0).unwrap();
+        assert_eq!(
+            &chunk.get_instructions()[..7],
+            &[
+                OpCode::Constant.as_byte(), 0, 0,
+                OpCode::Constant.as_byte(), 1, 0,
+                OpCode::CheckedAdd.as_byte(),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_full_optimizations() {
+        let (program, environment) = prepare_program("entry main() { let a: u64 = 5; a; foreach i in [1u8, 2u8] { return i as u64 } return 0 }");
+        let compiler = Compiler::new(&program, &environment)
+            .with_optimizations(OptimizationLevel::Full);
+        let module = compiler.compile().unwrap();
+
+        let chunk = module.get_chunk_at(0).unwrap();
+        assert_eq!(
+            chunk.get_instructions(),
+            &[
+                OpCode::Constant.as_byte(), 0, 0,
+                OpCode::MemorySet.as_byte(), 0, 0,
+                // MemoryLoad + Pop are removed
+                OpCode::Constant.as_byte(), 1, 0,
+                OpCode::Constant.as_byte(), 2, 0,
+                OpCode::NewArray.as_byte(), 2,
+                OpCode::IteratorBegin.as_byte(),
+                OpCode::IteratorNext.as_byte(), 28, 0, 0, 0,
+                OpCode::MemorySet.as_byte(), 1, 0,
+                // MemoryLoad + Cast are fused
+                OpCode::MemoryCast.as_byte(), 1, 0, 3,
+                OpCode::Return.as_byte(),
+                // Jump after the return is removed
+                OpCode::IteratorEnd.as_byte(),
+                OpCode::Constant.as_byte(), 3, 0,
+                OpCode::Return.as_byte()
+            ]
+        );
+    }
+
+    #[test]
+    fn test_custom_optimization_pass() {
+        struct ClearChunk;
+
+        impl OptimizationPass for ClearChunk {
+            fn name(&self) -> &str {
+                "clear-chunk"
+            }
+
+            fn run(&self, instructions: &mut Vec<Instruction>, _: &mut Module) -> bool {
+                let changed = !instructions.is_empty();
+                instructions.clear();
+                changed
+            }
+        }
+
+        let (program, environment) = prepare_program("entry main() { return 0 }");
+        let mut compiler = Compiler::new(&program, &environment);
+        compiler.optimizer_mut().add_pass(ClearChunk);
+        let module = compiler.compile().unwrap();
+
+        assert!(module.get_chunk_at(0).unwrap().get_instructions().is_empty());
+    }
+
     #[test]
     fn test_if() {
         let (program, environment) = prepare_program("entry main() { if true { return 0 } return 1 }");
@@ -1244,4 +1869,34 @@ mod tests {
             ]
         );
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_debug_info() {
+        let (program, environment) = prepare_program("entry main() { let a: u64 = 1; if a > 0 { a += 1; } return a }");
+        let mut compiler = Compiler::new(&program, &environment);
+        compiler.set_debug_info(true);
+        let module = compiler.compile().unwrap();
+
+        // One span per statement, in the order of the chunk
+        let spans = module.debug_info().unwrap().get_spans(0).unwrap();
+        assert_eq!(spans.len(), 4);
+        assert!(spans.windows(2).all(|w| w[0].0 < w[1].0));
+        assert_eq!(module.debug_info().unwrap().get_chunk_name(0), Some("main"));
+
+        // Disabled by default
+        let module = Compiler::new(&program, &environment).compile().unwrap();
+        assert!(module.debug_info().is_none());
+    }
+
+    #[test]
+    fn test_entry_exports() {
+        let (program, environment) = prepare_program("fn helper() -> u64 { return 1 } entry main() { return helper() } entry other() { return 2 }");
+        let module = Compiler::new(&program, &environment).compile().unwrap();
+
+        // Only the entry functions are exported
+        assert_eq!(module.exports().len(), 2);
+        assert_eq!(module.get_export("main"), Some(1));
+        assert_eq!(module.get_export("other"), Some(2));
+        assert_eq!(module.get_export("helper"), None);
+    }
+}
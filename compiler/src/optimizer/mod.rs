@@ -0,0 +1,319 @@
+mod passes;
+
+use std::collections::{HashMap, HashSet};
+use log::trace;
+use xelis_bytecode::{Chunk, Module, OpCode};
+
+use crate::CompilerError;
+
+pub use passes::*;
+
+// Maximum iterations of the full pipeline
+// A pass may enable another one, so we run them until nothing changes
+const MAX_ITERATIONS: usize = 8;
+
+// Level of optimizations applied by the compiler
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum OptimizationLevel {
+    // No optimization at all, the bytecode is emitted as is
+    None,
+    // Constant folding only
+    Basic,
+    // Constant folding and all the peephole passes
+    Full,
+}
+
+// A decoded instruction
+// Jumps addresses are converted to instruction indexes,
+// so passes can add or remove instructions without patching them
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Instruction {
+    pub opcode: OpCode,
+    // Arguments bytes, the jump address is excluded
+    pub args: Vec<u8>,
+    // Index of the targeted instruction for a jump
+    // It may be equal to the instructions len to jump at the end
+    pub target: Option<usize>,
+    // Offset of the instruction in the chunk before any pass
+    // None if the instruction was created by a pass
+    pub origin: Option<usize>,
+}
+
+impl Instruction {
+    // Create a new instruction
+    pub fn new(opcode: OpCode, args: Vec<u8>) -> Self {
+        Self {
+            opcode,
+            args,
+            target: None,
+            origin: None
+        }
+    }
+
+    // Read the u16 argument at the offset
+    pub fn read_u16(&self, offset: usize) -> Option<u16> {
+        let bytes = self.args.get(offset..offset + 2)?;
+        Some(u16::from_le_bytes([bytes[0], bytes[1]]))
+    }
+
+    // Is it the end of the current execution path
+    #[inline]
+    pub fn is_terminator(&self) -> bool {
+        matches!(self.opcode, OpCode::Return | OpCode::Jump)
+    }
+}
+
+// A pass rewriting the decoded instructions of a chunk
+// The module is provided to read or register constants
+pub trait OptimizationPass {
+    // Name of the pass, used for logging
+    fn name(&self) -> &str;
+
+    // Run the pass on the instructions
+    // Returns true if the instructions were changed
+    fn run(&self, instructions: &mut Vec<Instruction>, module: &mut Module) -> bool;
+}
+
+// Decode the instructions of a chunk
+pub fn decode_chunk(chunk: &Chunk) -> Result<Vec<Instruction>, CompilerError> {
+    let bytes = chunk.get_instructions();
+    let mut instructions = Vec::new();
+    let mut addresses = Vec::new();
+    let mut indexes = HashMap::new();
+
+    let mut offset = 0;
+    while offset < bytes.len() {
+        let byte = bytes[offset];
+        let opcode = OpCode::from_byte(byte)
+            .ok_or(CompilerError::InvalidOpCode(byte))?;
+
+        let len = opcode.arguments_bytes();
+        let args = bytes.get(offset + 1..offset + 1 + len)
+            .ok_or(CompilerError::InvalidOpCodeArguments(opcode))?;
+
+        indexes.insert(offset, instructions.len());
+        let mut instruction = if opcode.is_jump() {
+            addresses.push(Some(u32::from_le_bytes([args[0], args[1], args[2], args[3]])));
+            Instruction::new(opcode, args[4..].to_vec())
+        } else {
+            addresses.push(None);
+            Instruction::new(opcode, args.to_vec())
+        };
+        instruction.origin = Some(offset);
+        instructions.push(instruction);
+
+        offset += 1 + len;
+    }
+
+    // Jumping at the end is allowed
+    indexes.insert(offset, instructions.len());
+
+    for (instruction, addr) in instructions.iter_mut().zip(addresses) {
+        if let Some(addr) = addr {
+            let index = indexes.get(&(addr as usize))
+                .ok_or(CompilerError::InvalidJumpAddress(addr))?;
+            instruction.target = Some(*index);
+        }
+    }
+
+    Ok(instructions)
+}
+
+// Encode the instructions back to a chunk
+pub fn encode_chunk(instructions: &[Instruction]) -> Result<Chunk, CompilerError> {
+    // Compute the address of each instruction
+    let mut addresses = Vec::with_capacity(instructions.len() + 1);
+    let mut offset = 0;
+    for instruction in instructions {
+        addresses.push(offset as u32);
+        offset += 1 + instruction.args.len() + if instruction.target.is_some() { 4 } else { 0 };
+    }
+    addresses.push(offset as u32);
+
+    let mut chunk = Chunk::new();
+    for instruction in instructions {
+        chunk.emit_opcode(instruction.opcode);
+        if let Some(target) = instruction.target {
+            let addr = addresses.get(target)
+                .ok_or(CompilerError::InvalidJumpTarget(target))?;
+            chunk.write_u32(*addr);
+        }
+        chunk.write_bytes(&instruction.args);
+    }
+
+    Ok(chunk)
+}
+
+// Get all the instructions indexes targeted by a jump
+pub fn jump_targets(instructions: &[Instruction]) -> HashSet<usize> {
+    instructions.iter()
+        .filter_map(|i| i.target)
+        .collect()
+}
+
+// Get the indexes of the jumps following a JumpTable
+// The VM reaches them by their position, they must be kept in place
+pub fn jump_table_entries(instructions: &[Instruction]) -> HashSet<usize> {
+    instructions.iter()
+        .enumerate()
+        .filter(|(_, v)| v.opcode == OpCode::JumpTable)
+        .flat_map(|(i, v)| {
+            let len = v.read_u16(2).unwrap_or(0) as usize;
+            i + 1..i + 1 + len
+        })
+        .collect()
+}
+
+// Remove the instructions not marked to be kept
+// Jumps targets are updated to the next kept instruction
+pub fn retain_instructions(instructions: &mut Vec<Instruction>, keep: &[bool]) {
+    // New index of each instruction, with the end included
+    let mut indexes = Vec::with_capacity(instructions.len() + 1);
+    let mut next = 0;
+    for kept in keep.iter().copied().chain(std::iter::once(true)) {
+        indexes.push(next);
+        if kept {
+            next += 1;
+        }
+    }
+
+    let mut i = 0;
+    instructions.retain(|_| {
+        let kept = keep[i];
+        i += 1;
+        kept
+    });
+
+    for instruction in instructions.iter_mut() {
+        if let Some(target) = instruction.target.as_mut() {
+            *target = indexes[*target];
+        }
+    }
+}
+
+// Pipeline of passes applied on each compiled chunk
+pub struct Optimizer {
+    passes: Vec<Box<dyn OptimizationPass>>,
+}
+
+impl Optimizer {
+    // Create an optimizer without any pass
+    pub fn new() -> Self {
+        Self {
+            passes: Vec::new()
+        }
+    }
+
+    // Create an optimizer with the default passes of the level
+    pub fn with_level(level: OptimizationLevel) -> Self {
+        let mut optimizer = Self::new();
+        if level >= OptimizationLevel::Full {
+            optimizer.add_pass(RemoveUnreachableCode);
+            optimizer.add_pass(CollapseJumps);
+            optimizer.add_pass(RemovePushPop);
+            optimizer.add_pass(FuseConstantCast);
+            optimizer.add_pass(FuseInstructions);
+        }
+
+        optimizer
+    }
+
+    // Add a pass at the end of the pipeline
+    pub fn add_pass<P: OptimizationPass + 'static>(&mut self, pass: P) {
+        self.passes.push(Box::new(pass));
+    }
+
+    // Get the passes registered
+    pub fn passes(&self) -> &[Box<dyn OptimizationPass>] {
+        &self.passes
+    }
+
+    // Is there any pass to run
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.passes.is_empty()
+    }
+
+    // Run all the passes on the chunk until nothing changes
+    pub fn optimize(&self, chunk: &mut Chunk, module: &mut Module) -> Result<(), CompilerError> {
+        self.optimize_with_origins(chunk, module)
+            .map(|_| ())
+    }
+
+    // Run all the passes on the chunk until nothing changes
+    // Returns the new offset and the original offset of each instruction kept,
+    // or None if the chunk was not changed
+    pub fn optimize_with_origins(&self, chunk: &mut Chunk, module: &mut Module) -> Result<Option<Vec<(usize, usize)>>, CompilerError> {
+        if self.is_empty() {
+            return Ok(None)
+        }
+
+        let mut instructions = decode_chunk(chunk)?;
+        for _ in 0..MAX_ITERATIONS {
+            let mut changed = false;
+            for pass in self.passes.iter() {
+                if pass.run(&mut instructions, module) {
+                    trace!("Pass {} changed the chunk", pass.name());
+                    changed = true;
+                }
+            }
+
+            if !changed {
+                break;
+            }
+        }
+
+        *chunk = encode_chunk(&instructions)?;
+
+        let mut origins = Vec::with_capacity(instructions.len());
+        let mut offset = 0;
+        for instruction in instructions.iter() {
+            if let Some(origin) = instruction.origin {
+                origins.push((offset, origin));
+            }
+            offset += 1 + instruction.args.len() + if instruction.target.is_some() { 4 } else { 0 };
+        }
+
+        Ok(Some(origins))
+    }
+}
+
+impl Default for Optimizer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_decode_encode() {
+        let mut chunk = Chunk::new();
+        chunk.emit_opcode(OpCode::Constant);
+        chunk.write_u16(0);
+        chunk.emit_opcode(OpCode::JumpIfFalse);
+        chunk.write_u32(8);
+        chunk.emit_opcode(OpCode::Jump);
+        chunk.write_u32(0);
+        chunk.emit_opcode(OpCode::Return);
+
+        let instructions = decode_chunk(&chunk).unwrap();
+        assert_eq!(instructions.len(), 4);
+        assert_eq!(instructions[1].target, Some(2));
+        assert_eq!(instructions[2].target, Some(0));
+
+        let encoded = encode_chunk(&instructions).unwrap();
+        assert_eq!(encoded.get_instructions(), chunk.get_instructions());
+    }
+
+    #[test]
+    fn test_decode_invalid_jump() {
+        let mut chunk = Chunk::new();
+        chunk.emit_opcode(OpCode::Jump);
+        chunk.write_u32(2);
+
+        assert!(matches!(decode_chunk(&chunk), Err(CompilerError::InvalidJumpAddress(2))));
+    }
+}
@@ -0,0 +1,469 @@
+use xelis_bytecode::{Module, OpCode};
+use xelis_types::{Constant, ConstantWrapper, Type, Value, U256};
+use xelis_ast::execute_cast;
+
+use super::{jump_table_entries, jump_targets, retain_instructions, Instruction, OptimizationPass};
+
+// Remove the instructions that can't be reached
+// Everything after a Return or a Jump is dead until the next jump target
+// or the next entry of a jump table
+pub struct RemoveUnreachableCode;
+
+impl OptimizationPass for RemoveUnreachableCode {
+    fn name(&self) -> &str {
+        "remove-unreachable-code"
+    }
+
+    fn run(&self, instructions: &mut Vec<Instruction>, _: &mut Module) -> bool {
+        let targets = jump_targets(instructions);
+        let entries = jump_table_entries(instructions);
+        let mut keep = Vec::with_capacity(instructions.len());
+        let mut reachable = true;
+        for (i, instruction) in instructions.iter().enumerate() {
+            if targets.contains(&i) || entries.contains(&i) {
+                reachable = true;
+            }
+
+            keep.push(reachable);
+            if reachable && instruction.is_terminator() {
+                reachable = false;
+            }
+        }
+
+        if keep.iter().all(|v| *v) {
+            return false
+        }
+
+        retain_instructions(instructions, &keep);
+        true
+    }
+}
+
+// Redirect the jumps targeting an unconditional jump to its final target
+// and remove the jumps to the next instruction
+pub struct CollapseJumps;
+
+impl OptimizationPass for CollapseJumps {
+    fn name(&self) -> &str {
+        "collapse-jumps"
+    }
+
+    fn run(&self, instructions: &mut Vec<Instruction>, _: &mut Module) -> bool {
+        let mut changed = false;
+        for i in 0..instructions.len() {
+            let Some(mut target) = instructions[i].target else {
+                continue
+            };
+
+            // Limit the hops to prevent any infinite loop
+            let mut hops = 0;
+            while let Some(next) = instructions.get(target)
+                .filter(|v| v.opcode == OpCode::Jump)
+                .and_then(|v| v.target)
+            {
+                if next == target || hops >= instructions.len() {
+                    break;
+                }
+
+                target = next;
+                hops += 1;
+            }
+
+            if instructions[i].target != Some(target) {
+                instructions[i].target = Some(target);
+                changed = true;
+            }
+        }
+
+        // The entries of a jump table are kept even if they jump to the next instruction
+        let entries = jump_table_entries(instructions);
+        let keep = instructions.iter()
+            .enumerate()
+            .map(|(i, v)| !(v.opcode == OpCode::Jump && v.target == Some(i + 1)) || entries.contains(&i))
+            .collect::<Vec<_>>();
+
+        if keep.iter().any(|v| !*v) {
+            retain_instructions(instructions, &keep);
+            changed = true;
+        }
+
+        changed
+    }
+}
+
+// Remove a value pushed on the stack and directly popped
+pub struct RemovePushPop;
+
+impl OptimizationPass for RemovePushPop {
+    fn name(&self) -> &str {
+        "remove-push-pop"
+    }
+
+    fn run(&self, instructions: &mut Vec<Instruction>, _: &mut Module) -> bool {
+        let targets = jump_targets(instructions);
+        let mut keep = vec![true; instructions.len()];
+        let mut changed = false;
+
+        let mut i = 0;
+        while i + 1 < instructions.len() {
+            let is_push = matches!(instructions[i].opcode, OpCode::Constant | OpCode::MemoryLoad | OpCode::Copy);
+            // The Pop must not be reachable from elsewhere
+            if is_push && instructions[i + 1].opcode == OpCode::Pop && !targets.contains(&(i + 1)) {
+                keep[i] = false;
+                keep[i + 1] = false;
+                changed = true;
+                i += 2;
+            } else {
+                i += 1;
+            }
+        }
+
+        if changed {
+            retain_instructions(instructions, &keep);
+        }
+
+        changed
+    }
+}
+
+// Cast the constant at compile time when it is followed by a Cast
+pub struct FuseConstantCast;
+
+impl OptimizationPass for FuseConstantCast {
+    fn name(&self) -> &str {
+        "fuse-constant-cast"
+    }
+
+    fn run(&self, instructions: &mut Vec<Instruction>, module: &mut Module) -> bool {
+        let targets = jump_targets(instructions);
+        let mut keep = vec![true; instructions.len()];
+        let mut changed = false;
+
+        for i in 0..instructions.len().saturating_sub(1) {
+            if instructions[i].opcode != OpCode::Constant
+                || instructions[i + 1].opcode != OpCode::Cast
+                || targets.contains(&(i + 1))
+                || !keep[i]
+            {
+                continue;
+            }
+
+            let Some(value) = instructions[i].read_u16(0)
+                .and_then(|id| module.get_constant_at(id as usize))
+                .and_then(|v| v.as_value().ok())
+                .cloned()
+            else {
+                continue
+            };
+
+            let Some(casted) = instructions[i + 1].args.first()
+                .and_then(|v| Type::primitive_type_from_byte(*v))
+                .and_then(|t| execute_cast(value, &t))
+            else {
+                continue
+            };
+
+            // Reuse the constant if declared, the ids read by Constant are u16
+            let casted = Constant::Default(casted);
+            let index = match module.constants().get_index_of(&ConstantWrapper(casted.clone())) {
+                Some(index) => index,
+                None if module.constants().len() <= u16::MAX as usize => module.add_constant(casted),
+                None => continue
+            };
+
+            instructions[i].args = (index as u16).to_le_bytes().to_vec();
+            keep[i + 1] = false;
+            changed = true;
+        }
+
+        if changed {
+            retain_instructions(instructions, &keep);
+        }
+
+        changed
+    }
+}
+
+// Fuse the sequences dominating the loop bodies into superinstructions
+// to reduce the instructions dispatched by the VM
+pub struct FuseInstructions;
+
+impl FuseInstructions {
+    // Is the constant an unsigned integer equal to 1
+    fn is_one(module: &Module, id: Option<u16>) -> bool {
+        let Some(value) = id.and_then(|id| module.get_constant_at(id as usize))
+            .and_then(|v| v.as_value().ok())
+        else {
+            return false
+        };
+
+        matches!(
+            value,
+            Value::U8(1) | Value::U16(1) | Value::U32(1) | Value::U64(1) | Value::U128(1)
+        ) || matches!(value, Value::U256(v) if *v == U256::ONE)
+    }
+}
+
+impl OptimizationPass for FuseInstructions {
+    fn name(&self) -> &str {
+        "fuse-instructions"
+    }
+
+    fn run(&self, instructions: &mut Vec<Instruction>, module: &mut Module) -> bool {
+        let targets = jump_targets(instructions);
+        let mut keep = vec![true; instructions.len()];
+        let mut changed = false;
+
+        let mut i = 0;
+        while i < instructions.len() {
+            // Only the first instruction of a sequence can be jumped to
+            let next = |n: usize| instructions.get(i + n)
+                .filter(|_| !targets.contains(&(i + n)))
+                .map(|v| v.opcode);
+
+            let fused = match (instructions[i].opcode, next(1), next(2)) {
+                // MemoryLoad a, MemoryLoad b, Add => MemoryAdd a b
+                (OpCode::MemoryLoad, Some(OpCode::MemoryLoad), Some(OpCode::Add)) => {
+                    let mut args = instructions[i].args.clone();
+                    args.extend_from_slice(&instructions[i + 1].args);
+                    Some((OpCode::MemoryAdd, args, None, 3))
+                },
+                // MemoryLoad a, Constant 1, AssignAdd => MemoryInc a
+                (OpCode::MemoryLoad, Some(OpCode::Constant), Some(OpCode::AssignAdd))
+                    if Self::is_one(module, instructions[i + 1].read_u16(0)) => {
+                    Some((OpCode::MemoryInc, instructions[i].args.clone(), None, 3))
+                },
+                // MemoryLoad a, Cast t => MemoryCast a t
+                (OpCode::MemoryLoad, Some(OpCode::Cast), _) => {
+                    let mut args = instructions[i].args.clone();
+                    args.extend_from_slice(&instructions[i + 1].args);
+                    Some((OpCode::MemoryCast, args, None, 2))
+                },
+                // Comparison, JumpIfFalse => fused conditional jump
+                (opcode, Some(OpCode::JumpIfFalse), _) => opcode.as_compare_jump()
+                    .map(|v| (v, Vec::new(), instructions[i + 1].target, 2)),
+                _ => None
+            };
+
+            let Some((opcode, args, target, len)) = fused else {
+                i += 1;
+                continue
+            };
+
+            let instruction = &mut instructions[i];
+            instruction.opcode = opcode;
+            instruction.args = args;
+            instruction.target = target;
+            keep[i + 1..i + len].fill(false);
+
+            changed = true;
+            i += len;
+        }
+
+        if changed {
+            retain_instructions(instructions, &keep);
+        }
+
+        changed
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn jump(opcode: OpCode, target: usize) -> Instruction {
+        Instruction {
+            opcode,
+            args: Vec::new(),
+            target: Some(target),
+            origin: None
+        }
+    }
+
+    #[test]
+    fn test_remove_unreachable_code() {
+        let mut instructions = vec![
+            Instruction::new(OpCode::Return, Vec::new()),
+            Instruction::new(OpCode::Pop, Vec::new()),
+            Instruction::new(OpCode::Return, Vec::new()),
+        ];
+
+        assert!(RemoveUnreachableCode.run(&mut instructions, &mut Module::new()));
+        assert_eq!(instructions, vec![Instruction::new(OpCode::Return, Vec::new())]);
+    }
+
+    #[test]
+    fn test_collapse_jumps() {
+        let mut instructions = vec![
+            jump(OpCode::JumpIfFalse, 2),
+            Instruction::new(OpCode::Return, Vec::new()),
+            jump(OpCode::Jump, 3),
+            Instruction::new(OpCode::Return, Vec::new()),
+        ];
+
+        assert!(CollapseJumps.run(&mut instructions, &mut Module::new()));
+        // The jump to the next instruction is removed
+        assert_eq!(instructions, vec![
+            jump(OpCode::JumpIfFalse, 2),
+            Instruction::new(OpCode::Return, Vec::new()),
+            Instruction::new(OpCode::Return, Vec::new()),
+        ]);
+    }
+
+    #[test]
+    fn test_jump_table_entries_kept() {
+        let mut table = jump(OpCode::JumpTable, 4);
+        table.args = vec![0, 0, 2, 0];
+        let mut instructions = vec![
+            table,
+            jump(OpCode::Jump, 4),
+            jump(OpCode::Jump, 3),
+            Instruction::new(OpCode::Pop, Vec::new()),
+            Instruction::new(OpCode::Return, Vec::new()),
+        ];
+        let expected = instructions.clone();
+
+        // The second entry follows a jump and jumps to the next instruction
+        assert!(!RemoveUnreachableCode.run(&mut instructions, &mut Module::new()));
+        assert!(!CollapseJumps.run(&mut instructions, &mut Module::new()));
+        assert_eq!(instructions, expected);
+    }
+
+    #[test]
+    fn test_remove_push_pop() {
+        let mut instructions = vec![
+            Instruction::new(OpCode::Constant, vec![0, 0]),
+            Instruction::new(OpCode::Pop, Vec::new()),
+            Instruction::new(OpCode::Return, Vec::new()),
+        ];
+
+        assert!(RemovePushPop.run(&mut instructions, &mut Module::new()));
+        assert_eq!(instructions, vec![Instruction::new(OpCode::Return, Vec::new())]);
+    }
+
+    #[test]
+    fn test_fuse_constant_cast() {
+        let mut module = Module::new();
+        module.add_constant(Value::U64(10));
+
+        let mut instructions = vec![
+            Instruction::new(OpCode::Constant, vec![0, 0]),
+            Instruction::new(OpCode::Cast, vec![Type::U8.primitive_byte().unwrap()]),
+            Instruction::new(OpCode::Return, Vec::new()),
+        ];
+
+        assert!(FuseConstantCast.run(&mut instructions, &mut module));
+        assert_eq!(instructions, vec![
+            Instruction::new(OpCode::Constant, vec![1, 0]),
+            Instruction::new(OpCode::Return, Vec::new()),
+        ]);
+        assert_eq!(module.get_constant_at(1), Some(&Value::U8(10).into()));
+
+        // The casted value is already declared
+        let mut instructions = vec![
+            Instruction::new(OpCode::Constant, vec![0, 0]),
+            Instruction::new(OpCode::Cast, vec![Type::U8.primitive_byte().unwrap()]),
+        ];
+
+        assert!(FuseConstantCast.run(&mut instructions, &mut module));
+        assert_eq!(instructions, vec![Instruction::new(OpCode::Constant, vec![1, 0])]);
+        assert_eq!(module.constants().len(), 2);
+    }
+
+    #[test]
+    fn test_fuse_constant_cast_full_constants() {
+        let mut module = Module::new();
+        for i in 0..=u16::MAX as u64 {
+            module.add_constant(Value::U64(i));
+        }
+
+        // No id left for the casted value
+        let mut instructions = vec![
+            Instruction::new(OpCode::Constant, vec![10, 0]),
+            Instruction::new(OpCode::Cast, vec![Type::U8.primitive_byte().unwrap()]),
+        ];
+
+        let expected = instructions.clone();
+        assert!(!FuseConstantCast.run(&mut instructions, &mut module));
+        assert_eq!(instructions, expected);
+        assert_eq!(module.constants().len(), u16::MAX as usize + 1);
+    }
+
+    #[test]
+    fn test_fuse_instructions() {
+        let mut module = Module::new();
+        module.add_constant(Value::U64(1));
+
+        let mut instructions = vec![
+            // Loop condition targeted by the jump back
+            Instruction::new(OpCode::MemoryLoad, vec![0, 0]),
+            Instruction::new(OpCode::Constant, vec![0, 0]),
+            Instruction::new(OpCode::Lt, Vec::new()),
+            jump(OpCode::JumpIfFalse, 13),
+            // sum = sum + i
+            Instruction::new(OpCode::MemoryLoad, vec![1, 0]),
+            Instruction::new(OpCode::MemoryLoad, vec![1, 0]),
+            Instruction::new(OpCode::MemoryLoad, vec![0, 0]),
+            Instruction::new(OpCode::Add, Vec::new()),
+            Instruction::new(OpCode::Assign, Vec::new()),
+            // i += 1
+            Instruction::new(OpCode::MemoryLoad, vec![0, 0]),
+            Instruction::new(OpCode::Constant, vec![0, 0]),
+            Instruction::new(OpCode::AssignAdd, Vec::new()),
+            jump(OpCode::Jump, 0),
+        ];
+
+        assert!(FuseInstructions.run(&mut instructions, &mut module));
+        assert_eq!(instructions, vec![
+            Instruction::new(OpCode::MemoryLoad, vec![0, 0]),
+            Instruction::new(OpCode::Constant, vec![0, 0]),
+            jump(OpCode::LtJumpIfFalse, 8),
+            Instruction::new(OpCode::MemoryLoad, vec![1, 0]),
+            Instruction::new(OpCode::MemoryAdd, vec![1, 0, 0, 0]),
+            Instruction::new(OpCode::Assign, Vec::new()),
+            Instruction::new(OpCode::MemoryInc, vec![0, 0]),
+            jump(OpCode::Jump, 0),
+        ]);
+
+        // Nothing left to fuse
+        assert!(!FuseInstructions.run(&mut instructions, &mut module));
+    }
+
+    #[test]
+    fn test_fuse_memory_cast() {
+        let mut module = Module::new();
+        let mut instructions = vec![
+            Instruction::new(OpCode::MemoryLoad, vec![2, 0]),
+            Instruction::new(OpCode::Cast, vec![Type::U64.primitive_byte().unwrap()]),
+            Instruction::new(OpCode::Return, Vec::new()),
+        ];
+
+        assert!(FuseInstructions.run(&mut instructions, &mut module));
+        assert_eq!(instructions, vec![
+            Instruction::new(OpCode::MemoryCast, vec![2, 0, Type::U64.primitive_byte().unwrap()]),
+            Instruction::new(OpCode::Return, Vec::new()),
+        ]);
+    }
+
+    #[test]
+    fn test_fuse_instructions_jump_target() {
+        let mut module = Module::new();
+        module.add_constant(Value::U64(2));
+
+        // The Add is jumped to, and the constant isn't 1
+        let mut instructions = vec![
+            jump(OpCode::JumpIfFalse, 3),
+            Instruction::new(OpCode::MemoryLoad, vec![0, 0]),
+            Instruction::new(OpCode::MemoryLoad, vec![1, 0]),
+            Instruction::new(OpCode::Add, Vec::new()),
+            Instruction::new(OpCode::MemoryLoad, vec![0, 0]),
+            Instruction::new(OpCode::Constant, vec![0, 0]),
+            Instruction::new(OpCode::AssignAdd, Vec::new()),
+        ];
+
+        let expected = instructions.clone();
+        assert!(!FuseInstructions.run(&mut instructions, &mut module));
+        assert_eq!(instructions, expected);
+    }
+}
@@ -0,0 +1,178 @@
+use std::iter;
+use thiserror::Error;
+use xelis_builder::EnvironmentBuilder;
+use xelis_bytecode::Module;
+use xelis_lexer::{Lexer, LexerError};
+use xelis_parser::{Parser, ParserError};
+
+use crate::{Compiler, CompilerError, OptimizationLevel};
+
+#[derive(Debug, Error)]
+pub enum ProjectError<'a> {
+    #[error("duplicated file '{0}'")]
+    DuplicatedFile(&'a str),
+    #[error("{file}: {error}")]
+    Lexer {
+        file: &'a str,
+        error: LexerError
+    },
+    #[error("{file}: {error}")]
+    Parser {
+        file: &'a str,
+        // Boxed as the parser error is large
+        error: Box<ParserError<'a>>
+    },
+    // The program of all the files is compiled at once
+    #[error(transparent)]
+    Compiler(#[from] CompilerError),
+}
+
+impl ProjectError<'_> {
+    // Name of the file where the error happened
+    pub fn file(&self) -> Option<&str> {
+        match self {
+            Self::DuplicatedFile(file)
+            | Self::Lexer { file, .. }
+            | Self::Parser { file, .. } => Some(file),
+            Self::Compiler(_) => None
+        }
+    }
+}
+
+// Build a single module from several named sources
+// The files are read in their order of addition and share the declarations:
+// like the declarations above it in a single file, a file sees the structs, enums,
+// constants and functions of the files added before it
+pub struct ProjectBuilder<'a> {
+    environment: &'a EnvironmentBuilder<'a>,
+    files: Vec<(&'a str, &'a str)>,
+    level: OptimizationLevel,
+}
+
+impl<'a> ProjectBuilder<'a> {
+    pub fn new(environment: &'a EnvironmentBuilder<'a>) -> Self {
+        Self {
+            environment,
+            files: Vec::new(),
+            level: OptimizationLevel::Basic,
+        }
+    }
+
+    // Set the optimizations level used to compile the module
+    pub fn with_optimizations(mut self, level: OptimizationLevel) -> Self {
+        self.level = level;
+        self
+    }
+
+    // Add a source after the previous ones
+    pub fn with_file(mut self, name: &'a str, source: &'a str) -> Self {
+        self.add_file(name, source);
+        self
+    }
+
+    pub fn add_file(&mut self, name: &'a str, source: &'a str) {
+        self.files.push((name, source));
+    }
+
+    // Get the names of the files in their order
+    pub fn files(&self) -> impl Iterator<Item = &str> {
+        self.files.iter().map(|(name, _)| *name)
+    }
+
+    // Parse all the files and compile them into a module
+    pub fn build(self) -> Result<Module, ProjectError<'a>> {
+        let mut parser = Parser::with(iter::empty(), self.environment);
+        for (i, (file, source)) in self.files.iter().enumerate() {
+            if self.files[..i].iter().any(|(name, _)| name == file) {
+                return Err(ProjectError::DuplicatedFile(file));
+            }
+
+            let tokens = Lexer::new(source).collect::<Result<Vec<_>, _>>()
+                .map_err(|error| ProjectError::Lexer { file, error })?;
+
+            parser.parse_source(tokens)
+                .map_err(|error| ProjectError::Parser { file, error: Box::new(error) })?;
+        }
+
+        let (program, _, _) = parser.into_program();
+        let module = Compiler::new(&program, self.environment.environment())
+            .with_optimizations(self.level)
+            .compile()?;
+
+        Ok(module)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use xelis_parser::ParserErrorKind;
+    use super::*;
+
+    const TYPES: &str = r#"
+        struct Point {
+            x: u64,
+            y: u64
+        }
+
+        const ORIGIN: u64 = 100
+
+        fn (p Point) sum() -> u64 {
+            return p.x + p.y
+        }
+    "#;
+
+    const MAIN: &str = r#"
+        entry main() {
+            let p: Point = Point { x: 1, y: 2 };
+            return p.sum() + ORIGIN
+        }
+    "#;
+
+    #[test]
+    fn test_build() {
+        let environment = EnvironmentBuilder::default();
+        let module = ProjectBuilder::new(&environment)
+            .with_file("types.xel", TYPES)
+            .with_file("main.xel", MAIN)
+            .build()
+            .unwrap();
+
+        assert_eq!(module.structs().len(), 1);
+        assert_eq!(module.chunks().len(), 2);
+        assert_eq!(module.entry_by_name("main"), Some(1));
+    }
+
+    #[test]
+    fn test_file_errors() {
+        let environment = EnvironmentBuilder::default();
+
+        // The declarations of the next files are not visible
+        let err = ProjectBuilder::new(&environment)
+            .with_file("main.xel", MAIN)
+            .with_file("types.xel", TYPES)
+            .build()
+            .unwrap_err();
+        assert_eq!(err.file(), Some("main.xel"));
+
+        let err = ProjectBuilder::new(&environment)
+            .with_file("types.xel", TYPES)
+            .with_file("other.xel", "struct Point { z: u8 }")
+            .build()
+            .unwrap_err();
+        assert!(matches!(err, ProjectError::Parser { file: "other.xel", ref error } if matches!(error.kind, ParserErrorKind::TypeNameAlreadyUsed("Point"))), "{}", err);
+
+        let err = ProjectBuilder::new(&environment)
+            .with_file("types.xel", TYPES)
+            .with_file("broken.xel", "entry main() { return \"a }")
+            .build()
+            .unwrap_err();
+        assert!(matches!(err, ProjectError::Lexer { file: "broken.xel", .. }), "{}", err);
+
+        let err = ProjectBuilder::new(&environment)
+            .with_file("types.xel", TYPES)
+            .with_file("types.xel", MAIN)
+            .build()
+            .unwrap_err();
+        assert!(matches!(err, ProjectError::DuplicatedFile("types.xel")));
+    }
+}
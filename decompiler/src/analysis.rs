@@ -0,0 +1,215 @@
+use std::collections::HashMap;
+use xelis_bytecode::{Chunk, OpCode};
+
+use super::DecompilerError;
+
+// A decoded instruction with its raw arguments
+#[derive(Debug, Clone)]
+pub struct Instruction {
+    pub offset: usize,
+    pub opcode: OpCode,
+    pub args: Vec<u8>,
+}
+
+impl Instruction {
+    pub fn u8(&self, index: usize) -> u8 {
+        self.args[index]
+    }
+
+    pub fn u16(&self, index: usize) -> u16 {
+        u16::from_le_bytes([self.args[index], self.args[index + 1]])
+    }
+
+    pub fn u32(&self, index: usize) -> u32 {
+        u32::from_le_bytes([self.args[index], self.args[index + 1], self.args[index + 2], self.args[index + 3]])
+    }
+
+    // Address read by a jump
+    pub fn target(&self) -> Option<usize> {
+        self.opcode.is_jump().then(|| self.u32(0) as usize)
+    }
+
+    // Jump taken when the value popped is false, the fused comparisons included
+    pub fn is_conditional_jump(&self) -> bool {
+        matches!(
+            self.opcode,
+            OpCode::JumpIfFalse
+            | OpCode::LtJumpIfFalse
+            | OpCode::LteJumpIfFalse
+            | OpCode::GtJumpIfFalse
+            | OpCode::GteJumpIfFalse
+        )
+    }
+
+    // The next instruction is never executed after this one
+    pub fn ends_flow(&self) -> bool {
+        matches!(self.opcode, OpCode::Return | OpCode::Jump | OpCode::JumpTable)
+    }
+}
+
+// Decode all the instructions of a chunk
+pub fn decode(id: usize, chunk: &Chunk) -> Result<Vec<Instruction>, DecompilerError> {
+    let bytes = chunk.get_instructions();
+    let mut instructions = Vec::new();
+    let mut offset = 0;
+    while let Some(byte) = bytes.get(offset) {
+        let opcode = OpCode::from_byte(*byte)
+            .ok_or(DecompilerError::InvalidOpCode { chunk: id, offset })?;
+
+        let len = opcode.arguments_bytes();
+        let args = bytes.get(offset + 1..offset + 1 + len)
+            .ok_or(DecompilerError::InvalidOpCode { chunk: id, offset })?;
+
+        instructions.push(Instruction {
+            offset,
+            opcode,
+            args: args.to_vec()
+        });
+        offset += 1 + len;
+    }
+
+    Ok(instructions)
+}
+
+// Count of parameters stored by the chunk before its statements
+// The compiler stores them in the first registers, a statement always pushes a value first
+pub fn parameters_count(instructions: &[Instruction]) -> usize {
+    instructions.iter()
+        .enumerate()
+        .take_while(|(i, instruction)| instruction.opcode == OpCode::MemorySet && instruction.u16(0) as usize == *i)
+        .count()
+}
+
+// What the instructions depending on the module and the environment push and pop
+#[derive(Debug, Default)]
+pub struct Effects {
+    // Does each native function return a value
+    pub natives: Vec<bool>,
+    // Does each chunk return a value
+    pub chunks: Vec<bool>,
+    // Count of fields of each struct by id, the environment ones first
+    pub structs: Vec<usize>,
+    // Count of fields of each variant of each enum by id
+    pub enums: HashMap<u16, Vec<usize>>,
+}
+
+impl Effects {
+    // Count of values popped and pushed by an instruction
+    pub fn of(&self, instruction: &Instruction) -> (usize, usize) {
+        match instruction.opcode {
+            OpCode::Constant
+            | OpCode::MemoryLoad
+            | OpCode::CopyN
+            | OpCode::MemoryAdd
+            | OpCode::MemoryCast
+            | OpCode::ChunkId => (0, 1),
+            OpCode::MemorySet
+            | OpCode::Pop
+            | OpCode::JumpIfFalse
+            | OpCode::IteratorBegin
+            | OpCode::JumpTable => (1, 0),
+            OpCode::PopN => (instruction.u8(0) as usize, 0),
+            OpCode::Copy => (1, 2),
+            OpCode::Swap
+            | OpCode::Swap2
+            | OpCode::Jump
+            | OpCode::IteratorNext
+            | OpCode::IteratorEnd
+            | OpCode::Return
+            | OpCode::MemoryInc => (0, 0),
+            OpCode::SubLoad
+            | OpCode::IterableLength
+            | OpCode::Cast
+            | OpCode::CallPrecompile
+            | OpCode::Neg
+            | OpCode::Inc
+            | OpCode::Dec => (1, 1),
+            OpCode::InvokeChunk | OpCode::SysCall => {
+                let id = instruction.u16(0) as usize;
+                let on_value = instruction.u8(2) != 0;
+                let args = instruction.u8(3) as usize + on_value as usize;
+                let returns = if instruction.opcode == OpCode::InvokeChunk {
+                    self.chunks.get(id)
+                } else {
+                    self.natives.get(id)
+                };
+                (args, returns.copied().unwrap_or(false) as usize)
+            },
+            // The exports of another module are expected to return a value
+            OpCode::ExternalCall => (instruction.u8(4) as usize, 1),
+            OpCode::NewArray => (instruction.u8(0) as usize, 1),
+            OpCode::NewMap => (instruction.u8(0) as usize * 2, 1),
+            OpCode::NewStruct => (self.structs.get(instruction.u16(0) as usize).copied().unwrap_or(0), 1),
+            OpCode::NewEnum => {
+                let fields = self.enums.get(&instruction.u16(0))
+                    .and_then(|variants| variants.get(instruction.u8(2) as usize))
+                    .copied()
+                    .unwrap_or(0);
+                (fields, 1)
+            },
+            OpCode::Unpack => (1, instruction.u8(0) as usize),
+            OpCode::Assign
+            | OpCode::AssignAdd
+            | OpCode::AssignSub
+            | OpCode::AssignMul
+            | OpCode::AssignDiv
+            | OpCode::AssignMod
+            | OpCode::AssignPow
+            | OpCode::AssignBitwiseAnd
+            | OpCode::AssignBitwiseOr
+            | OpCode::AssignBitwiseXor
+            | OpCode::AssignBitwiseShl
+            | OpCode::AssignBitwiseShr
+            | OpCode::LtJumpIfFalse
+            | OpCode::LteJumpIfFalse
+            | OpCode::GtJumpIfFalse
+            | OpCode::GteJumpIfFalse => (2, 0),
+            // Binary operators, ranges and array calls
+            _ => (2, 1),
+        }
+    }
+}
+
+// Depth of the stack before each instruction, None if it can't be reached
+pub fn stack_depths(instructions: &[Instruction], parameters: usize, effects: &Effects) -> Vec<Option<usize>> {
+    let mut depths = vec![None; instructions.len()];
+    let index_of = |offset: usize| instructions.binary_search_by_key(&offset, |i| i.offset).ok();
+
+    let mut pending = vec![(0, parameters)];
+    while let Some((index, depth)) = pending.pop() {
+        let Some(instruction) = instructions.get(index) else {
+            continue
+        };
+        if depths[index].is_some() {
+            continue
+        }
+        depths[index] = Some(depth);
+
+        let (pops, pushes) = effects.of(instruction);
+        let after = depth.saturating_sub(pops) + pushes;
+        match instruction.opcode {
+            OpCode::Return => continue,
+            // The value is only pushed when the iterator isn't empty
+            OpCode::IteratorNext => pending.push((index + 1, depth + 1)),
+            OpCode::JumpTable => {
+                let len = instruction.u16(6) as usize;
+                pending.extend((1..=len).map(|i| (index + i, after)));
+            },
+            _ if !instruction.ends_flow() => pending.push((index + 1, after)),
+            _ => {}
+        }
+
+        if let Some(target) = instruction.target().and_then(index_of) {
+            pending.push((target, after));
+        }
+    }
+
+    depths
+}
+
+// Does a chunk return a value: a Return is reached with a value on the stack
+pub fn returns_value(instructions: &[Instruction], depths: &[Option<usize>]) -> bool {
+    instructions.iter()
+        .zip(depths)
+        .any(|(instruction, depth)| instruction.opcode == OpCode::Return && depth.is_some_and(|d| d > 0))
+}
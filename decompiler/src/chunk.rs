@@ -0,0 +1,1010 @@
+use core::fmt::Write;
+use std::collections::HashSet;
+use xelis_bytecode::{OpCode, SourceSpan};
+use xelis_types::{Constant, Value, U256};
+
+use super::{
+    analysis::{self, Instruction},
+    DecompilerError,
+    Symbols
+};
+
+// Count of unconditional jumps followed to find where a jump lands
+const MAX_JUMP_HOPS: usize = 16;
+
+// An expression rebuilt from the values pushed on the stack
+#[derive(Debug, Clone)]
+struct Expr {
+    text: String,
+    // A function is called: the expression is kept as a statement when its value is dropped
+    effects: bool,
+    // Operands of an equality, to write `a != b` once negated
+    eq: Option<(String, String)>,
+}
+
+impl Expr {
+    fn new(text: impl Into<String>) -> Self {
+        Self {
+            text: text.into(),
+            effects: false,
+            eq: None
+        }
+    }
+
+    fn with_effects(text: impl Into<String>, effects: bool) -> Self {
+        Self {
+            effects,
+            ..Self::new(text)
+        }
+    }
+
+    // Negation of a condition
+    fn negate(&self) -> String {
+        match (&self.eq, self.text.strip_prefix('!')) {
+            (Some((left, right)), _) => format!("({} != {})", left, right),
+            (None, Some(inner)) => inner.to_owned(),
+            (None, None) => format!("!{}", self.text)
+        }
+    }
+}
+
+// Loop enclosing the statements, its jumps are written as break and continue
+struct Loop {
+    // Offsets jumped to by a continue: the head and the operation of a for loop
+    continues: Vec<usize>,
+    // Offset after the loop
+    exit: usize,
+}
+
+// Rebuild the statements of a chunk
+// The instructions are walked by ranges: a structure found at an instruction
+// decompiles its inner ranges and resumes after its last instruction
+pub struct ChunkDecompiler<'a> {
+    symbols: &'a Symbols<'a>,
+    id: usize,
+    instructions: &'a [Instruction],
+    // Depth of the stack before each instruction
+    depths: Vec<Option<usize>>,
+    spans: &'a [(usize, SourceSpan)],
+    // Size of the chunk in bytes, the offset after its last instruction
+    size: usize,
+    // Loops being decompiled, the innermost last
+    loops: Vec<Loop>,
+    // Indexes of the loop heads being decompiled
+    heads: HashSet<usize>,
+    // Offsets targeted by a goto
+    labels: HashSet<usize>,
+    lines: Vec<String>,
+    indent: usize,
+    // Register and value of the declaration written on the last line
+    last_let: Option<(u16, String)>,
+    // Source line of the next statement
+    line: Option<usize>,
+}
+
+impl<'a> ChunkDecompiler<'a> {
+    pub fn new(symbols: &'a Symbols<'a>, id: usize) -> Self {
+        let instructions = &symbols.chunks[id];
+        Self {
+            symbols,
+            id,
+            instructions,
+            depths: analysis::stack_depths(instructions, symbols.parameters[id], &symbols.effects),
+            spans: symbols.module.debug_info()
+                .and_then(|info| info.get_spans(id))
+                .unwrap_or_default(),
+            size: symbols.module.get_chunk_at(id).map_or(0, |chunk| chunk.get_instructions().len()),
+            loops: Vec::new(),
+            heads: HashSet::new(),
+            labels: HashSet::new(),
+            lines: Vec::new(),
+            indent: 1,
+            last_let: None,
+            line: None,
+        }
+    }
+
+    // Decompile the chunk into a function
+    pub fn decompile(mut self) -> Result<String, DecompilerError> {
+        let symbols = self.symbols;
+        let module = symbols.module;
+        let parameters = symbols.parameters[self.id];
+        let instance = symbols.instances.contains(&self.id);
+
+        let mut declared = (0..parameters as u16).collect::<HashSet<_>>();
+        let mut stack = Vec::new();
+        self.block(parameters, self.instructions.len(), self.size, &mut declared, &mut stack);
+
+        let mut output = String::new();
+        writeln!(output, "// chunk {}", self.id)?;
+        if let Some((_, span)) = self.spans.first() {
+            writeln!(output, "// line {}", span.line)?;
+        }
+
+        let params = (instance as usize..parameters)
+            .map(|register| {
+                let _type = module.get_signature(self.id)
+                    .and_then(|signature| signature.parameters().get(register - instance as usize))
+                    .map_or_else(|| "any".to_owned(), |(_, t)| symbols.type_name(t));
+                format!("{}: {}", self.register(register as u16), _type)
+            })
+            .collect::<Vec<_>>()
+            .join(", ");
+        let name = symbols.chunk_name(self.id);
+
+        if module.is_entry_chunk(self.id) {
+            writeln!(output, "entry {}({}) {{", name, params)?;
+        } else {
+            if module.is_test_chunk(self.id) {
+                writeln!(output, "#[test]")?;
+            }
+            let public = if module.is_exported_chunk(self.id) { "pub " } else { "" };
+            let instance = if instance { "(self any) " } else { "" };
+            let return_type = match module.get_signature(self.id) {
+                Some(signature) => signature.return_type().map(|t| symbols.type_name(t)),
+                None => symbols.effects.chunks[self.id].then(|| "any".to_owned())
+            };
+            match return_type {
+                Some(t) => writeln!(output, "{}fn {}{}({}) -> {} {{", public, instance, name, params, t)?,
+                None => writeln!(output, "{}fn {}{}({}) {{", public, instance, name, params)?
+            };
+        }
+
+        for line in self.lines {
+            writeln!(output, "{}", line)?;
+        }
+        writeln!(output, "}}")?;
+
+        Ok(output)
+    }
+
+    // Name of a register: the parameters are named by the signature
+    fn register(&self, register: u16) -> String {
+        let instance = self.symbols.instances.contains(&self.id);
+        if instance && register == 0 {
+            return "self".to_owned()
+        }
+
+        let index = register as usize - instance as usize;
+        if let Some((name, _)) = self.symbols.module.get_signature(self.id).and_then(|s| s.parameters().get(index)) {
+            return name.clone()
+        }
+
+        if (register as usize) < self.symbols.parameters[self.id] {
+            format!("param{}", index)
+        } else {
+            format!("v{}", register)
+        }
+    }
+
+    // Write a statement at the current indentation
+    fn push(&mut self, text: impl Into<String>) {
+        let mut line = format!("{}{}", "    ".repeat(self.indent), text.into());
+        if let Some(source) = self.line.take() {
+            let _ = write!(line, " // line {}", source);
+        }
+        self.lines.push(line);
+        self.last_let = None;
+    }
+
+    // Index of the instruction at an offset, the end of the chunk included
+    fn index_of(&self, offset: usize) -> Option<usize> {
+        if offset == self.size {
+            return Some(self.instructions.len())
+        }
+        self.instructions.binary_search_by_key(&offset, |i| i.offset).ok()
+    }
+
+    // Offset of the instruction at an index, the end of the chunk included
+    fn offset_at(&self, index: usize) -> usize {
+        self.instructions.get(index).map_or(self.size, |i| i.offset)
+    }
+
+    // Offset where a jump lands once the unconditional jumps are followed
+    fn resolve(&self, mut offset: usize) -> usize {
+        for _ in 0..MAX_JUMP_HOPS {
+            match self.index_of(offset).and_then(|i| self.instructions.get(i)) {
+                Some(instruction) if instruction.opcode == OpCode::Jump => offset = instruction.u32(0) as usize,
+                _ => break
+            }
+        }
+        offset
+    }
+
+    // A jump out of the innermost loop
+    fn loop_jump(&self, target: usize) -> Option<&'static str> {
+        let current = self.loops.last()?;
+        let target = self.resolve(target);
+        if target == self.resolve(current.exit) {
+            Some("break")
+        } else if current.continues.iter().any(|offset| self.resolve(*offset) == target) {
+            Some("continue")
+        } else {
+            None
+        }
+    }
+
+    // Decompile the statements of a range of instructions
+    // The follow is the offset reached once the range is done
+    fn block(&mut self, start: usize, end: usize, follow: usize, declared: &mut HashSet<u16>, stack: &mut Vec<Expr>) {
+        let mut index = start;
+        while index < end {
+            index = self.step(index, end, follow, declared, stack);
+        }
+    }
+
+    // Decompile the range in a new scope, values and declarations don't leak out of it
+    fn scope(&mut self, start: usize, end: usize, follow: usize, declared: &HashSet<u16>, stack: &[Expr]) {
+        self.indent += 1;
+        self.block(start, end, follow, &mut declared.clone(), &mut stack.to_vec());
+        self.indent -= 1;
+    }
+
+    // Decompile a range expected to only push values
+    // Returns None if it writes a statement
+    fn expression(&mut self, start: usize, end: usize, follow: usize, declared: &HashSet<u16>, stack: &[Expr]) -> Option<Vec<Expr>> {
+        let lines = self.lines.len();
+        let last_let = self.last_let.clone();
+        let mut values = stack.to_vec();
+        self.block(start, end, follow, &mut declared.clone(), &mut values);
+
+        if self.lines.len() != lines {
+            self.lines.truncate(lines);
+            self.last_let = last_let;
+            return None
+        }
+        Some(values)
+    }
+
+    // Decompile the next instruction or the structure starting at it
+    // Returns the index to resume from
+    fn step(&mut self, index: usize, end: usize, follow: usize, declared: &mut HashSet<u16>, stack: &mut Vec<Expr>) -> usize {
+        let offset = self.instructions[index].offset;
+        if self.labels.contains(&offset) {
+            self.lines.push(format!("{}label_{}:", "    ".repeat(self.indent - 1), offset));
+        }
+        if let Ok(span) = self.spans.binary_search_by_key(&offset, |(start, _)| *start) {
+            self.line = Some(self.spans[span].1.line);
+        }
+
+        // A loop jumps back to its first instruction
+        if !self.heads.contains(&index) {
+            let back = (index..end).rev()
+                .find(|i| self.instructions[*i].opcode == OpCode::Jump && self.instructions[*i].u32(0) as usize == offset);
+            if let Some(back) = back {
+                return self.while_loop(index, back, declared, stack)
+            }
+        }
+
+        self.instruction(index, end, follow, declared, stack)
+    }
+
+    fn pop(stack: &mut Vec<Expr>) -> Expr {
+        stack.pop().unwrap_or_else(|| Expr::new("?"))
+    }
+
+    // Pop the values of a constructor or a call, in the order they were pushed
+    fn pop_n(stack: &mut Vec<Expr>, count: usize) -> Vec<Expr> {
+        let mut values = (0..count).map(|_| Self::pop(stack)).collect::<Vec<_>>();
+        values.reverse();
+        values
+    }
+
+    fn join(values: &[Expr]) -> String {
+        values.iter().map(|v| v.text.as_str()).collect::<Vec<_>>().join(", ")
+    }
+
+    fn binary(stack: &mut Vec<Expr>, operator: &str) -> Expr {
+        let right = Self::pop(stack);
+        let left = Self::pop(stack);
+        Expr::with_effects(format!("({} {} {})", left.text, operator, right.text), left.effects || right.effects)
+    }
+
+    // Push the value of a call, or write it as a statement when it returns nothing
+    fn call(&mut self, stack: &mut Vec<Expr>, text: String, returns: bool) {
+        if returns {
+            stack.push(Expr::with_effects(text, true));
+        } else {
+            self.push(text);
+        }
+    }
+
+    // Declare or assign a register
+    fn store(&mut self, register: u16, value: Expr, declared: &mut HashSet<u16>) {
+        let name = self.register(register);
+        if declared.insert(register) {
+            self.push(format!("let {} = {}", name, value.text));
+            self.last_let = Some((register, value.text));
+        } else {
+            self.push(format!("{} = {}", name, value.text));
+        }
+    }
+
+    fn instruction(&mut self, index: usize, end: usize, follow: usize, declared: &mut HashSet<u16>, stack: &mut Vec<Expr>) -> usize {
+        let instruction = &self.instructions[index];
+        match instruction.opcode {
+            OpCode::Constant => {
+                let text = self.constant(instruction.u16(0));
+                stack.push(Expr::new(text));
+            },
+            OpCode::MemoryLoad => stack.push(Expr::new(self.register(instruction.u16(0)))),
+            OpCode::MemorySet => {
+                let value = Self::pop(stack);
+                self.store(instruction.u16(0), value, declared);
+            },
+            OpCode::SubLoad => {
+                let value = Self::pop(stack);
+                stack.push(Expr::with_effects(format!("{}.field{}", value.text, instruction.u8(0)), value.effects));
+            },
+            OpCode::Pop | OpCode::PopN => {
+                let count = if instruction.opcode == OpCode::Pop { 1 } else { instruction.u8(0) as usize };
+                for value in Self::pop_n(stack, count) {
+                    if value.effects {
+                        self.push(value.text);
+                    }
+                }
+            },
+            OpCode::Copy => {
+                let value = stack.last().cloned().unwrap_or_else(|| Expr::new("?"));
+                stack.push(value);
+            },
+            OpCode::CopyN => {
+                let value = stack.len()
+                    .checked_sub(1 + instruction.u8(0) as usize)
+                    .map_or_else(|| Expr::new("?"), |i| stack[i].clone());
+                stack.push(value);
+            },
+            OpCode::Swap | OpCode::Swap2 => {
+                let (a, b) = if instruction.opcode == OpCode::Swap {
+                    (0, instruction.u8(0) as usize)
+                } else {
+                    (instruction.u8(0) as usize, instruction.u8(1) as usize)
+                };
+                let len = stack.len();
+                if a < len && b < len {
+                    stack.swap(len - 1 - a, len - 1 - b);
+                }
+            },
+            OpCode::Jump => {
+                let target = instruction.u32(0) as usize;
+                if let Some(keyword) = self.loop_jump(target) {
+                    self.push(keyword);
+                } else if !(index + 1 == end && self.resolve(target) == self.resolve(follow)) {
+                    self.labels.insert(target);
+                    self.push(format!("goto label_{}", target));
+                }
+            },
+            OpCode::JumpIfFalse
+            | OpCode::LtJumpIfFalse
+            | OpCode::LteJumpIfFalse
+            | OpCode::GtJumpIfFalse
+            | OpCode::GteJumpIfFalse => return self.branch(index, end, follow, declared, stack),
+            OpCode::IterableLength => {
+                let value = Self::pop(stack);
+                stack.push(Expr::with_effects(format!("{}.len()", value.text), value.effects));
+            },
+            OpCode::IteratorBegin => {
+                let value = Self::pop(stack);
+                if let Some(next) = self.foreach(index, value.clone(), declared, stack) {
+                    return next
+                }
+                self.push(format!("// iterate over {}", value.text));
+            },
+            OpCode::IteratorNext => {
+                self.push("// next value of the iterator");
+                stack.push(Expr::new("next"));
+            },
+            OpCode::IteratorEnd => self.push("// end of the iterator"),
+            OpCode::Return => {
+                if self.depths[index].is_some_and(|depth| depth > 0) {
+                    let value = Self::pop(stack);
+                    self.push(format!("return {}", value.text));
+                } else {
+                    self.push("return");
+                }
+            },
+            OpCode::ArrayCall => {
+                let at = Self::pop(stack);
+                let value = Self::pop(stack);
+                stack.push(Expr::with_effects(format!("{}[{}]", value.text, at.text), value.effects || at.effects));
+            },
+            OpCode::Cast => {
+                let value = Self::pop(stack);
+                let _type = xelis_types::Type::primitive_type_from_byte(instruction.u8(0))
+                    .map_or_else(|| "?".to_owned(), |t| t.to_string());
+                stack.push(Expr::with_effects(format!("({} as {})", value.text, _type), value.effects));
+            },
+            OpCode::InvokeChunk => {
+                let id = instruction.u16(0) as usize;
+                let args = Self::pop_n(stack, instruction.u8(3) as usize);
+                let name = self.symbols.chunk_name(id);
+                let text = if instruction.u8(2) != 0 {
+                    format!("{}.{}({})", Self::pop(stack).text, name, Self::join(&args))
+                } else {
+                    format!("{}({})", name, Self::join(&args))
+                };
+                let returns = self.symbols.effects.chunks.get(id).copied().unwrap_or(false);
+                self.call(stack, text, returns);
+            },
+            OpCode::SysCall => {
+                let id = instruction.u16(0) as usize;
+                let args = Self::pop_n(stack, instruction.u8(3) as usize);
+                let (name, on_type) = self.symbols.native_name(id);
+                let text = match (instruction.u8(2) != 0, on_type) {
+                    (true, _) => format!("{}.{}({})", Self::pop(stack).text, name, Self::join(&args)),
+                    (false, Some(on_type)) => format!("{}::{}({})", self.symbols.type_name(on_type), name, Self::join(&args)),
+                    (false, None) => format!("{}({})", name, Self::join(&args))
+                };
+                let returns = self.symbols.effects.natives.get(id).copied().unwrap_or(false);
+                self.call(stack, text, returns);
+            },
+            OpCode::ExternalCall => {
+                let args = Self::pop_n(stack, instruction.u8(4) as usize);
+                let text = format!("{}::{}({})", self.string(instruction.u16(0)), self.string(instruction.u16(2)), Self::join(&args));
+                stack.push(Expr::with_effects(text, true));
+            },
+            // A function given to a native is written by its name
+            OpCode::ChunkId => stack.push(Expr::new(self.symbols.chunk_name(instruction.u16(0) as usize))),
+            OpCode::CallPrecompile => {
+                let input = Self::pop(stack);
+                stack.push(Expr::with_effects(format!("precompile#{}({})", instruction.u16(0), input.text), true));
+            },
+            OpCode::NewArray => {
+                let values = Self::pop_n(stack, instruction.u8(0) as usize);
+                let effects = values.iter().any(|v| v.effects);
+                stack.push(Expr::with_effects(format!("[{}]", Self::join(&values)), effects));
+            },
+            OpCode::NewStruct => {
+                let id = instruction.u16(0);
+                let count = self.symbols.effects.structs.get(id as usize).copied().unwrap_or(0);
+                let values = Self::pop_n(stack, count);
+                let fields = self.symbols.struct_fields(id, count)
+                    .iter()
+                    .zip(&values)
+                    .map(|(name, value)| format!("{}: {}", name, value.text))
+                    .collect::<Vec<_>>();
+                let effects = values.iter().any(|v| v.effects);
+                stack.push(Expr::with_effects(format!("{} {{ {} }}", self.symbols.struct_name(id), fields.join(", ")), effects));
+            },
+            OpCode::NewRange | OpCode::NewRangeInclusive => {
+                let operator = if instruction.opcode == OpCode::NewRange { ".." } else { "..=" };
+                let value = Self::binary(stack, operator);
+                stack.push(Expr::with_effects(value.text.replace(&format!(" {} ", operator), operator), value.effects));
+            },
+            OpCode::NewMap => {
+                let values = Self::pop_n(stack, instruction.u8(0) as usize * 2);
+                let entries = values.chunks(2)
+                    .map(|entry| format!("{}: {}", entry[0].text, entry[1].text))
+                    .collect::<Vec<_>>();
+                let effects = values.iter().any(|v| v.effects);
+                stack.push(Expr::with_effects(format!("{{{}}}", entries.join(", ")), effects));
+            },
+            OpCode::NewEnum => {
+                let id = instruction.u16(0);
+                let variant = instruction.u8(2);
+                let count = self.symbols.effects.enums.get(&id)
+                    .and_then(|variants| variants.get(variant as usize))
+                    .copied()
+                    .unwrap_or(0);
+                let values = Self::pop_n(stack, count);
+                let (variant, names) = self.symbols.enum_variant(id, variant, count);
+                let mut text = format!("{}::{}", self.symbols.enum_name(id), variant);
+                if !values.is_empty() {
+                    let fields = names.iter()
+                        .zip(&values)
+                        .map(|(name, value)| format!("{}: {}", name, value.text))
+                        .collect::<Vec<_>>();
+                    let _ = write!(text, " {{ {} }}", fields.join(", "));
+                }
+                stack.push(Expr::with_effects(text, values.iter().any(|v| v.effects)));
+            },
+            OpCode::Unpack => {
+                let value = Self::pop(stack);
+                let count = instruction.u8(0) as usize;
+                // The values are stored in the declared order
+                let stores = self.instructions.get(index + 1..index + 1 + count)
+                    .filter(|stores| stores.len() == count && stores.iter().all(|i| i.opcode == OpCode::MemorySet));
+                if let Some(stores) = stores {
+                    let names = stores.iter().map(|i| self.register(i.u16(0))).collect::<Vec<_>>();
+                    let new = stores.iter().filter(|i| declared.insert(i.u16(0))).count() > 0;
+                    self.push(format!("{}({}) = {}", if new { "let " } else { "" }, names.join(", "), value.text));
+                    return index + 1 + count
+                }
+
+                for i in (0..count).rev() {
+                    stack.push(Expr::with_effects(format!("{}.field{}", value.text, i), value.effects));
+                }
+            },
+            OpCode::Neg => {
+                let value = Self::pop(stack);
+                stack.push(Expr::with_effects(value.negate(), value.effects));
+            },
+            OpCode::Eq => {
+                let mut value = Self::binary(stack, "==");
+                if let Some(operands) = value.text.strip_prefix('(').and_then(|t| t.strip_suffix(')')) {
+                    value.eq = operands.split_once(" == ").map(|(l, r)| (l.to_owned(), r.to_owned()));
+                }
+                stack.push(value);
+            },
+            opcode if Self::is_assign(opcode) => {
+                let value = Self::pop(stack);
+                let target = Self::pop(stack);
+                self.push(format!("{} {} {}", target.text, Self::operator(instruction.opcode), value.text));
+            },
+            OpCode::Inc | OpCode::Dec => {
+                let value = stack.last().map_or_else(|| "?".to_owned(), |v| v.text.clone());
+                self.push(format!("{} {} 1", value, if instruction.opcode == OpCode::Inc { "+=" } else { "-=" }));
+            },
+            OpCode::MemoryAdd => {
+                let text = format!("({} + {})", self.register(instruction.u16(0)), self.register(instruction.u16(2)));
+                stack.push(Expr::new(text));
+            },
+            OpCode::MemoryCast => {
+                let _type = xelis_types::Type::primitive_type_from_byte(instruction.u8(2))
+                    .map_or_else(|| "?".to_owned(), |t| t.to_string());
+                stack.push(Expr::new(format!("({} as {})", self.register(instruction.u16(0)), _type)));
+            },
+            OpCode::MemoryInc => {
+                let name = self.register(instruction.u16(0));
+                self.push(format!("{} += 1", name));
+            },
+            OpCode::JumpTable => {
+                let value = Self::pop(stack);
+                return self.jump_table(index, value, end, follow, declared, stack)
+            },
+            opcode => {
+                let value = Self::binary(stack, Self::operator(opcode));
+                stack.push(value);
+            }
+        }
+
+        index + 1
+    }
+
+    fn is_assign(opcode: OpCode) -> bool {
+        matches!(
+            opcode,
+            OpCode::Assign
+            | OpCode::AssignAdd
+            | OpCode::AssignSub
+            | OpCode::AssignMul
+            | OpCode::AssignDiv
+            | OpCode::AssignMod
+            | OpCode::AssignPow
+            | OpCode::AssignBitwiseAnd
+            | OpCode::AssignBitwiseOr
+            | OpCode::AssignBitwiseXor
+            | OpCode::AssignBitwiseShl
+            | OpCode::AssignBitwiseShr
+        )
+    }
+
+    // Symbol of an operator
+    fn operator(opcode: OpCode) -> &'static str {
+        match opcode {
+            OpCode::Add => "+",
+            OpCode::Sub => "-",
+            OpCode::Mul => "*",
+            OpCode::Div => "/",
+            OpCode::Mod => "%",
+            OpCode::Pow => "**",
+            OpCode::And => "&&",
+            OpCode::Or => "||",
+            OpCode::BitwiseAnd => "&",
+            OpCode::BitwiseOr => "|",
+            OpCode::BitwiseXor => "^",
+            OpCode::BitwiseShl => "<<",
+            OpCode::BitwiseShr => ">>",
+            OpCode::Eq => "==",
+            OpCode::Gt | OpCode::GtJumpIfFalse => ">",
+            OpCode::Lt | OpCode::LtJumpIfFalse => "<",
+            OpCode::Gte | OpCode::GteJumpIfFalse => ">=",
+            OpCode::Lte | OpCode::LteJumpIfFalse => "<=",
+            OpCode::Assign => "=",
+            OpCode::AssignAdd => "+=",
+            OpCode::AssignSub => "-=",
+            OpCode::AssignMul => "*=",
+            OpCode::AssignDiv => "/=",
+            OpCode::AssignMod => "%=",
+            OpCode::AssignPow => "**=",
+            OpCode::AssignBitwiseAnd => "&=",
+            OpCode::AssignBitwiseOr => "|=",
+            OpCode::AssignBitwiseXor => "^=",
+            OpCode::AssignBitwiseShl => "<<=",
+            OpCode::AssignBitwiseShr => ">>=",
+            OpCode::CheckedAdd => "+?",
+            OpCode::CheckedSub => "-?",
+            OpCode::CheckedMul => "*?",
+            OpCode::SaturatingAdd => "+|",
+            OpCode::SaturatingSub => "-|",
+            OpCode::SaturatingMul => "*|",
+            OpCode::WrappingAdd => "+%",
+            OpCode::WrappingSub => "-%",
+            OpCode::WrappingMul => "*%",
+            _ => "?"
+        }
+    }
+
+    fn constant(&self, id: u16) -> String {
+        match self.symbols.module.get_constant_at(id as usize) {
+            Some(constant) => self.symbols.constant(constant),
+            None => format!("<constant {}>", id)
+        }
+    }
+
+    // A string constant used as a name
+    fn string(&self, id: u16) -> String {
+        match self.symbols.module.get_constant_at(id as usize) {
+            Some(Constant::Default(Value::String(s))) => s.clone(),
+            _ => format!("<constant {}>", id)
+        }
+    }
+
+    // Pop the condition tested by a conditional jump
+    fn condition(instruction: &Instruction, stack: &mut Vec<Expr>) -> Expr {
+        match instruction.opcode {
+            OpCode::JumpIfFalse => Self::pop(stack),
+            opcode => Self::binary(stack, Self::operator(opcode))
+        }
+    }
+
+    // A conditional jump: the operators && and ||, the ? operator,
+    // an if with its optional else, a ternary or a switch comparing each value
+    fn branch(&mut self, index: usize, end: usize, follow: usize, declared: &mut HashSet<u16>, stack: &mut Vec<Expr>) -> usize {
+        let instruction = &self.instructions[index];
+        let target = instruction.u32(0) as usize;
+        let target_index = self.index_of(target).filter(|t| *t > index);
+        let opcode_at = |i: Option<usize>| i.and_then(|i| self.instructions.get(i)).map(|i| i.opcode);
+        let before = |n: usize| opcode_at(index.checked_sub(n));
+
+        if instruction.opcode == OpCode::JumpIfFalse {
+            if let Some(t) = target_index {
+                // value?: return the error, else read the value of the result
+                let is_try = before(1) == Some(OpCode::SysCall)
+                    && before(2) == Some(OpCode::Copy)
+                    && opcode_at(Some(t - 1)) == Some(OpCode::Return)
+                    && opcode_at(Some(t)) == Some(OpCode::SubLoad);
+                if is_try {
+                    Self::pop(stack);
+                    let value = Self::pop(stack);
+                    stack.push(Expr::with_effects(format!("{}?", value.text), true));
+                    return t + 1
+                }
+
+                // left && right, left || right: the left value is kept when the right one is skipped
+                let operator = match opcode_at(Some(t - 1)) {
+                    Some(OpCode::And) if before(1) == Some(OpCode::Copy) => Some("&&"),
+                    Some(OpCode::Or) if before(1) == Some(OpCode::Neg) && before(2) == Some(OpCode::Copy) => Some("||"),
+                    _ => None
+                };
+                if let Some(operator) = operator.filter(|_| t - 1 > index) {
+                    Self::pop(stack);
+                    let left = Self::pop(stack);
+                    let right = self.expression(index + 1, t - 1, self.offset_at(t - 1), declared, &[])
+                        .and_then(|mut values| values.pop())
+                        .unwrap_or_else(|| Expr::new("?"));
+                    stack.push(Expr::with_effects(format!("({} {} {})", left.text, operator, right.text), left.effects || right.effects));
+                    return t
+                }
+
+                if let Some(next) = self.switch_chain(index, declared, stack, end, follow) {
+                    return next
+                }
+            }
+        }
+
+        let condition = Self::condition(instruction, stack);
+        match target_index {
+            Some(t) if t <= end => self.if_else(index, t, condition, end, follow, declared, stack),
+            _ => {
+                match self.loop_jump(target) {
+                    Some(keyword) => self.push(format!("if {} {{ {} }}", condition.negate(), keyword)),
+                    None => {
+                        self.labels.insert(target);
+                        self.push(format!("if {} {{ goto label_{} }}", condition.negate(), target));
+                    }
+                }
+                index + 1
+            }
+        }
+    }
+
+    // An if jumping to its else or its end when the condition is false
+    // A then ending with a jump over the following instructions has an else
+    #[allow(clippy::too_many_arguments)]
+    fn if_else(&mut self, index: usize, t: usize, condition: Expr, end: usize, follow: usize, declared: &mut HashSet<u16>, stack: &mut Vec<Expr>) -> usize {
+        let skip = self.instructions.get(t - 1)
+            .filter(|i| t - 1 > index && i.opcode == OpCode::Jump)
+            .map(|i| i.u32(0) as usize);
+
+        // Range of the else, the offset reached after the then
+        // A break or a continue ending the then is kept unless it jumps over an else
+        let otherwise = skip.and_then(|target| match self.index_of(target) {
+            // A jump to the end of a loop body is an else only if the then can't reach it
+            Some(u) if u > t && u <= end && (u < end || self.loop_jump(target).is_none() || self.instructions[t - 2].ends_flow()) => Some((t, u, target)),
+            _ if self.loop_jump(target).is_none() && self.resolve(target) == self.resolve(follow) => Some((t, end, target)),
+            _ => None
+        });
+
+        let Some((start, stop, after)) = otherwise else {
+            let target = self.offset_at(t);
+            self.push(format!("if {} {{", condition.text));
+            self.scope(index + 1, t, target, declared, stack);
+            self.push("}");
+            return t
+        };
+
+        // Both sides only push a value: condition ? a : b
+        let values = self.expression(index + 1, t - 1, after, declared, stack)
+            .zip(self.expression(start, stop, self.offset_at(stop), declared, stack));
+        if let Some((mut valid, mut invalid)) = values.filter(|(a, b)| a.len() == stack.len() + 1 && b.len() == stack.len() + 1) {
+            let (valid, invalid) = (Self::pop(&mut valid), Self::pop(&mut invalid));
+            let effects = condition.effects || valid.effects || invalid.effects;
+            stack.push(Expr::with_effects(format!("({} ? {} : {})", condition.text, valid.text, invalid.text), effects));
+            return stop
+        }
+
+        self.push(format!("if {} {{", condition.text));
+        self.scope(index + 1, t - 1, after, declared, stack);
+        if start < stop {
+            self.push("} else {");
+            let follow = if stop == end { follow } else { self.offset_at(stop) };
+            self.scope(start, stop, follow, declared, stack);
+        }
+        self.push("}");
+        stop
+    }
+
+    // A loop from its first instruction to the jump back to it
+    // The condition is the first jump to the exit, a for loop ends with an operation
+    // on the variable declared just before it
+    fn while_loop(&mut self, head: usize, back: usize, declared: &mut HashSet<u16>, stack: &[Expr]) -> usize {
+        let head_offset = self.instructions[head].offset;
+        let exit = self.offset_at(back + 1);
+        self.heads.insert(head);
+
+        // Walk the values pushed until the jump to the exit
+        let lines = self.lines.len();
+        let last_let = self.last_let.clone();
+        let mut values = Vec::new();
+        let mut condition = None;
+        let mut index = head;
+        while index < back && self.lines.len() == lines {
+            let instruction = &self.instructions[index];
+            if instruction.is_conditional_jump() && instruction.u32(0) as usize == exit {
+                condition = Some(Self::condition(instruction, &mut values)).filter(|_| values.is_empty());
+                break
+            }
+            index = self.step(index, back, head_offset, &mut declared.clone(), &mut values);
+        }
+        self.lines.truncate(lines);
+        self.last_let = last_let;
+
+        let (condition, body) = match condition {
+            Some(condition) => (condition.text, index + 1),
+            None => ("true".to_owned(), head)
+        };
+
+        // The last statement of a for loop starts where the stack is back to its depth at the jump
+        let operation = (body..back).rev()
+            .find(|i| self.depths[*i] == self.depths[back])
+            .filter(|_| condition != "true");
+        let variable = self.last_let.clone().zip(operation).filter(|((register, _), start)| {
+            let first = &self.instructions[*start];
+            let last = &self.instructions[back - 1];
+            let assign = matches!(first.opcode, OpCode::MemoryLoad | OpCode::MemoryInc) && first.u16(0) == *register;
+            assign && (first.opcode == OpCode::MemoryInc || Self::is_assign(last.opcode))
+        });
+
+        let (continues, body_end) = match &variable {
+            Some((_, start)) => (vec![head_offset, self.instructions[*start].offset], *start),
+            None => (vec![head_offset], back)
+        };
+
+        let for_register = variable.as_ref().map(|((register, _), _)| *register);
+        if let Some(((register, value), start)) = variable {
+            let line = self.lines.len();
+            self.block(start, back, head_offset, &mut declared.clone(), &mut Vec::new());
+            let operation = self.lines.drain(line..)
+                .map(|l| l.split(" // ").next().unwrap_or_default().trim().to_owned())
+                .collect::<Vec<_>>()
+                .join(", ");
+            self.lines.pop();
+            self.push(format!("for {} = {}; {}; {} {{", self.register(register), value, condition, operation));
+        } else {
+            self.push(format!("while {} {{", condition));
+        }
+
+        self.loops.push(Loop { continues, exit });
+        let follow = self.offset_at(body_end);
+        self.scope(body, body_end, follow, declared, stack);
+        self.loops.pop();
+        // The variable of a for loop is only declared in it
+        if let Some(register) = for_register {
+            declared.remove(&register);
+        }
+        self.heads.remove(&head);
+        self.push("}");
+
+        back + 1
+    }
+
+    // foreach value in values: IteratorNext jumps to the IteratorEnd after the jump back to it
+    fn foreach(&mut self, index: usize, values: Expr, declared: &HashSet<u16>, stack: &[Expr]) -> Option<usize> {
+        let next = self.instructions.get(index + 1).filter(|i| i.opcode == OpCode::IteratorNext)?;
+        let store = self.instructions.get(index + 2).filter(|i| i.opcode == OpCode::MemorySet)?;
+        let end = self.index_of(next.u32(0) as usize)
+            .filter(|end| self.instructions.get(*end).is_some_and(|i| i.opcode == OpCode::IteratorEnd))?;
+        let back = self.instructions.get(end - 1)
+            .filter(|i| i.opcode == OpCode::Jump && i.u32(0) as usize == next.offset)?;
+
+        let register = store.u16(0);
+        let head = next.offset;
+        self.push(format!("foreach {} in {} {{", self.register(register), values.text));
+
+        let mut declared = declared.clone();
+        declared.insert(register);
+        self.loops.push(Loop { continues: vec![head], exit: self.instructions[end].offset });
+        self.heads.insert(index + 1);
+        self.scope(index + 3, end - 1, back.offset, &declared, stack);
+        self.heads.remove(&(index + 1));
+        self.loops.pop();
+        self.push("}");
+
+        Some(end + 1)
+    }
+
+    // Value of a switch: the declaration written just before is replaced by the switch
+    fn switch_value(&mut self, value: Expr) -> String {
+        match self.last_let.take() {
+            Some((register, init)) if self.register(register) == value.text => {
+                self.lines.pop();
+                init
+            },
+            _ => value.text
+        }
+    }
+
+    // A switch without jump table compares its value with each case value
+    // `!(value == case)` jumps to the case, the last jump goes to the default
+    fn switch_chain(&mut self, index: usize, declared: &mut HashSet<u16>, stack: &mut Vec<Expr>, end: usize, follow: usize) -> Option<usize> {
+        let (register, _) = self.last_let.clone()?;
+        let is_case = |i: usize| -> Option<(u16, usize)> {
+            let window = self.instructions.get(i..i + 5)?;
+            let opcodes = window.iter().map(|i| i.opcode).collect::<Vec<_>>();
+            let matches = opcodes == [OpCode::MemoryLoad, OpCode::Constant, OpCode::Eq, OpCode::Neg, OpCode::JumpIfFalse]
+                && window[0].u16(0) == register;
+            matches.then(|| (window[1].u16(0), window[4].u32(0) as usize))
+        };
+
+        let mut cases = vec![is_case(index.checked_sub(4)?)?];
+        let mut next = index + 1;
+        while let Some(case) = is_case(next) {
+            cases.push(case);
+            next += 5;
+        }
+        let default = self.instructions.get(next).filter(|i| i.opcode == OpCode::Jump)?.u32(0) as usize;
+
+        // The value popped is the comparison with the first case
+        Self::pop(stack);
+        let value = Expr::new(self.register(register));
+        let cases = cases.into_iter()
+            .map(|(constant, target)| (target, self.constant(constant)))
+            .collect();
+        Some(self.switch(value, cases, default, next + 1, end, follow, declared, stack))
+    }
+
+    // A switch on contiguous values: a jump per value follows the JumpTable
+    #[allow(clippy::too_many_arguments)]
+    fn jump_table(&mut self, index: usize, value: Expr, end: usize, follow: usize, declared: &mut HashSet<u16>, stack: &[Expr]) -> usize {
+        let instruction = &self.instructions[index];
+        let default = instruction.u32(0) as usize;
+        let first = self.symbols.module.get_constant_at(instruction.u16(4) as usize);
+        let len = instruction.u16(6) as usize;
+
+        let cases = (0..len)
+            .filter_map(|i| self.instructions.get(index + 1 + i).map(|jump| (jump.u32(0) as usize, Self::case_value(self.symbols, first, i))))
+            .collect();
+        self.switch(value, cases, default, index + 1 + len, end, follow, declared, stack)
+    }
+
+    // Value of the case at a position of a jump table
+    fn case_value(symbols: &Symbols, first: Option<&Constant>, position: usize) -> String {
+        let Some(Constant::Default(first)) = first else {
+            return "?".to_owned()
+        };
+
+        let value = first.clone()
+            .cast_to_u256()
+            .ok()
+            .and_then(|v| v.checked_add(U256::from(position as u64)));
+        let value = match (value, first) {
+            (Some(v), Value::U8(_)) => Value::U8(v.low_u64() as u8),
+            (Some(v), Value::U16(_)) => Value::U16(v.low_u64() as u16),
+            (Some(v), Value::U32(_)) => Value::U32(v.low_u64() as u32),
+            (Some(v), Value::U64(_)) => Value::U64(v.low_u64()),
+            (Some(v), Value::U128(_)) => Value::U128(v.low_u128()),
+            (Some(v), _) => Value::U256(v),
+            (None, _) => return "?".to_owned()
+        };
+        symbols.value(&value)
+    }
+
+    // Write the cases laid out after the dispatch in the order of their offsets,
+    // a case ends with a jump to the end of the switch unless it falls through the next one
+    #[allow(clippy::too_many_arguments)]
+    fn switch(&mut self, value: Expr, cases: Vec<(usize, String)>, default: usize, start: usize, end: usize, follow: usize, declared: &mut HashSet<u16>, stack: &[Expr]) -> usize {
+        let value = self.switch_value(value);
+
+        // Values grouped by the case they jump to
+        let mut targets: Vec<(usize, Vec<String>)> = Vec::new();
+        for (target, value) in cases {
+            match targets.iter_mut().find(|(t, _)| *t == target) {
+                Some((_, values)) => values.push(value),
+                None => targets.push((target, vec![value]))
+            }
+        }
+        targets.sort_by_key(|(target, _)| *target);
+
+        // Cases laid out before the default have a body, the others jump to the end of the switch
+        // or to the default once the jumps are collapsed
+        let default_index = self.index_of(default).unwrap_or(end).clamp(start, end);
+        let (targets, outer): (Vec<_>, Vec<_>) = targets.into_iter()
+            .partition(|(target, _)| self.index_of(*target).is_some_and(|i| i >= start && i < default_index));
+        let (to_default, empty): (Vec<_>, Vec<_>) = outer.into_iter()
+            .partition(|(target, _)| self.resolve(*target) == self.resolve(default));
+
+        let bodies = targets.iter()
+            .enumerate()
+            .map(|(i, (target, _))| {
+                let from = self.index_of(*target).unwrap_or(default_index);
+                let to = targets.get(i + 1)
+                    .and_then(|(next, _)| self.index_of(*next))
+                    .unwrap_or(default_index);
+                (from, to)
+            })
+            .collect::<Vec<_>>();
+
+        // The end of the switch is the target of the jumps ending the cases
+        let stop = bodies.iter()
+            .filter_map(|(from, to)| self.instructions.get(*to - 1).filter(|_| to > from))
+            .filter(|i| i.opcode == OpCode::Jump && self.loop_jump(i.u32(0) as usize).is_none())
+            .map(|i| i.u32(0) as usize)
+            .chain(empty.iter().map(|(target, _)| *target))
+            .filter_map(|target| self.index_of(target))
+            .find(|stop| *stop >= default_index && *stop <= end)
+            .unwrap_or(end);
+        let after = if stop == end { follow } else { self.offset_at(stop) };
+
+        self.push(format!("switch {} {{", value));
+        self.indent += 1;
+        for (_, values) in &empty {
+            self.push(format!("case {}:", values.join(", ")));
+        }
+        for ((_, values), (from, to)) in targets.iter().zip(bodies) {
+            self.push(format!("case {}:", values.join(", ")));
+            self.scope(from, to, after, declared, stack);
+            if to > from && !self.instructions[to - 1].ends_flow() {
+                self.indent += 1;
+                self.push("fallthrough");
+                self.indent -= 1;
+            }
+        }
+        for (_, values) in &to_default {
+            self.push(format!("case {}:", values.join(", ")));
+            if default_index < stop {
+                self.indent += 1;
+                self.push("fallthrough");
+                self.indent -= 1;
+            }
+        }
+        if default_index < stop {
+            self.push("default:");
+            self.scope(default_index, stop, after, declared, stack);
+        }
+        self.indent -= 1;
+        self.push("}");
+
+        stop
+    }
+}
@@ -0,0 +1,341 @@
+// Decompilation of the modules into a readable pseudo-source
+// Auditors can review a module deployed without its source: the structured control flow
+// (if/else, ternaries, while, for and foreach loops, switches) and the expressions are rebuilt
+// from the patterns emitted by the compiler, using the debug info for the names and lines when present.
+// The output is close to the language but not guaranteed to compile: the types of the locals,
+// the names of the module types and of their fields aren't stored in the bytecode,
+// they are written as `any`, `StructN` and `fieldN`.
+// A jump that can't be structured is written as a goto to a label.
+
+mod analysis;
+mod chunk;
+
+#[cfg(test)]
+mod tests;
+
+use core::fmt::Write;
+use std::collections::HashSet;
+use thiserror::Error;
+use xelis_builder::{Builder, EnvironmentBuilder};
+use xelis_bytecode::{Module, OpCode};
+use xelis_types::{Constant, IdentifierType, Type, Value};
+
+use analysis::{Effects, Instruction};
+use chunk::ChunkDecompiler;
+
+#[derive(Debug, Error, PartialEq, Eq)]
+pub enum DecompilerError {
+    #[error("invalid opcode at {offset} in chunk {chunk}")]
+    InvalidOpCode {
+        chunk: usize,
+        offset: usize
+    },
+    #[error("chunk {0} not found")]
+    ChunkNotFound(usize),
+    #[error(transparent)]
+    Fmt(#[from] core::fmt::Error),
+}
+
+// Rebuild a pseudo-source from a module
+pub struct Decompiler<'a> {
+    module: &'a Module,
+    environment: &'a EnvironmentBuilder<'a>,
+}
+
+// Names and signatures known about the module and its environment
+struct Symbols<'a> {
+    module: &'a Module,
+    environment: &'a EnvironmentBuilder<'a>,
+    // Decoded instructions of each chunk
+    chunks: Vec<Vec<Instruction>>,
+    // Count of parameters of each chunk, the instance included
+    parameters: Vec<usize>,
+    // Chunks called on a value, their first parameter is the instance
+    instances: HashSet<usize>,
+    effects: Effects,
+}
+
+impl<'a> Decompiler<'a> {
+    // Create a new decompiler for the module
+    // The environment provides the names of the native functions and types
+    pub fn new(module: &'a Module, environment: &'a EnvironmentBuilder<'a>) -> Self {
+        Self {
+            module,
+            environment
+        }
+    }
+
+    // Decompile the whole module: its types then its chunks
+    pub fn decompile(&self) -> Result<String, DecompilerError> {
+        let symbols = Symbols::new(self.module, self.environment)?;
+
+        let mut output = String::new();
+        for (index, struct_type) in self.module.structs().iter().enumerate() {
+            let fields = struct_type.fields()
+                .iter()
+                .enumerate()
+                .map(|(i, t)| format!("field{}: {}", i, symbols.type_name(t)))
+                .collect::<Vec<_>>();
+            writeln!(output, "struct {} {{ {} }}", symbols.struct_name(struct_type.id()), fields.join(", "))?;
+            if let Some(chunk) = self.module.get_migration(index) {
+                writeln!(output, "// migrated by {}", symbols.chunk_name(chunk))?;
+            }
+        }
+
+        for enum_type in self.module.enums() {
+            let variants = enum_type.variants()
+                .iter()
+                .enumerate()
+                .map(|(i, variant)| {
+                    if variant.fields().is_empty() {
+                        return format!("Variant{}", i)
+                    }
+                    let fields = variant.fields()
+                        .iter()
+                        .enumerate()
+                        .map(|(i, t)| format!("field{}: {}", i, symbols.type_name(t)))
+                        .collect::<Vec<_>>();
+                    format!("Variant{} {{ {} }}", i, fields.join(", "))
+                })
+                .collect::<Vec<_>>();
+            writeln!(output, "enum {} {{ {} }}", symbols.enum_name(enum_type.id()), variants.join(", "))?;
+        }
+
+        for (name, data) in self.module.events() {
+            writeln!(output, "// event {}: {}", name, symbols.type_name(data))?;
+        }
+
+        for id in 0..self.module.chunks().len() {
+            if !output.is_empty() {
+                writeln!(output)?;
+            }
+            output.push_str(&ChunkDecompiler::new(&symbols, id).decompile()?);
+        }
+
+        Ok(output)
+    }
+
+    // Decompile a single chunk
+    pub fn decompile_chunk(&self, id: usize) -> Result<String, DecompilerError> {
+        if id >= self.module.chunks().len() {
+            return Err(DecompilerError::ChunkNotFound(id));
+        }
+
+        let symbols = Symbols::new(self.module, self.environment)?;
+        ChunkDecompiler::new(&symbols, id).decompile()
+    }
+}
+
+impl<'a> Symbols<'a> {
+    fn new(module: &'a Module, environment: &'a EnvironmentBuilder<'a>) -> Result<Self, DecompilerError> {
+        let chunks = module.chunks()
+            .iter()
+            .enumerate()
+            .map(|(id, chunk)| analysis::decode(id, chunk))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let instances = chunks.iter()
+            .flatten()
+            .filter(|instruction| instruction.opcode == OpCode::InvokeChunk && instruction.u8(2) != 0)
+            .map(|instruction| instruction.u16(0) as usize)
+            .collect::<HashSet<_>>();
+
+        let parameters = chunks.iter()
+            .enumerate()
+            .map(|(id, instructions)| match module.get_signature(id) {
+                Some(signature) => signature.parameters().len() + instances.contains(&id) as usize,
+                None => analysis::parameters_count(instructions)
+            })
+            .collect::<Vec<_>>();
+
+        let mut structs = environment.environment()
+            .get_structures()
+            .iter()
+            .map(|s| s.fields().len())
+            .collect::<Vec<_>>();
+        structs.extend(module.structs().iter().map(|s| s.fields().len()));
+
+        let enums = environment.environment()
+            .get_enums()
+            .iter()
+            .chain(module.enums())
+            .map(|e| (e.id(), e.variants().iter().map(|v| v.fields().len()).collect()))
+            .collect();
+
+        let mut effects = Effects {
+            natives: environment.get_functions()
+                .iter()
+                .map(|f| f.return_type().is_some())
+                .collect(),
+            chunks: (0..chunks.len())
+                .map(|id| module.get_signature(id).is_some_and(|s| s.return_type().is_some()))
+                .collect(),
+            structs,
+            enums,
+        };
+
+        // A chunk without signature returns a value if it returns with a value on the stack
+        // Its callers push a value once it is known, so repeat until nothing changes
+        let mut changed = true;
+        while changed {
+            changed = false;
+            for (id, instructions) in chunks.iter().enumerate() {
+                if effects.chunks[id] || module.get_signature(id).is_some() {
+                    continue
+                }
+
+                let depths = analysis::stack_depths(instructions, parameters[id], &effects);
+                if analysis::returns_value(instructions, &depths) {
+                    effects.chunks[id] = true;
+                    changed = true;
+                }
+            }
+        }
+
+        Ok(Self {
+            module,
+            environment,
+            chunks,
+            parameters,
+            instances,
+            effects
+        })
+    }
+
+    // Name of a chunk: from the debug info, its export or its id
+    fn chunk_name(&self, id: usize) -> String {
+        if let Some(name) = self.module.debug_info().and_then(|info| info.get_chunk_name(id)) {
+            return name.to_owned()
+        }
+
+        self.module.exports()
+            .iter()
+            .chain(self.module.tests())
+            .find(|(_, chunk)| **chunk == id)
+            .map(|(name, _)| name.clone())
+            .unwrap_or_else(|| format!("chunk_{}", id))
+    }
+
+    // Name of a native function and the type it is called on
+    fn native_name(&self, id: usize) -> (String, Option<&Type>) {
+        self.environment.get_functions_mapper()
+            .get_functions()
+            .into_iter()
+            .find(|(function_id, _, _)| *function_id as usize == id)
+            .map(|(_, signature, _)| (signature.get_name().clone(), signature.get_on_type().as_ref()))
+            .unwrap_or_else(|| (format!("native_{}", id), None))
+    }
+
+    fn struct_name(&self, id: IdentifierType) -> String {
+        self.environment.get_struct_manager()
+            .get_name_by_id(&id)
+            .map(str::to_owned)
+            .unwrap_or_else(|| format!("Struct{}", id))
+    }
+
+    // Names of the fields of a struct, only known for the environment ones
+    fn struct_fields(&self, id: IdentifierType, count: usize) -> Vec<String> {
+        match self.environment.get_struct_manager().get_by_id(&id) {
+            Ok(builder) => builder.names().iter().map(|name| name.to_string()).collect(),
+            Err(_) => (0..count).map(|i| format!("field{}", i)).collect()
+        }
+    }
+
+    fn enum_name(&self, id: IdentifierType) -> String {
+        self.environment.get_enum_manager()
+            .get_name_by_id(&id)
+            .map(str::to_owned)
+            .unwrap_or_else(|| format!("Enum{}", id))
+    }
+
+    // Name of an enum variant with the names of its fields
+    fn enum_variant(&self, id: IdentifierType, variant: u8, count: usize) -> (String, Vec<String>) {
+        match self.environment.get_enum_manager().get_by_id(&id) {
+            Ok(builder) => {
+                let name = builder.names()
+                    .get(variant as usize)
+                    .map(|name| name.to_string())
+                    .unwrap_or_else(|| format!("Variant{}", variant));
+                let fields = builder.builder_type()
+                    .variants()
+                    .get(variant as usize)
+                    .map(|fields| fields.iter().map(|(name, _)| name.to_string()).collect())
+                    .unwrap_or_default();
+                (name, fields)
+            },
+            Err(_) => (format!("Variant{}", variant), (0..count).map(|i| format!("field{}", i)).collect())
+        }
+    }
+
+    fn type_name(&self, _type: &Type) -> String {
+        match _type {
+            Type::Struct(s) => self.struct_name(s.id()),
+            Type::Enum(e) => self.enum_name(e.id()),
+            Type::Opaque(opaque) => self.environment.get_opaque_name(opaque)
+                .map_or_else(|| _type.to_string(), str::to_owned),
+            Type::Array(inner) => format!("{}[]", self.type_name(inner)),
+            Type::Optional(inner) => format!("optional<{}>", self.type_name(inner)),
+            Type::Range(inner) => format!("range<{}>", self.type_name(inner)),
+            Type::Map(key, value) => format!("map<{}, {}>", self.type_name(key), self.type_name(value)),
+            Type::Tuple(types) => format!("({})", types.iter().map(|t| self.type_name(t)).collect::<Vec<_>>().join(", ")),
+            _ => _type.to_string()
+        }
+    }
+
+    fn value(&self, value: &Value) -> String {
+        match value {
+            Value::Null => "null".to_owned(),
+            Value::U64(v) => v.to_string(),
+            Value::U8(_) | Value::U16(_) | Value::U32(_) | Value::U128(_) | Value::U256(_) => match value.get_type() {
+                Ok(t) => format!("({} as {})", value, t),
+                Err(_) => value.to_string()
+            },
+            Value::String(s) => format!("{:?}", s),
+            Value::Boolean(b) => b.to_string(),
+            Value::Range(start, end, _, inclusive) => {
+                format!("({}..{}{})", self.value(start), if *inclusive { "=" } else { "" }, self.value(end))
+            },
+            Value::Blob(bytes) => format!("blob({:?})", bytes),
+        }
+    }
+
+    fn constant(&self, constant: &Constant) -> String {
+        match constant {
+            Constant::Default(v) => self.value(v),
+            Constant::Struct(fields, struct_type) => {
+                let names = self.struct_fields(struct_type.id(), fields.len());
+                let fields = names.iter()
+                    .zip(fields)
+                    .map(|(name, field)| format!("{}: {}", name, self.constant(field)))
+                    .collect::<Vec<_>>();
+                format!("{} {{ {} }}", self.struct_name(struct_type.id()), fields.join(", "))
+            },
+            Constant::Array(values) => {
+                format!("[{}]", values.iter().map(|v| self.constant(v)).collect::<Vec<_>>().join(", "))
+            },
+            Constant::Optional(value) => match value {
+                Some(value) => self.constant(value),
+                None => "null".to_owned()
+            },
+            Constant::Map(entries) => {
+                let entries = entries.iter()
+                    .map(|(k, v)| format!("{}: {}", self.constant(k), self.constant(v)))
+                    .collect::<Vec<_>>();
+                format!("{{{}}}", entries.join(", "))
+            },
+            Constant::Enum(fields, enum_type) => {
+                let (variant, names) = self.enum_variant(enum_type.id(), enum_type.variant_id(), fields.len());
+                let name = format!("{}::{}", self.enum_name(enum_type.id()), variant);
+                if fields.is_empty() {
+                    return name
+                }
+
+                let fields = names.iter()
+                    .zip(fields)
+                    .map(|(name, field)| format!("{}: {}", name, self.constant(field)))
+                    .collect::<Vec<_>>();
+                format!("{} {{ {} }}", name, fields.join(", "))
+            }
+        }
+    }
+}
@@ -0,0 +1,170 @@
+use xelis_builder::EnvironmentBuilder;
+use xelis_compiler::{Compiler, OptimizationLevel};
+use xelis_lexer::Lexer;
+use xelis_parser::Parser;
+use super::*;
+
+fn decompile_with(code: &str, level: OptimizationLevel, debug_info: bool) -> String {
+    let tokens: Vec<_> = Lexer::new(code).collect::<Result<_, _>>().unwrap();
+    let environment = EnvironmentBuilder::default();
+    let (program, _) = Parser::with(tokens.into_iter(), &environment).parse().unwrap();
+
+    let mut compiler = Compiler::new(&program, environment.environment())
+        .with_optimizations(level);
+    compiler.set_debug_info(debug_info);
+    let module = compiler.compile().unwrap();
+
+    Decompiler::new(&module, &environment)
+        .decompile()
+        .unwrap()
+}
+
+fn decompile(code: &str) -> String {
+    decompile_with(code, OptimizationLevel::None, false)
+}
+
+const CONTROL_FLOW: &str = r#"
+    fn sum(values: u64[]) -> u64 {
+        let total: u64 = 0;
+        foreach value in values {
+            if value == 3 {
+                continue
+            }
+            total += value
+        }
+        return total
+    }
+
+    fn max(a: u64, b: u64) -> u64 {
+        return a > b ? a : b
+    }
+
+    entry main() {
+        let values: u64[] = [1, 2, 3];
+        let count: u64 = 0;
+        for i: u64 = 0; i < 10; i += 1 {
+            if (i > 5) && (i != 7) {
+                break
+            } else {
+                count += 1
+            }
+        }
+        while count > 0 {
+            count -= 1
+        }
+        if (count == 0) || (values.len() > 2) {
+            count = sum(values)
+        }
+        return max(count, 2)
+    }
+"#;
+
+const TYPES: &str = r#"
+    struct Point { x: u64, y: u64 }
+    enum Shape {
+        Empty,
+        Square { corner: Point, size: u64 }
+    }
+
+    fn (p Point) area() -> u64 {
+        return p.x * p.y
+    }
+
+    fn parse(value: u64) -> Result<u64, string> {
+        if value == 0 {
+            return Err("zero")
+        }
+        return Ok(value * 2)
+    }
+
+    fn sum(a: u64, b: u64) -> Result<u64, string> {
+        let x: u64 = parse(a)?;
+        return Ok(x + (parse(b)?))
+    }
+
+    fn code(op: u64) -> u64 {
+        switch op {
+            case 10: return 1
+            case 11:
+            case 12: return 3
+            default: op += 1
+        }
+        return op
+    }
+
+    pub fn square(size: u64) -> Shape {
+        return Shape::Square { corner: Point { x: 0, y: 0 }, size: size }
+    }
+
+    entry main() {
+        let p: Point = Point { x: 1, y: 2 };
+        let r: Result<u64, string> = sum(1, 2);
+        return p.area() + code(12) + r.unwrap_or(0)
+    }
+"#;
+
+#[test]
+fn test_decompile_control_flow() {
+    let source = decompile(CONTROL_FLOW);
+
+    assert!(source.contains("fn chunk_0(param0: any) -> any {"));
+    assert!(source.contains("foreach v2 in param0 {"));
+    assert!(source.contains("continue"));
+    assert!(source.contains("return ((param0 > param1) ? param0 : param1)"));
+    assert!(source.contains("entry main() {"));
+    assert!(source.contains("for v2 = 0; (v2 < 10); v2 += 1 {"));
+    assert!(source.contains("if ((v2 > 5) && (v2 != 7)) {\n            break\n        } else {\n            v1 += 1\n        }"));
+    assert!(source.contains("while (v1 > 0) {\n        v1 -= 1\n    }"));
+    assert!(source.contains("if ((v1 == 0) || (v0.len() > (2 as u32))) {\n        v1 = chunk_0(v0)\n    }"));
+    assert!(!source.contains("goto"));
+}
+
+#[test]
+fn test_decompile_optimized_with_debug_info() {
+    let source = decompile_with(CONTROL_FLOW, OptimizationLevel::Full, true);
+
+    assert!(source.contains("fn sum(param0: any) -> any {"));
+    assert!(source.contains("fn max(param0: any, param1: any) -> any {"));
+    assert!(source.contains("let v1 = 0 // line 3"));
+    assert!(source.contains("for v2 = 0; (v2 < 10); v2 += 1 {"));
+    assert!(source.contains("v1 = sum(v0) // line 31"));
+    assert!(!source.contains("goto"));
+}
+
+#[test]
+fn test_decompile_types_and_calls() {
+    let source = decompile_with(TYPES, OptimizationLevel::None, true);
+
+    assert!(source.contains("enum Enum0 { Variant0, Variant1 { field0: Struct0, field1: u64 } }"));
+    assert!(source.contains("fn (self any) area() -> any {\n    return (self.field0 * self.field1)"));
+    assert!(source.contains("return Result::Err { error: \"zero\" }"));
+    assert!(source.contains("let v2 = parse(param0)?"));
+    assert!(source.contains("return Result::Ok { value: (v2 + parse(param1)?) }"));
+    assert!(source.contains("switch param0 {\n        case 10:"));
+    assert!(source.contains("case 11:\n        case 12:\n            return 3"));
+    assert!(source.contains("default:\n            param0 += 1"));
+    // Exported chunks keep their signature
+    assert!(source.contains("pub fn square(size: u64) -> Enum0 {"));
+    assert!(source.contains("Enum0::Variant1 { field0: Struct0 { field0: 0, field1: 0 }, field1: size }"));
+    assert!(source.contains("(v0.area() + (code(12) + v1.unwrap_or(0)))"));
+}
+
+#[test]
+fn test_decompile_collapsed_switch() {
+    // The jump of the empty case goes to the end of the switch once collapsed
+    let source = decompile_with(TYPES, OptimizationLevel::Full, false);
+    assert!(source.contains("switch param0 {\n        case 11:\n        case 10:\n            return 1"));
+    assert!(source.contains("    }\n    return param0\n}"));
+}
+
+#[test]
+fn test_decompile_chunk() {
+    let tokens: Vec<_> = Lexer::new(CONTROL_FLOW).collect::<Result<_, _>>().unwrap();
+    let environment = EnvironmentBuilder::default();
+    let (program, _) = Parser::with(tokens.into_iter(), &environment).parse().unwrap();
+    let module = Compiler::new(&program, environment.environment()).compile().unwrap();
+
+    let decompiler = Decompiler::new(&module, &environment);
+    assert!(decompiler.decompile_chunk(1).unwrap().starts_with("// chunk 1\nfn chunk_1("));
+    assert_eq!(decompiler.decompile_chunk(3), Err(DecompilerError::ChunkNotFound(3)));
+}
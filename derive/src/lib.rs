@@ -0,0 +1,113 @@
+use proc_macro::TokenStream;
+use proc_macro2::TokenStream as TokenStream2;
+use quote::quote;
+use syn::{parse_macro_input, Data, DeriveInput, Error, Fields, Index};
+
+// Derive `FromXelisValue` for a struct
+// The VM struct fields are read in the declaration order of the Rust fields
+#[proc_macro_derive(FromXelisValue)]
+pub fn derive_from_xelis_value(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    expand_from(input)
+        .unwrap_or_else(Error::into_compile_error)
+        .into()
+}
+
+// Derive `IntoXelisValue` for a struct
+// The expected type must be the struct type registered in the environment
+#[proc_macro_derive(IntoXelisValue)]
+pub fn derive_into_xelis_value(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    expand_into(input)
+        .unwrap_or_else(Error::into_compile_error)
+        .into()
+}
+
+// Only structs are supported, enums have no equivalent Rust layout
+fn struct_fields(input: &DeriveInput) -> Result<&Fields, Error> {
+    match &input.data {
+        Data::Struct(data) => Ok(&data.fields),
+        _ => Err(Error::new_spanned(&input.ident, "only structs can be converted to a VM value"))
+    }
+}
+
+fn expand_from(input: DeriveInput) -> Result<TokenStream2, Error> {
+    let fields = struct_fields(&input)?;
+    let name = &input.ident;
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+    let len = fields.len();
+
+    let read = quote! {
+        ::xelis_types::FromXelisValue::from_xelis_value(fields.next().unwrap().into_owned())?
+    };
+    let body = match fields {
+        Fields::Named(named) => {
+            let idents = named.named.iter().map(|f| &f.ident);
+            quote!(Self { #(#idents: #read,)* })
+        },
+        Fields::Unnamed(unnamed) => {
+            let reads = unnamed.unnamed.iter().map(|_| &read);
+            quote!(Self(#(#reads,)*))
+        },
+        Fields::Unit => quote!(Self)
+    };
+
+    Ok(quote! {
+        impl #impl_generics ::xelis_types::FromXelisValue for #name #ty_generics #where_clause {
+            #[allow(unused_mut, unused_variables)]
+            fn from_xelis_value(value: ::xelis_types::ValueCell) -> Result<Self, ::xelis_types::ValueError> {
+                let mut fields = match value {
+                    ::xelis_types::ValueCell::Struct(fields, _) if fields.len() == #len => fields.into_iter(),
+                    _ => return Err(::xelis_types::ValueError::ExpectedStruct)
+                };
+
+                Ok(#body)
+            }
+        }
+    })
+}
+
+fn expand_into(input: DeriveInput) -> Result<TokenStream2, Error> {
+    let fields = struct_fields(&input)?;
+    let name = &input.ident;
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+    let len = fields.len();
+
+    let accessors: Vec<TokenStream2> = match fields {
+        Fields::Named(named) => named.named.iter()
+            .map(|f| {
+                let ident = &f.ident;
+                quote!(self.#ident)
+            })
+            .collect(),
+        Fields::Unnamed(unnamed) => (0..unnamed.unnamed.len())
+            .map(|i| {
+                let index = Index::from(i);
+                quote!(self.#index)
+            })
+            .collect(),
+        Fields::Unit => Vec::new()
+    };
+    let values = accessors.iter()
+        .enumerate()
+        .map(|(i, accessor)| quote! {
+            values.push(::xelis_types::IntoXelisValue::into_xelis_value(#accessor, &struct_type.fields()[#i])?.into());
+        });
+
+    Ok(quote! {
+        impl #impl_generics ::xelis_types::IntoXelisValue for #name #ty_generics #where_clause {
+            #[allow(unused_mut)]
+            fn into_xelis_value(self, _type: &::xelis_types::Type) -> Result<::xelis_types::ValueCell, ::xelis_types::ValueError> {
+                let struct_type = match _type {
+                    ::xelis_types::Type::Struct(struct_type) if struct_type.fields().len() == #len => struct_type,
+                    _ => return Err(::xelis_types::ValueError::ExpectedStruct)
+                };
+
+                let mut values = Vec::with_capacity(#len);
+                #(#values)*
+
+                Ok(::xelis_types::ValueCell::Struct(values.into(), struct_type.clone()))
+            }
+        }
+    })
+}
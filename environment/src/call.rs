@@ -0,0 +1,32 @@
+use crate::MaybeSend;
+
+// A call from a contract to the export of another one
+// Given to the host before the callee is executed
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CallRequest<'b> {
+    // Contract doing the call, none if the host didn't set it
+    pub caller: Option<&'b str>,
+    // Name the called module is linked with
+    pub callee: &'b str,
+    // Name of the export called
+    pub export: &'b str,
+    // Count of the calls between contracts in progress
+    pub depth: usize,
+    // Is the caller executed in read-only
+    pub read_only: bool,
+}
+
+// Decision of the host for a call between contracts
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CallPermission {
+    Allowed,
+    // The callee can't update the state of the host
+    ReadOnly,
+    Denied,
+}
+
+// Hook deciding which calls between contracts are executed
+// Used by the hosts to sandbox the contracts
+pub trait CallAuthorizer: MaybeSend {
+    fn authorize(&mut self, request: CallRequest) -> CallPermission;
+}
@@ -0,0 +1,40 @@
+// Data of the chain given by the host for an execution, inserted in the Context
+// Each value is optional, the natives reading a missing one return an error
+// The VM never reads the clock of the OS, so the executions stay deterministic
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ChainInfo {
+    // Timestamp of the block in milliseconds since the unix epoch
+    pub timestamp: Option<u64>,
+    // Height of the block being executed
+    pub block_height: Option<u64>,
+    // Hash of the block being executed
+    pub block_hash: Option<[u8; 32]>,
+    // Hash of the transaction being executed
+    pub tx_hash: Option<[u8; 32]>,
+}
+
+impl ChainInfo {
+    // Set the timestamp of the block
+    pub fn with_timestamp(mut self, timestamp: u64) -> Self {
+        self.timestamp = Some(timestamp);
+        self
+    }
+
+    // Set the height of the block
+    pub fn with_block_height(mut self, height: u64) -> Self {
+        self.block_height = Some(height);
+        self
+    }
+
+    // Set the hash of the block
+    pub fn with_block_hash(mut self, hash: [u8; 32]) -> Self {
+        self.block_hash = Some(hash);
+        self
+    }
+
+    // Set the hash of the transaction
+    pub fn with_tx_hash(mut self, hash: [u8; 32]) -> Self {
+        self.tx_hash = Some(hash);
+        self
+    }
+}
@@ -1,6 +1,8 @@
-use std::{any::{Any, TypeId}, collections::HashMap, hash::{BuildHasherDefault, Hasher}};
+use core::{any::TypeId, hash::{BuildHasherDefault, Hasher}};
+use alloc::{boxed::Box, string::String, vec::Vec};
+use xelis_types::{HashMap, Path, Value, ValueCell};
 
-use crate::EnvironmentError;
+use crate::{AnyData, CallAuthorizer, CallPermission, CallRequest, EnvironmentError, Event, MaybeSync, StorageProvider, Tracer};
 
 // A hasher for `TypeId`s that takes advantage of its known characteristics.
 #[derive(Debug, Default)]
@@ -20,11 +22,33 @@ impl Hasher for NoOpHasher {
     }
 }
 
+// State of the Context saved before an execution
+// Used to roll back the storage writes, events and refunds on a revert
+// Checkpoints are nested, each one must be committed or rolled back once
+#[derive(Debug, PartialEq, Eq)]
+pub struct StateCheckpoint {
+    // Count of the checkpoints opened before this one
+    depth: usize,
+    journal: usize,
+    calls: usize,
+    events: usize,
+    events_size: usize,
+    gas_refund: u64,
+}
+
+// Contract executed by a call from another one
+#[derive(Debug)]
+struct CallFrame {
+    contract: String,
+    // Read-only flag of the caller, restored at the return
+    read_only: bool,
+}
+
 // Data is a wrapper around Any that allows for borrowed and mutable references.
 pub enum Data<'a> {
-    Owned(Box<dyn Any>),
-    Borrowed(&'a dyn Any),
-    Mut(&'a mut dyn Any),
+    Owned(Box<AnyData>),
+    Borrowed(&'a AnyData),
+    Mut(&'a mut AnyData),
 }
 
 impl<'a> Data<'a> {
@@ -67,6 +91,10 @@ impl<'a> Data<'a> {
     }
 }
 
+// Memory charged for each value stored in an array, a struct, an enum or a map
+// Fixed so the accounting is the same on every platform
+pub const VALUE_MEMORY_USAGE: usize = 32;
+
 // Context is a simple data store that allows for storing and retrieving values of different types.
 pub struct Context<'a> {
     data: HashMap<TypeId, Data<'a>, BuildHasherDefault<NoOpHasher>>,
@@ -76,11 +104,54 @@ pub struct Context<'a> {
     max_gas: u64,
     // Price per byte of memory
     memory_price_per_byte: u64,
+    // Bytes of memory used by the live values of the execution
+    memory_usage: usize,
+    // Max bytes of memory the execution can allocate
+    max_memory: usize,
     // Max value depth allowed
     // This is used to prevent stack overflow attacks
     max_value_depth: usize,
     // Current gas used in the execution
     current_gas: u64,
+    // Gas given back by the natives, like when freeing storage
+    // Only deducted from the gas usage at the end of the execution
+    gas_refund: u64,
+    // Max percent of the gas used which can be refunded
+    max_gas_refund_percent: u64,
+    // Seed of the random generators, provided by the host
+    // The execution never reads the OS entropy to stay reproducible
+    random_seed: Option<[u8; 32]>,
+    // Optional hooks called during the execution
+    // Borrowed so the host can read it back after the execution
+    tracer: Option<&'a mut dyn Tracer>,
+    // Persistent storage of the host
+    storage: Option<&'a mut dyn StorageProvider>,
+    // Previous values of the keys written since the outermost checkpoint
+    // Replayed in reverse order to roll back the storage
+    journal: Vec<(Vec<u8>, Option<Vec<u8>>)>,
+    // Count of the checkpoints opened
+    // The writes are only journaled while one is open
+    checkpoints: usize,
+    // Events emitted during the execution
+    events: Vec<Event>,
+    // Total size of the emitted events
+    events_size: usize,
+    // Max events allowed in an execution
+    max_events: usize,
+    // Max total size in bytes of the events
+    max_events_size: usize,
+    // Reject the natives updating the state of the host
+    // Used for the view and simulation calls
+    read_only: bool,
+    // Contract invoked by the host
+    contract: Option<String>,
+    // Account or contract which asked the host for the execution
+    caller: Option<String>,
+    // Contracts called by the executed one, the last is the current one
+    calls: Vec<CallFrame>,
+    // Hook deciding the calls between contracts
+    // Without it, all the calls are allowed
+    call_authorizer: Option<&'a mut dyn CallAuthorizer>,
 }
 
 impl Default for Context<'_> {
@@ -96,8 +167,26 @@ impl<'a> Context<'a> {
             data: HashMap::default(),
             max_gas: u64::MAX,
             current_gas: 0,
+            gas_refund: 0,
+            max_gas_refund_percent: 20,
             memory_price_per_byte: 0,
+            memory_usage: 0,
+            max_memory: usize::MAX,
             max_value_depth: 16,
+            random_seed: None,
+            tracer: None,
+            storage: None,
+            journal: Vec::new(),
+            checkpoints: 0,
+            events: Vec::new(),
+            events_size: 0,
+            max_events: 64,
+            max_events_size: 64 * 1024,
+            read_only: false,
+            contract: None,
+            caller: None,
+            calls: Vec::new(),
+            call_authorizer: None,
         }
     }
 
@@ -119,12 +208,165 @@ impl<'a> Context<'a> {
         self.memory_price_per_byte
     }
 
+    // Set the max bytes of memory the execution can allocate
+    #[inline(always)]
+    pub fn set_max_memory(&mut self, bytes: usize) {
+        self.max_memory = bytes;
+    }
+
+    // Get the max bytes of memory the execution can allocate
+    #[inline(always)]
+    pub fn max_memory(&self) -> usize {
+        self.max_memory
+    }
+
+    // Get the bytes of memory currently allocated
+    #[inline(always)]
+    pub fn current_memory_usage(&self) -> usize {
+        self.memory_usage
+    }
+
+    // Charge newly allocated memory, paid with the memory price per byte
+    // An error is returned if the max memory is reached
+    pub fn increase_memory_usage(&mut self, bytes: usize) -> Result<(), EnvironmentError> {
+        self.increase_gas_usage((bytes as u64).saturating_mul(self.memory_price_per_byte))?;
+
+        let memory_usage = self.memory_usage.saturating_add(bytes);
+        if memory_usage > self.max_memory {
+            return Err(EnvironmentError::OutOfMemory)
+        }
+
+        self.memory_usage = memory_usage;
+        Ok(())
+    }
+
+    // Release memory no longer used, the gas paid is not refunded
+    #[inline]
+    pub fn decrease_memory_usage(&mut self, bytes: usize) {
+        self.memory_usage = self.memory_usage.saturating_sub(bytes);
+    }
+
+    // Bytes used by a value: VALUE_MEMORY_USAGE for each value it contains
+    // and the bytes of its strings and blobs
+    // The value itself is held by a slot charged by its owner
+    pub fn value_memory_usage(&self, value: &ValueCell) -> Result<usize, EnvironmentError> {
+        self.memory_usage_of(value, false)
+    }
+
+    // Charge a value materialized by the execution, like a loaded constant
+    #[inline]
+    pub fn increase_value_memory_usage(&mut self, value: &ValueCell) -> Result<(), EnvironmentError> {
+        let bytes = self.value_memory_usage(value)?;
+        self.increase_memory_usage(bytes)
+    }
+
+    // Charge a value stored in a collection or a variable
+    // A temporary value is moved with the memory charged when it was created,
+    // a copy is charged for all its content even while it shares it with its
+    // source, as the content is copied once one of them is mutated
+    // The copy walks the value to find the values referenced elsewhere,
+    // it costs 1 gas per inner value
+    pub fn increase_stored_memory_usage(&mut self, value: &Path) -> Result<(), EnvironmentError> {
+        if let Path::Owned(_) = value {
+            return Ok(())
+        }
+
+        let (values, bytes) = value.as_ref()
+            .memory_size(self.max_value_depth)?;
+        self.increase_gas_usage(values.saturating_sub(1) as u64)?;
+        self.increase_memory_usage(Self::bytes_of(values, bytes))
+    }
+
+    // Bytes freed when the value is dropped
+    // The values it shares with another owner or copy stay alive and aren't counted
+    pub fn owned_memory_usage(&self, value: &ValueCell) -> Result<usize, EnvironmentError> {
+        self.memory_usage_of(value, true)
+    }
+
+    // Release the memory of a value dropped by the execution
+    // A borrowed or still shared value isn't freed, the content shared
+    // with a copy is only released with the last of them
+    pub fn decrease_value_memory_usage(&mut self, value: &Path) -> Result<(), EnvironmentError> {
+        let bytes = match value {
+            Path::Owned(v) => self.owned_memory_usage(v)?,
+            Path::Wrapper(v) if !v.is_shared() => self.owned_memory_usage(&v.borrow())?,
+            _ => return Ok(())
+        };
+
+        self.decrease_memory_usage(bytes);
+        Ok(())
+    }
+
+    fn memory_usage_of(&self, value: &ValueCell, owned: bool) -> Result<usize, EnvironmentError> {
+        let (values, bytes) = match value {
+            ValueCell::Default(Value::String(s)) => return Ok(s.len()),
+            ValueCell::Default(Value::Blob(blob)) => return Ok(blob.len()),
+            ValueCell::Default(_) => return Ok(0),
+            // The depth is limited as a value may reference itself
+            value if owned => value.owned_memory_size(self.max_value_depth)?,
+            value => value.memory_size(self.max_value_depth)?
+        };
+
+        Ok(Self::bytes_of(values, bytes))
+    }
+
+    // The value itself is held by the slot of its owner
+    #[inline]
+    fn bytes_of(values: usize, bytes: usize) -> usize {
+        values.saturating_sub(1).saturating_mul(VALUE_MEMORY_USAGE).saturating_add(bytes)
+    }
+
     // Get the current gas usage
     #[inline(always)]
     pub fn current_gas_usage(&self) -> u64 {
         self.current_gas
     }
 
+    // Get the gas left before reaching the gas limit
+    #[inline(always)]
+    pub fn remaining_gas(&self) -> u64 {
+        self.max_gas.saturating_sub(self.current_gas)
+    }
+
+    // Refund gas, like when a native frees storage
+    // The refund doesn't raise the gas available to the execution
+    #[inline]
+    pub fn refund_gas(&mut self, gas: u64) {
+        self.gas_refund = self.gas_refund.saturating_add(gas);
+    }
+
+    // Get the gas refunded, before applying the cap
+    #[inline(always)]
+    pub fn gas_refund(&self) -> u64 {
+        self.gas_refund
+    }
+
+    // Set the max percent of the gas used which can be refunded
+    #[inline(always)]
+    pub fn set_max_gas_refund_percent(&mut self, percent: u64) {
+        assert!(percent <= 100, "Max gas refund must be a percentage");
+        self.max_gas_refund_percent = percent;
+    }
+
+    // Get the max percent of the gas used which can be refunded
+    #[inline(always)]
+    pub fn max_gas_refund_percent(&self) -> u64 {
+        self.max_gas_refund_percent
+    }
+
+    // Get the gas refunded, capped to the max percent of the gas used
+    #[inline]
+    pub fn effective_gas_refund(&self) -> u64 {
+        let cap = (self.current_gas as u128 * self.max_gas_refund_percent as u128 / 100) as u64;
+        self.gas_refund.min(cap)
+    }
+
+    // Get the gas to pay for the execution, once the refund is deducted
+    #[inline]
+    pub fn effective_gas_usage(&self) -> u64 {
+        self.current_gas - self.effective_gas_refund()
+    }
+
     // Get the max value depth allowed
     #[inline(always)]
     pub fn max_value_depth(&self) -> usize {
@@ -138,6 +380,161 @@ impl<'a> Context<'a> {
         self.max_value_depth = depth;
     }
 
+    // Set the seed used by the random generators
+    #[inline(always)]
+    pub fn set_random_seed(&mut self, seed: [u8; 32]) {
+        self.random_seed = Some(seed);
+    }
+
+    // Get the seed used by the random generators
+    #[inline(always)]
+    pub fn random_seed(&self) -> Option<&[u8; 32]> {
+        self.random_seed.as_ref()
+    }
+
+    // Set the max events allowed in an execution
+    #[inline(always)]
+    pub fn set_max_events(&mut self, count: usize) {
+        self.max_events = count;
+    }
+
+    // Set the max total size in bytes of the events
+    #[inline(always)]
+    pub fn set_max_events_size(&mut self, size: usize) {
+        self.max_events_size = size;
+    }
+
+    // Set if the execution is read-only
+    // The calls to the natives marked as mutating return a ReadOnlyViolation error
+    #[inline(always)]
+    pub fn set_read_only(&mut self, read_only: bool) {
+        self.read_only = read_only;
+    }
+
+    // Is the execution read-only
+    #[inline(always)]
+    pub fn is_read_only(&self) -> bool {
+        self.read_only
+    }
+
+    // Set the identity of the contract invoked by the host
+    #[inline]
+    pub fn set_contract(&mut self, contract: impl Into<String>) {
+        self.contract = Some(contract.into());
+    }
+
+    // Set the identity of the caller of the contract invoked by the host
+    #[inline]
+    pub fn set_caller(&mut self, caller: impl Into<String>) {
+        self.caller = Some(caller.into());
+    }
+
+    // Get the contract being executed
+    pub fn current_contract(&self) -> Option<&str> {
+        match self.calls.last() {
+            Some(frame) => Some(&frame.contract),
+            None => self.contract.as_deref()
+        }
+    }
+
+    // Get the caller of the contract being executed
+    pub fn caller(&self) -> Option<&str> {
+        match self.calls.len() {
+            0 => self.caller.as_deref(),
+            1 => self.contract.as_deref(),
+            len => Some(&self.calls[len - 2].contract)
+        }
+    }
+
+    // Count of the calls between contracts in progress
+    #[inline(always)]
+    pub fn call_depth(&self) -> usize {
+        self.calls.len()
+    }
+
+    // Set the hook deciding the calls between contracts
+    #[inline]
+    pub fn set_call_authorizer(&mut self, authorizer: &'a mut dyn CallAuthorizer) {
+        self.call_authorizer = Some(authorizer);
+    }
+
+    // Remove the call authorizer and returns it
+    #[inline]
+    pub fn take_call_authorizer(&mut self) -> Option<&'a mut dyn CallAuthorizer> {
+        self.call_authorizer.take()
+    }
+
+    // Start a call to the export of another contract
+    // The callee inherits the read-only flag of its caller
+    pub fn enter_call(&mut self, callee: &str, export: &str) -> Result<(), EnvironmentError> {
+        let read_only = self.read_only;
+        if let Some(authorizer) = self.call_authorizer.as_mut() {
+            let request = CallRequest {
+                caller: match self.calls.last() {
+                    Some(frame) => Some(&frame.contract),
+                    None => self.contract.as_deref()
+                },
+                callee,
+                export,
+                depth: self.calls.len(),
+                read_only,
+            };
+
+            match authorizer.authorize(request) {
+                CallPermission::Allowed => {},
+                CallPermission::ReadOnly => self.read_only = true,
+                CallPermission::Denied => return Err(EnvironmentError::CallDenied(callee.into()))
+            }
+        }
+
+        self.calls.push(CallFrame {
+            contract: callee.into(),
+            read_only,
+        });
+
+        Ok(())
+    }
+
+    // End the current call between contracts
+    pub fn exit_call(&mut self) {
+        if let Some(frame) = self.calls.pop() {
+            self.read_only = frame.read_only;
+        }
+    }
+
+    // Drop the calls above the depth, like when they failed
+    fn truncate_calls(&mut self, depth: usize) {
+        if let Some(frame) = self.calls.get(depth) {
+            self.read_only = frame.read_only;
+            self.calls.truncate(depth);
+        }
+    }
+
+    // Append an event, an error is returned if a limit is reached
+    pub fn push_event(&mut self, event: Event) -> Result<(), EnvironmentError> {
+        let size = self.events_size.saturating_add(event.size());
+        if self.events.len() >= self.max_events || size > self.max_events_size {
+            return Err(EnvironmentError::EventsLimitReached)
+        }
+
+        self.events_size = size;
+        self.events.push(event);
+        Ok(())
+    }
+
+    // Get the events emitted
+    #[inline(always)]
+    pub fn events(&self) -> &[Event] {
+        &self.events
+    }
+
+    // Remove all the events emitted and returns them
+    #[inline]
+    pub fn take_events(&mut self) -> Vec<Event> {
+        self.events_size = 0;
+        core::mem::take(&mut self.events)
+    }
+
     // Increase the gas usage by a specific amount
     #[inline]
     pub fn increase_gas_usage(&mut self, gas: u64) -> Result<(), EnvironmentError> {
@@ -153,19 +550,19 @@ impl<'a> Context<'a> {
 
     // Insert a borrowed value into the Context
     #[inline]
-    pub fn insert_ref<T: 'static>(&mut self, value: &'a T) {
+    pub fn insert_ref<T: MaybeSync + 'static>(&mut self, value: &'a T) {
         self.data.insert(TypeId::of::<T>(), Data::Borrowed(value));
     }
 
     // Insert a mutable value into the Context
     #[inline]
-    pub fn insert_mut<T: 'static>(&mut self, value: &'a mut T) {
+    pub fn insert_mut<T: MaybeSync + 'static>(&mut self, value: &'a mut T) {
         self.data.insert(TypeId::of::<T>(), Data::Mut(value));
     }
 
     // Insert an owned value into the Context
     #[inline]
-    pub fn insert<T: 'static>(&mut self, value: T) {
+    pub fn insert<T: MaybeSync + 'static>(&mut self, value: T) {
         self.data.insert(TypeId::of::<T>(), Data::Owned(Box::new(value)));
     }
 
@@ -181,6 +578,20 @@ impl<'a> Context<'a> {
         self.data.get_mut(&TypeId::of::<T>()).map(|v| v.downcast_mut()).flatten()
     }
 
+    // Get a value provided by the host, an error is returned if it's missing
+    // Used by the native functions reading the host data, like the block height
+    #[inline]
+    pub fn try_get<T: 'static>(&self) -> Result<&T, EnvironmentError> {
+        self.get().ok_or(EnvironmentError::MissingData(core::any::type_name::<T>()))
+    }
+
+    // Get a mutable value provided by the host, an error is returned if it's missing
+    // A value inserted with `insert_ref` can't be mutated and is reported as missing
+    #[inline]
+    pub fn try_get_mut<T: 'static>(&mut self) -> Result<&mut T, EnvironmentError> {
+        self.get_mut().ok_or(EnvironmentError::MissingData(core::any::type_name::<T>()))
+    }
+
     // Get an owned value from the Context
     #[inline]
     pub fn take<T: 'static>(&mut self) -> Option<T> {
@@ -209,11 +620,183 @@ impl<'a> Context<'a> {
     #[inline]
     pub fn reset_gas_usage(&mut self) {
         self.current_gas = 0;
+        self.gas_refund = 0;
+    }
+
+    // Reset the memory usage
+    #[inline]
+    pub fn reset_memory_usage(&mut self) {
+        self.memory_usage = 0;
+    }
+
+    // Set the tracer called during the execution
+    #[inline]
+    pub fn set_tracer(&mut self, tracer: &'a mut dyn Tracer) {
+        self.tracer = Some(tracer);
+    }
+
+    // Remove the tracer and returns it
+    #[inline]
+    pub fn take_tracer(&mut self) -> Option<&'a mut dyn Tracer> {
+        self.tracer.take()
+    }
+
+    // Check if a tracer is set
+    #[inline]
+    pub fn has_tracer(&self) -> bool {
+        self.tracer.is_some()
+    }
+
+    // Set the storage used by the storage functions
+    #[inline]
+    pub fn set_storage(&mut self, storage: &'a mut dyn StorageProvider) {
+        self.storage = Some(storage);
+    }
+
+    // Remove the storage and returns it
+    #[inline]
+    pub fn take_storage(&mut self) -> Option<&'a mut dyn StorageProvider> {
+        self.storage.take()
+    }
+
+    // Get the storage, an error is returned if the host didn't provide one
+    #[inline]
+    pub fn storage_mut(&mut self) -> Result<&mut (dyn StorageProvider + 'a), EnvironmentError> {
+        match self.storage.as_mut() {
+            Some(storage) => Ok(&mut **storage),
+            None => Err(EnvironmentError::MissingStorage)
+        }
+    }
+
+    // Store a value, the previous one is kept to be able to roll back
+    pub fn storage_set(&mut self, key: &[u8], value: Vec<u8>) -> Result<(), EnvironmentError> {
+        let storage = self.storage_mut()?;
+        let previous = storage.get(key)?;
+        storage.set(key, value)?;
+        self.record(key, previous);
+        Ok(())
+    }
+
+    // Delete a key, the previous value is kept to be able to roll back
+    // Returns true if the key was present
+    pub fn storage_delete(&mut self, key: &[u8]) -> Result<bool, EnvironmentError> {
+        let storage = self.storage_mut()?;
+        let previous = storage.get(key)?;
+        if previous.is_none() {
+            return Ok(false)
+        }
+
+        let deleted = storage.delete(key)?;
+        self.record(key, previous);
+        Ok(deleted)
+    }
+
+    // Keep the previous value of a key if a checkpoint may roll it back
+    #[inline]
+    fn record(&mut self, key: &[u8], previous: Option<Vec<u8>>) {
+        if self.checkpoints > 0 {
+            self.journal.push((key.to_vec(), previous));
+        }
+    }
+
+    // Count of the checkpoints opened
+    #[inline(always)]
+    pub fn checkpoints(&self) -> usize {
+        self.checkpoints
+    }
+
+    // Open a checkpoint to roll back to the current state later
+    // It can be opened while another one is, like for a sub execution
+    pub fn checkpoint(&mut self) -> StateCheckpoint {
+        let checkpoint = StateCheckpoint {
+            depth: self.checkpoints,
+            journal: self.journal.len(),
+            calls: self.calls.len(),
+            events: self.events.len(),
+            events_size: self.events_size,
+            gas_refund: self.gas_refund,
+        };
+        self.checkpoints += 1;
+
+        checkpoint
+    }
+
+    // Close a checkpoint with the ones opened after it
+    fn close(&mut self, checkpoint: &StateCheckpoint) -> Result<(), EnvironmentError> {
+        if checkpoint.depth >= self.checkpoints {
+            return Err(EnvironmentError::CheckpointClosed)
+        }
+
+        self.checkpoints = checkpoint.depth;
+        Ok(())
+    }
+
+    // Undo the storage writes, events, refunds and calls done since the checkpoint
+    // The gas consumed is kept
+    pub fn rollback(&mut self, checkpoint: StateCheckpoint) -> Result<(), EnvironmentError> {
+        self.close(&checkpoint)?;
+
+        let entries = self.journal.split_off(checkpoint.journal);
+        for (key, previous) in entries.into_iter().rev() {
+            let storage = self.storage_mut()?;
+            match previous {
+                Some(value) => storage.set(&key, value)?,
+                None => {
+                    storage.delete(&key)?;
+                }
+            }
+        }
+
+        self.truncate_calls(checkpoint.calls);
+        self.events.truncate(checkpoint.events);
+        self.events_size = checkpoint.events_size;
+        self.gas_refund = checkpoint.gas_refund;
+
+        Ok(())
+    }
+
+    // Keep the changes done since the checkpoint
+    // They can still be rolled back by the checkpoints opened before it
+    pub fn commit(&mut self, checkpoint: StateCheckpoint) -> Result<(), EnvironmentError> {
+        self.close(&checkpoint)?;
+
+        // The previous values aren't needed anymore once the outermost one is committed
+        if self.checkpoints == 0 {
+            self.journal.clear();
+        }
+
+        Ok(())
+    }
+
+    // Notify the tracer of the next instruction
+    #[inline(always)]
+    pub fn trace_instruction(&mut self, chunk_id: u16, offset: usize, opcode: u8) {
+        if let Some(tracer) = self.tracer.as_mut() {
+            tracer.on_instruction(chunk_id, offset, opcode, self.current_gas);
+        }
+    }
+
+    // Notify the tracer of a chunk invocation
+    #[inline(always)]
+    pub fn trace_call(&mut self, chunk_id: u16) {
+        if let Some(tracer) = self.tracer.as_mut() {
+            tracer.on_call(chunk_id);
+        }
+    }
+
+    // Notify the tracer of a native function call
+    #[inline(always)]
+    pub fn trace_native_call(&mut self, function_id: u16, gas: u64) {
+        if let Some(tracer) = self.tracer.as_mut() {
+            tracer.on_native_call(function_id, gas);
+        }
     }
 }
 
 #[cfg(test)]
 mod tests {
+    use alloc::vec;
+    use xelis_types::SubValue;
     use super::*;
 
     #[test]
@@ -250,6 +833,204 @@ mod tests {
         assert_eq!(context.contains::<i32>(), false);
     }
 
+    #[test]
+    fn test_context_memory_usage() {
+        let mut context = Context::new();
+        context.set_max_memory(100);
+        context.set_memory_price_per_byte(2);
+
+        context.increase_memory_usage(60).unwrap();
+        assert_eq!(context.current_memory_usage(), 60);
+        assert_eq!(context.current_gas_usage(), 120);
+
+        assert!(matches!(context.increase_memory_usage(41), Err(EnvironmentError::OutOfMemory)));
+        assert_eq!(context.current_memory_usage(), 60);
+
+        context.decrease_memory_usage(20);
+        context.increase_memory_usage(60).unwrap();
+        assert_eq!(context.current_memory_usage(), 100);
+
+        context.reset_memory_usage();
+        assert_eq!(context.current_memory_usage(), 0);
+    }
+
+    #[test]
+    fn test_context_value_memory_usage() {
+        let mut context = Context::new();
+        let shared = SubValue::new(Value::String("abc".into()).into());
+        let array = ValueCell::Array(vec![shared.reference(), Value::String("de".into()).into()].into());
+
+        // Two values and their 5 bytes
+        context.increase_value_memory_usage(&array).unwrap();
+        assert_eq!(context.current_memory_usage(), 2 * VALUE_MEMORY_USAGE + 5);
+
+        // The shared string is still alive after the array is dropped
+        context.decrease_value_memory_usage(&Path::Owned(array)).unwrap();
+        assert_eq!(context.current_memory_usage(), 3);
+
+        // Borrowed or shared, nothing is freed
+        let value = ValueCell::from(Value::String("abc".into()));
+        context.decrease_value_memory_usage(&Path::Borrowed(&value)).unwrap();
+        let path = Path::Wrapper(shared.reference());
+        context.decrease_value_memory_usage(&path).unwrap();
+        assert_eq!(context.current_memory_usage(), 3);
+
+        drop(shared);
+        context.decrease_value_memory_usage(&path).unwrap();
+        assert_eq!(context.current_memory_usage(), 0);
+
+        // A copy is charged for the content it shares with its source,
+        // which is only freed with the last of them
+        let array = ValueCell::Array(vec![Value::String("abc".into()).into()].into());
+        context.increase_stored_memory_usage(&Path::Borrowed(&array)).unwrap();
+        let copy = array.clone();
+        assert_eq!(context.current_memory_usage(), VALUE_MEMORY_USAGE + 3);
+        // Walking its single inner value
+        assert_eq!(context.current_gas_usage(), 1);
+
+        context.decrease_value_memory_usage(&Path::Owned(array)).unwrap();
+        assert_eq!(context.current_memory_usage(), VALUE_MEMORY_USAGE + 3);
+        context.decrease_value_memory_usage(&Path::Owned(copy)).unwrap();
+        assert_eq!(context.current_memory_usage(), 0);
+
+        // A temporary value is moved with the memory already charged
+        context.increase_stored_memory_usage(&Path::Owned(Value::String("abc".into()).into())).unwrap();
+        assert_eq!(context.current_memory_usage(), 0);
+    }
+
+    #[test]
+    fn test_context_gas_refund() {
+        let mut context = Context::new();
+        context.set_gas_limit(1000);
+        context.increase_gas_usage(400).unwrap();
+        assert_eq!(context.remaining_gas(), 600);
+
+        // Capped to 20% of the gas used by default
+        context.refund_gas(100);
+        assert_eq!(context.effective_gas_refund(), 80);
+        assert_eq!(context.effective_gas_usage(), 320);
+        assert_eq!(context.remaining_gas(), 600);
+
+        context.set_max_gas_refund_percent(50);
+        assert_eq!(context.effective_gas_usage(), 300);
+
+        context.reset_gas_usage();
+        assert_eq!(context.gas_refund(), 0);
+        assert_eq!(context.effective_gas_usage(), 0);
+    }
+
+    #[test]
+    fn test_context_rollback() {
+        use alloc::collections::BTreeMap;
+        use crate::StorageIterator;
+
+        #[derive(Default)]
+        struct MemoryStorage(BTreeMap<Vec<u8>, Vec<u8>>);
+
+        impl StorageProvider for MemoryStorage {
+            fn get(&self, key: &[u8]) -> Result<Option<Vec<u8>>, anyhow::Error> {
+                Ok(self.0.get(key).cloned())
+            }
+
+            fn set(&mut self, key: &[u8], value: Vec<u8>) -> Result<(), anyhow::Error> {
+                self.0.insert(key.to_vec(), value);
+                Ok(())
+            }
+
+            fn delete(&mut self, key: &[u8]) -> Result<bool, anyhow::Error> {
+                Ok(self.0.remove(key).is_some())
+            }
+
+            fn iterate_prefix<'a>(&'a self, _: &[u8]) -> Result<StorageIterator<'a>, anyhow::Error> {
+                Ok(Box::new(core::iter::empty()))
+            }
+        }
+
+        let mut storage = MemoryStorage::default();
+        storage.0.insert(b"a".to_vec(), vec![1]);
+        storage.0.insert(b"b".to_vec(), vec![2]);
+
+        let mut context = Context::new();
+        context.set_storage(&mut storage);
+        // Not journaled without a checkpoint
+        context.storage_set(b"c", vec![3]).unwrap();
+
+        let checkpoint = context.checkpoint();
+        context.storage_set(b"a", vec![10]).unwrap();
+        context.storage_set(b"a", vec![11]).unwrap();
+        assert!(context.storage_delete(b"b").unwrap());
+        assert!(!context.storage_delete(b"d").unwrap());
+        context.storage_set(b"d", vec![4]).unwrap();
+        context.refund_gas(10);
+
+        context.rollback(checkpoint).unwrap();
+        assert_eq!(context.gas_refund(), 0);
+        assert_eq!(context.checkpoints(), 0);
+        drop(context);
+
+        assert_eq!(storage.0.get(b"a".as_slice()), Some(&vec![1]));
+        assert_eq!(storage.0.get(b"b".as_slice()), Some(&vec![2]));
+        assert_eq!(storage.0.get(b"c".as_slice()), Some(&vec![3]));
+        assert_eq!(storage.0.get(b"d".as_slice()), None);
+
+        // A committed inner checkpoint is rolled back by the outer one
+        let mut context = Context::new();
+        context.set_storage(&mut storage);
+        let outer = context.checkpoint();
+        context.storage_set(b"a", vec![20]).unwrap();
+
+        let inner = context.checkpoint();
+        context.storage_set(b"b", vec![21]).unwrap();
+        context.commit(inner).unwrap();
+
+        let inner = context.checkpoint();
+        context.storage_set(b"c", vec![22]).unwrap();
+        context.rollback(inner).unwrap();
+        assert_eq!(context.storage_mut().unwrap().get(b"b").unwrap(), Some(vec![21]));
+        assert_eq!(context.storage_mut().unwrap().get(b"c").unwrap(), Some(vec![3]));
+
+        context.rollback(outer).unwrap();
+        assert_eq!(context.storage_mut().unwrap().get(b"a").unwrap(), Some(vec![1]));
+        assert_eq!(context.storage_mut().unwrap().get(b"b").unwrap(), Some(vec![2]));
+
+        // Closing the outer checkpoint closes the inner one
+        let outer = context.checkpoint();
+        let inner = context.checkpoint();
+        context.storage_set(b"a", vec![30]).unwrap();
+        context.commit(outer).unwrap();
+        assert!(matches!(context.rollback(inner), Err(EnvironmentError::CheckpointClosed)));
+        assert_eq!(context.storage_mut().unwrap().get(b"a").unwrap(), Some(vec![30]));
+    }
+
+    #[test]
+    fn test_context_calls() {
+        let mut context = Context::new();
+        assert_eq!(context.caller(), None);
+        assert_eq!(context.current_contract(), None);
+
+        context.set_caller("alice");
+        context.set_contract("a");
+        context.enter_call("b", "run").unwrap();
+        context.enter_call("c", "run").unwrap();
+        assert_eq!(context.call_depth(), 2);
+        assert_eq!(context.caller(), Some("b"));
+        assert_eq!(context.current_contract(), Some("c"));
+
+        context.exit_call();
+        assert_eq!(context.caller(), Some("a"));
+        assert_eq!(context.current_contract(), Some("b"));
+
+        // The calls are dropped by a rollback
+        let checkpoint = context.checkpoint();
+        context.enter_call("c", "run").unwrap();
+        context.rollback(checkpoint).unwrap();
+        assert_eq!(context.call_depth(), 1);
+
+        context.exit_call();
+        assert_eq!(context.caller(), Some("alice"));
+        assert_eq!(context.current_contract(), Some("a"));
+    }
+
     #[test]
     fn test_context_mut() {
         struct Dummy(i32);
@@ -262,4 +1043,22 @@ mod tests {
         context.get_mut::<Dummy>().unwrap().0 = 2;
         assert_eq!(context.get_mut::<Dummy>().unwrap().0, 2);
     }
+
+    #[test]
+    fn test_context_try_get() {
+        struct Height(u64);
+
+        let height = Height(10);
+        let mut context = Context::new();
+        assert!(matches!(context.try_get::<Height>(), Err(EnvironmentError::MissingData(_))));
+
+        context.insert_ref(&height);
+        assert_eq!(context.try_get::<Height>().unwrap().0, 10);
+        // A borrowed value is read-only
+        assert!(context.try_get_mut::<Height>().is_err());
+
+        context.insert(Height(20));
+        context.try_get_mut::<Height>().unwrap().0 += 1;
+        assert_eq!(context.try_get::<Height>().unwrap().0, 21);
+    }
 }
\ No newline at end of file
@@ -1,3 +1,4 @@
+use alloc::{boxed::Box, string::String};
 use thiserror::Error;
 use xelis_types::{ValueCell, ValueError};
 
@@ -7,10 +8,17 @@ pub enum EnvironmentError {
     InvalidParameter,
     #[error("Assertion failed")]
     AssertionFailed,
+    #[error("Assertion failed: {0}")]
+    AssertionFailedWith(String),
+    // Boxed to not grow the size of the error
+    #[error("Assertion failed: {} != {}", .0.0, .0.1)]
+    AssertionNotEqual(Box<(ValueCell, ValueCell)>),
     #[error("Invalid function call")]
     InvalidFnCall,
     #[error("Invalid function call: expected instance")]
     FnExpectedInstance,
+    #[error("Reverted with {0}")]
+    Revert(ValueCell),
     #[error("Panic: {0}")]
     Panic(ValueCell),
     #[error("Out of bounds: {0} > {1}")]
@@ -38,4 +46,38 @@ pub enum EnvironmentError {
     Any(#[from] anyhow::Error),
     #[error("Out of memory")]
     OutOfMemory,
+    #[error("String too large: {actual} bytes, max {max}")]
+    StringTooLarge {
+        max: usize,
+        actual: usize,
+    },
+    #[error("Invalid regex: {0}")]
+    InvalidRegex(String),
+    #[error("Invalid format: {0}")]
+    InvalidFormat(&'static str),
+    #[error("No random seed provided")]
+    MissingRandomSeed,
+    #[error("No storage provider")]
+    MissingStorage,
+    #[error("Checkpoint already closed")]
+    CheckpointClosed,
+    #[error("Call to '{0}' denied")]
+    CallDenied(String),
+    #[error("No contract identity")]
+    MissingContract,
+    #[error("No caller identity")]
+    MissingCaller,
+    #[error("No {0} provided by the host")]
+    MissingChainData(&'static str),
+    #[error("No data of type {0} in the context")]
+    MissingData(&'static str),
+    #[error("Events limit reached")]
+    EventsLimitReached,
+    #[error("State mutation in a read-only execution")]
+    ReadOnlyViolation,
+    #[error("Invalid length: expected {expected} bytes, got {actual}")]
+    InvalidLength {
+        expected: usize,
+        actual: usize,
+    },
 }
\ No newline at end of file
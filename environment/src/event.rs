@@ -0,0 +1,56 @@
+use alloc::{string::String, vec};
+use xelis_types::{Path, Value, ValueCell};
+
+// Event emitted by a script during the execution
+// The host retrieves them once the execution is done
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Event {
+    // Name of the event
+    pub name: String,
+    // Payload, fully owned so it is not affected by the execution after its emission
+    pub data: ValueCell,
+}
+
+impl Event {
+    // Approximate size in bytes of the event, used to bound the events of an execution
+    // The payload depth must have been checked before
+    pub fn size(&self) -> usize {
+        let mut size = self.name.len();
+        let mut stack = vec![Path::Borrowed(&self.data)];
+        while let Some(next) = stack.pop() {
+            let handle = next.as_ref();
+            match handle.as_value() {
+                ValueCell::Default(v) => size += value_size(v),
+                ValueCell::Array(values) | ValueCell::Struct(values, _) | ValueCell::Enum(values, _) => {
+                    stack.extend(values.iter().map(|v| Path::Wrapper(v.clone())));
+                },
+                ValueCell::Iterator(iter) => stack.extend(iter.values().map(|v| Path::Wrapper(v.clone()))),
+                ValueCell::TypedArray(array) => size += array.iter().map(|v| value_size(&v)).sum::<usize>(),
+                ValueCell::Optional(None) => size += 1,
+                ValueCell::Optional(Some(v)) => stack.push(Path::Wrapper(v.clone())),
+                ValueCell::Map(map) => {
+                    for (k, v) in map {
+                        stack.push(Path::Owned(k.clone()));
+                        stack.push(Path::Wrapper(v.clone()));
+                    }
+                }
+            }
+        }
+
+        size
+    }
+}
+
+fn value_size(value: &Value) -> usize {
+    match value {
+        Value::Null | Value::Boolean(_) | Value::U8(_) => 1,
+        Value::U16(_) => 2,
+        Value::U32(_) => 4,
+        Value::U64(_) => 8,
+        Value::U128(_) => 16,
+        Value::U256(_) => 32,
+        Value::String(s) => s.len(),
+        Value::Blob(bytes) => bytes.len(),
+        Value::Range(start, end, _, _) => value_size(start) + value_size(end)
+    }
+}
@@ -1,3 +1,5 @@
+use core::fmt;
+use alloc::{sync::Arc, vec::Vec};
 use xelis_types::{Path, Type, ValueCell};
 use crate::Context;
 
@@ -8,11 +10,16 @@ use super::EnvironmentError;
 pub type FnReturnType = Result<Option<ValueCell>, EnvironmentError>;
 pub type FnInstance<'a> = Result<&'a mut ValueCell, EnvironmentError>;
 pub type FnParams<'a> = Vec<Path<'a>>;
-pub type OnCallFn = fn(FnInstance, FnParams, &mut Context) -> FnReturnType;
+// Closures are allowed so the host can capture its own state (database handles, chain context...)
+// Shared so the environment can still be cloned
+pub type OnCallFn = Arc<dyn Fn(FnInstance, FnParams, &mut Context) -> FnReturnType + Send + Sync>;
+// Compute the dynamic cost of a call based on the instance and parameters
+// This is added to the flat cost of the function
+pub type CostFn = fn(Option<&ValueCell>, &FnParams) -> u64;
 
 // Native function that is implemented in Rust
 // This is used to register functions in the environment
-#[derive(Debug, Clone)]
+#[derive(Clone)]
 pub struct NativeFunction {
     // function on type
     for_type: Option<Type>,
@@ -20,28 +27,77 @@ pub struct NativeFunction {
     on_call: OnCallFn,
     // cost for each call
     cost: u64,
+    // optional cost depending on the call inputs
+    cost_fn: Option<CostFn>,
     // expected type of the returned value
-    return_type: Option<Type>
+    return_type: Option<Type>,
+    // accept any number of extra parameters of any type
+    variadic: bool,
+    // same result on every platform and VM version for the same inputs
+    deterministic: bool,
+    // update the state of the host, like its storage or the events
+    mutating: bool,
+    // only checked in the debug builds, like the assertions
+    debug_only: bool,
+    // values of its iterator instance read by the function
+    consumed_values: u64
+}
+
+// The callback can't be displayed
+impl fmt::Debug for NativeFunction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("NativeFunction")
+            .field("for_type", &self.for_type)
+            .field("parameters", &self.parameters)
+            .field("cost", &self.cost)
+            .field("cost_fn", &self.cost_fn)
+            .field("return_type", &self.return_type)
+            .field("variadic", &self.variadic)
+            .field("deterministic", &self.deterministic)
+            .field("mutating", &self.mutating)
+            .field("debug_only", &self.debug_only)
+            .field("consumed_values", &self.consumed_values)
+            .finish_non_exhaustive()
+    }
 }
 
 impl NativeFunction {
     // Create a new instance of the NativeFunction
-    pub fn new(for_type: Option<Type>, parameters: Vec<Type>, on_call: OnCallFn, cost: u64, return_type: Option<Type>) -> Self {
+    pub fn new(for_type: Option<Type>, parameters: Vec<Type>, on_call: impl Fn(FnInstance, FnParams, &mut Context) -> FnReturnType + Send + Sync + 'static, cost: u64, return_type: Option<Type>) -> Self {
         Self {
             for_type,
             parameters,
-            on_call,
+            on_call: Arc::new(on_call),
             cost,
-            return_type
+            cost_fn: None,
+            return_type,
+            variadic: false,
+            deterministic: true,
+            mutating: false,
+            debug_only: false,
+            consumed_values: 0
         }
     }
 
     // Execute the function
     pub fn call_function(&self, instance_value: Option<&mut ValueCell>, parameters: FnParams, context: &mut Context) -> Result<Option<ValueCell>, EnvironmentError> {
-        if parameters.len() != self.parameters.len() || (instance_value.is_some() != self.for_type.is_some()) {
+        let invalid_len = if self.variadic {
+            parameters.len() < self.parameters.len()
+        } else {
+            parameters.len() != self.parameters.len()
+        };
+
+        if invalid_len || (instance_value.is_some() != self.for_type.is_some()) {
             return Err(EnvironmentError::InvalidFnCall)
         }
 
+        if self.mutating && context.is_read_only() {
+            return Err(EnvironmentError::ReadOnlyViolation)
+        }
+
+        let cost = self.compute_cost(instance_value.as_deref(), &parameters);
+        context.increase_gas_usage(cost)?;
+
         let instance = match instance_value {
             Some(v) => Ok(v),
             None => Err(EnvironmentError::FnExpectedInstance)
@@ -50,8 +106,19 @@ impl NativeFunction {
     }
 
     // Set the function on call
-    pub fn set_on_call(&mut self, on_call: OnCallFn) {
-        self.on_call = on_call;
+    pub fn set_on_call(&mut self, on_call: impl Fn(FnInstance, FnParams, &mut Context) -> FnReturnType + Send + Sync + 'static) {
+        self.on_call = Arc::new(on_call);
+    }
+
+    // Get the function on call
+    pub fn get_on_call(&self) -> &OnCallFn {
+        &self.on_call
+    }
+
+    // Get the type on which the function is called
+    // None if the function has no instance
+    pub fn get_for_type(&self) -> &Option<Type> {
+        &self.for_type
     }
 
     // Get parameters of the function
@@ -59,6 +126,60 @@ impl NativeFunction {
         &self.parameters
     }
 
+    // Is the function accepting extra parameters after the declared ones
+    pub fn is_variadic(&self) -> bool {
+        self.variadic
+    }
+
+    // Set if the function accepts extra parameters after the declared ones
+    pub fn set_variadic(&mut self, variadic: bool) {
+        self.variadic = variadic;
+    }
+
+    // Is the result of the function the same on every platform
+    pub fn is_deterministic(&self) -> bool {
+        self.deterministic
+    }
+
+    // Mark a function whose result may diverge between hosts, like reading the system time
+    // Such functions are rejected when the strict determinism is enabled
+    pub fn set_deterministic(&mut self, deterministic: bool) {
+        self.deterministic = deterministic;
+    }
+
+    // Is the function updating the state of the host
+    pub fn is_mutating(&self) -> bool {
+        self.mutating
+    }
+
+    // Mark a function updating the state of the host, like writing in the storage
+    // Such functions are rejected in a read-only execution
+    pub fn set_mutating(&mut self, mutating: bool) {
+        self.mutating = mutating;
+    }
+
+    // Is the function a check removed from the release builds
+    pub fn is_debug_only(&self) -> bool {
+        self.debug_only
+    }
+
+    // Mark a function returning nothing whose calls are only a check, like an assertion
+    // The compiler removes them, with their arguments, when the debug assertions are disabled
+    pub fn set_debug_only(&mut self, debug_only: bool) {
+        self.debug_only = debug_only;
+    }
+
+    // Get the count of values of its iterator instance read by the function
+    pub fn get_consumed_values(&self) -> u64 {
+        self.consumed_values
+    }
+
+    // Set the count of values of its iterator instance read by the function, u64::MAX for all of them
+    // The VM invokes the chunks of the iterator adapters on these values before the call
+    pub fn set_consumed_values(&mut self, values: u64) {
+        self.consumed_values = values;
+    }
+
     // Get the expected type of the returned value
     pub fn return_type(&self) -> &Option<Type> {
         &self.return_type
@@ -73,4 +194,102 @@ impl NativeFunction {
     pub fn set_cost(&mut self, cost: u64) {
         self.cost = cost;
     }
-}
\ No newline at end of file
+
+    // Get the dynamic cost function
+    pub fn get_cost_fn(&self) -> Option<CostFn> {
+        self.cost_fn
+    }
+
+    // Set the dynamic cost function
+    pub fn set_cost_fn(&mut self, cost_fn: Option<CostFn>) {
+        self.cost_fn = cost_fn;
+    }
+
+    // Compute the total cost of a call with the given inputs
+    pub fn compute_cost(&self, instance: Option<&ValueCell>, parameters: &FnParams) -> u64 {
+        match self.cost_fn {
+            Some(f) => self.cost.saturating_add(f(instance, parameters)),
+            None => self.cost
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use xelis_types::Value;
+    use super::*;
+
+    fn dummy(_: FnInstance, _: FnParams, _: &mut Context) -> FnReturnType {
+        Ok(None)
+    }
+
+    fn len_cost(_: Option<&ValueCell>, parameters: &FnParams) -> u64 {
+        parameters.iter()
+            .filter_map(|p| p.as_ref().as_string().ok().map(|s| s.len() as u64))
+            .sum()
+    }
+
+    #[test]
+    fn test_dynamic_cost() {
+        let mut function = NativeFunction::new(None, vec![Type::String], dummy, 5, None);
+        let params = || vec![Path::Owned(Value::String("hello world".to_owned()).into())];
+        assert_eq!(function.compute_cost(None, &params()), 5);
+
+        function.set_cost_fn(Some(len_cost));
+        assert_eq!(function.compute_cost(None, &params()), 16);
+
+        let mut context = Context::new();
+        function.call_function(None, params(), &mut context).unwrap();
+        assert_eq!(context.current_gas_usage(), 16);
+
+        // Not enough gas for the dynamic part
+        context.reset_gas_usage();
+        context.set_gas_limit(10);
+        assert!(matches!(function.call_function(None, params(), &mut context), Err(EnvironmentError::NotEnoughGas { .. })));
+    }
+
+    #[test]
+    fn test_closure_state() {
+        let calls = Arc::new(core::sync::atomic::AtomicU64::new(0));
+        let counter = calls.clone();
+        let function = NativeFunction::new(None, vec![], move |_, _, _| {
+            counter.fetch_add(1, core::sync::atomic::Ordering::Relaxed);
+            Ok(None)
+        }, 1, None);
+
+        let mut context = Context::new();
+        function.call_function(None, vec![], &mut context).unwrap();
+        function.clone().call_function(None, vec![], &mut context).unwrap();
+        assert_eq!(calls.load(core::sync::atomic::Ordering::Relaxed), 2);
+    }
+
+    #[test]
+    fn test_variadic() {
+        let mut function = NativeFunction::new(None, vec![Type::String], dummy, 1, None);
+        let params = |n: usize| (0..n).map(|_| Path::Owned(Value::U8(0).into())).collect::<Vec<_>>();
+        let mut context = Context::new();
+
+        assert!(matches!(function.call_function(None, params(2), &mut context), Err(EnvironmentError::InvalidFnCall)));
+
+        function.set_variadic(true);
+        function.call_function(None, params(1), &mut context).unwrap();
+        function.call_function(None, params(3), &mut context).unwrap();
+        assert!(matches!(function.call_function(None, params(0), &mut context), Err(EnvironmentError::InvalidFnCall)));
+    }
+
+    #[test]
+    fn test_read_only() {
+        let mut function = NativeFunction::new(None, vec![], dummy, 1, None);
+        let mut context = Context::new();
+        context.set_read_only(true);
+        function.call_function(None, vec![], &mut context).unwrap();
+
+        // No gas is charged for a rejected call
+        function.set_mutating(true);
+        assert!(matches!(function.call_function(None, vec![], &mut context), Err(EnvironmentError::ReadOnlyViolation)));
+        assert_eq!(context.current_gas_usage(), 1);
+
+        context.set_read_only(false);
+        function.call_function(None, vec![], &mut context).unwrap();
+    }
+}
@@ -1,18 +1,36 @@
+#![cfg_attr(not(any(feature = "std", test)), no_std)]
+
+extern crate alloc;
+
 mod error;
 mod function;
 mod context;
+mod tracer;
+mod storage;
+mod event;
+mod sync;
+mod call;
+mod precompile;
+mod chain;
 
-use indexmap::IndexSet;
-use xelis_types::{EnumType, StructType};
+use alloc::{sync::Arc, vec::Vec};
+use xelis_types::{EnumType, IndexSet, StructType};
 
 pub use error::EnvironmentError;
 pub use function::*;
 pub use context::*;
+pub use tracer::Tracer;
+pub use storage::{StorageProvider, StorageIterator};
+pub use event::Event;
+pub use sync::*;
+pub use call::{CallAuthorizer, CallPermission, CallRequest};
+pub use precompile::{Precompile, Precompiles};
+pub use chain::ChainInfo;
 
 
 /// Environment is used to store all the registered functions and structures
 /// It is used to give a context/std library to the parser / interpreter / VM
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Default)]
 pub struct Environment {
     // All functions provided by the Environment
     functions: Vec<NativeFunction>,
@@ -20,16 +38,8 @@ pub struct Environment {
     structures: IndexSet<StructType>,
     // All enums provided by the Environment
     enums: IndexSet<EnumType>,
-}
-
-impl Default for Environment {
-    fn default() -> Self {
-        Self {
-            functions: Vec::new(),
-            structures: IndexSet::new(),
-            enums: IndexSet::new(),
-        }
-    }
+    // Operations of the host called by their id
+    precompiles: Precompiles,
 }
 
 impl Environment {
@@ -79,6 +89,24 @@ impl Environment {
         self.enums.insert(_enum);
     }
 
+    // Register a precompile, the previous one with the same id is replaced
+    #[inline]
+    pub fn add_precompile(&mut self, precompile: Arc<dyn Precompile>) -> Option<Arc<dyn Precompile>> {
+        self.precompiles.insert(precompile)
+    }
+
+    // Get all the registered precompiles
+    #[inline(always)]
+    pub fn get_precompiles(&self) -> &Precompiles {
+        &self.precompiles
+    }
+
+    // Get a precompile by its id
+    #[inline]
+    pub fn get_precompile(&self, id: u16) -> Option<&dyn Precompile> {
+        self.precompiles.get(id)
+    }
+
     // Allow to change the cost of a function
     pub fn set_cost_for_function_at_index(&mut self, index: usize, cost: u64) {
         if let Some(function) = self.functions.get_mut(index) {
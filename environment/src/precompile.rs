@@ -0,0 +1,56 @@
+use core::fmt;
+use alloc::{collections::BTreeMap, sync::Arc, vec::Vec};
+
+// Heavy operation implemented natively by the host, like a pairing or a proof verification
+// It is called from the bytecode by its id using the CallPrecompile opcode
+// The input and the output are raw bytes, encoded as expected by the precompile
+pub trait Precompile: Send + Sync {
+    // Unique id of the precompile in the environment
+    fn id(&self) -> u16;
+
+    // Gas charged for every call
+    fn base_gas(&self) -> u64;
+
+    // Gas charged in addition depending on the input
+    fn dynamic_gas(&self, _input: &[u8]) -> u64 {
+        0
+    }
+
+    // Execute the precompile on the input
+    // An error stops the execution
+    fn execute(&self, input: &[u8]) -> Result<Vec<u8>, anyhow::Error>;
+}
+
+// Precompiles registered in an environment by their id
+#[derive(Clone, Default)]
+pub struct Precompiles(BTreeMap<u16, Arc<dyn Precompile>>);
+
+impl Precompiles {
+    // Register a precompile, the previous one with the same id is replaced
+    pub fn insert(&mut self, precompile: Arc<dyn Precompile>) -> Option<Arc<dyn Precompile>> {
+        self.0.insert(precompile.id(), precompile)
+    }
+
+    // Get a precompile by its id
+    #[inline]
+    pub fn get(&self, id: u16) -> Option<&dyn Precompile> {
+        self.0.get(&id).map(|precompile| precompile.as_ref())
+    }
+
+    // Is a precompile registered with this id
+    #[inline]
+    pub fn contains(&self, id: u16) -> bool {
+        self.0.contains_key(&id)
+    }
+
+    // Ids of the registered precompiles, in ascending order
+    pub fn ids(&self) -> impl Iterator<Item = u16> + '_ {
+        self.0.keys().copied()
+    }
+}
+
+impl fmt::Debug for Precompiles {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_list().entries(self.0.keys()).finish()
+    }
+}
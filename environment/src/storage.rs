@@ -0,0 +1,22 @@
+use alloc::{boxed::Box, vec::Vec};
+use crate::MaybeSend;
+
+// Entries returned by a prefix iteration
+pub type StorageIterator<'a> = Box<dyn Iterator<Item = (Vec<u8>, Vec<u8>)> + 'a>;
+
+// Persistent key/value storage provided by the host
+// Keys and values are raw bytes, the values being encoded by the VM
+// Errors are reported by the host and stop the execution
+pub trait StorageProvider: MaybeSend {
+    // Get the value stored for a key
+    fn get(&self, key: &[u8]) -> Result<Option<Vec<u8>>, anyhow::Error>;
+
+    // Store a value, replacing the previous one
+    fn set(&mut self, key: &[u8], value: Vec<u8>) -> Result<(), anyhow::Error>;
+
+    // Delete a key, returns true if it was present
+    fn delete(&mut self, key: &[u8]) -> Result<bool, anyhow::Error>;
+
+    // Iterate over all the entries whose key starts with the prefix
+    fn iterate_prefix<'a>(&'a self, prefix: &[u8]) -> Result<StorageIterator<'a>, anyhow::Error>;
+}
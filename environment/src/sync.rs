@@ -0,0 +1,31 @@
+use core::any::Any;
+
+// Bounds required on the host values referenced by a context
+// With the `sync` feature, they must be thread-safe so the VM holding the context
+// can be sent to another thread, otherwise they are implemented by every type
+
+#[cfg(feature = "sync")]
+pub trait MaybeSend: Send {}
+#[cfg(feature = "sync")]
+impl<T: Send + ?Sized> MaybeSend for T {}
+
+#[cfg(not(feature = "sync"))]
+pub trait MaybeSend {}
+#[cfg(not(feature = "sync"))]
+impl<T: ?Sized> MaybeSend for T {}
+
+#[cfg(feature = "sync")]
+pub trait MaybeSync: Send + Sync {}
+#[cfg(feature = "sync")]
+impl<T: Send + Sync + ?Sized> MaybeSync for T {}
+
+#[cfg(not(feature = "sync"))]
+pub trait MaybeSync {}
+#[cfg(not(feature = "sync"))]
+impl<T: ?Sized> MaybeSync for T {}
+
+// Any value stored in a context
+#[cfg(feature = "sync")]
+pub type AnyData = dyn Any + Send + Sync;
+#[cfg(not(feature = "sync"))]
+pub type AnyData = dyn Any;
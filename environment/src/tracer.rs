@@ -0,0 +1,17 @@
+use crate::MaybeSend;
+
+// Hooks called by the VM during an execution
+// Every callback does nothing by default, so only the needed ones have to be implemented
+pub trait Tracer: MaybeSend {
+    // Called before executing an instruction
+    // `gas_used` is the total gas used before this instruction
+    fn on_instruction(&mut self, _chunk_id: u16, _offset: usize, _opcode: u8, _gas_used: u64) {}
+
+    // Called when a chunk is invoked, including the entry chunk
+    fn on_call(&mut self, _chunk_id: u16) {}
+
+    // Called after a native function returned
+    // `gas` is the gas consumed by the call
+    fn on_native_call(&mut self, _function_id: u16, _gas: u64) {}
+}
+
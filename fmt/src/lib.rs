@@ -0,0 +1,566 @@
+use std::borrow::Cow;
+use xelis_ast::{Literal, Token, TokenResult};
+use xelis_lexer::{Comment, Lexer, LexerError};
+
+// One level of indentation
+const INDENT: &str = "    ";
+
+// Format a source code with the canonical style
+// The line breaks written are kept, at most one empty line is kept between two lines
+// Everything else is rewritten: indentation, spaces between the tokens,
+// semicolons, string quotes and trailing commas of the lists written on several lines
+pub fn format(source: &str) -> Result<String, LexerError> {
+    let mut lexer = Lexer::new(source).keep_comments();
+    let tokens = lexer.by_ref().collect::<Result<Vec<_>, _>>()?;
+    let comments = lexer.take_comments();
+
+    Ok(Formatter::new(source, tokens, comments).format())
+}
+
+// Returns true if the source code is already formatted
+pub fn is_formatted(source: &str) -> Result<bool, LexerError> {
+    format(source).map(|formatted| formatted == source)
+}
+
+// Element of the source code, in the order it is written
+enum Element<'a> {
+    // index of the token in the tokens list
+    Token(usize),
+    Comment(Comment<'a>),
+}
+
+// What a token is used for, when the token alone is ambiguous
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Role {
+    // `<` and `>` around the inner types of a generic type
+    GenericOpen,
+    GenericClose,
+    // `:` in a path like `Enum::Variant`
+    PathColon,
+    // `:` between a name and its type or value
+    TypeColon,
+    // `?` and `:` of a ternary condition
+    Ternary,
+    // `?` returning the error of a Result
+    Try,
+    // `!` before a value
+    Not,
+    Other,
+}
+
+// Group opened by a `{`, `(` or `[`
+struct Group {
+    // indentation of the line where the group is opened
+    indent: usize,
+    // the group is a list of comma separated values
+    list: bool,
+    // the group contains the variants of an enum
+    enum_body: bool,
+    // ternary conditions waiting for their `:`
+    ternaries: usize,
+    // the group contains the cases of a switch, indented once more than their labels
+    switch_body: bool,
+}
+
+struct Formatter<'a> {
+    // characters of each line of the source code
+    lines: Vec<Vec<char>>,
+    tokens: Vec<TokenResult<'a>>,
+    elements: Vec<Element<'a>>,
+    output: String,
+    groups: Vec<Group>,
+    // ternary conditions waiting for their `:` outside of any group
+    ternaries: usize,
+    // generic types currently opened
+    generic_depth: usize,
+    // indentation of the current line
+    indent: usize,
+    // last token written with its role
+    previous: Option<(usize, Role)>,
+    // the last element written is a comment
+    after_comment: bool,
+    // position in the output right after the last token written
+    // used to insert a trailing comma before a comment
+    token_end: usize,
+    // number of groups opened when a `for` header started
+    for_header: Option<usize>,
+    // number of groups opened when a `switch` header started
+    switch_header: Option<usize>,
+}
+
+impl<'a> Formatter<'a> {
+    fn new(source: &str, tokens: Vec<TokenResult<'a>>, comments: Vec<Comment<'a>>) -> Self {
+        // merge the tokens and the comments based on their position
+        let mut elements = Vec::with_capacity(tokens.len() + comments.len());
+        let mut comments = comments.into_iter().peekable();
+        for (i, token) in tokens.iter().enumerate() {
+            while let Some(comment) = comments.next_if(|c| (c.line, c.column) < (token.line, token.column_start)) {
+                elements.push(Element::Comment(comment));
+            }
+            elements.push(Element::Token(i));
+        }
+        elements.extend(comments.map(Element::Comment));
+
+        Self {
+            lines: source.lines().map(|line| line.chars().collect()).collect(),
+            tokens,
+            elements,
+            output: String::new(),
+            groups: Vec::new(),
+            ternaries: 0,
+            generic_depth: 0,
+            indent: 0,
+            previous: None,
+            after_comment: false,
+            token_end: 0,
+            for_header: None,
+            switch_header: None,
+        }
+    }
+
+    fn format(mut self) -> String {
+        let mut end_line = None;
+        for i in 0..self.elements.len() {
+            let (line, last_line) = match &self.elements[i] {
+                Element::Token(t) => {
+                    let token = &self.tokens[*t];
+                    let lines = match &token.token {
+                        Token::Value(Literal::String(value)) => value.matches('\n').count(),
+                        _ => 0
+                    };
+                    (token.line, token.line + lines)
+                },
+                Element::Comment(comment) => (comment.line, comment.end_line)
+            };
+
+            // lines skipped since the previous element
+            let new_lines = end_line.map(|end| line.saturating_sub(end));
+            end_line = Some(last_line);
+
+            match &self.elements[i] {
+                Element::Token(t) => self.write_token(*t, new_lines),
+                Element::Comment(comment) => {
+                    let text = comment.text;
+                    self.write_comment(text, new_lines);
+                }
+            }
+        }
+
+        if !self.output.is_empty() {
+            self.output.push('\n');
+        }
+
+        self.output
+    }
+
+    fn token(&self, t: usize) -> Option<&Token<'a>> {
+        self.tokens.get(t).map(|v| &v.token)
+    }
+
+    fn previous_token(&self) -> Option<&Token<'a>> {
+        self.previous.and_then(|(t, _)| self.token(t))
+    }
+
+    // text of a token, as written in the source code
+    // strings are always written between double quotes
+    fn token_text(&self, t: usize) -> Cow<'a, str> {
+        let token = &self.tokens[t];
+        if let Token::Value(Literal::String(value)) = &token.token {
+            let escaped = value.replace('\\', "\\\\").replace('"', "\\\"");
+            return Cow::Owned(format!("\"{}\"", escaped));
+        }
+
+        let text = self.lines.get(token.line - 1)
+            .and_then(|line| line.get(token.column_start - 1..token.column_end))
+            .map(|chars| chars.iter().collect())
+            .unwrap_or_default();
+
+        Cow::Owned(text)
+    }
+
+    // write the line breaks before an element
+    // returns true if the element starts a new line
+    fn write_new_lines(&mut self, new_lines: Option<usize>, closing: bool) -> bool {
+        let Some(new_lines) = new_lines.filter(|n| *n > 0) else {
+            return false
+        };
+
+        self.output.push('\n');
+        // keep one empty line, except at the start or end of a group
+        let opening = !self.after_comment && matches!(self.previous_token(), Some(Token::BraceOpen | Token::ParenthesisOpen | Token::BracketOpen));
+        if new_lines > 1 && !opening && !closing {
+            self.output.push('\n');
+        }
+
+        true
+    }
+
+    // write the indentation of a new line
+    fn write_indent(&mut self, indent: usize) {
+        self.indent = indent;
+        for _ in 0..indent {
+            self.output.push_str(INDENT);
+        }
+    }
+
+    // indentation of a line inside the current groups
+    fn group_indent(&self) -> usize {
+        self.groups.last().map_or(0, |group| if group.switch_body {
+            group.indent + 2
+        } else {
+            group.indent + 1
+        })
+    }
+
+    fn write_comment(&mut self, text: &str, new_lines: Option<usize>) {
+        if self.write_new_lines(new_lines, false) {
+            self.write_indent(self.group_indent());
+        } else if !self.output.is_empty() {
+            self.output.push(' ');
+        }
+
+        self.output.push_str(text);
+        self.after_comment = true;
+    }
+
+    fn write_token(&mut self, t: usize, new_lines: Option<usize>) {
+        let role = self.role_of(t);
+        let token = self.tokens[t].token.clone();
+        let closing = matches!(token, Token::BraceClose | Token::ParenthesisClose | Token::BracketClose);
+
+        let mut indent = self.group_indent();
+        // the labels of a switch are between the braces and the statements
+        if matches!(token, Token::Case | Token::Default) && self.groups.last().is_some_and(|group| group.switch_body) {
+            indent -= 1;
+        }
+
+        if closing {
+            if let Some(group) = self.groups.pop() {
+                indent = group.indent;
+                let opened = matches!(self.previous_token(), Some(Token::BraceOpen | Token::ParenthesisOpen | Token::BracketOpen));
+                let after_comma = matches!(self.previous_token(), Some(Token::Comma));
+                if group.list && !opened {
+                    let new_line = new_lines.is_some_and(|n| n > 0);
+                    if new_line && !after_comma {
+                        // a list written on several lines ends with a comma
+                        self.output.insert(self.token_end, ',');
+                    } else if !new_line && after_comma && !self.after_comment {
+                        // but not a list written on one line
+                        self.output.truncate(self.token_end - 1);
+                    }
+                }
+            }
+        }
+
+        if self.write_new_lines(new_lines, closing) {
+            // a line continuing an expression is indented once more
+            let continuing = token == Token::Dot || self.previous
+                .is_some_and(|(p, role)| role == Role::Other && self.tokens[p].token.is_operator() && self.tokens[p].token != Token::BracketOpen);
+            if continuing && !closing {
+                indent += 1;
+            }
+            self.write_indent(indent);
+        } else {
+            // the semicolons are only kept between the parts of a `for` header
+            if self.for_header.is_some() && self.has_semicolon_before(t) {
+                self.output.push(';');
+            }
+
+            if self.needs_space(&token, role) {
+                self.output.push(' ');
+            }
+        }
+
+        let text = self.token_text(t);
+        self.output.push_str(&text);
+        self.token_end = self.output.len();
+        self.previous = Some((t, role));
+        self.after_comment = false;
+
+        if token == Token::For {
+            self.for_header = Some(self.groups.len());
+        } else if token == Token::BraceOpen && self.for_header == Some(self.groups.len()) {
+            self.for_header = None;
+        }
+
+        let switch_body = token == Token::BraceOpen && self.switch_header == Some(self.groups.len());
+        if token == Token::Switch {
+            self.switch_header = Some(self.groups.len());
+        } else if switch_body {
+            self.switch_header = None;
+        }
+
+        if matches!(token, Token::BraceOpen | Token::ParenthesisOpen | Token::BracketOpen) {
+            let group = Group {
+                indent: self.indent,
+                list: self.is_list(t),
+                enum_body: token == Token::BraceOpen && t >= 2 && self.token(t - 2) == Some(&Token::Enum),
+                ternaries: 0,
+                switch_body,
+            };
+            self.groups.push(group);
+        }
+    }
+
+    // a semicolon is written between the previous token and this one on the same line
+    fn has_semicolon_before(&self, t: usize) -> bool {
+        let Some(previous) = t.checked_sub(1).and_then(|p| self.tokens.get(p)) else {
+            return false
+        };
+        let token = &self.tokens[t];
+        if previous.line != token.line {
+            return false
+        }
+
+        self.lines.get(token.line - 1)
+            .and_then(|line| line.get(previous.column_end..token.column_start - 1))
+            .is_some_and(|chars| chars.contains(&';'))
+    }
+
+    // find what the token is used for
+    fn role_of(&mut self, t: usize) -> Role {
+        let previous = self.previous.map(|(p, role)| (&self.tokens[p].token, role));
+        match &self.tokens[t].token {
+            Token::OperatorLessThan if previous.is_some_and(|(p, _)| p.accept_generic()) && self.is_generic(t) => {
+                self.generic_depth += 1;
+                Role::GenericOpen
+            },
+            Token::OperatorGreaterThan if self.generic_depth > 0 => {
+                self.generic_depth -= 1;
+                Role::GenericClose
+            },
+            Token::Colon if self.token(t + 1) == Some(&Token::Colon) || previous.is_some_and(|(_, role)| role == Role::PathColon) => Role::PathColon,
+            Token::Colon => {
+                let ternaries = match self.groups.last_mut() {
+                    Some(group) => &mut group.ternaries,
+                    None => &mut self.ternaries
+                };
+
+                if *ternaries > 0 {
+                    *ternaries -= 1;
+                    Role::Ternary
+                } else {
+                    Role::TypeColon
+                }
+            },
+            Token::OperatorTernary => {
+                // a ternary is followed by a value on the same line
+                let line = self.tokens[t].line;
+                let ternary = self.tokens.get(t + 1).is_some_and(|next| next.line == line && matches!(
+                    next.token,
+                    Token::Identifier(_) | Token::Value(_) | Token::ParenthesisOpen | Token::BracketOpen | Token::IsNot
+                ));
+
+                if ternary {
+                    match self.groups.last_mut() {
+                        Some(group) => group.ternaries += 1,
+                        None => self.ternaries += 1
+                    };
+                    Role::Ternary
+                } else {
+                    Role::Try
+                }
+            },
+            Token::IsNot => Role::Not,
+            _ => Role::Other
+        }
+    }
+
+    // `<` opens a generic type if it is closed by a `>` with only types in between
+    fn is_generic(&self, t: usize) -> bool {
+        let mut depth = 0;
+        for token in &self.tokens[t..] {
+            match token.token {
+                Token::OperatorLessThan => depth += 1,
+                Token::OperatorGreaterThan => {
+                    depth -= 1;
+                    if depth == 0 {
+                        return true
+                    }
+                },
+                Token::Comma
+                | Token::BracketOpen
+                | Token::BracketClose
+                | Token::ParenthesisOpen
+                | Token::ParenthesisClose => {},
+                ref token if token.is_type() => {},
+                _ => return false
+            }
+        }
+
+        false
+    }
+
+    // the group opened by the token is a list of comma separated values
+    // that accepts a trailing comma
+    fn is_list(&self, t: usize) -> bool {
+        let previous = t.checked_sub(1).and_then(|p| self.tokens.get(p));
+        let same_line = previous.is_some_and(|p| p.line == self.tokens[t].line);
+        match self.tokens[t].token {
+            // function calls and parameters
+            Token::ParenthesisOpen => same_line && matches!(previous.map(|p| &p.token), Some(Token::Identifier(_))),
+            // arrays
+            Token::BracketOpen => self.has_comma(t),
+            Token::BraceOpen => {
+                let declaration = t >= 2 && matches!(self.token(t - 2), Some(Token::Struct | Token::Enum));
+                let variant = self.groups.last().is_some_and(|group| group.enum_body);
+                let map = self.previous.is_some_and(|(_, role)| role == Role::GenericClose);
+                let constructor = matches!(previous.map(|p| &p.token), Some(Token::Identifier(_)))
+                    && matches!(self.token(t + 1), Some(Token::Identifier(_)))
+                    && match self.token(t + 2) {
+                        Some(Token::Colon) => self.token(t + 3) != Some(&Token::Colon),
+                        Some(Token::Comma | Token::BraceClose) => true,
+                        _ => false
+                    };
+
+                declaration || variant || map || constructor
+            },
+            _ => false
+        }
+    }
+
+    // the group opened by the token has a comma at its own level
+    fn has_comma(&self, t: usize) -> bool {
+        let mut depth = 0;
+        for token in &self.tokens[t + 1..] {
+            match token.token {
+                Token::BraceOpen | Token::ParenthesisOpen | Token::BracketOpen => depth += 1,
+                Token::BraceClose | Token::ParenthesisClose | Token::BracketClose => {
+                    if depth == 0 {
+                        break
+                    }
+                    depth -= 1;
+                },
+                Token::Comma if depth == 0 => return true,
+                _ => {}
+            }
+        }
+
+        false
+    }
+
+    // a space is written between the previous token and this one
+    fn needs_space(&self, token: &Token<'a>, role: Role) -> bool {
+        let Some((p, previous_role)) = self.previous.filter(|_| !self.after_comment) else {
+            return !self.output.is_empty() && !matches!(token, Token::Comma | Token::ParenthesisClose | Token::BracketClose)
+        };
+        let previous = &self.tokens[p].token;
+
+        if matches!(previous_role, Role::GenericOpen | Role::PathColon | Role::Not)
+            || matches!(previous, Token::ParenthesisOpen | Token::BracketOpen | Token::Dot) {
+            return false
+        }
+
+        if matches!(role, Role::GenericOpen | Role::GenericClose | Role::PathColon | Role::TypeColon | Role::Try) {
+            return false
+        }
+
+        match token {
+            Token::ParenthesisClose
+            | Token::BracketClose
+            | Token::Comma
+            | Token::Dot => false,
+            // function calls
+            Token::ParenthesisOpen => !matches!(previous, Token::Identifier(_) | Token::ParenthesisClose | Token::BracketClose),
+            // indexes, array types and attributes
+            Token::BracketOpen => !(previous.is_type() || matches!(previous, Token::ParenthesisClose | Token::BracketClose | Token::Hash) || previous_role == Role::GenericClose),
+            // empty braces
+            Token::BraceClose => *previous != Token::BraceOpen,
+            _ => true
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use xelis_builder::EnvironmentBuilder;
+    use xelis_parser::Parser;
+    use super::*;
+
+    // parse the code to verify that the formatting is valid
+    fn assert_valid(code: &str) {
+        let environment = EnvironmentBuilder::default();
+        let tokens = Lexer::new(code).collect::<Result<Vec<_>, _>>().unwrap();
+        if let Err(e) = Parser::with(tokens.into_iter(), &environment).parse() {
+            panic!("invalid formatted code: {}\n{}", e.with_source(code), code);
+        }
+    }
+
+    fn assert_format(code: &str, expected: &str) {
+        let formatted = format(code).unwrap();
+        assert_eq!(formatted, expected);
+        // formatting twice gives the same result
+        assert_eq!(format(&formatted).unwrap(), expected);
+        assert_valid(&formatted);
+    }
+
+    #[test]
+    fn test_indentation_and_spaces() {
+        let code = "entry main(){\nlet a:u64=10;\n        let ok:bool= !(a==7)\n        if (a>5)&&ok{\n  return a+1\n}\nreturn 0;\n}";
+        let expected = "entry main() {\n    let a: u64 = 10\n    let ok: bool = !(a == 7)\n    if (a > 5) && ok {\n        return a + 1\n    }\n    return 0\n}\n";
+        assert_format(code, expected);
+    }
+
+    #[test]
+    fn test_comments() {
+        let code = "// Entry point\nentry main() {\n    // the value\n  let a: u64 = 10 // ten\n\n\n\n    /* block */ return a\n}";
+        let expected = "// Entry point\nentry main() {\n    // the value\n    let a: u64 = 10 // ten\n\n    /* block */ return a\n}\n";
+        assert_format(code, expected);
+    }
+
+    #[test]
+    fn test_trailing_commas() {
+        let code = "fn sum(a: u64, b: u64,) -> u64 {\n    return a + b\n}\n\nstruct Point {\n    x: u64,\n    y: u64\n}\n\nenum Message {\n    Hello,\n    World { a: u64, }\n}\n\nentry main() {\n    let p: Point = Point {\n        x: 1,\n        y: 2\n    }\n    let m: Message = Message::World { a: 1 }\n    let values: u64[] = [\n        p.x,\n        p.y\n    ]\n    return sum(\n        values[0],\n        values[1]\n    )\n}";
+        let expected = "fn sum(a: u64, b: u64) -> u64 {\n    return a + b\n}\n\nstruct Point {\n    x: u64,\n    y: u64,\n}\n\nenum Message {\n    Hello,\n    World { a: u64 },\n}\n\nentry main() {\n    let p: Point = Point {\n        x: 1,\n        y: 2,\n    }\n    let m: Message = Message::World { a: 1 }\n    let values: u64[] = [\n        p.x,\n        p.y,\n    ]\n    return sum(\n        values[0],\n        values[1],\n    )\n}\n";
+        assert_format(code, expected);
+    }
+
+    #[test]
+    fn test_generics_paths_and_ternary() {
+        let code = "fn check(v: optional < u64 >) -> Result < u64 , string > {\n    let m: map<string, optional<u64>> = {}\n    let x: u64 = v.unwrap_or(0) > 5 ? 1 : 0\n    if x < 1 {\n        return Err ( \"zero\" )\n    }\n    return Ok(x)\n}\n\nfn run() -> Result<u64, string> {\n    let v: optional<u64> = null\n    let a: u64 = check(v)?\n    return Ok(a + 1)\n}\n\nentry main() {\n    return run().unwrap_or(0)\n}";
+        let expected = "fn check(v: optional<u64>) -> Result<u64, string> {\n    let m: map<string, optional<u64>> = {}\n    let x: u64 = v.unwrap_or(0) > 5 ? 1 : 0\n    if x < 1 {\n        return Err(\"zero\")\n    }\n    return Ok(x)\n}\n\nfn run() -> Result<u64, string> {\n    let v: optional<u64> = null\n    let a: u64 = check(v)?\n    return Ok(a + 1)\n}\n\nentry main() {\n    return run().unwrap_or(0)\n}\n";
+        assert_format(code, expected);
+    }
+
+    #[test]
+    fn test_attributes() {
+        let code = "# [ invariant ( a > 1 ) ]\nfn f(a: u64) -> u64 {\n    return a\n}\n\nentry main() {\n    return f(2)\n}";
+        let expected = "#[invariant(a > 1)]\nfn f(a: u64) -> u64 {\n    return a\n}\n\nentry main() {\n    return f(2)\n}\n";
+        assert_format(code, expected);
+    }
+
+    #[test]
+    fn test_literals() {
+        let code = "entry main() {\n    let a: u8 = 0xFFu8\n    let b: u64 = 1_000\n    let s: string = 'it\\'s \"ok\"'\n    foreach v in [b, a as u64] {\n        println(s + v)\n    }\n    return b\n}";
+        let expected = "entry main() {\n    let a: u8 = 0xFFu8\n    let b: u64 = 1_000\n    let s: string = \"it's \\\"ok\\\"\"\n    foreach v in [b, a as u64] {\n        println(s + v)\n    }\n    return b\n}\n";
+        assert_format(code, expected);
+    }
+
+    #[test]
+    fn test_for_header() {
+        let code = "entry main() {\n    let a: u64 = 0;\n    for i: u64 = 0;i < 10 ;i += 1 {\n        a += i;\n    }\n    return a;\n}";
+        let expected = "entry main() {\n    let a: u64 = 0\n    for i: u64 = 0; i < 10; i += 1 {\n        a += i\n    }\n    return a\n}\n";
+        assert_format(code, expected);
+    }
+
+    #[test]
+    fn test_switch() {
+        let code = "entry main() {\n    let a: u64 = 0;\n    switch(a){\ncase 0:\na = 1;\nfallthrough\n  case 1,2 :\n        a += 1\n    default :\n    if a > 1 {\n    a = 0\n    }\n    }\n    return a\n}";
+        let expected = "entry main() {\n    let a: u64 = 0\n    switch (a) {\n        case 0:\n            a = 1\n            fallthrough\n        case 1, 2:\n            a += 1\n        default:\n            if a > 1 {\n                a = 0\n            }\n    }\n    return a\n}\n";
+        assert_format(code, expected);
+    }
+
+    #[test]
+    fn test_continuation_lines() {
+        let code = "entry main() {\n    let a: u64 = 1 +\n    2\n    let b: u64[] = [a]\n    return b\n    .len() as u64\n}";
+        let expected = "entry main() {\n    let a: u64 = 1 +\n        2\n    let b: u64[] = [a]\n    return b\n        .len() as u64\n}\n";
+        assert_format(code, expected);
+    }
+
+    #[test]
+    fn test_is_formatted() {
+        assert!(is_formatted("entry main() {\n    return 0\n}\n").unwrap());
+        assert!(!is_formatted("entry main() { return 0; }").unwrap());
+        assert!(is_formatted("").unwrap());
+    }
+}
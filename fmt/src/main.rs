@@ -0,0 +1,92 @@
+use std::{
+    env,
+    fs,
+    io::{self, Read, Write},
+    process::ExitCode
+};
+
+const USAGE: &str = "Usage: xelis-fmt [--check] [files...]
+Format the files in place, or the standard input to the standard output if no file is given
+    --check    only verify that the code is formatted, exit with an error otherwise";
+
+fn main() -> ExitCode {
+    let mut check = false;
+    let mut files = Vec::new();
+    for arg in env::args().skip(1) {
+        match arg.as_str() {
+            "--check" => check = true,
+            "-h" | "--help" => {
+                println!("{}", USAGE);
+                return ExitCode::SUCCESS
+            },
+            _ => files.push(arg)
+        }
+    }
+
+    if files.is_empty() {
+        return exit_code(format_stdin(check))
+    }
+
+    // all the files are formatted even if one fails
+    let mut code = ExitCode::SUCCESS;
+    for path in &files {
+        if exit_code(format_file(path, check)) != ExitCode::SUCCESS {
+            code = ExitCode::FAILURE;
+        }
+    }
+
+    code
+}
+
+fn exit_code(result: Result<bool, String>) -> ExitCode {
+    match result {
+        Ok(true) => ExitCode::SUCCESS,
+        Ok(false) => ExitCode::FAILURE,
+        Err(e) => {
+            eprintln!("{}", e);
+            ExitCode::FAILURE
+        }
+    }
+}
+
+// Returns false if the check mode is enabled and the code isn't formatted
+fn format_stdin(check: bool) -> Result<bool, String> {
+    let mut source = String::new();
+    io::stdin().read_to_string(&mut source)
+        .map_err(|e| format!("failed to read the standard input: {}", e))?;
+
+    let formatted = xelis_fmt::format(&source)
+        .map_err(|e| format!("<stdin>: {}", e))?;
+
+    if check {
+        return Ok(formatted == source)
+    }
+
+    io::stdout().write_all(formatted.as_bytes())
+        .map_err(|e| format!("failed to write the standard output: {}", e))?;
+
+    Ok(true)
+}
+
+// Format a file in place, only rewritten if its content changes
+fn format_file(path: &str, check: bool) -> Result<bool, String> {
+    let source = fs::read_to_string(path)
+        .map_err(|e| format!("failed to read {}: {}", path, e))?;
+
+    let formatted = xelis_fmt::format(&source)
+        .map_err(|e| format!("{}: {}", path, e))?;
+
+    if formatted == source {
+        return Ok(true)
+    }
+
+    if check {
+        println!("{} is not formatted", path);
+        return Ok(false)
+    }
+
+    fs::write(path, formatted)
+        .map_err(|e| format!("failed to write {}: {}", path, e))?;
+
+    Ok(true)
+}
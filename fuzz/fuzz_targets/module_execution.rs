@@ -0,0 +1,38 @@
+#![no_main]
+
+use std::sync::LazyLock;
+use libfuzzer_sys::fuzz_target;
+use xelis_builder::EnvironmentBuilder;
+use xelis_bytecode::Module;
+use xelis_environment::Environment;
+use xelis_vm::{ModuleValidator, VM};
+
+// Tight limits so a run stays short and bounded in memory
+const GAS_LIMIT: u64 = 100_000;
+const MEMORY_PRICE_PER_BYTE: u64 = 1;
+
+static ENVIRONMENT: LazyLock<Environment> = LazyLock::new(|| EnvironmentBuilder::default().build());
+
+// Every entry of a validated module must end with a value or an error, never with a panic
+fuzz_target!(|data: &[u8]| {
+    let Ok(module) = Module::from_bytes(data) else {
+        return
+    };
+
+    if ModuleValidator::new(&module, &ENVIRONMENT).verify().is_err() {
+        return
+    }
+
+    for id in 0..module.chunks().len() {
+        if !module.is_entry_chunk(id) {
+            continue
+        }
+
+        let mut vm = VM::new(&module, &ENVIRONMENT);
+        vm.context_mut().set_gas_limit(GAS_LIMIT);
+        vm.context_mut().set_memory_price_per_byte(MEMORY_PRICE_PER_BYTE);
+        if vm.invoke_entry_chunk(id as u16).is_ok() {
+            let _ = vm.run();
+        }
+    }
+});
@@ -0,0 +1,26 @@
+#![no_main]
+
+use std::sync::LazyLock;
+use libfuzzer_sys::fuzz_target;
+use xelis_builder::EnvironmentBuilder;
+use xelis_bytecode::Module;
+use xelis_environment::Environment;
+use xelis_vm::ModuleValidator;
+
+static ENVIRONMENT: LazyLock<Environment> = LazyLock::new(|| EnvironmentBuilder::default().build());
+
+// Any input must be rejected with an error, never with a panic
+fuzz_target!(|data: &[u8]| {
+    let Ok(module) = Module::from_bytes(data) else {
+        return
+    };
+
+    if ModuleValidator::new(&module, &ENVIRONMENT).verify().is_err() {
+        return
+    }
+
+    // A valid module is serialized back to the same module
+    let bytes = module.to_bytes().expect("valid module can't be serialized");
+    let decoded = Module::from_bytes(&bytes).expect("valid module can't be decoded back");
+    assert_eq!(decoded.to_bytes().unwrap(), bytes);
+});
@@ -42,13 +42,27 @@ pub enum LexerErrorKind {
     ExpectedType
 }
 
+// Comment found in the source code
+// Comments are only collected when the lexer keeps them
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Comment<'a> {
+    // comment text, including its `//` or `/* */` delimiters
+    pub text: &'a str,
+    // line where the comment starts
+    pub line: usize,
+    // line where the comment ends, only differs for multi-line comments
+    pub end_line: usize,
+    // column where the comment starts
+    pub column: usize
+}
+
 pub struct Lexer<'a> {
     // input code
     input: &'a str,
     // characters in the input
     // used to build tokens
     chars: VecDeque<char>,
-    // current position index in the input
+    // current byte position in the input
     // this is used to get slices from it
     pos: usize,
     // current line number we are reading
@@ -58,7 +72,9 @@ pub struct Lexer<'a> {
     // Used to keep track of the depth of the generics <...>
     generic_depth: usize,
     // Track if the last parsed token was an identifier
-    accept_generic: bool
+    accept_generic: bool,
+    // comments found, only when they are kept
+    comments: Option<Vec<Comment<'a>>>
 }
 
 impl<'a> Lexer<'a> {
@@ -71,10 +87,25 @@ impl<'a> Lexer<'a> {
             line: 1,
             column: 0,
             generic_depth: 0,
-            accept_generic: false
+            accept_generic: false,
+            comments: None
         }
     }
 
+    // keep the comments found while reading the tokens
+    // they are retrieved using `take_comments`
+    pub fn keep_comments(mut self) -> Self {
+        self.comments = Some(Vec::new());
+        self
+    }
+
+    // returns the comments collected since the last call
+    pub fn take_comments(&mut self) -> Vec<Comment<'a>> {
+        self.comments.as_mut()
+            .map(std::mem::take)
+            .unwrap_or_default()
+    }
+
     // peek the next character
     fn peek(&self) -> Result<char, LexerError> {
         self.chars.front()
@@ -88,9 +119,7 @@ impl<'a> Lexer<'a> {
 
     // advance by n characters
     fn advance_by(&mut self, n: usize) -> Result<(), LexerError> {
-        let drain = self.chars.drain(0..n);
-
-        if drain.len() != n {
+        if self.chars.len() < n {
             return Err(LexerError {
                 line: self.line,
                 column: self.column,
@@ -98,7 +127,7 @@ impl<'a> Lexer<'a> {
             });
         }
 
-        self.pos += n;
+        self.pos += self.chars.drain(0..n).map(char::len_utf8).sum::<usize>();
         self.column += n;
 
         Ok(())
@@ -117,7 +146,7 @@ impl<'a> Lexer<'a> {
     // get the next character
     fn next_char(&mut self) -> Option<char> {
         self.chars.pop_front().map(|c| {
-            self.pos += 1;
+            self.pos += c.len_utf8();
             self.column += 1;
             c
         })
@@ -135,7 +164,7 @@ impl<'a> Lexer<'a> {
 
     // push a character back to the list
     fn push_back(&mut self, c: char) {
-        self.pos -= 1;
+        self.pos -= c.len_utf8();
         self.column -= 1;
         self.chars.push_front(c);
     }
@@ -150,6 +179,11 @@ impl<'a> Lexer<'a> {
         let mut end_index = 0;
         for (i, s) in slice.chars().enumerate() {
             if alone_special_chars.contains(&s) {
+                // `?` can end an operator like the checked `+?`
+                let operator = slice.char_indices().nth(i).and_then(|(index, _)| slice.get(..=index));
+                if s == '?' && i > 0 && operator.and_then(Token::value_of).is_some() {
+                    end_index = i;
+                }
                 break;
             }
 
@@ -214,21 +248,6 @@ impl<'a> Lexer<'a> {
         self.get_slice(init_pos, self.pos)
     }
 
-    // this will consume characters until the delimiter returns true
-    fn skip_until<F>(&mut self, delimiter: F) -> Result<(), LexerError>
-    where 
-        F: Fn(&char) -> bool,
-    {
-        loop {
-            let c = self.advance()?;
-            if delimiter(&c) {
-                break;
-            }
-        }
-
-        Ok(())
-    }
-
     // this will read the whole string until the end character
     // it supports escaped characters
     fn read_string(&mut self, end: char) -> Result<Cow<'a, str>, LexerError> {
@@ -256,10 +275,14 @@ impl<'a> Lexer<'a> {
                 // skip the escaped character
             } else {
                 if escape {
-                    init_pos = self.pos - 1;
+                    init_pos = self.pos - c.len_utf8();
                     escape = false;
                 }
             }
+
+            if c == '\n' {
+                self.new_line();
+            }
         }
 
         Ok(match transformed_string {
@@ -351,6 +374,12 @@ impl<'a> Lexer<'a> {
         })
     }
 
+    // start a new line, the `\n` is already consumed
+    fn new_line(&mut self) {
+        self.line += 1;
+        self.column = 0;
+    }
+
     // read a multi-line comment
     // expected format is /* ... */
     fn skip_multi_line_comment(&mut self) -> Result<(), LexerError> {
@@ -359,12 +388,22 @@ impl<'a> Lexer<'a> {
             if c == '*' && self.peek()? == '/' {
                 self.advance()?;
                 break;
+            } else if c == '\n' {
+                self.new_line();
             }
         }
 
         Ok(())
     }
 
+    // read a single line comment until the end of the line
+    // the line break is not consumed
+    fn skip_line_comment(&mut self) {
+        while self.chars.front().is_some_and(|c| *c != '\n') {
+            self.next_char();
+        }
+    }
+
     // read a token
     // it also supports optional types
     fn read_token(&mut self, diff: usize) -> Result<TokenResult<'a>, LexerError> {
@@ -390,15 +429,14 @@ impl<'a> Lexer<'a> {
     fn next_token(&mut self) -> Result<Option<TokenResult<'a>>, LexerError> {
         while let Some(c) = self.next_char() {
             let token: TokenResult<'a> = match c {
-                '\n' | '\r' | '\t' => {
-                    debug!("Skipping whitespace");
-                    self.line += 1;
-                    self.column = 0;
+                '\n' => {
+                    debug!("Skipping new line");
+                    self.new_line();
                     self.accept_generic = false;
                     continue;
                 },
                 // skipped characters
-                ' ' | ';' => {
+                ' ' | '\r' | '\t' | ';' => {
                     debug!("Skipping character: {}", c);
                     // we just skip these characters
                     self.accept_generic = false;
@@ -408,11 +446,12 @@ impl<'a> Lexer<'a> {
                 // It supports escaped characters
                 '"' | '\'' => {
                     debug!("Reading string");
+                    let line = self.line;
                     let column_start = self.column;
                     let value = self.read_string(c)?;
                     TokenResult {
                         token: Token::Value(Literal::String(value)),
-                        line: self.line,
+                        line,
                         column_start,
                         column_end: self.column
                     }
@@ -423,17 +462,24 @@ impl<'a> Lexer<'a> {
                     v == '/' || v == '*'
                 } => {
                     debug!("Skipping comment");
+                    let (start, line, column) = (self.pos - 1, self.line, self.column);
                     let v = self.advance()?;
                     if v == '/' {
-                        self.skip_until(|c| *c == '\n')?;
+                        self.skip_line_comment();
                     } else {
                         self.skip_multi_line_comment()?;
                     }
+
+                    let text = self.get_slice(start, self.pos)?.trim_end();
+                    let end_line = self.line;
+                    if let Some(comments) = self.comments.as_mut() {
+                        comments.push(Comment { text, line, end_line, column });
+                    }
                     continue;
                 },
                 // read a number value
                 c if c.is_digit(10) => self.read_number(c)?,
-                c if c == '_' || c.is_alphabetic() => self.read_token(1)?,
+                c if c == '_' || c.is_alphabetic() => self.read_token(c.len_utf8())?,
                 _ => {
                     if let Some((token, diff)) = self.find_potential_token() {
                         trace!("Found potential token: {:?} with diff {}", token, diff);
@@ -507,6 +553,43 @@ mod tests {
         ]);
     }
 
+    #[test]
+    fn test_overflow_operators() {
+        let code = "+? -? *? +| -| *| +% -% *% a+?b";
+        let lexer = Lexer::new(code);
+        let tokens = lexer.get().unwrap();
+        assert_eq!(tokens, vec![
+            Token::OperatorCheckedPlus,
+            Token::OperatorCheckedMinus,
+            Token::OperatorCheckedMultiply,
+            Token::OperatorSaturatingPlus,
+            Token::OperatorSaturatingMinus,
+            Token::OperatorSaturatingMultiply,
+            Token::OperatorWrappingPlus,
+            Token::OperatorWrappingMinus,
+            Token::OperatorWrappingMultiply,
+            Token::Identifier("a"),
+            Token::OperatorCheckedPlus,
+            Token::Identifier("b")
+        ]);
+    }
+
+    #[test]
+    fn test_ternary_after_operator() {
+        let code = "a==b?c:d";
+        let lexer = Lexer::new(code);
+        let tokens = lexer.get().unwrap();
+        assert_eq!(tokens, vec![
+            Token::Identifier("a"),
+            Token::OperatorEquals,
+            Token::Identifier("b"),
+            Token::OperatorTernary,
+            Token::Identifier("c"),
+            Token::Colon,
+            Token::Identifier("d")
+        ]);
+    }
+
     #[test]
     fn test_assign_operator_without_whitespace() {
         let code = "a>>=10+7*3";
@@ -1140,4 +1223,45 @@ mod tests {
             Token::Identifier("b")
         ]);
     }
+    #[test]
+    fn test_accurate_line_after_comments() {
+        let code = "// comment\n/* multi\nline */\thello\r\nworld";
+        let mut lexer = Lexer::new(code);
+        let token = lexer.next().unwrap().unwrap();
+        assert_eq!(token.token, Token::Identifier("hello"));
+        assert_eq!(token.line, 3);
+        assert_eq!(token.column_start, 9);
+
+        let token = lexer.next().unwrap().unwrap();
+        assert_eq!(token.token, Token::Identifier("world"));
+        assert_eq!(token.line, 4);
+        assert_eq!(token.column_start, 1);
+    }
+
+    #[test]
+    fn test_keep_comments() {
+        let code = "let a = 10 // première valeur\n/* multi\nline */ let b = a";
+        let mut lexer = Lexer::new(code).keep_comments();
+        let tokens = lexer.by_ref()
+            .map(|v| v.map(|t| t.token))
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+
+        assert_eq!(tokens, vec![
+            Token::Let,
+            Token::Identifier("a"),
+            Token::OperatorAssign,
+            Token::Value(Literal::Number(10)),
+            Token::Let,
+            Token::Identifier("b"),
+            Token::OperatorAssign,
+            Token::Identifier("a")
+        ]);
+
+        assert_eq!(lexer.take_comments(), vec![
+            Comment { text: "// première valeur", line: 1, end_line: 1, column: 12 },
+            Comment { text: "/* multi\nline */", line: 2, end_line: 3, column: 1 }
+        ]);
+        assert!(lexer.take_comments().is_empty());
+    }
 }
\ No newline at end of file
@@ -0,0 +1,439 @@
+use std::collections::HashSet;
+
+use xelis_ast::{Token, TokenResult};
+use xelis_builder::{Builder, EnvironmentBuilder};
+use xelis_lexer::{Lexer, LexerError};
+use xelis_parser::{Parser, PartialProgram};
+use xelis_types::{IdentifierType, Type};
+
+// Position in a document, lines and characters start at 0 like in the protocol
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Position {
+    pub line: usize,
+    pub character: usize,
+}
+
+// The end position is excluded
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Range {
+    pub start: Position,
+    pub end: Position,
+}
+
+impl Range {
+    // The lines and columns of the tokens start at 1, the column end is included
+    fn new(line: usize, column_start: usize, column_end: usize) -> Self {
+        let line = line.saturating_sub(1);
+        Self {
+            start: Position {
+                line,
+                character: column_start.saturating_sub(1)
+            },
+            end: Position {
+                line,
+                character: column_end.max(column_start)
+            }
+        }
+    }
+
+    fn of_token(token: &TokenResult) -> Self {
+        Self::new(token.line, token.column_start, token.column_end)
+    }
+
+    fn contains(&self, position: Position) -> bool {
+        self.start.line == position.line
+            && self.start.character <= position.character
+            && position.character < self.end.character
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Severity {
+    Error,
+    Warning,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Diagnostic {
+    pub range: Range,
+    pub severity: Severity,
+    pub code: Option<&'static str>,
+    pub message: String,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum CompletionKind {
+    Function,
+    Method,
+    Struct,
+    Enum,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Completion {
+    pub label: String,
+    pub kind: CompletionKind,
+    pub detail: String,
+}
+
+// Declaration of a name found in the tokens
+struct Declaration {
+    // index of the name token
+    index: usize,
+    // a local is only visible after its declaration
+    local: bool,
+}
+
+// Tokens of a document
+// The lexing stops at the first error, the tokens read before it are kept
+pub struct Document<'a> {
+    tokens: Vec<TokenResult<'a>>,
+    lexer_error: Option<LexerError>,
+}
+
+impl<'a> Document<'a> {
+    pub fn new(text: &'a str) -> Self {
+        let mut tokens = Vec::new();
+        let mut lexer_error = None;
+        for result in Lexer::new(text) {
+            match result {
+                Ok(token) => tokens.push(token),
+                Err(e) => {
+                    lexer_error = Some(e);
+                    break;
+                }
+            }
+        }
+
+        Self {
+            tokens,
+            lexer_error
+        }
+    }
+
+    fn parse(&self, env: &'a EnvironmentBuilder<'a>) -> PartialProgram<'a> {
+        Parser::with(self.tokens.iter().cloned(), env).parse_lenient()
+    }
+
+    // Errors of the lexer and the parser, and the warnings of the parser
+    pub fn diagnostics(&self, env: &'a EnvironmentBuilder<'a>) -> Vec<Diagnostic> {
+        let partial = self.parse(env);
+        let mut diagnostics = Vec::new();
+        if let Some(e) = &self.lexer_error {
+            diagnostics.push(Diagnostic {
+                range: Range::new(e.line, e.column, e.column),
+                severity: Severity::Error,
+                code: None,
+                message: e.kind.to_string()
+            });
+        }
+
+        diagnostics.extend(partial.errors.iter().map(|e| Diagnostic {
+            range: Range::new(e.line, e.column_start, e.column_end),
+            severity: Severity::Error,
+            code: None,
+            message: match &e.suggestion {
+                Some(suggestion) => format!("{}, did you mean '{}'?", e.kind, suggestion),
+                None => e.kind.to_string()
+            }
+        }));
+
+        diagnostics.extend(partial.warnings.iter().map(|w| Diagnostic {
+            range: Range::new(w.span.line, w.span.column_start, w.span.column_end),
+            severity: Severity::Warning,
+            code: Some(w.code.as_str()),
+            message: w.message.clone()
+        }));
+
+        diagnostics
+    }
+
+    // Returns the index of the identifier token at this position
+    fn identifier_at(&self, position: Position) -> Option<(usize, &'a str)> {
+        self.tokens.iter()
+            .enumerate()
+            .find(|(_, token)| Range::of_token(token).contains(position))
+            .and_then(|(index, token)| match token.token {
+                Token::Identifier(name) => Some((index, name)),
+                _ => None
+            })
+    }
+
+    fn identifier(&self, index: usize) -> Option<&'a str> {
+        match self.tokens.get(index)?.token {
+            Token::Identifier(name) => Some(name),
+            _ => None
+        }
+    }
+
+    fn is(&self, index: usize, token: Token) -> bool {
+        self.tokens.get(index).is_some_and(|t| t.token == token)
+    }
+
+    // All the declarations of a name
+    // Found from the tokens only, so the scopes of the locals are not checked
+    fn declarations(&self, name: &str) -> Vec<Declaration> {
+        let mut declarations = Vec::new();
+        let mut in_signature = false;
+        for (index, token) in self.tokens.iter().enumerate() {
+            match token.token {
+                Token::Struct | Token::Enum | Token::Interface | Token::Const | Token::Entry => {
+                    if self.identifier(index + 1) == Some(name) {
+                        declarations.push(Declaration { index: index + 1, local: false });
+                    }
+                    in_signature = token.token == Token::Entry;
+                },
+                Token::Function => {
+                    // fn name(...) or fn (instance Type) name(...)
+                    let name_index = if self.is(index + 1, Token::ParenthesisOpen) {
+                        if self.identifier(index + 2) == Some(name) {
+                            declarations.push(Declaration { index: index + 2, local: true });
+                        }
+                        (index + 2..self.tokens.len())
+                            .find(|i| self.is(*i, Token::ParenthesisClose))
+                            .map(|i| i + 1)
+                    } else {
+                        Some(index + 1)
+                    };
+
+                    if let Some(i) = name_index.filter(|i| self.identifier(*i) == Some(name)) {
+                        declarations.push(Declaration { index: i, local: false });
+                    }
+                    in_signature = true;
+                },
+                Token::BraceOpen => in_signature = false,
+                // parameters
+                Token::Identifier(id) if in_signature && id == name && self.is(index + 1, Token::Colon) => {
+                    declarations.push(Declaration { index, local: true });
+                },
+                // let name, for name, foreach name
+                Token::Let | Token::For | Token::ForEach if self.identifier(index + 1) == Some(name) && !self.is(index + 2, Token::ParenthesisOpen) => {
+                    declarations.push(Declaration { index: index + 1, local: true });
+                },
+                // let (a, b) = ... or let Some(name) = ...
+                Token::ParenthesisOpen if self.is(index.wrapping_sub(1), Token::Let) || self.is(index.wrapping_sub(2), Token::Let) => {
+                    let names = (index + 1..self.tokens.len())
+                        .take_while(|i| !self.is(*i, Token::ParenthesisClose))
+                        .filter(|i| self.identifier(*i) == Some(name));
+                    declarations.extend(names.map(|index| Declaration { index, local: true }));
+                },
+                _ => {}
+            }
+        }
+
+        declarations
+    }
+
+    // Location of the declaration of the identifier at this position
+    // The closest local declared before it is preferred to a global declaration
+    pub fn definition(&self, position: Position) -> Option<Range> {
+        let (index, name) = self.identifier_at(position)?;
+        let declarations = self.declarations(name);
+        let local = declarations.iter()
+            .filter(|d| d.local && d.index <= index)
+            .max_by_key(|d| d.index);
+        let global = declarations.iter().find(|d| !d.local);
+
+        local.or(global)
+            .map(|d| Range::of_token(&self.tokens[d.index]))
+    }
+
+    // Description of the struct, enum or functions named by the identifier at this position
+    pub fn hover(&self, env: &'a EnvironmentBuilder<'a>, position: Position) -> Option<String> {
+        let (_, name) = self.identifier_at(position)?;
+        let partial = self.parse(env);
+        let mapper = &partial.mapper;
+
+        if let Ok(builder) = mapper.structs().get_by_name(name) {
+            let fields = builder.names()
+                .iter()
+                .zip(builder.get_type().fields())
+                .map(|(name, _type)| format!("{}: {}", name, _type))
+                .collect::<Vec<_>>();
+            return Some(format!("struct {} {{ {} }}", name, fields.join(", ")))
+        }
+
+        if let Ok(builder) = mapper.enums().get_by_name(name) {
+            let variants = builder.names()
+                .iter()
+                .zip(builder.builder_type().variants())
+                .map(|(name, fields)| if fields.is_empty() {
+                    name.to_string()
+                } else {
+                    let fields = fields.iter()
+                        .map(|(name, _type)| format!("{}: {}", name, _type))
+                        .collect::<Vec<_>>();
+                    format!("{} {{ {} }}", name, fields.join(", "))
+                })
+                .collect::<Vec<_>>();
+            return Some(format!("enum {} {{ {} }}", name, variants.join(", ")))
+        }
+
+        let mut signatures = mapper.functions()
+            .get_functions()
+            .into_iter()
+            .filter(|(_, signature, f)| f.name == name || signature.get_name() == name)
+            .map(|(id, signature, f)| {
+                let return_type = return_type(env, &partial, id);
+                format_signature(signature.get_name(), signature.get_on_type().as_ref(), &f.parameters, f.variadic, return_type.as_ref())
+            })
+            .collect::<Vec<_>>();
+        signatures.sort();
+
+        Some(signatures.join("\n")).filter(|s| !s.is_empty())
+    }
+
+    // Functions, structs and enums of the environment and of the document
+    pub fn completions(&self, env: &'a EnvironmentBuilder<'a>) -> Vec<Completion> {
+        let partial = self.parse(env);
+        let mapper = &partial.mapper;
+
+        let functions = mapper.functions()
+            .get_functions()
+            .into_iter()
+            .map(|(id, signature, f)| {
+                let on_type = signature.get_on_type().as_ref();
+                let return_type = return_type(env, &partial, id);
+                Completion {
+                    label: signature.get_name().clone(),
+                    kind: if on_type.is_some() { CompletionKind::Method } else { CompletionKind::Function },
+                    detail: format_signature(signature.get_name(), on_type, &f.parameters, f.variadic, return_type.as_ref())
+                }
+            });
+        let structs = mapper.structs()
+            .names()
+            .into_iter()
+            .map(|name| Completion {
+                label: name.to_owned(),
+                kind: CompletionKind::Struct,
+                detail: format!("struct {}", name)
+            });
+        let enums = mapper.enums()
+            .names()
+            .into_iter()
+            .map(|name| Completion {
+                label: name.to_owned(),
+                kind: CompletionKind::Enum,
+                detail: format!("enum {}", name)
+            });
+
+        // The same function may be registered for several types
+        let mut seen = HashSet::new();
+        let mut completions: Vec<_> = functions.chain(structs)
+            .chain(enums)
+            .filter(|c| seen.insert(c.clone()))
+            .collect();
+        completions.sort_by(|a, b| a.label.cmp(&b.label).then_with(|| a.detail.cmp(&b.detail)));
+        completions
+    }
+}
+
+// Return type of a native function or of a function of the document
+fn return_type(env: &EnvironmentBuilder, partial: &PartialProgram, id: IdentifierType) -> Option<Type> {
+    let natives = env.get_functions();
+    let index = id as usize;
+    if index < natives.len() {
+        natives[index].return_type().clone()
+    } else {
+        partial.program.functions()
+            .get(index - natives.len())
+            .and_then(|f| f.return_type().clone())
+    }
+}
+
+// fn name(a: u64, b: string) -> bool
+// Functions on a type are displayed as Type::name
+fn format_signature(name: &str, on_type: Option<&Type>, parameters: &[(&str, Type)], variadic: bool, return_type: Option<&Type>) -> String {
+    let mut parameters = parameters.iter()
+        .map(|(name, _type)| format!("{}: {}", name, _type))
+        .collect::<Vec<_>>();
+    if variadic {
+        parameters.push("...".to_owned());
+    }
+
+    let mut signature = match on_type {
+        Some(on_type) => format!("fn {}::{}({})", on_type, name, parameters.join(", ")),
+        None => format!("fn {}({})", name, parameters.join(", "))
+    };
+    if let Some(return_type) = return_type {
+        signature.push_str(&format!(" -> {}", return_type));
+    }
+
+    signature
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CODE: &str = r#"struct Point {
+    x: u64
+}
+
+fn double(value: u64) -> u64 {
+    let result: u64 = value * 2
+    return result
+}
+
+entry main() {
+    let point: Point = Point { x: 10 }
+    return double(point.x) + unknown
+}"#;
+
+    fn position(line: usize, character: usize) -> Position {
+        Position { line, character }
+    }
+
+    #[test]
+    fn test_diagnostics() {
+        let env = EnvironmentBuilder::default();
+        let document = Document::new(CODE);
+        let diagnostics = document.diagnostics(&env);
+
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0].severity, Severity::Error);
+        assert_eq!(diagnostics[0].range, Range::new(12, 30, 36));
+        assert_eq!(diagnostics[0].message, "unexpected variable name 'unknown'");
+    }
+
+    #[test]
+    fn test_definition() {
+        let document = Document::new(CODE);
+
+        // local variable
+        assert_eq!(document.definition(position(6, 12)), Some(Range::new(6, 9, 14)));
+        // parameter
+        assert_eq!(document.definition(position(5, 22)), Some(Range::new(5, 11, 15)));
+        // function
+        assert_eq!(document.definition(position(11, 12)), Some(Range::new(5, 4, 9)));
+        // struct
+        assert_eq!(document.definition(position(10, 16)), Some(Range::new(1, 8, 12)));
+        // not an identifier
+        assert_eq!(document.definition(position(10, 4)), None);
+    }
+
+    #[test]
+    fn test_hover() {
+        let env = EnvironmentBuilder::default();
+        let document = Document::new(CODE);
+
+        assert_eq!(document.hover(&env, position(10, 16)).as_deref(), Some("struct Point { x: u64 }"));
+        assert_eq!(document.hover(&env, position(11, 12)).as_deref(), Some("fn double(value: u64) -> u64"));
+        // variables are not described
+        assert_eq!(document.hover(&env, position(5, 22)), None);
+    }
+
+    #[test]
+    fn test_completions() {
+        let env = EnvironmentBuilder::default();
+        let document = Document::new(CODE);
+        let completions = document.completions(&env);
+
+        let find = |label: &str| completions.iter().find(|c| c.label == label);
+        assert_eq!(find("Point").map(|c| c.kind), Some(CompletionKind::Struct));
+        assert_eq!(find("double").map(|c| c.detail.as_str()), Some("fn double(value: u64) -> u64"));
+        // natives of the environment
+        assert_eq!(find("println").map(|c| c.kind), Some(CompletionKind::Function));
+        assert!(completions.iter().any(|c| c.label == "len" && c.kind == CompletionKind::Method));
+    }
+}
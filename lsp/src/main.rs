@@ -0,0 +1,20 @@
+mod analysis;
+mod server;
+mod transport;
+
+use std::io;
+use xelis_builder::EnvironmentBuilder;
+use server::Server;
+
+// Language server for the scripts, speaking the Language Server Protocol over stdio
+// It publishes the diagnostics of the lexer and the parser, and provides
+// the go-to-definition, the hover and the completion
+// The natives of the standard environment are known for the hover and the completion
+fn main() -> io::Result<()> {
+    let environment = EnvironmentBuilder::default();
+    let mut server = Server::new(&environment);
+
+    let stdin = io::stdin();
+    let stdout = io::stdout();
+    server.run(&mut stdin.lock(), &mut stdout.lock())
+}
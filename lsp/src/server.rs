@@ -0,0 +1,274 @@
+use std::{collections::HashMap, io::{self, BufRead, Write}};
+use serde_json::{json, Value};
+use xelis_builder::EnvironmentBuilder;
+
+use crate::{
+    analysis::{CompletionKind, Diagnostic, Document, Position, Range, Severity},
+    transport::{read_message, write_message}
+};
+
+// JSON-RPC error codes
+const METHOD_NOT_FOUND: i64 = -32601;
+const INVALID_PARAMS: i64 = -32602;
+
+// Language server over the lexer and the parser
+// Documents are fully synchronized and analyzed again on each request
+pub struct Server<'a> {
+    environment: &'a EnvironmentBuilder<'a>,
+    // text of the opened documents by uri
+    documents: HashMap<String, String>,
+    // set by the shutdown request, exit is then expected
+    shutdown: bool,
+}
+
+impl<'a> Server<'a> {
+    pub fn new(environment: &'a EnvironmentBuilder<'a>) -> Self {
+        Self {
+            environment,
+            documents: HashMap::new(),
+            shutdown: false
+        }
+    }
+
+    // Handle the messages until the exit notification or the end of the input
+    pub fn run(&mut self, reader: &mut impl BufRead, writer: &mut impl Write) -> io::Result<()> {
+        while let Some(message) = read_message(reader)? {
+            if message["method"] == "exit" {
+                break;
+            }
+
+            for response in self.handle(&message) {
+                write_message(writer, &response)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    // Returns the messages to send back
+    pub fn handle(&mut self, message: &Value) -> Vec<Value> {
+        let method = message["method"].as_str().unwrap_or_default();
+        let params = &message["params"];
+
+        // Requests have an id, notifications don't
+        let Some(id) = message.get("id") else {
+            return self.handle_notification(method, params)
+        };
+
+        let result = match method {
+            "initialize" => Ok(json!({
+                "capabilities": {
+                    // full text synchronization
+                    "textDocumentSync": 1,
+                    "hoverProvider": true,
+                    "definitionProvider": true,
+                    "completionProvider": {
+                        "triggerCharacters": [".", ":"]
+                    }
+                },
+                "serverInfo": {
+                    "name": "xelis-lsp",
+                    "version": env!("CARGO_PKG_VERSION")
+                }
+            })),
+            "shutdown" => {
+                self.shutdown = true;
+                Ok(Value::Null)
+            },
+            "textDocument/hover" => self.with_position(params, |document, env, position| {
+                document.hover(env, position)
+                    .map(|text| json!({
+                        "contents": {
+                            "kind": "markdown",
+                            "value": format!("```rust\n{}\n```", text)
+                        }
+                    }))
+                    .unwrap_or(Value::Null)
+            }),
+            "textDocument/definition" => {
+                let uri = params["textDocument"]["uri"].clone();
+                self.with_position(params, |document, _, position| {
+                    document.definition(position)
+                        .map(|range| json!({ "uri": uri, "range": range_to_json(range) }))
+                        .unwrap_or(Value::Null)
+                })
+            },
+            "textDocument/completion" => self.with_document(params, |document, env| {
+                let items = document.completions(env)
+                    .into_iter()
+                    .map(|c| json!({
+                        "label": c.label,
+                        "kind": completion_kind(c.kind),
+                        "detail": c.detail
+                    }))
+                    .collect::<Vec<_>>();
+                Value::Array(items)
+            }),
+            _ => Err((METHOD_NOT_FOUND, format!("method '{}' not found", method)))
+        };
+
+        let response = match result {
+            Ok(result) => json!({ "jsonrpc": "2.0", "id": id, "result": result }),
+            Err((code, message)) => json!({ "jsonrpc": "2.0", "id": id, "error": { "code": code, "message": message } })
+        };
+        vec![response]
+    }
+
+    fn handle_notification(&mut self, method: &str, params: &Value) -> Vec<Value> {
+        let uri = params["textDocument"]["uri"].as_str().unwrap_or_default().to_owned();
+        match method {
+            "textDocument/didOpen" => {
+                let text = params["textDocument"]["text"].as_str().unwrap_or_default();
+                self.documents.insert(uri.clone(), text.to_owned());
+                vec![self.publish_diagnostics(&uri)]
+            },
+            "textDocument/didChange" => {
+                // Full synchronization, the last change is the whole document
+                let text = params["contentChanges"].as_array()
+                    .and_then(|changes| changes.last())
+                    .and_then(|change| change["text"].as_str());
+                match text {
+                    Some(text) => {
+                        self.documents.insert(uri.clone(), text.to_owned());
+                        vec![self.publish_diagnostics(&uri)]
+                    },
+                    None => Vec::new()
+                }
+            },
+            "textDocument/didClose" => {
+                self.documents.remove(&uri);
+                // Clear the diagnostics of the closed document
+                vec![diagnostics_notification(&uri, Vec::new())]
+            },
+            _ => Vec::new()
+        }
+    }
+
+    fn publish_diagnostics(&self, uri: &str) -> Value {
+        let diagnostics = self.documents.get(uri)
+            .map(|text| Document::new(text).diagnostics(self.environment))
+            .unwrap_or_default();
+        diagnostics_notification(uri, diagnostics)
+    }
+
+    fn with_document(&self, params: &Value, f: impl FnOnce(&Document, &EnvironmentBuilder) -> Value) -> Result<Value, (i64, String)> {
+        let uri = params["textDocument"]["uri"].as_str()
+            .ok_or_else(|| (INVALID_PARAMS, "missing document uri".to_owned()))?;
+        let text = self.documents.get(uri)
+            .ok_or_else(|| (INVALID_PARAMS, format!("document '{}' is not opened", uri)))?;
+
+        Ok(f(&Document::new(text), self.environment))
+    }
+
+    fn with_position(&self, params: &Value, f: impl FnOnce(&Document, &EnvironmentBuilder, Position) -> Value) -> Result<Value, (i64, String)> {
+        let position = &params["position"];
+        let (Some(line), Some(character)) = (position["line"].as_u64(), position["character"].as_u64()) else {
+            return Err((INVALID_PARAMS, "missing position".to_owned()))
+        };
+
+        let position = Position {
+            line: line as usize,
+            character: character as usize
+        };
+        self.with_document(params, |document, env| f(document, env, position))
+    }
+}
+
+fn diagnostics_notification(uri: &str, diagnostics: Vec<Diagnostic>) -> Value {
+    let diagnostics = diagnostics.into_iter()
+        .map(|d| json!({
+            "range": range_to_json(d.range),
+            "severity": match d.severity {
+                Severity::Error => 1,
+                Severity::Warning => 2
+            },
+            "code": d.code,
+            "source": "xelis",
+            "message": d.message
+        }))
+        .collect::<Vec<_>>();
+
+    json!({
+        "jsonrpc": "2.0",
+        "method": "textDocument/publishDiagnostics",
+        "params": {
+            "uri": uri,
+            "diagnostics": diagnostics
+        }
+    })
+}
+
+fn range_to_json(range: Range) -> Value {
+    json!({
+        "start": { "line": range.start.line, "character": range.start.character },
+        "end": { "line": range.end.line, "character": range.end.character }
+    })
+}
+
+// Values of the CompletionItemKind of the protocol
+fn completion_kind(kind: CompletionKind) -> u8 {
+    match kind {
+        CompletionKind::Method => 2,
+        CompletionKind::Function => 3,
+        CompletionKind::Struct => 22,
+        CompletionKind::Enum => 13,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const URI: &str = "file:///main.slx";
+
+    fn open(server: &mut Server, text: &str) -> Value {
+        let message = json!({
+            "jsonrpc": "2.0",
+            "method": "textDocument/didOpen",
+            "params": { "textDocument": { "uri": URI, "languageId": "xelis", "version": 1, "text": text } }
+        });
+        server.handle(&message).remove(0)
+    }
+
+    #[test]
+    fn test_diagnostics_published() {
+        let env = EnvironmentBuilder::default();
+        let mut server = Server::new(&env);
+
+        let notification = open(&mut server, "entry main() {\n    return valeu\n}");
+        assert_eq!(notification["method"], "textDocument/publishDiagnostics");
+        let diagnostics = notification["params"]["diagnostics"].as_array().unwrap();
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0]["severity"], 1);
+        assert_eq!(diagnostics[0]["range"]["start"], json!({ "line": 1, "character": 11 }));
+    }
+
+    #[test]
+    fn test_requests() {
+        let env = EnvironmentBuilder::default();
+        let mut server = Server::new(&env);
+        open(&mut server, "struct Point {\n    x: u64\n}\n\nentry main() {\n    let point: Point = Point { x: 1 }\n    return point.x\n}");
+
+        let request = |method: &str, line: u64, character: u64| json!({
+            "jsonrpc": "2.0",
+            "id": 1,
+            "method": method,
+            "params": {
+                "textDocument": { "uri": URI },
+                "position": { "line": line, "character": character }
+            }
+        });
+
+        let response = server.handle(&request("textDocument/definition", 6, 12)).remove(0);
+        assert_eq!(response["result"]["range"]["start"], json!({ "line": 5, "character": 8 }));
+
+        let response = server.handle(&request("textDocument/hover", 5, 16)).remove(0);
+        assert_eq!(response["result"]["contents"]["value"], "```rust\nstruct Point { x: u64 }\n```");
+
+        let response = server.handle(&request("textDocument/completion", 0, 0)).remove(0);
+        assert!(response["result"].as_array().unwrap().iter().any(|item| item["label"] == "Point"));
+
+        let response = server.handle(&request("unknown", 0, 0)).remove(0);
+        assert_eq!(response["error"]["code"], METHOD_NOT_FOUND);
+    }
+}
@@ -1,15 +1,28 @@
+use xelis_ast::Span;
 use xelis_types::{IdentifierType, Type};
 
+#[derive(Clone, Debug)]
+struct Variable<'a> {
+    name: &'a str,
+    _type: Type,
+    // Span of the declaration, only set for the local variables
+    // that must be reported when unused
+    declared_at: Option<Span>,
+    used: bool,
+}
+
 #[derive(Clone, Debug)]
 pub struct Context<'a> {
     // scopes are used to store variables
-    scopes: Vec<(&'a str, Type)>,
+    scopes: Vec<Variable<'a>>,
     // checkpoints are used to manage scopes
     checkpoints: Vec<usize>,
     // max variables count for each scope depth
     max_variables_count: usize,
     // is_in_loop is used to allow the use of the break and continue keywords
     is_in_loop: bool,
+    // local variables never used, collected when their scope ends
+    unused_variables: Vec<(&'a str, Span)>,
 }
 
 impl<'a> Context<'a> {
@@ -21,13 +34,14 @@ impl<'a> Context<'a> {
             checkpoints: Vec::new(),
             max_variables_count: 0,
             is_in_loop: false,
+            unused_variables: Vec::new(),
         }
     }
 
     // get the value type of a variable registered in scopes using its name
     pub fn get_type_of_variable<'b>(&'b self, key: &IdentifierType) -> Option<&'b Type> {
         self.scopes.get(*key as usize)
-            .map(|v| &v.1)
+            .map(|v| &v._type)
     }
 
     // returns true if this variable name is registered in scopes
@@ -41,8 +55,31 @@ impl<'a> Context<'a> {
         self.max_variables_count as IdentifierType
     }
 
+    // the latest declaration is used, so a variable can be shadowed
     pub fn get_variable_id(&self, key: &str) -> Option<IdentifierType> {
-        self.scopes.iter().position(|(k, _)| *k == key).map(|v| v as IdentifierType)
+        self.scopes.iter().rposition(|v| v.name == key).map(|v| v as IdentifierType)
+    }
+
+    // names of all the variables in scope
+    pub fn variable_names(&self) -> impl Iterator<Item = &'a str> + '_ {
+        self.scopes.iter().map(|v| v.name)
+    }
+
+    // variables that can be accessed by their name, in their declaration order
+    // a shadowed variable is not returned
+    pub fn visible_variables(&self) -> Vec<(&'a str, IdentifierType)> {
+        self.scopes.iter()
+            .enumerate()
+            .filter(|(i, v)| self.get_variable_id(v.name) == Some(*i as IdentifierType))
+            .map(|(i, v)| (v.name, i as IdentifierType))
+            .collect()
+    }
+
+    // same as get_variable_id but the variable is marked as used
+    pub fn use_variable(&mut self, key: &str) -> Option<IdentifierType> {
+        let id = self.get_variable_id(key)?;
+        self.scopes[id as usize].used = true;
+        Some(id)
     }
 
     // register a variable in the current scope
@@ -56,7 +93,24 @@ impl<'a> Context<'a> {
 
     // register a variable in the current scope unchecked
     pub fn register_variable_unchecked(&mut self, key: &'a str, var_type: Type) -> IdentifierType {
-        self.scopes.push((key, var_type));
+        self.push_variable(key, var_type, None)
+    }
+
+    // register a local variable declared in the source code
+    // it is reported when its scope ends without being used
+    // `_` is never reported
+    pub fn declare_variable(&mut self, key: &'a str, var_type: Type, span: Span) -> IdentifierType {
+        let declared_at = Some(span).filter(|_| key != "_");
+        self.push_variable(key, var_type, declared_at)
+    }
+
+    fn push_variable(&mut self, name: &'a str, _type: Type, declared_at: Option<Span>) -> IdentifierType {
+        self.scopes.push(Variable {
+            name,
+            _type,
+            declared_at,
+            used: false
+        });
 
         (self.scopes.len() - 1) as IdentifierType
     }
@@ -73,7 +127,28 @@ impl<'a> Context<'a> {
         if self.max_variables_count < len {
             self.max_variables_count = len;
         }
-        self.scopes.truncate(checkpoint);
+
+        let unused = self.scopes.drain(checkpoint..)
+            .filter(|v| !v.used)
+            .filter_map(|v| v.declared_at.map(|span| (v.name, span)));
+        self.unused_variables.extend(unused);
+    }
+
+    // returns the unused variables collected since the last call
+    pub fn take_unused_variables(&mut self) -> Vec<(&'a str, Span)> {
+        std::mem::take(&mut self.unused_variables)
+    }
+
+    // number of scopes opened
+    pub fn scope_depth(&self) -> usize {
+        self.checkpoints.len()
+    }
+
+    // close the scopes opened after the given depth
+    pub fn end_scopes_until(&mut self, depth: usize) {
+        while self.checkpoints.len() > depth {
+            self.end_scope();
+        }
     }
 
     // returns if the Context is in a loop
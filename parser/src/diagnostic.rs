@@ -0,0 +1,123 @@
+use std::fmt;
+
+use xelis_ast::Span;
+
+// Kind of a warning, the program is still valid
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum WarningCode {
+    // A local variable is declared but never read or assigned
+    UnusedVariable,
+    // A statement can't be reached because the previous one always
+    // returns, breaks or continues
+    UnreachableCode,
+    // A variable has the same name as a variable or constant in scope
+    ShadowedIdentifier,
+}
+
+impl WarningCode {
+    // Stable name of the code, used by tooling
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::UnusedVariable => "unused_variable",
+            Self::UnreachableCode => "unreachable_code",
+            Self::ShadowedIdentifier => "shadowed_identifier",
+        }
+    }
+}
+
+impl fmt::Display for WarningCode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+// Warning produced while parsing a program
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Warning {
+    pub span: Span,
+    pub code: WarningCode,
+    pub message: String,
+}
+
+impl Warning {
+    pub fn new(span: Span, code: WarningCode, message: String) -> Self {
+        Self {
+            span,
+            code,
+            message
+        }
+    }
+}
+
+impl fmt::Display for Warning {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "warning[{}] at line {}, column {} to {}: {}", self.code, self.span.line, self.span.column_start, self.span.column_end, self.message)
+    }
+}
+
+// Find the known name closest to a misspelled one
+// Only the names at a small edit distance are suggested
+pub(crate) fn find_similar<'b>(name: &str, candidates: impl IntoIterator<Item = &'b str>) -> Option<&'b str> {
+    let max_distance = (name.chars().count() / 3).max(1);
+    candidates.into_iter()
+        .filter(|candidate| *candidate != name)
+        .map(|candidate| (edit_distance(name, candidate), candidate))
+        .filter(|(distance, _)| *distance <= max_distance)
+        // the name is used on equal distance to always give the same suggestion
+        .min()
+        .map(|(_, candidate)| candidate)
+}
+
+// Edit distance counting insertions, deletions, substitutions
+// and transpositions of two adjacent characters
+fn edit_distance(a: &str, b: &str) -> usize {
+    let a: Vec<char> = a.chars().collect();
+    let b: Vec<char> = b.chars().collect();
+    // distances[i][j] is the distance between the first i chars of a and the first j chars of b
+    let mut distances = vec![vec![0; b.len() + 1]; a.len() + 1];
+    for (i, row) in distances.iter_mut().enumerate() {
+        row[0] = i;
+    }
+    for (j, distance) in distances[0].iter_mut().enumerate() {
+        *distance = j;
+    }
+
+    for i in 1..=a.len() {
+        for j in 1..=b.len() {
+            let cost = (a[i - 1] != b[j - 1]) as usize;
+            let mut distance = (distances[i - 1][j - 1] + cost)
+                .min(distances[i - 1][j] + 1)
+                .min(distances[i][j - 1] + 1);
+            if i > 1 && j > 1 && a[i - 1] == b[j - 2] && a[i - 2] == b[j - 1] {
+                distance = distance.min(distances[i - 2][j - 2] + 1);
+            }
+            distances[i][j] = distance;
+        }
+    }
+
+    distances[a.len()][b.len()]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_edit_distance() {
+        assert_eq!(edit_distance("value", "value"), 0);
+        assert_eq!(edit_distance("valeu", "value"), 1);
+        assert_eq!(edit_distance("vlaeu", "value"), 2);
+        assert_eq!(edit_distance("vale", "value"), 1);
+        assert_eq!(edit_distance("", "abc"), 3);
+    }
+
+    #[test]
+    fn test_find_similar() {
+        let names = ["value", "values", "total", "VALUE"];
+        assert_eq!(find_similar("valeu", names), Some("value"));
+        assert_eq!(find_similar("totl", names), Some("total"));
+        assert_eq!(find_similar("counter", names), None);
+        // an exact match is not a suggestion
+        assert_eq!(find_similar("total", ["total"]), None);
+    }
+}
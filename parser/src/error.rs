@@ -1,17 +1,97 @@
-use std::borrow::Cow;
+use std::{borrow::Cow, fmt};
 
 use thiserror::Error;
-use xelis_ast::{Expression, Token};
+use xelis_ast::{Expression, Span, Token};
 use xelis_builder::BuilderError;
-use xelis_types::{Type, ValueError, IdentifierType};
+use xelis_types::{Type, Value, ValueError, IdentifierType};
 
 #[derive(Debug, Error)]
-#[error("error at line {line}, column {column_start} to {column_end}: {kind}")]
+#[error("error at line {line}, column {column_start} to {column_end}: {kind}{}", .suggestion.as_ref().map(|s| format!(", did you mean '{}'?", s)).unwrap_or_default())]
 pub struct ParserError<'a> {
     pub line: usize,
     pub column_start: usize,
     pub column_end: usize,
-    pub kind: ParserErrorKind<'a>
+    pub kind: ParserErrorKind<'a>,
+    // Closest known name for a misspelled identifier
+    pub suggestion: Option<String>
+}
+
+impl<'a> ParserError<'a> {
+    // Set the closest known name, if any
+    pub fn with_suggestion(mut self, suggestion: Option<&str>) -> Self {
+        self.suggestion = suggestion.map(str::to_owned);
+        self
+    }
+
+    // Report the error on another token than the last one read
+    pub(crate) fn with_span(mut self, span: Span) -> Self {
+        self.line = span.line;
+        self.column_start = span.column_start;
+        self.column_end = span.column_end;
+        self
+    }
+
+    // Location of the token where the error happened
+    pub fn span(&self) -> Span {
+        Span {
+            line: self.line,
+            column_start: self.column_start,
+            column_end: self.column_end
+        }
+    }
+
+    // Display the error with the line of the source code it comes from
+    // The source must be the code given to the lexer
+    pub fn with_source<'b>(&'b self, source: &'b str) -> SourceError<'b, 'a> {
+        SourceError {
+            error: self,
+            source
+        }
+    }
+}
+
+// Render a parser error with the source line and a caret under the token:
+//
+// error: unexpected variable name 'valeu'
+//  --> line 3, column 12
+//   |
+// 3 |     return valeu
+//   |            ^^^^^
+//   = help: did you mean 'value'?
+pub struct SourceError<'b, 'a> {
+    error: &'b ParserError<'a>,
+    source: &'b str
+}
+
+impl fmt::Display for SourceError<'_, '_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let error = self.error;
+        let number = error.line.to_string();
+        let padding = " ".repeat(number.len());
+
+        writeln!(f, "error: {}", error.kind)?;
+        write!(f, "{}--> line {}, column {}", padding, error.line, error.column_start)?;
+
+        // Lines and columns start at 1, a token without position is at line 0
+        let line = error.line.checked_sub(1)
+            .and_then(|i| self.source.lines().nth(i));
+        if let Some(line) = line {
+            // Tabs are kept so the caret is aligned with the source line
+            let indent: String = line.chars()
+                .take(error.column_start.saturating_sub(1))
+                .map(|c| if c == '\t' { '\t' } else { ' ' })
+                .collect();
+            let len = (error.column_end + 1).saturating_sub(error.column_start).max(1);
+
+            write!(f, "\n{} |\n{} | {}\n{} | {}{}", padding, number, line, padding, indent, "^".repeat(len))?;
+        }
+
+        if let Some(suggestion) = &error.suggestion {
+            write!(f, "\n{} = help: did you mean '{}'?", padding, suggestion)?;
+        }
+
+        Ok(())
+    }
 }
 
 #[derive(Debug, Error)]
@@ -30,6 +110,14 @@ pub enum ParserErrorKind<'a> {
     NoValueForVariable(&'a str),
     #[error("cannot call this function, its an entry function")]
     FunctionIsEntry,
+    #[error("cannot call this function, its a test function")]
+    FunctionIsTest,
+    #[error("cannot call this function on an instance, its a static function")]
+    FunctionIsStatic,
+    #[error("cannot call this function without an instance")]
+    FunctionRequiresInstance,
+    #[error("function '{0}' can't be given to the iterator, it must be declared before, take a value of the iterator and return a value, a bool for a filter")]
+    InvalidFunctionReference(&'a str),
     #[error("invalid field name, got '{0}' but expected '{1}'")]
     InvalidFieldName(&'a str, &'a str),
     #[error("enum variant name '{0}' is already used")]
@@ -38,6 +126,16 @@ pub enum ParserErrorKind<'a> {
     InvalidEnumFieldName(&'a str),
     #[error("enum type name '{0}' is already used")]
     TypeNameAlreadyUsed(&'a str),
+    #[error("interface '{0}' not found")]
+    InterfaceNotFound(&'a str),
+    #[error("interface function '{0}' is already declared")]
+    InterfaceFunctionAlreadyUsed(&'a str),
+    #[error("interface function '{0}' is not implemented")]
+    MissingInterfaceFunction(&'a str),
+    #[error("function '{0}' does not match its interface signature")]
+    InvalidInterfaceFunction(&'a str),
+    #[error("function '{0}' is not part of the interface")]
+    FunctionNotInInterface(&'a str),
     #[error("enum name cannot be empty")]
     EmptyEnumName,
     #[error("invalid enum name '{0}'")]
@@ -52,10 +150,19 @@ pub enum ParserErrorKind<'a> {
     InvalidRangeType(Type, Type),
     #[error("invalid range type '{0}'")]
     InvalidRangeTypePrimitive(Type),
+    #[error("invalid switch type '{0}', expected a number or a string")]
+    InvalidSwitchType(Type),
+    #[error("switch case '{0}' is already used")]
+    DuplicatedSwitchCase(Value),
+    #[error("the default case must be the last one of the switch")]
+    SwitchDefaultNotLast,
+    #[error("fallthrough must end a case followed by another one")]
+    InvalidFallthrough,
+    // The wrapped errors and the expressions are boxed to not grow the size of the error
     #[error(transparent)]
-    ValueError(#[from] ValueError),
+    ValueError(Box<ValueError>),
     #[error(transparent)]
-    BuilderError(#[from] BuilderError),
+    BuilderError(Box<BuilderError>),
     #[error("invalid struct field order")]
     InvalidStructFieldOrder,
     #[error("invalid field count")]
@@ -74,6 +181,10 @@ pub enum ParserErrorKind<'a> {
     AssignReturnNothing,
     #[error("entry function cannot have a type")]
     EntryFunctionCannotHaveForType,
+    #[error("test function cannot have parameters")]
+    TestFunctionCannotHaveParameters,
+    #[error("public function cannot have a type")]
+    PublicFunctionCannotHaveForType,
     #[error("expected token")]
     ExpectedToken,
     #[error("variable name must start with an alphabetic character: '{0}'")]
@@ -82,8 +193,8 @@ pub enum ParserErrorKind<'a> {
     ExpectedIdentifierToken(Token<'a>),
     #[error("unexpected token '{0:?}'")]
     UnexpectedToken(Token<'a>),
-    #[error("invalid token, got '{0:?}' expected '{1:?}'")]
-    InvalidToken(Token<'a>, Token<'a>),
+    #[error("invalid token, got '{:?}' expected '{:?}'", .0.0, .0.1)]
+    InvalidToken(Box<(Token<'a>, Token<'a>)>),
     #[error("variable name is already used: {0}")]
     VariableNameAlreadyUsed(&'a str),
     #[error("variable id is already used: {0}")]
@@ -123,11 +234,11 @@ pub enum ParserErrorKind<'a> {
     #[error("dead code not allowed")]
     DeadCodeNotAllowed,
     #[error("invalid for expression '{0:?}'")]
-    InvalidForExpression(Expression),
+    InvalidForExpression(Box<Expression>),
     #[error("operator not found for token '{0:?}'")]
     OperatorNotFound(Token<'a>),
     #[error("invalid condition for type '{0}': {1:?}")]
-    InvalidCondition(Type, Expression),
+    InvalidCondition(Type, Box<Expression>),
     #[error("invalid operation: not same type: '{0}' and '{1}'")]
     InvalidOperationNotSameType(Type, Type),
     #[error("cast error: '{0}' and '{1:?}'")]
@@ -144,8 +255,44 @@ pub enum ParserErrorKind<'a> {
     NoValueType,
     #[error("empty array constructor")]
     EmptyArrayConstructor,
+    #[error("invalid tuple size: {0}")]
+    InvalidTupleSize(usize),
+    #[error("invalid tuple index {0} for type '{1}'")]
+    InvalidTupleIndex(usize, Type),
+    #[error("expected a tuple, got '{0}'")]
+    ExpectedTuple(Type),
+    #[error("expected a struct, got '{0}'")]
+    ExpectedStruct(Type),
+    #[error("expected an array, got '{0}'")]
+    ExpectedArray(Type),
+    #[error("field '{0}' is already used in the pattern")]
+    DuplicatedFieldInPattern(&'a str),
+    #[error("missing fields in the struct pattern, use '..' to ignore them")]
+    MissingFieldsInPattern,
+    #[error("expected an optional, got '{0}'")]
+    ExpectedOptional(Type),
+    #[error("expected a result, got '{0}'")]
+    ExpectedResult(Type),
+    #[error("cannot propagate the error of '{0}', the function must return a result with the same error type")]
+    InvalidTryReturnType(Type),
+    #[error("unknown attribute '{0}'")]
+    UnknownAttribute(&'a str),
+    #[error("an attribute must be followed by a function")]
+    AttributeWithoutFunction,
     #[error("invalid map key type")]
     InvalidMapKeyType,
     #[error("unknown error")]
     UnknownError,
-}
\ No newline at end of file
+}
+
+impl From<ValueError> for ParserErrorKind<'_> {
+    fn from(error: ValueError) -> Self {
+        Self::ValueError(Box::new(error))
+    }
+}
+
+impl From<BuilderError> for ParserErrorKind<'_> {
+    fn from(error: BuilderError) -> Self {
+        Self::BuilderError(Box::new(error))
+    }
+}
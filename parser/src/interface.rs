@@ -0,0 +1,68 @@
+use xelis_types::Type;
+
+// `Self` in an interface declaration is stored as this generic type
+// It is replaced by the implementing type when the interface is implemented
+pub const SELF_TYPE: Type = Type::T(0);
+
+// A function required by an interface
+#[derive(Debug, Clone)]
+pub struct InterfaceFunction<'a> {
+    pub name: &'a str,
+    // Does the function receive the instance as `self`
+    pub instance: bool,
+    pub parameters: Vec<Type>,
+    pub return_type: Option<Type>,
+}
+
+// Interface declared by the program
+// A struct implementing it must declare all its functions with the same signatures
+// Calls are resolved statically as for any other function of the struct
+#[derive(Debug, Clone)]
+pub struct Interface<'a> {
+    functions: Vec<InterfaceFunction<'a>>,
+}
+
+impl<'a> Interface<'a> {
+    pub fn new(functions: Vec<InterfaceFunction<'a>>) -> Self {
+        Self {
+            functions
+        }
+    }
+
+    pub fn functions(&self) -> &[InterfaceFunction<'a>] {
+        &self.functions
+    }
+
+    pub fn get_function(&self, name: &str) -> Option<&InterfaceFunction<'a>> {
+        self.functions.iter().find(|f| f.name == name)
+    }
+}
+
+// Replace `Self` by the implementing type, including in the inner types
+pub fn resolve_self_type(_type: &Type, zelf: &Type) -> Type {
+    match _type {
+        Type::T(0) => zelf.clone(),
+        Type::Array(inner) => Type::Array(Box::new(resolve_self_type(inner, zelf))),
+        Type::Optional(inner) => Type::Optional(Box::new(resolve_self_type(inner, zelf))),
+        Type::Range(inner) => Type::Range(Box::new(resolve_self_type(inner, zelf))),
+        Type::Iterator(inner) => Type::Iterator(Box::new(resolve_self_type(inner, zelf))),
+        Type::Map(key, value) => Type::Map(Box::new(resolve_self_type(key, zelf)), Box::new(resolve_self_type(value, zelf))),
+        Type::Result(value, error) => Type::Result(Box::new(resolve_self_type(value, zelf)), Box::new(resolve_self_type(error, zelf))),
+        Type::Tuple(types) => Type::Tuple(types.iter().map(|t| resolve_self_type(t, zelf)).collect()),
+        _ => _type.clone()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_resolve_self_type() {
+        let zelf = Type::U64;
+        assert_eq!(resolve_self_type(&SELF_TYPE, &zelf), Type::U64);
+        assert_eq!(resolve_self_type(&Type::Array(Box::new(SELF_TYPE)), &zelf), Type::Array(Box::new(Type::U64)));
+        assert_eq!(resolve_self_type(&Type::Map(Box::new(Type::String), Box::new(SELF_TYPE)), &zelf), Type::Map(Box::new(Type::String), Box::new(Type::U64)));
+        assert_eq!(resolve_self_type(&Type::Bool, &zelf), Type::Bool);
+    }
+}
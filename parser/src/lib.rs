@@ -1,5 +1,7 @@
 mod context;
+mod diagnostic;
 mod error;
+mod interface;
 mod mapper;
 
 use std::{
@@ -7,7 +9,6 @@ use std::{
     collections::{HashMap, VecDeque},
     mem
 };
-use error::ParserErrorKind;
 use log::trace;
 use mapper::GlobalMapper;
 use xelis_builder::{Builder, EnvironmentBuilder};
@@ -15,8 +16,19 @@ use xelis_ast::*;
 use xelis_environment::NativeFunction;
 use xelis_types::*;
 use context::Context;
+use interface::{resolve_self_type, Interface, InterfaceFunction, SELF_TYPE};
 
-pub use error::ParserError;
+pub use error::{ParserError, ParserErrorKind, SourceError};
+pub use diagnostic::{Warning, WarningCode};
+use diagnostic::find_similar;
+
+// Name of the hidden variable holding the optional of a if let or while let
+// It is not a valid identifier, so it can't be used by the scripts
+const OPTIONAL_VARIABLE: &str = "$optional";
+// Name of the hidden variable holding the value of a destructuring declaration
+const DESTRUCTURING_VARIABLE: &str = "$destructuring";
+// Name of the hidden variable holding the value compared by a switch
+const SWITCH_VARIABLE: &str = "$switch";
 
 macro_rules! err {
     ($self: expr, $kind: expr) => {
@@ -24,75 +36,12 @@ macro_rules! err {
             line: $self.line,
             column_start: $self.column_start,
             column_end: $self.column_end,
-            kind: $kind
-        }
-    };
-}
-
-
-macro_rules! op {
-    ($a: expr, $b: expr, $op: tt) => {{
-        match ($a, $b) {
-            (Value::U8(a), Value::U8(b)) => Value::U8(a $op b),
-            (Value::U16(a), Value::U16(b)) => Value::U16(a $op b),
-            (Value::U32(a), Value::U32(b)) => Value::U32(a $op b),
-            (Value::U64(a), Value::U64(b)) => Value::U64(a $op b),
-            (Value::U128(a), Value::U128(b)) => Value::U128(a $op b),
-            _ => return None
-        }
-    }};
-}
-
-macro_rules! op_div {
-    ($t: ident, $a: expr, $b: expr) => {
-        {
-            if *$b == 0 {
-                return None
-            }
-    
-            Value::$t($a / $b)
-        }
-    };
-    ($a: expr, $b: expr) => {
-        match ($a, $b) {
-            (Value::U8(a), Value::U8(b)) => op_div!(U8, a, b),
-            (Value::U16(a), Value::U16(b)) => op_div!(U16, a, b),
-            (Value::U32(a), Value::U32(b)) => op_div!(U32, a, b),
-            (Value::U64(a), Value::U64(b)) => op_div!(U64, a, b),
-            (Value::U128(a), Value::U128(b)) => op_div!(U128, a, b),
-            _ => return None
+            kind: $kind,
+            suggestion: None
         }
     };
 }
 
-macro_rules! op_bool {
-    ($a: expr, $b: expr, $op: tt) => {{
-        match ($a, $b) {
-            (Value::Boolean(a), Value::Boolean(b)) => Value::Boolean(a $op b),
-            (Value::U8(a), Value::U8(b)) => Value::Boolean(a $op b),
-            (Value::U16(a), Value::U16(b)) => Value::Boolean(a $op b),
-            (Value::U32(a), Value::U32(b)) => Value::Boolean(a $op b),
-            (Value::U64(a), Value::U64(b)) => Value::Boolean(a $op b),
-            (Value::U128(a), Value::U128(b)) => Value::Boolean(a $op b),
-            _ => return None
-        }
-    }};
-}
-
-macro_rules! op_num_with_bool {
-    ($a: expr, $b: expr, $op: tt) => {{
-        match ($a, $b) {
-            (Value::Boolean(a), Value::Boolean(b)) => Value::Boolean(a $op b),
-            (Value::U8(a), Value::U8(b)) => Value::U8(a $op b),
-            (Value::U16(a), Value::U16(b)) => Value::U16(a $op b),
-            (Value::U32(a), Value::U32(b)) => Value::U32(a $op b),
-            (Value::U64(a), Value::U64(b)) => Value::U64(a $op b),
-            (Value::U128(a), Value::U128(b)) => Value::U128(a $op b),
-            _ => return None
-        }
-    }};
-}
-
 enum Function<'a> {
     Native(&'a NativeFunction),
     Program(&'a FunctionType)
@@ -112,6 +61,52 @@ impl<'a> Function<'a> {
             _ => false
         }
     }
+
+    fn is_test(&self) -> bool {
+        match self {
+            Function::Program(f) => f.is_test(),
+            _ => false
+        }
+    }
+
+    // Is the function called on an instance
+    fn has_instance(&self) -> bool {
+        match self {
+            Function::Native(f) => f.get_for_type().is_some(),
+            Function::Program(f) => f.get_instance_name().is_some()
+        }
+    }
+
+    // A native function on any type returning any value returns the type of its instance
+    // Used by the functions copying their instance, like `clone`
+    fn returns_instance(&self) -> bool {
+        match self {
+            Function::Native(f) => matches!((f.get_for_type(), f.return_type()), (Some(Type::Any), Some(Type::Any))),
+            Function::Program(_) => false
+        }
+    }
+}
+
+// Kind of the function being read
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum FunctionKind {
+    Declared,
+    Public,
+    Entry,
+    Test
+}
+
+// Pattern of a destructuring declaration
+// Its bindings are declared once the value has been read
+#[derive(Debug)]
+enum Pattern<'a> {
+    // `_` ignores the value
+    Binding(&'a str),
+    Tuple(Vec<Pattern<'a>>),
+    // Fields by name, `..` ignores the remaining fields
+    Struct(Vec<(&'a str, Pattern<'a>)>, bool),
+    // `..` ignores the remaining values instead of requiring the exact length
+    Array(Vec<Pattern<'a>>, bool),
 }
 
 pub struct Parser<'a> {
@@ -131,7 +126,49 @@ pub struct Parser<'a> {
     // Used for errors, we track the line and column
     line: usize,
     column_start: usize,
-    column_end: usize
+    column_end: usize,
+    // Spans of the statements read in the current function
+    statements_spans: Vec<Span>,
+    // All interfaces declared by the program
+    interfaces: HashMap<&'a str, Interface<'a>>,
+    // Type used for `Self` in an impl block or an interface
+    self_type: Option<Type>,
+    // Constants declared in the impl blocks, accessed as `Type::NAME`
+    type_constants: HashMap<Type, HashMap<&'a str, ConstantDeclaration>>,
+    // Return type of the function being read, used by the `?` operator
+    return_type: Option<Type>,
+    // Warnings found while reading the program
+    warnings: Vec<Warning>,
+    // Errors are recorded instead of stopping the parsing, see `parse_lenient`
+    recover: bool,
+    // Errors recorded in the recovery mode
+    errors: Vec<ParserError<'a>>,
+    // Events emitted with a constant name, with the type of their data
+    events: Vec<(String, Type)>,
+    // Tokens of the conditions of the `#[invariant(...)]` attributes read before the next function
+    invariants: Vec<Vec<TokenResult<'a>>>
+}
+
+// Program read by `Parser::parse_lenient`
+// If any error was found, the program is incomplete and must not be compiled
+pub struct PartialProgram<'a> {
+    pub program: Program,
+    pub mapper: GlobalMapper<'a>,
+    pub errors: Vec<ParserError<'a>>,
+    pub warnings: Vec<Warning>,
+}
+
+// Statements read by `Parser::parse_session`
+// The statements are the body of the function `chunk_id`, its parameters are the
+// session variables and it returns a tuple of the `variables` followed by the value
+// of the last statement if `value_type` is set. Nothing is returned if both are empty
+pub struct SessionChunk<'a> {
+    pub program: Program,
+    pub mapper: GlobalMapper<'a>,
+    pub warnings: Vec<Warning>,
+    pub chunk_id: u16,
+    pub variables: Vec<(&'a str, Type)>,
+    pub value_type: Option<Type>,
 }
 
 impl<'a> Parser<'a> {
@@ -157,6 +194,16 @@ impl<'a> Parser<'a> {
             line: 0,
             column_start: 0,
             column_end: 0,
+            statements_spans: Vec::new(),
+            interfaces: HashMap::new(),
+            self_type: None,
+            type_constants: HashMap::new(),
+            return_type: None,
+            warnings: Vec::new(),
+            recover: false,
+            errors: Vec::new(),
+            events: Vec::new(),
+            invariants: Vec::new(),
         }
     }
 
@@ -219,6 +266,20 @@ impl<'a> Parser<'a> {
         !self.peek_is(token)
     }
 
+    // Check if the next token ends the current block of statements
+    // A case of a switch is ended by the next one
+    #[inline(always)]
+    fn peek_is_end_of_block(&self) -> bool {
+        self.tokens.front().is_some_and(|t| matches!(t.token, Token::BraceClose | Token::Case | Token::Default))
+    }
+
+    // Check if the next tokens are the `::` path separator
+    // A single `:` follows the expression of a ternary
+    #[inline(always)]
+    fn peek_is_path_separator(&self) -> bool {
+        self.tokens.iter().take(2).filter(|t| t.token == Token::Colon).count() == 2
+    }
+
     // Check if the next token is an identifier
     #[inline(always)]
     fn peek_is_identifier(&self) -> bool {
@@ -232,7 +293,7 @@ impl<'a> Parser<'a> {
     fn expect_token(&mut self, expected: Token<'a>) -> Result<(), ParserError<'a>> {
         let token = self.advance()?;
         if token != expected {
-            return Err(err!(self, ParserErrorKind::InvalidToken(token, expected)))
+            return Err(err!(self, ParserErrorKind::InvalidToken(Box::new((token, expected)))))
         }
         Ok(())
     }
@@ -280,13 +341,30 @@ impl<'a> Parser<'a> {
 
                 Type::Map(Box::new(key), Box::new(value))
             }
+            Token::ParenthesisOpen => Type::Tuple(self.read_tuple_types()?),
+            Token::Identifier("Result") => {
+                let value = self.get_generic_type()?;
+                self.expect_token(Token::Comma)?;
+                let token = self.advance()?;
+                let error = self.get_type_from_token(token)?;
+                self.expect_token(Token::OperatorGreaterThan)?;
+
+                Type::Result(Box::new(value), Box::new(error))
+            },
+            Token::Identifier("Iterator") => Type::Iterator(Box::new(self.get_single_inner_type()?)),
+            Token::Identifier("Self") if self.self_type.is_some() => self.self_type.clone().unwrap(),
             Token::Identifier(id) => {
                 if let Ok(builder) = self.global_mapper.structs().get_by_name(id) {
                     Type::Struct(builder.get_type().clone())
                 } else if let Ok(builder) = self.global_mapper.enums().get_by_name(id) {
                     Type::Enum(builder.get_type().clone())
+                } else if let Some(opaque) = self.environment.get_opaque_by_name(id) {
+                    Type::Opaque(opaque)
                 } else {
-                    return Err(err!(self, ParserErrorKind::TypeNameNotFound(id)))
+                    let names = self.global_mapper.structs().names()
+                        .into_iter()
+                        .chain(self.global_mapper.enums().names());
+                    return Err(err!(self, ParserErrorKind::TypeNameNotFound(id)).with_suggestion(find_similar(id, names)))
                 }
             },
             token => return Err(err!(self, ParserErrorKind::UnexpectedToken(token)))
@@ -325,6 +403,30 @@ impl<'a> Parser<'a> {
         Ok(_type)
     }
 
+    /**
+     * Read the types of a tuple, the opening parenthesis is already consumed
+     * Example: (u64, string)
+     * A tuple must contains at least two types
+     */
+    fn read_tuple_types(&mut self) -> Result<Vec<Type>, ParserError<'a>> {
+        let mut types = Vec::new();
+        loop {
+            types.push(self.read_type()?);
+            if self.peek_is(Token::ParenthesisClose) {
+                break;
+            }
+
+            self.expect_token(Token::Comma)?;
+        }
+        self.expect_token(Token::ParenthesisClose)?;
+
+        if types.len() < 2 || types.len() > u8::MAX as usize {
+            return Err(err!(self, ParserErrorKind::InvalidTupleSize(types.len())))
+        }
+
+        Ok(types)
+    }
+
     // get the type of an expression
     fn get_type_from_expression<'b>(&'b self, on_type: Option<&Type>, expression: &'b Expression, context: &'b Context<'a>) -> Result<Cow<'b, Type>, ParserError<'a>> {
         match self.get_type_from_expression_internal(on_type, expression, context)? {
@@ -347,6 +449,11 @@ impl<'a> Parser<'a> {
                 }
             },
             Type::Optional(inner) => Type::Optional(Box::new(self.get_from_generic_type(on_type, inner, path, context)?)),
+            Type::Array(inner) => Type::Array(Box::new(self.get_from_generic_type(on_type, inner, path, context)?)),
+            Type::Iterator(inner) => Type::Iterator(Box::new(self.get_from_generic_type(on_type, inner, path, context)?)),
+            Type::Tuple(types) => Type::Tuple(types.iter()
+                .map(|t| self.get_from_generic_type(on_type, t, path, context))
+                .collect::<Result<_, _>>()?),
             _ => _type.clone()
         })
     }
@@ -360,25 +467,58 @@ impl<'a> Parser<'a> {
                 Some(v) => Cow::Owned(Type::Array(Box::new(self.get_type_from_expression(on_type, v, context)?.into_owned()))),
                 None => return Err(err!(self, ParserErrorKind::EmptyArrayConstructor)) // cannot determine type from empty array
             },
+            Expression::TupleConstructor(values) => {
+                let mut types = Vec::with_capacity(values.len());
+                for value in values {
+                    types.push(self.get_type_from_expression(on_type, value, context)?.into_owned());
+                }
+                Cow::Owned(Type::Tuple(types))
+            },
             Expression::MapConstructor(_, key_type, value_type) => Cow::Owned(Type::Map(Box::new(key_type.clone()), Box::new(value_type.clone()))),
             Expression::EnumConstructor(_, _type) => Cow::Owned(Type::Enum(_type.enum_type().clone())),
+            Expression::ResultConstructor(_, _, _type) => Cow::Borrowed(_type),
+            Expression::Try(expr, _) => match self.get_type_from_expression(on_type, expr, context)?.into_owned() {
+                Type::Result(value, _) => Cow::Owned(*value),
+                _type => return Err(err!(self, ParserErrorKind::ExpectedResult(_type)))
+            },
             Expression::Variable(ref var_name) => match on_type {
                 Some(t) => {
-                    if let Type::Struct(_type) = t {
-                        let index = *var_name as usize;
-                        if let Some(field_type) = _type.fields().get(index) {
-                            Cow::Owned(field_type.clone())
-                        } else {
-                            return Err(err!(self, ParserErrorKind::UnexpectedMappedVariableId(var_name.clone())))
-                        }
-                    } else {
-                        return Err(err!(self, ParserErrorKind::UnexpectedMappedVariableId(var_name.clone())))
+                    let index = *var_name as usize;
+                    let field_type = match t {
+                        Type::Struct(_type) => _type.fields().get(index),
+                        Type::Tuple(types) => types.get(index),
+                        _ => None
+                    };
+
+                    match field_type {
+                        Some(field_type) => Cow::Owned(field_type.clone()),
+                        None => return Err(err!(self, ParserErrorKind::UnexpectedMappedVariableId(*var_name)))
                     }
                 },
                 None => Cow::Borrowed(context.get_type_of_variable(var_name).ok_or_else(|| err!(self, ParserErrorKind::UnexpectedMappedVariableId(*var_name)))?),
             },
-            Expression::FunctionCall(path, name, _) => {
+            Expression::FunctionReference(_) => Cow::Owned(Type::U16),
+            Expression::FunctionCall(path, name, parameters) => {
                 let f = self.get_function(*name)?;
+                // An iterator adapter of any value produces the values returned by its function
+                if let (Some(Type::Iterator(inner)), [Expression::FunctionReference(id)]) = (f.return_type(), parameters.as_slice()) {
+                    if **inner == Type::Any {
+                        let return_type = self.get_function(*id)?
+                            .return_type()
+                            .clone()
+                            .ok_or_else(|| err!(self, ParserErrorKind::FunctionNoReturnType))?;
+                        return Ok(Some(Cow::Owned(Type::Iterator(Box::new(return_type)))))
+                    }
+                }
+
+                if f.returns_instance() {
+                    return match (on_type, path) {
+                        (Some(t), _) => Ok(Some(Cow::Owned(t.clone()))),
+                        (None, Some(v)) => self.get_type_from_expression(None, v, context).map(Some),
+                        (None, None) => Err(err!(self, ParserErrorKind::NoValueType))
+                    };
+                }
+
                 let return_type = f.return_type();
                 match return_type {
                     Some(ref v) => Cow::Owned(self.get_from_generic_type(on_type, v, path.as_deref(), context)?),
@@ -435,7 +575,13 @@ impl<'a> Parser<'a> {
                 | Operator::BitwiseAnd
                 | Operator::BitwiseOr
                 | Operator::BitwiseShl
-                | Operator::BitwiseShr => {
+                | Operator::BitwiseShr
+                | Operator::SaturatingAdd
+                | Operator::SaturatingSub
+                | Operator::SaturatingMul
+                | Operator::WrappingAdd
+                | Operator::WrappingSub
+                | Operator::WrappingMul => {
                     let left_type = self.get_type_from_expression(on_type, left, context)?;
                     let right_type = self.get_type_from_expression(on_type, right, context)?;
 
@@ -444,6 +590,18 @@ impl<'a> Parser<'a> {
                     }
                     left_type
                 },
+                // Checked operators return null on overflow
+                Operator::CheckedAdd
+                | Operator::CheckedSub
+                | Operator::CheckedMul => {
+                    let left_type = self.get_type_from_expression(on_type, left, context)?;
+                    let right_type = self.get_type_from_expression(on_type, right, context)?;
+
+                    if !left_type.is_number() || !right_type.is_number() || left_type != right_type {
+                        return Err(err!(self, ParserErrorKind::InvalidOperationNotSameType(left_type.into_owned(), right_type.into_owned())))
+                    }
+                    Cow::Owned(Type::Optional(Box::new(left_type.into_owned())))
+                },
                 Operator::Pow => {
                     let left_type = self.get_type_from_expression(on_type, left, context)?;
                     let right_type = self.get_type_from_expression(on_type, right, context)?;
@@ -459,10 +617,11 @@ impl<'a> Parser<'a> {
                     left_type
                 }
             },
-            Expression::IsNot(_) => Cow::Owned(Type::Bool),
+            // Bitwise not on a number keeps its type
+            Expression::IsNot(expr) => self.get_type_from_expression(on_type, expr, context)?,
             Expression::Ternary(_, expr, _) => self.get_type_from_expression(on_type, expr, context)?,
             Expression::Cast(_, _type) => Cow::Borrowed(_type),
-            Expression::RangeConstructor(start, _) => Cow::Owned(Type::Range(Box::new(self.get_type_from_expression(on_type, start, context)?.into_owned()))),
+            Expression::RangeConstructor(start, _, _) => Cow::Owned(Type::Range(Box::new(self.get_type_from_expression(on_type, start, context)?.into_owned()))),
         };
 
         Ok(Some(_type))
@@ -472,15 +631,25 @@ impl<'a> Parser<'a> {
     // function_name(param1, param2, ...)
     fn read_function_call(&mut self, path: Option<Expression>, on_type: Option<&Type>, name: &str, context: &mut Context<'a>) -> Result<Expression, ParserError<'a>> {
         trace!("Read function call: {}", name);
+        let span = self.current_span();
 
         // we remove the token from the list
         self.expect_token(Token::ParenthesisOpen)?;
         let mut parameters: Vec<Expression> = Vec::new();
         let mut types: Vec<Type> = Vec::new();
 
+        // The adapters of an iterator take a declared function by its name
+        let adapter = match on_type {
+            Some(Type::Iterator(inner)) if matches!(name, "map" | "filter") => Some((inner.as_ref().clone(), name == "filter")),
+            _ => None
+        };
+
         // read parameters for function call
         while self.peek_is_not(Token::ParenthesisClose) {
-            let expr = self.read_expression(context)?;
+            let expr = match &adapter {
+                Some((inner, filter)) if parameters.is_empty() => self.read_function_reference(inner, *filter)?,
+                _ => self.read_expression(context)?
+            };
             // We are forced to clone the type because we can't borrow it from the expression
             // I prefer to do this than doing an iteration below
             let t = self.get_type_from_expression(None, &expr, context)?.into_owned();
@@ -492,10 +661,18 @@ impl<'a> Parser<'a> {
             }
         }
 
+        // Variadic functions may receive more parameters than a declared function
+        if parameters.len() > u8::MAX as usize {
+            return Err(err!(self, ParserErrorKind::TooManyParameters))
+        }
+
+        // Type of the data of an emitted event
+        let event_data = if name == "emit" { types.get(1).cloned() } else { None };
+
         let id = self.global_mapper
             .functions()
             .get_compatible(Signature::new(name.to_owned(), on_type.cloned(), types), &mut parameters)
-            .map_err(|e| err!(self, e.into()))?;
+            .map_err(|e| err!(self, e.into()).with_span(span).with_suggestion(self.similar_function(name, on_type)))?;
 
         // Entry are only callable by external
         let f = self.get_function(id)?;
@@ -503,10 +680,49 @@ impl<'a> Parser<'a> {
             return Err(err!(self, ParserErrorKind::FunctionIsEntry))
         }
 
+        // Tests are only invoked by a test runner
+        if f.is_test() {
+            return Err(err!(self, ParserErrorKind::FunctionIsTest))
+        }
+
+        // Events are described in the ABI of the module when their name is known
+        if on_type.is_none() && matches!(f, Function::Native(_)) {
+            if let (Some(Expression::Constant(event)), Some(data)) = (parameters.first(), event_data) {
+                if let Ok(event) = event.as_string() {
+                    self.events.push((event.clone(), data));
+                }
+            }
+        }
+
         self.expect_token(Token::ParenthesisClose)?;
         Ok(Expression::FunctionCall(path.map(Box::new), id, parameters))
     }
 
+    // Read a function given to an iterator adapter by its name
+    // It must take a value of the iterator and return a value, a bool for a filter
+    fn read_function_reference(&mut self, inner: &Type, filter: bool) -> Result<Expression, ParserError<'a>> {
+        let name = self.next_identifier()?;
+        let id = self.global_mapper
+            .functions()
+            .get(&Signature::new(name.to_owned(), None, vec![inner.clone()]))
+            .map_err(|_| err!(self, ParserErrorKind::InvalidFunctionReference(name)))?;
+
+        let valid = match self.get_function(id)? {
+            Function::Program(f) if !f.is_entry() && !f.is_test() => match f.return_type() {
+                Some(Type::Bool) => true,
+                Some(_) => !filter,
+                None => false
+            },
+            _ => false
+        };
+
+        if !valid {
+            return Err(err!(self, ParserErrorKind::InvalidFunctionReference(name)))
+        }
+
+        Ok(Expression::FunctionReference(id))
+    }
+
     // Read fields of a constructor with the following syntax:
     // { field1, field2, ... }
     // or with values
@@ -517,8 +733,13 @@ impl<'a> Parser<'a> {
         let mut fields = Vec::new();
         while self.peek_is_not(Token::BraceClose) {
             let field_name = self.next_identifier()?;
+            let span = self.current_span();
             let expr = match self.advance()? {
-                Token::Comma | Token::BraceClose => Expression::Variable(context.get_variable_id(field_name).ok_or_else(|| err!(self, ParserErrorKind::UnexpectedVariable(field_name)))?),
+                Token::Comma | Token::BraceClose => Expression::Variable(context.use_variable(field_name).ok_or_else(|| {
+                    err!(self, ParserErrorKind::UnexpectedVariable(field_name))
+                        .with_span(span)
+                        .with_suggestion(self.similar_variable(field_name, context))
+                })?),
                 Token::Colon => self.read_expression(context)?,
                 token => return Err(err!(self, ParserErrorKind::UnexpectedToken(token)))
             };
@@ -547,6 +768,25 @@ impl<'a> Parser<'a> {
         Ok(())
     }
 
+    // Read a tuple constructor with the following syntax:
+    // (value1, value2)
+    // The first value is already read, expected types are used for the number literals
+    fn read_tuple_constructor(&mut self, first: Expression, types: Option<&Vec<Type>>, context: &mut Context<'a>) -> Result<Expression, ParserError<'a>> {
+        let mut values = vec![first];
+        while self.peek_is(Token::Comma) {
+            self.expect_token(Token::Comma)?;
+            let expected_type = types.and_then(|t| t.get(values.len()));
+            values.push(self.read_expr(None, true, true, expected_type, context)?);
+        }
+        self.expect_token(Token::ParenthesisClose)?;
+
+        if values.len() > u8::MAX as usize {
+            return Err(err!(self, ParserErrorKind::InvalidTupleSize(values.len())))
+        }
+
+        Ok(Expression::TupleConstructor(values))
+    }
+
     // Read a struct constructor with the following syntax:
     // struct_name { field_name: value1, field2: value2 }
     // If we have a field that has the same name as a variable we can pass it as following:
@@ -582,6 +822,7 @@ impl<'a> Parser<'a> {
     // Or if no fields: enum_name::variant_name
     fn read_enum_variant_constructor(&mut self, enum_type: EnumType, variant_name: &'a str, context: &mut Context<'a>) -> Result<Expression, ParserError<'a>> {
         trace!("Read enum variant constructor: {:?}::{}", enum_type, variant_name);
+        let span = self.current_span();
         // If its an enum variant with fields
         let fields = if self.peek_is(Token::BraceOpen) {
             self.expect_token(Token::BraceOpen)?;
@@ -594,7 +835,11 @@ impl<'a> Parser<'a> {
             .get_by_ref(&enum_type)
             .map_err(|e| err!(self, e.into()))?;
         let (variant_id, variant_fields) = builder.get_variant_by_name(&variant_name)
-            .ok_or_else(|| err!(self, ParserErrorKind::EnumVariantNotFound(variant_name)))?;
+            .ok_or_else(|| {
+                err!(self, ParserErrorKind::EnumVariantNotFound(variant_name))
+                    .with_span(span)
+                    .with_suggestion(find_similar(variant_name, builder.names().iter().copied()))
+            })?;
 
         if variant_fields.len() != fields.len() {
             return Err(err!(self, ParserErrorKind::InvalidFieldCount))
@@ -614,7 +859,55 @@ impl<'a> Parser<'a> {
         Ok(Expression::EnumConstructor(fields_expressions, EnumValueType::new(enum_type, variant_id)))
     }
 
+    // Read a result constructor with the following syntax:
+    // Ok(value) or Err(error)
+    // The expected result type gives the type of the other variant
+    fn read_result_constructor(&mut self, variant_name: &'a str, result_type: &Type, context: &mut Context<'a>) -> Result<Expression, ParserError<'a>> {
+        trace!("Read result constructor: {}", variant_name);
+        let Type::Result(value_type, error_type) = result_type else {
+            return Err(err!(self, ParserErrorKind::ExpectedResult(result_type.clone())))
+        };
+
+        let builder = self.global_mapper.enums()
+            .get_by_name("Result")
+            .map_err(|e| err!(self, e.into()))?;
+        let (variant_id, _) = builder.get_variant_by_name(variant_name)
+            .ok_or_else(|| err!(self, ParserErrorKind::EnumVariantNotFound(variant_name)))?;
+        let enum_type = builder.get_type().clone();
+
+        let expected_type = if variant_name == "Ok" { value_type } else { error_type };
+        self.expect_token(Token::ParenthesisOpen)?;
+        let mut value = self.read_expr(None, true, true, Some(expected_type), context)?;
+        self.expect_token(Token::ParenthesisClose)?;
+        self.verify_type_of(&mut value, expected_type, context)?;
+
+        Ok(Expression::ResultConstructor(Box::new(value), EnumValueType::new(enum_type, variant_id), result_type.clone()))
+    }
+
+    // Read the `?` operator on a result
+    // The error is returned to the caller, so the function must return a result with the same error type
+    fn read_try(&self, expr: Expression, on_type: Option<&Type>, context: &Context<'a>) -> Result<Expression, ParserError<'a>> {
+        let _type = self.get_type_from_expression(on_type, &expr, context)?.into_owned();
+        let Type::Result(_, error) = &_type else {
+            return Err(err!(self, ParserErrorKind::ExpectedResult(_type)))
+        };
+
+        match &self.return_type {
+            Some(Type::Result(_, expected)) if error.is_compatible_with(expected) => {},
+            _ => return Err(err!(self, ParserErrorKind::InvalidTryReturnType(_type)))
+        };
+
+        let mut parameters = Vec::new();
+        let id = self.global_mapper
+            .functions()
+            .get_compatible(Signature::new("is_err".to_owned(), Some(_type.clone()), Vec::new()), &mut parameters)
+            .map_err(|e| err!(self, e.into()))?;
+
+        Ok(Expression::Try(Box::new(expr), id))
+    }
+
     // Read a constant from the environment
+    // or a static function call like `Type::name()`
     fn read_type_constant(&mut self, token: Token<'a>, context: &mut Context<'a>) -> Result<Expression, ParserError<'a>> {
         let _type = self.get_type_from_token(token)?;
         self.expect_token(Token::Colon)?;
@@ -622,7 +915,22 @@ impl<'a> Parser<'a> {
 
         let constant_name = self.next_identifier()?;
 
-        if let Type::Enum(enum_type) = _type {
+        let associated_constant = self.type_constants.get(&_type)
+            .and_then(|constants| constants.get(constant_name));
+
+        if self.peek_is(Token::ParenthesisOpen) {
+            trace!("Read static function call: {:?}::{}", _type, constant_name);
+            let expr = self.read_function_call(None, Some(&_type), constant_name, context)?;
+            if let Expression::FunctionCall(_, id, _) = &expr {
+                if self.get_function(*id)?.has_instance() {
+                    return Err(err!(self, ParserErrorKind::FunctionRequiresInstance))
+                }
+            }
+            Ok(expr)
+        } else if let Some(constant) = associated_constant {
+            trace!("Read associated constant: {:?}::{}", _type, constant_name);
+            Ok(Expression::Constant(constant.value.clone()))
+        } else if let Type::Enum(enum_type) = _type {
             self.read_enum_variant_constructor(enum_type, constant_name, context)
         } else {
             trace!("Read type constant: {:?}::{}", _type, constant_name);
@@ -632,48 +940,25 @@ impl<'a> Parser<'a> {
         }
     }
 
-    // Execute the selected operator
-    fn execute_operator(&self, op: &Operator, left: &Value, right: &Value) -> Option<Value> {
-        Some(match op {
-            Operator::Add => {
-                if left.is_string() || right.is_string() {
-                    Value::String(format!("{}{}", left, right))
-                } else {
-                    op!(left, right, +)
-                }
-            },
-            Operator::Sub => op!(left, right, -),
-            Operator::Div => op_div!(left, right),
-            Operator::Mul => op!(left, right, *),
-            Operator::Mod => op!(left, right, %),
-            Operator::Pow => {
-                let pow_n = right.as_u32().ok()?;
-                match left {
-                    Value::U8(v) => Value::U8(v.pow(pow_n)),
-                    Value::U16(v) => Value::U16(v.pow(pow_n)),
-                    Value::U32(v) => Value::U32(v.pow(pow_n)),
-                    Value::U64(v) => Value::U64(v.pow(pow_n)),
-                    Value::U128(v) => Value::U128(v.pow(pow_n)),
-                    _ => return None
-                }
-            },
+    // Read a function call in a namespace with the following syntax:
+    // namespace::function_name(param1, param2, ...)
+    // Namespaces can be nested: namespace::inner::function_name(...)
+    fn read_namespace_call(&mut self, namespace: &str, context: &mut Context<'a>) -> Result<Expression, ParserError<'a>> {
+        let mut path = namespace.to_owned();
+        loop {
+            self.expect_token(Token::Colon)?;
+            self.expect_token(Token::Colon)?;
+            let name = self.next_identifier()?;
+            path.push_str("::");
+            path.push_str(name);
 
-            Operator::BitwiseXor => op!(left, right, ^),
-            Operator::BitwiseAnd => op_num_with_bool!(left, right, &),
-            Operator::BitwiseOr => op_num_with_bool!(left, right, |),
-            Operator::BitwiseShl => op!(left, right, <<),
-            Operator::BitwiseShr => op!(left, right, >>),
-
-            Operator::Eq => Value::Boolean(left == right),
-            Operator::Neq => Value::Boolean(left != right),
-            Operator::Gte => op_bool!(left, right, >=),
-            Operator::Gt => op_bool!(left, right, >),
-            Operator::Lte => op_bool!(left, right, <=),
-            Operator::Lt => op_bool!(left, right, <),
-            Operator::And => Value::Boolean(left.as_bool().ok()? && right.as_bool().ok()?),
-            Operator::Or => Value::Boolean(left.as_bool().ok()? || right.as_bool().ok()?),
-            Operator::Assign(_) => return None,
-        })
+            if !self.environment.has_namespace(&path) || !self.peek_is(Token::Colon) {
+                break;
+            }
+        }
+
+        trace!("Read namespace function call: {}", path);
+        self.read_function_call(None, None, &path, context)
     }
 
     // Try to convert an expression to a value
@@ -698,7 +983,16 @@ impl<'a> Parser<'a> {
                 }
                 Constant::Array(new_values)
             },
-            Expression::RangeConstructor(min, max) => {
+            // A tuple has no constant representation, only its values are converted
+            Expression::TupleConstructor(values) => {
+                for value in values {
+                    if let Some(v) = self.try_convert_expr_to_value(value) {
+                        *value = Expression::Constant(v);
+                    }
+                }
+                return None
+            },
+            Expression::RangeConstructor(min, max, inclusive) => {
                 let min_value = self.try_convert_expr_to_value(min);
                 let max_value = self.try_convert_expr_to_value(max);
 
@@ -716,7 +1010,7 @@ impl<'a> Parser<'a> {
                 let max = max_value?.into_value().ok()?;
 
                 let value_type = min.get_type().ok()?;
-                Constant::Default(Value::Range(Box::new(min), Box::new(max), value_type))
+                Constant::Default(Value::Range(Box::new(min), Box::new(max), value_type, *inclusive))
             },
             Expression::StructConstructor(fields, struct_type) => {
                 let mut new_fields = Vec::with_capacity(fields.len());
@@ -737,7 +1031,7 @@ impl<'a> Parser<'a> {
                 Constant::Enum(new_fields, enum_type.clone())
             },
             Expression::MapConstructor(entries, _, _) => {
-                let mut new_entries = HashMap::with_capacity(entries.len());
+                let mut new_entries = IndexMap::with_capacity(entries.len());
                 for (key, value) in entries {
                     let k = self.try_convert_expr_to_value(key);
                     let v = self.try_convert_expr_to_value(value);
@@ -777,7 +1071,7 @@ impl<'a> Parser<'a> {
             },
             Expression::IsNot(expr) => {
                 let v = self.try_convert_expr_to_value(expr)?;
-                Constant::Default(Value::Boolean(!v.to_bool().ok()?))
+                Constant::Default(v.as_value().ok()?.not().ok()?)
             },
             Expression::Operator(op, left, right) => {
                 let l = self.try_convert_expr_to_value(left);
@@ -791,7 +1085,7 @@ impl<'a> Parser<'a> {
                     *right.as_mut() = Expression::Constant(r.clone());
                 }
 
-                Constant::Default(self.execute_operator(op, l?.as_value().ok()?, r?.as_value().ok()?)?)
+                Constant::Default(execute_operator(op, l?.as_value().ok()?, r?.as_value().ok()?)?)
             },
             Expression::SubExpression(expr) => self.try_convert_expr_to_value(expr)?,
             Expression::Ternary(condition, left, right) => {
@@ -913,15 +1207,32 @@ impl<'a> Parser<'a> {
                     }
                 },
                 Token::ParenthesisOpen => {
-                    let expr = self.read_expr(None, true, true, expected_type, context)?;
-                    self.expect_token(Token::ParenthesisClose)?;
-                    Expression::SubExpression(Box::new(expr))
+                    let types = match expected_type {
+                        Some(Type::Tuple(types)) => Some(types),
+                        _ => None
+                    };
+
+                    let expr = self.read_expr(None, true, true, types.map_or(expected_type, |t| t.first()), context)?;
+                    if self.peek_is(Token::Comma) {
+                        self.read_tuple_constructor(expr, types, context)?
+                    } else {
+                        self.expect_token(Token::ParenthesisClose)?;
+                        Expression::SubExpression(Box::new(expr))
+                    }
+                },
+                Token::Identifier(variant @ ("Ok" | "Err")) if on_type.is_none() && matches!(expected_type, Some(Type::Result(_, _))) => {
+                    self.read_result_constructor(variant, expected_type.unwrap(), context)?
+                },
+                // `map` is also the keyword of the map type
+                Token::Map if on_type.is_some() && self.peek_is(Token::ParenthesisOpen) => {
+                    self.read_function_call(last_expression.take(), on_type, "map", context)?
                 },
                 Token::Identifier(id) => {
                     match self.peek()? {
                         // function call
                         Token::ParenthesisOpen => self.read_function_call(last_expression.take(), on_type, id, context)?,
-                        Token::Colon => self.read_type_constant(Token::Identifier(id), context)?,
+                        Token::Colon if self.peek_is_path_separator() && self.environment.has_namespace(id) => self.read_namespace_call(id, context)?,
+                        Token::Colon if self.peek_is_path_separator() => self.read_type_constant(Token::Identifier(id), context)?,
                         _ => {
                             match on_type {
                                 // mostly an access to a struct field
@@ -931,27 +1242,35 @@ impl<'a> Parser<'a> {
                                             .map_err(|e| err!(self, e.into()))?;
                                         match builder.get_id_for_field(id) {
                                             Some(v) => Expression::Variable(v),
-                                            None => return Err(err!(self, ParserErrorKind::UnexpectedVariable(id)))
+                                            None => return Err(err!(self, ParserErrorKind::UnexpectedVariable(id)).with_suggestion(find_similar(id, builder.names().iter().copied())))
                                         }
                                     } else {
                                         return Err(err!(self, ParserErrorKind::UnexpectedType(t.clone())))
                                     }
                                 },
                                 None => {
-                                    if let Some(id) = context.get_variable_id(id) {
+                                    if let Some(id) = context.use_variable(id) {
                                         Expression::Variable(id)
                                     } else if let Some(constant) = self.constants.get(id) {
                                         Expression::Constant(constant.value.clone())
                                     } else if let Ok(builder) = self.global_mapper.structs().get_by_name(&id) {
                                         self.read_struct_constructor(builder.get_type().clone(), context)?
                                     } else {
-                                        return Err(err!(self, ParserErrorKind::UnexpectedVariable(id)))
+                                        return Err(err!(self, ParserErrorKind::UnexpectedVariable(id)).with_suggestion(self.similar_variable(id, context)))
                                     }
                                 }
                             }
                         }
                     }
                 },
+                // access to a tuple value: tuple.0
+                Token::Value(Literal::Number(index)) if matches!(on_type, Some(Type::Tuple(_))) => {
+                    let _type = on_type.unwrap();
+                    match _type {
+                        Type::Tuple(types) if (index as usize) < types.len() => Expression::Variable(index as IdentifierType),
+                        _ => return Err(err!(self, ParserErrorKind::InvalidTupleIndex(index as usize, _type.clone())))
+                    }
+                },
                 Token::Value(value) => {
                     Expression::Constant(
                         Constant::Default(match value {
@@ -988,6 +1307,12 @@ impl<'a> Parser<'a> {
                             // Read a type constant
                             if self.peek_is(Token::Dot) {
                                 self.expect_token(Token::Dot)?;
+                                // `..=` includes the end
+                                let inclusive = self.peek_is(Token::OperatorAssign);
+                                if inclusive {
+                                    self.expect_token(Token::OperatorAssign)?;
+                                }
+
                                 let end_expr = self.read_expr(Some(&_type), false, false, expected_type, context)?;
                                 let end_type = self.get_type_from_expression(on_type, &end_expr, context)?;
                                 if _type != *end_type {
@@ -998,7 +1323,7 @@ impl<'a> Parser<'a> {
                                     return Err(err!(self, ParserErrorKind::InvalidRangeTypePrimitive(_type)))
                                 }
 
-                                Expression::RangeConstructor(Box::new(value), Box::new(end_expr))
+                                Expression::RangeConstructor(Box::new(value), Box::new(end_expr), inclusive)
                             } else {
                                 // Read a variable access OR a function call
                                 let right_expr = self.read_expr(Some(&_type), false, false, expected_type, context)?;
@@ -1006,6 +1331,10 @@ impl<'a> Parser<'a> {
                                     if path.is_some() {
                                         return Err(err!(self, ParserErrorKind::UnexpectedPathInFunctionCall))
                                     }
+
+                                    if !self.get_function(name)?.has_instance() {
+                                        return Err(err!(self, ParserErrorKind::FunctionIsStatic))
+                                    }
                                     Expression::FunctionCall(Some(Box::new(value)), name, params)
                                 } else {
                                     Expression::Path(Box::new(value), Box::new(right_expr))
@@ -1018,16 +1347,21 @@ impl<'a> Parser<'a> {
                 Token::IsNot => { // it's an operator, but not declared as
                     let expr = self.read_expression(context)?;
                     let expr_type = self.get_type_from_expression(on_type, &expr, context)?;
-                    if *expr_type != Type::Bool {
+                    if *expr_type != Type::Bool && !expr_type.is_number() {
                         return Err(err!(self, ParserErrorKind::InvalidValueType(expr_type.into_owned(), Type::Bool)))
                     }
 
                     Expression::IsNot(Box::new(expr))
                 },
+                // error propagation: expr?
+                Token::OperatorTernary if last_expression.as_ref().is_some_and(|expr| self.get_type_from_expression(on_type, expr, context).is_ok_and(|t| t.is_result())) => {
+                    required_operator = !required_operator;
+                    self.read_try(last_expression.take().unwrap(), on_type, context)?
+                },
                 Token::OperatorTernary => match last_expression { // condition ? expr : expr
                     Some(expr) => {
                         if *self.get_type_from_expression(on_type, &expr, context)? != Type::Bool {
-                            return Err(err!(self, ParserErrorKind::InvalidCondition(Type::Bool, expr)))
+                            return Err(err!(self, ParserErrorKind::InvalidCondition(Type::Bool, Box::new(expr))))
                         }
 
                         let valid_expr = self.read_expr(on_type, true, true, expected_type, context)?;
@@ -1140,6 +1474,16 @@ impl<'a> Parser<'a> {
             | Operator::BitwiseShl
             | Operator::BitwiseShr
 
+            | Operator::CheckedAdd
+            | Operator::CheckedSub
+            | Operator::CheckedMul
+            | Operator::SaturatingAdd
+            | Operator::SaturatingSub
+            | Operator::SaturatingMul
+            | Operator::WrappingAdd
+            | Operator::WrappingSub
+            | Operator::WrappingMul
+
             | Operator::Gt
             | Operator::Lt
             | Operator::Lte
@@ -1269,6 +1613,27 @@ impl<'a> Parser<'a> {
         Ok(statements)
     }
 
+    // Register a local variable declared in the source code
+    // A warning is produced if it shadows a variable or a constant
+    fn declare_variable(&mut self, name: &'a str, value_type: Type, context: &mut Context<'a>) -> IdentifierType {
+        let span = self.declaration_span();
+        if name != "_" {
+            let shadowed = if context.has_variable(name) {
+                Some("variable")
+            } else if self.constants.contains_key(name) {
+                Some("constant")
+            } else {
+                None
+            };
+
+            if let Some(kind) = shadowed {
+                self.warn(span, WarningCode::ShadowedIdentifier, format!("variable '{}' shadows a {} with the same name", name, kind));
+            }
+        }
+
+        context.declare_variable(name, value_type, span)
+    }
+
     // Read a variable declaration
     fn read_variable_internal(&mut self, context: &mut Context<'a>, is_const: bool) -> Result<(&'a str, Type, Expression), ParserError<'a>> {
         let name: &'a str = self.next_identifier()?;
@@ -1346,12 +1711,7 @@ impl<'a> Parser<'a> {
      */
     fn read_variable(&mut self, context: &mut Context<'a>) -> Result<DeclarationStatement, ParserError<'a>> {
         let (name, value_type, value) = self.read_variable_internal(context, false)?;
-        let id = if name != "_" {
-            context.register_variable_unchecked(name, value_type.clone())
-        } else {
-            context.register_variable(name, value_type.clone())
-                .ok_or_else(|| err!(self, ParserErrorKind::VariableNameAlreadyUsed(name)))?
-        };
+        let id = self.declare_variable(name, value_type.clone(), context);
 
         Ok(DeclarationStatement {
             id,
@@ -1361,111 +1721,708 @@ impl<'a> Parser<'a> {
     }
 
 
-    fn read_const(&mut self, context: &mut Context<'a>) -> Result<(), ParserError<'a>> {
-        let (name, value_type, mut value) = self.read_variable_internal(context, true)?;
+    /**
+     * Example: let (a, b) = pair;
+     * Rules:
+     * - The value must be a tuple with the same number of values
+     * - Each variable takes the type of its tuple value
+     * - '_' can be used to ignore a value
+     */
+    fn read_tuple_destructuring(&mut self, context: &mut Context<'a>) -> Result<Statement, ParserError<'a>> {
+        self.expect_token(Token::ParenthesisOpen)?;
+        let mut names = Vec::new();
+        loop {
+            let name = self.next_identifier()?;
+            if name != "_" {
+                if !name.starts_with(char::is_alphabetic) {
+                    return Err(err!(self, ParserErrorKind::VariableMustStartWithAlphabetic(name)))
+                }
 
-        let const_value = self.try_convert_expr_to_value(&mut value)
-                .ok_or(err!(self, ParserErrorKind::InvalidConstantValue))?;
+                if names.contains(&name) {
+                    return Err(err!(self, ParserErrorKind::VariableNameAlreadyUsed(name)))
+                }
+            }
+            names.push(name);
 
-        self.constants.insert(name, ConstantDeclaration {
-            value: const_value,
-            value_type
-        });
+            if self.peek_is(Token::ParenthesisClose) {
+                break;
+            }
+            self.expect_token(Token::Comma)?;
+        }
+        self.expect_token(Token::ParenthesisClose)?;
+        self.expect_token(Token::OperatorAssign)?;
 
-        Ok(())
-    }
+        let value = self.read_expr(None, true, true, None, context)?;
+        let types = match self.get_type_from_expression(None, &value, context)?.into_owned() {
+            Type::Tuple(types) => types,
+            _type => return Err(err!(self, ParserErrorKind::ExpectedTuple(_type)))
+        };
 
-    fn read_loop_body(&mut self, context: &mut Context<'a>, return_type: &Option<Type>) -> Result<Vec<Statement>, ParserError<'a>> {
-        // support nested loop
-        let old_value = context.is_in_a_loop();
-        context.set_in_a_loop(true);
-        self.expect_token(Token::BraceOpen)?;
-        let statements = self.read_body(context, return_type)?;
-        context.set_in_a_loop(old_value);
+        if types.len() != names.len() {
+            return Err(err!(self, ParserErrorKind::InvalidTupleSize(names.len())))
+        }
 
-        Ok(statements)
+        // Variables are registered after reading the value, it can't reference them
+        let ids = names.into_iter()
+            .zip(types)
+            .map(|(name, _type)| self.declare_variable(name, _type, context))
+            .collect();
+
+        Ok(Statement::TupleDestructuring(ids, value))
     }
 
-    // Read a single statement
-    fn read_statement(&mut self, context: &mut Context<'a>, return_type: &Option<Type>) -> Result<Option<Statement>, ParserError<'a>> {
-        if let Some(token) = self.next() {
-            let statement: Statement = match token {
-                Token::BraceClose => return Ok(None),
-                Token::For => { // Example: for i: u64 = 0; i < 10; i += 1 {}
-                    context.begin_scope();
-                    let var = self.read_variable(context)?;
-                    let condition = self.read_expression(context)?;
-                    let condition_type = self.get_type_from_expression(None, &condition, context)?;
-                    if  *condition_type != Type::Bool {
-                        return Err(err!(self, ParserErrorKind::InvalidCondition(condition_type.into_owned(), condition)))
+    // Read a pattern, its bindings are not declared yet
+    fn read_pattern(&mut self) -> Result<Pattern<'a>, ParserError<'a>> {
+        let pattern = match self.advance()? {
+            Token::Identifier(name) => return Ok(Pattern::Binding(name)),
+            Token::ParenthesisOpen => {
+                let (patterns, _) = self.read_patterns(Token::ParenthesisClose, false)?;
+                Pattern::Tuple(patterns)
+            },
+            Token::BracketOpen => {
+                let (patterns, rest) = self.read_patterns(Token::BracketClose, true)?;
+                Pattern::Array(patterns, rest)
+            },
+            Token::BraceOpen => {
+                let mut fields = Vec::new();
+                let mut rest = false;
+                while self.peek_is_not(Token::BraceClose) {
+                    if self.peek_is(Token::Dot) {
+                        self.read_rest_pattern()?;
+                        rest = true;
+                        break;
                     }
 
-                    let increment = self.read_expression(context)?;
-                    match &increment { // allow only assignations on this expr
-                        Expression::Operator(op, _, _) if op.is_assignation() => {},
-                        _ => return Err(err!(self, ParserErrorKind::InvalidForExpression(increment)))
+                    // `field` is a shorthand for `field: field`
+                    let field = self.next_identifier()?;
+                    let pattern = if self.peek_is(Token::Colon) {
+                        self.expect_token(Token::Colon)?;
+                        self.read_pattern()?
+                    } else {
+                        Pattern::Binding(field)
                     };
+                    fields.push((field, pattern));
 
-                    let statements = self.read_loop_body(context, return_type)?;
-                    context.end_scope();
-
-                    Statement::For(var, condition, increment, statements)
+                    if self.peek_is_not(Token::Comma) {
+                        break;
+                    }
+                    self.expect_token(Token::Comma)?;
                 }
-                Token::ForEach => { // Example: foreach a in array {}
-                    context.begin_scope();
-                    let variable = self.next_identifier()?;
-                    self.expect_token(Token::In)?;
-                    let expr = self.read_expression(context)?;
-                    let expr_type = self.get_type_from_expression(None, &expr, context)?;
+                self.expect_token(Token::BraceClose)?;
+                Pattern::Struct(fields, rest)
+            },
+            token => return Err(err!(self, ParserErrorKind::ExpectedIdentifierToken(token)))
+        };
+
+        Ok(pattern)
+    }
+
+    // Read the patterns until the closing token
+    // The rest pattern must be the last one
+    fn read_patterns(&mut self, close: Token<'a>, allow_rest: bool) -> Result<(Vec<Pattern<'a>>, bool), ParserError<'a>> {
+        let mut patterns = Vec::new();
+        let mut rest = false;
+        while self.peek_is_not(close.clone()) {
+            if allow_rest && self.peek_is(Token::Dot) {
+                self.read_rest_pattern()?;
+                rest = true;
+                break;
+            }
+
+            patterns.push(self.read_pattern()?);
+            if self.peek_is_not(Token::Comma) {
+                break;
+            }
+            self.expect_token(Token::Comma)?;
+        }
+        self.expect_token(close)?;
+
+        Ok((patterns, rest))
+    }
+
+    fn read_rest_pattern(&mut self) -> Result<(), ParserError<'a>> {
+        self.expect_token(Token::Dot)?;
+        self.expect_token(Token::Dot)
+    }
+
+    // Store the value in a hidden variable to read it only once
+    // A variable is used directly
+    fn store_pattern_value(&mut self, value: Expression, value_type: Type, statements: &mut Vec<Statement>, context: &mut Context<'a>) -> Expression {
+        if let Expression::Variable(_) = value {
+            return value;
+        }
+
+        let id = context.register_variable_unchecked(DESTRUCTURING_VARIABLE, value_type.clone());
+        statements.push(Statement::Variable(DeclarationStatement { id, value_type, value }));
+        Expression::Variable(id)
+    }
+
+    // Check the name of a binding, `_` can be used several times
+    fn verify_binding_name(&self, name: &'a str, names: &mut Vec<&'a str>) -> Result<(), ParserError<'a>> {
+        if name != "_" {
+            if !name.starts_with(char::is_alphabetic) {
+                return Err(err!(self, ParserErrorKind::VariableMustStartWithAlphabetic(name)))
+            }
+
+            if names.contains(&name) {
+                return Err(err!(self, ParserErrorKind::VariableNameAlreadyUsed(name)))
+            }
+            names.push(name);
+        }
+
+        Ok(())
+    }
+
+    // Lower a pattern into the declarations of its bindings
+    // The fields and values are read using paths and indexes on the value
+    // The variables are declared in the order of the statements, like their memory slots
+    fn lower_pattern(&mut self, pattern: Pattern<'a>, value: Expression, value_type: Type, names: &mut Vec<&'a str>, statements: &mut Vec<Statement>, context: &mut Context<'a>) -> Result<(), ParserError<'a>> {
+        match pattern {
+            Pattern::Binding("_") => {},
+            Pattern::Binding(name) => {
+                self.verify_binding_name(name, names)?;
+                let id = self.declare_variable(name, value_type.clone(), context);
+                statements.push(Statement::Variable(DeclarationStatement { id, value_type, value }));
+            },
+            Pattern::Tuple(patterns) => {
+                let Type::Tuple(types) = value_type else {
+                    return Err(err!(self, ParserErrorKind::ExpectedTuple(value_type)))
+                };
+
+                if types.len() != patterns.len() {
+                    return Err(err!(self, ParserErrorKind::InvalidTupleSize(patterns.len())))
+                }
+
+                let base = self.store_pattern_value(value, Type::Tuple(types.clone()), statements, context);
+                for (i, (pattern, _type)) in patterns.into_iter().zip(types).enumerate() {
+                    let value = Expression::Path(Box::new(base.clone()), Box::new(Expression::Variable(i as IdentifierType)));
+                    self.lower_pattern(pattern, value, _type, names, statements, context)?;
+                }
+            },
+            Pattern::Struct(fields, rest) => {
+                let Type::Struct(struct_type) = value_type else {
+                    return Err(err!(self, ParserErrorKind::ExpectedStruct(value_type)))
+                };
+
+                let builder = self.global_mapper.structs().get_by_ref(&struct_type)
+                    .map_err(|e| err!(self, e.into()))?;
+                let mut resolved: Vec<(IdentifierType, Pattern<'a>)> = Vec::with_capacity(fields.len());
+                for (field, pattern) in fields {
+                    let id = builder.get_id_for_field(field)
+                        .ok_or_else(|| err!(self, ParserErrorKind::UnexpectedVariable(field)).with_suggestion(find_similar(field, builder.names().iter().copied())))?;
+
+                    if resolved.iter().any(|(other, _)| *other == id) {
+                        return Err(err!(self, ParserErrorKind::DuplicatedFieldInPattern(field)))
+                    }
+                    resolved.push((id, pattern));
+                }
+
+                if !rest && resolved.len() != struct_type.fields().len() {
+                    return Err(err!(self, ParserErrorKind::MissingFieldsInPattern))
+                }
+
+                let base = self.store_pattern_value(value, Type::Struct(struct_type.clone()), statements, context);
+                for (id, pattern) in resolved {
+                    let value = Expression::Path(Box::new(base.clone()), Box::new(Expression::Variable(id)));
+                    let _type = struct_type.fields()[id as usize].clone();
+                    self.lower_pattern(pattern, value, _type, names, statements, context)?;
+                }
+            },
+            Pattern::Array(patterns, true) => {
+                let Type::Array(inner) = value_type else {
+                    return Err(err!(self, ParserErrorKind::ExpectedArray(value_type)))
+                };
+
+                let base = self.store_pattern_value(value, Type::Array(inner.clone()), statements, context);
+                for (i, pattern) in patterns.into_iter().enumerate() {
+                    let index = Expression::Constant(Value::U32(i as u32).into());
+                    let value = Expression::ArrayCall(Box::new(base.clone()), Box::new(index));
+                    self.lower_pattern(pattern, value, (*inner).clone(), names, statements, context)?;
+                }
+            },
+            Pattern::Array(patterns, false) => {
+                let Type::Array(inner) = value_type else {
+                    return Err(err!(self, ParserErrorKind::ExpectedArray(value_type)))
+                };
+
+                if patterns.len() > u8::MAX as usize {
+                    return Err(err!(self, ParserErrorKind::InvalidTupleSize(patterns.len())))
+                }
+
+                // Unpacked like a tuple, the execution fails if the length is different
+                // The nested patterns are read from hidden variables
+                let mut ids = Vec::with_capacity(patterns.len());
+                let mut nested = Vec::new();
+                for pattern in patterns {
+                    let id = match pattern {
+                        Pattern::Binding(name) => {
+                            self.verify_binding_name(name, names)?;
+                            self.declare_variable(name, (*inner).clone(), context)
+                        },
+                        pattern => {
+                            let id = context.register_variable_unchecked(DESTRUCTURING_VARIABLE, (*inner).clone());
+                            nested.push((id, pattern));
+                            id
+                        }
+                    };
+                    ids.push(id);
+                }
+                statements.push(Statement::TupleDestructuring(ids, value));
+
+                for (id, pattern) in nested {
+                    self.lower_pattern(pattern, Expression::Variable(id), (*inner).clone(), names, statements, context)?;
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /**
+     * Example: let { owner, fee: { amount, .. } } = config;
+     * Example: let [first, second, ..] = values;
+     * Rules:
+     * - A struct pattern lists the fields by name, `field` is a shorthand for `field: field`
+     * - Without `..`, all the fields of the struct must be listed
+     * - Without `..`, the array must have the same number of values, checked during the execution
+     * - Each variable takes the type of its field or value
+     * - '_' can be used to ignore a value
+     */
+    fn read_destructuring(&mut self, context: &mut Context<'a>) -> Result<Statement, ParserError<'a>> {
+        let pattern = self.read_pattern()?;
+        self.expect_token(Token::OperatorAssign)?;
+
+        let value = self.read_expr(None, true, true, None, context)?;
+        let value_type = self.get_type_from_expression(None, &value, context)?.into_owned();
+
+        // Variables are registered after reading the value, it can't reference them
+        let mut statements = Vec::new();
+        self.lower_pattern(pattern, value, value_type, &mut Vec::new(), &mut statements, context)?;
+        // The generated declarations
+        self.repeat_statement_span(statements.len());
+
+        Ok(Statement::Destructuring(statements))
+    }
+
+    fn read_const(&mut self, context: &mut Context<'a>) -> Result<(), ParserError<'a>> {
+        let (name, constant) = self.read_const_declaration(context)?;
+        self.constants.insert(name, constant);
+
+        Ok(())
+    }
+
+    // Read a constant and evaluate its value
+    fn read_const_declaration(&mut self, context: &mut Context<'a>) -> Result<(&'a str, ConstantDeclaration), ParserError<'a>> {
+        let (name, value_type, mut value) = self.read_variable_internal(context, true)?;
+
+        let const_value = self.try_convert_expr_to_value(&mut value)
+                .ok_or(err!(self, ParserErrorKind::InvalidConstantValue))?;
+
+        Ok((name, ConstantDeclaration {
+            value: const_value,
+            value_type
+        }))
+    }
+
+    // Read the optional else branch of a if
+    fn read_else(&mut self, context: &mut Context<'a>, return_type: &Option<Type>) -> Result<Option<Vec<Statement>>, ParserError<'a>> {
+        if !self.peek_is(Token::Else) {
+            return Ok(None)
+        }
+
+        self.advance()?;
+        Ok(Some(if self.peek_is(Token::If) {
+            let statement = self.read_statement(context, return_type)?;
+            vec![statement.ok_or(err!(self, ParserErrorKind::UnexpectedToken(Token::If)))?]
+        } else {
+            self.expect_token(Token::BraceOpen)?;
+            self.read_body(context, return_type)?
+        }))
+    }
+
+    // Read the binding of a if let or while let: let Some(name) = value
+    // Returns the binding name, the optional value and its inner type
+    fn read_optional_binding(&mut self, context: &mut Context<'a>) -> Result<(&'a str, Expression, Type), ParserError<'a>> {
+        self.expect_token(Token::Let)?;
+        self.expect_token(Token::Identifier("Some"))?;
+        self.expect_token(Token::ParenthesisOpen)?;
+        let name = self.next_identifier()?;
+        if name != "_" && !name.starts_with(char::is_alphabetic) {
+            return Err(err!(self, ParserErrorKind::VariableMustStartWithAlphabetic(name)))
+        }
+        self.expect_token(Token::ParenthesisClose)?;
+        self.expect_token(Token::OperatorAssign)?;
+
+        let value = self.read_expression(context)?;
+        match self.get_type_from_expression(None, &value, context)?.into_owned() {
+            Type::Optional(inner) => Ok((name, value, *inner)),
+            _type => Err(err!(self, ParserErrorKind::ExpectedOptional(_type)))
+        }
+    }
+
+    // Call a function of the optional stored in the variable
+    fn call_on_optional(&self, name: &str, _type: &Type, id: IdentifierType) -> Result<Expression, ParserError<'a>> {
+        let mut parameters = Vec::new();
+        let function = self.global_mapper
+            .functions()
+            .get_compatible(Signature::new(name.to_owned(), Some(_type.clone()), Vec::new()), &mut parameters)
+            .map_err(|e| err!(self, e.into()))?;
+
+        Ok(Expression::FunctionCall(Some(Box::new(Expression::Variable(id))), function, parameters))
+    }
+
+    // Span of the last token read
+    fn current_span(&self) -> Span {
+        Span {
+            line: self.line,
+            column_start: self.column_start,
+            column_end: self.column_end
+        }
+    }
+
+    // Variables are reported at the statement declaring them
+    fn declaration_span(&self) -> Span {
+        self.statements_spans.last()
+            .copied()
+            .unwrap_or_else(|| self.current_span())
+    }
+
+    fn warn(&mut self, span: Span, code: WarningCode, message: String) {
+        self.warnings.push(Warning::new(span, code, message));
+    }
+
+    // Closest variable or constant name in scope
+    fn similar_variable(&self, name: &str, context: &Context<'a>) -> Option<&'a str> {
+        // The hidden variables can't be used by the scripts
+        let variables = context.variable_names()
+            .filter(|variable| !variable.starts_with('$'));
+        find_similar(name, variables.chain(self.constants.keys().copied()))
+    }
+
+    // Closest function name callable with the same instance type
+    // Nothing is suggested if the name exists, only the parameters don't match
+    fn similar_function(&self, name: &str, on_type: Option<&Type>) -> Option<&str> {
+        let names = self.global_mapper.functions().get_names(on_type);
+        if names.contains(&name) {
+            return None
+        }
+
+        find_similar(name, names)
+    }
+
+    // The statements generated by a if let or while let are not in the source code
+    // They use the span of the statement that generated them
+    fn repeat_statement_span(&mut self, count: usize) {
+        if let Some(span) = self.statements_spans.last().copied() {
+            self.statements_spans.extend(std::iter::repeat_n(span, count));
+        }
+    }
+
+    // Register the hidden variable holding the optional and the binding declaration
+    // The binding is registered in a new scope that must be closed by the caller
+    fn register_optional_binding(&mut self, name: &'a str, value: Expression, inner: Type, context: &mut Context<'a>) -> Result<(Statement, Expression, Statement), ParserError<'a>> {
+        let value_type = Type::Optional(Box::new(inner.clone()));
+        let id = context.register_variable_unchecked(OPTIONAL_VARIABLE, value_type.clone());
+        let condition = self.call_on_optional("is_some", &value_type, id)?;
+        let unwrap = self.call_on_optional("unwrap", &value_type, id)?;
+
+        context.begin_scope();
+        let binding = DeclarationStatement {
+            id: self.declare_variable(name, inner.clone(), context),
+            value_type: inner,
+            value: unwrap
+        };
+
+        Ok((Statement::Variable(DeclarationStatement { id, value_type, value }), condition, Statement::Variable(binding)))
+    }
+
+    /**
+     * Example: if let Some(v) = opt { ... } else { ... }
+     * It is read as:
+     * {
+     *     let tmp = opt;
+     *     if tmp.is_some() {
+     *         let v = tmp.unwrap();
+     *         ...
+     *     } else { ... }
+     * }
+     */
+    fn read_if_let(&mut self, context: &mut Context<'a>, return_type: &Option<Type>) -> Result<Statement, ParserError<'a>> {
+        context.begin_scope();
+        let (name, value, inner) = self.read_optional_binding(context)?;
+        let (variable, condition, binding) = self.register_optional_binding(name, value, inner, context)?;
+        // variable, if and binding
+        self.repeat_statement_span(3);
+
+        self.expect_token(Token::BraceOpen)?;
+        let mut body = vec![binding];
+        body.extend(self.read_body(context, return_type)?);
+        context.end_scope();
+
+        let else_statement = self.read_else(context, return_type)?;
+        context.end_scope();
+
+        Ok(Statement::Scope(vec![variable, Statement::If(condition, body, else_statement)]))
+    }
+
+    /**
+     * Example: while let Some(v) = it.next() { ... }
+     * It is read as:
+     * while true {
+     *     let tmp = it.next();
+     *     if tmp.is_some() {
+     *         let v = tmp.unwrap();
+     *         ...
+     *     } else {
+     *         break;
+     *     }
+     * }
+     */
+    fn read_while_let(&mut self, context: &mut Context<'a>, return_type: &Option<Type>) -> Result<Statement, ParserError<'a>> {
+        context.begin_scope();
+        let (name, value, inner) = self.read_optional_binding(context)?;
+        let (variable, condition, binding) = self.register_optional_binding(name, value, inner, context)?;
+        // variable, if and binding
+        self.repeat_statement_span(3);
+
+        let mut body = vec![binding];
+        body.extend(self.read_loop_body(context, return_type)?);
+        context.end_scope();
+        context.end_scope();
+        // break
+        self.repeat_statement_span(1);
+
+        let statements = vec![variable, Statement::If(condition, body, Some(vec![Statement::Break]))];
+        Ok(Statement::While(Expression::Constant(Constant::Default(Value::Boolean(true))), statements))
+    }
+
+    /**
+     * Example: foreach a in array { ... }
+     * Example: foreach (key, value) in map { ... }
+     * Rules:
+     * - Arrays, ranges, maps and the iterable types of the environment can be iterated
+     * - A map gives a tuple of each key and its value
+     * - The value can be destructured with a pattern, like a let
+     * - An iterable type of the environment is iterated on the array returned by its function
+     */
+    fn read_foreach(&mut self, context: &mut Context<'a>, return_type: &Option<Type>) -> Result<Statement, ParserError<'a>> {
+        context.begin_scope();
+        let pattern = self.read_pattern()?;
+        self.expect_token(Token::In)?;
+        let mut expr = self.read_expression(context)?;
+        let mut expr_type = self.get_type_from_expression(None, &expr, context)?.into_owned();
+
+        if let Some(id) = self.environment.get_iterable_function(&expr_type) {
+            expr = Expression::FunctionCall(Some(Box::new(expr)), id, Vec::new());
+            expr_type = self.get_type_from_expression(None, &expr, context)?.into_owned();
+        }
+
+        // verify that we can iter on it
+        let Some(value_type) = expr_type.get_iterated_type() else {
+            return Err(err!(self, ParserErrorKind::NotIterable(expr_type)))
+        };
+
+        let (id, statements) = match pattern {
+            Pattern::Binding(variable) => {
+                if context.has_variable(variable) {
+                    return Err(err!(self, ParserErrorKind::VariableNameAlreadyUsed(variable)))
+                }
+
+                (context.declare_variable(variable, value_type, self.declaration_span()), Vec::new())
+            },
+            pattern => {
+                // The value is stored in a hidden variable and destructured at the start of the body
+                let id = context.register_variable_unchecked(DESTRUCTURING_VARIABLE, value_type.clone());
+                let mut statements = Vec::new();
+                self.lower_pattern(pattern, Expression::Variable(id), value_type, &mut Vec::new(), &mut statements, context)?;
+                // The generated declarations
+                self.repeat_statement_span(statements.len() + 1);
+
+                (id, vec![Statement::Destructuring(statements)])
+            }
+        };
+
+        let mut body = statements;
+        body.extend(self.read_loop_body(context, return_type)?);
+        context.end_scope();
+
+        Ok(Statement::ForEach(id, expr, body))
+    }
+
+    /**
+     * Example:
+     * switch (value) {
+     *     case 1: ...
+     *     case 2, 3: ... fallthrough
+     *     default: ...
+     * }
+     * Rules:
+     * - The value is a number or a string, the case values are constants of its type
+     * - A value can only be used by one case
+     * - A case doesn't continue in the next one, unless it ends with `fallthrough`
+     * - The default case is optional and must be the last one
+     * - `break` and `continue` apply to the loop containing the switch
+     */
+    fn read_switch(&mut self, context: &mut Context<'a>, return_type: &Option<Type>) -> Result<Statement, ParserError<'a>> {
+        let value = self.read_expression(context)?;
+        let value_type = self.get_type_from_expression(None, &value, context)?.into_owned();
+        if !value_type.is_number() && value_type != Type::String {
+            return Err(err!(self, ParserErrorKind::InvalidSwitchType(value_type)))
+        }
+
+        // The value is read once and stored in a hidden variable
+        context.begin_scope();
+        let id = context.register_variable_unchecked(SWITCH_VARIABLE, value_type.clone());
+
+        self.expect_token(Token::BraceOpen)?;
+        let mut cases: Vec<SwitchCase> = Vec::new();
+        let mut default = None;
+        loop {
+            match self.advance()? {
+                Token::BraceClose => break,
+                Token::Case if default.is_none() => {
+                    let mut values = Vec::new();
+                    loop {
+                        let mut expr = self.read_expr(None, true, true, Some(&value_type), context)?;
+                        let expr_type = self.get_type_from_expression(None, &expr, context)?;
+                        if *expr_type != value_type {
+                            return Err(err!(self, ParserErrorKind::InvalidValueType(expr_type.into_owned(), value_type)))
+                        }
+
+                        let value = self.try_convert_expr_to_value(&mut expr)
+                            .and_then(|v| v.into_value().ok())
+                            .ok_or(err!(self, ParserErrorKind::InvalidConstantValue))?;
+
+                        if values.contains(&value) || cases.iter().any(|case| case.values.contains(&value)) {
+                            return Err(err!(self, ParserErrorKind::DuplicatedSwitchCase(value)))
+                        }
+                        values.push(value);
+
+                        if self.peek_is_not(Token::Comma) {
+                            break;
+                        }
+                        self.expect_token(Token::Comma)?;
+                    }
+
+                    self.expect_token(Token::Colon)?;
+                    let (statements, fallthrough) = self.read_switch_case(context, return_type)?;
+                    cases.push(SwitchCase { values, statements, fallthrough });
+                },
+                Token::Default if default.is_none() => {
+                    self.expect_token(Token::Colon)?;
+                    let (statements, _) = self.read_switch_case(context, return_type)?;
+                    default = Some(statements);
+                },
+                Token::Case | Token::Default => return Err(err!(self, ParserErrorKind::SwitchDefaultNotLast)),
+                token => return Err(err!(self, ParserErrorKind::UnexpectedToken(token)))
+            }
+        }
+        context.end_scope();
+
+        Ok(Statement::Switch(DeclarationStatement { id, value_type, value }, cases, default))
+    }
+
+    // Read the statements of a case until the next case or the end of the switch
+    // Returns true if they end with a fallthrough
+    fn read_switch_case(&mut self, context: &mut Context<'a>, return_type: &Option<Type>) -> Result<(Vec<Statement>, bool), ParserError<'a>> {
+        context.begin_scope();
+        let mut statements = Vec::new();
+        let mut fallthrough = false;
+        while !self.peek_is_end_of_block() {
+            if self.peek_is(Token::Fallthrough) {
+                self.expect_token(Token::Fallthrough)?;
+                // The next case is required to continue in it
+                if !matches!(self.peek()?, Token::Case | Token::Default) {
+                    return Err(err!(self, ParserErrorKind::InvalidFallthrough))
+                }
+
+                fallthrough = true;
+                break;
+            }
+
+            // The end of the block is never consumed here
+            if let Some(statement) = self.read_statement(context, return_type)? {
+                statements.push(statement);
+            }
+        }
+        context.end_scope();
 
-                    // verify that we can iter on it
-                    if !expr_type.is_iterable() {
-                        return Err(err!(self, ParserErrorKind::NotIterable(expr_type.into_owned())))
+        Ok((statements, fallthrough))
+    }
+
+    fn read_loop_body(&mut self, context: &mut Context<'a>, return_type: &Option<Type>) -> Result<Vec<Statement>, ParserError<'a>> {
+        // support nested loop
+        let old_value = context.is_in_a_loop();
+        context.set_in_a_loop(true);
+        self.expect_token(Token::BraceOpen)?;
+        let statements = self.read_body(context, return_type)?;
+        context.set_in_a_loop(old_value);
+
+        Ok(statements)
+    }
+
+    // Read a single statement
+    fn read_statement(&mut self, context: &mut Context<'a>, return_type: &Option<Type>) -> Result<Option<Statement>, ParserError<'a>> {
+        if let Some(token) = self.next() {
+            // Spans are registered before reading the inner statements
+            // so they follow a depth-first order
+            if !matches!(token, Token::BraceClose) {
+                self.statements_spans.push(self.current_span());
+            }
+
+            let statement: Statement = match token {
+                Token::BraceClose => return Ok(None),
+                Token::For => { // Example: for i: u64 = 0; i < 10; i += 1 {}
+                    context.begin_scope();
+                    let var = self.read_variable(context)?;
+                    let condition = self.read_expression(context)?;
+                    let condition_type = self.get_type_from_expression(None, &condition, context)?;
+                    if  *condition_type != Type::Bool {
+                        return Err(err!(self, ParserErrorKind::InvalidCondition(condition_type.into_owned(), Box::new(condition))))
                     }
 
-                    let id = context.register_variable(variable, expr_type.get_inner_type().clone())
-                        .ok_or_else(|| err!(self, ParserErrorKind::VariableNameAlreadyUsed(variable)))?;
+                    let increment = self.read_expression(context)?;
+                    match &increment { // allow only assignations on this expr
+                        Expression::Operator(op, _, _) if op.is_assignation() => {},
+                        _ => return Err(err!(self, ParserErrorKind::InvalidForExpression(Box::new(increment))))
+                    };
+
                     let statements = self.read_loop_body(context, return_type)?;
                     context.end_scope();
 
-                    Statement::ForEach(id, expr, statements)
-                },
+                    Statement::For(var, condition, increment, statements)
+                }
+                Token::ForEach => self.read_foreach(context, return_type)?,
+                Token::Switch => self.read_switch(context, return_type)?,
+                Token::While if self.peek_is(Token::Let) => self.read_while_let(context, return_type)?,
                 Token::While => { // Example: while i < 10 {}
                     let condition = self.read_expression(context)?;
                     let condition_type = self.get_type_from_expression(None, &condition, context)?;
                     if  *condition_type != Type::Bool {
-                        return Err(err!(self, ParserErrorKind::InvalidCondition(condition_type.into_owned(), condition)))
+                        return Err(err!(self, ParserErrorKind::InvalidCondition(condition_type.into_owned(), Box::new(condition))))
                     }
 
                     let statements = self.read_loop_body(context, return_type)?;
 
                     Statement::While(condition, statements)
                 },
+                Token::If if self.peek_is(Token::Let) => self.read_if_let(context, return_type)?,
                 Token::If => {
                     let condition = self.read_expression(context)?;
                     let condition_type = self.get_type_from_expression(None, &condition, context)?;
                     if *condition_type != Type::Bool {
-                        return Err(err!(self, ParserErrorKind::InvalidCondition(condition_type.into_owned(), condition)))
+                        return Err(err!(self, ParserErrorKind::InvalidCondition(condition_type.into_owned(), Box::new(condition))))
                     }
 
                     self.expect_token(Token::BraceOpen)?;
                     let body = self.read_body(context, return_type)?;
-                    let else_statement = if self.peek_is(Token::Else) {
-                        self.advance()?;
-                        Some(if self.peek_is(Token::If) {
-                            let statement = self.read_statement(context, return_type)?;
-                            vec![statement.ok_or(err!(self, ParserErrorKind::UnexpectedToken(Token::If)))?]
-                        } else {
-                            self.expect_token(Token::BraceOpen)?;
-                            self.read_body(context, return_type)?
-                        })
-                    } else {
-                        None
-                    };
+                    let else_statement = self.read_else(context, return_type)?;
 
                     Statement::If(condition, body, else_statement)
                 },
                 Token::BraceOpen => Statement::Scope(self.read_body(context, return_type)?),
+                Token::Let if self.peek_is(Token::ParenthesisOpen) => self.read_tuple_destructuring(context)?,
+                Token::Let if self.peek_is(Token::BraceOpen) || self.peek_is(Token::BracketOpen) => self.read_destructuring(context)?,
                 Token::Let => Statement::Variable(self.read_variable(context)?),
                 Token::Return => {
                     let opt: Option<Expression> = if let Some(return_type) = return_type {
@@ -1480,7 +2437,7 @@ impl<'a> Parser<'a> {
                     };
 
                     // we can't have anything after a return
-                    if self.peek_is_not(Token::BraceClose) {
+                    if !self.peek_is_end_of_block() {
                         return Err(err!(self, ParserErrorKind::DeadCodeNotAllowed));
                     }
 
@@ -1492,7 +2449,7 @@ impl<'a> Parser<'a> {
                     }
 
                     // we can't have anything after a continue
-                    if self.peek_is_not(Token::BraceClose) {
+                    if !self.peek_is_end_of_block() {
                         return Err(err!(self, ParserErrorKind::DeadCodeNotAllowed));
                     }
 
@@ -1504,7 +2461,7 @@ impl<'a> Parser<'a> {
                     }
 
                     // we can't have anything after a break
-                    if self.peek_is_not(Token::BraceClose) {
+                    if !self.peek_is_end_of_block() {
                         return Err(err!(self, ParserErrorKind::DeadCodeNotAllowed));
                     }
 
@@ -1527,14 +2484,94 @@ impl<'a> Parser<'a> {
     fn read_statements(&mut self, context: &mut Context<'a>, return_type: &Option<Type>) -> Result<Vec<Statement>, ParserError<'a>> {
         trace!("Read statements");
         let mut statements: Vec<Statement> = Vec::new();
-        while let Some(statement) = self.read_statement(context, return_type)? {
+        let mut unreachable_reported = false;
+        loop {
+            let span_index = self.statements_spans.len();
+            let depth = context.scope_depth();
+            let in_loop = context.is_in_a_loop();
+            let statement = match self.read_statement(context, return_type) {
+                Ok(Some(statement)) => statement,
+                Ok(None) => break,
+                Err(e) if self.recover => {
+                    // Drop the broken statement and continue with the next one
+                    self.errors.push(e);
+                    self.statements_spans.truncate(span_index);
+                    context.end_scopes_until(depth);
+                    context.set_in_a_loop(in_loop);
+                    self.skip_statement();
+                    continue;
+                },
+                Err(e) => return Err(e)
+            };
             trace!("statement: {:?}", statement);
+
+            // Only the first unreachable statement of a block is reported
+            if !unreachable_reported && statements.last().is_some_and(Self::is_diverging) {
+                unreachable_reported = true;
+                let span = self.statements_spans.get(span_index)
+                    .copied()
+                    .unwrap_or_else(|| self.current_span());
+                self.warn(span, WarningCode::UnreachableCode, "unreachable statement".to_owned());
+            }
+
             statements.push(statement);
         }
 
         Ok(statements)
     }
 
+    // Skip the tokens until the start of the next statement, used by the recovery mode
+    // A statement starts with a keyword or on a new line, the closing brace of the block is kept
+    fn skip_statement(&mut self) {
+        let line = self.line;
+        let mut depth = 0usize;
+        while let Some(next) = self.tokens.front() {
+            if depth == 0 {
+                let is_statement = matches!(next.token, Token::Let | Token::If | Token::For | Token::ForEach | Token::While | Token::Switch | Token::Return | Token::Break | Token::Continue);
+                if next.token == Token::BraceClose || is_statement || next.line > line {
+                    break;
+                }
+            }
+
+            match self.next() {
+                Some(Token::BraceOpen) => depth += 1,
+                Some(Token::BraceClose) => depth = depth.saturating_sub(1),
+                _ => {}
+            }
+        }
+    }
+
+    // Skip the tokens until the next top level declaration, used by the recovery mode
+    fn skip_declaration(&mut self) {
+        while let Some(next) = self.tokens.front() {
+            let qualifier = matches!(next.token, Token::Identifier("test") | Token::Identifier("pub"))
+                && self.tokens.get(1).is_some_and(|t| t.token == Token::Function);
+            if qualifier || Self::is_declaration(&next.token) {
+                break;
+            }
+            self.next();
+        }
+    }
+
+    // Returns true if the statement never continues to the next one
+    // A statement directly after a return, break or continue is already an error,
+    // this is used for the branches that all return, break or continue
+    fn is_diverging(statement: &Statement) -> bool {
+        match statement {
+            Statement::Return(_) | Statement::Break | Statement::Continue => true,
+            Statement::If(_, statements, Some(else_statements)) => {
+                statements.last().is_some_and(Self::is_diverging)
+                    && else_statements.last().is_some_and(Self::is_diverging)
+            },
+            Statement::Scope(statements) => statements.last().is_some_and(Self::is_diverging),
+            Statement::Switch(_, cases, Some(default)) => {
+                cases.iter().all(|case| case.fallthrough || case.statements.last().is_some_and(Self::is_diverging))
+                    && default.last().is_some_and(Self::is_diverging)
+            },
+            _ => false
+        }
+    }
+
     // Read the parameters for a function
     fn read_parameters(&mut self) -> Result<Vec<(&'a str, Type)>, ParserError<'a>> {
         let mut parameters = Vec::new();
@@ -1576,6 +2613,16 @@ impl<'a> Parser<'a> {
                 Statement::Return(Some(_)) => {
                     ok = true;
                 },
+                Statement::Scope(statements) => {
+                    ok = Self::ends_with_return(statements)?;
+                },
+                // Without a default case, no case may match
+                Statement::Switch(_, cases, Some(default)) => {
+                    ok = Self::ends_with_return(default)?;
+                    for case in cases.iter().filter(|case| !case.fallthrough) {
+                        ok = ok && Self::ends_with_return(&case.statements)?;
+                    }
+                },
                 _ => {}
             }
         }
@@ -1590,32 +2637,56 @@ impl<'a> Parser<'a> {
      * - fn foo() -> u64 { ... }
      * - fn foo(a: u64, b: u64) { ... }
      * - fn (f Foo) bar() { ... }
+     * - fn bar(self) { ... } inside an impl block
+     * - test fn foo() { ... }
+     * - pub fn foo() { ... }
      * Rules:
      * - Signature is based on function name, and parameters
      * - Entry function is a "public callable" function and must return a u64 value
+     * - Test function has no parameters nor return type and is only invoked by a test runner
+     * - Public function is exported by its name and cannot be a method
      */
-    fn read_function(&mut self, entry: bool, context: &mut Context<'a>) -> Result<(), ParserError<'a>> {
+    fn read_function(&mut self, kind: FunctionKind, impl_type: Option<&Type>, context: &mut Context<'a>) -> Result<(), ParserError<'a>> {
+        let entry = kind == FunctionKind::Entry;
         trace!("Read function");
         context.begin_scope();
 
-        let token = self.advance()?;
-        let (instance_name, for_type, name) = if !entry && token == Token::ParenthesisOpen {
-            let instance_name = self.next_identifier()?;
-            let for_type = self.read_type()?;
-            let id = context.register_variable(instance_name, for_type.clone())
-                .ok_or_else(|| err!(self, ParserErrorKind::VariableNameAlreadyUsed(instance_name)))?;
-
-            self.expect_token(Token::ParenthesisClose)?;
+        let (instance_name, for_type, name) = if let Some(impl_type) = impl_type {
+            // The instance is given by an explicit `self` as first parameter
+            // Without it, the function is static and called as `Type::name()`
+            let name = self.next_identifier()?;
+            self.expect_token(Token::ParenthesisOpen)?;
+            let instance_name = if self.read_self_parameter()? {
+                let id = context.register_variable("self", impl_type.clone())
+                    .ok_or_else(|| err!(self, ParserErrorKind::VariableNameAlreadyUsed("self")))?;
+                Some(id)
+            } else {
+                None
+            };
 
-            (Some(id), Some(for_type), self.next_identifier()?)
+            (instance_name, Some(impl_type.clone()), name)
         } else {
-            let Token::Identifier(name) = token else {
-                return Err(err!(self, ParserErrorKind::ExpectedIdentifierToken(token)))
+            let token = self.advance()?;
+            let header = if !entry && token == Token::ParenthesisOpen {
+                let instance_name = self.next_identifier()?;
+                let for_type = self.read_type()?;
+                let id = context.register_variable(instance_name, for_type.clone())
+                    .ok_or_else(|| err!(self, ParserErrorKind::VariableNameAlreadyUsed(instance_name)))?;
+
+                self.expect_token(Token::ParenthesisClose)?;
+
+                (Some(id), Some(for_type), self.next_identifier()?)
+            } else {
+                let Token::Identifier(name) = token else {
+                    return Err(err!(self, ParserErrorKind::ExpectedIdentifierToken(token)))
+                };
+                (None, None, name)
             };
-            (None, None, name)
+
+            self.expect_token(Token::ParenthesisOpen)?;
+            header
         };
 
-        self.expect_token(Token::ParenthesisOpen)?;
         let parameters = self.read_parameters()?;
         self.expect_token(Token::ParenthesisClose)?;
 
@@ -1623,6 +2694,15 @@ impl<'a> Parser<'a> {
             return Err(err!(self, ParserErrorKind::TooManyParameters))
         }
 
+        if kind == FunctionKind::Test && (!parameters.is_empty() || for_type.is_some()) {
+            return Err(err!(self, ParserErrorKind::TestFunctionCannotHaveParameters))
+        }
+
+        // a public function is exported by its name only
+        if kind == FunctionKind::Public && for_type.is_some() {
+            return Err(err!(self, ParserErrorKind::PublicFunctionCannotHaveForType))
+        }
+
         // all entries must return a u64 value without being specified
         let return_type: Option<Type> = if entry {
             // an entrypoint cannot be a method
@@ -1631,7 +2711,7 @@ impl<'a> Parser<'a> {
             }
 
             Some(Type::U64)
-        } else if self.peek_is(Token::ReturnType) { // read returned type
+        } else if kind != FunctionKind::Test && self.peek_is(Token::ReturnType) { // read returned type
             self.advance()?;
             Some(self.read_type()?)
         } else {
@@ -1645,6 +2725,9 @@ impl<'a> Parser<'a> {
         }
 
         let has_return_type = return_type.is_some();
+        let parameters_names: Vec<String> = parameters.iter()
+            .map(|(name, _)| (*name).to_owned())
+            .collect();
         let mut new_params = Vec::with_capacity(parameters.len());
         for (name, param_type) in parameters {
             let id = context.register_variable(name, param_type.clone())
@@ -1653,27 +2736,50 @@ impl<'a> Parser<'a> {
         }
 
 
-        let function = match entry {
-            true => FunctionType::Entry(EntryFunction::new(new_params, Vec::new(), context.max_variables_count() as u16)),
-            false => FunctionType::Declared(DeclaredFunction::new(
-                for_type,
-                instance_name,
-                new_params,
-                Vec::new(),
-                return_type.clone(),
-                0
-            ))
+        let invariants = self.read_invariants(name, context)?;
+        let mut function = match kind {
+            FunctionKind::Entry => {
+                let mut entry = EntryFunction::new(new_params, Vec::new(), context.max_variables_count());
+                entry.set_parameters_names(parameters_names);
+                FunctionType::Entry(entry)
+            },
+            FunctionKind::Test => FunctionType::Test(DeclaredFunction::new(None, None, Vec::new(), Vec::new(), None, 0)),
+            FunctionKind::Declared | FunctionKind::Public => {
+                let mut function = DeclaredFunction::new(
+                    for_type,
+                    instance_name,
+                    new_params,
+                    Vec::new(),
+                    return_type.clone(),
+                    0
+                );
+                function.set_parameters_names(parameters_names);
+                function.set_public(kind == FunctionKind::Public);
+                FunctionType::Declared(function)
+            }
         };
 
+        function.set_invariants(invariants);
+
         // push function before reading statements to allow recursive calls
         self.functions.push(function);
 
         self.expect_token(Token::BraceOpen)?;
+        self.statements_spans.clear();
+        self.return_type = return_type.clone();
+        let errors_count = self.errors.len();
         let statements = self.read_body(context, &return_type)?;
+        self.return_type = None;
         context.end_scope();
 
+        for (name, span) in context.take_unused_variables() {
+            self.warn(span, WarningCode::UnusedVariable, format!("variable '{}' is never used", name));
+        }
+
         // verify that the function ends with a return
-        if has_return_type && !Self::ends_with_return(&statements)? {
+        // In the recovery mode, the return may be a statement dropped on error
+        let recovered = self.errors.len() != errors_count;
+        if has_return_type && !recovered && !Self::ends_with_return(&statements)? {
             return Err(err!(self, ParserErrorKind::NoReturnFound))
         }
 
@@ -1682,11 +2788,98 @@ impl<'a> Parser<'a> {
             .ok_or(err!(self, ParserErrorKind::UnknownError))?;
 
         last.set_statements(statements);
+        last.set_statements_spans(mem::take(&mut self.statements_spans));
+        last.set_name(name.to_owned());
         last.set_max_variables_count(context.max_variables_count() as u16);
 
         Ok(())
     }
 
+    // Read an attribute of the next function with the following syntax:
+    // #[invariant(condition)]
+    // The condition uses the parameters of the function, so its tokens are kept until they are declared
+    fn read_attribute(&mut self) -> Result<(), ParserError<'a>> {
+        trace!("Read attribute");
+        self.expect_token(Token::BracketOpen)?;
+        let name = self.next_identifier()?;
+        if name != "invariant" {
+            return Err(err!(self, ParserErrorKind::UnknownAttribute(name)))
+        }
+
+        self.expect_token(Token::ParenthesisOpen)?;
+        let mut tokens = Vec::new();
+        let mut depth = 0usize;
+        loop {
+            let token = self.tokens.pop_front()
+                .ok_or(err!(self, ParserErrorKind::ExpectedToken))?;
+            // The closing parenthesis is kept to end the condition
+            let end = match token.token {
+                Token::ParenthesisOpen => {
+                    depth += 1;
+                    false
+                },
+                Token::ParenthesisClose if depth == 0 => true,
+                Token::ParenthesisClose => {
+                    depth -= 1;
+                    false
+                },
+                _ => false
+            };
+            tokens.push(token);
+            if end {
+                break
+            }
+        }
+        self.expect_token(Token::BracketClose)?;
+
+        if tokens.len() == 1 {
+            return Err(err!(self, ParserErrorKind::EmptyValue))
+        }
+
+        // Only the functions declared at the top level have attributes
+        let function = match self.peek()? {
+            Token::Hash | Token::Function | Token::Entry => true,
+            Token::Identifier("pub" | "test") => self.tokens.get(1).is_some_and(|t| t.token == Token::Function),
+            _ => false
+        };
+        if !function {
+            return Err(err!(self, ParserErrorKind::AttributeWithoutFunction))
+        }
+
+        self.invariants.push(tokens);
+        Ok(())
+    }
+
+    // Read the conditions of the invariants of the function, once its parameters are declared
+    // Each condition is checked by an assertion, removed by the compiler with the other ones
+    // when the debug assertions are disabled
+    fn read_invariants(&mut self, name: &str, context: &mut Context<'a>) -> Result<Vec<Expression>, ParserError<'a>> {
+        let mut invariants = Vec::new();
+        for tokens in mem::take(&mut self.invariants) {
+            // The tokens of the function are restored even on error for the recovery mode
+            let rest = mem::replace(&mut self.tokens, tokens.into());
+            let condition = self.read_expression(context)
+                .and_then(|condition| self.expect_token(Token::ParenthesisClose).map(|_| condition));
+            self.tokens = rest;
+            let condition = condition?;
+
+            let condition_type = self.get_type_from_expression(None, &condition, context)?;
+            if *condition_type != Type::Bool {
+                return Err(err!(self, ParserErrorKind::InvalidCondition(condition_type.into_owned(), Box::new(condition))))
+            }
+
+            let message = Expression::Constant(Constant::Default(Value::String(format!("invariant of '{}' failed", name))));
+            let mut parameters = vec![condition, message];
+            let id = self.global_mapper
+                .functions()
+                .get_compatible(Signature::new("assert".to_owned(), None, vec![Type::Bool, Type::String]), &mut parameters)
+                .map_err(|e| err!(self, e.into()))?;
+            invariants.push(Expression::FunctionCall(None, id, parameters));
+        }
+
+        Ok(invariants)
+    }
+
     // Read a type with the following syntax:
     // import "filename.xel";
     // or with an alias:
@@ -1711,34 +2904,216 @@ impl<'a> Parser<'a> {
             return Err(err!(self, ParserErrorKind::NotImplemented))
         }
 
-        Ok(())
-    }
+        Ok(())
+    }
+
+    // check if a function with the same signature exists
+    fn has_function(&self, id: u16) -> bool {
+        self.get_function(id).is_ok()
+    }
+
+    // get a function using its identifier
+    fn get_function<'b>(&'b self, id: u16) -> Result<Function<'b>, ParserError<'a>> {
+        // the id is the index of the function in the functions array
+        let index = id as usize;
+        let len = self.environment.get_functions().len();
+        if index < len {
+            Ok(Function::Native(&self.environment.get_functions()[index]))
+        } else {
+            match self.functions.get(index - len) {
+                Some(func) => Ok(Function::Program(func)),
+                None => Err(err!(self, ParserErrorKind::FunctionNotFound))
+            }
+        }
+    }
+
+    // Verify that a type name is not already used
+    fn is_name_available(&self, name: &str) -> bool {
+        trace!("Check if name is available: {}", name);
+        self.global_mapper.structs().get_by_name(name).is_err()
+            && self.global_mapper.enums().get_by_name(name).is_err()
+            && self.environment.get_opaque_by_name(name).is_none()
+            && !self.environment.has_namespace(name)
+            && !self.interfaces.contains_key(name)
+    }
+
+    // Read the `self` parameter of a function in an impl block or an interface
+    // Returns false if the function has no instance
+    fn read_self_parameter(&mut self) -> Result<bool, ParserError<'a>> {
+        if !self.peek_is(Token::Identifier("self")) {
+            return Ok(false)
+        }
+
+        self.advance()?;
+        if self.peek_is(Token::Comma) {
+            self.expect_token(Token::Comma)?;
+        }
+
+        Ok(true)
+    }
+
+    /**
+     * Example: impl Rect { fn area(self) -> u64 { ... } fn square(size: u64) -> Rect { ... } }
+     * Or to implement an interface: impl Shape for Rect { ... }
+     * Rules:
+     * - Only structs and enums can be implemented
+     * - A constant is accessed as `Rect::NAME`, an enum constant can't use the name of a variant
+     * - A method receives its instance through `self` and is called as `value.area()`
+     * - A function without `self` is static and called as `Rect::square(size)`
+     * - `Self` can be used as the implemented type
+     * - All the functions of an implemented interface must be declared, and only them
+     */
+    fn read_impl(&mut self, context: &mut Context<'a>) -> Result<(), ParserError<'a>> {
+        let mut name = self.next_identifier()?;
+        let interface = if self.peek_is(Token::For) {
+            self.expect_token(Token::For)?;
+            let interface = self.interfaces.get(name)
+                .cloned()
+                .ok_or_else(|| err!(self, ParserErrorKind::InterfaceNotFound(name)))?;
+
+            name = self.next_identifier()?;
+            Some(interface)
+        } else {
+            None
+        };
+        trace!("Read impl: {}", name);
+
+        let _type = self.get_type_from_token(Token::Identifier(name))?;
+        if !matches!(_type, Type::Struct(_) | Type::Enum(_)) {
+            return Err(err!(self, ParserErrorKind::UnexpectedType(_type)))
+        }
+
+        self.expect_token(Token::BraceOpen)?;
+        self.self_type = Some(_type.clone());
+
+        let first_function = self.functions.len();
+        let mut names = Vec::new();
+        loop {
+            if self.peek_is(Token::Function) {
+                self.expect_token(Token::Function)?;
+                if let Token::Identifier(name) = self.peek()? {
+                    names.push(*name);
+                }
+
+                self.read_function(FunctionKind::Declared, Some(&_type), context)?;
+            } else if self.peek_is(Token::Const) {
+                self.expect_token(Token::Const)?;
+                self.read_associated_const(&_type, context)?;
+            } else {
+                break;
+            }
+        }
+
+        self.self_type = None;
+        self.expect_token(Token::BraceClose)?;
+
+        if let Some(interface) = interface {
+            self.verify_interface(&interface, &_type, &names, first_function)?;
+        }
+
+        Ok(())
+    }
+
+    // Read a constant declared in an impl block
+    fn read_associated_const(&mut self, _type: &Type, context: &mut Context<'a>) -> Result<(), ParserError<'a>> {
+        let (name, constant) = self.read_const_declaration(context)?;
+
+        // `Enum::NAME` would be ambiguous with a variant
+        if let Type::Enum(enum_type) = _type {
+            let builder = self.global_mapper.enums()
+                .get_by_ref(enum_type)
+                .map_err(|e| err!(self, e.into()))?;
+            if builder.get_variant_by_name(name).is_some() {
+                return Err(err!(self, ParserErrorKind::EnumVariantAlreadyUsed(name)))
+            }
+        }
+
+        let constants = self.type_constants.entry(_type.clone()).or_default();
+        if constants.insert(name, constant).is_some() {
+            return Err(err!(self, ParserErrorKind::VariableNameAlreadyUsed(name)))
+        }
+
+        Ok(())
+    }
+
+    // Verify that the functions declared in an impl block match the interface
+    fn verify_interface(&self, interface: &Interface<'a>, _type: &Type, names: &[&'a str], first_function: usize) -> Result<(), ParserError<'a>> {
+        let functions = &self.functions[first_function..];
+        for (name, function) in names.iter().zip(functions) {
+            let expected = interface.get_function(name)
+                .ok_or_else(|| err!(self, ParserErrorKind::FunctionNotInInterface(name)))?;
+
+            let same_parameters = function.get_parameters().len() == expected.parameters.len()
+                && function.get_parameters().iter()
+                    .zip(&expected.parameters)
+                    .all(|(p, t)| *p.get_type() == resolve_self_type(t, _type));
+            let same_return_type = *function.return_type() == expected.return_type.as_ref().map(|t| resolve_self_type(t, _type));
+
+            if function.get_instance_name().is_some() != expected.instance || !same_parameters || !same_return_type {
+                return Err(err!(self, ParserErrorKind::InvalidInterfaceFunction(name)))
+            }
+        }
+
+        if let Some(missing) = interface.functions().iter().find(|f| !names.contains(&f.name)) {
+            return Err(err!(self, ParserErrorKind::MissingInterfaceFunction(missing.name)))
+        }
+
+        Ok(())
+    }
+
+    /**
+     * Example: interface Shape { fn area(self) -> u64 fn bigger(self, other: Self) -> bool }
+     * Rules:
+     * - The name must be unique
+     * - Functions have no body, `self` is the instance and `Self` the implementing type
+     */
+    fn read_interface(&mut self) -> Result<(), ParserError<'a>> {
+        let name = self.next_identifier()?;
+        trace!("Read interface: {}", name);
+
+        if !self.is_name_available(name) {
+            return Err(err!(self, ParserErrorKind::TypeNameAlreadyUsed(name)))
+        }
+
+        self.expect_token(Token::BraceOpen)?;
+        self.self_type = Some(SELF_TYPE);
+
+        let mut functions: Vec<InterfaceFunction> = Vec::new();
+        while self.peek_is(Token::Function) {
+            self.expect_token(Token::Function)?;
+            let function_name = self.next_identifier()?;
+            if functions.iter().any(|f| f.name == function_name) {
+                return Err(err!(self, ParserErrorKind::InterfaceFunctionAlreadyUsed(function_name)))
+            }
 
-    // check if a function with the same signature exists
-    fn has_function(&self, id: u16) -> bool {
-        self.get_function(id).is_ok()
-    }
+            self.expect_token(Token::ParenthesisOpen)?;
+            let instance = self.read_self_parameter()?;
+            let parameters = self.read_parameters()?
+                .into_iter()
+                .map(|(_, t)| t)
+                .collect();
+            self.expect_token(Token::ParenthesisClose)?;
 
-    // get a function using its identifier
-    fn get_function<'b>(&'b self, id: u16) -> Result<Function<'b>, ParserError<'a>> {
-        // the id is the index of the function in the functions array
-        let index = id as usize;
-        let len = self.environment.get_functions().len();
-        if index < len {
-            Ok(Function::Native(&self.environment.get_functions()[index]))
-        } else {
-            match self.functions.get(index - len) {
-                Some(func) => Ok(Function::Program(func)),
-                None => Err(err!(self, ParserErrorKind::FunctionNotFound))
-            }
+            let return_type = if self.peek_is(Token::ReturnType) {
+                self.advance()?;
+                Some(self.read_type()?)
+            } else {
+                None
+            };
+
+            functions.push(InterfaceFunction {
+                name: function_name,
+                instance,
+                parameters,
+                return_type
+            });
         }
-    }
 
-    // Verify that a type name is not already used
-    fn is_name_available(&self, name: &str) -> bool {
-        trace!("Check if name is available: {}", name);
-        self.global_mapper.structs().get_by_name(name).is_err()
-            && self.global_mapper.enums().get_by_name(name).is_err()
+        self.self_type = None;
+        self.expect_token(Token::BraceClose)?;
+        self.interfaces.insert(name, Interface::new(functions));
+
+        Ok(())
     }
 
     /**
@@ -1858,25 +3233,208 @@ impl<'a> Parser<'a> {
 
     // Parse the tokens and return a Program
     // The function mapper is also returned for external calls
-    pub fn parse(mut self) -> Result<(Program, GlobalMapper<'a>), ParserError<'a>> {
+    pub fn parse(self) -> Result<(Program, GlobalMapper<'a>), ParserError<'a>> {
+        self.parse_with_warnings()
+            .map(|(program, mapper, _)| (program, mapper))
+    }
+
+    // Same as parse, but the warnings found are also returned
+    // They are sorted by their position in the source code
+    pub fn parse_with_warnings(mut self) -> Result<(Program, GlobalMapper<'a>, Vec<Warning>), ParserError<'a>> {
+        self.read_declarations()?;
+        Ok(self.finish())
+    }
+
+    // Read the declarations of another source after the current tokens, like the next file of a project
+    // The declarations already read are visible, so a source can only use the ones of the previous sources
+    pub fn parse_source<I: IntoIterator<Item = TokenResult<'a>>>(&mut self, tokens: I) -> Result<(), ParserError<'a>> {
+        self.tokens.extend(tokens);
+        self.read_declarations()
+    }
+
+    // Build the program of all the sources read by `parse_source`
+    pub fn into_program(self) -> (Program, GlobalMapper<'a>, Vec<Warning>) {
+        self.finish()
+    }
+
+    // Parse the tokens without stopping at the first error, made for the editors
+    // A broken statement is skipped until the next one, and a broken declaration
+    // until the next top level keyword, all the errors are returned with the program read
+    pub fn parse_lenient(mut self) -> PartialProgram<'a> {
+        self.recover = true;
+        if let Err(e) = self.read_declarations() {
+            self.errors.push(e);
+        }
+
+        let errors = mem::take(&mut self.errors);
+        let (program, mapper, warnings) = self.finish();
+        PartialProgram {
+            program,
+            mapper,
+            errors,
+            warnings
+        }
+    }
+
+    // Parse the top level declarations, then the statements left, made for the interactive sessions
+    // The statements can use the variables given, and the variables declared at their top level
+    // are returned so they can be given to the next statements
+    pub fn parse_session(mut self, variables: Vec<(&'a str, Type)>) -> Result<SessionChunk<'a>, ParserError<'a>> {
+        let mut context: Context = Context::new();
+        while let Some(token) = self.tokens.front().map(|t| t.token.clone()).filter(Self::is_declaration) {
+            self.advance()?;
+            self.read_declaration(token, &mut context)?;
+        }
+
+        let mut context: Context = Context::new();
+        context.begin_scope();
+        let mut parameters = Vec::with_capacity(variables.len());
+        for (name, value_type) in variables {
+            let id = context.register_variable_unchecked(name, value_type.clone());
+            parameters.push(Parameter::new(id, value_type));
+        }
+
+        // the statements are read as a block, closed after the last token
+        let end = TokenResult {
+            token: Token::BraceClose,
+            line: self.tokens.back().map_or(self.line, |t| t.line),
+            column_start: 0,
+            column_end: 0
+        };
+        self.tokens.push_back(end);
+
+        self.statements_spans.clear();
+        let mut statements = self.read_statements(&mut context, &None)?;
+        // a closing brace written stops the statements before the end
+        if let Some(token) = self.next() {
+            return Err(err!(self, ParserErrorKind::UnexpectedToken(token)))
+        }
+
+        // the value of the last statement is returned if it's not an assignation
+        let value = match statements.last() {
+            Some(Statement::Expression(Expression::Operator(op, _, _))) if op.is_assignation() => None,
+            Some(Statement::Expression(expr)) => self.get_type_from_expression(None, expr, &context)
+                .ok()
+                .map(Cow::into_owned),
+            _ => None
+        };
+        let value = value.and_then(|value_type| match statements.pop() {
+            Some(Statement::Expression(expr)) => Some((expr, value_type)),
+            _ => None
+        });
+
+        let mut variables = Vec::new();
+        let mut values = Vec::new();
+        for (name, id) in context.visible_variables() {
+            // hidden variables can't be named by the next statements
+            if name == "_" || name.starts_with('$') {
+                continue;
+            }
+
+            context.use_variable(name);
+            let value_type = context.get_type_of_variable(&id)
+                .ok_or_else(|| err!(self, ParserErrorKind::UnknownError))?
+                .clone();
+            variables.push((name, value_type));
+            values.push(Expression::Variable(id));
+        }
+        context.end_scope();
+
+        let mut types: Vec<Type> = variables.iter().map(|(_, t)| t.clone()).collect();
+        let value_type = value.map(|(expr, value_type)| {
+            values.push(expr);
+            types.push(value_type.clone());
+            value_type
+        });
+
+        let return_type = if values.is_empty() {
+            None
+        } else {
+            statements.push(Statement::Return(Some(Expression::TupleConstructor(values))));
+            Some(Type::Tuple(types))
+        };
+
+        for (name, span) in context.take_unused_variables() {
+            self.warn(span, WarningCode::UnusedVariable, format!("variable '{}' is never used", name));
+        }
+
+        let mut function = FunctionType::Declared(DeclaredFunction::new(None, None, parameters, statements, return_type, context.max_variables_count()));
+        function.set_statements_spans(mem::take(&mut self.statements_spans));
+
+        let chunk_id = self.functions.len() as u16;
+        self.functions.push(function);
+
+        let (program, mapper, warnings) = self.finish();
+        Ok(SessionChunk {
+            program,
+            mapper,
+            warnings,
+            chunk_id,
+            variables,
+            value_type
+        })
+    }
+
+    // Token starting a top level declaration
+    fn is_declaration(token: &Token<'a>) -> bool {
+        matches!(token, Token::Import | Token::Const | Token::Function | Token::Entry | Token::Impl | Token::Interface | Token::Struct | Token::Enum | Token::Hash)
+    }
+
+    // Read all the top level declarations
+    fn read_declarations(&mut self) -> Result<(), ParserError<'a>> {
         let mut context: Context = Context::new();
         while let Some(token) = self.next() {
-            match token {
-                Token::Import => {
-                    self.read_import()?;
-                    continue;
-                }
-                Token::Const => self.read_const(&mut context)?,
-                Token::Function => self.read_function(false, &mut context)?,
-                Token::Entry => self.read_function(true, &mut context)?,
-                Token::Struct => self.read_struct()?,
-                Token::Enum => self.read_enum()?,
-                token => return Err(err!(self, ParserErrorKind::UnexpectedToken(token)))
-            };
+            match self.read_declaration(token, &mut context) {
+                Ok(()) => {},
+                Err(e) if self.recover => {
+                    self.errors.push(e);
+                    // The state of the broken declaration is dropped
+                    context = Context::new();
+                    self.self_type = None;
+                    self.invariants.clear();
+                    self.return_type = None;
+                    self.skip_declaration();
+                },
+                Err(e) => return Err(e)
+            }
+        }
+
+        Ok(())
+    }
+
+    fn read_declaration(&mut self, token: Token<'a>, context: &mut Context<'a>) -> Result<(), ParserError<'a>> {
+        match token {
+            Token::Import => self.read_import(),
+            Token::Const => self.read_const(context),
+            Token::Function => self.read_function(FunctionKind::Declared, None, context),
+            Token::Entry => self.read_function(FunctionKind::Entry, None, context),
+            // `test` and `pub` are only qualifiers before `fn` so they stay usable as names
+            Token::Identifier("test") if self.peek_is(Token::Function) => {
+                self.advance()?;
+                self.read_function(FunctionKind::Test, None, context)
+            },
+            // `pub` exports the function by its name in the module
+            Token::Identifier("pub") if self.peek_is(Token::Function) => {
+                self.advance()?;
+                self.read_function(FunctionKind::Public, None, context)
+            },
+            Token::Hash => self.read_attribute(),
+            Token::Impl => self.read_impl(context),
+            Token::Interface => self.read_interface(),
+            Token::Struct => self.read_struct(),
+            Token::Enum => self.read_enum(),
+            token => Err(err!(self, ParserErrorKind::UnexpectedToken(token)))
         }
+    }
 
-        let program = Program::with(self.constants.into_iter().map(|(_, v)| v).collect(), self.global_mapper.structs().finalize(), self.global_mapper.enums().finalize(), self.functions);
-        Ok((program, self.global_mapper))
+    // Build the program from everything read
+    fn finish(mut self) -> (Program, GlobalMapper<'a>, Vec<Warning>) {
+        let mut program = Program::with(self.constants.into_values().collect(), self.global_mapper.structs().finalize(), self.global_mapper.enums().finalize(), self.functions);
+        for (name, data) in self.events {
+            program.add_event(name, data);
+        }
+        self.warnings.sort_by_key(|w| (w.span.line, w.span.column_start));
+        (program, self.global_mapper, self.warnings)
     }
 }
 
@@ -2000,7 +3558,48 @@ mod tests {
                         Value::Range(
                             Box::new(Value::U64(0)),
                             Box::new(Value::U64(10)),
-                            Type::U64
+                            Type::U64,
+                            false
+                        ).into()
+                    )
+                }
+            )
+        );
+    }
+
+    #[test]
+    fn test_range_inclusive() {
+        let tokens = vec![
+            Token::Let,
+            Token::Identifier("a"),
+            Token::Colon,
+
+            Token::Range,
+            Token::OperatorLessThan,
+            Token::Number(NumberType::U8),
+            Token::OperatorGreaterThan,
+            Token::OperatorAssign,
+
+            Token::Value(Literal::U8(0)),
+            Token::Dot,
+            Token::Dot,
+            Token::OperatorAssign,
+            Token::Value(Literal::U8(255)),
+        ];
+
+        let statements = test_parser_statement(tokens, Vec::new());
+        assert_eq!(
+            statements[0],
+            Statement::Variable(
+                DeclarationStatement {
+                    id: 0,
+                    value_type: Type::Range(Box::new(Type::U8)),
+                    value: Expression::Constant(
+                        Value::Range(
+                            Box::new(Value::U8(0)),
+                            Box::new(Value::U8(255)),
+                            Type::U8,
+                            true
                         ).into()
                     )
                 }
@@ -2064,7 +3663,7 @@ mod tests {
                 DeclarationStatement {
                     id: 0,
                     value_type: Type::Map(Box::new(Type::U64), Box::new(Type::String)),
-                    value: Expression::Constant(Constant::Map(HashMap::new()))
+                    value: Expression::Constant(Constant::Map(IndexMap::new()))
                 }
             )
         );
@@ -2093,7 +3692,7 @@ mod tests {
 
         let statements = test_parser_statement(tokens, Vec::new());
 
-        let mut map = HashMap::new();
+        let mut map = IndexMap::new();
         map.insert(Value::U64(0).into(), Value::String("hello".to_owned()).into());
 
         assert_eq!(
@@ -2170,7 +3769,7 @@ mod tests {
                 DeclarationStatement {
                     id: 0,
                     value_type: Type::Map(Box::new(Type::U64), Box::new(Type::Map(Box::new(Type::U64), Box::new(Type::String)))),
-                    value: Expression::Constant(Constant::Map(HashMap::new()))
+                    value: Expression::Constant(Constant::Map(IndexMap::new()))
                 }
             )
         );
@@ -2200,7 +3799,8 @@ mod tests {
                     Value::Range(
                         Box::new(Value::U64(0)),
                         Box::new(Value::U64(10)),
-                        Type::U64
+                        Type::U64,
+                        false
                     ).into()
                 ),
                 Vec::new()
@@ -2691,6 +4291,24 @@ mod tests {
         assert_eq!(statements.len(), 1);
     }
 
+    #[test]
+    fn test_ternary_identifier_before_colon() {
+        // A single `:` after an identifier is not a path separator
+        // i < 10 ? i : 0
+        let tokens = vec![
+            Token::Identifier("i"),
+            Token::OperatorLessThan,
+            Token::Value(Literal::U64(10)),
+            Token::OperatorTernary,
+            Token::Identifier("i"),
+            Token::Colon,
+            Token::Value(Literal::U64(0)),
+        ];
+
+        let statements = test_parser_statement(tokens, vec![("i", Type::U64)]);
+        assert_eq!(statements.len(), 1);
+    }
+
     #[test]
     fn test_struct() {
         // Message { message_id: u64, message: string }
@@ -2773,6 +4391,58 @@ mod tests {
         assert!(parser.read_statements(&mut context, &None).is_err());
     }
 
+    #[test]
+    fn test_opaque_type() {
+        fn new(_: xelis_environment::FnInstance, _: xelis_environment::FnParams, _: &mut xelis_environment::Context) -> xelis_environment::FnReturnType {
+            Ok(Some(Value::Null.into()))
+        }
+
+        let build_env = || {
+            let mut env = EnvironmentBuilder::new();
+            let _type = Type::Opaque(env.register_opaque("Handle"));
+            env.register_static_function("new", _type.clone(), Vec::new(), new, 1, Some(_type));
+            env
+        };
+
+        // let h: Handle = Handle::new();
+        let tokens = vec![
+            Token::Let,
+            Token::Identifier("h"),
+            Token::Colon,
+            Token::Identifier("Handle"),
+            Token::OperatorAssign,
+            Token::Identifier("Handle"),
+            Token::Colon,
+            Token::Colon,
+            Token::Identifier("new"),
+            Token::ParenthesisOpen,
+            Token::ParenthesisClose,
+        ];
+
+        let statements = test_parser_statement_with(tokens, Vec::new(), &None, build_env());
+        assert_eq!(statements.len(), 1);
+
+        // Its values can't be built by the scripts
+        // let h: Handle = Handle {};
+        let tokens = vec![
+            Token::Let,
+            Token::Identifier("h"),
+            Token::Colon,
+            Token::Identifier("Handle"),
+            Token::OperatorAssign,
+            Token::Identifier("Handle"),
+            Token::BraceOpen,
+            Token::BraceClose
+        ];
+
+        let env = build_env();
+        let mut parser = Parser::new(VecDeque::from(tokens), &env);
+        let mut context = Context::new();
+        context.begin_scope();
+
+        assert!(parser.read_statements(&mut context, &None).is_err());
+    }
+
     #[test]
     fn test_type_constant() {
         // let test: u64 = u64::MAX;
@@ -2794,6 +4464,70 @@ mod tests {
         assert_eq!(statements.len(), 1);
     }
 
+    #[test]
+    fn test_static_function() {
+        fn zero(_: xelis_environment::FnInstance, _: xelis_environment::FnParams, _: &mut xelis_environment::Context) -> xelis_environment::FnReturnType {
+            Ok(Some(Value::U64(0).into()))
+        }
+
+        let build_env = || {
+            let mut env = EnvironmentBuilder::new();
+            env.register_static_function("zero", Type::U64, Vec::new(), zero, 1, Some(Type::U64));
+            env
+        };
+
+        // let test: u64 = u64::zero();
+        let tokens = vec![
+            Token::Let,
+            Token::Identifier("test"),
+            Token::Colon,
+            Token::Number(NumberType::U64),
+            Token::OperatorAssign,
+            Token::Number(NumberType::U64),
+            Token::Colon,
+            Token::Colon,
+            Token::Identifier("zero"),
+            Token::ParenthesisOpen,
+            Token::ParenthesisClose,
+        ];
+
+        let statements = test_parser_statement_with(tokens, Vec::new(), &None, build_env());
+        assert_eq!(
+            statements,
+            vec![
+                Statement::Variable(DeclarationStatement {
+                    id: 0,
+                    value_type: Type::U64,
+                    value: Expression::FunctionCall(None, 0, Vec::new())
+                })
+            ]
+        );
+
+        // A static function cannot be called on an instance
+        // let test: u64 = a.zero();
+        let tokens = vec![
+            Token::Let,
+            Token::Identifier("test"),
+            Token::Colon,
+            Token::Number(NumberType::U64),
+            Token::OperatorAssign,
+            Token::Identifier("a"),
+            Token::Dot,
+            Token::Identifier("zero"),
+            Token::ParenthesisOpen,
+            Token::ParenthesisClose,
+        ];
+
+        let env = build_env();
+        let mut parser = Parser::new(VecDeque::from(tokens), &env);
+        let mut context = Context::new();
+        context.begin_scope();
+        context.register_variable("a", Type::U64).unwrap();
+
+        let err = parser.read_statements(&mut context, &None).unwrap_err();
+        assert!(matches!(err.kind, ParserErrorKind::FunctionIsStatic));
+    }
+
     #[test]
     fn test_enum() {
         // enum Message { HELLO, WORLD { a: u64 } }
@@ -2895,4 +4629,53 @@ mod tests {
         let statements = test_parser_statement_with(tokens, Vec::new(), &None, env);
         assert_eq!(statements.len(), 1);
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_namespace() {
+        let mut env = EnvironmentBuilder::new();
+        env.namespace("Math", |ns| {
+            ns.register_native_function("zero", Vec::new(), |_, _, _| Ok(Some(Value::U64(0).into())), 1, Some(Type::U64));
+        });
+
+        // let test: u64 = Math::zero();
+        let tokens = vec![
+            Token::Let,
+            Token::Identifier("test"),
+            Token::Colon,
+            Token::Number(NumberType::U64),
+            Token::OperatorAssign,
+            Token::Identifier("Math"),
+            Token::Colon,
+            Token::Colon,
+            Token::Identifier("zero"),
+            Token::ParenthesisOpen,
+            Token::ParenthesisClose,
+        ];
+
+        let statements = test_parser_statement_with(tokens, Vec::new(), &None, env);
+        assert_eq!(
+            statements,
+            vec![
+                Statement::Variable(DeclarationStatement {
+                    id: 0,
+                    value_type: Type::U64,
+                    value: Expression::FunctionCall(None, 0, Vec::new())
+                })
+            ]
+        );
+
+        // A struct can't use the name of a namespace
+        // struct Math {}
+        let tokens = vec![
+            Token::Struct,
+            Token::Identifier("Math"),
+            Token::BraceOpen,
+            Token::BraceClose
+        ];
+
+        let mut env = EnvironmentBuilder::new();
+        env.namespace("Math", |_| {});
+        let parser = Parser::new(tokens, &env);
+        assert!(parser.parse().is_err());
+    }
+}
@@ -19,7 +19,8 @@ impl<'a> GlobalMapper<'a> {
         Self {
             functions_mapper: FunctionMapper::with_parent(environment.get_functions_mapper()),
             struct_manager: StructManager::with_parent(environment.get_struct_manager()),
-            enum_manager: EnumManager::with_parent(environment.get_enum_manager()),
+            // The module enums don't follow the environment ones to keep their ids
+            enum_manager: EnumManager::with_parent_starting_at(environment.get_enum_manager(), 0),
         }
     }
 
@@ -0,0 +1,104 @@
+mod session;
+
+use std::io::{self, BufRead, Write};
+use xelis_builder::EnvironmentBuilder;
+use xelis_lexer::{Lexer, LexerErrorKind};
+use xelis_ast::Token;
+use session::{Evaluation, Session};
+
+const HELP: &str = "Enter statements to execute them, or declarations to keep them
+The variables declared by the statements are kept for the next ones
+Commands:
+    :vars     list the variables
+    :gas      show the gas used by each input
+    :reset    forget all the declarations and variables
+    :help     show this message
+    :quit     exit";
+
+// Interactive session over stdin with the natives of the standard environment
+fn main() -> io::Result<()> {
+    let environment = EnvironmentBuilder::default();
+    let mut session = Session::new(&environment);
+    let mut show_gas = false;
+
+    let stdin = io::stdin();
+    let mut lines = stdin.lock().lines();
+    let mut stdout = io::stdout();
+    let mut input = String::new();
+    loop {
+        write!(stdout, "{}", if input.is_empty() { ">> " } else { ".. " })?;
+        stdout.flush()?;
+
+        let Some(line) = lines.next().transpose()? else {
+            break
+        };
+
+        if input.is_empty() {
+            match line.trim() {
+                "" => continue,
+                ":quit" | ":exit" => break,
+                ":help" => {
+                    println!("{}", HELP);
+                    continue
+                },
+                ":gas" => {
+                    show_gas = !show_gas;
+                    println!("gas usage {}", if show_gas { "shown" } else { "hidden" });
+                    continue
+                },
+                ":reset" => {
+                    session.reset();
+                    continue
+                },
+                ":vars" => {
+                    for variable in session.variables() {
+                        println!("{}: {} = {}", variable.name, variable.value_type, variable.value);
+                    }
+                    continue
+                },
+                _ => {}
+            }
+        }
+
+        input.push_str(&line);
+        input.push('\n');
+        if !is_complete(&input) {
+            continue
+        }
+
+        match session.eval(&input) {
+            Ok(Evaluation::Declared) => {},
+            Ok(Evaluation::Executed { value, gas_usage }) => {
+                if let Some((value, value_type)) = value {
+                    println!("{}: {}", value, value_type);
+                }
+
+                if show_gas {
+                    println!("gas used: {}", gas_usage);
+                }
+            },
+            Err(e) => eprintln!("{}", e)
+        }
+        input.clear();
+    }
+
+    Ok(())
+}
+
+// An input is complete when its braces, parentheses and brackets are closed
+// and its strings and comments are terminated
+fn is_complete(input: &str) -> bool {
+    let mut depth = 0isize;
+    for token in Lexer::new(input) {
+        match token {
+            Ok(token) => match token.token {
+                Token::BraceOpen | Token::ParenthesisOpen | Token::BracketOpen => depth += 1,
+                Token::BraceClose | Token::ParenthesisClose | Token::BracketClose => depth -= 1,
+                _ => {}
+            },
+            Err(e) => return !matches!(e.kind, LexerErrorKind::EndOfFile)
+        }
+    }
+
+    depth <= 0
+}
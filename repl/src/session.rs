@@ -0,0 +1,229 @@
+use thiserror::Error;
+use xelis_builder::EnvironmentBuilder;
+use xelis_compiler::{Compiler, CompilerError};
+use xelis_lexer::{Lexer, LexerError};
+use xelis_parser::Parser;
+use xelis_types::{Constant, Type, ValueCell};
+use xelis_vm::{VMError, VM};
+
+// Keywords starting an input kept as a declaration
+const DECLARATIONS: [&str; 8] = ["import", "const", "fn", "entry", "impl", "interface", "struct", "enum"];
+
+#[derive(Debug, Error)]
+pub enum SessionError {
+    #[error(transparent)]
+    Lexer(#[from] LexerError),
+    // rendered with its source line as the parser error borrows the input
+    #[error("{0}")]
+    Parser(String),
+    #[error(transparent)]
+    Compiler(#[from] CompilerError),
+    // boxed as the VM errors are large
+    #[error(transparent)]
+    VM(Box<VMError>),
+    #[error("the statements returned before their end")]
+    NoValueReturned,
+}
+
+impl From<VMError> for SessionError {
+    fn from(error: VMError) -> Self {
+        Self::VM(Box::new(error))
+    }
+}
+
+// Result of an input
+#[derive(Debug)]
+pub enum Evaluation {
+    // declarations are kept for the next inputs
+    Declared,
+    // statements executed, with the value of the last one if any
+    Executed {
+        value: Option<(Constant, Type)>,
+        gas_usage: u64,
+    },
+}
+
+// Variable kept between the inputs
+#[derive(Debug, Clone)]
+pub struct Variable {
+    pub name: String,
+    pub value_type: Type,
+    pub value: Constant,
+}
+
+// Interactive session
+// Each input is either declarations, kept in the source of the session,
+// or statements compiled with the declarations into a throwaway module and executed
+// The variables declared by the statements are given to the next ones
+pub struct Session<'a> {
+    environment: &'a EnvironmentBuilder<'a>,
+    // source code of all the declarations entered
+    declarations: String,
+    variables: Vec<Variable>,
+}
+
+impl<'a> Session<'a> {
+    pub fn new(environment: &'a EnvironmentBuilder<'a>) -> Self {
+        Self {
+            environment,
+            declarations: String::new(),
+            variables: Vec::new(),
+        }
+    }
+
+    // Variables available for the next statements
+    pub fn variables(&self) -> &[Variable] {
+        &self.variables
+    }
+
+    // Forget all the declarations and variables
+    pub fn reset(&mut self) {
+        self.declarations.clear();
+        self.variables.clear();
+    }
+
+    // Returns true if the input is a declaration
+    pub fn is_declaration(input: &str) -> bool {
+        let first = input.trim_start()
+            .split(|c: char| !c.is_alphanumeric())
+            .next()
+            .unwrap_or_default();
+
+        DECLARATIONS.contains(&first)
+    }
+
+    // Evaluate an input, nothing is kept if it fails
+    pub fn eval(&mut self, input: &str) -> Result<Evaluation, SessionError> {
+        // the input is placed on its own lines after the declarations
+        let source = format!("{}\n{}\n", self.declarations, input);
+        let tokens = Lexer::new(&source).collect::<Result<Vec<_>, _>>()?;
+        let parser = Parser::with(tokens.into_iter(), self.environment);
+
+        if Self::is_declaration(input) {
+            parser.parse()
+                .map_err(|e| SessionError::Parser(e.with_source(&source).to_string()))?;
+
+            self.declarations.push_str(input);
+            self.declarations.push('\n');
+            return Ok(Evaluation::Declared)
+        }
+
+        let variables = self.variables.iter()
+            .map(|v| (v.name.as_str(), v.value_type.clone()))
+            .collect();
+        let chunk = parser.parse_session(variables)
+            .map_err(|e| SessionError::Parser(e.with_source(&source).to_string()))?;
+
+        let environment = self.environment.environment();
+        let module = Compiler::new(&chunk.program, environment).compile()?;
+        let mut vm = VM::new(&module, environment);
+        // the first parameter is read from the top of the stack
+        let args = self.variables.iter().rev().map(|v| ValueCell::from(v.value.clone()));
+        vm.invoke_chunk_with_args(chunk.chunk_id, args)?;
+
+        let returns_value = !chunk.variables.is_empty() || chunk.value_type.is_some();
+        // A revert discards the input, the variables are kept
+        let result = vm.run()?
+            .into_result()
+            .map_err(VMError::Reverted)?;
+        let gas_usage = vm.context().current_gas_usage();
+        if !returns_value {
+            return Ok(Evaluation::Executed { value: None, gas_usage })
+        }
+
+        let Constant::Array(mut values) = result else {
+            return Err(SessionError::NoValueReturned)
+        };
+
+        let value = chunk.value_type.and_then(|value_type| values.pop().map(|value| (value, value_type)));
+        self.variables = chunk.variables.into_iter()
+            .zip(values)
+            .map(|((name, value_type), value)| Variable {
+                name: name.to_owned(),
+                value_type,
+                value
+            })
+            .collect();
+
+        Ok(Evaluation::Executed { value, gas_usage })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use xelis_types::Value;
+    use super::*;
+
+    #[track_caller]
+    fn eval_value(session: &mut Session, input: &str) -> (Constant, Type) {
+        match session.eval(input) {
+            Ok(Evaluation::Executed { value: Some(value), .. }) => value,
+            res => panic!("expected a value for `{}`, got {:?}", input, res)
+        }
+    }
+
+    #[test]
+    fn test_expression() {
+        let environment = EnvironmentBuilder::default();
+        let mut session = Session::new(&environment);
+        let (value, value_type) = eval_value(&mut session, "10 + 5");
+        assert_eq!(value, Constant::Default(Value::U64(15)));
+        assert_eq!(value_type, Type::U64);
+        assert!(session.variables().is_empty());
+    }
+
+    #[test]
+    fn test_variables_are_kept() {
+        let environment = EnvironmentBuilder::default();
+        let mut session = Session::new(&environment);
+        assert!(matches!(session.eval("let a: u64 = 10").unwrap(), Evaluation::Executed { value: None, .. }));
+        assert!(matches!(session.eval("a += 5").unwrap(), Evaluation::Executed { value: None, .. }));
+        session.eval("let values: u64[] = [a, a * 2]").unwrap();
+
+        let (value, _) = eval_value(&mut session, "values[1] + a");
+        assert_eq!(value, Constant::Default(Value::U64(45)));
+
+        // shadowing keeps the latest declaration only
+        session.eval("let a: string = \"text\"").unwrap();
+        let names: Vec<_> = session.variables().iter().map(|v| (v.name.as_str(), v.value_type.clone())).collect();
+        assert_eq!(names, vec![("values", Type::Array(Box::new(Type::U64))), ("a", Type::String)]);
+    }
+
+    #[test]
+    fn test_declarations_are_kept() {
+        let environment = EnvironmentBuilder::default();
+        let mut session = Session::new(&environment);
+        assert!(matches!(session.eval("struct Point { x: u64, y: u64 }").unwrap(), Evaluation::Declared));
+        assert!(matches!(session.eval("fn (p Point) sum() -> u64 {\n    return p.x + p.y\n}").unwrap(), Evaluation::Declared));
+        assert!(matches!(session.eval("const BASE: u64 = 100").unwrap(), Evaluation::Declared));
+
+        session.eval("let p: Point = Point { x: 1, y: 2 }").unwrap();
+        let (value, _) = eval_value(&mut session, "p.sum() + BASE");
+        assert_eq!(value, Constant::Default(Value::U64(103)));
+    }
+
+    #[test]
+    fn test_failed_input_is_dropped() {
+        let environment = EnvironmentBuilder::default();
+        let mut session = Session::new(&environment);
+        session.eval("let a: u64 = 1").unwrap();
+
+        assert!(matches!(session.eval("let b: u64 = c"), Err(SessionError::Parser(_))));
+        assert!(matches!(session.eval("fn broken( {"), Err(SessionError::Parser(_))));
+        assert!(matches!(session.eval("let b: u64 = a / 0"), Err(SessionError::VM(_))));
+
+        // nothing was kept from the failed inputs
+        let names: Vec<_> = session.variables().iter().map(|v| v.name.as_str()).collect();
+        assert_eq!(names, vec!["a"]);
+        let (value, _) = eval_value(&mut session, "a");
+        assert_eq!(value, Constant::Default(Value::U64(1)));
+    }
+
+    #[test]
+    fn test_is_declaration() {
+        assert!(Session::is_declaration("  fn foo() {}"));
+        assert!(Session::is_declaration("struct A { a: u64 }"));
+        assert!(!Session::is_declaration("let constant: u64 = 1"));
+        assert!(!Session::is_declaration("function()"));
+    }
+}
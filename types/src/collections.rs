@@ -0,0 +1,66 @@
+// Collections used by the values and the crates of the VM
+// With the `std` feature, the std hash maps and their random state are used
+// Otherwise, no random source is available: hashbrown is used with a fixed hasher
+use core::hash::{BuildHasherDefault, Hasher};
+
+#[cfg(feature = "std")]
+pub use std::collections::{HashMap, HashSet};
+// Maps values are ordered by insertion to keep executions deterministic
+#[cfg(feature = "std")]
+pub use indexmap::{IndexMap, IndexSet};
+
+#[cfg(not(feature = "std"))]
+pub type HashMap<K, V, S = FixedState> = hashbrown::HashMap<K, V, S>;
+#[cfg(not(feature = "std"))]
+pub type HashSet<K, S = FixedState> = hashbrown::HashSet<K, S>;
+#[cfg(not(feature = "std"))]
+pub type IndexMap<K, V, S = FixedState> = indexmap::IndexMap<K, V, S>;
+#[cfg(not(feature = "std"))]
+pub type IndexSet<K, S = FixedState> = indexmap::IndexSet<K, S>;
+
+// Builder of the hasher used without the `std` feature
+pub type FixedState = BuildHasherDefault<FixedHasher>;
+
+// FNV-1a hasher, it has no random state so the hashes are the same across executions
+#[derive(Debug)]
+pub struct FixedHasher(u64);
+
+impl Default for FixedHasher {
+    fn default() -> Self {
+        Self(0xcbf2_9ce4_8422_2325)
+    }
+}
+
+impl Hasher for FixedHasher {
+    fn write(&mut self, bytes: &[u8]) {
+        for byte in bytes {
+            self.0 ^= *byte as u64;
+            self.0 = self.0.wrapping_mul(0x0100_0000_01b3);
+        }
+    }
+
+    fn finish(&self) -> u64 {
+        self.0
+    }
+}
+
+// Hasher that does nothing
+// Because we have u16 as the key, we don't need to hash it
+#[derive(Debug, Default)]
+pub struct NoOpHasher(u16);
+
+impl Hasher for NoOpHasher {
+    fn write(&mut self, bytes: &[u8]) {
+        self.0 = u16::from_le_bytes([bytes[0], bytes[1]]);
+    }
+
+    fn write_u64(&mut self, _: u64) {
+        unimplemented!("write_u64")
+    }
+
+    fn finish(&self) -> u64 {
+        self.0 as u64
+    }
+}
+
+pub type NoHashMap<V> = HashMap<crate::IdentifierType, V, BuildHasherDefault<NoOpHasher>>;
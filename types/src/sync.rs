@@ -0,0 +1,82 @@
+// Shared ownership and inner mutability used by the values
+// By default, Rc and RefCell are used as a VM and its values live on a single thread
+// With the `sync` feature, they are replaced by Arc and a RwLock so the values
+// (and so the VM holding them) can be sent to another thread
+
+#[cfg(not(feature = "sync"))]
+pub use alloc::rc::Rc as Shared;
+#[cfg(not(feature = "sync"))]
+pub use core::cell::{RefCell as Lock, Ref, RefMut};
+
+#[cfg(feature = "sync")]
+pub use self::lock::*;
+
+#[cfg(feature = "sync")]
+mod lock {
+    use core::fmt;
+
+    pub use alloc::sync::Arc as Shared;
+    pub use parking_lot::{
+        RwLockReadGuard as Ref,
+        RwLockWriteGuard as RefMut
+    };
+
+    // RwLock with the same API and behavior as a RefCell
+    // The values of a VM are only accessed by the thread running it,
+    // so a conflicting borrow is a bug and panics instead of waiting
+    #[derive(Default)]
+    pub struct Lock<T>(parking_lot::RwLock<T>);
+
+    impl<T> Lock<T> {
+        #[inline(always)]
+        pub fn new(value: T) -> Self {
+            Self(parking_lot::RwLock::new(value))
+        }
+
+        #[inline(always)]
+        pub fn borrow(&self) -> Ref<'_, T> {
+            self.0.try_read()
+                .expect("already mutably borrowed")
+        }
+
+        #[inline(always)]
+        pub fn borrow_mut(&self) -> RefMut<'_, T> {
+            self.0.try_write()
+                .expect("already borrowed")
+        }
+
+        #[inline(always)]
+        pub fn into_inner(self) -> T {
+            self.0.into_inner()
+        }
+
+        #[inline(always)]
+        pub fn as_ptr(&self) -> *mut T {
+            self.0.data_ptr()
+        }
+
+        #[inline(always)]
+        pub fn take(&self) -> T
+        where
+            T: Default
+        {
+            core::mem::take(&mut *self.borrow_mut())
+        }
+    }
+
+    impl<T: fmt::Debug> fmt::Debug for Lock<T> {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            f.debug_tuple("Lock")
+                .field(&*self.borrow())
+                .finish()
+        }
+    }
+
+    impl<T: PartialEq> PartialEq for Lock<T> {
+        fn eq(&self, other: &Self) -> bool {
+            *self.borrow() == *other.borrow()
+        }
+    }
+
+    impl<T: Eq> Eq for Lock<T> {}
+}
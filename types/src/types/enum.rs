@@ -1,11 +1,17 @@
-use std::{hash::{Hash, Hasher}, sync::Arc};
+use core::hash::{Hash, Hasher};
+use alloc::{sync::Arc, vec::Vec};
 
 use crate::IdentifierType;
 use super::Type;
 
+// The enums of the environment have their own ids, starting at this one
+// Registering a new one doesn't move the ids of the module enums
+pub const ENVIRONMENT_ENUM_ID_START: IdentifierType = 0x8000;
+
 // Represents a variant of an enum
 // This is similar to a struct
 #[derive(Clone, Hash, PartialEq, Eq, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct EnumVariant(Vec<Type>);
 
 impl EnumVariant {
@@ -23,6 +29,7 @@ impl EnumVariant {
 // Represents an enum like in Rust with variants
 // Support up to 255 variants
 #[derive(Clone, Eq, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Enum {
     id: IdentifierType,
     variants: Vec<EnumVariant>,
@@ -42,11 +49,13 @@ impl PartialEq for Enum {
 
 // Selected enum variant with associated type
 #[derive(Clone, Hash, PartialEq, Eq, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct EnumType(Arc<Enum>);
 
 // Represents the type of an enum variant
 // This is embed in the value to determine easily which variant it is
 #[derive(Clone, Hash, PartialEq, Eq, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct EnumValueType {
     enum_type: EnumType,
     variant_id: u8
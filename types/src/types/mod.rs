@@ -1,18 +1,21 @@
 mod r#struct;
 mod r#enum;
+mod opaque;
 
 pub use r#struct::*;
 pub use r#enum::*;
+pub use opaque::*;
 
-use crate::{values::Value, Constant};
-use std::{
-    collections::{HashMap, HashSet},
+use crate::{values::Value, Constant, HashMap, HashSet};
+use alloc::{boxed::Box, vec, vec::Vec};
+use core::{
     fmt,
     hash::{BuildHasher, Hash},
 };
 
 
 #[derive(Clone, Hash, PartialEq, Eq, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum Type {
     // Any Type is accepted
     Any,
@@ -35,9 +38,18 @@ pub enum Type {
     Optional(Box<Type>),
     Range(Box<Type>),
     Map(Box<Type>, Box<Type>),
+    // Fixed size list of values, each with its own type
+    // Stored as an array at runtime
+    Tuple(Vec<Type>),
+    // Value or error, stored as the built-in `Result` enum at runtime
+    Result(Box<Type>, Box<Type>),
+    // Lazy iterator over values of the inner type
+    Iterator(Box<Type>),
 
     Struct(StructType),
     Enum(EnumType),
+    // Type of the environment only used through its natives
+    Opaque(OpaqueType),
 }
 
 impl Type {
@@ -74,7 +86,7 @@ impl Type {
     // check if the type has an inner type
     pub fn has_inner_type(&self) -> bool {
         match self {
-            Type::Array(_) | Type::Optional(_) | Type::Range(_) | Type::Map(_, _) => true,
+            Type::Array(_) | Type::Optional(_) | Type::Range(_) | Type::Iterator(_) | Type::Map(_, _) => true,
             _ => false
         }
     }
@@ -97,7 +109,7 @@ impl Type {
             Value::String(_) => Type::String,
             Value::Boolean(_) => Type::Bool,
             Value::Blob(_type) => Type::Blob,
-            Value::Range(_, _, _type) => Type::Range(Box::new(_type.clone())),
+            Value::Range(_, _, _type, _) => Type::Range(Box::new(_type.clone())),
         })
     }
 
@@ -124,6 +136,7 @@ impl Type {
             Type::Array(ref _type) => _type,
             Type::Optional(ref _type) => _type,
             Type::Range(ref _type) => _type,
+            Type::Iterator(ref _type) => _type,
             _ => &self
         }
     }
@@ -133,13 +146,16 @@ impl Type {
         match id {
             0 => match &self {
                 Type::Map(key, _) => Some(key.as_ref()),
+                Type::Result(value, _) => Some(value.as_ref()),
                 Type::Array(inner) => Some(inner.as_ref()),
                 Type::Optional(inner) => Some(inner.as_ref()),
                 Type::Range(inner) => Some(inner.as_ref()),
+                Type::Iterator(inner) => Some(inner.as_ref()),
                 _ => None
             },
             1 => match &self {
                 Type::Map(_, value) => Some(value.as_ref()),
+                Type::Result(_, error) => Some(error.as_ref()),
                 _ => None
             }
             _ => None
@@ -165,7 +181,7 @@ impl Type {
     // check if the type contains a sub type
     pub fn contains_sub_type(&self) -> bool {
         match self {
-            Type::Array(_) | Type::Optional(_) | Type::Range(_) | Type::Map(_, _) => true,
+            Type::Array(_) | Type::Optional(_) | Type::Range(_) | Type::Iterator(_) | Type::Map(_, _) => true,
             _ => false
         }
     }
@@ -194,9 +210,15 @@ impl Type {
                 Type::Any => true,
                 _ => false
             },
+            Type::Iterator(inner) => match self {
+                Type::Iterator(inner2) => inner2.is_compatible_with(inner),
+                Type::Any => true,
+                _ => false
+            },
             Type::Enum(e) => match self {
                 Type::Enum(e2) => e == e2,
-                Type::Any => true,
+                // An enum can be wrapped in a generic type, like an optional
+                Type::Any | Type::T(_) => true,
                 _ => false
             },
             Type::Any | Type::T(_) => true,
@@ -215,6 +237,17 @@ impl Type {
                 Type::Any => true,
                 _ => false
             },
+            Type::Tuple(types) => match self {
+                Type::Tuple(types2) => types.len() == types2.len() && types2.iter().zip(types).all(|(a, b)| a.is_compatible_with(b)),
+                // A tuple can be wrapped in a generic type, like an optional
+                Type::Any | Type::T(_) => true,
+                _ => false
+            },
+            Type::Result(value, error) => match self {
+                Type::Result(value2, error2) => value2.is_compatible_with(value) && error2.is_compatible_with(error),
+                Type::Any => true,
+                _ => false
+            },
             o => *o == *self || self.is_generic(),
         }
     }
@@ -286,10 +319,16 @@ impl Type {
     }
 
     pub fn is_iterable(&self) -> bool {
+        self.get_iterated_type().is_some()
+    }
+
+    // Type of the values given by a foreach
+    // A map gives a tuple of each key and its value
+    pub fn get_iterated_type(&self) -> Option<Type> {
         match self {
-            Type::Array(_) => true,
-            Type::Range(_) => true,
-            _ => false
+            Type::Array(inner) | Type::Range(inner) | Type::Iterator(inner) => Some(inner.as_ref().clone()),
+            Type::Map(key, value) => Some(Type::Tuple(vec![key.as_ref().clone(), value.as_ref().clone()])),
+            _ => None
         }
     }
 
@@ -320,6 +359,14 @@ impl Type {
             _ => false
         }
     }
+
+    pub fn is_tuple(&self) -> bool {
+        matches!(self, Type::Tuple(_))
+    }
+
+    pub fn is_result(&self) -> bool {
+        matches!(self, Type::Result(_, _))
+    }
 }
 
 impl fmt::Display for Type {
@@ -340,8 +387,21 @@ impl fmt::Display for Type {
             Type::Array(_type) => write!(f, "{}[]", _type),
             Type::Optional(_type) => write!(f, "optional<{}>", _type),
             Type::Range(_type) => write!(f, "range<{}>", _type),
+            Type::Iterator(_type) => write!(f, "Iterator<{}>", _type),
             Type::Map(key, value) => write!(f, "map<{}, {}>", key, value),
+            Type::Result(value, error) => write!(f, "Result<{}, {}>", value, error),
+            Type::Tuple(types) => {
+                write!(f, "(")?;
+                for (i, _type) in types.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ", ")?;
+                    }
+                    write!(f, "{}", _type)?;
+                }
+                write!(f, ")")
+            },
             Type::Enum(id) => write!(f, "enum({:?})", id),
+            Type::Opaque(opaque) => write!(f, "opaque({})", opaque.id()),
         }
     }
 }
@@ -0,0 +1,20 @@
+use crate::IdentifierType;
+
+// Represents a type of the environment whose values are only built and read by its natives
+// The scripts can't construct it or access its content, like a handle to the host data
+#[derive(Clone, Copy, Hash, PartialEq, Eq, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct OpaqueType(IdentifierType);
+
+impl OpaqueType {
+    // Create a new opaque type
+    pub fn new(id: IdentifierType) -> Self {
+        Self(id)
+    }
+
+    // Get the unique identifier of the opaque type
+    #[inline(always)]
+    pub fn id(&self) -> IdentifierType {
+        self.0
+    }
+}
@@ -1,9 +1,11 @@
-use std::{hash::{Hash, Hasher}, sync::Arc};
+use core::hash::{Hash, Hasher};
+use alloc::{sync::Arc, vec::Vec};
 use crate::IdentifierType;
 use super::Type;
 
 // Represents a struct in the language
 #[derive(Clone, Eq, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Struct {
     // Unique identifier for serialization
     id: IdentifierType,
@@ -24,6 +26,7 @@ impl PartialEq for Struct {
 }
 
 #[derive(Clone, Hash, PartialEq, Eq, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct StructType(Arc<Struct>);
 
 impl StructType {
@@ -43,4 +46,10 @@ impl StructType {
     pub fn fields(&self) -> &Vec<Type> {
         &self.0.fields
     }
+
+    /// Check if the values of the old version of this struct can be read with this one
+    /// The fields are compared by position: new fields can only be appended
+    pub fn is_layout_compatible(&self, old: &StructType) -> bool {
+        self.fields().starts_with(old.fields())
+    }
 }
\ No newline at end of file
@@ -1,5 +1,5 @@
 use core::fmt;
-use std::{
+use core::{
     cmp::Ordering,
     ops::{
         Add,
@@ -14,6 +14,7 @@ use std::{
         DivAssign,
         Mul,
         MulAssign,
+        Not,
         Rem,
         RemAssign,
         Shl,
@@ -25,8 +26,23 @@ use std::{
     },
     str::FromStr
 };
+use alloc::{string::String, vec::Vec};
+use thiserror::Error;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Error)]
+pub enum ParseU256Error {
+    #[error("empty string")]
+    Empty,
+    #[error("invalid digit '{0}'")]
+    InvalidDigit(char),
+    #[error("number too large for u256")]
+    Overflow,
+    #[error("invalid radix {0}, expected 2 to 36")]
+    InvalidRadix(u32),
+}
 
 #[derive(Debug, Clone, Copy, Hash, Eq, Ord)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct U256([u64; 4]);
 
 impl Default for U256 {
@@ -67,19 +83,53 @@ impl U256 {
     }
 
     /// Create a new U256 from a string and a radix.
-    pub fn from_str_radix(s: &str, radix: u32) -> Result<U256, ()> {
+    /// The radix must be in the range 2..=36, digits above 9 are case insensitive.
+    pub fn from_str_radix(s: &str, radix: u32) -> Result<U256, ParseU256Error> {
+        if !(2..=36).contains(&radix) {
+            return Err(ParseU256Error::InvalidRadix(radix));
+        }
+
+        if s.is_empty() {
+            return Err(ParseU256Error::Empty);
+        }
+
         let mut result = U256::ZERO;
         for c in s.chars() {
-            if let Some(digit) = c.to_digit(radix) {
-                result = result * U256::from(radix) + U256::from(digit as u64);
-            } else {
-                return Err(());
-            }
+            let digit = c.to_digit(radix)
+                .ok_or(ParseU256Error::InvalidDigit(c))?;
+
+            result = result.checked_mul(U256::from(radix))
+                .and_then(|v| v.checked_add(U256::from(digit)))
+                .ok_or(ParseU256Error::Overflow)?;
         }
 
         Ok(result)
     }
 
+    /// Format the number in a radix, in the range 2..=36.
+    /// Digits above 9 are written in lowercase.
+    pub fn to_str_radix(&self, radix: u32) -> Result<String, ParseU256Error> {
+        if !(2..=36).contains(&radix) {
+            return Err(ParseU256Error::InvalidRadix(radix));
+        }
+
+        if self.is_zero() {
+            return Ok("0".into());
+        }
+
+        let mut digits = Vec::new();
+        let mut temp = *self;
+
+        // Use repeated division by the radix to extract each digit
+        while !temp.is_zero() {
+            let (quotient, remainder) = temp.div_rem_u64(radix as u64);
+            digits.push(char::from_digit(remainder as u32, radix).unwrap());
+            temp = quotient;
+        }
+
+        Ok(digits.iter().rev().collect())
+    }
+
     /// Addition with overflow handling
     pub fn overflowing_add(self, other: U256) -> (U256, bool) {
         let mut result = [0u64; 4];
@@ -112,112 +162,196 @@ impl U256 {
 
     /// Multiplication with overflow handling
     pub fn overflowing_mul(self, other: U256) -> (U256, bool) {
-        let mut result = [0u64; 4];
-        let mut overflow = false;
+        let (low, high) = self.full_mul(other);
+        (low, !high.is_zero())
+    }
 
-        // We multiply each pair of 64-bit segments, handling carries appropriately.
+    /// Full width multiplication
+    /// Returns the low and high 256 bits of the 512 bits product
+    pub fn full_mul(self, other: U256) -> (U256, U256) {
+        let mut result = [0u64; 8];
+
+        // Schoolbook multiplication of each pair of 64-bit segments
         for i in 0..4 {
             let mut carry = 0u128;
-            for j in 0..(4 - i) {
-                let a = self.0[i] as u128;
-                let b = other.0[j] as u128;
-                let product = a * b + result[i + j] as u128 + carry;
+            for j in 0..4 {
+                let product = self.0[i] as u128 * other.0[j] as u128 + result[i + j] as u128 + carry;
                 result[i + j] = product as u64;
                 carry = product >> 64;
             }
-            // If there's any carry left and we're outside the 256-bit bounds, we have overflow.
-            if carry > 0 {
-                if i + 4 < 4 {
-                    result[i + 4] = carry as u64;
-                } else {
-                    overflow = true;
-                }
+            result[i + 4] = carry as u64;
+        }
+
+        (
+            U256([result[0], result[1], result[2], result[3]]),
+            U256([result[4], result[5], result[6], result[7]])
+        )
+    }
+
+    /// Compute `self * mul / div` without overflowing on the intermediate product
+    /// Returns None if the divisor is zero or the result doesn't fit in 256 bits
+    pub fn mul_div(self, mul: U256, div: U256) -> Option<U256> {
+        if div.is_zero() {
+            return None;
+        }
+
+        let (low, high) = self.full_mul(mul);
+        if high.is_zero() {
+            return low.checked_div(div);
+        }
+
+        // The quotient would need more than 256 bits
+        if high >= div {
+            return None;
+        }
+
+        // Long division of the 512 bits product, the remainder starts with the high part
+        let mut remainder = high;
+        let mut quotient = U256::ZERO;
+        for i in (0..256).rev() {
+            // The remainder is less than the divisor, so doubling it can only overflow by one bit
+            let carry = remainder.bit(255);
+            remainder <<= 1u32;
+            if low.bit(i) {
+                remainder.0[0] |= 1;
+            }
+
+            quotient <<= 1u32;
+            if carry || remainder >= div {
+                remainder = remainder.wrapping_sub(div);
+                quotient.0[0] |= 1;
             }
         }
 
-        (U256(result), overflow)
+        Some(quotient)
     }
 
-    /// Division with overflow handling
-    /// Panics if the divisor is zero
-    pub fn overflowing_div(self, divisor: U256) -> (U256, bool) {
-        assert!(!divisor.is_zero(), "U256 division by zero");
+    /// Check if the bit at the index is set
+    fn bit(&self, index: u32) -> bool {
+        (self.0[(index / 64) as usize] >> (index % 64)) & 1 == 1
+    }
 
-        // Check if self is less than the divisor; if so, the result is zero with no overflow
-        if self < divisor {
-            return (U256([0; 4]), false);
+    /// Number of leading zero bits
+    pub fn leading_zeros(&self) -> u32 {
+        for i in (0..4).rev() {
+            if self.0[i] != 0 {
+                return (3 - i as u32) * 64 + self.0[i].leading_zeros();
+            }
         }
+        256
+    }
 
-        // If the divisor is 1, the result is self, with no overflow
-        if divisor.is_one() {
-            return (self, false);
+    /// Base 2 logarithm rounded down
+    /// Returns None if the number is zero
+    pub fn checked_ilog2(&self) -> Option<u32> {
+        if self.is_zero() {
+            None
+        } else {
+            Some(255 - self.leading_zeros())
+        }
+    }
+
+    /// Integer square root rounded down
+    pub fn isqrt(self) -> U256 {
+        if self.is_zero() {
+            return U256::ZERO;
         }
 
-        // Initialize quotient and remainder
-        let mut quotient = U256([0; 4]);
-        let mut remainder = self;
+        // Newton's method, starting above the root
+        let bits = 256 - self.leading_zeros();
+        let mut x = U256::ONE << bits.div_ceil(2);
+        loop {
+            let y = (x + self / x) >> 1u32;
+            if y >= x {
+                return x;
+            }
+            x = y;
+        }
+    }
 
-        // We start by aligning the divisor with the most significant bit of `self`
-        let mut shift = 0;
-        let mut divisor_shifted = divisor;
-        while remainder >= divisor_shifted {
-            divisor_shifted = divisor_shifted << 1;
-            shift += 1;
+    /// Shift left, returns None if the shift is greater than or equal to 256
+    pub fn checked_shl(self, shift: u32) -> Option<U256> {
+        if shift < 256 {
+            Some(self << shift)
+        } else {
+            None
+        }
+    }
+
+    /// Shift right, returns None if the shift is greater than or equal to 256
+    pub fn checked_shr(self, shift: u32) -> Option<U256> {
+        if shift < 256 {
+            Some(self >> shift)
+        } else {
+            None
         }
+    }
 
-        // Perform the division bit by bit
-        while shift > 0 {
-            divisor_shifted = divisor_shifted >> 1;
-            shift -= 1;
+    /// Raises self to the power of exp using exponentiation by squaring
+    /// Returns None on overflow
+    pub fn checked_pow(self, mut exp: u32) -> Option<U256> {
+        let mut base = self;
+        let mut result = U256::ONE;
+        while exp > 0 {
+            if exp & 1 == 1 {
+                result = result.checked_mul(base)?;
+            }
 
-            // If the remainder is greater than or equal to divisor_shifted, subtract and update quotient
-            if remainder >= divisor_shifted {
-                remainder = remainder - divisor_shifted;
-                quotient = quotient | (U256::ONE << shift);
+            exp >>= 1;
+            if exp > 0 {
+                base = base.checked_mul(base)?;
             }
         }
 
-        (quotient, false)
+        Some(result)
+    }
+
+    /// Division with overflow handling
+    /// Panics if the divisor is zero
+    pub fn overflowing_div(self, divisor: U256) -> (U256, bool) {
+        (self.div_rem(divisor).0, false)
     }
 
     /// Remainder with overflow handling
     /// Panics if the divisor is zero
     pub fn overflowing_rem(self, divisor: U256) -> (U256, bool) {
+        (self.div_rem(divisor).1, false)
+    }
+
+    /// Quotient and remainder of the division
+    /// Panics if the divisor is zero
+    fn div_rem(self, divisor: U256) -> (U256, U256) {
         assert!(!divisor.is_zero(), "U256 division by zero");
 
-        // Check if self is less than the divisor; if so, the remainder is self with no overflow
+        // If self is less than the divisor, the quotient is zero
         if self < divisor {
-            return (self, false);
+            return (U256::ZERO, self);
         }
 
-        // If the divisor is 1, the remainder is zero with no overflow
+        // If the divisor is 1, the quotient is self
         if divisor.is_one() {
-            return (U256::ZERO, false);
+            return (self, U256::ZERO);
         }
 
-        // Initialize remainder
-        let mut remainder = self;
-
-        // We start by aligning the divisor with the most significant bit of `self`
-        let mut shift = 0;
-        let mut divisor_shifted = divisor;
-        while remainder >= divisor_shifted {
-            divisor_shifted = divisor_shifted << 1;
-            shift += 1;
-        }
-
-        // Perform the division bit by bit
-        while shift > 0 {
-            divisor_shifted = divisor_shifted >> 1;
-            shift -= 1;
+        // Long division bit by bit, starting from the most significant bit of `self`
+        // The remainder is always less than the divisor, but shifting it may overflow:
+        // the carry is the bit shifted out
+        let mut quotient = U256::ZERO;
+        let mut remainder = U256::ZERO;
+        for i in (0..256 - self.leading_zeros()).rev() {
+            let carry = remainder.bit(255);
+            remainder <<= 1u32;
+            if self.bit(i) {
+                remainder.0[0] |= 1;
+            }
 
-            // If the remainder is greater than or equal to divisor_shifted, subtract
-            if remainder >= divisor_shifted {
-                remainder = remainder - divisor_shifted;
+            if carry || remainder >= divisor {
+                remainder = remainder.wrapping_sub(divisor);
+                quotient.0[i as usize / 64] |= 1 << (i % 64);
             }
         }
 
-        (remainder, false)
+        (quotient, remainder)
     }
 
     /// Checked subtraction with overflow handling
@@ -281,6 +415,36 @@ impl U256 {
         }
     }
 
+    /// Saturating addition, returns MAX on overflow
+    pub fn saturating_add(self, other: U256) -> U256 {
+        self.checked_add(other).unwrap_or(U256::MAX)
+    }
+
+    /// Saturating subtraction, returns MIN on underflow
+    pub fn saturating_sub(self, other: U256) -> U256 {
+        self.checked_sub(other).unwrap_or(U256::MIN)
+    }
+
+    /// Saturating multiplication, returns MAX on overflow
+    pub fn saturating_mul(self, other: U256) -> U256 {
+        self.checked_mul(other).unwrap_or(U256::MAX)
+    }
+
+    /// Wrapping addition, wraps around at the boundary of the type
+    pub fn wrapping_add(self, other: U256) -> U256 {
+        self.overflowing_add(other).0
+    }
+
+    /// Wrapping subtraction, wraps around at the boundary of the type
+    pub fn wrapping_sub(self, other: U256) -> U256 {
+        self.overflowing_sub(other).0
+    }
+
+    /// Wrapping multiplication, wraps around at the boundary of the type
+    pub fn wrapping_mul(self, other: U256) -> U256 {
+        self.overflowing_mul(other).0
+    }
+
     /// Export the data as a big-endian byte array
     pub fn to_be_bytes(&self) -> [u8; 32] {
         let bytes = [
@@ -297,7 +461,8 @@ impl U256 {
     pub fn from_be_bytes(bytes: [u8; 32]) -> Self {
         let mut data = [0u64; 4];
         for i in 0..4 {
-            data[i] = u64::from_be_bytes(bytes[i * 8..(i + 1) * 8].try_into().unwrap());
+            // The most significant part comes first
+            data[3 - i] = u64::from_be_bytes(bytes[i * 8..(i + 1) * 8].try_into().unwrap());
         }
 
         U256(data)
@@ -368,7 +533,7 @@ impl U256 {
 }
 
 impl FromStr for U256 {
-    type Err = ();
+    type Err = ParseU256Error;
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
         U256::from_str_radix(s, 10)
@@ -482,7 +647,7 @@ impl Shl<u32> for U256 {
 
         for i in (word_shift..4).rev() {
             result[i] = self.0[i - word_shift] << bit_shift;
-            if bit_shift > 0 && i > 0 {
+            if bit_shift > 0 && i > word_shift {
                 result[i] |= self.0[i - word_shift - 1] >> (64 - bit_shift);
             }
         }
@@ -532,7 +697,7 @@ impl Shl for U256 {
         let word_shift = word_shift as usize;
         for i in (word_shift..4).rev() {
             result[i] = self.0[i - word_shift] << bit_shift;
-            if bit_shift > 0 && i > 0 {
+            if bit_shift > 0 && i > word_shift {
                 result[i] |= self.0[i - word_shift - 1] >> (64 - bit_shift);
             }
         }
@@ -577,6 +742,14 @@ impl BitAnd for U256 {
     }
 }
 
+impl Not for U256 {
+    type Output = Self;
+
+    fn not(self) -> Self {
+        U256(self.0.map(|v| !v))
+    }
+}
+
 impl AddAssign for U256 {
     fn add_assign(&mut self, rhs: Self) {
         *self = *self + rhs;
@@ -705,21 +878,10 @@ impl Into<u128> for U256 {
 
 impl fmt::Display for U256 {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        if self.is_zero() {
-            return write!(f, "0");
-        }
-
-        let mut result = String::new();
-        let mut temp = *self;
-
-        // Use repeated division by 10 to extract each decimal digit
-        while !temp.is_zero() {
-            let (quotient, remainder) = temp.div_rem_u64(10);
-            result.push(char::from_digit(remainder as u32, 10).unwrap());
-            temp = quotient;
-        }
+        let result = self.to_str_radix(10)
+            .map_err(|_| fmt::Error)?;
 
-        write!(f, "{}", result.chars().rev().collect::<String>())
+        write!(f, "{}", result)
     }
 }
 
@@ -735,6 +897,46 @@ mod tests {
         assert_eq!(U256::from_str_radix("ffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffff", 16).unwrap(), U256::MAX);
     }
 
+    #[test]
+    fn test_from_str_radix_digits() {
+        assert_eq!(U256::from_str_radix("FF", 16).unwrap(), U256::from(255u64));
+        assert_eq!(U256::from_str_radix("101", 2).unwrap(), U256::from(5u64));
+
+        assert_eq!(U256::from_str_radix("", 10), Err(ParseU256Error::Empty));
+        assert_eq!(U256::from_str_radix("12a", 10), Err(ParseU256Error::InvalidDigit('a')));
+        assert_eq!(U256::from_str_radix("-1", 10), Err(ParseU256Error::InvalidDigit('-')));
+        assert_eq!(U256::from_str_radix("1", 37), Err(ParseU256Error::InvalidRadix(37)));
+        assert_eq!(U256::from_str_radix("1", 1), Err(ParseU256Error::InvalidRadix(1)));
+        assert_eq!(U256::from_str_radix("115792089237316195423570985008687907853269984665640564039457584007913129639936", 10), Err(ParseU256Error::Overflow));
+        assert_eq!(U256::from_str_radix("1ffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffff", 16), Err(ParseU256Error::Overflow));
+    }
+
+    #[test]
+    fn test_to_str_radix() {
+        assert_eq!(U256::ZERO.to_str_radix(16).unwrap(), "0");
+        assert_eq!(U256::from(255u64).to_str_radix(16).unwrap(), "ff");
+        assert_eq!(U256::from(5u64).to_str_radix(2).unwrap(), "101");
+        assert_eq!(U256::MAX.to_str_radix(16).unwrap(), "f".repeat(64));
+        assert_eq!(U256::from(1234567890u64).to_str_radix(36).unwrap(), "kf12oi");
+        assert_eq!(U256::ONE.to_str_radix(0), Err(ParseU256Error::InvalidRadix(0)));
+
+        for radix in 2..=36 {
+            let value = U256::new(1, 2, 3, 4);
+            assert_eq!(U256::from_str_radix(&value.to_str_radix(radix).unwrap(), radix).unwrap(), value);
+        }
+    }
+
+    #[test]
+    fn test_bytes() {
+        let value = U256::new(1, 2, 3, 4);
+        assert_eq!(U256::from_be_bytes(value.to_be_bytes()), value);
+        assert_eq!(U256::from_le_bytes(value.to_le_bytes()), value);
+
+        let mut bytes = [0u8; 32];
+        bytes[31] = 1;
+        assert_eq!(U256::from_be_bytes(bytes), U256::ONE);
+    }
+
     #[test]
     fn test_string() {
         assert_eq!(U256::ZERO.to_string(), "0");
@@ -792,6 +994,79 @@ mod tests {
         assert_eq!(overflow, true);
     }
 
+    #[test]
+    fn test_not_and_checked_shift() {
+        assert_eq!(!U256::ZERO, U256::MAX);
+        assert_eq!(!U256::MAX, U256::ZERO);
+        assert_eq!(U256::ONE.checked_shl(255), Some(U256([0, 0, 0, 1 << 63])));
+        assert_eq!(U256::ONE.checked_shl(256), None);
+        assert_eq!(U256::MAX.checked_shr(255), Some(U256::ONE));
+        assert_eq!(U256::MAX.checked_shr(256), None);
+    }
+
+    #[test]
+    fn test_saturating_wrapping() {
+        let max = U256::MAX;
+        assert_eq!(max.saturating_add(U256::ONE), U256::MAX);
+        assert_eq!(U256::ZERO.saturating_sub(U256::ONE), U256::ZERO);
+        assert_eq!(max.saturating_mul(U256::from(2u64)), U256::MAX);
+        assert_eq!(U256::from(2u64).saturating_mul(U256::from(3u64)), U256::from(6u64));
+
+        assert_eq!(max.wrapping_add(U256::ONE), U256::ZERO);
+        assert_eq!(U256::ZERO.wrapping_sub(U256::ONE), U256::MAX);
+        assert_eq!(max.wrapping_mul(U256::from(2u64)), max - U256::ONE);
+    }
+
+    #[test]
+    fn test_mul_overflow_high_words() {
+        // 2^192 * 2^64 = 2^256
+        let (_, overflow) = U256([0, 0, 0, 1]).overflowing_mul(U256([0, 1, 0, 0]));
+        assert!(overflow);
+
+        let (low, high) = U256::MAX.full_mul(U256::MAX);
+        assert_eq!(low, U256::ONE);
+        assert_eq!(high, U256::MAX - U256::ONE);
+    }
+
+    #[test]
+    fn test_mul_div() {
+        let a = U256::from(u128::MAX);
+        assert_eq!(a.mul_div(a, a), Some(a));
+        assert_eq!(U256::from(10u64).mul_div(U256::from(3u64), U256::from(4u64)), Some(U256::from(7u64)));
+
+        // The intermediate product overflows but not the result
+        let half = U256::MAX >> 1u32;
+        assert_eq!(U256::MAX.mul_div(half, half), Some(U256::MAX));
+        assert_eq!(U256::MAX.mul_div(U256::MAX, U256::MAX), Some(U256::MAX));
+        assert_eq!(U256::MAX.mul_div(U256::from(3u64), U256::from(6u64)), Some(U256::MAX >> 1u32));
+
+        assert_eq!(U256::MAX.mul_div(U256::from(2u64), U256::ONE), None);
+        assert_eq!(U256::ONE.mul_div(U256::ONE, U256::ZERO), None);
+    }
+
+    #[test]
+    fn test_isqrt() {
+        assert_eq!(U256::ZERO.isqrt(), U256::ZERO);
+        assert_eq!(U256::ONE.isqrt(), U256::ONE);
+        assert_eq!(U256::from(15u64).isqrt(), U256::from(3u64));
+        assert_eq!(U256::from(16u64).isqrt(), U256::from(4u64));
+        assert_eq!(U256::MAX.isqrt(), U256::from(u128::MAX));
+    }
+
+    #[test]
+    fn test_log2_and_pow() {
+        assert_eq!(U256::ZERO.leading_zeros(), 256);
+        assert_eq!(U256::MAX.leading_zeros(), 0);
+        assert_eq!(U256::ZERO.checked_ilog2(), None);
+        assert_eq!(U256::from(1025u64).checked_ilog2(), Some(10));
+        assert_eq!(U256([0, 0, 0, 1]).checked_ilog2(), Some(192));
+
+        assert_eq!(U256::from(3u64).checked_pow(5), Some(U256::from(243u64)));
+        assert_eq!(U256::from(2u64).checked_pow(255), Some(U256::ONE << 255u32));
+        assert_eq!(U256::from(2u64).checked_pow(256), None);
+        assert_eq!(U256::ZERO.checked_pow(0), Some(U256::ONE));
+    }
+
     #[test]
     fn test_and() {
         let a = U256([1, 0, 0, 0]);
@@ -823,6 +1098,12 @@ mod tests {
         assert_eq!(a.shl(128), U256([0, 0, 1, 0]));
     }
 
+    #[test]
+    fn test_shl_inside_word() {
+        let a = U256([1, 0, 0, 0]);
+        assert_eq!(a.shl(65), U256([0, 2, 0, 0]));
+    }
+
     #[test]
     fn test_shr() {
         let a = U256([0, 1, 0, 0]);
@@ -0,0 +1,237 @@
+use core::cmp::Ordering;
+use alloc::{vec, vec::Vec};
+use crate::{HashSet, SubValue, Value};
+use super::{Path, ValueCell, ValueError};
+
+// Result of comparing two values
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Outcome {
+    Equal,
+    // The values are different and ordered
+    Ordered(Ordering),
+    // The values are different and can't be ordered
+    Unordered,
+}
+
+enum Step<'a> {
+    // Compare both values, the flag is false when their ordering is meaningless
+    Values(Path<'a>, Path<'a>, usize, bool),
+    // Applied once all the previous elements are equal, like a length difference
+    Then(Ordering, bool),
+}
+
+// Elements of an array, inline values are copied
+fn elements<'a>(value: &ValueCell) -> Option<Vec<Path<'a>>> {
+    match value {
+        ValueCell::Array(values) => Some(values.iter().map(|v| Path::Wrapper(v.clone())).collect()),
+        ValueCell::TypedArray(array) => Some(array.iter().map(|v| Path::Owned(v.into())).collect()),
+        _ => None
+    }
+}
+
+fn compare_primitives(a: &Value, b: &Value) -> Outcome {
+    let ordering = match (a, b) {
+        // Null is the smallest value, like an empty optional
+        (Value::Null, Value::Null) => Some(Ordering::Equal),
+        (Value::Null, _) => Some(Ordering::Less),
+        (_, Value::Null) => Some(Ordering::Greater),
+        (Value::String(a), Value::String(b)) => Some(a.cmp(b)),
+        (Value::Boolean(a), Value::Boolean(b)) => Some(a.cmp(b)),
+        _ => a.partial_cmp(b)
+    };
+
+    match ordering {
+        Some(Ordering::Equal) => Outcome::Equal,
+        Some(ordering) => Outcome::Ordered(ordering),
+        None if a == b => Outcome::Equal,
+        None => Outcome::Unordered
+    }
+}
+
+impl ValueCell {
+    // Compare both values and their inner values
+    // Returns if they are equal and the count of values compared
+    // A value referencing itself is supported, the depth is limited
+    pub fn deep_eq(&self, other: &ValueCell, max_depth: usize) -> Result<(bool, usize), ValueError> {
+        let (outcome, compared) = self.deep_compare(other, max_depth)?;
+        Ok((outcome == Outcome::Equal, compared))
+    }
+
+    // Order both values and their inner values
+    // Returns none if the values are different and can't be ordered, like maps
+    // Arrays are ordered by their elements then by their length
+    pub fn deep_cmp(&self, other: &ValueCell, max_depth: usize) -> Result<(Option<Ordering>, usize), ValueError> {
+        let (outcome, compared) = self.deep_compare(other, max_depth)?;
+        let ordering = match outcome {
+            Outcome::Equal => Some(Ordering::Equal),
+            Outcome::Ordered(ordering) => Some(ordering),
+            Outcome::Unordered => None
+        };
+
+        Ok((ordering, compared))
+    }
+
+    fn deep_compare(&self, other: &ValueCell, max_depth: usize) -> Result<(Outcome, usize), ValueError> {
+        // Prevent allocation if both values are primitives
+        if let (Self::Default(a), Self::Default(b)) = (self, other) {
+            return Ok((compare_primitives(a, b), 1));
+        }
+
+        // Pairs of values already compared, a cycle is equal if nothing else differs
+        let mut visited: HashSet<(*const ValueCell, *const ValueCell)> = HashSet::default();
+        let mut stack = vec![Step::Values(Path::Borrowed(self), Path::Borrowed(other), 0, true)];
+        let mut compared = 0;
+
+        while let Some(step) = stack.pop() {
+            let (left, right, depth, ordered) = match step {
+                Step::Values(left, right, depth, ordered) => (left, right, depth, ordered),
+                Step::Then(Ordering::Equal, _) => continue,
+                Step::Then(ordering, true) => return Ok((Outcome::Ordered(ordering), compared)),
+                Step::Then(_, false) => return Ok((Outcome::Unordered, compared)),
+            };
+
+            if depth > max_depth {
+                return Err(ValueError::MaxDepthReached);
+            }
+            compared += 1;
+
+            let left_handle = left.as_ref();
+            let right_handle = right.as_ref();
+            let a = left_handle.as_value();
+            let b = right_handle.as_value();
+
+            let outcome = match (a, b) {
+                (Self::Default(a), Self::Default(b)) => compare_primitives(a, b),
+                // An empty optional is stored as null or as an optional
+                _ if a.is_null() || b.is_null() => match (a.is_null(), b.is_null()) {
+                    (true, true) => Outcome::Equal,
+                    (true, false) => Outcome::Ordered(Ordering::Less),
+                    _ => Outcome::Ordered(Ordering::Greater)
+                },
+                // A set optional may be stored without its wrapper
+                (Self::Optional(Some(a)), b) if !matches!(b, Self::Optional(_)) => {
+                    stack.push(Step::Values(Path::Wrapper(a.clone()), right.clone(), depth + 1, ordered));
+                    Outcome::Equal
+                },
+                (a, Self::Optional(Some(b))) if !matches!(a, Self::Optional(_)) => {
+                    stack.push(Step::Values(left.clone(), Path::Wrapper(b.clone()), depth + 1, ordered));
+                    Outcome::Equal
+                },
+                (Self::Default(_), _) | (_, Self::Default(_)) => Outcome::Unordered,
+                _ if core::ptr::eq(a, b) => Outcome::Equal,
+                _ if !visited.insert((a as *const ValueCell, b as *const ValueCell)) => Outcome::Equal,
+                (Self::Struct(a, a_type), Self::Struct(b, b_type)) => {
+                    if a_type != b_type || a.len() != b.len() {
+                        Outcome::Unordered
+                    } else {
+                        push_pairs(&mut stack, a, b, depth, ordered);
+                        Outcome::Equal
+                    }
+                },
+                (Self::Enum(a, a_type), Self::Enum(b, b_type)) => {
+                    if a_type.id() != b_type.id() {
+                        Outcome::Unordered
+                    } else if a_type.variant_id() != b_type.variant_id() {
+                        Outcome::Ordered(a_type.variant_id().cmp(&b_type.variant_id()))
+                    } else if a.len() != b.len() {
+                        Outcome::Unordered
+                    } else {
+                        push_pairs(&mut stack, a, b, depth, ordered);
+                        Outcome::Equal
+                    }
+                },
+                (Self::Optional(Some(a)), Self::Optional(Some(b))) => {
+                    stack.push(Step::Values(Path::Wrapper(a.clone()), Path::Wrapper(b.clone()), depth + 1, ordered));
+                    Outcome::Equal
+                },
+                // Maps are equal with the same entries, their order is ignored
+                (Self::Map(a), Self::Map(b)) => {
+                    if a.len() != b.len() {
+                        Outcome::Unordered
+                    } else {
+                        let mut outcome = Outcome::Equal;
+                        for (key, left) in a.iter() {
+                            match b.get(key) {
+                                Some(right) => stack.push(Step::Values(Path::Wrapper(left.clone()), Path::Wrapper(right.clone()), depth + 1, false)),
+                                None => {
+                                    outcome = Outcome::Unordered;
+                                    break;
+                                }
+                            }
+                        }
+                        outcome
+                    }
+                },
+                (a, b) => match (elements(a), elements(b)) {
+                    (Some(a), Some(b)) => {
+                        stack.push(Step::Then(a.len().cmp(&b.len()), ordered));
+                        for (left, right) in a.into_iter().zip(b).rev() {
+                            stack.push(Step::Values(left, right, depth + 1, ordered));
+                        }
+                        Outcome::Equal
+                    },
+                    // Iterators are only equal to themselves
+                    _ => Outcome::Unordered
+                }
+            };
+
+            match outcome {
+                Outcome::Equal => {},
+                Outcome::Ordered(ordering) if ordered => return Ok((Outcome::Ordered(ordering), compared)),
+                _ => return Ok((Outcome::Unordered, compared))
+            }
+        }
+
+        Ok((Outcome::Equal, compared))
+    }
+}
+
+// Push the fields to compare them in order
+fn push_pairs<'a>(stack: &mut Vec<Step<'a>>, a: &[SubValue], b: &[SubValue], depth: usize, ordered: bool) {
+    for (left, right) in a.iter().zip(b).rev() {
+        stack.push(Step::Values(Path::Wrapper(left.clone()), Path::Wrapper(right.clone()), depth + 1, ordered));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn array(values: Vec<ValueCell>) -> ValueCell {
+        ValueCell::Array(values.into_iter().map(SubValue::new).collect())
+    }
+
+    #[test]
+    fn test_deep_eq_arrays() {
+        let a = array(vec![Value::U8(1).into(), array(vec![Value::U8(2).into()])]);
+        let b = array(vec![Value::U8(1).into(), array(vec![Value::U8(2).into()])]);
+        let c = array(vec![Value::U8(1).into(), array(vec![Value::U8(3).into()])]);
+
+        assert!(a.deep_eq(&b, 8).unwrap().0);
+        assert!(!a.deep_eq(&c, 8).unwrap().0);
+        assert_eq!(a.deep_cmp(&c, 8).unwrap().0, Some(Ordering::Less));
+    }
+
+    #[test]
+    fn test_deep_cmp_length() {
+        let a = array(vec![Value::U8(1).into()]);
+        let b = array(vec![Value::U8(1).into(), Value::U8(0).into()]);
+
+        assert_eq!(a.deep_cmp(&b, 8).unwrap().0, Some(Ordering::Less));
+        assert_eq!(b.deep_cmp(&a, 8).unwrap().0, Some(Ordering::Greater));
+    }
+
+    #[test]
+    fn test_deep_eq_cycle() {
+        let a = SubValue::new(array(Vec::new()));
+        let b = SubValue::new(array(Vec::new()));
+        if let ValueCell::Array(values) = &mut *a.borrow_mut() {
+            values.push(a.reference());
+        }
+        if let ValueCell::Array(values) = &mut *b.borrow_mut() {
+            values.push(b.reference());
+        }
+
+        assert!(a.borrow().deep_eq(&b.borrow(), 8).unwrap().0);
+    }
+}
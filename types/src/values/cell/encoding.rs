@@ -0,0 +1,271 @@
+use alloc::{borrow::ToOwned, vec::Vec};
+use crate::{values::{Value, ValueError}, IndexMap, U256};
+use super::ValueCell;
+
+// Tags of the encoded values
+const NULL: u8 = 0;
+const U8: u8 = 1;
+const U16: u8 = 2;
+const U32: u8 = 3;
+const U64: u8 = 4;
+const U128: u8 = 5;
+const U256_TAG: u8 = 6;
+const STRING: u8 = 7;
+const BOOL: u8 = 8;
+const BLOB: u8 = 9;
+const ARRAY: u8 = 10;
+const OPTIONAL_NONE: u8 = 11;
+const OPTIONAL_SOME: u8 = 12;
+const MAP: u8 = 13;
+
+// Compact binary encoding of the values, used to persist them outside of the VM
+// Every value starts with a tag, integers are little endian and lengths are written as u32
+// Structs, enums and ranges are not encoded as their types can't be decoded without the environment
+impl ValueCell {
+    // Encode the value, the depth is limited as a value may reference itself
+    pub fn to_bytes(&self, max_depth: usize) -> Result<Vec<u8>, ValueError> {
+        let mut out = Vec::new();
+        self.write_bytes(&mut out, 0, max_depth)?;
+        Ok(out)
+    }
+
+    // Decode a value, all the bytes must be consumed
+    pub fn from_bytes(bytes: &[u8], max_depth: usize) -> Result<Self, ValueError> {
+        let mut reader = Reader { bytes, offset: 0 };
+        let value = reader.read_value(0, max_depth)?;
+        if reader.offset != bytes.len() {
+            return Err(ValueError::InvalidEncoding)
+        }
+
+        Ok(value)
+    }
+
+    fn write_bytes(&self, out: &mut Vec<u8>, depth: usize, max_depth: usize) -> Result<(), ValueError> {
+        if depth > max_depth {
+            return Err(ValueError::MaxDepthReached)
+        }
+
+        match self {
+            ValueCell::Default(v) => match v {
+                Value::Null => out.push(NULL),
+                Value::U8(n) => {
+                    out.push(U8);
+                    out.push(*n);
+                },
+                Value::U16(n) => {
+                    out.push(U16);
+                    out.extend_from_slice(&n.to_le_bytes());
+                },
+                Value::U32(n) => {
+                    out.push(U32);
+                    out.extend_from_slice(&n.to_le_bytes());
+                },
+                Value::U64(n) => {
+                    out.push(U64);
+                    out.extend_from_slice(&n.to_le_bytes());
+                },
+                Value::U128(n) => {
+                    out.push(U128);
+                    out.extend_from_slice(&n.to_le_bytes());
+                },
+                Value::U256(n) => {
+                    out.push(U256_TAG);
+                    out.extend_from_slice(&n.to_le_bytes());
+                },
+                Value::String(s) => {
+                    out.push(STRING);
+                    write_len(out, s.len())?;
+                    out.extend_from_slice(s.as_bytes());
+                },
+                Value::Boolean(b) => {
+                    out.push(BOOL);
+                    out.push(*b as u8);
+                },
+                Value::Blob(bytes) => {
+                    out.push(BLOB);
+                    write_len(out, bytes.len())?;
+                    out.extend_from_slice(bytes);
+                },
+                Value::Range(..) => return Err(ValueError::NotEncodable)
+            },
+            ValueCell::Array(values) => {
+                out.push(ARRAY);
+                write_len(out, values.len())?;
+                for value in values {
+                    value.borrow().write_bytes(out, depth + 1, max_depth)?;
+                }
+            },
+            // Encoded like a boxed array
+            ValueCell::TypedArray(array) => {
+                out.push(ARRAY);
+                write_len(out, array.len())?;
+                for value in array.iter() {
+                    ValueCell::Default(value).write_bytes(out, depth + 1, max_depth)?;
+                }
+            },
+            ValueCell::Optional(None) => out.push(OPTIONAL_NONE),
+            ValueCell::Optional(Some(value)) => {
+                out.push(OPTIONAL_SOME);
+                value.borrow().write_bytes(out, depth + 1, max_depth)?;
+            },
+            ValueCell::Map(map) => {
+                out.push(MAP);
+                write_len(out, map.len())?;
+                for (k, v) in map {
+                    k.write_bytes(out, depth + 1, max_depth)?;
+                    v.borrow().write_bytes(out, depth + 1, max_depth)?;
+                }
+            },
+            ValueCell::Struct(..) | ValueCell::Enum(..) | ValueCell::Iterator(_) => return Err(ValueError::NotEncodable)
+        };
+
+        Ok(())
+    }
+}
+
+fn write_len(out: &mut Vec<u8>, len: usize) -> Result<(), ValueError> {
+    let len = u32::try_from(len)
+        .map_err(|_| ValueError::NotEncodable)?;
+    out.extend_from_slice(&len.to_le_bytes());
+    Ok(())
+}
+
+struct Reader<'a> {
+    bytes: &'a [u8],
+    offset: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn read(&mut self, n: usize) -> Result<&'a [u8], ValueError> {
+        let end = self.offset.checked_add(n)
+            .filter(|end| *end <= self.bytes.len())
+            .ok_or(ValueError::InvalidEncoding)?;
+
+        let bytes = &self.bytes[self.offset..end];
+        self.offset = end;
+        Ok(bytes)
+    }
+
+    fn read_array<const N: usize>(&mut self) -> Result<[u8; N], ValueError> {
+        Ok(self.read(N)?.try_into().unwrap())
+    }
+
+    fn read_u8(&mut self) -> Result<u8, ValueError> {
+        Ok(self.read(1)?[0])
+    }
+
+    // Each element takes at least one byte, so a length can't exceed the remaining bytes
+    // This prevents a huge allocation from a small input
+    fn read_len(&mut self) -> Result<usize, ValueError> {
+        let len = u32::from_le_bytes(self.read_array()?) as usize;
+        if len > self.bytes.len() - self.offset {
+            return Err(ValueError::InvalidEncoding)
+        }
+
+        Ok(len)
+    }
+
+    fn read_value(&mut self, depth: usize, max_depth: usize) -> Result<ValueCell, ValueError> {
+        if depth > max_depth {
+            return Err(ValueError::MaxDepthReached)
+        }
+
+        Ok(match self.read_u8()? {
+            NULL => Value::Null.into(),
+            U8 => Value::U8(self.read_u8()?).into(),
+            U16 => Value::U16(u16::from_le_bytes(self.read_array()?)).into(),
+            U32 => Value::U32(u32::from_le_bytes(self.read_array()?)).into(),
+            U64 => Value::U64(u64::from_le_bytes(self.read_array()?)).into(),
+            U128 => Value::U128(u128::from_le_bytes(self.read_array()?)).into(),
+            U256_TAG => Value::U256(U256::from_le_bytes(self.read_array()?)).into(),
+            STRING => {
+                let len = self.read_len()?;
+                let s = core::str::from_utf8(self.read(len)?)
+                    .map_err(|_| ValueError::InvalidEncoding)?;
+                Value::String(s.to_owned()).into()
+            },
+            BOOL => match self.read_u8()? {
+                0 => Value::Boolean(false).into(),
+                1 => Value::Boolean(true).into(),
+                _ => return Err(ValueError::InvalidEncoding)
+            },
+            BLOB => {
+                let len = self.read_len()?;
+                Value::Blob(self.read(len)?.to_vec()).into()
+            },
+            ARRAY => {
+                let len = self.read_len()?;
+                let mut values = Vec::with_capacity(len);
+                for _ in 0..len {
+                    values.push(self.read_value(depth + 1, max_depth)?);
+                }
+                ValueCell::new_array(values)
+            },
+            OPTIONAL_NONE => ValueCell::Optional(None),
+            OPTIONAL_SOME => ValueCell::Optional(Some(self.read_value(depth + 1, max_depth)?.into())),
+            MAP => {
+                let len = self.read_len()?;
+                let mut map = IndexMap::with_capacity_and_hasher(len, Default::default());
+                for _ in 0..len {
+                    let key = self.read_value(depth + 1, max_depth)?;
+                    let value = self.read_value(depth + 1, max_depth)?;
+                    // Duplicated keys would be silently merged
+                    if map.insert(key, value.into()).is_some() {
+                        return Err(ValueError::InvalidEncoding)
+                    }
+                }
+                ValueCell::Map(map.into())
+            },
+            _ => return Err(ValueError::InvalidEncoding)
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn roundtrip(value: ValueCell) {
+        let bytes = value.to_bytes(16).unwrap();
+        assert_eq!(ValueCell::from_bytes(&bytes, 16).unwrap(), value);
+    }
+
+    #[test]
+    fn test_encoding_roundtrip() {
+        roundtrip(Value::Null.into());
+        roundtrip(Value::U8(42).into());
+        roundtrip(Value::U64(u64::MAX).into());
+        roundtrip(Value::U256(U256::MAX).into());
+        roundtrip(Value::String("hello".to_owned()).into());
+        roundtrip(Value::Boolean(true).into());
+        roundtrip(ValueCell::TypedArray(vec![1, 2, 3].into()));
+        roundtrip(Value::Blob(vec![1, 2, 3]).into());
+        roundtrip(ValueCell::Optional(None));
+        roundtrip(ValueCell::Optional(Some(Value::U16(7).into())));
+        roundtrip(ValueCell::Array(vec![Value::U32(1).into(), ValueCell::Array(Default::default()).into()].into()));
+
+        let mut map = IndexMap::default();
+        map.insert(Value::String("a".to_owned()).into(), Value::U128(1).into());
+        map.insert(Value::U8(2).into(), ValueCell::Optional(None).into());
+        roundtrip(ValueCell::Map(map.into()));
+    }
+
+    #[test]
+    fn test_invalid_encoding() {
+        // Unknown tag
+        assert!(ValueCell::from_bytes(&[255], 16).is_err());
+        // Truncated integer
+        assert!(ValueCell::from_bytes(&[U64, 1, 2], 16).is_err());
+        // Trailing bytes
+        assert!(ValueCell::from_bytes(&[NULL, 0], 16).is_err());
+        // Length bigger than the input
+        assert!(ValueCell::from_bytes(&[ARRAY, 255, 255, 255, 255], 16).is_err());
+        // Invalid boolean
+        assert!(ValueCell::from_bytes(&[BOOL, 2], 16).is_err());
+        // Too deep
+        assert!(ValueCell::from_bytes(&[OPTIONAL_SOME, OPTIONAL_SOME, NULL], 1).is_err());
+        // Ranges can't be encoded
+        let range = Value::Range(Box::new(Value::U8(0)), Box::new(Value::U8(1)), crate::Type::U8, false);
+        assert!(ValueCell::from(range).to_bytes(16).is_err());
+    }
+}
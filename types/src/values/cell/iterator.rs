@@ -0,0 +1,570 @@
+use alloc::{collections::VecDeque, vec::Vec};
+use crate::{sync::Shared, Type, Value, U256};
+use super::{SubValue, TypedArray, ValueCell, ValueError};
+
+// Values read by a lazy iterator
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum IteratorSource {
+    // Numbers from the start of a range, in the range type
+    Range(U256, Type),
+    // Values of an array, shared with it
+    Array(Shared<Vec<SubValue>>),
+    // Copy of a typed array, its values are already shared
+    TypedArray(TypedArray),
+}
+
+// Adapter applied on each value read from the source
+// A chunk can't be called by the iterator itself: the VM invokes it
+// and gives its result back, see `LazyIterator::resolve`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum IteratorAdapter {
+    // The value is replaced by the one returned by the chunk
+    Map(u16),
+    // The value is kept if the chunk returns true
+    Filter(u16),
+    // The positions in the source can't be used after a filter,
+    // so these count the values reaching them
+    // Values left to ignore
+    Skip(u64),
+    // Values left to produce
+    Take(u64),
+    // Values left to ignore before the next one, and the step
+    StepBy(u64, u64),
+}
+
+// Iterator producing its values one at a time
+// The adapters only move its position in the source: a chain like
+// `skip(2).step_by(3).take(5)` never allocates an intermediate array
+// Its source is shared between its copies, so copying it is O(1)
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LazyIterator {
+    source: IteratorSource,
+    // Count of values in the source
+    len: u64,
+    // Index in the source of the next value
+    index: u64,
+    // Distance in the source between two values
+    step: u64,
+    // Maximum count of values left to produce
+    remaining: u64,
+    // The index goes down the source
+    reverse: bool,
+    // Adapters calling a chunk, in their order
+    adapters: Vec<IteratorAdapter>,
+    // Values read from the source with the index of their next adapter
+    // The ones which went through all the adapters come first
+    queue: VecDeque<(SubValue, usize)>,
+    // Value given to the chunk of an adapter, waiting for its result
+    pending: Option<(SubValue, usize)>,
+}
+
+impl LazyIterator {
+    // Iterate over the numbers of a range
+    pub fn from_range(start: &Value, end: &Value, _type: Type, inclusive: bool) -> Result<Self, ValueError> {
+        let start = start.clone().cast_to_u256()?;
+        let end = end.clone().cast_to_u256()?;
+        // The positions in the source are u64, a bigger range can't be iterated
+        let len = match end.checked_sub(start) {
+            Some(diff) => {
+                let len = diff.as_u64().ok_or(ValueError::RangeTooLarge)?;
+                if inclusive {
+                    len.checked_add(1).ok_or(ValueError::RangeTooLarge)?
+                } else {
+                    len
+                }
+            },
+            None => 0
+        };
+
+        Ok(Self::new(IteratorSource::Range(start, _type), len))
+    }
+
+    // Iterate over the values of an array
+    pub fn from_values(values: Vec<SubValue>) -> Self {
+        let len = values.len() as u64;
+        Self::new(IteratorSource::Array(Shared::new(values)), len)
+    }
+
+    pub fn from_typed_array(array: TypedArray) -> Self {
+        let len = array.len() as u64;
+        Self::new(IteratorSource::TypedArray(array), len)
+    }
+
+    fn new(source: IteratorSource, len: u64) -> Self {
+        Self {
+            source,
+            len,
+            index: 0,
+            step: 1,
+            remaining: len,
+            reverse: false,
+            adapters: Vec::new(),
+            queue: VecDeque::new(),
+            pending: None,
+        }
+    }
+
+    // Values held by the iterator which can be referenced:
+    // the ones of an array source and the ones read from the source
+    pub fn values(&self) -> impl Iterator<Item = &SubValue> {
+        let source: &[SubValue] = match &self.source {
+            IteratorSource::Array(values) => values,
+            _ => &[]
+        };
+
+        source.iter()
+            .chain(self.queue.iter().map(|(value, _)| value))
+            .chain(self.pending.iter().map(|(value, _)| value))
+    }
+
+    // Take the values held, the ones of an array source only if this iterator is their last owner
+    pub fn into_values(self) -> Vec<SubValue> {
+        let mut values = match self.source {
+            IteratorSource::Array(values) => Shared::try_unwrap(values).unwrap_or_default(),
+            _ => Vec::new()
+        };
+
+        values.extend(self.queue.into_iter().map(|(value, _)| value));
+        values.extend(self.pending.map(|(value, _)| value));
+        values
+    }
+
+    // Copy the values held
+    pub fn into_owned(self) -> Self {
+        let copy = |value: SubValue| -> SubValue {
+            value.borrow().clone().into_owned().into()
+        };
+
+        let source = match self.source {
+            IteratorSource::Array(values) => IteratorSource::Array(Shared::new(values.iter()
+                .map(|v| copy(v.reference()))
+                .collect()
+            )),
+            source => source
+        };
+
+        Self {
+            source,
+            queue: self.queue.into_iter()
+                .map(|(value, adapter)| (copy(value), adapter))
+                .collect(),
+            pending: self.pending.map(|(value, adapter)| (copy(value), adapter)),
+            ..self
+        }
+    }
+
+    // Count of values left to read from the source
+    fn source_len(&self) -> u64 {
+        if self.index >= self.len {
+            return 0
+        }
+
+        let available = if self.reverse {
+            self.index / self.step + 1
+        } else {
+            (self.len - self.index).div_ceil(self.step)
+        };
+
+        self.remaining.min(available)
+    }
+
+    // Maximum count of values left to produce
+    // It is exact once the adapters were applied on all of them
+    pub fn len(&self) -> u64 {
+        let read = self.queue.len() as u64 + self.pending.is_some() as u64;
+        self.source_len().saturating_add(read)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    // Does a value have to go through a chunk before being produced
+    pub fn has_callbacks(&self) -> bool {
+        !self.adapters.is_empty()
+    }
+
+    // The positional adapters can move the position in the source
+    // while each value read is still produced in the source order
+    fn is_positional(&self) -> bool {
+        self.queue.is_empty()
+            && self.pending.is_none()
+            && self.adapters.iter().all(|adapter| matches!(adapter, IteratorAdapter::Map(_)))
+    }
+
+    // Move the index by n values
+    // Going down below the first value of the source ends the iterator
+    fn advance(&mut self, n: u64) {
+        let offset = n.saturating_mul(self.step);
+        if self.reverse {
+            match self.index.checked_sub(offset) {
+                Some(index) => self.index = index,
+                None => self.remaining = 0
+            }
+        } else {
+            self.index = self.index.saturating_add(offset);
+        }
+    }
+
+    // Ignore the next n values
+    // The values skipped before a map never go through its chunk
+    pub fn skip(&mut self, n: u64) {
+        if !self.is_positional() {
+            self.adapters.push(IteratorAdapter::Skip(n));
+            return
+        }
+
+        let n = n.min(self.source_len());
+        self.remaining -= n;
+        self.advance(n);
+    }
+
+    // Produce at most n values
+    pub fn take(&mut self, n: u64) {
+        if !self.is_positional() {
+            self.adapters.push(IteratorAdapter::Take(n));
+            return
+        }
+
+        self.remaining = self.remaining.min(n);
+    }
+
+    // Produce the first value then every n-th value
+    pub fn step_by(&mut self, n: u64) -> Result<(), ValueError> {
+        if n == 0 {
+            return Err(ValueError::InvalidStep)
+        }
+
+        if !self.is_positional() {
+            self.adapters.push(IteratorAdapter::StepBy(0, n));
+            return Ok(())
+        }
+
+        self.remaining = self.source_len().div_ceil(n);
+        self.step = self.step.saturating_mul(n);
+        Ok(())
+    }
+
+    // Produce the values left in the reverse order
+    // The maps and the filters keep the order of the values, but not a positional
+    // adapter after a filter nor the values already read
+    pub fn rev(&mut self) -> Result<(), ValueError> {
+        if !self.queue.is_empty() || self.pending.is_some() || self.adapters.iter().any(|adapter| !matches!(adapter, IteratorAdapter::Map(_) | IteratorAdapter::Filter(_))) {
+            return Err(ValueError::IteratorNotReversible)
+        }
+
+        let len = self.source_len();
+        if len > 0 {
+            // Index of the last value left
+            let offset = (len - 1) * self.step;
+            self.index = if self.reverse {
+                self.index - offset
+            } else {
+                self.index + offset
+            };
+        }
+
+        self.remaining = len;
+        self.reverse = !self.reverse;
+        Ok(())
+    }
+
+    // Produce the values returned by the chunk called with each value
+    pub fn map(&mut self, chunk: u16) {
+        self.adapters.push(IteratorAdapter::Map(chunk));
+    }
+
+    // Produce the values for which the chunk returns true
+    pub fn filter(&mut self, chunk: u16) {
+        self.adapters.push(IteratorAdapter::Filter(chunk));
+    }
+
+    // Count of values at the front of the queue which went through all the adapters
+    fn ready(&self) -> usize {
+        let len = self.adapters.len();
+        self.queue.iter()
+            .take_while(|(_, adapter)| *adapter == len)
+            .count()
+    }
+
+    // Apply the adapters until n values are ready to be produced or the iterator ends
+    // When a chunk must be called, it is returned with its argument and the
+    // iterator waits for its result, given back with `set_result`
+    pub fn resolve(&mut self, n: u64) -> Result<Option<(u16, SubValue)>, ValueError> {
+        if self.pending.is_some() {
+            return Err(ValueError::UnresolvedIterator)
+        }
+
+        let mut ready = self.ready();
+        while (ready as u64) < n {
+            let (value, mut adapter) = match self.queue.remove(ready) {
+                Some(entry) => entry,
+                None => match self.next_source_value()? {
+                    Some(value) => (value, 0),
+                    None => return Ok(None)
+                }
+            };
+
+            loop {
+                let Some(current) = self.adapters.get_mut(adapter) else {
+                    self.queue.insert(ready, (value, adapter));
+                    ready += 1;
+                    break
+                };
+
+                match current {
+                    IteratorAdapter::Map(chunk) | IteratorAdapter::Filter(chunk) => {
+                        let chunk = *chunk;
+                        self.pending = Some((value.reference(), adapter));
+                        return Ok(Some((chunk, value)))
+                    },
+                    IteratorAdapter::Skip(left) => if *left > 0 {
+                        *left -= 1;
+                        break
+                    },
+                    IteratorAdapter::Take(left) => {
+                        if *left == 0 {
+                            self.queue.truncate(ready);
+                            self.remaining = 0;
+                            return Ok(None)
+                        }
+
+                        // The next values won't be taken, they are not read
+                        *left -= 1;
+                        if *left == 0 {
+                            self.queue.truncate(ready);
+                            self.remaining = 0;
+                        }
+                    },
+                    IteratorAdapter::StepBy(left, step) => {
+                        if *left > 0 {
+                            *left -= 1;
+                            break
+                        }
+                        *left = *step - 1;
+                    }
+                }
+
+                adapter += 1;
+            }
+        }
+
+        Ok(None)
+    }
+
+    // Give the result of the chunk returned by `resolve`
+    // The value dropped by the adapter is returned
+    pub fn set_result(&mut self, result: SubValue) -> Result<Option<SubValue>, ValueError> {
+        let (value, adapter) = self.pending.take()
+            .ok_or(ValueError::UnresolvedIterator)?;
+
+        let ready = self.ready();
+        match self.adapters.get(adapter) {
+            Some(IteratorAdapter::Map(_)) => {
+                self.queue.insert(ready, (result, adapter + 1));
+                Ok(Some(value))
+            },
+            Some(IteratorAdapter::Filter(_)) => {
+                if result.borrow().as_bool()? {
+                    self.queue.insert(ready, (value, adapter + 1));
+                    Ok(None)
+                } else {
+                    Ok(Some(value))
+                }
+            },
+            _ => Err(ValueError::UnresolvedIterator)
+        }
+    }
+
+    // Produce the next value
+    // A value of a boxed array is shared with it, the others are created
+    // With callbacks, the value must have been resolved first
+    pub fn next_value(&mut self) -> Result<Option<SubValue>, ValueError> {
+        if self.adapters.is_empty() {
+            return self.next_source_value()
+        }
+
+        if self.ready() > 0 {
+            return Ok(self.queue.pop_front().map(|(value, _)| value))
+        }
+
+        if self.is_empty() {
+            return Ok(None)
+        }
+
+        Err(ValueError::UnresolvedIterator)
+    }
+
+    // Read the next value of the source
+    fn next_source_value(&mut self) -> Result<Option<SubValue>, ValueError> {
+        if self.source_len() == 0 {
+            return Ok(None)
+        }
+
+        let value = match &self.source {
+            IteratorSource::Range(start, _type) => {
+                let value = start.checked_add(self.index.into())
+                    .ok_or(ValueError::Overflow)?;
+                SubValue::new(ValueCell::Default(Value::U256(value).checked_cast_to_primitive_type(_type)?))
+            },
+            IteratorSource::Array(values) => values.get(self.index as usize)
+                .ok_or(ValueError::OutOfBounds(self.index as usize, values.len()))?
+                .reference(),
+            IteratorSource::TypedArray(array) => array.get(self.index as usize)
+                .ok_or(ValueError::OutOfBounds(self.index as usize, array.len()))?
+                .into()
+        };
+
+        self.remaining -= 1;
+        self.advance(1);
+
+        Ok(Some(value))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::vec;
+    use super::*;
+
+    fn collect(mut iter: LazyIterator) -> Vec<u64> {
+        let mut values = Vec::new();
+        while let Some(value) = iter.next_value().unwrap() {
+            values.push(value.borrow().as_u64().unwrap());
+        }
+        values
+    }
+
+    #[test]
+    fn test_range_adapters() {
+        let mut iter = LazyIterator::from_range(&Value::U64(0), &Value::U64(20), Type::U64, false).unwrap();
+        iter.skip(2);
+        iter.step_by(3).unwrap();
+        iter.take(4);
+        assert_eq!(iter.len(), 4);
+        assert_eq!(collect(iter), vec![2, 5, 8, 11]);
+
+        let mut iter = LazyIterator::from_range(&Value::U64(5), &Value::U64(10), Type::U64, false).unwrap();
+        iter.step_by(2).unwrap();
+        iter.skip(1);
+        assert_eq!(collect(iter), vec![7, 9]);
+
+        let mut iter = LazyIterator::from_range(&Value::U64(10), &Value::U64(5), Type::U64, false).unwrap();
+        assert!(iter.is_empty());
+        assert!(iter.step_by(0).is_err());
+
+        let iter = LazyIterator::from_range(&Value::U8(250), &Value::U8(255), Type::U8, true).unwrap();
+        assert_eq!(iter.len(), 6);
+    }
+
+    #[test]
+    fn test_rev() {
+        let mut iter = LazyIterator::from_range(&Value::U64(0), &Value::U64(10), Type::U64, true).unwrap();
+        iter.rev().unwrap();
+        iter.step_by(3).unwrap();
+        assert_eq!(collect(iter.clone()), vec![10, 7, 4, 1]);
+
+        iter.skip(1);
+        iter.rev().unwrap();
+        assert_eq!(collect(iter.clone()), vec![1, 4, 7]);
+
+        iter.take(2);
+        iter.rev().unwrap();
+        assert_eq!(collect(iter), vec![4, 1]);
+    }
+
+    #[test]
+    fn test_rev_large_range() {
+        let end = U256::from(u64::MAX);
+        let mut iter = LazyIterator::from_range(&Value::U256(U256::ZERO), &Value::U256(end), Type::U256, false).unwrap();
+        iter.rev().unwrap();
+        let value = iter.next_value().unwrap().unwrap();
+        assert_eq!(value.borrow().as_u256().unwrap(), end - U256::ONE);
+
+        let mut iter = LazyIterator::from_range(&Value::U128(1), &Value::U128(u64::MAX as u128 + 1), Type::U128, false).unwrap();
+        iter.step_by(2).unwrap();
+        iter.rev().unwrap();
+        assert_eq!(iter.next_value().unwrap().unwrap().borrow().as_u128().unwrap(), u64::MAX as u128);
+
+        // Its length can't be represented, it is never capped
+        let iter = LazyIterator::from_range(&Value::U128(0), &Value::U128(1 << 70), Type::U128, false);
+        assert!(matches!(iter, Err(ValueError::RangeTooLarge)));
+
+        let iter = LazyIterator::from_range(&Value::U64(0), &Value::U64(u64::MAX), Type::U64, true);
+        assert!(matches!(iter, Err(ValueError::RangeTooLarge)));
+    }
+
+    #[test]
+    fn test_array_shared() {
+        let values: Vec<SubValue> = (0..5u64).map(|v| Value::U64(v).into()).collect();
+        let mut iter = LazyIterator::from_values(values.iter().map(SubValue::reference).collect());
+        iter.take(10);
+        iter.skip(3);
+
+        let value = iter.next_value().unwrap().unwrap();
+        assert_eq!(value.as_ptr(), values[3].as_ptr());
+        assert_eq!(collect(iter), vec![4]);
+    }
+
+    // Resolve the values, the chunk 0 doubles a value and the chunk 1 keeps the even ones
+    fn resolve_all(mut iter: LazyIterator) -> (Vec<u64>, Vec<u16>) {
+        let mut calls = Vec::new();
+        while let Some((chunk, value)) = iter.resolve(u64::MAX).unwrap() {
+            calls.push(chunk);
+            let value = value.borrow().as_u64().unwrap();
+            let result = match chunk {
+                0 => Value::U64(value * 2),
+                _ => Value::Boolean(value % 2 == 0)
+            };
+            iter.set_result(result.into()).unwrap();
+        }
+
+        assert_eq!(iter.len(), iter.ready() as u64);
+        (collect(iter), calls)
+    }
+
+    #[test]
+    fn test_callbacks() {
+        let mut iter = LazyIterator::from_range(&Value::U64(0), &Value::U64(10), Type::U64, false).unwrap();
+        iter.filter(1);
+        iter.map(0);
+        iter.take(3);
+        assert!(iter.clone().next_value().is_err());
+
+        // The values after the third kept one are never read
+        let (values, calls) = resolve_all(iter);
+        assert_eq!(values, vec![0, 4, 8]);
+        assert_eq!(calls, vec![1, 0, 1, 1, 0, 1, 1, 0]);
+
+        // Skipped before the map, the first values don't go through it
+        let mut iter = LazyIterator::from_range(&Value::U64(0), &Value::U64(5), Type::U64, false).unwrap();
+        iter.map(0);
+        iter.skip(2);
+        iter.rev().unwrap();
+        let (values, calls) = resolve_all(iter);
+        assert_eq!(values, vec![8, 6, 4]);
+        assert_eq!(calls.len(), 3);
+
+        let mut iter = LazyIterator::from_range(&Value::U64(0), &Value::U64(10), Type::U64, false).unwrap();
+        iter.filter(1);
+        iter.step_by(2).unwrap();
+        assert!(iter.clone().rev().is_err());
+        assert_eq!(resolve_all(iter).0, vec![0, 4, 8]);
+    }
+
+    #[test]
+    fn test_adapter_after_resolved_value() {
+        let mut iter = LazyIterator::from_range(&Value::U64(1), &Value::U64(4), Type::U64, false).unwrap();
+        iter.map(0);
+        let (chunk, _) = iter.resolve(1).unwrap().unwrap();
+        assert_eq!(chunk, 0);
+        assert!(iter.resolve(1).is_err());
+        iter.set_result(Value::U64(2).into()).unwrap();
+        assert!(iter.resolve(1).unwrap().is_none());
+
+        // The value already mapped goes through the new adapter too
+        iter.map(0);
+        iter.skip(1);
+        assert_eq!(resolve_all(iter).0, vec![8, 12]);
+    }
+}
@@ -1,30 +1,41 @@
 mod path;
+mod encoding;
+mod typed;
+mod iterator;
+mod compare;
+mod shared;
 
-use std::{
-    borrow::Cow,
-    collections::{HashMap, HashSet},
+use core::{
     fmt,
     hash::{Hash, Hasher},
     ops::{Deref, DerefMut},
     ptr
 };
-use crate::{EnumValueType, StructType, Type, U256};
+use alloc::{borrow::Cow, boxed::Box, format, string::{String, ToString}, vec, vec::Vec};
+use crate::{EnumValueType, HashSet, IndexMap, StructType, Type, U256};
 use super::{Value, ValueError, SubValue, Constant};
 
 pub use path::*;
+pub use typed::TypedArray;
+pub use iterator::{IteratorAdapter, IteratorSource, LazyIterator};
+pub use shared::{SharedValues, SubValues};
 
 // Give inner mutability for values with inner types.
-// This is NOT thread-safe due to the RefCell usage.
-#[derive(Debug, Clone, PartialEq, Eq)]
+// It is only thread-safe with the `sync` feature.
+#[derive(Debug, Clone, Eq)]
 pub enum ValueCell {
     Default(Value),
-    Struct(Vec<SubValue>, StructType),
-    Array(Vec<SubValue>),
+    Struct(SharedValues<Vec<SubValue>>, StructType),
+    Array(SharedValues<Vec<SubValue>>),
+    // Same as an array, but the primitive values are stored inline
+    TypedArray(TypedArray),
     Optional(Option<SubValue>),
 
     // Map cannot be used as a key in another map
-    Map(HashMap<ValueCell, SubValue>),
-    Enum(Vec<SubValue>, EnumValueType),
+    Map(SharedValues<IndexMap<ValueCell, SubValue>>),
+    Enum(SharedValues<Vec<SubValue>>, EnumValueType),
+    // Lazy iterator, its values are produced when consumed
+    Iterator(Box<LazyIterator>),
 }
 
 // Wrapper to drop the value without stackoverflow
@@ -37,19 +48,29 @@ impl Drop for ValueCellWrapper {
             return
         }
 
-        let mut stack = vec![std::mem::take(&mut self.0)];
+        let mut stack = vec![core::mem::take(&mut self.0)];
         while let Some(value) = stack.pop() {
             match value {
-                ValueCell::Default(_) => {},
-                ValueCell::Struct(fields, _) => stack.extend(fields.into_iter().map(SubValue::into_owned)),
-                ValueCell::Array(values) => stack.extend(values.into_iter().map(SubValue::into_owned)),
+                ValueCell::Default(_) | ValueCell::TypedArray(_) => {},
+                // The values shared with a copy are dropped with it
+                ValueCell::Struct(values, _)
+                | ValueCell::Array(values)
+                | ValueCell::Enum(values, _) => stack.extend(values.try_into_inner()
+                    .into_iter()
+                    .flatten()
+                    .map(SubValue::into_owned)
+                ),
                 ValueCell::Optional(opt) => {
                     if let Some(value) = opt {
                         stack.push(value.into_owned());
                     }
                 },
-                ValueCell::Map(map) => stack.extend(map.into_iter().flat_map(|(k, v)| [k, v.into_owned()])),
-                ValueCell::Enum(fields, _) => stack.extend(fields.into_iter().map(SubValue::into_owned)),
+                ValueCell::Map(map) => stack.extend(map.try_into_inner()
+                    .into_iter()
+                    .flatten()
+                    .flat_map(|(k, v)| [k, v.into_owned()])
+                ),
+                ValueCell::Iterator(iter) => stack.extend(iter.into_values().into_iter().map(SubValue::into_owned)),
             }
         }
     }
@@ -69,9 +90,31 @@ impl DerefMut for ValueCellWrapper {
     }
 }
 
+impl PartialEq for ValueCell {
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (Self::Default(a), Self::Default(b)) => a == b,
+            (Self::Struct(a, a_type), Self::Struct(b, b_type)) => a == b && a_type == b_type,
+            (Self::Array(a), Self::Array(b)) => a == b,
+            (Self::TypedArray(a), Self::TypedArray(b)) => a == b,
+            // The same array may be stored inline or boxed
+            (Self::Array(a), Self::TypedArray(b)) | (Self::TypedArray(b), Self::Array(a)) => {
+                a.len() == b.len() && a.iter()
+                    .zip(b.iter())
+                    .all(|(a, b)| matches!(&*a.borrow(), Self::Default(v) if *v == b))
+            },
+            (Self::Optional(a), Self::Optional(b)) => a == b,
+            (Self::Map(a), Self::Map(b)) => a == b,
+            (Self::Enum(a, a_type), Self::Enum(b, b_type)) => a == b && a_type == b_type,
+            (Self::Iterator(a), Self::Iterator(b)) => a == b,
+            _ => false
+        }
+    }
+}
+
 impl Hash for ValueCell {
     fn hash<H: Hasher>(&self, state: &mut H) {
-        self.hash_with_pointers(state, &mut HashSet::new());
+        self.hash_with_pointers(state, &mut HashSet::default());
     }
 }
 
@@ -92,7 +135,10 @@ impl From<Constant> for ValueCell {
         match value {
             Constant::Default(v) => Self::Default(v),
             Constant::Struct(fields, _type) => Self::Struct(fields.into_iter().map(|v| v.into()).collect(), _type),
-            Constant::Array(values) => Self::Array(values.into_iter().map(|v| v.into()).collect()),
+            Constant::Array(values) => match TypedArray::collect(values.iter().map(|v| v.as_value().ok())) {
+                Some(array) => Self::TypedArray(array),
+                None => Self::Array(values.into_iter().map(|v| v.into()).collect())
+            },
             Constant::Optional(value) => Self::Optional(value.map(|v| (*v).into())),
             Constant::Map(map) => Self::Map(map.into_iter().map(|(k, v)| (k.into(), v.into())).collect()),
             Constant::Enum(fields, _type) => Self::Enum(fields.into_iter().map(|v| v.into()).collect(), _type)
@@ -123,6 +169,11 @@ impl ValueCell {
                         .hash_with_pointers(state, tracked_pointers)
                     );
             },
+            // Hashed like a boxed array so both are equal as map keys
+            ValueCell::TypedArray(array) => {
+                array.iter()
+                    .for_each(|v| v.hash(state));
+            },
             ValueCell::Optional(v) => {
                 if let Some(v) = v {
                     v.borrow()
@@ -144,10 +195,117 @@ impl ValueCell {
                     .for_each(|field| field.borrow()
                         .hash_with_pointers(state, tracked_pointers)
                     );
+            },
+            // Only its values left are hashed
+            ValueCell::Iterator(iter) => {
+                let mut iter = iter.as_ref().clone();
+                while let Ok(Some(value)) = iter.next_value() {
+                    value.borrow()
+                        .hash_with_pointers(state, tracked_pointers);
+                }
             }
         }
     }
 
+    // Check if the value at the pointer is this value or one of its inner values
+    // The depth is limited as a value may reference itself
+    pub fn contains_ptr(&self, ptr: *const ValueCell, max_depth: usize) -> Result<bool, ValueError> {
+        // Prevent allocation if the value is a default value
+        if matches!(self, Self::Default(_)) {
+            return Ok(core::ptr::eq(self, ptr));
+        }
+
+        let mut stack = vec![(Path::Borrowed(self), 0)];
+        while let Some((next, depth)) = stack.pop() {
+            if depth > max_depth {
+                return Err(ValueError::MaxDepthReached);
+            }
+
+            let handle = next.as_ref();
+            let value = handle.as_value();
+            if core::ptr::eq(value, ptr) {
+                return Ok(true);
+            }
+
+            match value {
+                ValueCell::Default(_) | ValueCell::TypedArray(_) => {},
+                ValueCell::Array(values)
+                | ValueCell::Struct(values, _)
+                | ValueCell::Enum(values, _) => {
+                    for value in values {
+                        stack.push((Path::Wrapper(value.clone()), depth + 1));
+                    }
+                },
+                ValueCell::Iterator(iter) => {
+                    for value in iter.values() {
+                        stack.push((Path::Wrapper(value.clone()), depth + 1));
+                    }
+                },
+                ValueCell::Optional(opt) => {
+                    if let Some(value) = opt {
+                        stack.push((Path::Wrapper(value.clone()), depth + 1));
+                    }
+                },
+                // Keys are owned by the map, only the values can be shared
+                ValueCell::Map(map) => {
+                    for value in map.values() {
+                        stack.push((Path::Wrapper(value.clone()), depth + 1));
+                    }
+                }
+            };
+        }
+
+        Ok(false)
+    }
+
+    // Check if the value or one of its inner values is an iterator calling the chunks of a module
+    // The depth is limited as a value may reference itself
+    pub fn contains_callbacks(&self, max_depth: usize) -> Result<bool, ValueError> {
+        if matches!(self, Self::Default(_) | Self::TypedArray(_)) {
+            return Ok(false);
+        }
+
+        let mut stack = vec![(Path::Borrowed(self), 0)];
+        while let Some((next, depth)) = stack.pop() {
+            if depth > max_depth {
+                return Err(ValueError::MaxDepthReached);
+            }
+
+            let handle = next.as_ref();
+            match handle.as_value() {
+                ValueCell::Default(_) | ValueCell::TypedArray(_) => {},
+                ValueCell::Array(values)
+                | ValueCell::Struct(values, _)
+                | ValueCell::Enum(values, _) => {
+                    for value in values {
+                        stack.push((Path::Wrapper(value.clone()), depth + 1));
+                    }
+                },
+                ValueCell::Iterator(iter) => {
+                    if iter.has_callbacks() {
+                        return Ok(true);
+                    }
+
+                    for value in iter.values() {
+                        stack.push((Path::Wrapper(value.clone()), depth + 1));
+                    }
+                },
+                ValueCell::Optional(opt) => {
+                    if let Some(value) = opt {
+                        stack.push((Path::Wrapper(value.clone()), depth + 1));
+                    }
+                },
+                ValueCell::Map(map) => {
+                    for value in map.values() {
+                        stack.push((Path::Wrapper(value.clone()), depth + 1));
+                    }
+                }
+            };
+        }
+
+        Ok(false)
+    }
+
     // Calculate the depth of the value
     pub fn calculate_depth(&self, max_depth: usize) -> Result<usize, ValueError> {
         // Prevent allocation if the value is a default value
@@ -176,6 +334,15 @@ impl ValueCell {
                         stack.push((Path::Wrapper(value.clone()), depth + 1));
                     }
                 },
+                // Its values are primitives, one level deeper
+                ValueCell::TypedArray(array) => {
+                    if !array.is_empty() {
+                        if depth + 1 > max_depth {
+                            return Err(ValueError::MaxDepthReached);
+                        }
+                        biggest_depth = biggest_depth.max(depth + 1);
+                    }
+                },
                 ValueCell::Struct(fields, _) => {
                     for field in fields {
                         stack.push((Path::Wrapper(field.clone()), depth + 1));
@@ -196,6 +363,11 @@ impl ValueCell {
                     for field in fields {
                         stack.push((Path::Wrapper(field.clone()), depth + 1));
                     }
+                },
+                ValueCell::Iterator(iter) => {
+                    for value in iter.values() {
+                        stack.push((Path::Wrapper(value.clone()), depth + 1));
+                    }
                 }
             };
         }
@@ -203,6 +375,87 @@ impl ValueCell {
         Ok(biggest_depth)
     }
 
+    // Count the values and the bytes of the strings and blobs
+    // Used to price a copy of the value
+    // The depth is limited as a value may reference itself
+    #[inline]
+    pub fn memory_size(&self, max_depth: usize) -> Result<(usize, usize), ValueError> {
+        self.count_memory(max_depth, false)
+    }
+
+    // Same as memory_size, but a value also referenced elsewhere is only counted
+    // as a single value, its content isn't freed with this one
+    // The values shared with a copy are freed with the last of them
+    #[inline]
+    pub fn owned_memory_size(&self, max_depth: usize) -> Result<(usize, usize), ValueError> {
+        self.count_memory(max_depth, true)
+    }
+
+    fn count_memory(&self, max_depth: usize, owned: bool) -> Result<(usize, usize), ValueError> {
+        let mut stack = vec![(Path::Borrowed(self), 0)];
+        let mut count = 0usize;
+        let mut bytes = 0usize;
+
+        let push_sub = |stack: &mut Vec<(Path, usize)>, count: &mut usize, value: &SubValue, depth: usize| {
+            if owned && value.is_shared() {
+                *count += 1;
+            } else {
+                stack.push((Path::Wrapper(value.clone()), depth + 1));
+            }
+        };
+
+        while let Some((next, depth)) = stack.pop() {
+            if depth > max_depth {
+                return Err(ValueError::MaxDepthReached);
+            }
+
+            count += 1;
+            let handle = next.as_ref();
+            match handle.as_value() {
+                ValueCell::Default(Value::String(s)) => bytes = bytes.saturating_add(s.len()),
+                ValueCell::Default(Value::Blob(blob)) => bytes = bytes.saturating_add(blob.len()),
+                ValueCell::Default(_) => {},
+                // Each inline value is counted like a boxed one
+                ValueCell::TypedArray(array) => count = count.saturating_add(array.len()),
+                ValueCell::Array(values)
+                | ValueCell::Struct(values, _)
+                | ValueCell::Enum(values, _) => {
+                    if owned && values.is_shared() {
+                        continue;
+                    }
+
+                    for value in values {
+                        push_sub(&mut stack, &mut count, value, depth);
+                    }
+                },
+                ValueCell::Optional(opt) => {
+                    // Only the inner value is counted
+                    if let Some(value) = opt {
+                        count -= 1;
+                        push_sub(&mut stack, &mut count, value, depth);
+                    }
+                },
+                ValueCell::Map(map) => {
+                    if owned && map.is_shared() {
+                        continue;
+                    }
+
+                    for (k, v) in map {
+                        stack.push((Path::Owned(k.clone()), depth + 1));
+                        push_sub(&mut stack, &mut count, v, depth);
+                    }
+                },
+                ValueCell::Iterator(iter) => {
+                    for value in iter.values() {
+                        push_sub(&mut stack, &mut count, value, depth);
+                    }
+                }
+            };
+        }
+
+        Ok((count, bytes))
+    }
+
     #[inline]
     pub fn is_null(&self) -> bool {
         match &self {
@@ -284,6 +537,14 @@ impl ValueCell {
         }
     }
 
+    #[inline]
+    pub fn as_mut_string(&mut self) -> Result<&mut String, ValueError> {
+        match self {
+            Self::Default(Value::String(n)) => Ok(n),
+            v => Err(ValueError::InvalidValueCell(v.clone(), Type::String))
+        }
+    }
+
     #[inline]
     pub fn as_bool(&self) -> Result<bool, ValueError> {
         match self {
@@ -293,17 +554,17 @@ impl ValueCell {
     }
 
     #[inline]
-    pub fn as_map(&self) -> Result<&HashMap<Self, SubValue>, ValueError> {
+    pub fn as_map(&self) -> Result<&IndexMap<Self, SubValue>, ValueError> {
         match self {
-            Self::Map(map) => Ok(map),
+            Self::Map(map) => Ok(&**map),
             _ => Err(ValueError::ExpectedStruct)
         }
     }
 
     #[inline]
-    pub fn as_mut_map(&mut self) -> Result<&mut HashMap<Self, SubValue>, ValueError> {
+    pub fn as_mut_map(&mut self) -> Result<&mut IndexMap<Self, SubValue>, ValueError> {
         match self {
-            Self::Map(map) => Ok(map),
+            Self::Map(map) => Ok(map.make_mut()),
             _ => Err(ValueError::ExpectedStruct),
         }
     }
@@ -311,15 +572,16 @@ impl ValueCell {
     #[inline]
     pub fn as_vec<'a>(&'a self) -> Result<&'a Vec<SubValue>, ValueError> {
         match self {
-            Self::Array(n) => Ok(n),
+            Self::Array(n) => Ok(&**n),
             v => Err(ValueError::InvalidValueCell(v.clone(), Type::Array(Box::new(Type::Any))))
         }
     }
 
     #[inline]
     pub fn as_mut_vec<'a>(&'a mut self) -> Result<&'a mut Vec<SubValue>, ValueError> {
+        self.box_typed_array();
         match self {
-            Self::Array(n) => Ok(n),
+            Self::Array(n) => Ok(n.make_mut()),
             v => Err(ValueError::InvalidValueCell(v.clone(), Type::Array(Box::new(Type::Any))))
         }
     }
@@ -330,7 +592,7 @@ impl ValueCell {
             Self::Default(Value::Null) => None,
             Self::Optional(opt) => opt.take().map(SubValue::into_owned),
             v => {
-                let value = std::mem::take(v);
+                let value = core::mem::take(v);
                 Some(value)
             }
         }
@@ -403,7 +665,7 @@ impl ValueCell {
     #[inline]
     pub fn to_map(self) -> Result<Vec<SubValue>, ValueError> {
         match self {
-            Self::Struct(fields, _) => Ok(fields),
+            Self::Struct(fields, _) => Ok(fields.into_inner()),
             _ => Err(ValueError::ExpectedStruct)
         }
     }
@@ -411,7 +673,8 @@ impl ValueCell {
     #[inline]
     pub fn to_vec(self) -> Result<Vec<SubValue>, ValueError> {
         match self {
-            Self::Array(n) => Ok(n),
+            Self::Array(n) => Ok(n.into_inner()),
+            Self::TypedArray(array) => Ok(array.to_sub_values()),
             v => Err(ValueError::InvalidValueCell(v.clone(), Type::Array(Box::new(Type::Any))))
         }
     }
@@ -419,8 +682,10 @@ impl ValueCell {
     #[inline]
     pub fn to_sub_vec(self) -> Result<Vec<SubValue>, ValueError> {
         match self {
-            Self::Array(values) => Ok(values),
-            Self::Struct(fields, _) => Ok(fields),
+            Self::Array(values)
+            | Self::Struct(values, _)
+            | Self::Enum(values, _) => Ok(values.into_inner()),
+            Self::TypedArray(array) => Ok(array.to_sub_values()),
             _ => Err(ValueError::SubValue)
         }
     }
@@ -428,28 +693,110 @@ impl ValueCell {
     #[inline]
     pub fn as_sub_vec(&self) -> Result<&Vec<SubValue>, ValueError> {
         match self {
-            Self::Array(values) => Ok(values),
-            Self::Struct(fields, _) => Ok(fields),
+            Self::Array(values)
+            | Self::Struct(values, _)
+            | Self::Enum(values, _) => Ok(&**values),
             _ => Err(ValueError::SubValue)
         }
     }
 
     #[inline]
     pub fn as_mut_sub_vec(&mut self) -> Result<&mut Vec<SubValue>, ValueError> {
+        self.box_typed_array();
         match self {
-            Self::Array(values) => Ok(values),
-            Self::Struct(fields, _) => Ok(fields),
+            Self::Array(values)
+            | Self::Struct(values, _)
+            | Self::Enum(values, _) => Ok(values.make_mut()),
             _ => Err(ValueError::SubValue)
         }
     }
 
+    // Convert an inline array to a boxed one so its values can be referenced
+    fn box_typed_array(&mut self) {
+        if let Self::TypedArray(array) = self {
+            *self = Self::Array(array.to_sub_values().into());
+        }
+    }
+
+    // Build an array, the values are stored inline
+    // if they are all primitives of the same type
+    pub fn new_array(values: Vec<ValueCell>) -> Self {
+        match TypedArray::collect(values.iter().map(|v| v.as_value().ok())) {
+            Some(array) => Self::TypedArray(array),
+            None => Self::Array(values.into_iter().map(SubValue::new).collect())
+        }
+    }
+
+    // Get the length of an array, inline or not
     #[inline]
-    pub fn as_range(&self) -> Result<(&Value, &Value, &Type), ValueError> {
+    pub fn array_len(&self) -> Result<usize, ValueError> {
+        match self {
+            Self::Array(values) => Ok(values.len()),
+            Self::TypedArray(array) => Ok(array.len()),
+            v => Err(ValueError::InvalidValueCell(v.clone(), Type::Array(Box::new(Type::Any))))
+        }
+    }
+
+    // Clone the element at the index of an array
+    pub fn get_array_element(&self, index: usize) -> Option<ValueCell> {
+        match self {
+            Self::Array(values) => values.get(index).map(|v| v.borrow().clone()),
+            Self::TypedArray(array) => array.get(index).map(Self::Default),
+            _ => None
+        }
+    }
+
+    // Replace the element at the index of an array
+    // An inline array is boxed if the value can't be stored in it
+    pub fn set_array_element(&mut self, index: usize, value: ValueCell) -> Result<(), ValueError> {
+        if let (Self::TypedArray(array), Self::Default(v)) = (&mut *self, &value) {
+            if array.set(index, v) {
+                return Ok(())
+            }
+        }
+
+        let values = self.as_mut_vec()?;
+        let len = values.len();
+        let element = values.get(index)
+            .ok_or(ValueError::OutOfBounds(index, len))?;
+        *element.borrow_mut() = value;
+
+        Ok(())
+    }
+
+    // Push a value at the end of an array
+    // An empty array becomes inline when a primitive is pushed
+    pub fn push_array_element(&mut self, value: ValueCell) -> Result<(), ValueError> {
+        if let Self::Default(v) = &value {
+            let pushed = match self {
+                Self::TypedArray(array) => array.push(v),
+                Self::Array(values) if values.is_empty() => match TypedArray::for_value(v) {
+                    Some(mut array) => {
+                        array.push(v);
+                        *self = Self::TypedArray(array);
+                        true
+                    },
+                    None => false
+                },
+                _ => false
+            };
+
+            if pushed {
+                return Ok(())
+            }
+        }
+
+        self.as_mut_vec()?.push(value.into());
+        Ok(())
+    }
+
+    #[inline]
+    pub fn as_range(&self) -> Result<(&Value, &Value, &Type, bool), ValueError> {
         self.as_value().and_then(Value::as_range)
     }
 
     #[inline]
-    pub fn to_range(self) -> Result<(Value, Value, Type), ValueError> {
+    pub fn to_range(self) -> Result<(Value, Value, Type, bool), ValueError> {
         self.into_value().and_then(Value::to_range)
     }
 
@@ -498,7 +845,7 @@ impl ValueCell {
 
     // Cast the value to the expected type
     pub fn mut_checked_cast_to_primitive_type(&mut self, expected: &Type) -> Result<(), ValueError> {
-        let take = std::mem::take(self);
+        let take = core::mem::take(self);
         let value = take.checked_cast_to_primitive_type(expected)?;
         *self = value;
         Ok(())
@@ -524,10 +871,10 @@ impl ValueCell {
                 }
             },
             Type::Range(inner) => {
-                let (start, end, _) = self.to_range()?;
+                let (start, end, _, inclusive) = self.to_range()?;
                 let start = start.checked_cast_to_primitive_type(inner)?;
                 let end = end.checked_cast_to_primitive_type(inner)?;
-                Ok(Value::Range(Box::new(start), Box::new(end), *inner.clone()))
+                Ok(Value::Range(Box::new(start), Box::new(end), *inner.clone(), inclusive))
             },
             _ => Err(ValueError::InvalidCastType(expected.clone()))
         }.map(Self::Default)
@@ -627,40 +974,46 @@ impl ValueCell {
         }
     }
 
-    // Clone all the SubValue into a new ValueCell
-    // We need to do it in iterative way to prevent any stackoverflow
+    // Copy the value so it doesn't reference any other value
+    // The values of the arrays, structs, enums and maps are shared with the copy
+    // until one of them is mutated, except those referenced by another variable
     pub fn into_owned(self) -> Self {
         match self {
             Self::Default(v) => Self::Default(v),
-            Self::Struct(fields, _type) => {
-                let mut new_fields = Vec::with_capacity(fields.len());
-                for field in fields {
-                    new_fields.push(field.into_owned().into());
-                }
-                Self::Struct(new_fields, _type)
-            },
-            Self::Array(values) => {
-                let mut new_values = Vec::with_capacity(values.len());
-                for value in values {
-                    new_values.push(value.into_owned().into());
-                }
-                Self::Array(new_values)
-            },
+            Self::Struct(fields, _type) => Self::Struct(fields.into_owned(), _type),
+            Self::Array(values) => Self::Array(values.into_owned()),
+            Self::TypedArray(array) => Self::TypedArray(array),
             Self::Optional(value) => Self::Optional(value.map(|v| v.into_owned().into())),
-            Self::Map(map) => {
-                let mut new_map = HashMap::with_capacity(map.len());
-                for (k, v) in map {
-                    new_map.insert(k.into_owned(), v.into_owned().into());
-                }
-                Self::Map(new_map)
-            },
-            Self::Enum(fields, _type) => {
-                let mut new_fields = Vec::with_capacity(fields.len());
-                for field in fields {
-                    new_fields.push(field.into_owned().into());
-                }
-                Self::Enum(new_fields, _type)
-            }
+            Self::Map(map) => Self::Map(map.into_owned()),
+            Self::Enum(fields, _type) => Self::Enum(fields.into_owned(), _type),
+            Self::Iterator(iter) => Self::Iterator(Box::new(iter.into_owned()))
+        }
+    }
+
+    // Copy a value whose inner values can't be referenced elsewhere
+    // Used to copy the values shared with another copy
+    fn share(&self) -> Self {
+        match self {
+            Self::Struct(fields, _type) => Self::Struct(fields.share(), _type.clone()),
+            Self::Array(values) => Self::Array(values.share()),
+            Self::Optional(value) => Self::Optional(value.as_ref().map(|v| v.borrow().share().into())),
+            Self::Map(map) => Self::Map(map.share()),
+            Self::Enum(fields, _type) => Self::Enum(fields.share(), _type.clone()),
+            v => v.clone().into_owned()
+        }
+    }
+
+    // Is no inner value referenced elsewhere, so the value can be shared with a copy
+    // The values held by an iterator are referenced by it
+    fn is_exclusive(&self) -> bool {
+        match self {
+            Self::Default(_) | Self::TypedArray(_) => true,
+            Self::Struct(values, _)
+            | Self::Array(values)
+            | Self::Enum(values, _) => values.is_exclusive(),
+            Self::Optional(value) => value.as_ref().is_none_or(|v| !v.is_shared() && v.borrow().is_exclusive()),
+            Self::Map(map) => map.is_exclusive(),
+            Self::Iterator(_) => false
         }
     }
 }
@@ -677,6 +1030,10 @@ impl fmt::Display for ValueCell {
                 let s: Vec<String> = values.iter().map(|v| format!("{}", v.borrow())).collect();
                 write!(f, "[{}]", s.join(", "))
             },
+            Self::TypedArray(array) => {
+                let s: Vec<String> = array.iter().map(|v| format!("{}", v)).collect();
+                write!(f, "[{}]", s.join(", "))
+            },
             Self::Optional(value) => match value.as_ref() {
                 Some(value) => write!(f, "optional<{}>", value.borrow().to_string()),
                 None => write!(f, "optional<null>")
@@ -688,7 +1045,8 @@ impl fmt::Display for ValueCell {
             Self::Enum(fields, enum_type) => {
                 let s: Vec<String> = fields.iter().enumerate().map(|(k, v)| format!("{}: {}", k, v.borrow())).collect();
                 write!(f, "enum{:?} {} {} {}", enum_type, "{", s.join(", "), "}")
-            }
+            },
+            Self::Iterator(iter) => write!(f, "iterator<{} left>", iter.len())
         }
     }
 }
@@ -701,21 +1059,56 @@ mod tests {
 
     #[test]
     fn test_max_depth() {
-        let mut map = ValueCell::Map(HashMap::new());
+        let mut map = ValueCell::Map(Default::default());
         for _ in 0..100 {
-            let mut inner_map = HashMap::new();
+            let mut inner_map = IndexMap::default();
             inner_map.insert(Value::U8(10).into(), SubValue::new(map));
-            map = ValueCell::Map(inner_map);
+            map = ValueCell::Map(inner_map.into());
         }
 
         assert!(matches!(map.calculate_depth(100), Ok(100)));
         assert!(matches!(map.calculate_depth(99), Err(ValueError::MaxDepthReached)));
     }
 
+    #[test]
+    fn test_copy_on_write() {
+        let inner = ValueCell::Array(vec![Value::String("a".to_owned()).into()].into());
+        let value = SubValue::new(ValueCell::Array(vec![inner.into(), Value::Null.into()].into()));
+        let is_shared = |value: &ValueCell| matches!(value, ValueCell::Array(values) if values.is_shared());
+
+        // The copy shares the values until one of them is mutated
+        let copy = value.borrow().clone();
+        assert!(is_shared(&copy));
+        assert!(is_shared(&value.borrow()));
+
+        // Referencing an element copies the values shared
+        let mut element = Path::Wrapper(value.reference()).get_sub_variable(0).unwrap();
+        assert!(!is_shared(&value.borrow()));
+        assert!(!is_shared(&copy));
+
+        element.as_mut()
+            .as_value_mut()
+            .push_array_element(Value::String("b".to_owned()).into())
+            .unwrap();
+        assert_eq!(value.borrow().as_vec().unwrap()[0].borrow().array_len().unwrap(), 2);
+        assert_eq!(copy.as_vec().unwrap()[0].borrow().array_len().unwrap(), 1);
+
+        // A value referenced elsewhere isn't shared with a copy
+        let second = value.borrow().clone();
+        assert!(!is_shared(&second));
+        assert!(!is_shared(&value.borrow()));
+        assert_eq!(second, *value.borrow());
+
+        // Its elements are shared once no longer referenced
+        drop(element);
+        let third = value.borrow().clone();
+        assert!(is_shared(&third));
+    }
+
     #[test]
     fn test_recursive_cycle() {
         // Create a map that contains itself
-        let map = SubValue::new(ValueCell::Map(HashMap::new()));
+        let map = SubValue::new(ValueCell::Map(Default::default()));
         {
             let mut m = map.borrow_mut();
             m.as_mut_map()
@@ -724,18 +1117,18 @@ mod tests {
         }
 
         let owned = map.into_owned();
-        let mut inner_map = HashMap::new();
+        let mut inner_map: IndexMap<_, _> = IndexMap::default();
         inner_map.insert(ValueCellWrapper(owned), Value::U8(10));
     }
 
     #[test]
     fn test_std_hash() {
         // Create a map that contains a map that contains a map...
-        let mut map = ValueCell::Map(HashMap::new());
+        let mut map = ValueCell::Map(Default::default());
         for _ in 0..28000 {
-            let mut inner_map = HashMap::new();
+            let mut inner_map = IndexMap::default();
             inner_map.insert(Value::U8(10).into(), map.into());
-            map = ValueCell::Map(inner_map);
+            map = ValueCell::Map(inner_map.into());
         }
 
         println!("Map");
@@ -1,18 +1,52 @@
-use std::{
+use core::{
     fmt,
-    cell::{Ref, RefMut},
     ops::{Deref, DerefMut}
 };
-use crate::ValueCell;
+use crate::{
+    sync::{Ref, RefMut},
+    ValueCell
+};
 
 pub enum ValueHandle<'a> {
     Borrowed(&'a ValueCell),
-    Ref(Ref<'a, ValueCell>)
+    Ref(Ref<'a, ValueCell>),
+    // Copy of an element stored inline in an array
+    Owned(ValueCell)
 }
 
 pub enum ValueHandleMut<'a> {
     Borrowed(&'a mut ValueCell),
-    RefMut(RefMut<'a, ValueCell>)
+    RefMut(RefMut<'a, ValueCell>),
+    Element(ElementHandle<'a>)
+}
+
+// Mutable handle on an element stored inline in an array
+// The element is copied and written back in the array once dropped
+pub struct ElementHandle<'a> {
+    array: RefMut<'a, ValueCell>,
+    index: usize,
+    value: ValueCell
+}
+
+impl<'a> ElementHandle<'a> {
+    pub fn new(array: RefMut<'a, ValueCell>, index: usize) -> Self {
+        let value = array.get_array_element(index)
+            .unwrap_or_default();
+
+        Self {
+            array,
+            index,
+            value
+        }
+    }
+}
+
+impl Drop for ElementHandle<'_> {
+    fn drop(&mut self) {
+        let value = core::mem::take(&mut self.value);
+        // The element can't be out of bounds as the array is borrowed since its copy
+        let _ = self.array.set_array_element(self.index, value);
+    }
 }
 
 impl<'a> ValueHandle<'a> {
@@ -20,7 +54,8 @@ impl<'a> ValueHandle<'a> {
     pub fn as_value<'b>(&'b self) -> &'b ValueCell {
         match self {
             Self::Borrowed(v) => v,
-            Self::Ref(v) => v
+            Self::Ref(v) => v,
+            Self::Owned(v) => v
         }
     }
 }
@@ -30,7 +65,8 @@ impl<'a> ValueHandleMut<'a> {
     pub fn as_value(&self) -> &ValueCell {
         match self {
             Self::Borrowed(v) => v,
-            Self::RefMut(v) => v
+            Self::RefMut(v) => v,
+            Self::Element(v) => &v.value
         }
     }
 
@@ -38,7 +74,8 @@ impl<'a> ValueHandleMut<'a> {
     pub fn as_value_mut(&mut self) -> &mut ValueCell {
         match self {
             Self::Borrowed(v) => v,
-            Self::RefMut(v) => v
+            Self::RefMut(v) => v,
+            Self::Element(v) => &mut v.value
         }
     }
 }
@@ -2,6 +2,7 @@ mod handle;
 
 use crate::{values::ValueError, SubValue, Value};
 pub use handle::{
+    ElementHandle,
     ValueHandle,
     ValueHandleMut
 };
@@ -13,7 +14,9 @@ pub enum Path<'a> {
     Owned(ValueCell),
     // Used for constants
     Borrowed(&'a ValueCell),
-    Wrapper(SubValue)
+    Wrapper(SubValue),
+    // Element at the index of a shared array stored inline
+    Element(SubValue, usize)
 }
 
 impl<'a> Path<'a> {
@@ -37,7 +40,7 @@ impl<'a> Path<'a> {
     pub fn shareable(&mut self) -> Path<'a> {
         match self {
             Self::Owned(v) => {
-                let dst = std::mem::take(v);
+                let dst = core::mem::take(v);
                 let inner = SubValue::new(dst);
                 *self = Self::Wrapper(inner.reference());
                 Self::Wrapper(inner)
@@ -47,13 +50,29 @@ impl<'a> Path<'a> {
                 *self = Self::Wrapper(shared.reference());
                 Self::Wrapper(shared)
             },
-            Self::Wrapper(v) => Self::Wrapper(v.reference())
+            Self::Wrapper(v) => Self::Wrapper(v.reference()),
+            Self::Element(v, index) => Self::Element(v.reference(), *index)
         }
     }
 
     // Get the sub value of the path
     pub fn get_sub_variable(self, index: usize) -> Result<Path<'a>, ValueError> {
         match self {
+            // An inline element can't be referenced, it's copied
+            // except for a shared array to write it back on assignment
+            Self::Owned(ValueCell::TypedArray(ref array)) | Self::Borrowed(&ValueCell::TypedArray(ref array)) => {
+                array.get(index)
+                    .map(Path::from)
+                    .ok_or_else(|| ValueError::OutOfBounds(index, array.len()))
+            },
+            Self::Wrapper(v) if matches!(*v.borrow(), ValueCell::TypedArray(_)) => {
+                let len = v.borrow().array_len()?;
+                if index >= len {
+                    return Err(ValueError::OutOfBounds(index, len))
+                }
+
+                Ok(Path::Element(v, index))
+            },
             Self::Owned(v) => {
                 let mut values = v.to_sub_vec()?;
                 let len = values.len();
@@ -64,6 +83,7 @@ impl<'a> Path<'a> {
                 let at_index = values.remove(index);
                 Ok(Path::Wrapper(at_index))
             },
+            // The value can't be mutated, the element is copied
             Self::Borrowed(v) => {
                 let values = v.as_sub_vec()?;
                 let len = values.len();
@@ -71,18 +91,20 @@ impl<'a> Path<'a> {
                     .get(index)
                     .ok_or_else(|| ValueError::OutOfBounds(index, len))?;
 
-                Ok(Path::Wrapper(at_index.reference()))
+                Ok(Path::Owned(at_index.borrow().clone().into_owned()))
             },
+            // The values shared with a copy are copied before the element is referenced
             Self::Wrapper(v) => {
-                let values = v.borrow();
-                let values = values.as_sub_vec()?;
+                let mut values = v.borrow_mut();
+                let values = values.as_mut_sub_vec()?;
                 let len = values.len();
                 let at_index = values
                     .get(index)
                     .ok_or_else(|| ValueError::OutOfBounds(index, len))?;
 
                 Ok(Path::Wrapper(at_index.reference()))
-            }
+            },
+            Self::Element(..) => Path::Owned(self.into_owned()).get_sub_variable(index)
         }
     }
 
@@ -92,7 +114,20 @@ impl<'a> Path<'a> {
         match self {
             Self::Owned(v) => v,
             Self::Borrowed(v) => v.clone(),
-            Self::Wrapper(v) => v.into_owned()
+            Self::Wrapper(v) => v.into_owned(),
+            Self::Element(v, index) => v.borrow()
+                .get_array_element(index)
+                .unwrap_or_default()
+        }
+    }
+
+    // Get the value of the path as a shared one
+    // A constant or an inline element is copied
+    #[inline]
+    pub fn into_sub_value(self) -> SubValue {
+        match self {
+            Self::Wrapper(v) => v,
+            path => SubValue::new(path.into_owned())
         }
     }
 
@@ -102,7 +137,11 @@ impl<'a> Path<'a> {
         match self {
             Self::Owned(v) => ValueHandle::Borrowed(v),
             Self::Borrowed(v) => ValueHandle::Borrowed(v),
-            Self::Wrapper(v) => ValueHandle::Ref(v.borrow())
+            Self::Wrapper(v) => ValueHandle::Ref(v.borrow()),
+            Self::Element(v, index) => ValueHandle::Owned(v.borrow()
+                .get_array_element(*index)
+                .unwrap_or_default()
+            )
         }
     }
 
@@ -119,7 +158,8 @@ impl<'a> Path<'a> {
                     _ => unreachable!()
                 }
             },
-            Self::Wrapper(v) => ValueHandleMut::RefMut(v.borrow_mut())
+            Self::Wrapper(v) => ValueHandleMut::RefMut(v.borrow_mut()),
+            Self::Element(v, index) => ValueHandleMut::Element(ElementHandle::new(v.borrow_mut(), *index))
         }
     }
 
@@ -0,0 +1,203 @@
+use core::ops::{Deref, DerefMut};
+use alloc::vec::Vec;
+use crate::{sync::Shared, IndexMap};
+use super::{SubValue, ValueCell};
+
+// Inner values of a boxed array, a struct, an enum or a map
+pub trait SubValues: Sized {
+    // Copy the values, those referenced elsewhere are copied deeply
+    fn copy(&self) -> Self;
+
+    // Copy the values of a shared collection
+    // They can't be referenced elsewhere, so only their top level is copied
+    fn share(&self) -> Self;
+
+    // Is no value referenced outside of this collection
+    fn is_exclusive(&self) -> bool;
+}
+
+impl SubValues for Vec<SubValue> {
+    fn copy(&self) -> Self {
+        self.iter()
+            .map(|v| SubValue::new(v.borrow().clone().into_owned()))
+            .collect()
+    }
+
+    fn share(&self) -> Self {
+        self.iter()
+            .map(|v| SubValue::new(v.borrow().share()))
+            .collect()
+    }
+
+    fn is_exclusive(&self) -> bool {
+        self.iter().all(is_exclusive)
+    }
+}
+
+impl SubValues for IndexMap<ValueCell, SubValue> {
+    fn copy(&self) -> Self {
+        self.iter()
+            .map(|(k, v)| (k.clone(), SubValue::new(v.borrow().clone().into_owned())))
+            .collect()
+    }
+
+    fn share(&self) -> Self {
+        self.iter()
+            .map(|(k, v)| (k.share(), SubValue::new(v.borrow().share())))
+            .collect()
+    }
+
+    fn is_exclusive(&self) -> bool {
+        self.values().all(is_exclusive)
+    }
+}
+
+#[inline]
+fn is_exclusive(value: &SubValue) -> bool {
+    !value.is_shared() && value.borrow().is_exclusive()
+}
+
+// Values shared between the copies of a value and copied on the first mutation
+// Copying a value walks it once to verify none of its values is referenced
+// elsewhere, the values are then shared until one of the copies is mutated
+// A shared value can't be referenced by a variable: the values are copied
+// before one is handed out, and a value referenced elsewhere is never shared
+#[derive(Debug)]
+pub struct SharedValues<T: SubValues>(Shared<T>);
+
+impl<T: SubValues> SharedValues<T> {
+    #[inline]
+    pub fn new(values: T) -> Self {
+        Self(Shared::new(values))
+    }
+
+    // Are the values shared with another copy
+    #[inline]
+    pub fn is_shared(&self) -> bool {
+        Shared::strong_count(&self.0) > 1
+    }
+
+    // Can the values be shared with a copy
+    #[inline]
+    pub fn is_exclusive(&self) -> bool {
+        self.is_shared() || self.0.is_exclusive()
+    }
+
+    // Get the values to mutate them or to reference one of them
+    // They are copied if shared with another copy
+    #[inline]
+    pub fn make_mut(&mut self) -> &mut T {
+        if self.is_shared() {
+            self.0 = Shared::new(self.0.share());
+        }
+
+        Shared::get_mut(&mut self.0)
+            .expect("values are not shared")
+    }
+
+    // Take the values, copied if shared with another copy
+    #[inline]
+    pub fn into_inner(self) -> T {
+        match Shared::try_unwrap(self.0) {
+            Ok(values) => values,
+            Err(values) => values.share()
+        }
+    }
+
+    // Take the values if this is their last owner
+    #[inline]
+    pub fn try_into_inner(self) -> Option<T> {
+        Shared::try_unwrap(self.0).ok()
+    }
+
+    // Same as clone, but the values are moved if they can't be shared
+    #[inline]
+    pub fn into_owned(self) -> Self {
+        if self.is_exclusive() {
+            self
+        } else {
+            Self::new(self.0.copy())
+        }
+    }
+
+    // Reference the values of a collection known to be exclusive
+    #[inline]
+    pub(super) fn share(&self) -> Self {
+        Self(self.0.clone())
+    }
+}
+
+// A copy shares the values unless one of them is referenced elsewhere
+// Unless they are already shared, all the values are walked
+impl<T: SubValues> Clone for SharedValues<T> {
+    fn clone(&self) -> Self {
+        if self.is_exclusive() {
+            self.share()
+        } else {
+            Self::new(self.0.copy())
+        }
+    }
+}
+
+impl<T: SubValues> Deref for SharedValues<T> {
+    type Target = T;
+
+    #[inline]
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl<T: SubValues> DerefMut for SharedValues<T> {
+    #[inline]
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        self.make_mut()
+    }
+}
+
+impl<T: SubValues + PartialEq> PartialEq for SharedValues<T> {
+    fn eq(&self, other: &Self) -> bool {
+        Shared::ptr_eq(&self.0, &other.0) || *self.0 == *other.0
+    }
+}
+
+impl<T: SubValues + Eq> Eq for SharedValues<T> {}
+
+impl<T: SubValues> From<T> for SharedValues<T> {
+    fn from(values: T) -> Self {
+        Self::new(values)
+    }
+}
+
+impl<T: SubValues + Default> Default for SharedValues<T> {
+    fn default() -> Self {
+        Self::new(T::default())
+    }
+}
+
+impl<A, T: SubValues + FromIterator<A>> FromIterator<A> for SharedValues<T> {
+    fn from_iter<I: IntoIterator<Item = A>>(iter: I) -> Self {
+        Self::new(iter.into_iter().collect())
+    }
+}
+
+impl<T: SubValues + IntoIterator> IntoIterator for SharedValues<T> {
+    type Item = T::Item;
+    type IntoIter = T::IntoIter;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.into_inner().into_iter()
+    }
+}
+
+impl<'a, T: SubValues> IntoIterator for &'a SharedValues<T>
+where
+    &'a T: IntoIterator
+{
+    type Item = <&'a T as IntoIterator>::Item;
+    type IntoIter = <&'a T as IntoIterator>::IntoIter;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.0.as_ref().into_iter()
+    }
+}
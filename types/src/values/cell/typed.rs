@@ -0,0 +1,274 @@
+use alloc::vec::Vec;
+use crate::{sync::Shared, Type, Value};
+use super::{SubValue, ValueCell};
+
+// Dispatch an expression on the inner vector of the typed array
+macro_rules! dispatch {
+    ($self: expr, $values: ident => $expr: expr) => {
+        match $self {
+            TypedArray::U8($values) => $expr,
+            TypedArray::U16($values) => $expr,
+            TypedArray::U32($values) => $expr,
+            TypedArray::U64($values) => $expr,
+        }
+    };
+}
+
+// Array of primitive values stored inline
+// This prevents to allocate a SubValue per element for the most used arrays
+// It is converted to a boxed array as soon as an element must be referenced
+// The values are shared between the copies of the array and cloned on the first mutation:
+// as they can't be referenced, copying the array is O(1)
+#[derive(Debug, Clone, Eq)]
+pub enum TypedArray {
+    U8(Shared<Vec<u8>>),
+    U16(Shared<Vec<u16>>),
+    U32(Shared<Vec<u32>>),
+    U64(Shared<Vec<u64>>),
+}
+
+impl TypedArray {
+    // Create an empty typed array able to store the value
+    pub fn for_value(value: &Value) -> Option<Self> {
+        Some(match value {
+            Value::U8(_) => Self::U8(Default::default()),
+            Value::U16(_) => Self::U16(Default::default()),
+            Value::U32(_) => Self::U32(Default::default()),
+            Value::U64(_) => Self::U64(Default::default()),
+            _ => return None
+        })
+    }
+
+    // Collect the values into a typed array
+    // The first value determines the type, None is returned
+    // if the array is empty or if any value is of another type
+    pub fn collect<'a, I>(values: I) -> Option<Self>
+    where
+        I: IntoIterator<Item = Option<&'a Value>>
+    {
+        let mut values = values.into_iter();
+        let first = values.next()??;
+
+        let mut array = Self::for_value(first)?;
+        array.reserve(values.size_hint().0 + 1);
+        array.push(first).then_some(())?;
+        for value in values {
+            array.push(value?).then_some(())?;
+        }
+
+        Some(array)
+    }
+
+    // Type of the elements
+    pub fn get_type(&self) -> Type {
+        match self {
+            Self::U8(_) => Type::U8,
+            Self::U16(_) => Type::U16,
+            Self::U32(_) => Type::U32,
+            Self::U64(_) => Type::U64,
+        }
+    }
+
+    #[inline]
+    pub fn len(&self) -> usize {
+        dispatch!(self, values => values.len())
+    }
+
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    // Size of the values in bytes
+    #[inline]
+    pub fn byte_len(&self) -> usize {
+        dispatch!(self, values => core::mem::size_of_val(values.as_slice()))
+    }
+
+    #[inline]
+    pub fn reserve(&mut self, additional: usize) {
+        dispatch!(self, values => Shared::make_mut(values).reserve(additional))
+    }
+
+    // Are the values shared with another copy of the array
+    #[inline]
+    pub fn is_shared(&self) -> bool {
+        dispatch!(self, values => Shared::strong_count(values) > 1)
+    }
+
+    // Get the value at the index
+    #[inline]
+    pub fn get(&self, index: usize) -> Option<Value> {
+        Some(match self {
+            Self::U8(values) => Value::U8(*values.get(index)?),
+            Self::U16(values) => Value::U16(*values.get(index)?),
+            Self::U32(values) => Value::U32(*values.get(index)?),
+            Self::U64(values) => Value::U64(*values.get(index)?),
+        })
+    }
+
+    // Set the value at the index
+    // Returns false if the value isn't of the array type or if the index is out of bounds
+    pub fn set(&mut self, index: usize, value: &Value) -> bool {
+        match (self, value) {
+            (Self::U8(values), Value::U8(v)) if index < values.len() => Shared::make_mut(values)[index] = *v,
+            (Self::U16(values), Value::U16(v)) if index < values.len() => Shared::make_mut(values)[index] = *v,
+            (Self::U32(values), Value::U32(v)) if index < values.len() => Shared::make_mut(values)[index] = *v,
+            (Self::U64(values), Value::U64(v)) if index < values.len() => Shared::make_mut(values)[index] = *v,
+            _ => return false
+        };
+
+        true
+    }
+
+    // Push a value at the end
+    // Returns false if the value isn't of the array type
+    pub fn push(&mut self, value: &Value) -> bool {
+        match (self, value) {
+            (Self::U8(values), Value::U8(v)) => Shared::make_mut(values).push(*v),
+            (Self::U16(values), Value::U16(v)) => Shared::make_mut(values).push(*v),
+            (Self::U32(values), Value::U32(v)) => Shared::make_mut(values).push(*v),
+            (Self::U64(values), Value::U64(v)) => Shared::make_mut(values).push(*v),
+            _ => return false
+        };
+
+        true
+    }
+
+    // Remove the last value
+    pub fn pop(&mut self) -> Option<Value> {
+        if self.is_empty() {
+            return None
+        }
+
+        Some(match self {
+            Self::U8(values) => Value::U8(Shared::make_mut(values).pop()?),
+            Self::U16(values) => Value::U16(Shared::make_mut(values).pop()?),
+            Self::U32(values) => Value::U32(Shared::make_mut(values).pop()?),
+            Self::U64(values) => Value::U64(Shared::make_mut(values).pop()?),
+        })
+    }
+
+    // Remove the value at the index, shifting all the values after it
+    pub fn remove(&mut self, index: usize) -> Option<Value> {
+        if index >= self.len() {
+            return None
+        }
+
+        Some(match self {
+            Self::U8(values) => Value::U8(Shared::make_mut(values).remove(index)),
+            Self::U16(values) => Value::U16(Shared::make_mut(values).remove(index)),
+            Self::U32(values) => Value::U32(Shared::make_mut(values).remove(index)),
+            Self::U64(values) => Value::U64(Shared::make_mut(values).remove(index)),
+        })
+    }
+
+    // Check if the array contains the value
+    pub fn contains(&self, value: &Value) -> bool {
+        match (self, value) {
+            (Self::U8(values), Value::U8(v)) => values.contains(v),
+            (Self::U16(values), Value::U16(v)) => values.contains(v),
+            (Self::U32(values), Value::U32(v)) => values.contains(v),
+            (Self::U64(values), Value::U64(v)) => values.contains(v),
+            _ => false
+        }
+    }
+
+    // Iterate over the values
+    pub fn iter(&self) -> impl Iterator<Item = Value> + '_ {
+        (0..self.len()).filter_map(|i| self.get(i))
+    }
+
+    // Get the bytes of an u8 array
+    #[inline]
+    pub fn as_bytes(&self) -> Option<&[u8]> {
+        match self {
+            Self::U8(values) => Some(values),
+            _ => None
+        }
+    }
+
+    // Box each value so they can be referenced
+    pub fn to_sub_values(&self) -> Vec<SubValue> {
+        self.iter()
+            .map(|v| SubValue::new(ValueCell::Default(v)))
+            .collect()
+    }
+}
+
+impl PartialEq for TypedArray {
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (Self::U8(a), Self::U8(b)) => a == b,
+            (Self::U16(a), Self::U16(b)) => a == b,
+            (Self::U32(a), Self::U32(b)) => a == b,
+            (Self::U64(a), Self::U64(b)) => a == b,
+            // Two empty arrays are equal whatever their type is
+            _ => self.is_empty() && other.is_empty()
+        }
+    }
+}
+
+impl From<Vec<u8>> for TypedArray {
+    fn from(values: Vec<u8>) -> Self {
+        Self::U8(Shared::new(values))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_collect() {
+        let values = [Value::U8(1), Value::U8(2)];
+        let array = TypedArray::collect(values.iter().map(Some)).unwrap();
+        assert_eq!(array, TypedArray::from(vec![1, 2]));
+        assert_eq!(array.get_type(), Type::U8);
+
+        // Mixed types and non primitive values stay boxed
+        let values = [Value::U8(1), Value::U16(2)];
+        assert!(TypedArray::collect(values.iter().map(Some)).is_none());
+        assert!(TypedArray::collect([Some(&Value::U8(1)), None]).is_none());
+        assert!(TypedArray::collect([Some(&Value::Boolean(true))]).is_none());
+        assert!(TypedArray::collect(core::iter::empty()).is_none());
+    }
+
+    #[test]
+    fn test_typed_array() {
+        let mut array = TypedArray::U64(Shared::new(vec![1, 2, 3]));
+        assert!(array.set(0, &Value::U64(10)));
+        assert!(!array.set(0, &Value::U8(10)));
+        assert!(!array.set(3, &Value::U64(10)));
+
+        assert!(array.push(&Value::U64(4)));
+        assert!(!array.push(&Value::U32(4)));
+        assert!(array.contains(&Value::U64(4)));
+        assert!(!array.contains(&Value::U32(4)));
+
+        assert_eq!(array.remove(1), Some(Value::U64(2)));
+        assert_eq!(array.remove(3), None);
+        assert_eq!(array.pop(), Some(Value::U64(4)));
+        assert_eq!(array.iter().collect::<Vec<_>>(), vec![Value::U64(10), Value::U64(3)]);
+        assert_eq!(array.to_sub_values(), vec![SubValue::from(Value::U64(10)), SubValue::from(Value::U64(3))]);
+    }
+
+    #[test]
+    fn test_copy_on_write() {
+        let mut array = TypedArray::from(vec![1, 2, 3]);
+        let copy = array.clone();
+        assert!(array.is_shared());
+        assert_eq!(array.as_bytes().map(<[u8]>::as_ptr), copy.as_bytes().map(<[u8]>::as_ptr));
+
+        // The values are cloned on the first mutation only
+        assert!(array.set(0, &Value::U8(10)));
+        assert!(!array.is_shared());
+        assert!(!copy.is_shared());
+        assert_eq!(array, TypedArray::from(vec![10, 2, 3]));
+        assert_eq!(copy, TypedArray::from(vec![1, 2, 3]));
+
+        let ptr = array.as_bytes().map(<[u8]>::as_ptr);
+        assert!(array.set(1, &Value::U8(20)));
+        assert_eq!(array.as_bytes().map(<[u8]>::as_ptr), ptr);
+    }
+}
@@ -1,8 +1,10 @@
-use std::{collections::HashMap, fmt, hash::{Hash, Hasher}};
-use crate::{EnumValueType, StructType, Type, U256};
+use core::{fmt, hash::{Hash, Hasher}};
+use alloc::{boxed::Box, format, string::{String, ToString}, vec, vec::Vec};
+use crate::{EnumValueType, IndexMap, StructType, Type, U256};
 use super::{Value, ValueCell, ValueError};
 
 #[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum Constant {
     Default(Value),
     Struct(Vec<Constant>, StructType),
@@ -11,7 +13,7 @@ pub enum Constant {
 
     // Use box directly because the range are primitive only
     // Map cannot be used as a key in another map
-    Map(HashMap<Constant, Constant>),
+    Map(IndexMap<Constant, Constant>),
     Enum(Vec<Constant>, EnumValueType),
 }
 
@@ -25,7 +27,7 @@ impl Drop for ConstantWrapper {
             return
         }
 
-        let mut stack = vec![std::mem::take(&mut self.0)];
+        let mut stack = vec![core::mem::take(&mut self.0)];
         while let Some(value) = stack.pop() {
             match value {
                 Constant::Default(_) => {},
@@ -99,9 +101,18 @@ impl From<ValueCell> for Constant {
             ValueCell::Default(v) => Self::Default(v),
             ValueCell::Struct(fields, struct_type) => Self::Struct(fields.into_iter().map(|v| v.into_owned().into()).collect(), struct_type),
             ValueCell::Array(values) => Self::Array(values.into_iter().map(|v| v.into_owned().into()).collect()),
+            ValueCell::TypedArray(array) => Self::Array(array.iter().map(Self::Default).collect()),
             ValueCell::Optional(opt) => Self::Optional(opt.map(|v| Box::new(v.into_owned().into()))),
             ValueCell::Map(map) => Self::Map(map.into_iter().map(|(k, v)| (k.into(), v.into_owned().into())).collect()),
             ValueCell::Enum(fields, enum_type) => Self::Enum(fields.into_iter().map(|v| v.into_owned().into()).collect(), enum_type),
+            // Only the values left are kept
+            ValueCell::Iterator(mut iter) => {
+                let mut values = Vec::new();
+                while let Ok(Some(value)) = iter.next_value() {
+                    values.push(value.into_owned().into());
+                }
+                Self::Array(values)
+            },
         }
     }
 }
@@ -196,7 +207,7 @@ impl Constant {
     }
 
     #[inline]
-    pub fn as_map(&self) -> Result<&HashMap<Self, Self>, ValueError> {
+    pub fn as_map(&self) -> Result<&IndexMap<Self, Self>, ValueError> {
         match self {
             Self::Map(map) => Ok(map),
             _ => Err(ValueError::ExpectedStruct)
@@ -204,7 +215,7 @@ impl Constant {
     }
 
     #[inline]
-    pub fn as_mut_map(&mut self) -> Result<&mut HashMap<Self, Self>, ValueError> {
+    pub fn as_mut_map(&mut self) -> Result<&mut IndexMap<Self, Self>, ValueError> {
         match self {
             Self::Map(map) => Ok(map),
             _ => Err(ValueError::ExpectedStruct)
@@ -367,12 +378,12 @@ impl Constant {
     }
 
     #[inline]
-    pub fn as_range(&self) -> Result<(&Value, &Value, &Type), ValueError> {
+    pub fn as_range(&self) -> Result<(&Value, &Value, &Type, bool), ValueError> {
         self.as_value().and_then(Value::as_range)
     }
 
     #[inline]
-    pub fn to_range(self) -> Result<(Value, Value, Type), ValueError> {
+    pub fn to_range(self) -> Result<(Value, Value, Type, bool), ValueError> {
         self.into_value().and_then(Value::to_range)
     }
 
@@ -412,7 +423,7 @@ impl Constant {
 
     // Cast the value to the expected type
     pub fn mut_checked_cast_to_primitive_type(&mut self, expected: &Type) -> Result<(), ValueError> {
-        let take = std::mem::take(self);
+        let take = core::mem::take(self);
         let value = take.checked_cast_to_primitive_type(expected)?;
         *self = value;
         Ok(())
@@ -438,10 +449,10 @@ impl Constant {
                 }
             },
             Type::Range(inner) => {
-                let (start, end, _) = self.to_range()?;
+                let (start, end, _, inclusive) = self.to_range()?;
                 let start = start.checked_cast_to_primitive_type(inner)?;
                 let end = end.checked_cast_to_primitive_type(inner)?;
-                Ok(Value::Range(Box::new(start), Box::new(end), *inner.clone()))
+                Ok(Value::Range(Box::new(start), Box::new(end), *inner.clone(), inclusive))
             },
             _ => Err(ValueError::InvalidCastType(expected.clone()))
         }.map(Self::Default)
@@ -540,6 +551,73 @@ impl Constant {
             _ => Err(ValueError::InvalidValueType(self, Type::Any))
         }
     }
+
+    // Verify that the constant is a value of the type
+    // The generic types accept any value, like `any`
+    pub fn is_of_type(&self, _type: &Type) -> bool {
+        let mut stack = vec![(self, _type)];
+        while let Some((value, _type)) = stack.pop() {
+            let valid = match (value, _type) {
+                (_, Type::Any | Type::T(_)) => true,
+                (Self::Default(Value::Null), Type::Optional(_)) => true,
+                (Self::Default(Value::Range(_, _, inner, _)), Type::Range(expected)) => inner == expected.as_ref(),
+                (Self::Default(v), _type) => Type::from_value(v).is_some_and(|t| t == *_type),
+                (Self::Optional(None), Type::Optional(_)) => true,
+                (Self::Optional(Some(v)), Type::Optional(inner)) => {
+                    stack.push((v, inner));
+                    true
+                },
+                (Self::Array(values), Type::Array(inner)) => {
+                    stack.extend(values.iter().map(|v| (v, inner.as_ref())));
+                    true
+                },
+                // Tuples are stored as arrays
+                (Self::Array(values), Type::Tuple(types)) if values.len() == types.len() => {
+                    stack.extend(values.iter().zip(types));
+                    true
+                },
+                (Self::Map(map), Type::Map(key, value)) => {
+                    for (k, v) in map {
+                        stack.push((k, key));
+                        stack.push((v, value));
+                    }
+                    true
+                },
+                (Self::Struct(fields, struct_type), Type::Struct(expected)) if struct_type == expected && fields.len() == expected.fields().len() => {
+                    stack.extend(fields.iter().zip(expected.fields()));
+                    true
+                },
+                (Self::Enum(fields, enum_type), Type::Enum(expected)) if enum_type.enum_type() == expected => {
+                    match expected.get_variant(enum_type.variant_id()) {
+                        Some(variant) if variant.fields().len() == fields.len() => {
+                            stack.extend(fields.iter().zip(variant.fields()));
+                            true
+                        },
+                        _ => false
+                    }
+                },
+                // Results are stored as the built-in enum, `Ok` first then `Err`
+                (Self::Enum(fields, enum_type), Type::Result(value, error)) if fields.len() == 1 => match enum_type.variant_id() {
+                    0 => {
+                        stack.push((&fields[0], value));
+                        true
+                    },
+                    1 => {
+                        stack.push((&fields[0], error));
+                        true
+                    },
+                    _ => false
+                },
+                _ => false
+            };
+
+            if !valid {
+                return false
+            }
+        }
+
+        true
+    }
 }
 
 impl fmt::Display for Constant {
@@ -595,7 +673,7 @@ mod tests {
     fn test_std_hash_map_as_key() {
         let mut map = Constant::Map(Default::default());
         for _ in 0..5000 {
-            let mut m = HashMap::new();
+            let mut m = IndexMap::default();
             m.insert(map, Constant::Default(Value::U8(0)));
             map = Constant::Map(m);
         }
@@ -607,4 +685,66 @@ mod tests {
         let _wrapped = ConstantWrapper(map);
         drop(_wrapped);
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_is_of_type() {
+        use crate::{EnumType, EnumVariant};
+
+        let struct_type = StructType::new(0, vec![Type::U64, Type::Optional(Box::new(Type::String))]);
+        let point = Constant::Struct(vec![
+            Constant::Default(Value::U64(1)),
+            Constant::Default(Value::Null)
+        ], struct_type.clone());
+        assert!(point.is_of_type(&Type::Struct(struct_type.clone())));
+        assert!(point.is_of_type(&Type::Any));
+        assert!(!point.is_of_type(&Type::Struct(StructType::new(1, vec![Type::U64, Type::String]))));
+
+        let values = Constant::Array(vec![Constant::Default(Value::U8(1)), Constant::Default(Value::U8(2))]);
+        assert!(values.is_of_type(&Type::Array(Box::new(Type::U8))));
+        assert!(!values.is_of_type(&Type::Array(Box::new(Type::U16))));
+        assert!(values.is_of_type(&Type::Tuple(vec![Type::U8, Type::U8])));
+        assert!(!values.is_of_type(&Type::Tuple(vec![Type::U8])));
+
+        let enum_type = EnumType::new(1, vec![EnumVariant::new(vec![]), EnumVariant::new(vec![Type::Struct(struct_type)])]);
+        let variant = Constant::Enum(vec![point], EnumValueType::new(enum_type.clone(), 1));
+        assert!(variant.is_of_type(&Type::Enum(enum_type.clone())));
+        assert!(!Constant::Enum(vec![], EnumValueType::new(enum_type.clone(), 1)).is_of_type(&Type::Enum(enum_type)));
+
+        let result = Type::Result(Box::new(Type::U64), Box::new(Type::String));
+        let result_type = EnumType::new(2, vec![EnumVariant::new(vec![Type::T(0)]), EnumVariant::new(vec![Type::T(1)])]);
+        assert!(Constant::Enum(vec![Constant::Default(Value::String("error".to_owned()))], EnumValueType::new(result_type.clone(), 1)).is_of_type(&result));
+        assert!(!Constant::Enum(vec![Constant::Default(Value::String("value".to_owned()))], EnumValueType::new(result_type, 0)).is_of_type(&result));
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_serde_json() {
+        use crate::{EnumType, EnumVariant};
+
+        let struct_type = StructType::new(0, vec![Type::U256, Type::Optional(Box::new(Type::String))]);
+        let enum_type = EnumType::new(1, vec![EnumVariant::new(vec![Type::Struct(struct_type.clone())])]);
+
+        let _type = Type::Map(Box::new(Type::U8), Box::new(Type::Enum(enum_type.clone())));
+        let json = serde_json::to_string(&_type).unwrap();
+        let decoded: Type = serde_json::from_str(&json).unwrap();
+        assert_eq!(decoded, _type);
+        // Equality is done on the id only, verify the fields are restored too
+        match decoded.get_generic_type(1) {
+            Some(Type::Enum(e)) => assert_eq!(e.variants(), enum_type.variants()),
+            _ => panic!("invalid map value type")
+        }
+
+        let constant = Constant::Enum(vec![
+            Constant::Struct(vec![
+                Constant::Default(Value::U256(U256::MAX)),
+                Constant::Optional(Some(Box::new(Constant::Default(Value::String("hello".to_owned())))))
+            ], struct_type)
+        ], EnumValueType::new(enum_type, 0));
+        let json = serde_json::to_string(&constant).unwrap();
+        assert_eq!(serde_json::from_str::<Constant>(&json).unwrap(), constant);
+
+        let value = Value::Range(Box::new(Value::U64(0)), Box::new(Value::U64(10)), Type::U64, false);
+        let json = serde_json::to_string(&value).unwrap();
+        assert_eq!(serde_json::from_str::<Value>(&json).unwrap(), value);
+    }
+}
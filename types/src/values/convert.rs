@@ -0,0 +1,172 @@
+use alloc::{boxed::Box, string::String, vec::Vec};
+use crate::{Type, U256};
+use super::{Constant, Value, ValueCell, ValueError};
+
+// Convert a VM value into a Rust type
+// Used by the natives to read their parameters
+pub trait FromXelisValue: Sized {
+    fn from_xelis_value(value: ValueCell) -> Result<Self, ValueError>;
+}
+
+// Convert a Rust type into a VM value
+// The expected type is required to build the structs, whose types are registered in the environment
+pub trait IntoXelisValue {
+    fn into_xelis_value(self, _type: &Type) -> Result<ValueCell, ValueError>;
+}
+
+impl FromXelisValue for ValueCell {
+    fn from_xelis_value(value: ValueCell) -> Result<Self, ValueError> {
+        Ok(value)
+    }
+}
+
+impl FromXelisValue for Constant {
+    fn from_xelis_value(value: ValueCell) -> Result<Self, ValueError> {
+        Ok(value.into())
+    }
+}
+
+impl IntoXelisValue for ValueCell {
+    fn into_xelis_value(self, _: &Type) -> Result<ValueCell, ValueError> {
+        Ok(self)
+    }
+}
+
+macro_rules! primitive {
+    ($t: ty, $variant: ident, $to: ident, $type: expr) => {
+        impl FromXelisValue for $t {
+            fn from_xelis_value(value: ValueCell) -> Result<Self, ValueError> {
+                value.$to()
+            }
+        }
+
+        impl IntoXelisValue for $t {
+            fn into_xelis_value(self, _type: &Type) -> Result<ValueCell, ValueError> {
+                let value = Value::$variant(self);
+                if !matches!(_type, Type::Any | Type::T(_)) && *_type != $type {
+                    return Err(ValueError::InvalidValue(value, _type.clone()))
+                }
+
+                Ok(value.into())
+            }
+        }
+    };
+}
+
+primitive!(u8, U8, to_u8, Type::U8);
+primitive!(u16, U16, to_u16, Type::U16);
+primitive!(u32, U32, to_u32, Type::U32);
+primitive!(u64, U64, to_u64, Type::U64);
+primitive!(u128, U128, to_u128, Type::U128);
+primitive!(U256, U256, to_u256, Type::U256);
+primitive!(bool, Boolean, to_bool, Type::Bool);
+primitive!(String, String, to_string, Type::String);
+
+impl<T: FromXelisValue> FromXelisValue for Vec<T> {
+    fn from_xelis_value(value: ValueCell) -> Result<Self, ValueError> {
+        value.to_vec()?
+            .into_iter()
+            .map(|v| T::from_xelis_value(v.into_owned()))
+            .collect()
+    }
+}
+
+impl<T: IntoXelisValue> IntoXelisValue for Vec<T> {
+    fn into_xelis_value(self, _type: &Type) -> Result<ValueCell, ValueError> {
+        let inner = match _type {
+            Type::Array(inner) => inner.as_ref(),
+            Type::Any | Type::T(_) => &Type::Any,
+            _ => return Err(ValueError::InvalidCastType(_type.clone()))
+        };
+
+        let values = self.into_iter()
+            .map(|v| v.into_xelis_value(inner).map(Into::into))
+            .collect::<Result<_, _>>()?;
+        Ok(ValueCell::Array(values))
+    }
+}
+
+impl<T: FromXelisValue> FromXelisValue for Option<T> {
+    fn from_xelis_value(mut value: ValueCell) -> Result<Self, ValueError> {
+        if !matches!(value, ValueCell::Optional(_) | ValueCell::Default(Value::Null)) {
+            return Err(ValueError::InvalidValueCell(value, Type::Optional(Box::new(Type::Any))))
+        }
+
+        value.take_as_optional()
+            .map(T::from_xelis_value)
+            .transpose()
+    }
+}
+
+impl<T: IntoXelisValue> IntoXelisValue for Option<T> {
+    fn into_xelis_value(self, _type: &Type) -> Result<ValueCell, ValueError> {
+        let inner = match _type {
+            Type::Optional(inner) => inner.as_ref(),
+            Type::Any | Type::T(_) => &Type::Any,
+            _ => return Err(ValueError::InvalidCastType(_type.clone()))
+        };
+
+        let value = self.map(|v| v.into_xelis_value(inner).map(Into::into))
+            .transpose()?;
+        Ok(ValueCell::Optional(value))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use xelis_types_derive::{FromXelisValue, IntoXelisValue};
+    use crate::StructType;
+    use super::*;
+
+    #[derive(Debug, Clone, PartialEq, FromXelisValue, IntoXelisValue)]
+    struct Account {
+        name: String,
+        balance: u64,
+        tags: Vec<String>,
+    }
+
+    #[derive(Debug, Clone, PartialEq, FromXelisValue, IntoXelisValue)]
+    struct Wrapper(Account, Option<u8>);
+
+    #[test]
+    fn test_primitives() {
+        let value = 42u64.into_xelis_value(&Type::U64).unwrap();
+        assert_eq!(u64::from_xelis_value(value).unwrap(), 42);
+
+        // The expected type must match
+        assert!(42u64.into_xelis_value(&Type::U8).is_err());
+        assert!(u8::from_xelis_value(Value::U64(1).into()).is_err());
+    }
+
+    #[test]
+    fn test_containers() {
+        let _type = Type::Array(Box::new(Type::Optional(Box::new(Type::String))));
+        let values = vec![Some("a".to_owned()), None];
+        let value = values.clone().into_xelis_value(&_type).unwrap();
+        assert_eq!(Vec::<Option<String>>::from_xelis_value(value).unwrap(), values);
+
+        assert!(Option::<u8>::from_xelis_value(Value::U8(1).into()).is_err());
+    }
+
+    #[test]
+    fn test_derive() {
+        let account_type = StructType::new(0, vec![Type::String, Type::U64, Type::Array(Box::new(Type::String))]);
+        let wrapper_type = Type::Struct(StructType::new(1, vec![Type::Struct(account_type.clone()), Type::Optional(Box::new(Type::U8))]));
+
+        let account = Account {
+            name: "alice".to_owned(),
+            balance: 100,
+            tags: vec!["admin".to_owned()],
+        };
+        let wrapper = Wrapper(account.clone(), Some(1));
+
+        let value = wrapper.clone().into_xelis_value(&wrapper_type).unwrap();
+        assert_eq!(Wrapper::from_xelis_value(value).unwrap(), wrapper);
+
+        // Fields must match the struct type
+        assert!(account.clone().into_xelis_value(&Type::U64).is_err());
+        let invalid_type = Type::Struct(StructType::new(2, vec![Type::String, Type::U8, Type::Array(Box::new(Type::String))]));
+        assert!(account.into_xelis_value(&invalid_type).is_err());
+        assert!(Account::from_xelis_value(Value::U64(1).into()).is_err());
+    }
+}
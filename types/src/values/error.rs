@@ -20,10 +20,16 @@ pub enum ValueError {
     InvalidCastType(Type),
     #[error("Operation not supported on non-number type")]
     OperationNotNumberType,
+    #[error("Arithmetic overflow")]
+    Overflow,
     #[error("Sub value")]
     SubValue,
     #[error("Optional value is null")]
     OptionalIsNull,
+    #[error("Result value is an error")]
+    ResultIsErr,
+    #[error("Result value is not an error")]
+    ResultIsOk,
     #[error("Value out of bounds: {0} on {1}")]
     OutOfBounds(usize, usize),
     #[error("Cast error")]
@@ -32,4 +38,16 @@ pub enum ValueError {
     InvalidPrimitiveType,
     #[error("Invalid unknown type")]
     UnknownType,
+    #[error("Value cannot be encoded")]
+    NotEncodable,
+    #[error("Invalid encoded value")]
+    InvalidEncoding,
+    #[error("Range is too large to be iterated")]
+    RangeTooLarge,
+    #[error("Iterator step must be greater than zero")]
+    InvalidStep,
+    #[error("Iterator can't be reversed once a value was read or after a positional adapter following a filter")]
+    IteratorNotReversible,
+    #[error("Iterator values must go through the chunks of its adapters first")]
+    UnresolvedIterator,
 }
@@ -2,12 +2,13 @@ mod error;
 mod pointer;
 mod cell;
 mod constant;
+mod convert;
 
-use std::{
-    borrow::Cow,
+use core::{
     cmp::Ordering,
     hash::{Hash, Hasher}
 };
+use alloc::{borrow::Cow, boxed::Box, string::{String, ToString}, vec::Vec};
 use super::{
     Type,
     U256
@@ -17,6 +18,7 @@ pub use pointer::*;
 pub use cell::*;
 pub use error::*;
 pub use constant::*;
+pub use convert::*;
 
 macro_rules! checked_cast {
     ($self: expr, $type: expr) => {
@@ -35,6 +37,7 @@ macro_rules! checked_cast {
 
 // This enum is dedicated for constants values / parser
 #[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum Value {
     Null,
     // number types
@@ -46,7 +49,8 @@ pub enum Value {
     U256(U256),
     String(String),
     Boolean(bool),
-    Range(Box<Value>, Box<Value>, Type),
+    // start, end, type of the bounds, end included
+    Range(Box<Value>, Box<Value>, Type, bool),
     // Blob represents a binary data
     Blob(Vec<u8>),
 }
@@ -101,11 +105,12 @@ impl Hash for Value {
                 8.hash(state);
                 n.hash(state);
             },
-            Value::Range(start, end, range_type) => {
+            Value::Range(start, end, range_type, inclusive) => {
                 9.hash(state);
                 start.hash(state);
                 end.hash(state);
                 range_type.hash(state);
+                inclusive.hash(state);
             },
             Value::Blob(n) => {
                 10.hash(state);
@@ -267,17 +272,17 @@ impl Value {
     }
 
     #[inline]
-    pub fn as_range(&self) -> Result<(&Value, &Value, &Type), ValueError> {
+    pub fn as_range(&self) -> Result<(&Value, &Value, &Type, bool), ValueError> {
         match self {
-            Value::Range(start, end, _type) => Ok((start, end, _type)),
+            Value::Range(start, end, _type, inclusive) => Ok((start, end, _type, *inclusive)),
             v => Err(ValueError::InvalidValue(v.clone(), Type::Range(Box::new(Type::Any))))
         }
     }
 
     #[inline]
-    pub fn to_range(self) -> Result<(Value, Value, Type), ValueError> {
+    pub fn to_range(self) -> Result<(Value, Value, Type, bool), ValueError> {
         match self {
-            Value::Range(start, end, _type) => Ok((*start, *end, _type)),
+            Value::Range(start, end, _type, inclusive) => Ok((*start, *end, _type, inclusive)),
             v => Err(ValueError::InvalidValue(v.clone(), Type::Range(Box::new(Type::Any))))
         }
     }
@@ -291,28 +296,44 @@ impl Value {
         }
     }
 
-    // Increment the value
+    // Increment the value, an overflow is an error
     pub fn increment(&mut self) -> Result<(), ValueError> {
-        Ok(match self {
-            Value::U8(n) => *n += 1,
-            Value::U16(n) => *n += 1,
-            Value::U32(n) => *n += 1,
-            Value::U64(n) => *n += 1,
-            Value::U128(n) => *n += 1,
-            Value::U256(n) => *n += U256::ONE,
+        match self {
+            Value::U8(n) => *n = n.checked_add(1).ok_or(ValueError::Overflow)?,
+            Value::U16(n) => *n = n.checked_add(1).ok_or(ValueError::Overflow)?,
+            Value::U32(n) => *n = n.checked_add(1).ok_or(ValueError::Overflow)?,
+            Value::U64(n) => *n = n.checked_add(1).ok_or(ValueError::Overflow)?,
+            Value::U128(n) => *n = n.checked_add(1).ok_or(ValueError::Overflow)?,
+            Value::U256(n) => *n = n.checked_add(U256::ONE).ok_or(ValueError::Overflow)?,
             _ => return Err(ValueError::OperationNotNumberType)
-        })
+        };
+        Ok(())
     }
 
-    // Decrement the value
+    // Decrement the value, an underflow is an error
     pub fn decrement(&mut self) -> Result<(), ValueError> {
+        match self {
+            Value::U8(n) => *n = n.checked_sub(1).ok_or(ValueError::Overflow)?,
+            Value::U16(n) => *n = n.checked_sub(1).ok_or(ValueError::Overflow)?,
+            Value::U32(n) => *n = n.checked_sub(1).ok_or(ValueError::Overflow)?,
+            Value::U64(n) => *n = n.checked_sub(1).ok_or(ValueError::Overflow)?,
+            Value::U128(n) => *n = n.checked_sub(1).ok_or(ValueError::Overflow)?,
+            Value::U256(n) => *n = n.checked_sub(U256::ONE).ok_or(ValueError::Overflow)?,
+            _ => return Err(ValueError::OperationNotNumberType)
+        };
+        Ok(())
+    }
+
+    // Logical not for a bool, bitwise not for a number
+    pub fn not(&self) -> Result<Value, ValueError> {
         Ok(match self {
-            Value::U8(n) => *n -= 1,
-            Value::U16(n) => *n -= 1,
-            Value::U32(n) => *n -= 1,
-            Value::U64(n) => *n -= 1,
-            Value::U128(n) => *n -= 1,
-            Value::U256(n) => *n -= U256::ONE,
+            Value::Boolean(v) => Value::Boolean(!v),
+            Value::U8(n) => Value::U8(!n),
+            Value::U16(n) => Value::U16(!n),
+            Value::U32(n) => Value::U32(!n),
+            Value::U64(n) => Value::U64(!n),
+            Value::U128(n) => Value::U128(!n),
+            Value::U256(n) => Value::U256(!*n),
             _ => return Err(ValueError::OperationNotNumberType)
         })
     }
@@ -350,7 +371,7 @@ impl Value {
 
     // Cast the value to the expected type
     pub fn mut_checked_cast_to_primitive_type(&mut self, expected: &Type) -> Result<(), ValueError> {
-        let take = std::mem::take(self);
+        let take = core::mem::take(self);
         let value = take.checked_cast_to_primitive_type(expected)?;
         *self = value;
         Ok(())
@@ -369,10 +390,10 @@ impl Value {
             Type::String => self.cast_to_string().map(Value::String),
             Type::Bool => self.cast_to_bool().map(Value::Boolean),
             Type::Range(inner) => {
-                let (start, end, _) = self.to_range()?;
+                let (start, end, _, inclusive) = self.to_range()?;
                 let start = start.checked_cast_to_primitive_type(inner)?;
                 let end = end.checked_cast_to_primitive_type(inner)?;
-                Ok(Value::Range(Box::new(start), Box::new(end), *inner.clone()))
+                Ok(Value::Range(Box::new(start), Box::new(end), *inner.clone(), inclusive))
             },
             _ => Err(ValueError::InvalidCastType(expected.clone()))
         }
@@ -527,8 +548,8 @@ impl Value {
     }
 }
 
-impl std::fmt::Display for Value {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+impl core::fmt::Display for Value {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
         match self {
             Value::Null => write!(f, "null"),
             Value::U8(v) => write!(f, "{}", v),
@@ -539,7 +560,8 @@ impl std::fmt::Display for Value {
             Value::U256(v) => write!(f, "{}", v),
             Value::String(s) => write!(f, "{}", s),
             Value::Boolean(b) => write!(f, "{}", b),
-            Value::Range(start, end, _) => write!(f, "{}..{}", start, end),
+            Value::Range(start, end, _, false) => write!(f, "{}..{}", start, end),
+            Value::Range(start, end, _, true) => write!(f, "{}..={}", start, end),
             Value::Blob(b) => write!(f, "{:?}", b),
         }
     }
@@ -0,0 +1,134 @@
+use core::{cell::RefCell, mem};
+use crate::{
+    sync::{Lock, Shared},
+    ValueCell
+};
+
+type Cell = Shared<Lock<ValueCell>>;
+
+// Arena of the values created during an execution
+// The allocation of a dropped SubValue is recycled for the next one,
+// and its value is kept until the end of the execution to be freed at once
+// Values are freed iteratively, so even a huge nested value can't overflow the stack
+#[derive(Default)]
+struct Arena {
+    // Allocations available for the next values
+    free: Vec<Cell>,
+    // Values dropped during the execution
+    dropped: Vec<ValueCell>,
+}
+
+thread_local! {
+    static ARENA: RefCell<Arena> = RefCell::default();
+    // Put in place of an allocation moved out of its SubValue
+    static PLACEHOLDER: Cell = Shared::new(Lock::new(ValueCell::default()));
+}
+
+// Allocate a value, reusing a recycled allocation if any
+#[inline]
+pub(super) fn alloc(value: ValueCell) -> Cell {
+    match ARENA.try_with(|arena| arena.borrow_mut().free.pop()) {
+        Ok(Some(cell)) => {
+            *cell.borrow_mut() = value;
+            cell
+        },
+        _ => Shared::new(Lock::new(value))
+    }
+}
+
+// Get a placeholder to move the allocation out of a SubValue
+#[inline]
+pub(super) fn placeholder() -> Cell {
+    PLACEHOLDER.with(Shared::clone)
+}
+
+// Recycle the allocation if this is the last reference to it
+pub(super) fn recycle(cell: &mut Cell) {
+    if Shared::strong_count(cell) != 1 || Shared::weak_count(cell) != 0 {
+        return
+    }
+
+    let Ok(placeholder) = PLACEHOLDER.try_with(Shared::clone) else {
+        return
+    };
+
+    let cell = mem::replace(cell, placeholder);
+    let value = cell.take();
+    // Values without sub values are cheap to drop right now
+    let value = match value {
+        ValueCell::Default(_) | ValueCell::TypedArray(_) => {
+            drop(value);
+            None
+        },
+        value => Some(value)
+    };
+
+    // Nothing must be dropped while the arena is borrowed
+    let _ = ARENA.try_with(move |arena| {
+        let mut arena = arena.borrow_mut();
+        arena.free.push(cell);
+        if let Some(value) = value {
+            arena.dropped.push(value);
+        }
+    });
+}
+
+// Free all the values dropped since the last call
+// and the recycled allocations left
+pub fn release() {
+    // Dropping a value recycles its sub values into the arena
+    while let Some(value) = ARENA.with(|arena| arena.borrow_mut().dropped.pop()) {
+        drop(value);
+    }
+
+    let free = ARENA.with(|arena| mem::take(&mut arena.borrow_mut().free));
+    drop(free);
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{SubValue, Value};
+    use super::*;
+
+    fn arena_len() -> (usize, usize) {
+        ARENA.with(|arena| {
+            let arena = arena.borrow();
+            (arena.free.len(), arena.dropped.len())
+        })
+    }
+
+    #[test]
+    fn test_recycle() {
+        release();
+
+        let value = SubValue::new(ValueCell::Array(vec![Value::U8(1).into(), Value::U8(2).into()].into()));
+        let reference = value.reference();
+        let ptr = value.as_ptr();
+        drop(value);
+        // Still referenced
+        assert_eq!(arena_len(), (0, 0));
+
+        drop(reference);
+        assert_eq!(arena_len(), (1, 1));
+
+        // The allocation is reused
+        let value = SubValue::new(Value::U8(3).into());
+        assert_eq!(value.as_ptr(), ptr);
+        assert_eq!(*value.borrow(), ValueCell::Default(Value::U8(3)));
+
+        release();
+        assert_eq!(arena_len(), (0, 0));
+    }
+
+    #[test]
+    fn test_release_nested() {
+        let mut value = ValueCell::Default(Value::Null);
+        for _ in 0..1_000_000 {
+            value = ValueCell::Array(vec![value.into()].into());
+        }
+
+        drop(value);
+        release();
+        assert_eq!(arena_len(), (0, 0));
+    }
+}
@@ -1,6 +1,5 @@
-use std::rc::Rc;
-
-use crate::{values::cell::ValueCell, ValueHandle, ValueHandleMut};
+use alloc::boxed::Box;
+use crate::{sync::Shared, values::cell::ValueCell, ValueHandle, ValueHandleMut};
 use super::{SubValue, ValuePointer};
 
 #[derive(Debug, Hash, Clone, PartialEq, Eq)]
@@ -22,11 +21,11 @@ impl ValuePointerInner {
     pub fn take_value(self) -> ValueCell {
         match self {
             Self::Owned(v) => *v,
-            Self::Shared(v) => match Rc::try_unwrap(v.into_inner()) {
+            Self::Shared(v) => match Shared::try_unwrap(v.into_inner()) {
                 Ok(value) => value.into_inner(),
                 Err(rc) => {
                     let mut value = rc.borrow_mut();
-                    std::mem::take(&mut value)
+                    core::mem::take(&mut value)
                 }
             }
         }
@@ -36,7 +35,7 @@ impl ValuePointerInner {
     pub fn into_value(self) -> ValueCell {
         match self {
             Self::Owned(v) => *v,
-            Self::Shared(v) => match Rc::try_unwrap(v.into_inner()) {
+            Self::Shared(v) => match Shared::try_unwrap(v.into_inner()) {
                 Ok(value) => value.into_inner(),
                 Err(rc) => rc.borrow().clone()
             }
@@ -47,7 +46,7 @@ impl ValuePointerInner {
     pub fn into_owned(self) -> ValuePointer {
         ValuePointer(match self {
             Self::Owned(_) => self,
-            Self::Shared(v) => Self::Owned(Box::new(match Rc::try_unwrap(v.into_inner()) {
+            Self::Shared(v) => Self::Owned(Box::new(match Shared::try_unwrap(v.into_inner()) {
                 Ok(value) => value.into_inner(),
                 Err(rc) => rc.borrow().clone()
             }))
@@ -58,7 +57,7 @@ impl ValuePointerInner {
     pub fn transform(&mut self) -> ValuePointer {
         ValuePointer(match self {
             Self::Owned(v) => {
-                let dst = std::mem::take(v);
+                let dst = core::mem::take(v);
                 let shared = Self::Shared(SubValue::new(*dst));
                 *self = shared.clone();
                 shared
@@ -1,14 +1,19 @@
 mod sub_value;
 mod inner;
+#[cfg(feature = "arena")]
+pub mod arena;
 
-use std::{
-    collections::HashSet,
+use core::{
     hash::{Hash, Hasher},
     ops::{Deref, DerefMut},
     ptr,
 };
+use alloc::boxed::Box;
 
+use crate::HashSet;
 use super::ValueCell;
+#[cfg(feature = "value_pointer_drop")]
+use super::ValueCellWrapper;
 
 pub use sub_value::SubValue;
 pub use inner::ValuePointerInner;
@@ -41,7 +46,7 @@ impl ValuePointer {
     // Take the value, even if it's shared, replace it by Null
     #[inline(always)]
     pub fn take_value(&mut self) -> ValueCell {
-        let v = std::mem::take(&mut self.0);
+        let v = core::mem::take(&mut self.0);
         v.take_value()
     }
 
@@ -72,14 +77,14 @@ impl ValuePointer {
     // Convert into a owned Pointer to fully own the value
     #[inline(always)]
     pub fn into_owned(&mut self) -> ValuePointer {
-        let v = std::mem::take(&mut self.0);
+        let v = core::mem::take(&mut self.0);
         v.into_owned()
     }
 
     // Get the owned value or clone it if it's shared
     #[inline(always)]
-    pub fn into_value(&mut self) -> Value {
-        let v = std::mem::take(&mut self.0);
+    pub fn into_value(&mut self) -> ValueCell {
+        let v = core::mem::take(&mut self.0);
         v.into_value()
     }
 }
@@ -119,47 +124,23 @@ impl From<ValueCell> for ValuePointer {
 impl Hash for ValuePointer {
     fn hash<H: Hasher>(&self, state: &mut H) {
         self.0.handle()
-            .hash_with_pointers(state, &mut HashSet::new());
+            .hash_with_pointers(state, &mut HashSet::default());
     }
 }
 
 #[cfg(feature = "value_pointer_drop")]
 impl Drop for ValuePointer {
     fn drop(&mut self) {
-        let ptr = self.get_value_ptr();
-        let mut stack = vec![self.into_value()];
-
-        // Only take the value from the Rc<RefCell>> if it's the same pointer
-        fn fetch_value(mut pointer: ValuePointer, ptr: *const Value) -> Value {
-            if pointer.get_value_ptr() == ptr {
-                pointer.take_value()
-            } else {
-                pointer.into_value()
-            }
-        }
-
-        while let Some(value) = stack.pop() {
-            match value {
-                Value::Map(map) => {
-                    for (k, v) in map {
-                        stack.push(k);
-                        stack.push(fetch_value(v, ptr));
-                    }
-                },
-                Value::Array(array) => {
-                    stack.extend(array.into_iter().map(|v| fetch_value(v, ptr)));
-                },
-                Value::Optional(Some(v)) => {
-                    stack.push(fetch_value(v, ptr));
-                },
-                Value::Struct(fields, _) => {
-                    stack.extend(fields.into_iter().map(|v| fetch_value(v, ptr)));
-                },
-                Value::Enum(fields, _) => {
-                    stack.extend(fields.into_iter().map(|v| fetch_value(v, ptr)));
-                }
-                _ => {}
+        // Only the last reference to a shared value drops it
+        let value = match core::mem::take(&mut self.0) {
+            ValuePointerInner::Owned(v) => *v,
+            ValuePointerInner::Shared(v) => match crate::sync::Shared::try_unwrap(v.into_inner()) {
+                Ok(value) => value.into_inner(),
+                Err(_) => return
             }
-        }
+        };
+
+        // Dropped iteratively by the wrapper
+        drop(ValueCellWrapper(value));
     }
-}
\ No newline at end of file
+}
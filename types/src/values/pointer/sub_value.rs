@@ -1,17 +1,25 @@
-use std::{
-    cell::{Ref, RefCell, RefMut},
-    hash::{Hash, Hasher},
-    rc::Rc
+use core::hash::{Hash, Hasher};
+use crate::{
+    sync::{Lock, Ref, RefMut, Shared},
+    Value,
+    ValueCell,
+    Constant
 };
-use crate::{Value, ValueCell, Constant};
+#[cfg(feature = "arena")]
+use super::arena;
 
 #[derive(Debug, Clone, PartialEq, Eq)]
-pub struct SubValue(Rc<RefCell<ValueCell>>);
+pub struct SubValue(Shared<Lock<ValueCell>>);
 
 impl SubValue {
     #[inline(always)]
     pub fn new(v: ValueCell) -> Self {
-        Self(Rc::new(RefCell::new(v)))
+        #[cfg(feature = "arena")]
+        let inner = arena::alloc(v);
+        #[cfg(not(feature = "arena"))]
+        let inner = Shared::new(Lock::new(v));
+
+        Self(inner)
     }
 
     #[inline(always)]
@@ -25,24 +33,52 @@ impl SubValue {
     }
 
     #[inline(always)]
-    pub fn into_inner(self) -> Rc<RefCell<ValueCell>> {
-        self.0
+    pub fn into_inner(self) -> Shared<Lock<ValueCell>> {
+        #[cfg(feature = "arena")]
+        let inner = {
+            let mut zelf = self;
+            core::mem::replace(&mut zelf.0, arena::placeholder())
+        };
+        #[cfg(not(feature = "arena"))]
+        let inner = self.0;
+
+        inner
     }
 
     #[inline(always)]
     pub fn into_owned(self) -> ValueCell {
-        match Rc::try_unwrap(self.0) {
+        match Shared::try_unwrap(self.into_inner()) {
             Ok(value) => value.into_inner(),
             Err(rc) => rc.borrow().clone().into_owned()
         }
     }
 
+    // Pointer to the inner value
+    #[inline(always)]
+    pub fn as_ptr(&self) -> *const ValueCell {
+        self.0.as_ptr()
+    }
+
+    // Is the value also referenced elsewhere
+    #[inline(always)]
+    pub fn is_shared(&self) -> bool {
+        Shared::strong_count(&self.0) > 1
+    }
+
     #[inline(always)]
     pub fn reference(&self) -> Self {
         Self(self.0.clone())
     }
 }
 
+// Recycle the allocation in the arena instead of freeing it
+#[cfg(feature = "arena")]
+impl Drop for SubValue {
+    fn drop(&mut self) {
+        arena::recycle(&mut self.0);
+    }
+}
+
 impl Hash for SubValue {
     fn hash<H: Hasher>(&self, state: &mut H) {
         self.0.borrow()
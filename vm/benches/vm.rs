@@ -1,7 +1,7 @@
 use criterion::{criterion_group, criterion_main, Criterion};
 use xelis_builder::EnvironmentBuilder;
 use xelis_bytecode::Module;
-use xelis_compiler::Compiler;
+use xelis_compiler::{Compiler, OptimizationLevel};
 use xelis_environment::Environment;
 use xelis_lexer::Lexer;
 use xelis_parser::Parser;
@@ -28,7 +28,10 @@ fn prepare(code: &str) -> (Module, Environment) {
     let env = EnvironmentBuilder::default();
     let (program, _) = Parser::new(tokens, &env).parse().unwrap();
     let env = env.build();
-    let module = Compiler::new(&program, &env).compile().unwrap();
+    let module = Compiler::new(&program, &env)
+        .with_optimizations(OptimizationLevel::Full)
+        .compile()
+        .unwrap();
 
     (module, env)
 }
@@ -0,0 +1,97 @@
+use core::fmt;
+use alloc::{borrow::ToOwned, format, string::String, vec::Vec};
+use xelis_bytecode::SourceSpan;
+
+use super::ChunkManager;
+
+// A call frame captured when an error occurred
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TraceFrame {
+    // Id of the chunk executed
+    pub chunk_id: u16,
+    // Offset of the failing instruction for the first frame,
+    // and of the return address for the callers
+    pub offset: usize,
+    // Name of the function, only available with debug info
+    pub name: Option<String>,
+    // Source location, only available with debug info
+    pub span: Option<SourceSpan>,
+}
+
+impl fmt::Display for TraceFrame {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match &self.name {
+            Some(name) => write!(f, "{} (chunk {})", name, self.chunk_id)?,
+            None => write!(f, "chunk {}", self.chunk_id)?
+        };
+
+        write!(f, " at offset {}", self.offset)?;
+        if let Some(span) = &self.span {
+            write!(f, ", {}", span)?;
+        }
+
+        Ok(())
+    }
+}
+
+// Call frames of the VM when an error occurred
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StackTrace {
+    // The first frame is the one that failed
+    frames: Vec<TraceFrame>,
+}
+
+impl StackTrace {
+    // Capture the backtrace from the failing chunk and its callers
+    // Callers are ordered like the call stack, the last one being the most recent
+    // Each frame is resolved using the debug info of its own module
+    pub(crate) fn capture(current: &ChunkManager, offset: usize, callers: &[ChunkManager]) -> Self {
+        let frames = core::iter::once((current, offset, offset))
+            .chain(callers.iter().rev().map(|manager| {
+                // The span is the one of the call instruction, right before the return address
+                let index = manager.index();
+                (manager, index, index.saturating_sub(1))
+            }))
+            .map(|(manager, offset, span_offset)| {
+                let chunk_id = manager.id();
+                let debug_info = manager.module().debug_info();
+                TraceFrame {
+                    chunk_id,
+                    offset,
+                    name: debug_info.and_then(|info| info.get_chunk_name(chunk_id as usize))
+                        .map(str::to_owned),
+                    span: debug_info.and_then(|info| info.get_span(chunk_id as usize, span_offset))
+                        .copied()
+                }
+            })
+            .collect();
+
+        Self {
+            frames
+        }
+    }
+
+    // Get all the frames, the first one is the one that failed
+    #[inline]
+    pub fn frames(&self) -> &[TraceFrame] {
+        &self.frames
+    }
+
+    // Location of the error for a single line message
+    pub(crate) fn location(&self) -> String {
+        match self.frames.first() {
+            Some(frame) => format!(" in {}", frame),
+            None => String::new()
+        }
+    }
+}
+
+impl fmt::Display for StackTrace {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "backtrace:")?;
+        for (i, frame) in self.frames.iter().enumerate() {
+            writeln!(f, "  {}: {}", i, frame)?;
+        }
+        Ok(())
+    }
+}
@@ -1,19 +1,33 @@
 mod reader;
 
-use std::ops::{Deref, DerefMut};
-use xelis_bytecode::Chunk;
-use xelis_types::Path;
-use super::{iterator::PathIterator, VMError};
+use core::ops::{Deref, DerefMut};
+use alloc::vec::Vec;
+use xelis_bytecode::{Chunk, Module};
+use xelis_environment::Context;
+use xelis_types::{Path, ValueCell};
+use super::{iterator::{IteratorStep, PathIterator}, stack::Stack, VMError};
 pub use reader::ChunkReader;
 
 // Manager for a chunk
 // It contains the reader and the stacks
 pub struct ChunkManager<'a> {
+    // Module owning the chunk
+    module: &'a Module,
+    // Id of the chunk in the module
+    id: u16,
     reader: ChunkReader<'a>,
     // Registers are temporary and "scoped" per chunk
     registers: Vec<Path<'a>>,
     // Iterators stack
     iterators: Vec<PathIterator<'a>>,
+    // Invoked by another contract, its return ends the call
+    // The values above this length of the stack are its arguments, then its returned value
+    external: Option<usize>,
+    // An instruction of the chunk was interpreted
+    // Its compiled version can't be used anymore as it may be paused in the middle of a block
+    interpreted: bool,
+    // Offset of the instruction waiting for the result of a chunk invoked for an iterator
+    callback: Option<usize>,
 }
 
 impl<'a> ChunkManager<'a> {
@@ -21,14 +35,62 @@ impl<'a> ChunkManager<'a> {
     // It will create a reader from the chunk
     // and initialize the stack and registers
     #[inline]
-    pub fn new(chunk: &'a Chunk) -> Self {
+    pub fn new(module: &'a Module, id: u16, chunk: &'a Chunk) -> Self {
         ChunkManager {
+            module,
+            id,
             reader: ChunkReader::new(chunk),
             registers: Vec::new(),
             iterators: Vec::new(),
+            external: None,
+            interpreted: false,
+            callback: None,
         }
     }
 
+    // Get the module owning the chunk
+    #[inline]
+    pub fn module(&self) -> &'a Module {
+        self.module
+    }
+
+    // Get the id of the chunk
+    #[inline]
+    pub fn id(&self) -> u16 {
+        self.id
+    }
+
+    // Was the chunk invoked by another contract
+    #[inline]
+    pub fn is_external(&self) -> bool {
+        self.external.is_some()
+    }
+
+    // Get the length of the stack below the arguments given by another contract
+    #[inline]
+    pub fn external_stack_len(&self) -> Option<usize> {
+        self.external
+    }
+
+    // Mark the chunk as invoked by another contract
+    // with the length of the stack below its arguments
+    #[inline]
+    pub fn set_external(&mut self, stack_len: Option<usize>) {
+        self.external = stack_len;
+    }
+
+    // Was an instruction of the chunk interpreted
+    #[inline]
+    pub fn is_interpreted(&self) -> bool {
+        self.interpreted
+    }
+
+    // Mark the chunk as interpreted
+    #[inline]
+    pub fn set_interpreted(&mut self) {
+        self.interpreted = true;
+    }
+
     // Get the registers
     #[inline]
     pub fn get_registers(&self) -> &Vec<Path<'a>> {
@@ -40,25 +102,108 @@ impl<'a> ChunkManager<'a> {
         self.iterators.push(iterator);
     }
 
+    // Add an iterator over the value to the stack
+    pub fn begin_iterator(&mut self, value: Path<'a>) -> Result<(), VMError> {
+        self.iterators.push(PathIterator::new(value)?);
+        Ok(())
+    }
+
     // Pop an iterator from the stack
     pub fn pop_iterator(&mut self) -> Result<PathIterator<'a>, VMError> {
         self.iterators.pop().ok_or(VMError::EmptyIterator)
     }
 
-    // Get the next value from the iterators stack
-    pub fn next_iterator(&mut self) -> Result<Option<Path<'a>>, VMError> {
-        Ok(self.iterators.last_mut()
-            .ok_or(VMError::EmptyIterator)?
-            .next()?)
+    // Get the next value from the iterators stack for the instruction at the offset
+    // A value going through the chunk of an adapter is pushed on the stack instead,
+    // the instruction must be executed again once the chunk returned its result
+    pub fn next_iterator(&mut self, offset: usize, stack: &mut Stack<'a>, context: &mut Context<'a>) -> Result<IteratorStep<'a>, VMError> {
+        let result = self.take_callback_result(offset, stack)?;
+        let iterator = self.iterators.last_mut()
+            .ok_or(VMError::EmptyIterator)?;
+
+        if let Some(result) = result {
+            if let Some(dropped) = iterator.set_result(result.into_sub_value())? {
+                context.decrease_value_memory_usage(&Path::Wrapper(dropped))?;
+            }
+        }
+
+        if let Some((chunk, argument)) = iterator.resolve()? {
+            self.wait_callback(offset, stack, argument.into())?;
+            return Ok(IteratorStep::Callback(chunk))
+        }
+
+        Ok(match iterator.next()? {
+            Some(value) => IteratorStep::Value(value),
+            None => IteratorStep::End
+        })
+    }
+
+    // Invoke the chunks of the adapters of the iterator instance of a native function
+    // on the values it reads, before calling it from the instruction at the offset
+    // The instance is below the arguments on the stack
+    // Returns the chunk to invoke with the value pushed on the stack,
+    // the instruction must be executed again once it returned its result
+    pub fn resolve_native_instance(&mut self, offset: usize, values: u64, args: usize, stack: &mut Stack<'a>, context: &mut Context<'a>) -> Result<Option<u16>, VMError> {
+        let result = self.take_callback_result(offset, stack)?;
+        let inner = stack.get_inner();
+        let index = inner.len().checked_sub(args + 1)
+            .ok_or(VMError::NotEnoughArguments)?;
+
+        let mut instance = inner[index].as_mut();
+        let ValueCell::Iterator(iterator) = instance.as_value_mut() else {
+            return match result {
+                Some(_) => Err(VMError::UnexpectedType),
+                None => Ok(None)
+            }
+        };
+
+        if let Some(result) = result {
+            if let Some(dropped) = iterator.set_result(result.into_sub_value())? {
+                context.decrease_value_memory_usage(&Path::Wrapper(dropped))?;
+            }
+        }
+
+        if !iterator.has_callbacks() {
+            return Ok(None)
+        }
+
+        let Some((chunk, argument)) = iterator.resolve(values)? else {
+            return Ok(None)
+        };
+
+        drop(instance);
+        self.wait_callback(offset, stack, argument.into())?;
+        Ok(Some(chunk))
+    }
+
+    // Pop the result of the chunk invoked for the instruction at the offset
+    fn take_callback_result(&mut self, offset: usize, stack: &mut Stack<'a>) -> Result<Option<Path<'a>>, VMError> {
+        if self.callback != Some(offset) {
+            return Ok(None)
+        }
+
+        self.callback = None;
+        stack.pop_stack().map(Some)
+    }
+
+    // Push the argument of the chunk to invoke and rewind on the instruction waiting for its result
+    // The chunk is interpreted from there, as the compiled ones resume after the calls only
+    fn wait_callback(&mut self, offset: usize, stack: &mut Stack<'a>, argument: Path<'a>) -> Result<(), VMError> {
+        stack.push_stack(argument)?;
+        self.callback = Some(offset);
+        self.interpreted = true;
+        self.reader.set_index(offset)
     }
 
     // Push/set a new value into the registers
+    // The value replaced is returned
     #[inline]
-    pub fn set_register(&mut self, index: usize, value: Path<'a>) {
+    pub fn set_register(&mut self, index: usize, value: Path<'a>) -> Option<Path<'a>> {
         if self.registers.len() <= index {
             self.registers.push(value);
+            None
         } else {
-            self.registers[index] = value;
+            Some(core::mem::replace(&mut self.registers[index], value))
         }
     }
 
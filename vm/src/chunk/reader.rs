@@ -19,6 +19,12 @@ impl<'a> ChunkReader<'a> {
         }
     }
 
+    // Get the chunk being read
+    #[inline]
+    pub fn chunk(&self) -> &'a Chunk {
+        self.chunk
+    }
+
     // Get the current index in our reader
     #[inline]
     pub fn index(&self) -> usize {
@@ -0,0 +1,129 @@
+use core::fmt;
+use alloc::{collections::BTreeMap, vec::Vec};
+use thiserror::Error;
+use xelis_bytecode::{Disassembler, DisassemblerError, Module};
+use xelis_types::HashMap;
+
+use super::Tracer;
+
+#[derive(Debug, Error)]
+pub enum CoverageError {
+    #[error("the module has no debug info")]
+    NoDebugInfo,
+    #[error(transparent)]
+    Disassembler(#[from] DisassemblerError),
+}
+
+// Hit counts of the source lines
+// Only the lines that produced instructions are present
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct CoverageReport {
+    // Hits of each line, it is the most executed instruction of the line
+    pub lines: BTreeMap<usize, u64>,
+}
+
+impl CoverageReport {
+    // Count of lines executed at least once
+    pub fn covered_lines(&self) -> usize {
+        self.lines.values().filter(|hits| **hits > 0).count()
+    }
+
+    // Lines never executed
+    pub fn uncovered_lines(&self) -> Vec<usize> {
+        self.lines.iter()
+            .filter(|(_, hits)| **hits == 0)
+            .map(|(line, _)| *line)
+            .collect()
+    }
+
+    // Percentage of lines executed
+    pub fn percentage(&self) -> f64 {
+        if self.lines.is_empty() {
+            return 100.0
+        }
+
+        self.covered_lines() as f64 * 100.0 / self.lines.len() as f64
+    }
+}
+
+impl fmt::Display for CoverageReport {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for (line, hits) in &self.lines {
+            writeln!(f, "{:>6} | {}", line, hits)?;
+        }
+
+        write!(f, "{}/{} lines covered ({:.2}%)", self.covered_lines(), self.lines.len(), self.percentage())
+    }
+}
+
+// Tracer recording how many times each instruction was executed
+// It can be kept across several executions to merge their coverage
+// Give it to the context using `Context::set_tracer`
+#[derive(Debug, Default)]
+pub struct Coverage {
+    // Hits of each offset by chunk
+    hits: HashMap<u16, HashMap<usize, u64>>,
+}
+
+impl Coverage {
+    // Create a new coverage collector
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // Get how many times the instruction at the offset of the chunk was executed
+    pub fn hits(&self, chunk_id: u16, offset: usize) -> u64 {
+        self.hits.get(&chunk_id)
+            .and_then(|offsets| offsets.get(&offset))
+            .copied()
+            .unwrap_or_default()
+    }
+
+    // Get the offsets executed in a chunk with their hits
+    pub fn chunk_hits(&self, chunk_id: u16) -> Option<&HashMap<usize, u64>> {
+        self.hits.get(&chunk_id)
+    }
+
+    // Add the hits of another coverage
+    pub fn merge(&mut self, other: &Coverage) {
+        for (chunk_id, offsets) in &other.hits {
+            let hits = self.hits.entry(*chunk_id).or_default();
+            for (offset, count) in offsets {
+                *hits.entry(*offset).or_default() += count;
+            }
+        }
+    }
+
+    // Map the hits to the source lines using the debug info of the module
+    // Every instruction of the module is counted, so the lines never executed are reported
+    pub fn report(&self, module: &Module) -> Result<CoverageReport, CoverageError> {
+        let debug_info = module.debug_info()
+            .ok_or(CoverageError::NoDebugInfo)?;
+
+        let disassembler = Disassembler::new(module);
+        let mut lines = BTreeMap::new();
+        for (id, chunk) in module.chunks().iter().enumerate() {
+            for instruction in disassembler.disassemble_instructions(id, chunk)? {
+                // Instructions before the first statement have no span
+                let Some(span) = debug_info.get_span(id, instruction.offset) else {
+                    continue
+                };
+
+                let hits = self.hits(id as u16, instruction.offset);
+                let line = lines.entry(span.line).or_default();
+                *line = hits.max(*line);
+            }
+        }
+
+        Ok(CoverageReport { lines })
+    }
+}
+
+impl Tracer for Coverage {
+    fn on_instruction(&mut self, chunk_id: u16, offset: usize, _: u8, _: u64) {
+        *self.hits.entry(chunk_id)
+            .or_default()
+            .entry(offset)
+            .or_default() += 1;
+    }
+}
@@ -0,0 +1,180 @@
+use alloc::{borrow::ToOwned, string::String, vec::Vec};
+use xelis_bytecode::{DisassembledInstruction, Disassembler};
+use xelis_types::{Constant, HashSet, Path};
+
+use super::{ChunkManager, RunResult, VM, VMError};
+
+// A frame of the call stack
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct StackFrame {
+    // Name of the linked module of the chunk, None for the module executed
+    pub module: Option<String>,
+    // Id of the chunk executed
+    pub chunk_id: u16,
+    // Offset of the next instruction to execute in the chunk
+    pub offset: usize,
+}
+
+// State of the execution after a debugger action
+#[derive(Debug)]
+pub enum DebugState {
+    // The execution is paused on a new instruction
+    Paused,
+    // A breakpoint was reached, the instruction at it is not executed yet
+    Breakpoint(StackFrame),
+    // The execution is done
+    Finished(Constant),
+    // The execution was reverted with a value
+    Reverted(Constant),
+}
+
+// Step debugger over a VM
+// The chunk must be invoked on the VM before stepping
+pub struct Debugger<'a> {
+    vm: VM<'a>,
+    // Breakpoints set by module, chunk id and offset
+    breakpoints: HashSet<StackFrame>,
+}
+
+impl<'a> Debugger<'a> {
+    // Create a new debugger for the VM
+    pub fn new(vm: VM<'a>) -> Self {
+        Self {
+            vm,
+            breakpoints: HashSet::default(),
+        }
+    }
+
+    // Get the VM
+    #[inline]
+    pub fn vm(&self) -> &VM<'a> {
+        &self.vm
+    }
+
+    // Get a mutable reference to the VM
+    #[inline]
+    pub fn vm_mut(&mut self) -> &mut VM<'a> {
+        &mut self.vm
+    }
+
+    // Get back the VM
+    #[inline]
+    pub fn into_inner(self) -> VM<'a> {
+        self.vm
+    }
+
+    // Set a breakpoint at the offset of the chunk
+    // The module is the name of a linked module, None for the module executed
+    // Returns false if it was already set
+    pub fn set_breakpoint(&mut self, module: Option<&str>, chunk_id: u16, offset: usize) -> bool {
+        self.breakpoints.insert(StackFrame {
+            module: module.map(ToOwned::to_owned),
+            chunk_id,
+            offset
+        })
+    }
+
+    // Remove a breakpoint
+    // Returns false if it was not set
+    pub fn remove_breakpoint(&mut self, module: Option<&str>, chunk_id: u16, offset: usize) -> bool {
+        self.breakpoints.remove(&StackFrame {
+            module: module.map(ToOwned::to_owned),
+            chunk_id,
+            offset
+        })
+    }
+
+    // Remove all the breakpoints
+    pub fn clear_breakpoints(&mut self) {
+        self.breakpoints.clear();
+    }
+
+    // Get all the breakpoints
+    pub fn breakpoints(&self) -> &HashSet<StackFrame> {
+        &self.breakpoints
+    }
+
+    // Execute a single instruction
+    pub fn step(&mut self) -> Result<DebugState, VMError> {
+        Ok(match self.vm.run_for(1)? {
+            RunResult::Finished(value) => DebugState::Finished(value),
+            RunResult::Reverted(value) => DebugState::Reverted(value),
+            RunResult::Paused => DebugState::Paused
+        })
+    }
+
+    // Execute until a breakpoint is reached or the execution is done
+    // The instruction at the current position is always executed,
+    // so calling it again on a breakpoint continues the execution
+    pub fn resume(&mut self) -> Result<DebugState, VMError> {
+        loop {
+            match self.step()? {
+                DebugState::Paused => {},
+                state => return Ok(state)
+            }
+
+            if let Some(frame) = self.current_frame() {
+                if self.breakpoints.contains(&frame) {
+                    return Ok(DebugState::Breakpoint(frame));
+                }
+            }
+        }
+    }
+
+    // Build the frame of a chunk in the call stack
+    fn frame(&self, manager: &ChunkManager) -> StackFrame {
+        let module = manager.module();
+        StackFrame {
+            module: if core::ptr::eq(module, self.vm.module()) {
+                None
+            } else {
+                self.vm.linked_module_name(module).map(ToOwned::to_owned)
+            },
+            chunk_id: manager.id(),
+            offset: manager.index()
+        }
+    }
+
+    // Get the current frame
+    pub fn current_frame(&self) -> Option<StackFrame> {
+        self.vm.call_stack()
+            .last()
+            .map(|manager| self.frame(manager))
+    }
+
+    // Get all the frames of the call stack
+    // The first one is the current frame
+    pub fn stack_frames(&self) -> Vec<StackFrame> {
+        self.vm.call_stack()
+            .iter()
+            .rev()
+            .map(|manager| self.frame(manager))
+            .collect()
+    }
+
+    // Get the local variables of a frame
+    // Frame index is the same as in `stack_frames`
+    pub fn locals(&self, frame: usize) -> Option<&[Path<'a>]> {
+        let call_stack = self.vm.call_stack();
+        let index = call_stack.len().checked_sub(frame + 1)?;
+        call_stack.get(index)
+            .map(|manager| manager.get_registers().as_slice())
+    }
+
+    // Get the values in the stack
+    pub fn stack(&self) -> &[Path<'a>] {
+        self.vm.get_stack().values()
+    }
+
+    // Decode the next instruction to execute
+    // It is decoded with the module of its chunk, which may be a linked module
+    pub fn current_instruction(&self) -> Option<DisassembledInstruction> {
+        let manager = self.vm.call_stack().last()?;
+        let offset = manager.index();
+        let disassembler = Disassembler::new(manager.module());
+        disassembler.disassemble_instructions(manager.id() as usize, manager.chunk())
+            .ok()?
+            .into_iter()
+            .find(|instruction| instruction.offset == offset)
+    }
+}
@@ -1,11 +1,18 @@
+use alloc::{boxed::Box, string::String};
 use thiserror::Error;
 use xelis_environment::EnvironmentError;
-use xelis_types::{Value, ValueError};
+use xelis_types::{Constant, Type, Value, ValueCell, ValueError};
+
+use crate::StackTrace;
 
 #[derive(Debug, Error)]
 pub enum VMError {
     #[error("division by zero")]
     DivisionByZero,
+    #[error("shift overflow")]
+    ShiftOverflow,
+    #[error("arithmetic overflow")]
+    ArithmeticOverflow,
     #[error("illegal call: entry chunk")]
     EntryChunkCalled,
     #[error("string too large")]
@@ -20,14 +27,28 @@ pub enum VMError {
     StackNotCleaned,
     #[error("invalid range type")]
     InvalidRangeType,
+    #[error("invalid unpack length: expected {0}, got {1}")]
+    InvalidUnpackLength(usize, usize),
     #[error("empty stack")]
     EmptyStack,
     #[error("incompatible values: {0:?} and {1:?}")]
     IncompatibleValues(Value, Value),
     #[error("chunk was not found")]
     ChunkNotFound,
+    #[error("linked module not found: {0}")]
+    ModuleNotFound(String),
+    #[error("export not found: {0}")]
+    ExportNotFound(String),
+    #[error("invalid name constant")]
+    InvalidNameConstant,
     #[error("chunk is not an entry")]
     ChunkNotEntry,
+    #[error("entry not found: {0}")]
+    EntryNotFound(String),
+    #[error("invalid entry parameters: expected {0}, got {1}")]
+    InvalidEntryParamsCount(usize, usize),
+    #[error("invalid entry parameter {0}: expected a value of type {1}")]
+    InvalidEntryParams(usize, Type),
     #[error("struct was not found")]
     StructNotFound,
     #[error("missing instruction in module")]
@@ -46,27 +67,83 @@ pub enum VMError {
     UnsupportedCastType,
     #[error("unsupported syscall operation")]
     UnknownSysCall,
+    #[error("unknown precompile {0}")]
+    UnknownPrecompile(u16),
     #[error("error from environment: {0}")]
     EnvironmentError(EnvironmentError),
     #[error("error from value: {0}")]
     ValueError(ValueError),
     #[error("empty iterator")]
     EmptyIterator,
+    #[error("iterator calling the chunks of its module given to another module")]
+    IteratorCallbacksCrossModule,
     #[error("stack index out of bounds")]
     StackIndexOutOfBounds,
     #[error("not enough arguments")]
     NotEnoughArguments,
+    #[error("not enough gas: limit {limit}, actual {actual}")]
+    NotEnoughGas {
+        limit: u64,
+        actual: u64,
+    },
+    #[error("out of memory")]
+    OutOfMemory,
     #[error("stack overflow")]
     StackOverflow,
     #[error("call stack overflow")]
     CallStackOverflow,
     #[error("unexpected type")]
     UnexpectedType,
+    #[error("non deterministic operation: syscall {0}")]
+    NonDeterministicOperation(u16),
+    // Only returned by the typed invocations, `run` gives the reverted value as an outcome
+    #[error("execution reverted with {0}")]
+    Reverted(Constant),
+    // Error raised during the execution with the call frames at this time
+    // Source locations are only available when the module has debug info
+    #[error("{error}{}", trace.location())]
+    Traced {
+        error: Box<VMError>,
+        trace: StackTrace,
+    },
+}
+
+impl VMError {
+    // Get the error without its backtrace
+    pub fn inner(&self) -> &VMError {
+        match self {
+            VMError::Traced { error, .. } => error.inner(),
+            error => error
+        }
+    }
+
+    // Get the backtrace of the error if it was raised during the execution
+    pub fn backtrace(&self) -> Option<&StackTrace> {
+        match self {
+            VMError::Traced { trace, .. } => Some(trace),
+            _ => None
+        }
+    }
+
+    // Get the value given to `panic` if the error was raised by it
+    // Otherwise the error is given back
+    pub fn into_panic(self) -> Result<ValueCell, VMError> {
+        match self {
+            VMError::Traced { error, trace } => error.into_panic()
+                .map_err(|error| VMError::Traced { error: Box::new(error), trace }),
+            VMError::EnvironmentError(EnvironmentError::Panic(value)) => Ok(value),
+            error => Err(error)
+        }
+    }
 }
 
 impl From<EnvironmentError> for VMError {
     fn from(error: EnvironmentError) -> Self {
-        VMError::EnvironmentError(error)
+        match error {
+            EnvironmentError::NotEnoughGas { limit, actual } => VMError::NotEnoughGas { limit, actual },
+            EnvironmentError::OutOfMemory => VMError::OutOfMemory,
+            error => VMError::EnvironmentError(error)
+        }
     }
 }
 
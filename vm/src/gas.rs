@@ -0,0 +1,601 @@
+use core::fmt;
+use alloc::{vec, vec::Vec};
+use xelis_bytecode::{ControlFlowGraph, Module, OpCode};
+use xelis_types::{Constant, HashMap, U256, Value};
+
+use super::{Environment, InstructionTable};
+
+// Why the gas used by a chunk can't be bounded
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UnboundedReason {
+    // The chunk can't be decoded, the module isn't valid
+    InvalidChunk(usize),
+    // The chunk calls itself, directly or not
+    Recursion(usize),
+    // No bound is known for the loop starting at this offset
+    // Annotate it with `GasEstimator::with_loop_bound`
+    Loop {
+        chunk: usize,
+        offset: usize
+    },
+    // The native function has a cost depending on its inputs
+    DynamicCost(u16),
+    // The precompile may charge gas depending on its input
+    Precompile(u16),
+    // The chunk is given to a native, which may invoke it any number of times
+    ChunkReference(u16),
+    // The gas used by the other module isn't known
+    ExternalCall {
+        chunk: usize,
+        offset: usize
+    },
+    // The memory isn't limited while it has a price
+    Memory,
+    // The bound doesn't fit in a u64
+    Overflow,
+}
+
+impl fmt::Display for UnboundedReason {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::InvalidChunk(id) => write!(f, "chunk {} is invalid", id),
+            Self::Recursion(id) => write!(f, "chunk {} is recursive", id),
+            Self::Loop { chunk, offset } => write!(f, "loop at {} in chunk {} has no bound", offset, chunk),
+            Self::DynamicCost(id) => write!(f, "native function {} has a dynamic cost", id),
+            Self::Precompile(id) => write!(f, "precompile {} may have a dynamic cost", id),
+            Self::ChunkReference(id) => write!(f, "chunk {} is given to a native", id),
+            Self::ExternalCall { chunk, offset } => write!(f, "external call at {} in chunk {}", offset, chunk),
+            Self::Memory => write!(f, "memory is unlimited"),
+            Self::Overflow => write!(f, "gas overflows"),
+        }
+    }
+}
+
+// Upper bound of the gas used by an execution
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GasBound {
+    Bounded(u64),
+    Unbounded(UnboundedReason),
+}
+
+impl GasBound {
+    #[inline]
+    pub fn is_bounded(&self) -> bool {
+        matches!(self, Self::Bounded(_))
+    }
+}
+
+impl fmt::Display for GasBound {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Bounded(gas) => write!(f, "{}", gas),
+            Self::Unbounded(reason) => write!(f, "unbounded: {}", reason),
+        }
+    }
+}
+
+// Static worst-case gas of the chunks of a module
+// Each path of a chunk is charged with the instruction costs of the table,
+// the flat cost of the natives and the worst case of the chunks called.
+// A loop is repeated up to its bound: annotated, or detected for a counter
+// compared with a constant and incremented by a constant, and for a foreach over a constant.
+// The memory charged by the constructors and the natives is bounded by the max memory times its price.
+pub struct GasEstimator<'a> {
+    module: &'a Module,
+    environment: &'a Environment,
+    table: InstructionTable<'a>,
+    // Max iterations of the loops by chunk and offset of their first instruction
+    loop_bounds: HashMap<(usize, usize), u64>,
+    memory_price_per_byte: u64,
+    max_memory: usize,
+}
+
+// A decoded instruction of a block
+struct Instruction<'b> {
+    offset: usize,
+    opcode: OpCode,
+    args: &'b [u8],
+}
+
+impl Instruction<'_> {
+    fn u16(&self, index: usize) -> u16 {
+        u16::from_le_bytes([self.args[index], self.args[index + 1]])
+    }
+
+    fn u32(&self, index: usize) -> usize {
+        u32::from_le_bytes([self.args[index], self.args[index + 1], self.args[index + 2], self.args[index + 3]]) as usize
+    }
+}
+
+// Loops of a chunk by head block
+struct Loop {
+    head: usize,
+    // Is each block part of the loop
+    body: Vec<bool>,
+    latches: Vec<usize>,
+}
+
+impl<'a> GasEstimator<'a> {
+    pub fn new(module: &'a Module, environment: &'a Environment) -> Self {
+        Self {
+            module,
+            environment,
+            table: InstructionTable::new(),
+            loop_bounds: HashMap::default(),
+            memory_price_per_byte: 0,
+            max_memory: usize::MAX,
+        }
+    }
+
+    // Use the instruction costs of the VM when they are customized
+    pub fn with_instruction_table(mut self, table: InstructionTable<'a>) -> Self {
+        self.table = table;
+        self
+    }
+
+    // Bound a loop by its max count of iterations
+    // The offset is the one of its first instruction: the condition of a while or a for,
+    // the IteratorNext of a foreach, as reported by `UnboundedReason::Loop`
+    pub fn with_loop_bound(mut self, chunk: usize, offset: usize, iterations: u64) -> Self {
+        self.loop_bounds.insert((chunk, offset), iterations);
+        self
+    }
+
+    // Charge the memory like the context of the VM
+    pub fn with_memory(mut self, price_per_byte: u64, max_memory: usize) -> Self {
+        self.memory_price_per_byte = price_per_byte;
+        self.max_memory = max_memory;
+        self
+    }
+
+    // Upper bound of the gas used by invoking a chunk
+    pub fn estimate_chunk(&self, id: usize) -> GasBound {
+        match self.chunks_gas().get(id) {
+            Some(gas) => self.with_memory_gas(*gas),
+            None => GasBound::Unbounded(UnboundedReason::InvalidChunk(id))
+        }
+    }
+
+    // Upper bound of each entry by chunk id
+    pub fn estimate_entries(&self) -> Vec<(usize, GasBound)> {
+        let gas = self.chunks_gas();
+        let mut entries = self.module.chunks_entry_ids()
+            .iter()
+            .map(|id| (*id, self.with_memory_gas(gas[*id])))
+            .collect::<Vec<_>>();
+        entries.sort_by_key(|(id, _)| *id);
+        entries
+    }
+
+    // Upper bound of the entry exported under a name
+    pub fn estimate_entry(&self, name: &str) -> Option<GasBound> {
+        self.module.entry_by_name(name).map(|id| self.estimate_chunk(id))
+    }
+
+    fn with_memory_gas(&self, gas: Result<u64, UnboundedReason>) -> GasBound {
+        let memory = match self.memory_price_per_byte {
+            0 => Ok(0),
+            price => (self.max_memory as u64).checked_mul(price).ok_or(UnboundedReason::Memory)
+        };
+
+        match gas.and_then(|gas| memory.and_then(|memory| gas.checked_add(memory).ok_or(UnboundedReason::Overflow))) {
+            Ok(gas) => GasBound::Bounded(gas),
+            Err(reason) => GasBound::Unbounded(reason)
+        }
+    }
+
+    // Worst case of all the chunks, without the memory
+    // The callees are estimated before their callers, a callee not estimated yet is recursive
+    fn chunks_gas(&self) -> Vec<Result<u64, UnboundedReason>> {
+        let chunks = self.module.chunks();
+        let graph = chunks.iter()
+            .map(|chunk| {
+                let mut callees = Vec::new();
+                let bytes = chunk.get_instructions();
+                let mut offset = 0;
+                while let Some(opcode) = bytes.get(offset).and_then(|b| OpCode::from_byte(*b)) {
+                    if opcode == OpCode::InvokeChunk {
+                        if let Some(id) = bytes.get(offset + 1..offset + 3) {
+                            callees.push(u16::from_le_bytes([id[0], id[1]]) as usize);
+                        }
+                    }
+                    offset += 1 + opcode.arguments_bytes();
+                }
+                callees
+            })
+            .collect::<Vec<_>>();
+
+        let mut gas: Vec<Option<Result<u64, UnboundedReason>>> = vec![None; chunks.len()];
+        let mut visited = vec![false; chunks.len()];
+        for root in 0..chunks.len() {
+            if visited[root] {
+                continue
+            }
+
+            // Depth-first, iterative so a long chain of calls can't overflow the stack
+            let mut pending = vec![(root, 0)];
+            visited[root] = true;
+            while let Some((node, edge)) = pending.last_mut() {
+                let node = *node;
+                if let Some(callee) = graph[node].get(*edge).copied() {
+                    *edge += 1;
+                    if callee < chunks.len() && !visited[callee] {
+                        visited[callee] = true;
+                        pending.push((callee, 0));
+                    }
+                    continue
+                }
+
+                gas[node] = Some(self.chunk_gas(node, &gas));
+                pending.pop();
+            }
+        }
+
+        gas.into_iter()
+            .enumerate()
+            .map(|(id, gas)| gas.unwrap_or(Err(UnboundedReason::InvalidChunk(id))))
+            .collect()
+    }
+
+    // Worst case of a chunk: the longest path of its blocks, the loops collapsed from the innermost one
+    fn chunk_gas(&self, id: usize, callees: &[Option<Result<u64, UnboundedReason>>]) -> Result<u64, UnboundedReason> {
+        let chunk = &self.module.chunks()[id];
+        let cfg = chunk.build_cfg().map_err(|_| UnboundedReason::InvalidChunk(id))?;
+        let bytes = chunk.get_instructions();
+        let instructions = cfg.blocks()
+            .iter()
+            .map(|block| decode(bytes, block.start, block.end))
+            .collect::<Vec<_>>();
+
+        let mut costs = vec![0; cfg.blocks().len()];
+        for block in cfg.reverse_postorder().iter().copied() {
+            costs[block] = self.block_gas(id, &instructions[block], callees)?;
+        }
+
+        // Natural loops grouped by head, the innermost first
+        let mut loops: Vec<Loop> = Vec::new();
+        for (latch, head) in cfg.back_edges() {
+            let index = match loops.iter().position(|l| l.head == head) {
+                Some(index) => index,
+                None => {
+                    loops.push(Loop { head, body: vec![false; cfg.blocks().len()], latches: Vec::new() });
+                    loops.len() - 1
+                }
+            };
+            let l = &mut loops[index];
+            l.latches.push(latch);
+            l.body[head] = true;
+            let mut pending = vec![latch];
+            while let Some(block) = pending.pop() {
+                if !l.body[block] {
+                    l.body[block] = true;
+                    pending.extend(cfg.predecessors(block).iter().copied().filter(|p| cfg.is_reachable(*p)));
+                }
+            }
+        }
+        loops.sort_by_key(|l| l.body.iter().filter(|b| **b).count());
+
+        // Loop collapsing each block, with the worst case of each collapsed loop
+        let mut owners: Vec<Option<usize>> = vec![None; cfg.blocks().len()];
+        let mut loops_gas = vec![0; cfg.blocks().len()];
+        for l in &loops {
+            let iterations = self.loop_bound(id, &cfg, &instructions, l)?;
+            let path = longest_path(&cfg, &costs, &owners, &loops_gas, l.head, |block| l.body[block] && block != l.head)
+                .ok_or(UnboundedReason::Loop { chunk: id, offset: cfg.blocks()[l.head].start })?;
+
+            // The condition is evaluated once more to exit
+            let path = path?;
+            loops_gas[l.head] = iterations.checked_add(1)
+                .and_then(|count| count.checked_mul(path))
+                .ok_or(UnboundedReason::Overflow)?;
+            for (block, owner) in owners.iter_mut().enumerate() {
+                if l.body[block] {
+                    *owner = Some(l.head);
+                }
+            }
+        }
+
+        if cfg.blocks().is_empty() {
+            return Ok(0)
+        }
+
+        longest_path(&cfg, &costs, &owners, &loops_gas, 0, |block| cfg.is_reachable(block) && block != 0)
+            .ok_or(UnboundedReason::Loop { chunk: id, offset: 0 })?
+    }
+
+    // Gas of the instructions of a block, the calls included
+    fn block_gas(&self, id: usize, instructions: &[Instruction], callees: &[Option<Result<u64, UnboundedReason>>]) -> Result<u64, UnboundedReason> {
+        let mut gas: u64 = 0;
+        for instruction in instructions {
+            let cost = match instruction.opcode {
+                OpCode::InvokeChunk => {
+                    let callee = instruction.u16(0) as usize;
+                    match callees.get(callee) {
+                        Some(Some(gas)) => (*gas)?,
+                        Some(None) => return Err(UnboundedReason::Recursion(callee)),
+                        None => return Err(UnboundedReason::InvalidChunk(id))
+                    }
+                },
+                OpCode::SysCall => {
+                    let native = instruction.u16(0);
+                    let function = self.environment.get_functions()
+                        .get(native as usize)
+                        .ok_or(UnboundedReason::InvalidChunk(id))?;
+                    if function.get_cost_fn().is_some() {
+                        return Err(UnboundedReason::DynamicCost(native))
+                    }
+                    function.get_cost()
+                },
+                OpCode::CallPrecompile => return Err(UnboundedReason::Precompile(instruction.u16(0))),
+                OpCode::ChunkId => return Err(UnboundedReason::ChunkReference(instruction.u16(0))),
+                OpCode::ExternalCall => return Err(UnboundedReason::ExternalCall { chunk: id, offset: instruction.offset }),
+                _ => 0
+            };
+
+            gas = gas.checked_add(self.table.get_instruction_cost(instruction.opcode))
+                .and_then(|gas| gas.checked_add(cost))
+                .ok_or(UnboundedReason::Overflow)?;
+        }
+
+        Ok(gas)
+    }
+
+    // Max iterations of a loop: annotated or detected
+    fn loop_bound(&self, id: usize, cfg: &ControlFlowGraph, instructions: &[Vec<Instruction>], l: &Loop) -> Result<u64, UnboundedReason> {
+        let offset = cfg.blocks()[l.head].start;
+        if let Some(iterations) = self.loop_bounds.get(&(id, offset)) {
+            return Ok(*iterations)
+        }
+
+        // The block entering the loop
+        let mut entries = cfg.predecessors(l.head).iter().filter(|p| !l.body[**p]);
+        let detected = match (entries.next(), entries.next(), l.latches.as_slice()) {
+            (Some(entry), None, [latch]) => self.counter_bound(cfg, instructions, l, *entry, *latch)
+                .or_else(|| self.foreach_bound(instructions, l, *entry)),
+            _ => None
+        };
+
+        detected.ok_or(UnboundedReason::Loop { chunk: id, offset })
+    }
+
+    // for i = start; i < end; i += step
+    // The counter must only be read by the body
+    fn counter_bound(&self, cfg: &ControlFlowGraph, instructions: &[Vec<Instruction>], l: &Loop, entry: usize, latch: usize) -> Option<u64> {
+        use OpCode::*;
+
+        // The condition compares the counter with a constant and exits the loop
+        let head = &instructions[l.head];
+        let (register, end, inclusive, exit) = match head.as_slice() {
+            [load, limit, jump] if matches!(jump.opcode, LtJumpIfFalse | LteJumpIfFalse) => {
+                (load, limit, jump.opcode == LteJumpIfFalse, jump.u32(0))
+            },
+            [load, limit, compare, jump] if matches!(compare.opcode, Lt | Lte) && jump.opcode == JumpIfFalse => {
+                (load, limit, compare.opcode == Lte, jump.u32(0))
+            },
+            _ => return None
+        };
+        if register.opcode != MemoryLoad || end.opcode != Constant || cfg.block_at(exit).is_some_and(|b| l.body[b]) {
+            return None
+        }
+        let register = register.u16(0);
+
+        // Initialized with a constant just before
+        let start = match instructions[entry].as_slice() {
+            [.., value, store] if value.opcode == Constant && store.opcode == MemorySet && store.u16(0) == register => value,
+            _ => return None
+        };
+
+        // Incremented at the end of each iteration
+        let latch_instructions = &instructions[latch];
+        let (step, step_len) = match latch_instructions.as_slice() {
+            [.., inc, jump] if inc.opcode == MemoryInc && inc.u16(0) == register && jump.opcode == Jump => (None, 2),
+            [.., load, value, add, jump] if load.opcode == MemoryLoad && load.u16(0) == register
+                && value.opcode == Constant && add.opcode == AssignAdd && jump.opcode == Jump => (Some(value), 4),
+            _ => return None
+        };
+
+        // Any other access to the counter must only read it
+        for (block, instructions) in instructions.iter().enumerate().filter(|(b, _)| l.body[*b]) {
+            let skip_end = if block == latch { step_len } else { 0 };
+            let checked = &instructions[..instructions.len() - skip_end];
+            for (i, instruction) in checked.iter().enumerate() {
+                if block == l.head && i == 0 {
+                    continue
+                }
+
+                let uses = matches!(instruction.opcode, MemoryLoad | MemorySet | MemoryInc) && instruction.u16(0) == register;
+                if uses && (instruction.opcode != MemoryLoad || !self.is_read(&checked[i + 1..], register)) {
+                    return None
+                }
+            }
+        }
+
+        let (start, end) = (self.number(start)?, self.number(end)?);
+        let step = match step {
+            Some(step) => self.number(step)?,
+            None => U256::ONE
+        };
+        if step.is_zero() {
+            return None
+        }
+
+        // Iterations with the counter below the end: ceil((end - start) / step)
+        let count = match end.checked_sub(start) {
+            Some(distance) if inclusive => distance.checked_div(step)?.checked_add(U256::ONE)?,
+            Some(distance) => distance.checked_add(step)?.checked_sub(U256::ONE)?.checked_div(step)?,
+            None => U256::ZERO
+        };
+        count.as_u64()
+    }
+
+    // Number loaded by a Constant instruction
+    fn number(&self, instruction: &Instruction) -> Option<U256> {
+        match self.module.get_constant_at(instruction.u16(0) as usize)? {
+            Constant::Default(value) if value.is_number() => value.clone().cast_to_u256().ok(),
+            _ => None
+        }
+    }
+
+    // Is the value of the register pushed before these instructions only read
+    // It must be consumed by an operator or a call, directly or after another value pushed
+    fn is_read(&self, next: &[Instruction], register: u16) -> bool {
+        use OpCode::*;
+
+        let consumes = |instruction: &Instruction, position: usize| match instruction.opcode {
+            Add | Sub | Mul | Div | Mod | Pow | And | Or
+            | BitwiseAnd | BitwiseOr | BitwiseXor | BitwiseShl | BitwiseShr
+            | Eq | Gt | Lt | Gte | Lte
+            | LtJumpIfFalse | LteJumpIfFalse | GtJumpIfFalse | GteJumpIfFalse
+            | CheckedAdd | CheckedSub | CheckedMul
+            | SaturatingAdd | SaturatingSub | SaturatingMul
+            | WrappingAdd | WrappingSub | WrappingMul
+            | ArrayCall | NewRange | NewRangeInclusive => true,
+            Cast | Neg | Pop | Return => position == 0,
+            // A primitive is copied when stored, like the parameters of a chunk
+            MemorySet => position == 0 && instruction.u16(0) != register,
+            InvokeChunk => (instruction.args[3] as usize + instruction.args[2] as usize) > position,
+            // A native may update its instance
+            SysCall => (instruction.args[3] as usize) > position,
+            _ => false
+        };
+
+        match next {
+            [first, ..] if consumes(first, 0) => true,
+            [pushed, second, ..] => {
+                let pushes = match pushed.opcode {
+                    Constant | MemoryAdd | MemoryCast | ChunkId => true,
+                    MemoryLoad => pushed.u16(0) != register,
+                    _ => false
+                };
+                pushes && consumes(second, 1)
+            },
+            _ => false
+        }
+    }
+
+    // foreach over a constant array, map or range
+    fn foreach_bound(&self, instructions: &[Vec<Instruction>], l: &Loop, entry: usize) -> Option<u64> {
+        if !matches!(instructions[l.head].as_slice(), [next] if next.opcode == OpCode::IteratorNext) {
+            return None
+        }
+
+        let constant = |instruction: &Instruction| match instruction.opcode {
+            OpCode::Constant => self.module.get_constant_at(instruction.u16(0) as usize),
+            _ => None
+        };
+        let range = |start: &Value, end: &Value, inclusive: bool| {
+            let start = start.clone().cast_to_u256().ok()?;
+            let end = end.clone().cast_to_u256().ok()?;
+            match end.checked_sub(start) {
+                Some(distance) if inclusive => distance.checked_add(U256::ONE)?.as_u64(),
+                Some(distance) => distance.as_u64(),
+                None => Some(0)
+            }
+        };
+
+        match instructions[entry].as_slice() {
+            [.., start, end, new, begin] if begin.opcode == OpCode::IteratorBegin && matches!(new.opcode, OpCode::NewRange | OpCode::NewRangeInclusive) => {
+                match (constant(start)?, constant(end)?) {
+                    (Constant::Default(start), Constant::Default(end)) => range(start, end, new.opcode == OpCode::NewRangeInclusive),
+                    _ => None
+                }
+            },
+            [.., values, begin] if begin.opcode == OpCode::IteratorBegin => match constant(values)? {
+                Constant::Array(values) => Some(values.len() as u64),
+                Constant::Map(entries) => Some(entries.len() as u64),
+                Constant::Default(Value::Range(start, end, _, inclusive)) => range(start, end, *inclusive),
+                _ => None
+            },
+            _ => None
+        }
+    }
+}
+
+// Decode the instructions between two offsets of a valid chunk
+fn decode(bytes: &[u8], start: usize, end: usize) -> Vec<Instruction<'_>> {
+    let mut instructions = Vec::new();
+    let mut offset = start;
+    while offset < end {
+        let Some(opcode) = OpCode::from_byte(bytes[offset]) else {
+            break
+        };
+        let next = offset + 1 + opcode.arguments_bytes();
+        instructions.push(Instruction {
+            offset,
+            opcode,
+            args: &bytes[offset + 1..next]
+        });
+        offset = next;
+    }
+    instructions
+}
+
+// Gas of the longest path from a block through the blocks accepted, the collapsed loops counted once
+// None if a cycle remains: the control flow can't be reduced to loops
+fn longest_path(
+    cfg: &ControlFlowGraph,
+    costs: &[u64],
+    owners: &[Option<usize>],
+    loops_gas: &[u64],
+    start: usize,
+    accepts: impl Fn(usize) -> bool
+) -> Option<Result<u64, UnboundedReason>> {
+    // A block stands for the loop collapsing it
+    let unit = |block: usize| owners[block].unwrap_or(block);
+    let cost = |unit: usize| if owners[unit] == Some(unit) { loops_gas[unit] } else { costs[unit] };
+    let is_member = |block: usize| block == start || accepts(block);
+
+    let members = (0..costs.len()).filter(|b| is_member(*b)).collect::<Vec<_>>();
+    let mut edges: HashMap<usize, Vec<usize>> = HashMap::default();
+    let mut incoming = vec![0usize; costs.len()];
+    for block in members.iter().copied() {
+        for successor in cfg.successors(block).iter().copied() {
+            // The jumps back to the start are the iterations, they are counted by the caller
+            if successor == start || !is_member(successor) {
+                continue
+            }
+
+            let (from, to) = (unit(block), unit(successor));
+            let targets = edges.entry(from).or_default();
+            if from != to && !targets.contains(&to) {
+                targets.push(to);
+                incoming[to] += 1;
+            }
+        }
+    }
+
+    // Topological order of the units from the start
+    let mut gas = vec![None; costs.len()];
+    gas[unit(start)] = Some(cost(unit(start)));
+    let mut ready = members.iter().map(|b| unit(*b)).filter(|u| incoming[*u] == 0).collect::<Vec<_>>();
+    ready.sort_unstable();
+    ready.dedup();
+
+    let mut units = members.iter().map(|b| unit(*b)).collect::<Vec<_>>();
+    units.sort_unstable();
+    units.dedup();
+
+    let mut done = 0;
+    while let Some(current) = ready.pop() {
+        done += 1;
+        for next in edges.get(&current).into_iter().flatten().copied() {
+            if let Some(from) = gas[current] {
+                let total = match u64::checked_add(from, cost(next)) {
+                    Some(total) => total,
+                    None => return Some(Err(UnboundedReason::Overflow))
+                };
+                gas[next] = Some(gas[next].map_or(total, |g: u64| g.max(total)));
+            }
+            incoming[next] -= 1;
+            if incoming[next] == 0 {
+                ready.push(next);
+            }
+        }
+    }
+
+    if done != units.len() {
+        return None
+    }
+
+    Some(Ok(units.iter().filter_map(|u| gas[*u]).max().unwrap_or(0)))
+}
@@ -1,37 +1,52 @@
-use std::collections::{HashMap, VecDeque};
-use xelis_environment::EnvironmentError;
-use xelis_types::{EnumValueType, Path, Value, ValueCell};
+use alloc::{boxed::Box, collections::VecDeque, vec::Vec};
+use xelis_environment::{EnvironmentError, VALUE_MEMORY_USAGE};
+use xelis_types::{EnumValueType, IndexMap, Path, Value, ValueCell};
 
 use crate::{stack::Stack, Backend, ChunkManager, Context, VMError};
 use super::InstructionResult;
 
-pub fn new_array<'a>(_: &Backend<'a>, stack: &mut Stack<'a>, manager: &mut ChunkManager<'a>, _: &mut Context<'a>) -> Result<InstructionResult, VMError> {
+pub fn new_array<'a>(_: &Backend<'a>, stack: &mut Stack<'a>, manager: &mut ChunkManager<'a>, context: &mut Context<'a>) -> Result<InstructionResult, VMError> {
     let length = manager.read_u8()?;
+    context.increase_memory_usage(length as usize * VALUE_MEMORY_USAGE)?;
+
     let mut array = VecDeque::with_capacity(length as usize);
     for _ in 0..length {
         let pop = stack.pop_stack()?;
-        array.push_front(pop.into_owned().into());
+        context.increase_stored_memory_usage(&pop)?;
+        array.push_front(pop.into_owned());
     }
 
-    stack.push_stack(Path::Owned(ValueCell::Array(array.into())))?;
+    stack.push_stack(Path::Owned(ValueCell::new_array(array.into())))?;
     Ok(InstructionResult::Nothing)
 }
 
-pub fn new_struct<'a>(backend: &Backend<'a>, stack: &mut Stack<'a>, manager: &mut ChunkManager<'a>, _: &mut Context<'a>) -> Result<InstructionResult, VMError> {
+pub fn new_struct<'a>(backend: &Backend<'a>, stack: &mut Stack<'a>, manager: &mut ChunkManager<'a>, context: &mut Context<'a>) -> Result<InstructionResult, VMError> {
     let id = manager.read_u16()?;
     let struct_type = backend.get_struct_with_id(id as usize)?;
 
     let fields_count = struct_type.fields().len();
+    context.increase_memory_usage(fields_count * VALUE_MEMORY_USAGE)?;
+
     let mut fields = VecDeque::with_capacity(fields_count);
     for _ in 0..fields_count {
-        fields.push_front(stack.pop_stack()?.into_owned().into());
+        let pop = stack.pop_stack()?;
+        context.increase_stored_memory_usage(&pop)?;
+        fields.push_front(pop.into_owned().into());
     }
 
-    stack.push_stack(Path::Owned(ValueCell::Struct(fields.into(), struct_type.clone())))?;
+    stack.push_stack(Path::Owned(ValueCell::Struct(Vec::from(fields).into(), struct_type.clone())))?;
     Ok(InstructionResult::Nothing)
 }
 
 pub fn new_range<'a>(_: &Backend<'a>, stack: &mut Stack<'a>, _: &mut ChunkManager<'a>, _: &mut Context<'a>) -> Result<InstructionResult, VMError> {
+    push_range(stack, false)
+}
+
+pub fn new_range_inclusive<'a>(_: &Backend<'a>, stack: &mut Stack<'a>, _: &mut ChunkManager<'a>, _: &mut Context<'a>) -> Result<InstructionResult, VMError> {
+    push_range(stack, true)
+}
+
+fn push_range(stack: &mut Stack<'_>, inclusive: bool) -> Result<InstructionResult, VMError> {
     let end = stack.pop_stack()?.into_owned();
     let start = stack.pop_stack()?.into_owned();
 
@@ -44,41 +59,69 @@ pub fn new_range<'a>(_: &Backend<'a>, stack: &mut Stack<'a>, _: &mut ChunkManage
         return Err(VMError::InvalidRangeType);
     }
 
-    let value = Value::Range(Box::new(start.into_value()?), Box::new(end.into_value()?), start_type);
+    let value = Value::Range(Box::new(start.into_value()?), Box::new(end.into_value()?), start_type, inclusive);
     stack.push_stack_unchecked(Path::Owned(ValueCell::Default(value)));
     Ok(InstructionResult::Nothing)
 }
 
-pub fn new_map<'a>(_: &Backend<'a>, stack: &mut Stack<'a>, manager: &mut ChunkManager<'a>, _: &mut Context<'a>) -> Result<InstructionResult, VMError> {
+pub fn new_map<'a>(_: &Backend<'a>, stack: &mut Stack<'a>, manager: &mut ChunkManager<'a>, context: &mut Context<'a>) -> Result<InstructionResult, VMError> {
     let len = manager.read_u8()?;
-    let mut map = HashMap::with_capacity(len as usize);
+    // Both the key and the value of each entry
+    context.increase_memory_usage(len as usize * 2 * VALUE_MEMORY_USAGE)?;
+
+    let mut entries = Vec::with_capacity(len as usize);
     for _ in 0..len {
         let value = stack.pop_stack()?;
-        let key = stack.pop_stack()?.into_owned();
-        if key.is_map() {
+        let key = stack.pop_stack()?;
+        if key.as_ref().is_map() {
             return Err(EnvironmentError::InvalidKeyType.into());
         }
 
-        map.insert(key, value.into_owned().into());
+        context.increase_stored_memory_usage(&key)?;
+        context.increase_stored_memory_usage(&value)?;
+        entries.push((key.into_owned(), value.into_owned().into()));
     }
 
-    stack.push_stack_unchecked(Path::Owned(ValueCell::Map(map)));
+    // Entries are popped in reverse, insert them in the declared order
+    let map: IndexMap<_, _> = entries.into_iter()
+        .rev()
+        .collect();
+
+    stack.push_stack_unchecked(Path::Owned(ValueCell::Map(map.into())));
     Ok(InstructionResult::Nothing)
 }
 
-pub fn new_enum<'a>(backend: &Backend<'a>, stack: &mut Stack<'a>, manager: &mut ChunkManager<'a>, _: &mut Context<'a>) -> Result<InstructionResult, VMError> {
+pub fn new_enum<'a>(backend: &Backend<'a>, stack: &mut Stack<'a>, manager: &mut ChunkManager<'a>, context: &mut Context<'a>) -> Result<InstructionResult, VMError> {
     let id = manager.read_u16()?;
     let enum_type = backend.get_enum_with_id(id as usize)?;
 
     let variant_id = manager.read_u8()?;
     let variant = enum_type.get_variant(variant_id)
         .ok_or(VMError::InvalidEnumVariant)?;
+    context.increase_memory_usage(variant.fields().len() * VALUE_MEMORY_USAGE)?;
 
     let mut values = VecDeque::with_capacity(variant.fields().len());
     for _ in variant.fields() {
-        values.push_front(stack.pop_stack()?.into_owned().into());
+        let pop = stack.pop_stack()?;
+        context.increase_stored_memory_usage(&pop)?;
+        values.push_front(pop.into_owned().into());
     }
 
-    stack.push_stack(Path::Owned(ValueCell::Enum(values.into(), EnumValueType::new(enum_type.clone(), variant_id))))?;
+    stack.push_stack(Path::Owned(ValueCell::Enum(Vec::from(values).into(), EnumValueType::new(enum_type.clone(), variant_id))))?;
     Ok(InstructionResult::Nothing)
-}
\ No newline at end of file
+}
+
+// Destructure a tuple, the first value is pushed last
+pub fn unpack<'a>(_: &Backend<'a>, stack: &mut Stack<'a>, manager: &mut ChunkManager<'a>, _: &mut Context<'a>) -> Result<InstructionResult, VMError> {
+    let len = manager.read_u8()? as usize;
+    let values = stack.pop_stack()?.into_owned().to_vec()?;
+    if values.len() != len {
+        return Err(VMError::InvalidUnpackLength(len, values.len()));
+    }
+
+    for value in values.into_iter().rev() {
+        stack.push_stack(Path::Owned(value.into_owned()))?;
+    }
+
+    Ok(InstructionResult::Nothing)
+}
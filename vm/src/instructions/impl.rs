@@ -1,16 +1,21 @@
-use std::collections::VecDeque;
-use xelis_types::Path;
+use core::mem;
+use alloc::collections::VecDeque;
+use xelis_environment::EnvironmentError;
+use xelis_types::{Path, Value, ValueCell};
 
 use crate::{stack::Stack, Backend, ChunkManager, Context, VMError};
 use super::InstructionResult;
 
 
-pub fn constant<'a>(backend: &Backend<'a>, stack: &mut Stack<'a>, manager: &mut ChunkManager<'a>, _: &mut Context<'a>) -> Result<InstructionResult, VMError> {
+// The constant is copied, so charged like any value created by the execution
+pub fn constant<'a>(backend: &Backend<'a>, stack: &mut Stack<'a>, manager: &mut ChunkManager<'a>, context: &mut Context<'a>) -> Result<InstructionResult, VMError> {
     let index = manager.read_u16()? as usize;
     let constant = backend.module.get_constant_at(index)
         .ok_or(VMError::ConstantNotFound)?;
 
-    stack.push_stack(Path::Owned(constant.clone().into()))?;
+    let value = constant.clone().into();
+    context.increase_value_memory_usage(&value)?;
+    stack.push_stack(Path::Owned(value))?;
     Ok(InstructionResult::Nothing)
 }
 
@@ -23,10 +28,18 @@ pub fn memory_load<'a>(_: &Backend<'a>, stack: &mut Stack<'a>, manager: &mut Chu
     Ok(InstructionResult::Nothing)
 }
 
-pub fn memory_set<'a>(_: &Backend<'a>, stack: &mut Stack<'a>, manager: &mut ChunkManager<'a>, _: &mut Context<'a>) -> Result<InstructionResult, VMError> {
+pub fn memory_set<'a>(_: &Backend<'a>, stack: &mut Stack<'a>, manager: &mut ChunkManager<'a>, context: &mut Context<'a>) -> Result<InstructionResult, VMError> {
     let index = manager.read_u16()?;
-    let value = stack.pop_stack()?;
-    manager.set_register(index as usize, value);
+    let mut value = stack.pop_stack()?;
+    // A primitive is copied so the variable doesn't alias the one it was read from
+    // Arrays, structs and maps stay shared, like the parameters of a function
+    if !matches!(value, Path::Owned(_)) && matches!(value.as_ref().as_value(), ValueCell::Default(_)) {
+        context.increase_stored_memory_usage(&value)?;
+        value = Path::Owned(value.into_owned());
+    }
+    if let Some(previous) = manager.set_register(index as usize, value) {
+        context.decrease_value_memory_usage(&previous)?;
+    }
 
     Ok(InstructionResult::Nothing)
 }
@@ -55,14 +68,17 @@ pub fn copy_n<'a>(_: &Backend<'a>, stack: &mut Stack<'a>, manager: &mut ChunkMan
     Ok(InstructionResult::Nothing)
 }
 
-pub fn pop<'a>(_: &Backend<'a>, stack: &mut Stack<'a>, _: &mut ChunkManager<'a>, _: &mut Context<'a>) -> Result<InstructionResult, VMError> {
-    stack.pop_stack()?;
+pub fn pop<'a>(_: &Backend<'a>, stack: &mut Stack<'a>, _: &mut ChunkManager<'a>, context: &mut Context<'a>) -> Result<InstructionResult, VMError> {
+    let value = stack.pop_stack()?;
+    context.decrease_value_memory_usage(&value)?;
     Ok(InstructionResult::Nothing)
 }
 
-pub fn pop_n<'a>(_: &Backend<'a>, stack: &mut Stack<'a>, manager: &mut ChunkManager<'a>, _: &mut Context<'a>) -> Result<InstructionResult, VMError> {
+pub fn pop_n<'a>(_: &Backend<'a>, stack: &mut Stack<'a>, manager: &mut ChunkManager<'a>, context: &mut Context<'a>) -> Result<InstructionResult, VMError> {
     let n = manager.read_u8()?;
-    stack.pop_stack_n(n)?;
+    for value in stack.pop_stack_n(n)? {
+        context.decrease_value_memory_usage(&value)?;
+    }
     Ok(InstructionResult::Nothing)
 }
 
@@ -88,6 +104,13 @@ pub fn array_call<'a>(_: &Backend<'a>, stack: &mut Stack<'a>, _: &mut ChunkManag
     Ok(InstructionResult::Nothing)
 }
 
+// The chunk is given as a function to a native
+pub fn chunk_id<'a>(_: &Backend<'a>, stack: &mut Stack<'a>, manager: &mut ChunkManager<'a>, _: &mut Context<'a>) -> Result<InstructionResult, VMError> {
+    let id = manager.read_u16()?;
+    stack.push_stack(Path::Owned(ValueCell::Default(Value::U16(id))))?;
+    Ok(InstructionResult::Nothing)
+}
+
 pub fn invoke_chunk<'a>(_: &Backend<'a>, stack: &mut Stack<'a>, manager: &mut ChunkManager<'a>, _: &mut Context<'a>) -> Result<InstructionResult, VMError> {
     let id = manager.read_u16()?;
     let on_value = manager.read_bool()?;
@@ -96,23 +119,55 @@ pub fn invoke_chunk<'a>(_: &Backend<'a>, stack: &mut Stack<'a>, manager: &mut Ch
         args += 1;
     }
 
-    // We need to reverse the order of the arguments
+    reverse_arguments(stack, args)?;
+
+    Ok(InstructionResult::InvokeChunk(id))
+}
+
+// The module and the export are resolved by the VM
+// as the callee runs with its own module
+pub fn external_call<'a>(_: &Backend<'a>, stack: &mut Stack<'a>, manager: &mut ChunkManager<'a>, _: &mut Context<'a>) -> Result<InstructionResult, VMError> {
+    let module = manager.read_u16()?;
+    let export = manager.read_u16()?;
+    let args = manager.read_u8()? as usize;
+
+    reverse_arguments(stack, args)?;
+
+    Ok(InstructionResult::InvokeExternalChunk(module, export, args as u8))
+}
+
+// We need to reverse the order of the arguments
+fn reverse_arguments(stack: &mut Stack, args: usize) -> Result<(), VMError> {
     let inner = stack.get_inner();
     let len = inner.len();
     if len < args {
         return Err(VMError::NotEnoughArguments);
     }
 
-    stack.get_inner()[len - args..len].reverse();
-
-    Ok(InstructionResult::InvokeChunk(id))
+    inner[len - args..len].reverse();
+    Ok(())
 }
 
 pub fn syscall<'a>(backend: &Backend<'a>, stack: &mut Stack<'a>, manager: &mut ChunkManager<'a>, context: &mut Context<'a>) -> Result<InstructionResult, VMError> {
+    let offset = manager.index() - 1;
     let id = manager.read_u16()?;
     let on_value = manager.read_bool()?;
     let args = manager.read_u8()?;
 
+    let f = backend.environment.get_functions().get(id as usize)
+        .ok_or(VMError::UnknownSysCall)?;
+
+    if backend.is_strict_determinism() && !f.is_deterministic() {
+        return Err(VMError::NonDeterministicOperation(id));
+    }
+
+    // The values read from an iterator instance go through the chunks of its adapters first
+    if on_value && f.get_consumed_values() > 0 {
+        if let Some(chunk) = manager.resolve_native_instance(offset, f.get_consumed_values(), args as usize, stack, context)? {
+            return Ok(InstructionResult::InvokeChunk(chunk))
+        }
+    }
+
     let mut arguments = VecDeque::with_capacity(args as usize);
     for _ in 0..args {
         arguments.push_front(stack.pop_stack()?);
@@ -124,17 +179,74 @@ pub fn syscall<'a>(backend: &Backend<'a>, stack: &mut Stack<'a>, manager: &mut C
         None
     };
 
-    let f = backend.environment.get_functions().get(id as usize)
-        .ok_or(VMError::UnknownSysCall)?;
+    // An inline element borrows its whole array, it is copied
+    // as the array may be the instance
+    for argument in arguments.iter_mut().filter(|v| matches!(v, Path::Element(..))) {
+        let value = mem::replace(argument, Path::Owned(ValueCell::default())).into_owned();
+        *argument = Path::Owned(value);
+    }
+
+    // The instance is mutably borrowed during the call, so an argument containing it
+    // is copied before, otherwise it couldn't be read by the function
+    let instance_ptr = match on_value.as_ref() {
+        Some(instance @ Path::Wrapper(_)) => Some(instance.as_ref().as_value() as *const ValueCell),
+        Some(Path::Element(array, _)) => Some(array.as_ptr()),
+        _ => None
+    };
+
+    if let Some(ptr) = instance_ptr {
+        for argument in arguments.iter_mut() {
+            if argument.as_ref().as_value().contains_ptr(ptr, context.max_value_depth())? {
+                let value = mem::replace(argument, Path::Owned(ValueCell::default())).into_owned();
+                *argument = Path::Owned(value);
+            }
+        }
+    }
 
     let mut instance = match on_value.as_mut() {
         Some(v) => Some(v.as_mut()),
         None => None,
     };
 
-    if let Some(v) = f.call_function(instance.as_deref_mut(), arguments.into(), context)? {
-        stack.push_stack(Path::Owned(v))?;
+    let gas = context.current_gas_usage();
+    let result = f.call_function(instance.as_deref_mut(), arguments.into(), context);
+    context.trace_native_call(id, context.current_gas_usage().saturating_sub(gas));
+
+    match result {
+        Ok(Some(v)) => stack.push_stack(Path::Owned(v))?,
+        Ok(None) => {},
+        // Not an error, the VM rolls back the state and returns the value
+        Err(EnvironmentError::Revert(value)) => return Ok(InstructionResult::Revert(value)),
+        Err(e) => return Err(e.into())
     }
 
     Ok(InstructionResult::Nothing)
-}
\ No newline at end of file
+}
+pub fn call_precompile<'a>(backend: &Backend<'a>, stack: &mut Stack<'a>, manager: &mut ChunkManager<'a>, context: &mut Context<'a>) -> Result<InstructionResult, VMError> {
+    let id = manager.read_u16()?;
+    let precompile = backend.environment.get_precompile(id)
+        .ok_or(VMError::UnknownPrecompile(id))?;
+
+    let input = stack.pop_stack()?;
+    let input = match input.as_ref().as_value() {
+        ValueCell::TypedArray(array) => array.as_bytes()
+            .map(<[u8]>::to_vec)
+            .ok_or(EnvironmentError::InvalidParameter)?,
+        value => value.as_vec()?
+            .iter()
+            .map(|v| v.borrow().as_u8())
+            .collect::<Result<_, _>>()?
+    };
+
+    // Charged before the execution, a precompile may be expensive
+    let gas = precompile.base_gas()
+        .saturating_add(precompile.dynamic_gas(&input));
+    context.increase_gas_usage(gas)?;
+
+    let output = precompile.execute(&input)
+        .map_err(EnvironmentError::from)?;
+
+    stack.push_stack(Path::Owned(ValueCell::TypedArray(output.into())))?;
+
+    Ok(InstructionResult::Nothing)
+}
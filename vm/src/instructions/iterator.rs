@@ -1,6 +1,6 @@
 use crate::{
-    iterator::PathIterator,
     stack::Stack,
+    IteratorStep,
     Backend,
     ChunkManager,
     Context,
@@ -12,24 +12,24 @@ use super::InstructionResult;
 
 pub fn iterable_length<'a>(_: &Backend<'a>, stack: &mut Stack<'a>, _: &mut ChunkManager<'a>, _: &mut Context<'a>) -> Result<InstructionResult, VMError> {
     let value = stack.pop_stack()?;
-    let len = value.as_ref().as_vec()?.len();
+    let len = value.as_ref().array_len()?;
     stack.push_stack_unchecked(Path::Owned(ValueCell::Default(Value::U32(len as u32))));
     Ok(InstructionResult::Nothing)
 }
 
 pub fn iterator_begin<'a>(_: &Backend<'a>, stack: &mut Stack<'a>, manager: &mut ChunkManager<'a>, _: &mut Context<'a>) -> Result<InstructionResult, VMError> {
     let value = stack.pop_stack()?;
-    let iterator = PathIterator::new(value)?;
-    manager.add_iterator(iterator);
+    manager.begin_iterator(value)?;
     Ok(InstructionResult::Nothing)
 }
 
-pub fn iterator_next<'a>(_: &Backend<'a>, stack: &mut Stack<'a>, manager: &mut ChunkManager<'a>, _: &mut Context<'a>) -> Result<InstructionResult, VMError> {
+pub fn iterator_next<'a>(_: &Backend<'a>, stack: &mut Stack<'a>, manager: &mut ChunkManager<'a>, context: &mut Context<'a>) -> Result<InstructionResult, VMError> {
+    let offset = manager.index() - 1;
     let addr = manager.read_u32()?;
-    if let Some(value) = manager.next_iterator()? {
-        stack.push_stack(value)?;
-    } else {
-        manager.set_index(addr as usize)?;
+    match manager.next_iterator(offset, stack, context)? {
+        IteratorStep::Value(value) => stack.push_stack(value)?,
+        IteratorStep::Callback(chunk) => return Ok(InstructionResult::InvokeChunk(chunk)),
+        IteratorStep::End => manager.set_index(addr as usize)?
     }
     Ok(InstructionResult::Nothing)
 }
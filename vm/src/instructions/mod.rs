@@ -9,6 +9,7 @@ use iterator::*;
 use constructor::*;
 
 use xelis_bytecode::OpCode;
+use xelis_types::ValueCell;
 
 use crate::Context;
 
@@ -19,12 +20,22 @@ pub enum InstructionResult {
     Nothing,
     Break,
     InvokeChunk(u16),
+    // Constants ids of the module name and of the export name, and the count of arguments
+    InvokeExternalChunk(u16, u16, u8),
+    // The execution is stopped and its state changes are rolled back
+    // The value is given back to the caller
+    Revert(ValueCell),
 }
 
 // A handler is a function pointer to an instruction
 // With its associated cost
 pub type Handler<'a> = (fn(&Backend<'a>, &mut Stack<'a>, &mut ChunkManager<'a>, &mut Context<'a>) -> Result<InstructionResult, VMError>, u64);
 
+// A chunk compiled ahead of time to native code, see `VM::set_compiled_chunk`
+// It executes the instructions from the current index of the manager
+// and returns the first result that isn't Nothing, like the interpreter does
+pub type CompiledChunk<'a> = fn(&Backend<'a>, &mut Stack<'a>, &mut ChunkManager<'a>, &mut Context<'a>) -> Result<InstructionResult, VMError>;
+
 // Table of instructions
 // It contains all the instructions that the VM can execute
 // It is a fixed size array of 256 elements
@@ -69,11 +80,16 @@ impl<'a> InstructionTable<'a> {
         instructions[OpCode::Cast.as_usize()] = (cast, 1);
         instructions[OpCode::InvokeChunk.as_usize()] = (invoke_chunk, 5);
         instructions[OpCode::SysCall.as_usize()] = (syscall, 2);
+        instructions[OpCode::ExternalCall.as_usize()] = (external_call, 10);
+        instructions[OpCode::CallPrecompile.as_usize()] = (call_precompile, 5);
+        instructions[OpCode::ChunkId.as_usize()] = (chunk_id, 1);
         instructions[OpCode::NewArray.as_usize()] = (new_array, 1);
         instructions[OpCode::NewStruct.as_usize()] = (new_struct, 1);
         instructions[OpCode::NewRange.as_usize()] = (new_range, 1);
+        instructions[OpCode::NewRangeInclusive.as_usize()] = (new_range_inclusive, 1);
         instructions[OpCode::NewMap.as_usize()] = (new_map, 1);
         instructions[OpCode::NewEnum.as_usize()] = (new_enum, 1);
+        instructions[OpCode::Unpack.as_usize()] = (unpack, 1);
 
         instructions[OpCode::Add.as_usize()] = (add, 1);
         instructions[OpCode::Sub.as_usize()] = (sub, 1);
@@ -114,6 +130,28 @@ impl<'a> InstructionTable<'a> {
         instructions[OpCode::Inc.as_usize()] = (increment, 1);
         instructions[OpCode::Dec.as_usize()] = (decrement, 1);
 
+        instructions[OpCode::CheckedAdd.as_usize()] = (checked_add, 2);
+        instructions[OpCode::CheckedSub.as_usize()] = (checked_sub, 2);
+        instructions[OpCode::CheckedMul.as_usize()] = (checked_mul, 4);
+        instructions[OpCode::SaturatingAdd.as_usize()] = (saturating_add, 1);
+        instructions[OpCode::SaturatingSub.as_usize()] = (saturating_sub, 1);
+        instructions[OpCode::SaturatingMul.as_usize()] = (saturating_mul, 3);
+        instructions[OpCode::WrappingAdd.as_usize()] = (wrapping_add, 1);
+        instructions[OpCode::WrappingSub.as_usize()] = (wrapping_sub, 1);
+        instructions[OpCode::WrappingMul.as_usize()] = (wrapping_mul, 3);
+
+        // Superinstructions cost the sum of the instructions they replace
+        // so the gas used doesn't depend on the optimizations
+        instructions[OpCode::MemoryAdd.as_usize()] = (memory_add, 11);
+        instructions[OpCode::MemoryInc.as_usize()] = (memory_increment, 9);
+        instructions[OpCode::MemoryCast.as_usize()] = (memory_cast, 6);
+        instructions[OpCode::LtJumpIfFalse.as_usize()] = (lt_jump_if_false, 5);
+        instructions[OpCode::LteJumpIfFalse.as_usize()] = (lte_jump_if_false, 5);
+        instructions[OpCode::GtJumpIfFalse.as_usize()] = (gt_jump_if_false, 5);
+        instructions[OpCode::GteJumpIfFalse.as_usize()] = (gte_jump_if_false, 5);
+
+        instructions[OpCode::JumpTable.as_usize()] = (jump_table, 3);
+
         Self { instructions }
     }
 
@@ -127,6 +165,18 @@ impl<'a> InstructionTable<'a> {
         self.instructions[opcode.as_usize()].1 = cost;
     }
 
+    // Get the handler of an instruction with its cost
+    #[inline(always)]
+    pub fn get_instruction(&self, opcode: OpCode) -> Handler<'a> {
+        self.instructions[opcode.as_usize()]
+    }
+
+    // Get the cost of an instruction
+    #[inline(always)]
+    pub fn get_instruction_cost(&self, opcode: OpCode) -> u64 {
+        self.instructions[opcode.as_usize()].1
+    }
+
     // Execute an instruction
     pub fn execute(&self, opcode: u8, backend: &Backend<'a>, stack: &mut Stack<'a>, chunk_manager: &mut ChunkManager<'a>, context: &mut Context<'a>) -> Result<InstructionResult, VMError> {
         let (instruction, cost) = self.instructions[opcode as usize];
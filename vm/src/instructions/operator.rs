@@ -1,3 +1,5 @@
+use core::mem;
+use alloc::borrow::ToOwned;
 use crate::{
     stack::Stack,
     Backend,
@@ -5,7 +7,8 @@ use crate::{
     Context,
     VMError
 };
-use xelis_types::{Value, ValueCell, Type, Path};
+use xelis_bytecode::OpCode;
+use xelis_types::{Value, ValueCell, ValueError, Type, Path};
 
 use super::InstructionResult;
 
@@ -26,16 +29,17 @@ macro_rules! op {
     }};
 }
 
+// Checked addition of numbers or concatenation of strings
 macro_rules! op_string {
-    ($a: expr, $b: expr, $op: tt) => {{
+    ($a: expr, $b: expr, $fn: ident) => {{
         match ($a.as_value(), $b.as_value()) {
             (ValueCell::Default(a), ValueCell::Default(b)) => match (a, b) {
-                (Value::U8(a), Value::U8(b)) => Value::U8(a $op b),
-                (Value::U16(a), Value::U16(b)) => Value::U16(a $op b),
-                (Value::U32(a), Value::U32(b)) => Value::U32(a $op b),
-                (Value::U64(a), Value::U64(b)) => Value::U64(a $op b),
-                (Value::U128(a), Value::U128(b)) => Value::U128(a $op b),
-                (Value::U256(a), Value::U256(b)) => Value::U256(*a $op *b),
+                (Value::U8(a), Value::U8(b)) => Value::U8(a.$fn(*b).ok_or(VMError::ArithmeticOverflow)?),
+                (Value::U16(a), Value::U16(b)) => Value::U16(a.$fn(*b).ok_or(VMError::ArithmeticOverflow)?),
+                (Value::U32(a), Value::U32(b)) => Value::U32(a.$fn(*b).ok_or(VMError::ArithmeticOverflow)?),
+                (Value::U64(a), Value::U64(b)) => Value::U64(a.$fn(*b).ok_or(VMError::ArithmeticOverflow)?),
+                (Value::U128(a), Value::U128(b)) => Value::U128(a.$fn(*b).ok_or(VMError::ArithmeticOverflow)?),
+                (Value::U256(a), Value::U256(b)) => Value::U256(a.$fn(*b).ok_or(VMError::ArithmeticOverflow)?),
                 (Value::String(a), Value::String(b)) => {
                     // Verify the final len is less than u32::MAX
                     let len = (a.len() as u32).checked_add(b.len() as u32);
@@ -43,7 +47,7 @@ macro_rules! op_string {
                         return Err(VMError::StringTooLarge);
                     }
 
-                    Value::String(a.to_owned() $op b)
+                    Value::String(a.to_owned() + b)
                 }
                 _ => {
                     // we need to handle if one of the values is a string
@@ -56,7 +60,7 @@ macro_rules! op_string {
                             return Err(VMError::StringTooLarge);
                         }
 
-                        Value::String(left $op &right)
+                        Value::String(left + &right)
                     } else {
                         return Err(VMError::UnexpectedType)
                     }
@@ -86,13 +90,96 @@ macro_rules! op_bool {
     }};
 }
 
+// Call an integer method like `saturating_add` on two values of the same type
+macro_rules! op_method {
+    ($a: expr, $b: expr, $fn: ident) => {{
+        match ($a.as_value(), $b.as_value()) {
+            (ValueCell::Default(a), ValueCell::Default(b)) => match (a, b) {
+                (Value::U8(a), Value::U8(b)) => Value::U8(a.$fn(*b)),
+                (Value::U16(a), Value::U16(b)) => Value::U16(a.$fn(*b)),
+                (Value::U32(a), Value::U32(b)) => Value::U32(a.$fn(*b)),
+                (Value::U64(a), Value::U64(b)) => Value::U64(a.$fn(*b)),
+                (Value::U128(a), Value::U128(b)) => Value::U128(a.$fn(*b)),
+                (Value::U256(a), Value::U256(b)) => Value::U256(a.$fn(*b)),
+                _ => return Err(VMError::UnexpectedType)
+            }
+            _ => return Err(VMError::UnexpectedType)
+        }
+    }};
+}
+
+// Call a checked integer method like `checked_add` on two values of the same type
+// The result is an optional, null if the operation overflowed
+macro_rules! op_checked {
+    ($a: expr, $b: expr, $fn: ident) => {{
+        let value = match ($a.as_value(), $b.as_value()) {
+            (ValueCell::Default(a), ValueCell::Default(b)) => match (a, b) {
+                (Value::U8(a), Value::U8(b)) => a.$fn(*b).map(Value::U8),
+                (Value::U16(a), Value::U16(b)) => a.$fn(*b).map(Value::U16),
+                (Value::U32(a), Value::U32(b)) => a.$fn(*b).map(Value::U32),
+                (Value::U64(a), Value::U64(b)) => a.$fn(*b).map(Value::U64),
+                (Value::U128(a), Value::U128(b)) => a.$fn(*b).map(Value::U128),
+                (Value::U256(a), Value::U256(b)) => a.$fn(*b).map(Value::U256),
+                _ => return Err(VMError::UnexpectedType)
+            }
+            _ => return Err(VMError::UnexpectedType)
+        };
+
+        ValueCell::Optional(value.map(Into::into))
+    }};
+}
+
+// Operation on numbers returning an error on overflow
+macro_rules! op_overflow {
+    ($a: expr, $b: expr, $fn: ident) => {{
+        match ($a.as_value(), $b.as_value()) {
+            (ValueCell::Default(a), ValueCell::Default(b)) => match (a, b) {
+                (Value::U8(a), Value::U8(b)) => a.$fn(*b).map(Value::U8),
+                (Value::U16(a), Value::U16(b)) => a.$fn(*b).map(Value::U16),
+                (Value::U32(a), Value::U32(b)) => a.$fn(*b).map(Value::U32),
+                (Value::U64(a), Value::U64(b)) => a.$fn(*b).map(Value::U64),
+                (Value::U128(a), Value::U128(b)) => a.$fn(*b).map(Value::U128),
+                (Value::U256(a), Value::U256(b)) => a.$fn(*b).map(Value::U256),
+                _ => return Err(VMError::UnexpectedType)
+            }
+            _ => return Err(VMError::UnexpectedType)
+        }.ok_or(VMError::ArithmeticOverflow)?
+    }};
+}
+
+// Shift a value by an amount of the same type
+// Shifting by the number of bits of the type or more is an error
+macro_rules! op_shift {
+    ($a: expr, $b: expr, $fn: ident) => {{
+        let value = match ($a.as_value(), $b.as_value()) {
+            (ValueCell::Default(a), ValueCell::Default(b)) => match (a, b) {
+                (Value::U8(a), Value::U8(b)) => a.$fn(*b as u32).map(Value::U8),
+                (Value::U16(a), Value::U16(b)) => a.$fn(*b as u32).map(Value::U16),
+                (Value::U32(a), Value::U32(b)) => a.$fn(*b).map(Value::U32),
+                (Value::U64(a), Value::U64(b)) => u32::try_from(*b).ok().and_then(|b| a.$fn(b)).map(Value::U64),
+                (Value::U128(a), Value::U128(b)) => u32::try_from(*b).ok().and_then(|b| a.$fn(b)).map(Value::U128),
+                (Value::U256(a), Value::U256(b)) => b.as_u64()
+                    .and_then(|b| u32::try_from(b).ok())
+                    .and_then(|b| a.$fn(b))
+                    .map(Value::U256),
+                _ => return Err(VMError::UnexpectedType)
+            }
+            _ => return Err(VMError::UnexpectedType)
+        };
+
+        value.ok_or(VMError::ShiftOverflow)?
+    }};
+}
+
 macro_rules! opcode_op {
-    ($self: expr, $macr: tt, $op: tt) => {
+    ($self: expr, $context: expr, $macr: tt, $op: tt) => {
         {
             let right = $self.pop_stack()?;
             let left = $self.pop_stack()?;
+            let value = $macr!(left.as_ref(), right.as_ref(), $op);
+            release_operands($context, &left, &right)?;
             // Push the result to the stack, no need to check as we poped 2 values
-            $self.push_stack_unchecked(Path::Owned($macr!(left.as_ref(), right.as_ref(), $op).into()));
+            $self.push_stack_unchecked(Path::Owned(value.into()));
         }
     };
 }
@@ -145,11 +232,12 @@ macro_rules! op_div {
 }
 
 macro_rules! opcode_op_assign {
-    ($self: expr, $macr: tt, $op: tt) => {
+    ($self: expr, $context: expr, $macr: tt, $op: tt) => {
         {
             let right = $self.pop_stack()?;
             let mut left = $self.pop_stack()?;
             let result = $macr!(left.as_ref(), right.as_ref(), $op);
+            $context.decrease_value_memory_usage(&right)?;
             *left.as_mut() = result.into();
         }
     };
@@ -157,58 +245,215 @@ macro_rules! opcode_op_assign {
 
 macro_rules! opcode_fn {
     ($fn: ident, $macro1: tt, $macro2: tt, $op: tt) => {
-        pub fn $fn<'a>(_: &Backend<'a>, stack: &mut Stack<'a>, _: &mut ChunkManager<'a>, _: &mut Context<'a>) -> Result<InstructionResult, VMError> {
-            $macro1!(stack, $macro2, $op);
+        pub fn $fn<'a>(_: &Backend<'a>, stack: &mut Stack<'a>, _: &mut ChunkManager<'a>, context: &mut Context<'a>) -> Result<InstructionResult, VMError> {
+            $macro1!(stack, context, $macro2, $op);
             Ok(InstructionResult::Nothing)
         }
     };
 }
 
-opcode_fn!(add, opcode_op, op_string, +);
-opcode_fn!(sub, opcode_op, op, -);
-opcode_fn!(mul, opcode_op, op, *);
+// A string built by the concatenation is charged as new memory
+pub fn add<'a>(_: &Backend<'a>, stack: &mut Stack<'a>, _: &mut ChunkManager<'a>, context: &mut Context<'a>) -> Result<InstructionResult, VMError> {
+    let right = stack.pop_stack()?;
+    let left = stack.pop_stack()?;
+    let result = op_string!(left.as_ref(), right.as_ref(), checked_add);
+    charge_string(&result, context)?;
+    release_operands(context, &left, &right)?;
+
+    // Push the result to the stack, no need to check as we poped 2 values
+    stack.push_stack_unchecked(Path::Owned(result.into()));
+    Ok(InstructionResult::Nothing)
+}
+opcode_fn!(sub, opcode_op, op_overflow, checked_sub);
+opcode_fn!(mul, opcode_op, op_overflow, checked_mul);
 opcode_fn!(div, opcode_op, op_div, /);
-opcode_fn!(rem, opcode_op, op, %);
+opcode_fn!(rem, opcode_op, op_div, %);
 
 opcode_fn!(bitwise_and, opcode_op, op, &);
 opcode_fn!(bitwise_or, opcode_op, op, |);
 opcode_fn!(bitwise_xor, opcode_op, op, ^);
-opcode_fn!(bitwise_shl, opcode_op, op, <<);
-opcode_fn!(bitwise_shr, opcode_op, op, >>);
+opcode_fn!(bitwise_shl, opcode_op, op_shift, checked_shl);
+opcode_fn!(bitwise_shr, opcode_op, op_shift, checked_shr);
+
+// Composite values are compared deeply, primitives must have the same type
+pub fn eq<'a>(_: &Backend<'a>, stack: &mut Stack<'a>, _: &mut ChunkManager<'a>, context: &mut Context<'a>) -> Result<InstructionResult, VMError> {
+    let right = stack.pop_stack()?;
+    let left = stack.pop_stack()?;
+    let value = match (left.as_ref().as_value(), right.as_ref().as_value()) {
+        (ValueCell::Default(a), ValueCell::Default(b)) if !a.is_null() && !b.is_null() => op_bool!(left.as_ref(), right.as_ref(), ==),
+        (a, b) => {
+            // The depth is limited as a value may reference itself
+            let (equal, compared) = a.deep_eq(b, context.max_value_depth())?;
+            context.increase_gas_usage(compared as u64)?;
+            Value::Boolean(equal)
+        }
+    };
+    release_operands(context, &left, &right)?;
+
+    // Push the result to the stack, no need to check as we poped 2 values
+    stack.push_stack_unchecked(Path::Owned(value.into()));
+    Ok(InstructionResult::Nothing)
+}
+
+// Fused comparison and JumpIfFalse
+macro_rules! opcode_compare_jump {
+    ($fn: ident, $op: tt) => {
+        pub fn $fn<'a>(_: &Backend<'a>, stack: &mut Stack<'a>, manager: &mut ChunkManager<'a>, context: &mut Context<'a>) -> Result<InstructionResult, VMError> {
+            let addr = manager.read_u32()?;
+            let right = stack.pop_stack()?;
+            let left = stack.pop_stack()?;
+            let value = op_bool!(left.as_ref(), right.as_ref(), $op);
+            release_operands(context, &left, &right)?;
+            if let Value::Boolean(false) = value {
+                manager.set_index(addr as usize)?;
+            }
+
+            Ok(InstructionResult::Nothing)
+        }
+    };
+}
+
+opcode_compare_jump!(lt_jump_if_false, <);
+opcode_compare_jump!(lte_jump_if_false, <=);
+opcode_compare_jump!(gt_jump_if_false, >);
+opcode_compare_jump!(gte_jump_if_false, >=);
+
+// Dispatch of a switch on contiguous integers
+// The Jump of each case follows the instruction, ordered from the first case value
+pub fn jump_table<'a>(backend: &Backend<'a>, stack: &mut Stack<'a>, manager: &mut ChunkManager<'a>, _: &mut Context<'a>) -> Result<InstructionResult, VMError> {
+    let addr = manager.read_u32()?;
+    let first = backend.get_constant_with_id(manager.read_u16()? as usize)?.as_value()?;
+    let len = manager.read_u16()?;
+
+    let value = stack.pop_stack()?.into_owned().into_value()?;
+    if value.get_type()? != first.get_type()? {
+        return Err(VMError::UnexpectedType)
+    }
+
+    let index = value.cast_to_u256()?
+        .checked_sub(first.clone().cast_to_u256()?)
+        .and_then(|v| v.as_u64())
+        .filter(|v| *v < len as u64);
+
+    let target = match index {
+        Some(index) => manager.index() + index as usize * (1 + OpCode::Jump.arguments_bytes()),
+        None => addr as usize
+    };
+    manager.set_index(target)?;
+
+    Ok(InstructionResult::Nothing)
+}
 
-opcode_fn!(eq, opcode_op, op_bool, ==);
 opcode_fn!(gt, opcode_op, op_bool, >);
 opcode_fn!(lt, opcode_op, op_bool, <);
 opcode_fn!(gte, opcode_op, op_bool, >=);
 opcode_fn!(lte, opcode_op, op_bool, <=);
 
-opcode_fn!(add_assign, opcode_op_assign, op_string, +);
-opcode_fn!(sub_assign, opcode_op_assign, op, -);
-opcode_fn!(mul_assign, opcode_op_assign ,op, *);
-opcode_fn!(div_assign, opcode_op_assign, op, /);
-opcode_fn!(rem_assign, opcode_op_assign, op, %);
+opcode_fn!(checked_add, opcode_op, op_checked, checked_add);
+opcode_fn!(checked_sub, opcode_op, op_checked, checked_sub);
+opcode_fn!(checked_mul, opcode_op, op_checked, checked_mul);
+
+opcode_fn!(saturating_add, opcode_op, op_method, saturating_add);
+opcode_fn!(saturating_sub, opcode_op, op_method, saturating_sub);
+opcode_fn!(saturating_mul, opcode_op, op_method, saturating_mul);
+
+opcode_fn!(wrapping_add, opcode_op, op_method, wrapping_add);
+opcode_fn!(wrapping_sub, opcode_op, op_method, wrapping_sub);
+opcode_fn!(wrapping_mul, opcode_op, op_method, wrapping_mul);
+
+pub fn add_assign<'a>(_: &Backend<'a>, stack: &mut Stack<'a>, _: &mut ChunkManager<'a>, context: &mut Context<'a>) -> Result<InstructionResult, VMError> {
+    let right = stack.pop_stack()?;
+    let mut left = stack.pop_stack()?;
+    let result = op_string!(left.as_ref(), right.as_ref(), checked_add);
+    charge_string(&result, context)?;
+
+    // The previous value is replaced in place
+    let previous = context.value_memory_usage(left.as_ref().as_value())?;
+    context.decrease_memory_usage(previous);
+    context.decrease_value_memory_usage(&right)?;
+
+    *left.as_mut() = result.into();
+    Ok(InstructionResult::Nothing)
+}
+
+// Fused MemoryLoad, MemoryLoad and Add
+pub fn memory_add<'a>(_: &Backend<'a>, stack: &mut Stack<'a>, manager: &mut ChunkManager<'a>, context: &mut Context<'a>) -> Result<InstructionResult, VMError> {
+    let left = manager.read_u16()?;
+    let right = manager.read_u16()?;
+    let left = manager.from_register(left as usize)?.shareable();
+    let right = manager.from_register(right as usize)?.shareable();
+
+    let result = op_string!(left.as_ref(), right.as_ref(), checked_add);
+    charge_string(&result, context)?;
+
+    stack.push_stack(Path::Owned(result.into()))?;
+    Ok(InstructionResult::Nothing)
+}
+
+// Fused MemoryLoad, Constant 1 and AssignAdd
+// The overflow is reported like the AssignAdd it replaces
+pub fn memory_increment<'a>(_: &Backend<'a>, _: &mut Stack<'a>, manager: &mut ChunkManager<'a>, _: &mut Context<'a>) -> Result<InstructionResult, VMError> {
+    let index = manager.read_u16()?;
+    manager.from_register(index as usize)?
+        .as_mut()
+        .increment()
+        .map_err(|e| match e {
+            ValueError::Overflow => VMError::ArithmeticOverflow,
+            e => e.into()
+        })?;
+
+    Ok(InstructionResult::Nothing)
+}
+
+// The operands consumed by an operator are dropped
+#[inline]
+fn release_operands(context: &mut Context, left: &Path, right: &Path) -> Result<(), VMError> {
+    context.decrease_value_memory_usage(left)?;
+    context.decrease_value_memory_usage(right)?;
+    Ok(())
+}
+
+fn charge_string(value: &Value, context: &mut Context) -> Result<(), VMError> {
+    if let Value::String(s) = value {
+        context.increase_memory_usage(s.len())?;
+    }
+
+    Ok(())
+}
+opcode_fn!(sub_assign, opcode_op_assign, op_overflow, checked_sub);
+opcode_fn!(mul_assign, opcode_op_assign, op_overflow, checked_mul);
+opcode_fn!(div_assign, opcode_op_assign, op_div, /);
+opcode_fn!(rem_assign, opcode_op_assign, op_div, %);
 
 opcode_fn!(bitwise_and_assign, opcode_op_assign, op, &);
 opcode_fn!(bitwise_or_assign, opcode_op_assign, op, |);
 opcode_fn!(bitwise_xor_assign, opcode_op_assign, op, ^);
-opcode_fn!(bitwise_shl_assign, opcode_op_assign, op, <<);
-opcode_fn!(bitwise_shr_assign, opcode_op_assign, op, >>);
+opcode_fn!(bitwise_shl_assign, opcode_op_assign, op_shift, checked_shl);
+opcode_fn!(bitwise_shr_assign, opcode_op_assign, op_shift, checked_shr);
 
+// Logical not for a bool, bitwise not for an integer
 pub fn neg<'a>(_: &Backend<'a>, stack: &mut Stack<'a>, _: &mut ChunkManager<'a>, _: &mut Context<'a>) -> Result<InstructionResult, VMError> {
     let value = stack.pop_stack()?;
-    stack.push_stack_unchecked(Path::Owned(Value::Boolean(!value.as_bool()?).into()));
+    let result = value.as_ref().as_value().as_value()?.not()?;
+    stack.push_stack_unchecked(Path::Owned(result.into()));
     Ok(InstructionResult::Nothing)
 }
 
 pub fn assign<'a>(_: &Backend<'a>, stack: &mut Stack<'a>, _: &mut ChunkManager<'a>, context: &mut Context<'a>) -> Result<InstructionResult, VMError> {
     let right = stack.pop_stack()?;
     let mut left = stack.pop_stack()?;
-    let owned = right.into_owned();
 
-    // Verify the depth of the owned value
-    owned.calculate_depth(context.max_value_depth())?;
+    // Verify the depth of the value
+    right.as_ref()
+        .calculate_depth(context.max_value_depth())?;
 
-    *left.as_mut() = owned;
+    context.increase_stored_memory_usage(&right)?;
+    let owned = right.into_owned();
+    let previous = mem::replace(&mut *left.as_mut(), owned);
+
+    // The previous value is replaced in place
+    let freed = context.owned_memory_usage(&previous)?;
+    context.decrease_memory_usage(freed);
     Ok(InstructionResult::Nothing)
 }
 
@@ -219,12 +464,12 @@ pub fn pow<'a>(_: &Backend<'a>, stack: &mut Stack<'a>, _: &mut ChunkManager<'a>,
         (ValueCell::Default(a), ValueCell::Default(b)) => {
             let pow_n = b.as_u32()?;
             match a {
-                Value::U8(a) => Value::U8(a.pow(pow_n)),
-                Value::U16(a) => Value::U16(a.pow(pow_n)),
-                Value::U32(a) => Value::U32(a.pow(pow_n)),
-                Value::U64(a) => Value::U64(a.pow(pow_n)),
-                Value::U128(a) => Value::U128(a.pow(pow_n)),
-                Value::U256(a) => Value::U256(a.pow(pow_n)),
+                Value::U8(a) => Value::U8(a.checked_pow(pow_n).ok_or(VMError::ArithmeticOverflow)?),
+                Value::U16(a) => Value::U16(a.checked_pow(pow_n).ok_or(VMError::ArithmeticOverflow)?),
+                Value::U32(a) => Value::U32(a.checked_pow(pow_n).ok_or(VMError::ArithmeticOverflow)?),
+                Value::U64(a) => Value::U64(a.checked_pow(pow_n).ok_or(VMError::ArithmeticOverflow)?),
+                Value::U128(a) => Value::U128(a.checked_pow(pow_n).ok_or(VMError::ArithmeticOverflow)?),
+                Value::U256(a) => Value::U256(a.checked_pow(pow_n).ok_or(VMError::ArithmeticOverflow)?),
                 _ => return Err(VMError::UnexpectedType)
             }
         }
@@ -243,12 +488,12 @@ pub fn pow_assign<'a>(_: &Backend<'a>, stack: &mut Stack<'a>, _: &mut ChunkManag
             (ValueCell::Default(a), ValueCell::Default(b)) => {
                 let pow_n = b.as_u32()?;
                 match a {
-                    Value::U8(a) => Value::U8(a.pow(pow_n)),
-                    Value::U16(a) => Value::U16(a.pow(pow_n)),
-                    Value::U32(a) => Value::U32(a.pow(pow_n)),
-                    Value::U64(a) => Value::U64(a.pow(pow_n)),
-                    Value::U128(a) => Value::U128(a.pow(pow_n)),
-                    Value::U256(a) => Value::U256(a.pow(pow_n)),
+                    Value::U8(a) => Value::U8(a.checked_pow(pow_n).ok_or(VMError::ArithmeticOverflow)?),
+                    Value::U16(a) => Value::U16(a.checked_pow(pow_n).ok_or(VMError::ArithmeticOverflow)?),
+                    Value::U32(a) => Value::U32(a.checked_pow(pow_n).ok_or(VMError::ArithmeticOverflow)?),
+                    Value::U64(a) => Value::U64(a.checked_pow(pow_n).ok_or(VMError::ArithmeticOverflow)?),
+                    Value::U128(a) => Value::U128(a.checked_pow(pow_n).ok_or(VMError::ArithmeticOverflow)?),
+                    Value::U256(a) => Value::U256(a.checked_pow(pow_n).ok_or(VMError::ArithmeticOverflow)?),
                     _ => return Err(VMError::UnexpectedType)
                 }
             }
@@ -260,11 +505,32 @@ pub fn pow_assign<'a>(_: &Backend<'a>, stack: &mut Stack<'a>, _: &mut ChunkManag
     Ok(InstructionResult::Nothing)
 }
 
-pub fn cast<'a>(_: &Backend<'a>, stack: &mut Stack<'a>, manager: &mut ChunkManager<'a>, _: &mut Context<'a>) -> Result<InstructionResult, VMError> {
+pub fn cast<'a>(_: &Backend<'a>, stack: &mut Stack<'a>, manager: &mut ChunkManager<'a>, context: &mut Context<'a>) -> Result<InstructionResult, VMError> {
+    let _type = manager.read_type()?;
+    let current = stack.pop_stack()?;
+    context.decrease_value_memory_usage(&current)?;
+
+    let value = cast_to(current.into_owned(), &_type)?;
+    charge_string(&value, context)?;
+    stack.push_stack(Path::Owned(value.into()))?;
+    Ok(InstructionResult::Nothing)
+}
+
+// Fused MemoryLoad and Cast
+pub fn memory_cast<'a>(_: &Backend<'a>, stack: &mut Stack<'a>, manager: &mut ChunkManager<'a>, context: &mut Context<'a>) -> Result<InstructionResult, VMError> {
+    let index = manager.read_u16()?;
     let _type = manager.read_type()?;
-    let current = stack.pop_stack()?
+    let current = manager.from_register(index as usize)?
+        .shareable()
         .into_owned();
 
+    let value = cast_to(current, &_type)?;
+    charge_string(&value, context)?;
+    stack.push_stack(Path::Owned(value.into()))?;
+    Ok(InstructionResult::Nothing)
+}
+
+fn cast_to(current: ValueCell, _type: &Type) -> Result<Value, VMError> {
     let value = match _type {
         Type::U8 => Value::U8(current.cast_to_u8()?),
         Type::U16 => Value::U16(current.cast_to_u16()?),
@@ -276,8 +542,7 @@ pub fn cast<'a>(_: &Backend<'a>, stack: &mut Stack<'a>, manager: &mut ChunkManag
         _ => return Err(VMError::UnsupportedCastType)
     };
 
-    stack.push_stack(Path::Owned(value.into()))?;
-    Ok(InstructionResult::Nothing)
+    Ok(value)
 }
 
 pub fn and<'a>(_: &Backend<'a>, stack: &mut Stack<'a>, _: &mut ChunkManager<'a>, _: &mut Context<'a>) -> Result<InstructionResult, VMError> {
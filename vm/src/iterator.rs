@@ -1,46 +1,87 @@
-use xelis_types::{Path, Type, Value, ValueCell, ValueError};
+use alloc::vec;
+use xelis_types::{LazyIterator, Path, SubValue, Value, ValueCell, ValueError};
+
+// Step of the iterator of a foreach
+#[derive(Debug)]
+pub enum IteratorStep<'a> {
+    // Next value produced
+    Value(Path<'a>),
+    // The chunk of an adapter must be invoked with the value pushed on the stack,
+    // then the instruction is executed again with its result
+    Callback(u16),
+    // No value left
+    End,
+}
 
 #[derive(Debug)]
 pub struct PathIterator<'a> {
     inner: Path<'a>,
     index: Value,
+    // Copy of a lazy iterator, or the numbers of a range, advanced in place of the index
+    lazy: Option<LazyIterator>,
 }
 
 impl<'a> PathIterator<'a> {
     pub fn new(inner: Path<'a>) -> Result<Self, ValueError> {
-        let index = match inner.as_ref().as_value() {
-            ValueCell::Default(Value::Range(_, _, index_type)) => match index_type {
-                Type::U8 => Value::U8(0),
-                Type::U16 => Value::U16(0),
-                Type::U32 => Value::U32(0),
-                Type::U64 => Value::U64(0),
-                Type::U128 => Value::U128(0),
-                Type::U256 => Value::U256(0u64.into()),
-                _ => return Err(ValueError::InvalidPrimitiveType),
+        let lazy = match inner.as_ref().as_value() {
+            ValueCell::Iterator(iter) => Some(iter.as_ref().clone()),
+            ValueCell::Default(Value::Range(start, end, _type, inclusive)) => {
+                if !_type.is_primitive() {
+                    return Err(ValueError::InvalidPrimitiveType)
+                }
+
+                Some(LazyIterator::from_range(start, end, _type.clone(), *inclusive)?)
             },
-            _ => Value::U32(0),
+            _ => None
         };
 
-        Ok(PathIterator { inner, index })
+        Ok(PathIterator { inner, index: Value::U32(0), lazy })
+    }
+
+    // Chunk of an adapter to invoke with a value before producing the next one
+    pub fn resolve(&mut self) -> Result<Option<(u16, SubValue)>, ValueError> {
+        match self.lazy.as_mut() {
+            Some(lazy) if lazy.has_callbacks() => lazy.resolve(1),
+            _ => Ok(None)
+        }
+    }
+
+    // Give the result of the chunk returned by `resolve`
+    // The value dropped by the adapter is returned
+    pub fn set_result(&mut self, result: SubValue) -> Result<Option<SubValue>, ValueError> {
+        self.lazy.as_mut()
+            .ok_or(ValueError::UnresolvedIterator)?
+            .set_result(result)
     }
 
     pub fn next(&mut self) -> Result<Option<Path<'a>>, ValueError> {
+        if let Some(lazy) = self.lazy.as_mut() {
+            return Ok(lazy.next_value()?.map(Path::Wrapper))
+        }
+
         let index = self.index.clone();
         self.index.increment()?;
 
-        let value = self.inner.as_ref();
-        Ok(match value.as_value() {
+        // The values shared with a copy are copied before one is referenced
+        let mut value = self.inner.as_mut();
+        Ok(match value.as_value_mut() {
             ValueCell::Array(v) => {
                 let index = index.to_u32()? as usize;
-                v.get(index)
-                .map(|v| Path::Wrapper(v.reference()))
+                v.make_mut()
+                    .get(index)
+                    .map(|v| Path::Wrapper(v.reference()))
             },
-            ValueCell::Default(Value::Range(start, end, _type)) => {
-                if index >= **start && index < **end {
-                    Some(Path::Owned(ValueCell::Default(index)))
-                } else {
-                    None
-                }
+            // Inline values are copied, no reference to keep
+            ValueCell::TypedArray(array) => {
+                let index = index.to_u32()? as usize;
+                array.get(index).map(Path::from)
+            },
+            // Each entry is a tuple of a copy of the key and its value
+            ValueCell::Map(map) => {
+                let index = index.to_u32()? as usize;
+                map.make_mut()
+                    .get_index(index)
+                    .map(|(key, value)| Path::Owned(ValueCell::Array(vec![key.clone().into(), value.reference()].into())))
             },
             _ => None,
         })
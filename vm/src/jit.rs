@@ -0,0 +1,424 @@
+// Experimental JIT compiling the hot chunks to native code with Cranelift
+//
+// A chunk is compiled once it has been invoked `threshold` times.
+// The native code keeps the values in the stack of the VM: each instruction
+// calls its handler from the instruction table, but the dispatch loop is removed
+// and the jumps are native branches between the basic blocks of the chunk.
+// The gas of each instruction is charged before executing it like the interpreter,
+// so an execution failing or running out of gas in a block has the same error and gas usage.
+// A chunk calling a native function (SysCall) is never compiled and stays interpreted.
+
+use core::{ffi::c_void, mem};
+use std::collections::{BTreeMap, BTreeSet};
+use cranelift_codegen::{
+    ir::{condcodes::IntCC, types, AbiParam, Block, InstBuilder, Signature},
+    settings::{self, Configurable},
+};
+use cranelift_frontend::{FunctionBuilder, FunctionBuilderContext, Switch};
+use cranelift_jit::{JITBuilder, JITModule};
+use cranelift_module::{default_libcall_names, Module as _};
+use xelis_bytecode::{Chunk, Module, OpCode};
+use xelis_types::{HashMap, HashSet};
+
+use crate::{stack::Stack, Backend, ChunkManager, ChunkReader, Context, InstructionResult, InstructionTable, VMError};
+
+// Invocations of a chunk before compiling it
+pub const JIT_THRESHOLD: u32 = 100;
+
+// Status returned by the native code and the trampolines
+// A positive status is the index of the next instruction
+// The result of the instruction is stored in the frame
+const EXIT: i64 = -1;
+// The error is stored in the frame
+const ERROR: i64 = -2;
+// End of the chunk
+const BREAK: i64 = -3;
+// The chunk was entered at an index which isn't a basic block,
+// like after a pause of the execution: nothing was executed
+const INVALID: i64 = -4;
+
+type NativeChunk = unsafe extern "C" fn(*mut c_void, usize) -> i64;
+type HandlerFn<'a> = fn(&Backend<'a>, &mut Stack<'a>, &mut ChunkManager<'a>, &mut Context<'a>) -> Result<InstructionResult, VMError>;
+
+// State given to the trampolines called by the native code
+struct Frame<'f, 'a> {
+    backend: &'f Backend<'a>,
+    stack: &'f mut Stack<'a>,
+    manager: &'f mut ChunkManager<'a>,
+    context: &'f mut Context<'a>,
+    result: Option<Result<InstructionResult, VMError>>,
+}
+
+// Charge the gas of an instruction and execute its handler, its arguments start at the index
+unsafe extern "C" fn execute(frame: *mut c_void, handler: usize, index: usize, gas: u64) -> i64 {
+    let frame = &mut *(frame as *mut Frame);
+    let handler = mem::transmute::<usize, HandlerFn>(handler);
+    let result = frame.manager.set_index(index)
+        .and_then(|_| frame.context.increase_gas_usage(gas).map_err(VMError::from))
+        .and_then(|_| handler(frame.backend, frame.stack, frame.manager, frame.context));
+
+    match result {
+        Ok(InstructionResult::Nothing) => frame.manager.index() as i64,
+        Ok(result) => {
+            frame.result = Some(Ok(result));
+            EXIT
+        },
+        Err(e) => {
+            frame.result = Some(Err(e));
+            ERROR
+        }
+    }
+}
+
+// Charge the gas of an instruction inlined in the native code, its arguments start at the index
+unsafe extern "C" fn charge(frame: *mut c_void, gas: u64, index: usize) -> i64 {
+    let frame = &mut *(frame as *mut Frame);
+    let result = frame.manager.set_index(index)
+        .and_then(|_| frame.context.increase_gas_usage(gas).map_err(VMError::from));
+
+    match result {
+        Ok(()) => 0,
+        Err(e) => {
+            frame.result = Some(Err(e));
+            ERROR
+        }
+    }
+}
+
+// A decoded instruction of a chunk
+struct Instruction {
+    offset: usize,
+    opcode: OpCode,
+    // Offset of the next instruction
+    next: usize,
+    // Address read by a jump
+    target: Option<usize>,
+    // Offsets of the jumps of a jump table, one per case
+    table: Vec<usize>,
+}
+
+impl Instruction {
+    // Does the instruction end its basic block
+    fn ends_block(&self) -> bool {
+        self.opcode.is_jump() || matches!(self.opcode, OpCode::Return | OpCode::InvokeChunk | OpCode::ExternalCall)
+    }
+}
+
+pub(crate) struct Jit {
+    // Invocations needed to compile a chunk
+    threshold: u32,
+    // Invocations of the chunks, by address of their module and id
+    counters: HashMap<(usize, u16), u32>,
+    // Compiled chunks
+    functions: HashMap<(usize, u16), NativeChunk>,
+    // Chunks that can't be compiled
+    rejected: HashSet<(usize, u16)>,
+    // Owner of the native code, created on the first compilation
+    module: Option<JITModule>,
+}
+
+// The native code is only executed by the thread running the VM
+#[cfg(feature = "sync")]
+unsafe impl Send for Jit {}
+
+impl Default for Jit {
+    fn default() -> Self {
+        Self {
+            threshold: JIT_THRESHOLD,
+            counters: HashMap::default(),
+            functions: HashMap::default(),
+            rejected: HashSet::default(),
+            module: None,
+        }
+    }
+}
+
+impl Jit {
+    // Set the invocations needed to compile a chunk
+    pub fn set_threshold(&mut self, threshold: u32) {
+        self.threshold = threshold;
+    }
+
+    // Count of chunks compiled
+    #[cfg(test)]
+    pub fn compiled_chunks(&self) -> usize {
+        self.functions.len()
+    }
+
+    // Forget the compiled chunks, they are compiled again once hot
+    // The gas costs and the handlers of the table are embedded in the native code
+    pub fn invalidate(&mut self) {
+        self.counters.clear();
+        self.functions.clear();
+        self.rejected.clear();
+    }
+
+    // Count an invocation of the chunk, compiling it when it becomes hot
+    pub fn on_call(&mut self, module: &Module, id: u16, chunk: &Chunk, table: &InstructionTable) {
+        let key = (module as *const Module as usize, id);
+        let counter = self.counters.entry(key).or_insert(0);
+        *counter = counter.saturating_add(1);
+        if *counter < self.threshold || self.functions.contains_key(&key) || self.rejected.contains(&key) {
+            return
+        }
+
+        match self.compile(chunk, table) {
+            Some(function) => self.functions.insert(key, function),
+            None => {
+                self.rejected.insert(key);
+                None
+            }
+        };
+    }
+
+    // Execute the compiled chunk from the current index of the manager
+    // None is returned if the chunk must be interpreted
+    pub fn execute<'a>(&self, manager: &mut ChunkManager<'a>, backend: &Backend<'a>, stack: &mut Stack<'a>, context: &mut Context<'a>) -> Option<Result<InstructionResult, VMError>> {
+        let key = (manager.module() as *const Module as usize, manager.id());
+        let function = self.functions.get(&key)?;
+
+        let index = manager.index();
+        let mut frame = Frame {
+            backend,
+            stack,
+            manager,
+            context,
+            result: None
+        };
+
+        let status = unsafe { function(&mut frame as *mut Frame as *mut c_void, index) };
+        match status {
+            BREAK => Some(Ok(InstructionResult::Break)),
+            INVALID => None,
+            _ => frame.result.take()
+        }
+    }
+
+    // Decode the instructions of the chunk
+    // None is returned if the chunk calls a native function
+    // The jumps of a jump table are decoded as instructions
+    fn decode(chunk: &Chunk) -> Option<Vec<Instruction>> {
+        let mut reader = ChunkReader::new(chunk);
+        let mut instructions = Vec::new();
+        while reader.index() < chunk.get_instructions().len() {
+            let offset = reader.index();
+            let opcode = reader.read_op_code().ok()?;
+            if opcode == OpCode::SysCall {
+                return None
+            }
+
+            let target = if opcode.is_jump() {
+                Some(reader.read_u32().ok()? as usize)
+            } else {
+                reader.advance(opcode.arguments_bytes()).ok()?;
+                None
+            };
+
+            // The jumps of the cases follow the jump table
+            let table = if opcode == OpCode::JumpTable {
+                reader.advance(2).ok()?;
+                let len = reader.read_u16().ok()? as usize;
+                let size = 1 + OpCode::Jump.arguments_bytes();
+                (0..len).map(|i| reader.index() + i * size).collect()
+            } else {
+                Vec::new()
+            };
+
+            instructions.push(Instruction {
+                offset,
+                opcode,
+                next: reader.index(),
+                target,
+                table
+            });
+        }
+
+        Some(instructions)
+    }
+
+    // Compile a chunk to native code
+    fn compile(&mut self, chunk: &Chunk, table: &InstructionTable) -> Option<NativeChunk> {
+        let instructions = Self::decode(chunk)?;
+        let len = chunk.get_instructions().len();
+
+        // The basic blocks start at the chunk start, at the jump targets,
+        // after a block end and at the chunk end to return
+        let mut leaders = BTreeSet::from([0, len]);
+        for instruction in instructions.iter() {
+            if let Some(target) = instruction.target {
+                leaders.insert(target);
+            }
+            leaders.extend(instruction.table.iter().copied());
+
+            if instruction.ends_block() {
+                leaders.insert(instruction.next);
+            }
+        }
+
+        let module = match self.module.as_mut() {
+            Some(module) => module,
+            None => {
+                let mut flags = settings::builder();
+                flags.set("use_colocated_libcalls", "false").ok()?;
+                flags.set("is_pic", "false").ok()?;
+                let isa = cranelift_native::builder().ok()?
+                    .finish(settings::Flags::new(flags))
+                    .ok()?;
+
+                self.module.insert(JITModule::new(JITBuilder::with_isa(isa, default_libcall_names())))
+            }
+        };
+
+        let pointer = module.target_config().pointer_type();
+        let mut signature = module.make_signature();
+        signature.params.extend([AbiParam::new(pointer), AbiParam::new(pointer)]);
+        signature.returns.push(AbiParam::new(types::I64));
+
+        let mut execute_signature = Signature::new(signature.call_conv);
+        execute_signature.params.extend([AbiParam::new(pointer), AbiParam::new(pointer), AbiParam::new(pointer), AbiParam::new(types::I64)]);
+        execute_signature.returns.push(AbiParam::new(types::I64));
+
+        let mut charge_signature = Signature::new(signature.call_conv);
+        charge_signature.params.extend([AbiParam::new(pointer), AbiParam::new(types::I64), AbiParam::new(pointer)]);
+        charge_signature.returns.push(AbiParam::new(types::I64));
+
+        let mut context = module.make_context();
+        context.func.signature = signature.clone();
+        let mut function_context = FunctionBuilderContext::new();
+        let mut builder = FunctionBuilder::new(&mut context.func, &mut function_context);
+        let execute_signature = builder.import_signature(execute_signature);
+        let charge_signature = builder.import_signature(charge_signature);
+
+        let entry = builder.create_block();
+        builder.append_block_params_for_function_params(entry);
+        let dispatch = builder.create_block();
+        builder.append_block_param(dispatch, types::I64);
+        let exit = builder.create_block();
+        builder.append_block_param(exit, types::I64);
+        let invalid = builder.create_block();
+        let blocks: BTreeMap<usize, Block> = leaders.iter()
+            .map(|leader| (*leader, builder.create_block()))
+            .collect();
+
+        // Enter the chunk at the index of the manager
+        builder.switch_to_block(entry);
+        let frame = builder.block_params(entry)[0];
+        let mut index = builder.block_params(entry)[1];
+        if pointer != types::I64 {
+            index = builder.ins().uextend(types::I64, index);
+        }
+        builder.ins().jump(dispatch, &[index]);
+
+        // Go to the basic block starting at the index, also used by the jump tables
+        builder.switch_to_block(dispatch);
+        let index = builder.block_params(dispatch)[0];
+        let mut switch = Switch::new();
+        for (leader, block) in blocks.iter() {
+            switch.set_entry(*leader as u128, *block);
+        }
+        switch.emit(&mut builder, index, invalid);
+
+        builder.switch_to_block(invalid);
+        let status = builder.ins().iconst(types::I64, INVALID);
+        builder.ins().jump(exit, &[status]);
+
+        builder.switch_to_block(exit);
+        let status = builder.block_params(exit)[0];
+        builder.ins().return_(&[status]);
+
+        let mut instructions = instructions.iter().peekable();
+        for (leader, block) in blocks.iter() {
+            builder.switch_to_block(*block);
+            if *leader == len {
+                let status = builder.ins().iconst(types::I64, BREAK);
+                builder.ins().jump(exit, &[status]);
+                continue
+            }
+
+            // Instructions of the block
+            let mut block_instructions = Vec::new();
+            while let Some(instruction) = instructions.next_if(|i| i.offset == *leader || !leaders.contains(&i.offset)) {
+                block_instructions.push(instruction);
+            }
+
+            let mut terminated = false;
+            for instruction in block_instructions {
+                let cost = table.get_instruction_cost(instruction.opcode);
+                let index = builder.ins().iconst(pointer, (instruction.offset + 1) as i64);
+                let gas = builder.ins().iconst(types::I64, cost as i64);
+
+                // The jump and the return are inlined, only their gas is charged
+                if matches!(instruction.opcode, OpCode::Jump | OpCode::Return) && cost > 0 {
+                    let callee = builder.ins().iconst(pointer, charge as *const () as i64);
+                    let call = builder.ins().call_indirect(charge_signature, callee, &[frame, gas, index]);
+                    let status = builder.inst_results(call)[0];
+                    Self::exit_on_error(&mut builder, status, exit);
+                }
+
+                match instruction.opcode {
+                    OpCode::Jump => {
+                        builder.ins().jump(blocks[&instruction.target?], &[]);
+                        terminated = true;
+                    },
+                    OpCode::Return => {
+                        let status = builder.ins().iconst(types::I64, BREAK);
+                        builder.ins().jump(exit, &[status]);
+                        terminated = true;
+                    },
+                    opcode => {
+                        let handler = table.get_instruction(opcode).0 as usize;
+                        let callee = builder.ins().iconst(pointer, execute as *const () as i64);
+                        let handler = builder.ins().iconst(pointer, handler as i64);
+                        let call = builder.ins().call_indirect(execute_signature, callee, &[frame, handler, index, gas]);
+                        let status = builder.inst_results(call)[0];
+                        Self::exit_on_error(&mut builder, status, exit);
+
+                        // The handler sets the index of the next instruction
+                        if opcode == OpCode::JumpTable {
+                            builder.ins().jump(dispatch, &[status]);
+                            terminated = true;
+                        } else if let Some(target) = instruction.target {
+                            let taken = builder.ins().icmp_imm(IntCC::Equal, status, target as i64);
+                            builder.ins().brif(taken, blocks[&target], &[], blocks[&instruction.next], &[]);
+                            terminated = true;
+                        }
+                    }
+                }
+            }
+
+            if !terminated {
+                let next = leaders.range(leader + 1..).next()?;
+                builder.ins().jump(blocks[next], &[]);
+            }
+        }
+
+        builder.seal_all_blocks();
+        builder.finalize();
+
+        let id = module.declare_anonymous_function(&signature).ok()?;
+        module.define_function(id, &mut context).ok()?;
+        module.clear_context(&mut context);
+        module.finalize_definitions().ok()?;
+
+        let function = module.get_finalized_function(id);
+        Some(unsafe { mem::transmute::<*const u8, NativeChunk>(function) })
+    }
+
+    // Leave the native code if the status is negative
+    fn exit_on_error(builder: &mut FunctionBuilder, status: cranelift_codegen::ir::Value, exit: Block) {
+        let next = builder.create_block();
+        let failed = builder.ins().icmp_imm(IntCC::SignedLessThan, status, 0);
+        builder.ins().brif(failed, exit, &[status], next, &[]);
+        builder.switch_to_block(next);
+    }
+}
+
+impl Drop for Jit {
+    fn drop(&mut self) {
+        if let Some(module) = self.module.take() {
+            self.functions.clear();
+            // No compiled chunk can be executed anymore
+            unsafe { module.free_memory() };
+        }
+    }
+}
@@ -1,37 +1,115 @@
+#![cfg_attr(not(any(feature = "std", test)), no_std)]
+
+extern crate alloc;
+
 mod chunk;
 mod error;
 mod iterator;
 mod stack;
 mod validator;
 mod instructions;
+mod debugger;
+mod backtrace;
+#[cfg(feature = "std")]
+mod profiler;
+#[cfg(feature = "jit")]
+mod jit;
+mod runner;
+mod coverage;
+mod gas;
 
 #[cfg(test)]
 mod tests;
 
-use stack::Stack;
+use alloc::{borrow::ToOwned, boxed::Box, string::{String, ToString}, vec::Vec};
 
 // Re-export the necessary types
-pub use xelis_environment::{Environment, Context};
+pub use xelis_environment::{Environment, EnvironmentError, Context, Tracer, VALUE_MEMORY_USAGE};
+use xelis_environment::StateCheckpoint;
 pub use xelis_types::{EnumType, Path, StructType, Constant};
-pub use xelis_bytecode::Module;
+use xelis_types::{FromXelisValue, HashMap, Value, ValueCell, ENVIRONMENT_ENUM_ID_START};
+pub use xelis_bytecode::{ChunkSignature, Module, ModuleFeatures, ModuleVersion, OpCode};
+
+pub use stack::Stack;
+pub use iterator::IteratorStep;
 
 pub use validator::*;
 pub use instructions::*;
 pub use error::VMError;
 pub use chunk::*;
+pub use debugger::*;
+pub use backtrace::*;
+#[cfg(feature = "std")]
+pub use profiler::*;
+#[cfg(feature = "jit")]
+pub use jit::JIT_THRESHOLD;
+pub use runner::*;
+pub use coverage::*;
+pub use gas::*;
+
+// With the `sync` feature, a VM can be sent to another thread
+// and the module and environment can be shared between the VMs of several threads
+#[cfg(feature = "sync")]
+const _: fn() = || {
+    fn assert_send<T: Send>() {}
+    fn assert_sync<T: Send + Sync>() {}
+
+    assert_send::<VM<'static>>();
+    assert_sync::<Module>();
+    assert_sync::<Environment>();
+};
+
+// 256 elements maximum in the stack:
+// Function Call can have up to 255 arguments and 1 on value
+const STACK_SIZE: usize = 256;
 
 // 64 elements maximum in the call stack
 const CALL_STACK_SIZE: usize = 64;
 
+// Limits of an execution
+// Each limit reached has its own error: `StackOverflow`, `CallStackOverflow`, `NotEnoughGas` and `OutOfMemory`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct VMConfig {
+    // Maximum count of values in the stack
+    pub max_stack_size: usize,
+    // Maximum count of chunks in the call stack
+    pub max_call_depth: usize,
+    // Gas limit of the execution
+    pub max_gas: u64,
+    // Maximum bytes of memory allocated by the execution
+    pub max_memory: usize,
+    // Reject the calls to the natives which may diverge between hosts
+    pub strict_determinism: bool,
+}
+
+impl Default for VMConfig {
+    fn default() -> Self {
+        Self {
+            max_stack_size: STACK_SIZE,
+            max_call_depth: CALL_STACK_SIZE,
+            max_gas: u64::MAX,
+            max_memory: usize::MAX,
+            strict_determinism: false,
+        }
+    }
+}
+
 // Backend of the VM
 // This is the immutable part of the VM
 pub struct Backend<'a> {
-    // The module to execute
+    // The module of the chunk being executed
+    // Updated at each chunk switch so the constants and types are resolved in the right module
     module: &'a Module,
     // The environment of the VM
     environment: &'a Environment,
     // The instruction table of the VM
     table: InstructionTable<'a>,
+    // Modules callable using their exports
+    linked_modules: HashMap<String, &'a Module>,
+    // Chunks compiled ahead of time, by address of their module and id
+    compiled_chunks: HashMap<(usize, u16), CompiledChunk<'a>>,
+    // Are the non deterministic natives rejected
+    strict_determinism: bool,
 }
 
 impl<'a> Backend<'a> {
@@ -56,21 +134,107 @@ impl<'a> Backend<'a> {
 
     // Get an enum with an id
     #[inline]
-    pub fn get_enum_with_id(&self, mut id: usize) -> Result<&EnumType, VMError> {
-        let env_enums = self.environment.get_enums();
-        if let Some(enum_type) = env_enums.get_index(id) {
-            return Ok(enum_type);
-        } else {
-            id -= env_enums.len();
+    pub fn get_enum_with_id(&self, id: usize) -> Result<&EnumType, VMError> {
+        // The environment enums have their own ids, after the module ones
+        let enum_type = match id.checked_sub(ENVIRONMENT_ENUM_ID_START as usize) {
+            Some(index) => self.environment.get_enums().get_index(index),
+            None => self.module.get_enum_at(id)
+        };
+
+        enum_type.ok_or(VMError::EnumNotFound)
+    }
+
+    // Get the cost of an instruction in the table of the VM
+    #[inline(always)]
+    pub fn get_instruction_cost(&self, opcode: OpCode) -> u64 {
+        self.table.get_instruction_cost(opcode)
+    }
+
+    // Get the environment of the VM
+    #[inline(always)]
+    pub fn environment(&self) -> &'a Environment {
+        self.environment
+    }
+
+    // Are the non deterministic natives rejected
+    #[inline(always)]
+    pub fn is_strict_determinism(&self) -> bool {
+        self.strict_determinism
+    }
+}
+
+// Result of a VM execution with a limited amount of instructions
+#[derive(Debug)]
+pub enum RunResult {
+    // The execution is done and returned a value
+    Finished(Constant),
+    // The budget was consumed before the end
+    // Call `resume` or `run_for` again to continue the execution
+    Paused,
+    // The execution was reverted with a value
+    // Its state changes were rolled back
+    Reverted(Constant),
+}
+
+// Outcome of a complete VM execution
+// A revert is a controlled failure, unlike the errors it is expected by the contract
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ExecutionOutcome {
+    // The execution returned a value
+    Success(Constant),
+    // The execution reverted with a value and its state changes were rolled back
+    Reverted(Constant),
+}
+
+impl ExecutionOutcome {
+    // Did the execution return normally
+    #[inline]
+    pub fn is_success(&self) -> bool {
+        matches!(self, Self::Success(_))
+    }
+
+    // Get the value returned or reverted with
+    #[inline]
+    pub fn value(&self) -> &Constant {
+        match self {
+            Self::Success(value) | Self::Reverted(value) => value
+        }
+    }
+
+    // Get the returned value, or the reverted one as the error
+    #[inline]
+    pub fn into_result(self) -> Result<Constant, Constant> {
+        match self {
+            Self::Success(value) => Ok(value),
+            Self::Reverted(value) => Err(value)
         }
+    }
+}
+
+// Entry of the module, by its exported name or its chunk id
+#[derive(Debug, Clone, Copy)]
+pub enum EntryRef<'b> {
+    Name(&'b str),
+    Id(u16),
+}
 
-        self.module.get_enum_at(id).ok_or(VMError::EnumNotFound)
+impl<'b> From<&'b str> for EntryRef<'b> {
+    fn from(name: &'b str) -> Self {
+        Self::Name(name)
+    }
+}
+
+impl From<u16> for EntryRef<'_> {
+    fn from(id: u16) -> Self {
+        Self::Id(id)
     }
 }
 
 // Virtual Machine to execute the bytecode from chunks of a Module.
 pub struct VM<'a> {
     backend: Backend<'a>,
+    // The module invoked by the host
+    module: &'a Module,
     // The call stack of the VM
     // Every chunks to proceed are stored here
     call_stack: Vec<ChunkManager<'a>>,
@@ -79,25 +243,55 @@ pub struct VM<'a> {
     stack: Stack<'a>,
     // Context given to each instruction
     context: Context<'a>,
+    // Maximum count of chunks in the call stack
+    max_call_depth: usize,
+    // State of the Context before the current execution
+    // Kept while the execution is paused to be able to revert it
+    checkpoint: Option<StateCheckpoint>,
+    // Native code of the hot chunks
+    #[cfg(feature = "jit")]
+    jit: jit::Jit,
 }
 
 impl<'a> VM<'a> {
     // Create a new VM
     pub fn new(module: &'a Module, environment: &'a Environment) -> Self {
-        Self::with(module, environment, InstructionTable::new(), Context::default())
+        Self::with_config(module, environment, VMConfig::default())
+    }
+
+    // Create a new VM with the limits of the config
+    pub fn with_config(module: &'a Module, environment: &'a Environment, config: VMConfig) -> Self {
+        let mut context = Context::default();
+        context.set_gas_limit(config.max_gas);
+        context.set_max_memory(config.max_memory);
+
+        let mut vm = Self::with(module, environment, InstructionTable::new(), context);
+        vm.stack = Stack::new(config.max_stack_size);
+        vm.max_call_depth = config.max_call_depth;
+        vm.backend.strict_determinism = config.strict_determinism;
+        vm
     }
 
     // Create a new VM with a given table and context
+    // The stack and call stack use the default limits
     pub fn with(module: &'a Module, environment: &'a Environment, table: InstructionTable<'a>, context: Context<'a>) -> Self {
         Self {
             backend: Backend {
                 module,
                 environment,
                 table,
+                linked_modules: HashMap::default(),
+                compiled_chunks: HashMap::default(),
+                strict_determinism: false,
             },
+            module,
             call_stack: Vec::with_capacity(4),
-            stack: Stack::new(),
+            stack: Stack::new(STACK_SIZE),
             context,
+            max_call_depth: CALL_STACK_SIZE,
+            checkpoint: None,
+            #[cfg(feature = "jit")]
+            jit: jit::Jit::default(),
         }
     }
 
@@ -107,6 +301,12 @@ impl<'a> VM<'a> {
         &self.stack
     }
 
+    // Get the call stack, the last element is the current chunk
+    #[inline]
+    pub fn call_stack(&self) -> &[ChunkManager<'a>] {
+        &self.call_stack
+    }
+
     // Get the context
     #[inline]
     pub fn context(&self) -> &Context<'a> {
@@ -128,9 +328,74 @@ impl<'a> VM<'a> {
     // Get a mutable reference to the instruction table
     #[inline]
     pub fn table_mut(&mut self) -> &mut InstructionTable<'a> {
+        // The chunks compiled by the JIT embed the handlers and the costs of the table
+        #[cfg(feature = "jit")]
+        self.jit.invalidate();
+
         &mut self.backend.table
     }
 
+    // Get the module executed
+    #[inline]
+    pub fn module(&self) -> &'a Module {
+        self.module
+    }
+
+    // Link a module so its exported chunks can be called using ExternalCall
+    // The linked module shares the gas, the stack and the call stack limits of the execution
+    pub fn link_module(&mut self, name: impl Into<String>, module: &'a Module) {
+        self.backend.linked_modules.insert(name.into(), module);
+    }
+
+    // Get the name a module was linked with
+    // None if it isn't linked, like the module executed
+    pub fn linked_module_name(&self, module: &Module) -> Option<&str> {
+        self.backend.linked_modules.iter()
+            .find(|(_, linked)| core::ptr::eq(**linked, module))
+            .map(|(name, _)| name.as_str())
+    }
+
+    // Reject the calls to the natives marked as non deterministic
+    // The modules should also be verified with the strict determinism of the validator
+    pub fn set_strict_determinism(&mut self, strict: bool) {
+        self.backend.strict_determinism = strict;
+    }
+
+    // Execute a chunk of a module with its compiled version instead of interpreting it
+    // The compiled chunk must be generated from this exact module, see the `xelis-aot` crate
+    // It is only used when no tracer is set and no instructions budget is given,
+    // otherwise the chunk is interpreted so every instruction can be traced or counted
+    // A chunk which started to be interpreted stays interpreted until its end
+    pub fn set_compiled_chunk(&mut self, module: &'a Module, id: u16, chunk: CompiledChunk<'a>) {
+        self.backend.compiled_chunks.insert((module as *const Module as usize, id), chunk);
+    }
+
+    // Execute the chunk with its compiled version if there is one and it can be used
+    #[inline]
+    fn execute_compiled(&mut self, manager: &mut ChunkManager<'a>, budget: Option<u64>) -> Option<Result<InstructionResult, VMError>> {
+        if budget.is_some() || self.context.has_tracer() || manager.is_interpreted() {
+            return None
+        }
+
+        if !self.backend.compiled_chunks.is_empty() {
+            let key = (manager.module() as *const Module as usize, manager.id());
+            if let Some(chunk) = self.backend.compiled_chunks.get(&key) {
+                return Some(chunk(&self.backend, &mut self.stack, manager, &mut self.context))
+            }
+        }
+
+        #[cfg(feature = "jit")]
+        return self.jit.execute(manager, &self.backend, &mut self.stack, &mut self.context);
+        #[cfg(not(feature = "jit"))]
+        None
+    }
+
+    // Set the invocations of a chunk needed to compile it to native code
+    #[cfg(feature = "jit")]
+    pub fn set_jit_threshold(&mut self, threshold: u32) {
+        self.jit.set_threshold(threshold);
+    }
+
     // Get the environment
     #[inline]
     pub fn environment(&self) -> &Environment {
@@ -139,18 +404,48 @@ impl<'a> VM<'a> {
 
     // Invoke a chunk using its id
     pub(crate) fn invoke_chunk_id(&mut self, id: u16) -> Result<(), VMError> {
-        if self.call_stack.len() >= CALL_STACK_SIZE {
+        self.push_chunk(self.module, id)
+    }
+
+    // Push a chunk of a module in the call stack
+    fn push_chunk(&mut self, module: &'a Module, id: u16) -> Result<(), VMError> {
+        if self.call_stack.len() >= self.max_call_depth {
             return Err(VMError::CallStackOverflow);
         }
 
-        let chunk = self.backend.module.get_chunk_at(id as usize)
+        let chunk = module.get_chunk_at(id as usize)
             .ok_or(VMError::ChunkNotFound)?;
 
-        let manager = ChunkManager::new(chunk);
+        self.context.trace_call(id);
+        #[cfg(feature = "jit")]
+        self.jit.on_call(module, id, chunk, &self.backend.table);
+
+        let manager = ChunkManager::new(module, id, chunk);
         self.call_stack.push(manager);
         Ok(())
     }
 
+    // Find an exported chunk using the names stored in the constants of the current module
+    // The names of the module and of the export are returned with it
+    fn resolve_export(&self, module: u16, export: u16) -> Result<(&'a Module, u16, &'a str, &'a str), VMError> {
+        let name = |id: u16| -> Result<&'a str, VMError> {
+            match self.backend.module.get_constant_at(id as usize) {
+                Some(Constant::Default(Value::String(s))) => Ok(s.as_str()),
+                _ => Err(VMError::InvalidNameConstant)
+            }
+        };
+
+        let module_name = name(module)?;
+        let module = *self.backend.linked_modules.get(module_name)
+            .ok_or_else(|| VMError::ModuleNotFound(module_name.to_owned()))?;
+
+        let export_name = name(export)?;
+        let id = module.get_export(export_name)
+            .ok_or_else(|| VMError::ExportNotFound(export_name.to_owned()))?;
+
+        Ok((module, id as u16, module_name, export_name))
+    }
+
     // Invoke a chunk using its id and arguments
     pub fn invoke_chunk_with_args<V: Into<Path<'a>>, I: Iterator<Item = V> + ExactSizeIterator>(&mut self, id: u16, args: I) -> Result<(), VMError> {
         self.stack.extend_stack(args.map(Into::into))?;
@@ -159,7 +454,7 @@ impl<'a> VM<'a> {
 
     // Invoke an entry chunk using its id
     pub fn invoke_entry_chunk(&mut self, id: u16) -> Result<(), VMError> {
-        if !self.backend.module.is_entry_chunk(id as usize) {
+        if !self.module.is_entry_chunk(id as usize) {
             return Err(VMError::ChunkNotEntry);
         }
         self.invoke_chunk_id(id)
@@ -172,24 +467,193 @@ impl<'a> VM<'a> {
         Ok(())
     }
 
+    // Invoke an entry with its parameters and run it until it returns
+    // The parameters are verified against the signature stored in the module by the compiler,
+    // an entry without a signature is expected to have no parameter
+    pub fn invoke_entry<'b, T: FromXelisValue>(&mut self, entry: impl Into<EntryRef<'b>>, params: Vec<Constant>) -> Result<T, VMError> {
+        let id = match entry.into() {
+            EntryRef::Name(name) => self.module.entry_by_name(name)
+                .ok_or_else(|| VMError::EntryNotFound(name.to_owned()))?,
+            EntryRef::Id(id) => id as usize,
+        };
+
+        if !self.module.is_entry_chunk(id) {
+            return Err(VMError::EntryNotFound(id.to_string()));
+        }
+
+        let signature = self.module.get_signature(id)
+            .map(ChunkSignature::parameters)
+            .unwrap_or_default();
+        if signature.len() != params.len() {
+            return Err(VMError::InvalidEntryParamsCount(signature.len(), params.len()));
+        }
+
+        for (i, (param, (_, _type))) in params.iter().zip(signature).enumerate() {
+            if !param.is_of_type(_type) {
+                return Err(VMError::InvalidEntryParams(i, _type.clone()));
+            }
+        }
+
+        // the first parameter is read from the top of the stack
+        self.invoke_entry_chunk_with_args(id as u16, params.into_iter().rev().map(ValueCell::from))?;
+        let value = match self.run()? {
+            ExecutionOutcome::Success(value) => value,
+            ExecutionOutcome::Reverted(value) => return Err(VMError::Reverted(value))
+        };
+
+        Ok(T::from_xelis_value(value.into())?)
+    }
+
+    // Is there any chunk left to execute
+    #[inline]
+    pub fn has_pending_execution(&self) -> bool {
+        !self.call_stack.is_empty()
+    }
+
     // Run the VM
     // It will execute the bytecode
     // First chunk executed should always return a value
-    pub fn run(&mut self) -> Result<Constant, VMError> {
-        while let Some(mut manager) = self.call_stack.pop() {
-            while let Some(opcode) = manager.next_u8() {
-                match self.backend.table.execute(opcode, &self.backend, &mut self.stack, &mut manager, &mut self.context)? {
-                    InstructionResult::Nothing => {},
-                    InstructionResult::InvokeChunk(id) => {
-                        if self.backend.module.is_entry_chunk(id as usize) {
-                            return Err(VMError::EntryChunkCalled);
-                        }
+    // A reverted execution has its state changes rolled back
+    pub fn run(&mut self) -> Result<ExecutionOutcome, VMError> {
+        match self.execute(None)? {
+            RunResult::Finished(value) => Ok(ExecutionOutcome::Success(value)),
+            RunResult::Reverted(value) => Ok(ExecutionOutcome::Reverted(value)),
+            // No budget was set
+            RunResult::Paused => unreachable!()
+        }
+    }
+
+    // Run the VM for a maximum of instructions
+    // The stack and call stack are kept between calls
+    // so the execution can be time-sliced by the host
+    pub fn run_for(&mut self, max_instructions: u64) -> Result<RunResult, VMError> {
+        self.execute(Some(max_instructions))
+    }
+
+    // Resume a paused execution until its end
+    pub fn resume(&mut self) -> Result<ExecutionOutcome, VMError> {
+        self.run()
+    }
+
+    // Invoke a chunk and run it until it returns
+    // A panic or a revert raised in the chunk or in the chunks it calls is returned as the error value
+    // and the stack and call stack are restored, so the VM can still be used after it
+    // The state changes of the chunk are rolled back on any failure, and kept for the caller otherwise
+    // Any other error is returned as a VMError
+    // It can be called while an execution is paused, only the invoked chunk is executed
+    pub fn invoke_catching(&mut self, id: u16) -> Result<Result<Constant, Constant>, VMError> {
+        let call_depth = self.call_stack.len();
+        let stack_len = self.stack.count();
+        let checkpoint = self.context.checkpoint();
+        self.invoke_chunk_id(id)?;
+
+        match self.execute_from(None, call_depth, stack_len) {
+            Ok(RunResult::Finished(value)) => {
+                self.context.commit(checkpoint)?;
+                Ok(Ok(value))
+            },
+            Ok(RunResult::Reverted(value)) => {
+                self.context.rollback(checkpoint)?;
+                Ok(Err(value))
+            },
+            // No budget was set
+            Ok(RunResult::Paused) => unreachable!(),
+            Err(e) => {
+                self.context.rollback(checkpoint)?;
+                let value = e.into_panic()?;
+                self.call_stack.truncate(call_depth);
+                self.stack.truncate_stack(stack_len);
+                Ok(Err(value.into()))
+            }
+        }
+    }
+
+    // Attach the backtrace of the call stack to the error
+    // The failing chunk must not be in the call stack anymore
+    fn trace_error(&self, error: VMError, manager: &ChunkManager, offset: usize) -> VMError {
+        VMError::Traced {
+            error: Box::new(error),
+            trace: StackTrace::capture(manager, offset, &self.call_stack)
+        }
+    }
+
+    // Execute the bytecode with an optional instructions budget
+    fn execute(&mut self, budget: Option<u64>) -> Result<RunResult, VMError> {
+        // A resumed execution keeps the state saved before its first slice
+        let checkpoint = match self.checkpoint.take() {
+            Some(checkpoint) => checkpoint,
+            None => self.context.checkpoint()
+        };
+
+        // A failed execution leaves the state as it was before it
+        let result = self.execute_from(budget, 0, 0);
+        match &result {
+            Ok(RunResult::Paused) => self.checkpoint = Some(checkpoint),
+            Ok(RunResult::Finished(_)) => self.context.commit(checkpoint)?,
+            Ok(RunResult::Reverted(_)) | Err(_) => self.context.rollback(checkpoint)?
+        }
+
+        // Free the values dropped during the execution
+        #[cfg(feature = "arena")]
+        if !matches!(result, Ok(RunResult::Paused)) {
+            xelis_types::arena::release();
+        }
+
+        result
+    }
+
+    // Execute the chunks above the call stack depth
+    // The values below the stack length belong to the caller and are kept
+    fn execute_from(&mut self, mut budget: Option<u64>, call_depth: usize, stack_len: usize) -> Result<RunResult, VMError> {
+        while self.call_stack.len() > call_depth {
+            let Some(mut manager) = self.call_stack.pop() else {
+                break
+            };
+            self.backend.module = manager.module();
+            if let Some(result) = self.execute_compiled(&mut manager, budget) {
+                let result = match result {
+                    Ok(result) => result,
+                    Err(e) => {
+                        // The failing instruction is before the current index
+                        let offset = manager.index().saturating_sub(1);
+                        return Err(self.trace_error(e, &manager, offset))
+                    }
+                };
+
+                if let InstructionResult::Revert(value) = result {
+                    return Ok(self.revert(value, call_depth, stack_len));
+                }
 
+                let offset = manager.index().saturating_sub(1);
+                self.switch_chunk(manager, result, offset)?;
+                continue;
+            }
+
+            manager.set_interpreted();
+            while let Some(opcode) = manager.next_u8() {
+                if let Some(remaining) = budget.as_mut() {
+                    if *remaining == 0 {
+                        // Rewind on the opcode and save the chunk state for the next call
+                        let index = manager.index() - 1;
+                        manager.set_index(index)?;
                         self.call_stack.push(manager);
-                        self.invoke_chunk_id(id)?;
-                        break;
-                    },
-                    InstructionResult::Break => {
+                        return Ok(RunResult::Paused);
+                    }
+                    *remaining -= 1;
+                }
+
+                let offset = manager.index() - 1;
+                self.context.trace_instruction(manager.id(), offset, opcode);
+                let result = match self.backend.table.execute(opcode, &self.backend, &mut self.stack, &mut manager, &mut self.context) {
+                    Ok(result) => result,
+                    Err(e) => return Err(self.trace_error(e, &manager, offset))
+                };
+
+                match result {
+                    InstructionResult::Nothing => {},
+                    InstructionResult::Revert(value) => return Ok(self.revert(value, call_depth, stack_len)),
+                    result => {
+                        self.switch_chunk(manager, result, offset)?;
                         break;
                     }
                 }
@@ -197,10 +661,102 @@ impl<'a> VM<'a> {
         }
 
         let end_value = self.stack.pop_stack()?.into_owned();
-        if self.stack.count() != 0 {
+        if self.stack.count() != stack_len {
             return Err(VMError::StackNotCleaned);
         }
 
-        Ok(end_value.into())
+        Ok(RunResult::Finished(end_value.into()))
+    }
+
+    // Drop the chunks and values of the reverted execution
+    // The chunks below the call stack depth and the values below the stack length are kept
+    fn revert(&mut self, value: ValueCell, call_depth: usize, stack_len: usize) -> RunResult {
+        self.call_stack.truncate(call_depth);
+        self.stack.truncate_stack(stack_len);
+        RunResult::Reverted(value.into())
+    }
+
+    // Verify the values above the stack length don't contain an iterator calling the chunks of a module
+    // They are given to another module, which would invoke its own chunks
+    fn verify_no_callbacks(&self, stack_len: usize) -> Result<(), VMError> {
+        let max_depth = self.context.max_value_depth();
+        for value in self.stack.values().iter().skip(stack_len) {
+            if value.as_ref().as_value().contains_callbacks(max_depth)? {
+                return Err(VMError::IteratorCallbacksCrossModule);
+            }
+        }
+
+        Ok(())
+    }
+
+    // Apply the result of the instruction at the offset of the chunk
+    // Invoking a chunk suspends the current one in the call stack, Break ends it
+    fn switch_chunk(&mut self, mut manager: ChunkManager<'a>, result: InstructionResult, offset: usize) -> Result<(), VMError> {
+        match result {
+            // Continue the chunk at the next iteration
+            InstructionResult::Nothing => self.call_stack.push(manager),
+            InstructionResult::InvokeChunk(id) => {
+                if self.backend.module.is_entry_chunk(id as usize) {
+                    return Err(self.trace_error(VMError::EntryChunkCalled, &manager, offset));
+                }
+
+                // Resolved in the module of the caller
+                self.call_stack.push(manager);
+                if let Err(e) = self.push_chunk(self.backend.module, id) {
+                    let manager = self.call_stack.pop()
+                        .ok_or(VMError::EmptyStack)?;
+                    return Err(self.trace_error(e, &manager, offset));
+                }
+            },
+            InstructionResult::InvokeExternalChunk(module, export, args) => {
+                let (module, id, name, export) = match self.resolve_export(module, export) {
+                    Ok(v) => v,
+                    Err(e) => return Err(self.trace_error(e, &manager, offset))
+                };
+
+                // The chunks of an iterator are resolved in the module running it
+                let stack_len = self.stack.count().saturating_sub(args as usize);
+                if let Err(e) = self.verify_no_callbacks(stack_len) {
+                    return Err(self.trace_error(e, &manager, offset));
+                }
+
+                // The host may deny the call or restrict it
+                if let Err(e) = self.context.enter_call(name, export) {
+                    return Err(self.trace_error(e.into(), &manager, offset));
+                }
+
+                self.call_stack.push(manager);
+                if let Err(e) = self.push_chunk(module, id) {
+                    self.context.exit_call();
+                    let manager = self.call_stack.pop()
+                        .ok_or(VMError::EmptyStack)?;
+                    return Err(self.trace_error(e, &manager, offset));
+                }
+
+                if let Some(callee) = self.call_stack.last_mut() {
+                    callee.set_external(Some(stack_len));
+                }
+            },
+            InstructionResult::Break => {
+                if let Some(stack_len) = manager.external_stack_len() {
+                    self.context.exit_call();
+                    if let Err(e) = self.verify_no_callbacks(stack_len) {
+                        return Err(self.trace_error(e, &manager, offset));
+                    }
+                }
+
+                // The variables of the chunk are dropped one by one,
+                // so a value shared by two of them is freed with the last
+                while let Ok(register) = manager.pop_register() {
+                    if let Err(e) = self.context.decrease_value_memory_usage(&register) {
+                        return Err(self.trace_error(e.into(), &manager, offset));
+                    }
+                }
+            },
+            // Handled by the execution loop before switching
+            InstructionResult::Revert(_) => unreachable!()
+        }
+
+        Ok(())
     }
-}
\ No newline at end of file
+}
@@ -0,0 +1,160 @@
+use std::{collections::HashMap, fmt, time::{Duration, Instant}};
+use xelis_bytecode::OpCode;
+
+use super::Tracer;
+
+// Aggregated statistics of an entry in the report
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ProfileStats {
+    // How many times it was executed
+    pub count: u64,
+    // Gas consumed
+    pub gas: u64,
+    // Wall time spent
+    pub time: Duration,
+}
+
+impl ProfileStats {
+    fn add(&mut self, count: u64, gas: u64, time: Duration) {
+        self.count += count;
+        self.gas = self.gas.saturating_add(gas);
+        self.time += time;
+    }
+}
+
+// Report built by the profiler
+// Gas and time of a chunk include the native functions called from it
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ProfileReport {
+    // Stats of each opcode, count is the instructions executed
+    pub opcodes: HashMap<OpCode, ProfileStats>,
+    // Stats of each chunk, count is the instructions executed
+    pub chunks: HashMap<u16, ProfileStats>,
+    // How many times each chunk was invoked
+    pub chunk_calls: HashMap<u16, u64>,
+    // Stats of each native function, count is the calls
+    pub native_functions: HashMap<u16, ProfileStats>,
+    // Stats of the whole execution
+    pub total: ProfileStats,
+}
+
+// Sort the entries by gas used, then by time
+fn sorted<K: Copy + Ord>(map: &HashMap<K, ProfileStats>) -> Vec<(K, ProfileStats)> {
+    let mut entries: Vec<_> = map.iter().map(|(k, v)| (*k, *v)).collect();
+    entries.sort_by(|(ka, a), (kb, b)| b.gas.cmp(&a.gas)
+        .then(b.time.cmp(&a.time))
+        .then(ka.cmp(kb))
+    );
+    entries
+}
+
+impl fmt::Display for ProfileReport {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "total: {} instructions, {} gas, {:?}", self.total.count, self.total.gas, self.total.time)?;
+
+        writeln!(f, "chunks:")?;
+        for (id, stats) in sorted(&self.chunks) {
+            let calls = self.chunk_calls.get(&id).copied().unwrap_or_default();
+            writeln!(f, "  {}: {} calls, {} instructions, {} gas, {:?}", id, calls, stats.count, stats.gas, stats.time)?;
+        }
+
+        writeln!(f, "native functions:")?;
+        for (id, stats) in sorted(&self.native_functions) {
+            writeln!(f, "  {}: {} calls, {} gas, {:?}", id, stats.count, stats.gas, stats.time)?;
+        }
+
+        writeln!(f, "opcodes:")?;
+        let opcodes: HashMap<u8, ProfileStats> = self.opcodes.iter()
+            .map(|(op, stats)| (op.as_byte(), *stats))
+            .collect();
+        for (byte, stats) in sorted(&opcodes) {
+            // Only known opcodes are registered
+            if let Some(op) = OpCode::from_byte(byte) {
+                writeln!(f, "  {:?}: {} executed, {} gas, {:?}", op, stats.count, stats.gas, stats.time)?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+// Instruction being executed, its cost is known once the next one starts
+struct PendingInstruction {
+    chunk_id: u16,
+    opcode: OpCode,
+    gas_used: u64,
+    start: Instant,
+}
+
+// Tracer aggregating the execution stats
+// Give it to the context using `Context::set_tracer`
+#[derive(Default)]
+pub struct Profiler {
+    report: ProfileReport,
+    pending: Option<PendingInstruction>,
+}
+
+impl Profiler {
+    // Create a new profiler
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // Close the pending instruction
+    fn flush(&mut self, gas_used: u64, now: Instant) {
+        if let Some(pending) = self.pending.take() {
+            let gas = gas_used.saturating_sub(pending.gas_used);
+            let time = now.duration_since(pending.start);
+
+            self.report.opcodes.entry(pending.opcode).or_default().add(1, gas, time);
+            self.report.chunks.entry(pending.chunk_id).or_default().add(1, gas, time);
+            self.report.total.add(1, gas, time);
+        }
+    }
+
+    // Close the last instruction executed
+    // `gas_used` is the total gas used at the end of the execution
+    pub fn finish(&mut self, gas_used: u64) {
+        self.flush(gas_used, Instant::now());
+    }
+
+    // Get the current report
+    pub fn report(&self) -> &ProfileReport {
+        &self.report
+    }
+
+    // Take the report and reset the profiler
+    pub fn take_report(&mut self) -> ProfileReport {
+        self.pending = None;
+        core::mem::take(&mut self.report)
+    }
+}
+
+impl Tracer for Profiler {
+    fn on_instruction(&mut self, chunk_id: u16, _: usize, opcode: u8, gas_used: u64) {
+        let now = Instant::now();
+        self.flush(gas_used, now);
+
+        if let Some(opcode) = OpCode::from_byte(opcode) {
+            self.pending = Some(PendingInstruction {
+                chunk_id,
+                opcode,
+                gas_used,
+                start: now
+            });
+        }
+    }
+
+    fn on_call(&mut self, chunk_id: u16) {
+        *self.report.chunk_calls.entry(chunk_id).or_default() += 1;
+    }
+
+    fn on_native_call(&mut self, function_id: u16, gas: u64) {
+        // The native call is done by the pending instruction
+        let time = self.pending.as_ref()
+            .map(|pending| pending.start.elapsed())
+            .unwrap_or_default();
+
+        self.report.native_functions.entry(function_id).or_default().add(1, gas, time);
+    }
+}
@@ -0,0 +1,145 @@
+use core::fmt;
+use alloc::{borrow::ToOwned, string::{String, ToString}, vec::Vec};
+use xelis_types::{Path, Value};
+
+use super::{Environment, Module, Tracer, VM, VMError};
+
+// Result of a test function
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TestResult {
+    // Name of the test function
+    pub name: String,
+    // Id of its chunk in the module
+    pub chunk_id: u16,
+    // Message of the error that failed the test, None if it passed
+    pub failure: Option<String>,
+    // Gas used by the test
+    pub gas_usage: u64,
+}
+
+impl TestResult {
+    // Did the test pass
+    #[inline]
+    pub fn passed(&self) -> bool {
+        self.failure.is_none()
+    }
+}
+
+// Results of all the tests of a module, in their declaration order
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct TestReport {
+    pub results: Vec<TestResult>,
+}
+
+impl TestReport {
+    // Count of tests passed
+    pub fn passed(&self) -> usize {
+        self.results.iter().filter(|r| r.passed()).count()
+    }
+
+    // Count of tests failed
+    pub fn failed(&self) -> usize {
+        self.results.len() - self.passed()
+    }
+
+    // Did all the tests pass
+    pub fn is_success(&self) -> bool {
+        self.results.iter().all(TestResult::passed)
+    }
+}
+
+impl fmt::Display for TestReport {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for result in &self.results {
+            match &result.failure {
+                None => writeln!(f, "test {} ... ok ({} gas)", result.name, result.gas_usage)?,
+                Some(failure) => writeln!(f, "test {} ... FAILED ({} gas): {}", result.name, result.gas_usage, failure)?
+            }
+        }
+
+        write!(f, "{} passed, {} failed", self.passed(), self.failed())
+    }
+}
+
+// Runner of the functions qualified with `test` in a module
+// Each test is executed in its own VM so they can't affect each other
+pub struct TestRunner<'a> {
+    environment: &'a Environment,
+    // Gas limit of each test
+    gas_limit: Option<u64>,
+}
+
+impl<'a> TestRunner<'a> {
+    // Create a new test runner using the environment of the module
+    pub fn new(environment: &'a Environment) -> Self {
+        Self {
+            environment,
+            gas_limit: None,
+        }
+    }
+
+    // Set the gas limit of each test
+    pub fn with_gas_limit(mut self, gas_limit: u64) -> Self {
+        self.gas_limit = Some(gas_limit);
+        self
+    }
+
+    // Run all the tests of the module
+    pub fn run(&self, module: &Module) -> TestReport {
+        let results = module.tests()
+            .iter()
+            .map(|(name, id)| self.run_test_with(module, name, *id as u16, None))
+            .collect();
+
+        TestReport { results }
+    }
+
+    // Run all the tests of the module with the tracer set on each of them
+    // The same tracer is kept for all the tests, like a `Coverage` merging their hits
+    pub fn run_with_tracer(&self, module: &Module, tracer: &mut dyn Tracer) -> TestReport {
+        let results = module.tests()
+            .iter()
+            .map(|(name, id)| self.run_test_with(module, name, *id as u16, Some(&mut *tracer)))
+            .collect();
+
+        TestReport { results }
+    }
+
+    // Run a single test chunk
+    pub fn run_test(&self, module: &Module, name: &str, chunk_id: u16) -> TestResult {
+        self.run_test_with(module, name, chunk_id, None)
+    }
+
+    fn run_test_with(&self, module: &Module, name: &str, chunk_id: u16, tracer: Option<&mut dyn Tracer>) -> TestResult {
+        let mut vm = VM::new(module, self.environment);
+        if let Some(gas_limit) = self.gas_limit {
+            vm.context_mut().set_gas_limit(gas_limit);
+        }
+
+        if let Some(tracer) = tracer {
+            vm.context_mut().set_tracer(tracer);
+        }
+
+        let failure = Self::execute(&mut vm, chunk_id).err()
+            .map(|e| e.to_string());
+
+        TestResult {
+            name: name.to_owned(),
+            chunk_id,
+            failure,
+            gas_usage: vm.context().current_gas_usage(),
+        }
+    }
+
+    fn execute(vm: &mut VM, chunk_id: u16) -> Result<(), VMError> {
+        // A test returns nothing, a null value is kept under its frame
+        // to be the value returned at the end of the execution
+        vm.invoke_chunk_with_args(chunk_id, core::iter::once(Path::Owned(Value::Null.into())))?;
+        // A revert fails the test like an error
+        vm.run()?
+            .into_result()
+            .map_err(VMError::Reverted)?;
+
+        Ok(())
+    }
+}
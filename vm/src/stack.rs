@@ -1,26 +1,26 @@
+use alloc::vec::Vec;
 use xelis_types::Path;
 
 use super::VMError;
 
-// 256 elements maximum in the stack:
-// Function Call can have up to 255 arguments and 1 on value
-const STACK_SIZE: usize = 256;
-
 pub struct Stack<'a> {
     stack: Vec<Path<'a>>,
+    // Maximum count of values in the stack
+    max_size: usize,
 }
 
 impl<'a> Stack<'a> {
-    pub fn new() -> Self {
+    pub fn new(max_size: usize) -> Self {
         Self {
             stack: Vec::with_capacity(16),
+            max_size,
         }
     }
 
     // Push a value to the stack
     #[inline]
     pub fn push_stack(&mut self, value: Path<'a>) -> Result<(), VMError> {
-        if self.stack.len() >= STACK_SIZE {
+        if self.stack.len() >= self.max_size {
             return Err(VMError::StackOverflow);
         }
 
@@ -68,7 +68,7 @@ impl<'a> Stack<'a> {
     // Push multiple values to the stack
     #[inline]
     pub fn extend_stack<I: IntoIterator<Item = Path<'a>> + ExactSizeIterator>(&mut self, values: I) -> Result<(), VMError> {
-        if self.stack.len() + values.len() >= STACK_SIZE {
+        if self.stack.len() + values.len() >= self.max_size {
             return Err(VMError::StackOverflow);
         }
 
@@ -84,14 +84,19 @@ impl<'a> Stack<'a> {
 
     // Pop last N values
     #[inline]
-    pub fn pop_stack_n(&mut self, n: u8) -> Result<(), VMError> {
+    pub fn pop_stack_n(&mut self, n: u8) -> Result<impl Iterator<Item = Path<'a>> + '_, VMError> {
         let len = self.stack.len();
         if len < n as usize {
             return Err(VMError::StackIndexOutOfBounds);
         }
 
-        self.stack.truncate(len - n as usize);
-        Ok(())
+        Ok(self.stack.drain(len - n as usize..))
+    }
+
+    // Remove the values above the given length
+    #[inline]
+    pub fn truncate_stack(&mut self, len: usize) {
+        self.stack.truncate(len);
     }
 
     // Get the last value from the stack
@@ -106,6 +111,12 @@ impl<'a> Stack<'a> {
         self.stack.last_mut().ok_or(VMError::EmptyStack)
     }
 
+    // Get all the values in the stack
+    #[inline]
+    pub fn values(&self) -> &[Path<'a>] {
+        &self.stack
+    }
+
     // Get the inner stack
     #[inline]
     pub fn get_inner(&mut self) -> &mut Vec<Path<'a>> {
@@ -0,0 +1,546 @@
+use xelis_compiler::{Compiler, OptimizationLevel};
+use xelis_lexer::Lexer;
+use xelis_parser::Parser;
+use super::*;
+
+// Random well-typed programs are generated with the value expected from a reference evaluator,
+// then compiled and executed at each optimization level.
+// Run them with `--features value_pointer_drop` or `--features arena` to cover the other drops of the values.
+
+// Count of programs generated, each one from its own seed
+const PROGRAMS: u64 = 300;
+// Max depth of the generated expressions
+const MAX_EXPR_DEPTH: usize = 4;
+// Max depth of the nested blocks
+const MAX_BLOCK_DEPTH: usize = 2;
+// Count of helper functions declared in each program
+const FUNCTIONS: usize = 2;
+
+const LEVELS: [OptimizationLevel; 3] = [OptimizationLevel::None, OptimizationLevel::Basic, OptimizationLevel::Full];
+
+// Deterministic xorshift generator so a failing seed can be replayed
+struct Rng(u64);
+
+impl Rng {
+    fn new(seed: u64) -> Self {
+        // the state must never be zero
+        Self(seed.wrapping_mul(0x9E37_79B9_7F4A_7C15) | 1)
+    }
+
+    fn next(&mut self) -> u64 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        self.0
+    }
+
+    fn below(&mut self, max: u64) -> u64 {
+        self.next() % max
+    }
+
+    fn chance(&mut self, percent: u64) -> bool {
+        self.below(100) < percent
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+enum BinaryOp {
+    Add,
+    Sub,
+    Mul,
+    Div,
+    Rem,
+    And,
+    Or,
+    Xor,
+    Shl,
+    Shr,
+}
+
+#[derive(Debug, Clone, Copy)]
+enum CompareOp {
+    Eq,
+    Neq,
+    Lt,
+    Lte,
+    Gt,
+    Gte,
+}
+
+// Expression of type u64
+// Every operation is total so the expected value never depends on an error
+#[derive(Debug)]
+enum Expr {
+    Literal(u64),
+    Variable(usize),
+    Binary(BinaryOp, Box<Expr>, Box<Expr>),
+    // truncated to a u8 and back to a u64
+    Cast(Box<Expr>),
+    Ternary(Box<Cond>, Box<Expr>, Box<Expr>),
+    Call(usize, Box<Expr>, Box<Expr>),
+    // values[expr % values.len()]
+    Index(Box<Expr>),
+    Length,
+}
+
+// Expression of type bool
+#[derive(Debug)]
+enum Cond {
+    Literal(bool),
+    Compare(CompareOp, Expr, Expr),
+    Not(Box<Cond>),
+    And(Box<Cond>, Box<Cond>),
+    Or(Box<Cond>, Box<Cond>),
+}
+
+#[derive(Debug)]
+enum Statement {
+    Let(usize, Expr),
+    Assign(usize, Expr),
+    Push(Expr),
+    If(Cond, Vec<Statement>, Vec<Statement>),
+    For(usize, u64, Vec<Statement>),
+}
+
+// Program with a main entry returning a u64
+// Variables are identified by their index, each one has its own name so there is no shadowing
+#[derive(Debug)]
+struct Program {
+    // expression of each helper function, using the parameters `a` and `b`
+    functions: Vec<Expr>,
+    // initial content of the `values` array
+    values: Vec<u64>,
+    statements: Vec<Statement>,
+    // variables declared at the top level, summed in the returned value
+    returned: Vec<usize>,
+    variables: usize,
+}
+
+// Helper functions see only their two parameters
+const PARAMETERS: [&str; 2] = ["a", "b"];
+
+struct Generator {
+    rng: Rng,
+    variables: usize,
+    // variables readable in the current scope
+    scope: Vec<usize>,
+    // variables of the scope that can be assigned, the loop counters are excluded
+    assignable: Vec<usize>,
+    // generating the expression of a helper function
+    in_function: bool,
+}
+
+impl Generator {
+    fn new(seed: u64) -> Self {
+        Self {
+            rng: Rng::new(seed),
+            variables: 0,
+            scope: Vec::new(),
+            assignable: Vec::new(),
+            in_function: false,
+        }
+    }
+
+    fn program(mut self) -> Program {
+        self.in_function = true;
+        self.scope = vec![0, 1];
+        let functions = (0..FUNCTIONS).map(|_| self.expr(MAX_EXPR_DEPTH)).collect();
+        self.in_function = false;
+        self.scope.clear();
+
+        let values = (0..1 + self.rng.below(3)).map(|_| self.literal()).collect();
+        let count = 2 + self.rng.below(6);
+        let statements = (0..count).map(|_| self.statement(0)).collect();
+
+        Program {
+            functions,
+            values,
+            statements,
+            returned: self.scope.clone(),
+            variables: self.variables,
+        }
+    }
+
+    fn literal(&mut self) -> u64 {
+        match self.rng.below(4) {
+            0 => self.rng.below(4),
+            1 => u64::MAX - self.rng.below(4),
+            2 => self.rng.next(),
+            _ => self.rng.below(1000)
+        }
+    }
+
+    fn expr(&mut self, depth: usize) -> Expr {
+        if depth == 0 || self.rng.chance(25) {
+            return match self.scope.len() {
+                0 => Expr::Literal(self.literal()),
+                len if self.rng.chance(60) => Expr::Variable(self.scope[self.rng.below(len as u64) as usize]),
+                _ => Expr::Literal(self.literal())
+            }
+        }
+
+        let depth = depth - 1;
+        match self.rng.below(14) {
+            0 if !self.in_function => Expr::Call(self.rng.below(FUNCTIONS as u64) as usize, Box::new(self.expr(depth)), Box::new(self.expr(depth))),
+            1 if !self.in_function => Expr::Index(Box::new(self.expr(depth))),
+            2 if !self.in_function => Expr::Length,
+            3 => Expr::Cast(Box::new(self.expr(depth))),
+            4 => Expr::Ternary(Box::new(self.cond(depth)), Box::new(self.expr(depth)), Box::new(self.expr(depth))),
+            n => {
+                let op = match n % 10 {
+                    0 => BinaryOp::Add,
+                    1 => BinaryOp::Sub,
+                    2 => BinaryOp::Mul,
+                    3 => BinaryOp::Div,
+                    4 => BinaryOp::Rem,
+                    5 => BinaryOp::And,
+                    6 => BinaryOp::Or,
+                    7 => BinaryOp::Xor,
+                    8 => BinaryOp::Shl,
+                    _ => BinaryOp::Shr
+                };
+
+                Expr::Binary(op, Box::new(self.expr(depth)), Box::new(self.expr(depth)))
+            }
+        }
+    }
+
+    fn cond(&mut self, depth: usize) -> Cond {
+        if depth == 0 || self.rng.chance(10) {
+            return Cond::Literal(self.rng.chance(50))
+        }
+
+        let depth = depth - 1;
+        match self.rng.below(6) {
+            0 => Cond::Not(Box::new(self.cond(depth))),
+            1 => Cond::And(Box::new(self.cond(depth)), Box::new(self.cond(depth))),
+            2 => Cond::Or(Box::new(self.cond(depth)), Box::new(self.cond(depth))),
+            _ => {
+                let op = match self.rng.below(6) {
+                    0 => CompareOp::Eq,
+                    1 => CompareOp::Neq,
+                    2 => CompareOp::Lt,
+                    3 => CompareOp::Lte,
+                    4 => CompareOp::Gt,
+                    _ => CompareOp::Gte
+                };
+
+                Cond::Compare(op, self.expr(depth), self.expr(depth))
+            }
+        }
+    }
+
+    fn new_variable(&mut self) -> usize {
+        self.variables += 1;
+        self.variables - 1
+    }
+
+    // Statements of a block, the variables declared in it are dropped at its end
+    fn block(&mut self, depth: usize) -> Vec<Statement> {
+        let (scope, assignable) = (self.scope.len(), self.assignable.len());
+        let count = 1 + self.rng.below(3);
+        let statements = (0..count).map(|_| self.statement(depth)).collect();
+        self.scope.truncate(scope);
+        self.assignable.truncate(assignable);
+
+        statements
+    }
+
+    fn statement(&mut self, depth: usize) -> Statement {
+        let nested = depth < MAX_BLOCK_DEPTH;
+        match self.rng.below(10) {
+            0 | 1 if !self.assignable.is_empty() => {
+                let id = self.assignable[self.rng.below(self.assignable.len() as u64) as usize];
+                Statement::Assign(id, self.expr(MAX_EXPR_DEPTH))
+            },
+            2 => Statement::Push(self.expr(MAX_EXPR_DEPTH)),
+            3 if nested => Statement::If(self.cond(MAX_EXPR_DEPTH), self.block(depth + 1), self.block(depth + 1)),
+            4 if nested => {
+                let id = self.new_variable();
+                let count = self.rng.below(5);
+                self.scope.push(id);
+                let statements = self.block(depth + 1);
+                self.scope.pop();
+
+                Statement::For(id, count, statements)
+            },
+            _ => {
+                let value = self.expr(MAX_EXPR_DEPTH);
+                let id = self.new_variable();
+                self.scope.push(id);
+                self.assignable.push(id);
+
+                Statement::Let(id, value)
+            }
+        }
+    }
+}
+
+// Reference evaluator giving the value expected from the VM
+struct Evaluator<'a> {
+    functions: &'a [Expr],
+    variables: Vec<u64>,
+    values: Vec<u64>,
+}
+
+impl<'a> Evaluator<'a> {
+    fn expr(&self, expr: &Expr, variables: &[u64]) -> u64 {
+        match expr {
+            Expr::Literal(v) => *v,
+            Expr::Variable(id) => variables[*id],
+            Expr::Binary(op, left, right) => {
+                let (left, right) = (self.expr(left, variables), self.expr(right, variables));
+                match op {
+                    BinaryOp::Add => left.wrapping_add(right),
+                    BinaryOp::Sub => left.wrapping_sub(right),
+                    BinaryOp::Mul => left.wrapping_mul(right),
+                    BinaryOp::Div => left / (right | 1),
+                    BinaryOp::Rem => left % (right | 1),
+                    BinaryOp::And => left & right,
+                    BinaryOp::Or => left | right,
+                    BinaryOp::Xor => left ^ right,
+                    BinaryOp::Shl => left << (right % 64),
+                    BinaryOp::Shr => left >> (right % 64),
+                }
+            },
+            Expr::Cast(v) => self.expr(v, variables) as u8 as u64,
+            Expr::Ternary(cond, left, right) => if self.cond(cond, variables) {
+                self.expr(left, variables)
+            } else {
+                self.expr(right, variables)
+            },
+            Expr::Call(id, a, b) => {
+                let parameters = [self.expr(a, variables), self.expr(b, variables)];
+                self.expr(&self.functions[*id], &parameters)
+            },
+            Expr::Index(index) => self.values[(self.expr(index, variables) % self.values.len() as u64) as usize],
+            Expr::Length => self.values.len() as u64,
+        }
+    }
+
+    fn cond(&self, cond: &Cond, variables: &[u64]) -> bool {
+        match cond {
+            Cond::Literal(v) => *v,
+            Cond::Compare(op, left, right) => {
+                let (left, right) = (self.expr(left, variables), self.expr(right, variables));
+                match op {
+                    CompareOp::Eq => left == right,
+                    CompareOp::Neq => left != right,
+                    CompareOp::Lt => left < right,
+                    CompareOp::Lte => left <= right,
+                    CompareOp::Gt => left > right,
+                    CompareOp::Gte => left >= right,
+                }
+            },
+            Cond::Not(v) => !self.cond(v, variables),
+            Cond::And(left, right) => self.cond(left, variables) && self.cond(right, variables),
+            Cond::Or(left, right) => self.cond(left, variables) || self.cond(right, variables),
+        }
+    }
+
+    fn statements(&mut self, statements: &[Statement]) {
+        for statement in statements {
+            match statement {
+                Statement::Let(id, expr) | Statement::Assign(id, expr) => {
+                    self.variables[*id] = self.expr(expr, &self.variables);
+                },
+                Statement::Push(expr) => {
+                    let value = self.expr(expr, &self.variables);
+                    self.values.push(value);
+                },
+                Statement::If(cond, then, otherwise) => if self.cond(cond, &self.variables) {
+                    self.statements(then);
+                } else {
+                    self.statements(otherwise);
+                },
+                Statement::For(id, count, body) => {
+                    self.variables[*id] = 0;
+                    while self.variables[*id] < *count {
+                        self.statements(body);
+                        self.variables[*id] += 1;
+                    }
+                }
+            }
+        }
+    }
+
+    fn run(program: &'a Program) -> u64 {
+        let mut evaluator = Evaluator {
+            functions: &program.functions,
+            variables: vec![0; program.variables],
+            values: program.values.clone(),
+        };
+        evaluator.statements(&program.statements);
+
+        program.returned.iter()
+            .fold(evaluator.values.len() as u64, |acc, id| acc.wrapping_add(evaluator.variables[*id]))
+    }
+}
+
+// Render the program as source code
+struct Writer<'a> {
+    program: &'a Program,
+    source: String,
+}
+
+impl<'a> Writer<'a> {
+    fn variable(id: usize) -> String {
+        format!("v{}", id)
+    }
+
+    fn expr(&self, expr: &Expr, names: &dyn Fn(usize) -> String) -> String {
+        match expr {
+            Expr::Literal(v) => v.to_string(),
+            Expr::Variable(id) => names(*id),
+            Expr::Binary(op, left, right) => {
+                let (left, right) = (self.expr(left, names), self.expr(right, names));
+                match op {
+                    BinaryOp::Add => format!("({} +% {})", left, right),
+                    BinaryOp::Sub => format!("({} -% {})", left, right),
+                    BinaryOp::Mul => format!("({} *% {})", left, right),
+                    BinaryOp::Div => format!("({} / ({} | 1))", left, right),
+                    BinaryOp::Rem => format!("({} % ({} | 1))", left, right),
+                    BinaryOp::And => format!("({} & {})", left, right),
+                    BinaryOp::Or => format!("({} | {})", left, right),
+                    BinaryOp::Xor => format!("({} ^ {})", left, right),
+                    BinaryOp::Shl => format!("({} << ({} % 64))", left, right),
+                    BinaryOp::Shr => format!("({} >> ({} % 64))", left, right),
+                }
+            },
+            Expr::Cast(v) => format!("(({} as u8) as u64)", self.expr(v, names)),
+            Expr::Ternary(cond, left, right) => format!("({} ? {} : {})", self.cond(cond, names), self.expr(left, names), self.expr(right, names)),
+            Expr::Call(id, a, b) => format!("f{}({}, {})", id, self.expr(a, names), self.expr(b, names)),
+            Expr::Index(index) => format!("at(values, {})", self.expr(index, names)),
+            Expr::Length => "(values.len() as u64)".to_owned(),
+        }
+    }
+
+    fn cond(&self, cond: &Cond, names: &dyn Fn(usize) -> String) -> String {
+        match cond {
+            Cond::Literal(v) => v.to_string(),
+            Cond::Compare(op, left, right) => {
+                let op = match op {
+                    CompareOp::Eq => "==",
+                    CompareOp::Neq => "!=",
+                    CompareOp::Lt => "<",
+                    CompareOp::Lte => "<=",
+                    CompareOp::Gt => ">",
+                    CompareOp::Gte => ">=",
+                };
+                format!("({} {} {})", self.expr(left, names), op, self.expr(right, names))
+            },
+            // `!` applies to the whole expression following it
+            Cond::Not(v) => format!("(!{})", self.cond(v, names)),
+            Cond::And(left, right) => format!("({} && {})", self.cond(left, names), self.cond(right, names)),
+            Cond::Or(left, right) => format!("({} || {})", self.cond(left, names), self.cond(right, names)),
+        }
+    }
+
+    fn line(&mut self, indent: usize, line: &str) {
+        self.source.push_str(&"    ".repeat(indent));
+        self.source.push_str(line);
+        self.source.push('\n');
+    }
+
+    fn statements(&mut self, statements: &[Statement], indent: usize) {
+        let names = Self::variable;
+        for statement in statements {
+            match statement {
+                Statement::Let(id, expr) => {
+                    let line = format!("let {}: u64 = {};", names(*id), self.expr(expr, &names));
+                    self.line(indent, &line);
+                },
+                Statement::Assign(id, expr) => {
+                    let line = format!("{} = {};", names(*id), self.expr(expr, &names));
+                    self.line(indent, &line);
+                },
+                Statement::Push(expr) => {
+                    let line = format!("values.push({});", self.expr(expr, &names));
+                    self.line(indent, &line);
+                },
+                Statement::If(cond, then, otherwise) => {
+                    let line = format!("if {} {{", self.cond(cond, &names));
+                    self.line(indent, &line);
+                    self.statements(then, indent + 1);
+                    self.line(indent, "} else {");
+                    self.statements(otherwise, indent + 1);
+                    self.line(indent, "}");
+                },
+                Statement::For(id, count, body) => {
+                    let name = names(*id);
+                    let line = format!("for {name}: u64 = 0; {name} < {count}; {name} += 1 {{");
+                    self.line(indent, &line);
+                    self.statements(body, indent + 1);
+                    self.line(indent, "}");
+                }
+            }
+        }
+    }
+
+    fn write(program: &'a Program) -> String {
+        let mut writer = Self {
+            program,
+            source: String::new(),
+        };
+
+        let parameters = |id: usize| PARAMETERS[id].to_owned();
+        for (id, expr) in program.functions.iter().enumerate() {
+            let line = format!("fn f{}(a: u64, b: u64) -> u64 {{ return {} }}", id, writer.expr(expr, &parameters));
+            writer.line(0, &line);
+        }
+
+        // A literal in the index would be typed from the index, even with a cast
+        // so the index is given as a u64 parameter
+        writer.line(0, "fn at(values: u64[], index: u64) -> u64 { return values[(index % (values.len() as u64)) as u32] }");
+        writer.line(0, "entry main() {");
+        let values: Vec<String> = writer.program.values.iter().map(ToString::to_string).collect();
+        writer.line(1, &format!("let values: u64[] = [{}];", values.join(", ")));
+        writer.statements(&program.statements, 1);
+
+        let returned = program.returned.iter()
+            .fold("(values.len() as u64)".to_owned(), |acc, id| format!("({} +% {})", acc, Self::variable(*id)));
+        writer.line(1, &format!("return {}", returned));
+        writer.line(0, "}");
+
+        writer.source
+    }
+}
+
+fn run_with_level(code: &str, level: OptimizationLevel) -> Result<Value, String> {
+    let tokens: Vec<_> = Lexer::new(code).collect::<Result<_, _>>().map_err(|e| e.to_string())?;
+    let environment = EnvironmentBuilder::default();
+    let (program, _) = Parser::with(tokens.into_iter(), &environment).parse()
+        .map_err(|e| e.with_source(code).to_string())?;
+
+    let environment = environment.build();
+    let module = Compiler::new(&program, &environment)
+        .with_optimizations(level)
+        .compile()
+        .map_err(|e| e.to_string())?;
+    ModuleValidator::new(&module, &environment).verify().map_err(|e| e.to_string())?;
+
+    // the helper functions and `at` are compiled before the entry
+    let mut vm = VM::new(&module, &environment);
+    vm.invoke_entry_chunk(FUNCTIONS as u16 + 1).map_err(|e| e.to_string())?;
+    vm.run()
+        .map_err(|e| e.to_string())?
+        .into_result()
+        .map_err(|value| format!("reverted with {}", value))?
+        .into_value()
+        .map_err(|e| e.to_string())
+}
+
+// Every generated program must return the value of the reference evaluator at each optimization level
+#[test]
+fn test_differential_optimization_levels() {
+    for seed in 0..PROGRAMS {
+        let program = Generator::new(seed).program();
+        let expected = Value::U64(Evaluator::run(&program));
+        let code = Writer::write(&program);
+
+        for level in LEVELS {
+            let result = run_with_level(&code, level);
+            assert_eq!(result.as_ref(), Ok(&expected), "seed {} at {:?}:\n{}", seed, level, code);
+        }
+    }
+}
@@ -1,9 +1,10 @@
-use xelis_compiler::Compiler;
-use xelis_environment::{Environment, EnvironmentError};
-use xelis_builder::EnvironmentBuilder;
+use xelis_bytecode::{AbiEntry, AbiEvent, AbiParameter, AbiStruct, AbiType, Chunk, Disassembler, OpCode};
+use xelis_compiler::{eliminate_dead_code, Compiler, CompilerError, OptimizationLevel};
+use xelis_environment::{CallAuthorizer, CallPermission, CallRequest, ChainInfo, Environment, EnvironmentError, StorageIterator, StorageProvider, VALUE_MEMORY_USAGE};
+use xelis_builder::{BuilderError, EnvironmentBuilder};
 use xelis_lexer::Lexer;
-use xelis_parser::Parser;
-use xelis_types::Value;
+use xelis_parser::{Parser, ParserErrorKind, WarningCode};
+use xelis_types::{Type, Value, ValueCell};
 use super::*;
 
 #[track_caller]
@@ -49,7 +50,583 @@ fn test_max_gas() {
     vm.context_mut().set_gas_limit(1000);
     vm.invoke_entry_chunk(0).unwrap();
 
-    assert!(matches!(vm.run(), Err(VMError::EnvironmentError(EnvironmentError::NotEnoughGas { .. }))));
+    assert!(matches!(vm.run().as_ref().map_err(VMError::inner), Err(VMError::NotEnoughGas { .. })));
+}
+
+#[test]
+fn test_vm_config() {
+    let code = r#"
+        fn sum(n: u64) -> u64 {
+            if n == 0 {
+                return 0
+            }
+            return n + sum(n - 1)
+        }
+
+        entry main() {
+            let values: u64[] = [1, 2, 3];
+            values.push(4);
+            return sum(5) + values.len() as u64
+        }
+    "#;
+
+    let (module, environment) = prepare_module(code);
+    let run = |config: VMConfig| {
+        let mut vm = VM::with_config(&module, &environment, config);
+        vm.invoke_entry_chunk(1).unwrap();
+        vm.run()
+    };
+
+    assert_eq!(run(VMConfig::default()).unwrap(), ExecutionOutcome::Success(Value::U64(19).into()));
+
+    let err = run(VMConfig { max_call_depth: 6, ..Default::default() }).unwrap_err();
+    assert!(matches!(err.inner(), VMError::CallStackOverflow), "{}", err);
+    assert!(run(VMConfig { max_call_depth: 7, ..Default::default() }).is_ok());
+
+    let err = run(VMConfig { max_stack_size: 4, ..Default::default() }).unwrap_err();
+    assert!(matches!(err.inner(), VMError::StackOverflow), "{}", err);
+
+    let err = run(VMConfig { max_gas: 10, ..Default::default() }).unwrap_err();
+    assert!(matches!(err.inner(), VMError::NotEnoughGas { limit: 10, .. }), "{}", err);
+
+    // The constant array and the pushed value
+    assert!(run(VMConfig { max_memory: 4 * VALUE_MEMORY_USAGE, ..Default::default() }).is_ok());
+    let err = run(VMConfig { max_memory: 4 * VALUE_MEMORY_USAGE - 1, ..Default::default() }).unwrap_err();
+    assert!(matches!(err.inner(), VMError::OutOfMemory), "{}", err);
+}
+
+#[test]
+fn test_max_memory() {
+    let code = r#"
+        entry main() {
+            let values: u64[] = [];
+            let s: string = "";
+            for i: u64 = 0; i < 10; i += 1 {
+                values.push(i);
+                s += "ab";
+            }
+
+            return values.len() as u64
+        }
+    "#;
+
+    let (module, environment) = prepare_module(code);
+    let run = |max_memory: usize| {
+        let mut vm = VM::new(&module, &environment);
+        vm.context_mut().set_max_memory(max_memory);
+        vm.invoke_entry_chunk(0).unwrap();
+        let result = vm.run().map_err(|e| e.inner().to_string());
+        (result, vm.context().current_memory_usage())
+    };
+
+    // The array cells and the string of 20 bytes, with the previous string
+    // and the constant still alive during the last concatenation
+    let peak = 10 * VALUE_MEMORY_USAGE + 20 + 18 + 2;
+    // The variables are released when the entry returns
+    assert_eq!(run(peak), (Ok(ExecutionOutcome::Success(Value::U64(10).into())), 0));
+
+    let (result, _) = run(peak - 1);
+    assert_eq!(result, Err(VMError::OutOfMemory.to_string()));
+}
+
+#[test]
+fn test_max_memory_released() {
+    // The memory of the arrays dropped at each iteration is reused by the next ones
+    let code = r#"
+        entry main() {
+            let values: u64[] = [];
+            for i: u64 = 0; i < 10; i += 1 {
+                let a: u64[] = [1, 2, 3, 4];
+                a.pop();
+                a.pop();
+                values.push(i);
+            }
+
+            return values.len() as u64
+        }
+    "#;
+
+    let (module, environment) = prepare_module(code);
+    let run = |max_memory: usize| {
+        let mut vm = VM::new(&module, &environment);
+        vm.context_mut().set_max_memory(max_memory);
+        vm.invoke_entry_chunk(0).unwrap();
+        let result = vm.run().map_err(|e| e.inner().to_string());
+        (result, vm.context().current_memory_usage())
+    };
+
+    // At the last iteration: the 9 values pushed, the array of the previous
+    // iteration with its 2 values left and the new constant array
+    let peak = 9 * VALUE_MEMORY_USAGE + 2 * VALUE_MEMORY_USAGE + 4 * VALUE_MEMORY_USAGE;
+    assert_eq!(run(peak), (Ok(ExecutionOutcome::Success(Value::U64(10).into())), 0));
+
+    let (result, _) = run(peak - 1);
+    assert_eq!(result, Err(VMError::OutOfMemory.to_string()));
+}
+
+#[test]
+fn test_max_memory_map_released() {
+    let code = r#"
+        entry main() {
+            let m: map<u64, string> = {};
+            for i: u64 = 0; i < 10; i += 1 {
+                m.insert(i, "abcd");
+                m.remove(i);
+            }
+
+            m.insert(0, "ab");
+            m.clear();
+            return m.len() as u64
+        }
+    "#;
+
+    let (module, environment) = prepare_module(code);
+    let run = |max_memory: usize| {
+        let mut vm = VM::new(&module, &environment);
+        vm.context_mut().set_max_memory(max_memory);
+        vm.invoke_entry_chunk(0).unwrap();
+        let result = vm.run().map_err(|e| e.inner().to_string());
+        (result, vm.context().current_memory_usage())
+    };
+
+    // A single entry is alive at a time
+    let peak = 2 * VALUE_MEMORY_USAGE + 4;
+    assert_eq!(run(peak), (Ok(ExecutionOutcome::Success(Value::U64(0).into())), 0));
+
+    let (result, _) = run(peak - 1);
+    assert_eq!(result, Err(VMError::OutOfMemory.to_string()));
+}
+
+#[test]
+fn test_max_memory_push_copies() {
+    // A pushed array is charged for its content, which it shares
+    // with the variable dropped at the end of each iteration
+    let code = r#"
+        entry main() {
+            let all: u64[][] = [];
+            for i: u64 = 0; i < 200; i += 1 {
+                let copy: u64[] = [];
+                for j: u64 = 0; j < 100; j += 1 {
+                    copy.push(j);
+                }
+                all.push(copy);
+            }
+
+            return all.len() as u64
+        }
+    "#;
+
+    let (module, environment) = prepare_module(code);
+    let run = |max_memory: usize| {
+        let mut vm = VM::new(&module, &environment);
+        vm.context_mut().set_max_memory(max_memory);
+        vm.invoke_entry_chunk(0).unwrap();
+        vm.run().map_err(|e| e.inner().to_string())
+    };
+
+    assert_eq!(run(usize::MAX), Ok(ExecutionOutcome::Success(Value::U64(200).into())));
+    assert_eq!(run(100_000), Err(VMError::OutOfMemory.to_string()));
+}
+
+#[test]
+fn test_native_dynamic_gas() {
+    let run = |len: usize| {
+        let code = format!(r#"
+            entry main() {{
+                let s: string = "{}";
+                let r: string = s.replace("a", "bb");
+                return r.len() as u64
+            }}
+        "#, "a".repeat(len));
+
+        let (module, environment) = prepare_module(&code);
+        let mut vm = VM::new(&module, &environment);
+        vm.invoke_entry_chunk(0).unwrap();
+        assert_eq!(vm.run().unwrap(), ExecutionOutcome::Success(Value::U64(len as u64 * 2).into()));
+        vm.context().current_gas_usage()
+    };
+
+    // The replace cost is based on the string size
+    let small = run(1);
+    let large = run(3200);
+    assert!(large >= small + 300);
+}
+
+#[test]
+fn test_tracer() {
+    #[derive(Default)]
+    struct Recorder {
+        instructions: usize,
+        calls: Vec<u16>,
+        native_calls: Vec<(u16, u64)>,
+        last_gas: u64,
+    }
+
+    impl Tracer for Recorder {
+        fn on_instruction(&mut self, _: u16, _: usize, opcode: u8, gas_used: u64) {
+            assert!(OpCode::from_byte(opcode).is_some());
+            assert!(gas_used >= self.last_gas);
+            self.last_gas = gas_used;
+            self.instructions += 1;
+        }
+
+        fn on_call(&mut self, chunk_id: u16) {
+            self.calls.push(chunk_id);
+        }
+
+        fn on_native_call(&mut self, function_id: u16, gas: u64) {
+            self.native_calls.push((function_id, gas));
+        }
+    }
+
+    let code = r#"
+        fn add(a: u64, b: u64) -> u64 {
+            return a + b
+        }
+
+        entry main() {
+            let s: string = "hello";
+            return add(add(1, 2), s.len() as u64)
+        }
+    "#;
+
+    let (module, environment) = prepare_module(code);
+    let mut recorder = Recorder::default();
+    let mut vm = VM::new(&module, &environment);
+    vm.context_mut().set_tracer(&mut recorder);
+    vm.invoke_entry_chunk(1).unwrap();
+    assert_eq!(vm.run().unwrap(), ExecutionOutcome::Success(Value::U64(8).into()));
+    let gas = vm.context().current_gas_usage();
+    drop(vm);
+
+    assert_eq!(recorder.calls, vec![1, 0, 0]);
+    assert_eq!(recorder.native_calls.len(), 1);
+    assert!(recorder.native_calls[0].1 > 0);
+    assert!(recorder.instructions > 0);
+    assert!(gas >= recorder.last_gas);
+}
+
+#[test]
+fn test_superinstructions() {
+    #[derive(Default)]
+    struct Counter(usize);
+
+    impl Tracer for Counter {
+        fn on_instruction(&mut self, _: u16, _: usize, _: u8, _: u64) {
+            self.0 += 1;
+        }
+    }
+
+    let code = r#"
+        entry main() {
+            let sum: u32 = 0;
+            let i: u32 = 0;
+            while i < 10 {
+                let j: u32 = 0;
+                while j < 10 {
+                    sum += i + j;
+                    j += 1;
+                }
+                i += 1;
+            }
+
+            for k: u32 = 10; k > 0; k -= 1 {
+                sum = sum + k;
+            }
+            return sum as u64
+        }
+    "#;
+
+    let run = |level: OptimizationLevel| {
+        let tokens = Lexer::new(code).get().unwrap();
+        let env = EnvironmentBuilder::default();
+        let (program, _) = Parser::new(tokens, &env).parse().unwrap();
+        let environment = env.build();
+        let module = Compiler::new(&program, &environment)
+            .with_optimizations(level)
+            .compile()
+            .unwrap();
+
+        let mut counter = Counter::default();
+        let mut vm = VM::new(&module, &environment);
+        vm.context_mut().set_tracer(&mut counter);
+        vm.invoke_entry_chunk(0).unwrap();
+        let value = vm.run().unwrap();
+        let gas = vm.context().current_gas_usage();
+        drop(vm);
+
+        (value, gas, counter.0)
+    };
+
+    let (value, gas, dispatched) = run(OptimizationLevel::None);
+    let (fused_value, fused_gas, fused_dispatched) = run(OptimizationLevel::Full);
+
+    assert_eq!(value, ExecutionOutcome::Success(Value::U64(955).into()));
+    assert_eq!(fused_value, value);
+    // Superinstructions cost the same gas as the sequences they replace
+    assert_eq!(fused_gas, gas);
+    // At least 30% less instructions dispatched
+    assert!(fused_dispatched * 10 <= dispatched * 7, "{} -> {}", dispatched, fused_dispatched);
+}
+
+#[test]
+#[cfg(feature = "std")]
+fn test_profiler() {
+    let code = r#"
+        fn add(a: u64, b: u64) -> u64 {
+            return a + b
+        }
+
+        entry main() {
+            let s: string = "hello";
+            let total: u64 = 0;
+            for i: u64 = 0; i < 10; i += 1 {
+                total = add(total, s.len() as u64);
+            }
+            return total
+        }
+    "#;
+
+    let (module, environment) = prepare_module(code);
+    let mut profiler = Profiler::new();
+    let mut vm = VM::new(&module, &environment);
+    vm.context_mut().set_tracer(&mut profiler);
+    vm.invoke_entry_chunk(1).unwrap();
+    assert_eq!(vm.run().unwrap(), ExecutionOutcome::Success(Value::U64(50).into()));
+    let gas = vm.context().current_gas_usage();
+    drop(vm);
+
+    profiler.finish(gas);
+    let report = profiler.take_report();
+
+    // Every gas unit is attributed to an instruction
+    assert_eq!(report.total.gas, gas);
+    assert_eq!(report.chunks.values().map(|s| s.gas).sum::<u64>(), gas);
+    assert_eq!(report.opcodes.values().map(|s| s.count).sum::<u64>(), report.total.count);
+
+    assert_eq!(report.chunk_calls.get(&0), Some(&10));
+    assert_eq!(report.chunk_calls.get(&1), Some(&1));
+    assert_eq!(report.opcodes.get(&OpCode::InvokeChunk).map(|s| s.count), Some(10));
+    assert_eq!(report.native_functions.len(), 1);
+    assert_eq!(report.native_functions.values().next().unwrap().count, 10);
+
+    let output = report.to_string();
+    assert!(output.contains("native functions:"));
+    assert!(output.contains("InvokeChunk: 10 executed"));
+}
+
+#[test]
+fn test_run_for() {
+    let code = r#"
+        fn add(a: u64, b: u64) -> u64 {
+            return a + b
+        }
+
+        entry main() {
+            let total: u64 = 0;
+            for i: u64 = 0; i < 100; i += 1 {
+                total = add(total, i);
+            }
+            return total
+        }
+    "#;
+
+    let (module, environment) = prepare_module(code);
+    let mut vm = VM::new(&module, &environment);
+    vm.invoke_entry_chunk(1).unwrap();
+
+    let mut slices = 0;
+    let value = loop {
+        match vm.run_for(10).unwrap() {
+            RunResult::Finished(value) => break value,
+            RunResult::Reverted(value) => panic!("reverted with {}", value),
+            RunResult::Paused => {
+                assert!(vm.has_pending_execution());
+                slices += 1;
+            }
+        }
+    };
+
+    assert!(slices > 10);
+    assert!(!vm.has_pending_execution());
+    assert_eq!(value, Value::U64(4950).into());
+
+    // Resume until the end after a pause
+    vm.invoke_entry_chunk(1).unwrap();
+    assert!(matches!(vm.run_for(5).unwrap(), RunResult::Paused));
+    assert_eq!(vm.resume().unwrap(), ExecutionOutcome::Success(Value::U64(4950).into()));
+}
+
+#[test]
+fn test_invoke_catching() {
+    let code = r#"
+        fn check(value: u64) -> u64 {
+            if value == 0 {
+                panic("zero");
+            }
+            return value
+        }
+
+        entry ok() {
+            return check(10) + 1
+        }
+
+        entry fail() {
+            let a: u64 = 5;
+            return a + check(0)
+        }
+
+        entry divide() {
+            let b: u64 = 0;
+            return 1 / b
+        }
+    "#;
+
+    let (module, environment) = prepare_module(code);
+    let mut vm = VM::new(&module, &environment);
+
+    // The panic is returned as a value and the VM stays usable
+    let value = vm.invoke_catching(2).unwrap();
+    assert_eq!(value, Err(Value::String("zero".to_owned()).into()));
+    assert!(!vm.has_pending_execution());
+    assert_eq!(vm.get_stack().count(), 0);
+    assert_eq!(vm.invoke_catching(1).unwrap(), Ok(Value::U64(11).into()));
+
+    // Only the invoked chunk is executed while an execution is paused
+    vm.invoke_entry_chunk(1).unwrap();
+    assert!(matches!(vm.run_for(2).unwrap(), RunResult::Paused));
+    let stack_len = vm.get_stack().count();
+    assert!(vm.invoke_catching(2).unwrap().is_err());
+    assert_eq!(vm.get_stack().count(), stack_len);
+    assert_eq!(vm.resume().unwrap(), ExecutionOutcome::Success(Value::U64(11).into()));
+
+    // Other errors are not caught
+    let err = vm.invoke_catching(3).unwrap_err();
+    assert!(matches!(err.inner(), VMError::DivisionByZero));
+}
+
+#[test]
+fn test_debugger() {
+    let code = r#"
+        fn add(a: u64, b: u64) -> u64 {
+            return a + b
+        }
+
+        entry main() {
+            let a: u64 = 10;
+            return add(a, 5)
+        }
+    "#;
+
+    let (module, environment) = prepare_module(code);
+    let mut vm = VM::new(&module, &environment);
+    vm.invoke_entry_chunk(1).unwrap();
+
+    let mut debugger = Debugger::new(vm);
+    assert!(debugger.set_breakpoint(None, 0, 0));
+
+    let frame = match debugger.resume().unwrap() {
+        DebugState::Breakpoint(frame) => frame,
+        state => panic!("unexpected state: {:?}", state)
+    };
+    assert_eq!(frame, StackFrame { module: None, chunk_id: 0, offset: 0 });
+
+    let frames = debugger.stack_frames();
+    assert_eq!(frames.len(), 2);
+    assert_eq!(frames[1].chunk_id, 1);
+    assert!(debugger.current_instruction().is_some());
+
+    // Local variable of the caller
+    let locals = debugger.locals(1).unwrap();
+    assert_eq!(locals.len(), 1);
+    assert_eq!(locals[0].as_ref().as_u64().unwrap(), 10);
+
+    // Step inside the function
+    assert!(matches!(debugger.step().unwrap(), DebugState::Paused));
+    assert!(debugger.current_frame().unwrap().offset > 0);
+
+    match debugger.resume().unwrap() {
+        DebugState::Finished(value) => assert_eq!(value, Value::U64(15).into()),
+        state => panic!("unexpected state: {:?}", state)
+    }
+}
+
+#[test]
+fn test_debug_info_error_location() {
+    let code = r#"
+        entry main() {
+            let a: u64 = 10;
+            let b: u64 = 0;
+            if a > 5 {
+                return a / b
+            }
+            return 0
+        }
+    "#;
+
+    for level in [OptimizationLevel::None, OptimizationLevel::Full] {
+        let tokens: Vec<_> = Lexer::new(code).collect::<Result<_, _>>().unwrap();
+        let env = EnvironmentBuilder::default();
+        let (program, _) = Parser::with(tokens.into_iter(), &env).parse().unwrap();
+        let environment = env.build();
+
+        let mut compiler = Compiler::new(&program, &environment).with_optimizations(level);
+        compiler.set_debug_info(true);
+        let module = compiler.compile().unwrap();
+
+        let mut vm = VM::new(&module, &environment);
+        vm.invoke_entry_chunk(0).unwrap();
+        let err = vm.run().unwrap_err();
+
+        assert!(matches!(err.inner(), VMError::DivisionByZero));
+        let frame = &err.backtrace().unwrap().frames()[0];
+        assert_eq!(frame.span.map(|span| span.line), Some(6));
+        assert!(err.to_string().contains("line 6"));
+    }
+}
+
+#[test]
+fn test_backtrace() {
+    let code = r#"
+        fn div(a: u64, b: u64) -> u64 {
+            return a / b
+        }
+
+        fn compute(b: u64) -> u64 {
+            return div(10, b) + 1
+        }
+
+        entry main() {
+            return compute(0)
+        }
+    "#;
+
+    let tokens: Vec<_> = Lexer::new(code).collect::<Result<_, _>>().unwrap();
+    let env = EnvironmentBuilder::default();
+    let (program, _) = Parser::with(tokens.into_iter(), &env).parse().unwrap();
+    let environment = env.build();
+
+    // Without debug info, only the chunks are known
+    let module = Compiler::new(&program, &environment).compile().unwrap();
+    let err = run_internal(module, &environment, 2).unwrap_err();
+    assert!(matches!(err.inner(), VMError::DivisionByZero));
+    let frames = err.backtrace().unwrap().frames();
+    assert_eq!(frames.iter().map(|f| f.chunk_id).collect::<Vec<_>>(), vec![0, 1, 2]);
+    assert!(frames.iter().all(|f| f.name.is_none() && f.span.is_none()));
+
+    let mut compiler = Compiler::new(&program, &environment);
+    compiler.set_debug_info(true);
+    let module = compiler.compile().unwrap();
+    let err = run_internal(module, &environment, 2).unwrap_err();
+    let backtrace = err.backtrace().unwrap();
+    let frames = backtrace.frames();
+    assert_eq!(frames.iter().map(|f| f.name.as_deref()).collect::<Vec<_>>(), vec![Some("div"), Some("compute"), Some("main")]);
+    assert_eq!(frames.iter().map(|f| f.span.map(|s| s.line)).collect::<Vec<_>>(), vec![Some(3), Some(7), Some(11)]);
+
+    let output = backtrace.to_string();
+    assert!(output.contains("0: div (chunk 0)"));
+    assert!(output.contains("2: main (chunk 2)"));
+    assert!(err.to_string().starts_with("division by zero in div (chunk 0)"));
 }
 
 #[test]
@@ -397,73 +974,322 @@ fn test_array_in_struct() {
 }
 
 #[test]
-fn test_continue() {
+fn test_typed_array() {
     let code = r#"
+        fn set(values: u8[]) {
+            values[1] = 42;
+        }
+
         entry main() {
-            let x: u64 = 0;
-            for i: u64 = 0; i < 10; i += 1 {
-                if ((i % 2) == 0) {
-                    continue
+            let arr: u8[] = [1, 2, 3];
+            arr[0] = 10;
+            arr[2] += 5;
+            let copy: u8 = arr[0];
+            copy += 1;
+            set(arr);
+
+            let grid: u64[][] = [[1, 2], [3, 4]];
+            grid[1][0] = 30;
+
+            let values: u32[] = [];
+            values.push(7);
+            values.push(8);
+
+            // Slices share their values with the array
+            let slice: u8[] = arr.slice(0..2);
+            slice[0] = 20;
+
+            let total: u64 = 0;
+            foreach v in arr {
+                total += v as u64;
+            }
+            foreach row in grid {
+                foreach v in row {
+                    total += v;
                 }
-                x += 1
             }
-            return x
+            foreach v in values {
+                total += v as u64;
+            }
+
+            assert(values.contains(8));
+            assert(!values.contains(9));
+            assert(values.pop().unwrap() == 8);
+            assert(arr.first().unwrap() == 20);
+            assert(arr.last().unwrap() == 8);
+            return total + values.len() as u64
         }
     "#;
 
-    assert_eq!(run_code(code), Value::U64(5));
+    // 20 + 42 + 8 + 1 + 2 + 30 + 4 + 7 + 8 + 1
+    assert_eq!(run_code_id(code, 1), Value::U64(123));
 }
 
 #[test]
-fn test_break() {
+fn test_typed_array_inline() {
+    let mut env = EnvironmentBuilder::default();
+    for (name, ty) in [("is_inline", Type::U64), ("is_inline_bytes", Type::U8)] {
+        env.register_native_function(name, None, vec![("values", Type::Array(Box::new(ty)))], |_, params, _| {
+            let inline = matches!(params[0].as_ref().as_value(), ValueCell::TypedArray(_));
+            Ok(Some(Value::Boolean(inline).into()))
+        }, 1, Some(Type::Bool));
+    }
+
     let code = r#"
         entry main() {
-            let x: u64 = 0;
-            for i: u64 = 0; i < 10; i += 1 {
-                if (i == 5) {
-                    break
-                }
-                x += 1
+            let values: u64[] = [1, 2, 3];
+            assert(is_inline(values));
+            values[0] = 10;
+            values[1] += 1;
+            assert(is_inline(values));
+
+            let pushed: u64[] = [];
+            for i: u64 = 0; i < 3; i += 1 {
+                pushed.push(i);
             }
-            return x
+            assert(is_inline(pushed));
+            assert(is_inline_bytes("ab".to_bytes()));
+
+            // Referencing the values boxes them
+            let slice: u64[] = values.slice(0..2);
+            assert(!is_inline(values));
+            assert(!is_inline(slice));
+
+            return values[0] + values[1] + pushed[2]
         }
     "#;
+    let tokens: Vec<_> = Lexer::new(code).collect::<Result<_, _>>().unwrap();
+    let (program, _) = Parser::with(tokens.into_iter(), &env).parse().unwrap();
+    let env = env.build();
+    let module = Compiler::new(&program, &env).compile().unwrap();
 
-    assert_eq!(run_code(code), Value::U64(5));
+    assert_eq!(run_internal(module, &env, 0).unwrap(), Value::U64(15));
 }
 
 #[test]
-fn test_nested_loops() {
+fn test_typed_array_copy_on_write() {
+    let mut env = EnvironmentBuilder::default();
+    env.register_native_function("is_shared", None, vec![("values", Type::Array(Box::new(Type::U64)))], |_, params, _| {
+        let shared = match params[0].as_ref().as_value() {
+            ValueCell::TypedArray(array) => array.is_shared(),
+            _ => false
+        };
+        Ok(Some(Value::Boolean(shared).into()))
+    }, 1, Some(Type::Bool));
+
     let code = r#"
+        struct Holder {
+            values: u64[]
+        }
+
         entry main() {
-            let x: u64 = 0;
-            for i: u64 = 0; i < 10; i += 1 {
-                for j: u64 = 0; j < 10; j += 1 {
-                    x = x + 1
-                }
-            }
-            return x
+            let values: u64[] = [1, 2, 3];
+            let copy: u64[] = [];
+            copy = values;
+            let holder: Holder = Holder { values: values };
+            assert(is_shared(values));
+            assert(is_shared(copy));
+
+            // Mutating a copy doesn't affect the others
+            values[0] = 10;
+            assert(!is_shared(values));
+            assert(is_shared(copy));
+            assert(is_shared(holder.values));
+
+            copy.push(4);
+            assert(!is_shared(copy));
+            assert(!is_shared(holder.values));
+            holder.values[1] = 20;
+
+            return values[0] + values[1] + copy[0] + copy[3] + holder.values[0] + holder.values[1]
         }
     "#;
+    let tokens: Vec<_> = Lexer::new(code).collect::<Result<_, _>>().unwrap();
+    let (program, _) = Parser::with(tokens.into_iter(), &env).parse().unwrap();
+    let env = env.build();
+    let module = Compiler::new(&program, &env).compile().unwrap();
 
-    assert_eq!(run_code(code), Value::U64(100));
+    // 10 + 2 + 1 + 4 + 1 + 20
+    assert_eq!(run_internal(module, &env, 0).unwrap(), Value::U64(38));
 }
 
 #[test]
-fn test_for_array() {
+fn test_copy_on_write() {
+    let mut env = EnvironmentBuilder::default();
+    env.register_native_function("is_shared", None, vec![("values", Type::Array(Box::new(Type::Any)))], |_, params, _| {
+        let shared = match params[0].as_ref().as_value() {
+            ValueCell::Array(values) => values.is_shared(),
+            _ => false
+        };
+        Ok(Some(Value::Boolean(shared).into()))
+    }, 1, Some(Type::Bool));
+
     let code = r#"
+        struct Point {
+            x: u64
+        }
+
+        struct Holder {
+            points: Point[]
+        }
+
         entry main() {
-            let arr: u64[] = [10, 20, 30];
-            let x: u64 = 0;
-            for i: u32 = 0; i < arr.len(); i += 1 {
-                let y: u64 = arr[i];
-                x = x + y
-            }
-            return x
+            let points: Point[] = [Point { x: 1 }, Point { x: 2 }];
+            let copy: Point[] = [];
+            copy = points;
+            let holder: Holder = Holder { points: points };
+            assert(is_shared(points));
+            assert(is_shared(copy));
+
+            // Mutating an element of a copy doesn't affect the others
+            points[0].x = 10;
+            assert(!is_shared(points));
+            assert(is_shared(copy));
+            assert(is_shared(holder.points));
+
+            // An element referenced by a variable isn't shared with a copy
+            let first: Point = copy[0];
+            assert(!is_shared(copy));
+            let other: Point[] = [];
+            other = copy;
+            assert(!is_shared(copy));
+            assert(!is_shared(other));
+            first.x = 30;
+
+            holder.points.push(Point { x: 3 });
+            return points[0].x + copy[0].x + other[0].x + holder.points[0].x + holder.points.len() as u64
         }
     "#;
+    let tokens: Vec<_> = Lexer::new(code).collect::<Result<_, _>>().unwrap();
+    let (program, _) = Parser::with(tokens.into_iter(), &env).parse().unwrap();
+    let env = env.build();
+    let module = Compiler::new(&program, &env).compile().unwrap();
 
-    assert_eq!(run_code(code), Value::U64(60));
+    // 10 + 30 + 1 + 1 + 3
+    assert_eq!(run_internal(module, &env, 0).unwrap(), Value::U64(45));
+}
+
+#[cfg(feature = "sync")]
+#[test]
+fn test_parallel_execution() {
+    let code = r#"
+        struct Account {
+            balances: u64[]
+        }
+
+        entry main() {
+            let balances: u64[] = [];
+            let account: Account = Account { balances: balances };
+            for i: u64 = 0; i < 100; i += 1 {
+                account.balances.push(i);
+            }
+
+            let total: u64 = 0;
+            foreach balance in account.balances {
+                total += balance;
+            }
+            return total
+        }
+    "#;
+
+    let (module, environment) = prepare_module(code);
+    let (module, environment) = (&module, &environment);
+
+    // The module and the environment are shared by the VMs of each thread
+    let results: Vec<_> = std::thread::scope(|scope| {
+        let handles: Vec<_> = (0..4).map(|_| scope.spawn(move || {
+            let mut vm = VM::new(module, environment);
+            vm.invoke_entry_chunk(0).unwrap();
+            vm.run().unwrap()
+        })).collect();
+
+        handles.into_iter()
+            .map(|handle| handle.join().unwrap())
+            .collect()
+    });
+    assert_eq!(results, vec![ExecutionOutcome::Success(Constant::Default(Value::U64(4950))); 4]);
+
+    // A paused execution can be resumed on another thread
+    let mut vm = VM::new(module, environment);
+    vm.invoke_entry_chunk(0).unwrap();
+    assert!(matches!(vm.run_for(50).unwrap(), RunResult::Paused));
+
+    let result = std::thread::scope(|scope| {
+        scope.spawn(move || vm.resume().unwrap())
+            .join()
+            .unwrap()
+    });
+    assert_eq!(result, ExecutionOutcome::Success(Constant::Default(Value::U64(4950))));
+}
+
+#[test]
+fn test_continue() {
+    let code = r#"
+        entry main() {
+            let x: u64 = 0;
+            for i: u64 = 0; i < 10; i += 1 {
+                if ((i % 2) == 0) {
+                    continue
+                }
+                x += 1
+            }
+            return x
+        }
+    "#;
+
+    assert_eq!(run_code(code), Value::U64(5));
+}
+
+#[test]
+fn test_break() {
+    let code = r#"
+        entry main() {
+            let x: u64 = 0;
+            for i: u64 = 0; i < 10; i += 1 {
+                if (i == 5) {
+                    break
+                }
+                x += 1
+            }
+            return x
+        }
+    "#;
+
+    assert_eq!(run_code(code), Value::U64(5));
+}
+
+#[test]
+fn test_nested_loops() {
+    let code = r#"
+        entry main() {
+            let x: u64 = 0;
+            for i: u64 = 0; i < 10; i += 1 {
+                for j: u64 = 0; j < 10; j += 1 {
+                    x = x + 1
+                }
+            }
+            return x
+        }
+    "#;
+
+    assert_eq!(run_code(code), Value::U64(100));
+}
+
+#[test]
+fn test_for_array() {
+    let code = r#"
+        entry main() {
+            let arr: u64[] = [10, 20, 30];
+            let x: u64 = 0;
+            for i: u32 = 0; i < arr.len(); i += 1 {
+                let y: u64 = arr[i];
+                x = x + y
+            }
+            return x
+        }
+    "#;
+
+    assert_eq!(run_code(code), Value::U64(60));
 }
 
 #[test]
@@ -643,252 +1469,3785 @@ fn test_map_inline() {
 }
 
 #[test]
-fn test_self_reference() {
+fn test_map_insertion_order() {
     let code = r#"
         entry main() {
-            let x: u64[][] = [[10]];
-            x.push(x[0]);
-            x[1][0] = 20;
-            return x[0][0]
+            let x: map<u64, u64> = {
+                3: 30,
+                1: 10,
+                2: 20
+            };
+            x.insert(9, 90);
+            x.remove(1);
+            x.insert(3, 31);
+
+            let keys: u64[] = x.keys();
+            let values: u64[] = x.values();
+            let r: u64 = 0;
+            foreach k in keys {
+                r *= 10;
+                r += k;
+            }
+            foreach v in values {
+                r *= 100;
+                r += v;
+            }
+            return r
         }
     "#;
 
+    // keys: 3, 2, 9 then values: 31, 20, 90
     assert_eq!(
         run_code(code),
-        Value::U64(10)
+        Value::U64(329312090)
     );
 }
 
 #[test]
-fn test_self_reference_struct() {
+fn test_map_functions() {
     let code = r#"
-        struct Test {
-            x: u64
-        }
-
         entry main() {
-            let t: Test[] = [Test { x: 10 }];
-            t.push(t.get(0).unwrap());
-            t[1].x = 20;
-            return t[0].x
+            let x: map<string, u64> = {
+                "a": 1,
+                "b": 2
+            };
+            let other: map<string, u64> = {
+                "c": 3,
+                "a": 10
+            };
+            x.merge(other);
+            assert(other.len() == 2);
+
+            assert(x.get_or_insert("b", 0) == 2);
+            assert(x.get_or_insert("d", 4) == 4);
+            assert(x.get("d").unwrap() == 4);
+
+            // a: 10, b: 2, c: 3, d: 4
+            let r: u64 = 0;
+            foreach e in x.entries() {
+                let (k, v) = e;
+                assert(k.len() == 1);
+                r = (r * 100) + v;
+            }
+
+            x.retain(["d", "a", "z"]);
+            let keys: string[] = x.keys();
+            assert(keys[0] == "a");
+            assert(keys[1] == "d");
+            return r + x.len() as u64
         }
     "#;
 
-    assert_eq!(
-        run_code(code),
-        Value::U64(10)
-    );
+    assert_eq!(run_code(code), Value::U64(10020304 + 2));
 }
 
 #[test]
-fn test_self_reference_map() {
+fn test_foreach_iterables() {
+    let mut env = EnvironmentBuilder::default();
+    let pair = env.register_structure("Pair", vec![("a", Type::U64), ("b", Type::U64)]);
+    env.register_native_function("values", Some(Type::Struct(pair.clone())), vec![], |zelf, _, _| {
+        let ValueCell::Struct(fields, _) = zelf? else {
+            unreachable!()
+        };
+        Ok(Some(ValueCell::Array(fields.iter().map(|v| v.reference()).collect())))
+    }, 5, Some(Type::Array(Box::new(Type::U64))));
+    env.register_iterable(Type::Struct(pair), "values");
+
     let code = r#"
-        struct Dummy {
-            x: u64
+        struct Point {
+            x: u64,
+            y: u64
         }
+
         entry main() {
-            let x: map<string, Dummy> = {};
-            x.insert("a", Dummy { x: 10 });
-            let dummy: Dummy = x.get("a").unwrap();
-            x.insert("b", dummy);
-            x.get("b").unwrap().x = 20;
+            let m: map<string, u64> = {
+                "a": 1,
+                "b": 2,
+                "c": 3
+            };
+            let r: u64 = 0;
+            foreach (k, v) in m {
+                assert(k.len() == 1);
+                r = (r * 10) + v;
+            }
 
-            assert(!is_same_ptr(x.get("a").unwrap(), x.get("b").unwrap()));
+            let points: Point[] = [Point { x: 1, y: 2 }, Point { x: 3, y: 4 }];
+            foreach { x, y } in points {
+                r = (r * 10) + (x * y);
+            }
 
-            return x.get("a").unwrap().x
+            let pair: Pair = Pair { a: 5, b: 6 };
+            foreach value in pair {
+                r = (r * 10) + value;
+            }
+            return r
         }
     "#;
+    let tokens: Vec<_> = Lexer::new(code).collect::<Result<_, _>>().unwrap();
+    let (program, _) = Parser::with(tokens.into_iter(), &env).parse().unwrap();
+    let env = env.build();
+    let module = Compiler::new(&program, &env).compile().unwrap();
 
-    assert_eq!(
-        run_code(code),
-        Value::U64(10)
-    );
+    assert_eq!(run_internal(module, &env, 0).unwrap(), Value::U64(1233256));
 }
 
 #[test]
-fn test_enum() {
+fn test_lazy_iterator() {
     let code = r#"
-        enum Test {
-            A,
-            B { value: u64 }
+        entry main() {
+            let numbers: range<u64> = 0..1000000;
+            // 2, 5, 8, 11
+            let it: Iterator<u64> = numbers.iter().skip(2).step_by(3).take(4);
+            assert(it.count() == 4);
+            assert(it.sum().unwrap() == 26);
+
+            let first: u64 = it.next().unwrap();
+            assert(first == 2);
+            assert(it.collect()[0] == 5);
+
+            let values: u8[] = [1, 2, 3, 4, 5];
+            let r: u64 = 0;
+            foreach v in values.iter().skip(1).step_by(2) {
+                r = (r * 10) + (v as u64);
+            }
+
+            let empty: u64[] = [];
+            assert(empty.iter().sum().is_none());
+            return (r * 1000) + it.count()
         }
+    "#;
 
+    assert_eq!(run_code(code), Value::U64(24003));
+
+    // The sum is checked in the type of the values
+    let code = r#"
         entry main() {
-            let x: Test = Test::B { value: 10 };
-            return 10
+            let values: u8[] = [200, 100];
+            return values.iter().sum().unwrap() as u64
         }
     "#;
-
-    assert_eq!(
-        run_code(code),
-        Value::U64(10)
-    );
+    assert!(try_run_code(code, 0).is_err());
 }
 
 #[test]
-fn test_array_slice() {
+fn test_iterator_adapters() {
     let code = r#"
+        fn double(v: u64) -> u64 {
+            return v * 2
+        }
+
+        fn is_even(v: u64) -> bool {
+            return (v % 2) == 0
+        }
+
+        fn describe(v: u8) -> string {
+            return "n" + v
+        }
+
         entry main() {
-            let x: u64[] = [10, 20, 30, 40, 50];
-            let y: u64[] = x.slice(1..4);
+            let numbers: range<u64> = 0..10;
+            let r: u64 = 0;
+            foreach v in numbers.iter().filter(is_even).map(double) {
+                r = (r * 100) + v;
+            }
+            assert(r == 4081216);
 
-            assert(is_same_ptr(y[0], x[1]));
-            y.push(60);
-            assert(!is_same_ptr(y[3], x[4]));
+            // The adapters are resolved by the natives consuming the values
+            let it: Iterator<u64> = numbers.iter().map(double).filter(is_even).skip(3);
+            assert(it.next().unwrap() == 6);
+            assert(it.count() == 6);
+            assert(it.sum().unwrap() == 78);
+            assert(it.collect()[5] == 18);
 
-            y.push(x[4]);
-            assert(!is_same_ptr(y[4], x[4]));
+            // The values skipped before a map aren't mapped
+            let first: u64 = numbers.iter().skip(8).map(double).next().unwrap();
+            assert(first == 16);
+            assert(numbers.iter().filter(is_even).take(2).sum().unwrap() == 2);
 
-            return y[0] + y[1] + y[2]
+            let values: u8[] = [1, 2];
+            let names: string[] = values.iter().map(describe).collect();
+            assert(names[1] == "n2");
+
+            return numbers.rev().filter(is_even).map(double).next().unwrap()
         }
     "#;
 
-    assert_eq!(
-        run_code(code),
-        Value::U64(90)
-    );
-}
+    assert_eq!(run_code_id(code, 3), Value::U64(16));
 
-#[test]
-fn test_recursive_call() {
-    let code = r#"
-        fn fib(n: u64) -> u64 {
-            if n == 0 {
-                return 0
-            } else if n == 1 {
-                return 1
-            }
+    // The referenced chunks are kept and remapped by the dead code elimination
+    let code = format!("fn unused() -> u64 {{ return 0 }}\n{code}");
+    let tokens: Vec<_> = Lexer::new(&code).collect::<Result<_, _>>().unwrap();
+    let env = EnvironmentBuilder::default();
+    let (program, _) = Parser::with(tokens.into_iter(), &env).parse().unwrap();
+    let environment = env.build();
+    let module = Compiler::new(&program, &environment).compile().unwrap();
+    let module = eliminate_dead_code(module).unwrap();
 
-            return fib(n - 1) + fib(n - 2)
+    assert_eq!(module.chunks().len(), 4);
+    assert_eq!(run_internal(module, &environment, 3).unwrap(), Value::U64(16));
+
+    // The values taken after a filter can't be reversed
+    let code = r#"
+        fn is_even(v: u64) -> bool {
+            return (v % 2) == 0
         }
 
         entry main() {
-            return fib(10)
+            let numbers: range<u64> = 0..10;
+            assert(numbers.iter().filter(is_even).rev().next().unwrap() == 8);
+            return numbers.iter().filter(is_even).take(2).rev().next().unwrap()
         }
     "#;
-
-    assert_eq!(
-        run_code_id(code, 1),
-        Value::U64(55)
-    );
+    assert!(try_run_code(code, 1).is_err());
+
+    // The function must be declared before with a matching signature
+    let parse = |code: &str| {
+        let tokens: Vec<_> = Lexer::new(code).collect::<Result<_, _>>().unwrap();
+        let env = EnvironmentBuilder::default();
+        Parser::with(tokens.into_iter(), &env).parse().map(|_| ()).map_err(|e| e.kind.to_string())
+    };
+
+    for (code, name) in [
+        ("entry main() { let r: range<u64> = 0..10; return r.iter().map(double).count() } fn double(v: u64) -> u64 { return v * 2 }", "double"),
+        ("fn double(v: u8) -> u64 { return 2 } entry main() { let r: range<u64> = 0..10; return r.iter().map(double).count() }", "double"),
+        ("fn double(v: u64) -> u64 { return v * 2 } entry main() { let r: range<u64> = 0..10; return r.iter().filter(double).count() }", "double"),
+        ("fn nothing(v: u64) {} entry main() { let r: range<u64> = 0..10; return r.iter().map(nothing).count() }", "nothing"),
+    ] {
+        assert_eq!(parse(code), Err(ParserErrorKind::InvalidFunctionReference(name).to_string()), "{code}");
+    }
 }
 
 #[test]
-fn test_const() {
+fn test_range_improvements() {
     let code = r#"
-        const X: u64 = 10;
-
         entry main() {
-            return X
+            let total: u64 = 0;
+            foreach i in 5..8 {
+                total += i;
+            }
+            assert(total == 18);
+
+            // The end of the type is reached without overflow
+            let bytes: range<u8> = 250u8..=255u8;
+            let count: u64 = 0;
+            foreach b in bytes {
+                count += 1;
+            }
+            assert(count == 6);
+            assert(bytes.len() == 6);
+            assert(bytes.contains(255));
+            assert(!(250u8..255u8).contains(255));
+            assert(bytes.collect()[5] == 255);
+
+            let numbers: range<u64> = 0..=10;
+            // 10, 7, 4, 1
+            let r: u64 = 0;
+            foreach v in numbers.rev().step_by(3) {
+                r = (r * 100) + v;
+            }
+
+            // 0, 4, 8 then reversed
+            let last: u64 = numbers.step_by(4).rev().next().unwrap();
+            return (r * 10) + last
         }
     "#;
 
-    assert_eq!(
-        run_code(code),
-        Value::U64(10)
-    );
-}
+    assert_eq!(run_code(code), Value::U64(100704018));
 
-#[test]
-fn test_const_add() {
+    // All the values of the type are counted
     let code = r#"
-        const ZERO: u64 = 0
-        const HELLO_WORLD: string = "Hello World"
-
         entry main() {
-            let message: string = HELLO_WORLD + " " + ZERO
-            return message.len() as u64
+            let bytes: range<u8> = 0u8..=255u8;
+            return bytes.len()
         }
     "#;
+    assert_eq!(run_code(code), Value::U64(256));
 
-    assert_eq!(
-        run_code(code),
-        Value::U64(13)
-    );
-}
-
-#[test]
-fn test_optional_cast() {
     let code = r#"
-        struct Test { value: u64 }
         entry main() {
-            let x: optional<Test> = Test { value: 10 };
-            let y: optional<u64> = x.unwrap().value;
-            let v: u64 = y.unwrap();
-            return v
+            let numbers: range<u256> = 0u256..(1u256 << 70);
+            return numbers.len()
         }
     "#;
-
-    assert_eq!(
-        run_code(code),
-        Value::U64(10)
-    );
+    assert!(try_run_code(code, 0).is_err());
 }
 
-
 #[test]
-fn test_optional_unwrap_or() {
+fn test_switch() {
     let code = r#"
+        fn eval(op: u8, a: u64, b: u64) -> u64 {
+            switch (op) {
+                case 0: return a + b
+                case 1: return a - b
+                case 2, 3:
+                    let c: u64 = a * b;
+                    return c
+                case 4:
+                    a += 1;
+                    fallthrough
+                case 5:
+                    return a
+                default:
+                    return 0
+            }
+        }
+
+        fn name(s: string) -> u64 {
+            let r: u64 = 0;
+            switch s {
+                case "a": r = 1;
+                case "b", "c": r = 2;
+            }
+            return r
+        }
+
         entry main() {
-            let x: optional<u8> = null;
-            return x.unwrap_or(10) as u64
+            let total: u64 = 0;
+            foreach op in 0u8..8u8 {
+                if op == 7 {
+                    break
+                }
+                // continue and break apply to the loop
+                switch op {
+                    case 6: continue
+                }
+                total = (total * 100) + eval(op, 10, 3);
+            }
+            return (total * 10) + name("c")
         }
     "#;
 
-    assert_eq!(
-        run_code(code),
-        Value::U64(10)
-    );
+    assert_eq!(run_code_id(code, 2), Value::U64(1307303011102));
+
+    // The contiguous integer cases are dispatched by a jump table at every level
+    for level in [OptimizationLevel::None, OptimizationLevel::Full] {
+        let tokens: Vec<_> = Lexer::new(code).collect::<Result<_, _>>().unwrap();
+        let env = EnvironmentBuilder::default();
+        let (program, _) = Parser::with(tokens.into_iter(), &env).parse().unwrap();
+        let environment = env.build();
+        let module = Compiler::new(&program, &environment)
+            .with_optimizations(level)
+            .compile()
+            .unwrap();
+
+        let code = module.get_chunk_at(0).unwrap().get_instructions();
+        let mut offset = 0;
+        let mut tables = 0;
+        while offset < code.len() {
+            let opcode = OpCode::from_byte(code[offset]).unwrap();
+            if opcode == OpCode::JumpTable {
+                tables += 1;
+            }
+            offset += 1 + opcode.arguments_bytes();
+        }
+        assert_eq!(tables, 1, "{:?}", level);
+        assert_eq!(run_internal(module, &environment, 2).unwrap(), Value::U64(1307303011102));
+    }
+
+    let parse = |body: &str| {
+        let code = format!("entry main() {{\n let x: u64 = 1;\n {}\n return 0\n }}", body);
+        let tokens: Vec<_> = Lexer::new(&code).collect::<Result<_, _>>().unwrap();
+        let env = EnvironmentBuilder::default();
+        Parser::with(tokens.into_iter(), &env).parse().map(|_| ()).map_err(|e| e.kind.to_string())
+    };
+
+    assert_eq!(parse("switch x { case 1: x = 2; default: x = 3; }"), Ok(()));
+    assert_eq!(parse("switch x { case 1, 1: x = 2; }"), Err(ParserErrorKind::DuplicatedSwitchCase(Value::U64(1)).to_string()));
+    assert_eq!(parse("switch x { default: x = 3; case 1: x = 2; }"), Err(ParserErrorKind::SwitchDefaultNotLast.to_string()));
+    assert_eq!(parse("switch x { case 1: fallthrough }"), Err(ParserErrorKind::InvalidFallthrough.to_string()));
+    assert_eq!(parse("switch x { case x: x = 2; }"), Err(ParserErrorKind::InvalidConstantValue.to_string()));
+    assert_eq!(parse("switch [x] { case 1: x = 2; }"), Err(ParserErrorKind::InvalidSwitchType(Type::Array(Box::new(Type::U64))).to_string()));
 }
 
 #[test]
-fn test_div_by_zero() {
+fn test_dead_code_elimination() {
     let code = r#"
-        entry main() {
-            let x: u64 = 10;
-            let y: u64 = 0;
-            return x / y
+        struct Unused {
+            a: u64
         }
-    "#;
+
+        struct Point {
+            x: u64,
+            y: u64
+        }
+
+        enum Shape {
+            Empty,
+            Square { corner: Point, size: u64 }
+        }
+
+        fn unused(a: u64) -> u64 {
+            let u: Unused = Unused { a: a };
+            return u.a
+        }
+
+        fn area(shape: Shape, p: Point) -> u64 {
+            let empty: Shape = Shape::Empty;
+            if shape == empty {
+                return 0
+            }
+            return (p.y * 25) + p.x
+        }
+
+        fn code(op: u64) -> u64 {
+            switch op {
+                case 10: return 1
+                case 11: return 2
+                case 12: return 3
+                default: return 0
+            }
+        }
+
+        entry main() {
+            let p: Point = Point { x: 1, y: 2 };
+            let s: Shape = Shape::Square { corner: p, size: 7 };
+            return (area(s, p) * 10) + code(12)
+        }
+    "#;
+
+    let tokens: Vec<_> = Lexer::new(code).collect::<Result<_, _>>().unwrap();
+    let env = EnvironmentBuilder::default();
+    let (program, _) = Parser::with(tokens.into_iter(), &env).parse().unwrap();
+    let environment = env.build();
+
+    let mut compiler = Compiler::new(&program, &environment);
+    compiler.set_dead_code_elimination(true);
+    let module = compiler.compile().unwrap();
+    assert_eq!(module.chunks().len(), 3);
+    assert_eq!(module.structs().len(), 1);
+    assert_eq!(module.enums().len(), 1);
+    assert!(ModuleValidator::new(&module, &environment).verify().is_ok());
+
+    // The new ids are kept by the serialization
+    let module = Module::from_bytes(&module.to_bytes().unwrap()).unwrap();
+    let id = module.entry_by_name("main").unwrap() as u16;
+    assert_eq!(run_internal(module, &environment, id).unwrap(), Value::U64(513));
+}
+
+#[test]
+fn test_string_utf8() {
+    let code = r#"
+        entry main() {
+            let s: string = "a🦀é";
+            assert(s.len() == 7);
+            assert(s.len_bytes() == 7);
+            assert(s.len_chars() == 3);
+            assert(s.char_at(1).unwrap() == "🦀");
+
+            let letters: string[] = s.chars();
+            assert(letters.len() == 3);
+            assert(letters[1] == "🦀");
+            assert(letters[2] == "é");
+
+            // Byte indexes inside a char have no substring
+            assert(s.substring(1).unwrap() == "🦀é");
+            assert(s.substring(2).is_none());
+            assert(s.substring(1, 3).is_none());
+            assert(s.substring(1, 5).unwrap() == "🦀");
+            assert(s.substring(6).is_none());
+            assert(s.substring(8).is_none());
+            assert(s.substring(5, 1).is_none());
+
+            assert(s.substring_chars(1, 2).unwrap() == "🦀");
+            assert(s.substring_chars(1, 3).unwrap() == "🦀é");
+            assert(s.substring_chars(3, 3).unwrap() == "");
+            assert(s.substring_chars(2, 4).is_none());
+            assert(s.substring_chars(2, 1).is_none());
+            return s.index_of("é").unwrap() as u64
+        }
+    "#;
+
+    assert_eq!(run_code(code), Value::U64(5));
+}
+
+#[test]
+fn test_string_padding() {
+    let code = r#"
+        entry main() {
+            let s: string = "7";
+            assert(s.pad_start(3, "0") == "007");
+            assert(s.pad_end(4, "ab") == "7aba");
+            assert(s.pad_start(1, "0") == "7");
+            assert(s.pad_start(3, "") == "7");
+            assert("🦀".pad_start(3, "é") == "éé🦀");
+
+            assert("ab".repeat(3) == "ababab");
+            assert("ab".repeat(0) == "");
+
+            let padded: string = "  a b  ";
+            assert(padded.trim_start() == "a b  ");
+            assert(padded.trim_end() == "  a b");
+
+            let (key, value) = "a=b=c".split_once("=").unwrap();
+            assert(key == "a");
+            assert(value == "b=c");
+            assert("abc".split_once("=").is_none());
+            return value.len() as u64
+        }
+    "#;
+
+    assert_eq!(run_code(code), Value::U64(3));
+
+    // The output size is capped
+    let code = r#"
+        entry main() {
+            let s: string = "abcd";
+            return s.repeat(100000).len() as u64
+        }
+    "#;
+    let err = try_run_code(code, 0).unwrap_err();
+    assert!(matches!(err.inner(), VMError::EnvironmentError(EnvironmentError::StringTooLarge { max: 65536, actual: 400000 })), "{}", err);
+
+    let code = r#"
+        entry main() {
+            let s: string = "a";
+            return s.pad_end(4000000000, "b").len() as u64
+        }
+    "#;
+    let err = try_run_code(code, 0).unwrap_err();
+    assert!(matches!(err.inner(), VMError::EnvironmentError(EnvironmentError::StringTooLarge { .. })), "{}", err);
+}
+
+#[test]
+fn test_deep_equality() {
+    let code = r#"
+        struct Point {
+            x: u64,
+            y: u64
+        }
+
+        struct Line {
+            start: Point,
+            end: Point,
+            tags: string[]
+        }
+
+        entry main() {
+            let a: Line = Line { start: Point { x: 1, y: 2 }, end: Point { x: 3, y: 4 }, tags: ["a"] };
+            let b: Line = Line { start: Point { x: 1, y: 2 }, end: Point { x: 3, y: 4 }, tags: ["a"] };
+            assert(a == b);
+            b.end.y = 5;
+            assert(a != b);
+
+            let values: u64[][] = [[1, 2], [3]];
+            assert(values == [[1, 2], [3]]);
+            assert(values != [[1, 2], [3, 4]]);
+
+            let m1: map<string, Point> = {};
+            m1.insert("a", Point { x: 1, y: 2 });
+            m1.insert("b", Point { x: 3, y: 4 });
+            let m2: map<string, Point> = {};
+            m2.insert("b", Point { x: 3, y: 4 });
+            m2.insert("a", Point { x: 1, y: 2 });
+            assert(m1 == m2);
+            m2.insert("c", Point { x: 5, y: 6 });
+            assert(m1 != m2);
+
+            let some: optional<Point> = Point { x: 1, y: 2 };
+            let none: optional<Point> = null;
+            assert(some != none);
+            assert(some.unwrap() == Point { x: 1, y: 2 });
+            assert(none == null);
+            return 0
+        }
+    "#;
+
+    assert_eq!(run_code(code), Value::U64(0));
+}
+
+#[test]
+fn test_compare() {
+    let code = r#"
+        struct Point {
+            x: u64,
+            y: u64
+        }
+
+        entry main() {
+            assert(compare(1, 2).unwrap() == Ordering::Less);
+            assert(compare("b", "a").unwrap() == Ordering::Greater);
+            assert(compare(Point { x: 1, y: 2 }, Point { x: 1, y: 2 }).unwrap() == Ordering::Equal);
+            assert(compare(Point { x: 1, y: 3 }, Point { x: 2, y: 0 }).unwrap() == Ordering::Less);
+
+            let a: u64[] = [1, 2];
+            assert(compare(a, [1, 2, 0]).unwrap() == Ordering::Less);
+            assert(compare(a, [1]).unwrap() == Ordering::Greater);
+
+            let m1: map<string, u64> = {};
+            let m2: map<string, u64> = {};
+            m2.insert("a", 1);
+            assert(compare(m1, m2).is_none());
+            return 0
+        }
+    "#;
+
+    assert_eq!(run_code(code), Value::U64(0));
+}
+
+#[test]
+fn test_clone() {
+    let code = r#"
+        struct Point {
+            x: u64,
+            y: u64
+        }
+
+        struct Line {
+            start: Point,
+            end: Point,
+            tags: string[]
+        }
+
+        entry main() {
+            let a: Line = Line { start: Point { x: 1, y: 2 }, end: Point { x: 3, y: 4 }, tags: ["a"] };
+            let shared: Line = a;
+            let copy: Line = a.clone();
+            shared.start.x = 10;
+            copy.end.y = 20;
+            copy.tags.push("b");
+            assert(a.start.x == 10);
+            assert(a.end.y == 4);
+            assert(a.tags.len() == 1);
+            assert(copy.start.x == 1);
+
+            let m: map<string, Point> = {};
+            m.insert("a", Point { x: 1, y: 2 });
+            let m2: map<string, Point> = m.clone();
+            m2.get("a").unwrap().x = 5;
+            assert(m.get("a").unwrap().x == 1);
+
+            return a.clone().end.x + "abc".clone().len() as u64
+        }
+    "#;
+
+    assert_eq!(run_code(code), Value::U64(6));
+}
+
+#[test]
+fn test_clone_memory() {
+    let code = r#"
+        entry main() {
+            let values: string[] = ["abcd", "ef"];
+            let copy: string[] = values.clone();
+            return copy.len() as u64
+        }
+    "#;
+
+    let (module, environment) = prepare_module(code);
+    let run = |max_memory: usize| {
+        let mut vm = VM::new(&module, &environment);
+        vm.context_mut().set_max_memory(max_memory);
+        vm.invoke_entry_chunk(0).unwrap();
+        let result = vm.run().map_err(|e| e.inner().to_string());
+        (result, vm.context().current_memory_usage())
+    };
+
+    // The constant array and its copy: two strings and their 6 bytes each
+    let peak = 2 * (2 * VALUE_MEMORY_USAGE + 6);
+    // The copy still shares its strings with the array when dropped,
+    // they are only released once with the last of them
+    assert_eq!(run(peak), (Ok(ExecutionOutcome::Success(Value::U64(2).into())), 2 * VALUE_MEMORY_USAGE + 6));
+
+    let (result, _) = run(peak - 1);
+    assert_eq!(result, Err(VMError::OutOfMemory.to_string()));
+}
+
+#[test]
+fn test_string_builder() {
+    let code = r#"
+        entry main() {
+            let sb: StringBuilder = StringBuilder::new();
+            for i: u64 = 0; i < 3; i += 1 {
+                sb.append("a");
+                sb.append_u64(i);
+            }
+            let s: string = sb.to_string();
+            assert(s == "a0a1a2");
+            return sb.len() as u64
+        }
+    "#;
+
+    assert_eq!(run_code(code), Value::U64(6));
+}
+
+#[test]
+fn test_string_builder_gas() {
+    let run = |len: usize| {
+        let code = format!(r#"
+            entry main() {{
+                let sb: StringBuilder = StringBuilder::new();
+                sb.append("{}");
+                return sb.len() as u64
+            }}
+        "#, "a".repeat(len));
+
+        let (module, environment) = prepare_module(&code);
+        let mut vm = VM::new(&module, &environment);
+        vm.invoke_entry_chunk(0).unwrap();
+        assert_eq!(vm.run().unwrap(), ExecutionOutcome::Success(Value::U64(len as u64).into()));
+        vm.context().current_gas_usage()
+    };
+
+    // Appending is charged per byte
+    assert!(run(3200) >= run(1) + 100);
+}
+
+#[test]
+fn test_regex() {
+    let code = r#"
+        entry main() {
+            let s: string = "a1b22c333";
+            assert(s.regex_match("^[a-z][0-9]+"));
+            assert(!s.regex_match("^[0-9]"));
+
+            let numbers: string[] = s.regex_find_all("[0-9]+");
+            assert(numbers[0] == "1");
+            assert(numbers[2] == "333");
+
+            // Capture groups are not expanded
+            let replaced: string = s.regex_replace("([0-9])+", "$1");
+            assert(replaced == "a$1b$1c$1");
+            return numbers[1].len() as u64
+        }
+    "#;
+
+    assert_eq!(run_code(code), Value::U64(2));
+}
+
+#[test]
+fn test_regex_invalid_pattern() {
+    let code = r#"
+        entry main() {
+            let s: string = "abc";
+            s.regex_match("(a");
+            return 0
+        }
+    "#;
+
+    let err = try_run_code(code, 0).unwrap_err();
+    assert!(matches!(err.inner(), VMError::EnvironmentError(EnvironmentError::InvalidRegex(_))));
+
+    // Pattern too long
+    let code = format!(r#"
+        entry main() {{
+            let s: string = "abc";
+            s.regex_match("{}");
+            return 0
+        }}
+    "#, "a".repeat(257));
+
+    let err = try_run_code(&code, 0).unwrap_err();
+    assert!(matches!(err.inner(), VMError::EnvironmentError(EnvironmentError::InvalidRegex(_))));
+}
+
+#[test]
+fn test_regex_gas() {
+    let run = |len: usize| {
+        let code = format!(r#"
+            entry main() {{
+                let s: string = "{}";
+                let a: bool = s.regex_match("b+");
+                let b: bool = s.regex_match("b+");
+                assert(!a && !b);
+                return 0
+            }}
+        "#, "a".repeat(len));
+
+        let (module, environment) = prepare_module(&code);
+        let mut vm = VM::new(&module, &environment);
+        vm.invoke_entry_chunk(0).unwrap();
+        vm.run().unwrap();
+        vm.context().current_gas_usage()
+    };
+
+    // Matching is charged per byte, cached or not
+    assert!(run(3200) >= run(1) + 200);
+}
+
+#[test]
+fn test_format() {
+    let code = r#"
+        entry main() {
+            let name: string = "alice";
+            let amount: u64 = 25;
+            let s: string = format("{} has {} coins {{{}}}", name, amount, true);
+            assert(s == "alice has 25 coins {true}");
+            assert(format("none") == "none");
+            return s.len() as u64
+        }
+    "#;
+
+    assert_eq!(run_code(code), Value::U64(25));
+}
+
+#[test]
+fn test_format_invalid() {
+    let run = |args: &str| {
+        let code = format!(r#"
+            entry main() {{
+                let s: string = format({});
+                return 0
+            }}
+        "#, args);
+
+        let err = try_run_code(&code, 0).unwrap_err();
+        assert!(matches!(err.inner(), VMError::EnvironmentError(EnvironmentError::InvalidFormat(_))), "{}", err);
+    };
+
+    run(r#""{} {}", 1"#);
+    run(r#""{}", 1, 2"#);
+    run(r#""{", 1"#);
+}
+
+#[test]
+fn test_overflow_operators() {
+    let code = r#"
+        entry main() {
+            let max: u8 = 250;
+            let one: u8 = 10;
+
+            let checked: optional<u8> = max +? one;
+            assert(checked.is_none());
+            let zero: optional<u8> = one -? one;
+            assert(zero.unwrap() == 0);
+            assert((max *? one).is_none());
+
+            assert((max +| one) == 255);
+            assert((one -| max) == 0);
+            assert((max *| one) == 255);
+
+            assert((max +% one) == 4);
+            assert((one -% max) == 16);
+            assert((max *% 2) == 244);
+
+            let a: u256 = 0;
+            let b: u256 = 1;
+            assert((a -| b) == 0);
+            return ((a -% b) == u256::MAX.unwrap()) as u64
+        }
+    "#;
+
+    assert_eq!(run_code(code), Value::U64(1));
+}
+
+#[test]
+fn test_overflow_natives() {
+    let code = r#"
+        entry main() {
+            let max: u64 = u64::MAX.unwrap();
+            assert(max.checked_add(1).is_none());
+            assert(max.checked_sub(1).unwrap() == (max - 1));
+            assert(max.saturating_add(1) == max);
+            assert(max.wrapping_add(2) == 1);
+
+            let a: u16 = 300;
+            let b: u16 = 300;
+            assert(a.saturating_mul(b) == u16::MAX.unwrap());
+            assert(a.overflowing_div(0).is_none());
+            assert(a.overflowing_rem(0).is_none());
+            assert(a.overflowing_div(3).unwrap() == 100);
+            return a.wrapping_mul(b) as u64
+        }
+    "#;
+
+    assert_eq!(run_code(code), Value::U64(90000 % 65536));
+}
+
+#[test]
+fn test_bitwise_operators() {
+    let code = r#"
+        entry main() {
+            let a: u8 = 12;
+            let b: u8 = 10;
+            assert((a & b) == 8);
+            assert((a | b) == 14);
+            assert((a ^ b) == 6);
+            assert((!a) == 243);
+
+            let c: u8 = 1;
+            let shift: u8 = 7;
+            assert((c << shift) == 128);
+
+            let flags: u16 = 0;
+            flags |= 4;
+            flags |= 1;
+            flags &= 5;
+            flags ^= 1;
+            assert(flags == 4);
+
+            let big: u256 = 1;
+            let offset: u256 = 200;
+            let top: u256 = big << offset;
+            assert((top >> offset) == big);
+            assert((!top) == (u256::MAX.unwrap() - top));
+
+            let x: u64 = 255;
+            let y: u64 = 4;
+            x >>= y;
+            return (x << y) | (x & y)
+        }
+    "#;
+
+    assert_eq!(run_code(code), Value::U64(240 | 4));
+}
+
+#[test]
+fn test_shift_overflow() {
+    for (t, shift) in [("u8", "8"), ("u32", "32"), ("u64", "64"), ("u128", "128"), ("u256", "256")] {
+        let code = format!(r#"
+            entry main() {{
+                let a: {t} = 1;
+                let b: {t} = {shift};
+                let c: {t} = a << b;
+                return 0
+            }}
+        "#);
+
+        let err = try_run_code(&code, 0).unwrap_err();
+        assert!(matches!(err.inner(), VMError::ShiftOverflow), "{}", t);
+    }
+}
+
+#[test]
+fn test_hashing() {
+    let code = r#"
+        entry main() {
+            let empty: u8[] = [];
+            let blake: u8[] = blake3(empty);
+            assert(blake.len() == 32);
+            assert(blake[0] == 175);
+            assert(blake[31] == 98);
+
+            let keccak: u8[] = keccak256(empty);
+            assert(keccak[0] == 197);
+            assert(keccak[31] == 112);
+
+            let sha: u8[] = sha256("abc".to_bytes());
+            return ((sha[0] as u64) * 256) + (sha[1] as u64)
+        }
+    "#;
+
+    assert_eq!(run_code(code), Value::U64(0xba78));
+}
+
+#[test]
+fn test_verify_ed25519() {
+    // RFC 8032, test 2
+    let pubkey = "[61, 64, 23, 195, 232, 67, 137, 90, 146, 183, 10, 167, 77, 27, 126, 188, 156, 152, 44, 207, 46, 196, 150, 140, 192, 205, 85, 241, 42, 244, 102, 12]";
+    let signature = "[146, 160, 9, 169, 240, 212, 202, 184, 114, 14, 130, 11, 95, 100, 37, 64, 162, 178, 123, 84, 22, 80, 63, 143, 179, 118, 34, 35, 235, 219, 105, 218, 8, 90, 193, 228, 62, 21, 153, 110, 69, 143, 54, 19, 208, 241, 29, 140, 56, 123, 46, 174, 180, 48, 42, 238, 176, 13, 41, 22, 18, 187, 12, 0]";
+    let code = format!(r#"
+        entry main() {{
+            let pubkey: u8[] = {pubkey};
+            let message: u8[] = [114];
+            let signature: u8[] = {signature};
+            assert(verify_ed25519(pubkey, message, signature));
+
+            let other: u8[] = [115];
+            assert(!verify_ed25519(pubkey, other, signature));
+            return 0
+        }}
+    "#);
+
+    assert_eq!(run_code(&code), Value::U64(0));
+
+    // The signature must have exactly 64 bytes
+    let code = format!(r#"
+        entry main() {{
+            let pubkey: u8[] = {pubkey};
+            let message: u8[] = [114];
+            let signature: u8[] = [1, 2, 3];
+            assert(verify_ed25519(pubkey, message, signature));
+            return 0
+        }}
+    "#);
+
+    let err = try_run_code(&code, 0).unwrap_err();
+    assert!(matches!(err.inner(), VMError::EnvironmentError(EnvironmentError::InvalidLength { expected: 64, actual: 3 })));
+}
+
+#[test]
+fn test_random() {
+    let code = r#"
+        entry main() {
+            let random: Random = Random::new();
+            let values: u64[] = [1, 2, 3, 4, 5, 6, 7, 8];
+            random.shuffle(values);
+
+            let sum: u64 = 0;
+            foreach v in values {
+                sum += v;
+            }
+            assert(sum == 36);
+
+            for i: u32 = 0; i < 100; i += 1 {
+                let v: u64 = random.next_range(10, 20);
+                assert(v >= 10);
+                assert(v < 20);
+            }
+
+            return (values[0] * 10) + (random.next_u64() % 10)
+        }
+    "#;
+
+    let (module, environment) = prepare_module(code);
+    let run = |seed: Option<[u8; 32]>| {
+        let mut vm = VM::new(&module, &environment);
+        if let Some(seed) = seed {
+            vm.context_mut().set_random_seed(seed);
+        }
+        vm.invoke_entry_chunk(0).unwrap();
+        vm.run().map(|v| v.into_result().unwrap().into_value().unwrap())
+    };
+
+    // Same seed, same execution
+    let first = run(Some([1; 32])).unwrap();
+    assert_eq!(run(Some([1; 32])).unwrap(), first);
+
+    // The array is shuffled in place
+    let firsts: Vec<_> = (0..8).map(|i| run(Some([i; 32])).unwrap().as_u64().unwrap() / 10).collect();
+    assert!(firsts.iter().all(|v| (1..=8).contains(v)));
+    assert!(firsts.iter().any(|v| *v != firsts[0]));
+
+    let err = run(None).unwrap_err();
+    assert!(matches!(err.inner(), VMError::EnvironmentError(EnvironmentError::MissingRandomSeed)));
+}
+
+#[test]
+fn test_encoding() {
+    let code = r#"
+        entry main() {
+            let bytes: u8[] = [0, 15, 255];
+            assert(hex_encode(bytes) == "000fff");
+            assert(base64_encode(bytes) == "AA//");
+
+            let decoded: u8[] = hex_decode("0aFF").unwrap();
+            assert(decoded[0] == 10);
+            assert(decoded[1] == 255);
+
+            let text: u8[] = base64_decode("aGVsbG8=").unwrap();
+            assert(text[0] == 104);
+            assert(text[4] == 111);
+
+            // Invalid inputs are null
+            assert(hex_decode("abc").is_none());
+            assert(hex_decode("zz").is_none());
+            assert(base64_decode("a").is_none());
+
+            let roundtrip: u8[] = base64_decode(base64_encode(text)).unwrap();
+            return roundtrip[1] as u64
+        }
+    "#;
+
+    assert_eq!(run_code(code), Value::U64(101));
+}
+
+#[test]
+fn test_json() {
+    let code = r#"
+        entry main() {
+            let json: Json = json_parse("{\"name\": \"xelis\", \"values\": [1, 2, 3], \"ok\": true, \"none\": null}").unwrap();
+            assert(json.kind() == "object");
+            assert(json.len().unwrap() == 4);
+            assert(json.get("name").unwrap().as_string().unwrap() == "xelis");
+            assert(json.get("ok").unwrap().as_bool().unwrap());
+            assert(json.get("none").unwrap().is_null());
+            assert(json.get("missing").is_none());
+
+            let values: Json = json.get("values").unwrap();
+            assert(values.kind() == "array");
+            assert(values.at(3).is_none());
+
+            // Fields are kept in order
+            let keys: string[] = json.keys().unwrap();
+            assert(keys[0] == "name");
+            assert(keys[3] == "none");
+            assert(json_stringify(json) == "{\"name\":\"xelis\",\"values\":[1,2,3],\"ok\":true,\"none\":null}");
+
+            // Invalid or unsupported documents
+            assert(json_parse("{").is_none());
+            assert(json_parse("[1.5]").is_none());
+            assert(json_parse("-1").is_none());
+
+            let names: string[] = ["a\"b", "c"];
+            assert(json_stringify(names) == "[\"a\\\"b\",\"c\"]");
+
+            return values.at(2).unwrap().as_u64().unwrap()
+        }
+    "#;
+
+    assert_eq!(run_code(code), Value::U64(3));
+}
+
+#[test]
+fn test_u256_math() {
+    let code = r#"
+        entry main() {
+            let max: u256 = u256::MAX.unwrap();
+            let two: u256 = 2;
+            let e: u32 = 255;
+            assert(two.pow(e).is_some());
+            assert(two.pow(e + 1).is_none());
+            assert(max.isqrt() == (u256::MAX.unwrap() >> 128));
+            assert(max.leading_zeros() == 0);
+            assert(max.log2().unwrap() == 255);
+
+            let zero: u256 = 0;
+            assert(zero.log2().is_none());
+
+            // The intermediate product doesn't fit in a u256
+            let half: u256 = max / two;
+            let other_half: u256 = max / two;
+            let denominator: u256 = u256::MAX.unwrap();
+            assert(max.mul_div(two, denominator).unwrap() == two);
+            assert(max.mul_div(half, other_half).unwrap() == max);
+            let one: u256 = 1;
+            assert(max.mul_div(two, one).is_none());
+            assert(max.mul_div(two, zero).is_none());
+
+            let x: u256 = 1000;
+            return (x.isqrt() + x.leading_zeros() as u256) as u64
+        }
+    "#;
+
+    assert_eq!(run_code(code), Value::U64(31 + 246));
+}
+
+#[test]
+fn test_u256_conversions() {
+    let code = r#"
+        entry main() {
+            let value: u256 = u256::from_string("115792089237316195423570985008687907853269984665640564039457584007913129639935").unwrap();
+            assert(value == u256::MAX.unwrap());
+            assert(u256::from_string("115792089237316195423570985008687907853269984665640564039457584007913129639936").is_none());
+            assert(u256::from_string("12a").is_none());
+            assert(u256::from_string("").is_none());
+
+            let hex: string = value.to_string(16).unwrap();
+            assert(hex.len() == 64);
+            let max_byte: u256 = 255;
+            assert(max_byte.to_string(16).unwrap() == "ff");
+            assert(max_byte.to_string(10).unwrap() == "255");
+            assert(max_byte.to_string(37).is_none());
+
+            // Round trip through the big-endian bytes
+            let bytes: u8[] = max_byte.to_bytes_be();
+            assert(bytes.len() == 32);
+            assert(bytes[31] == 255);
+            assert(u256::from_bytes_be(bytes).unwrap() == max_byte);
+
+            // Shorter inputs are padded with leading zeros
+            let short: u8[] = [1, 0];
+            let too_long: u8[] = [];
+            for i: u32 = 0; i < 33; i += 1 {
+                too_long.push(1);
+            }
+            assert(u256::from_bytes_be(too_long).is_none());
+            return u256::from_bytes_be(short).unwrap() as u64
+        }
+    "#;
+
+    assert_eq!(run_code(code), Value::U64(256));
+}
+
+#[test]
+fn test_integer_formatting() {
+    let code = r#"
+        entry main() {
+            assert(parse_u8("255").unwrap() == 255);
+            assert(parse_u8("256").is_none());
+            assert(parse_u16("65535").unwrap() == 65535);
+            assert(parse_u32("0042").unwrap() == 42);
+            assert(parse_u64("").is_none());
+            assert(parse_u64("+1").is_none());
+            assert(parse_u64("-1").is_none());
+            assert(parse_u64("1_000").is_none());
+            assert(parse_u64(" 1").is_none());
+            assert(parse_u128("340282366920938463463374607431768211455").unwrap() == u128::MAX.unwrap());
+            assert(parse_u256("115792089237316195423570985008687907853269984665640564039457584007913129639936").is_none());
+
+            let value: u64 = 255;
+            assert(value.to_hex() == "ff");
+            assert(value.to_binary() == "11111111");
+            assert(value.to_string_padded(5) == "00255");
+            assert(value.to_string_padded(2) == "255");
+
+            let byte: u8 = 0;
+            assert(byte.to_hex() == "0");
+            assert(byte.to_binary() == "0");
+            assert(u256::MAX.unwrap().to_hex().len() == 64);
+
+            let small: u16 = 7;
+            return small.to_string_padded(3).len() as u64 + parse_u64(small.to_string_padded(3)).unwrap()
+        }
+    "#;
+
+    assert_eq!(run_code(code), Value::U64(10));
+}
+
+#[test]
+fn test_self_reference() {
+    let code = r#"
+        entry main() {
+            let x: u64[][] = [[10]];
+            x.push(x[0]);
+            x[1][0] = 20;
+            return x[0][0]
+        }
+    "#;
+
+    assert_eq!(
+        run_code(code),
+        Value::U64(10)
+    );
+}
+
+#[test]
+fn test_self_reference_struct() {
+    let code = r#"
+        struct Test {
+            x: u64
+        }
+
+        entry main() {
+            let t: Test[] = [Test { x: 10 }];
+            t.push(t.get(0).unwrap());
+            t[1].x = 20;
+            return t[0].x
+        }
+    "#;
+
+    assert_eq!(
+        run_code(code),
+        Value::U64(10)
+    );
+}
+
+#[test]
+fn test_self_reference_map() {
+    let code = r#"
+        struct Dummy {
+            x: u64
+        }
+        entry main() {
+            let x: map<string, Dummy> = {};
+            x.insert("a", Dummy { x: 10 });
+            let dummy: Dummy = x.get("a").unwrap();
+            x.insert("b", dummy);
+            x.get("b").unwrap().x = 20;
+
+            assert(!is_same_ptr(x.get("a").unwrap(), x.get("b").unwrap()));
+
+            return x.get("a").unwrap().x
+        }
+    "#;
+
+    assert_eq!(
+        run_code(code),
+        Value::U64(10)
+    );
+}
+
+#[test]
+fn test_enum() {
+    let code = r#"
+        enum Test {
+            A,
+            B { value: u64 }
+        }
+
+        entry main() {
+            let x: Test = Test::B { value: 10 };
+            return 10
+        }
+    "#;
+
+    assert_eq!(
+        run_code(code),
+        Value::U64(10)
+    );
+}
+
+#[test]
+fn test_array_slice() {
+    let code = r#"
+        entry main() {
+            let x: u64[] = [10, 20, 30, 40, 50];
+            let y: u64[] = x.slice(1..4);
+
+            assert(is_same_ptr(y[0], x[1]));
+            y.push(60);
+            assert(!is_same_ptr(y[3], x[4]));
+
+            y.push(x[4]);
+            assert(!is_same_ptr(y[4], x[4]));
+
+            return y[0] + y[1] + y[2]
+        }
+    "#;
+
+    assert_eq!(
+        run_code(code),
+        Value::U64(90)
+    );
+}
+
+#[test]
+fn test_recursive_call() {
+    let code = r#"
+        fn fib(n: u64) -> u64 {
+            if n == 0 {
+                return 0
+            } else if n == 1 {
+                return 1
+            }
+
+            return fib(n - 1) + fib(n - 2)
+        }
+
+        entry main() {
+            return fib(10)
+        }
+    "#;
+
+    assert_eq!(
+        run_code_id(code, 1),
+        Value::U64(55)
+    );
+}
+
+#[test]
+fn test_validator_recursion() {
+    let code = r#"
+        fn fib(n: u64) -> u64 {
+            if n <= 1 {
+                return n
+            }
+
+            return fib(n - 1) + fib(n - 2)
+        }
+
+        entry main() {
+            return fib(10)
+        }
+    "#;
+
+    let (module, environment) = prepare_module(code);
+    assert!(ModuleValidator::new(&module, &environment).verify().is_ok());
+    let config = ValidatorConfig {
+        allow_recursion: false,
+        ..Default::default()
+    };
+    let result = ModuleValidator::new(&module, &environment).with_config(config).verify();
+    assert!(matches!(result, Err(ValidatorError::RecursiveCall(0))));
+}
+
+#[test]
+fn test_validator_infinite_recursion() {
+    let codes = [
+        "fn f(n: u64) -> u64 { return f(n + 1) }
+        entry main() { return f(0) }",
+        "fn f(n: u64) -> u64 { if n > 10 { return f(n - 1) } return f(n + 1) }
+        entry main() { return f(0) }",
+    ];
+
+    for code in codes {
+        let (module, environment) = prepare_module(code);
+        let result = ModuleValidator::new(&module, &environment).verify();
+        assert!(matches!(result, Err(ValidatorError::InfiniteRecursion(_))), "{:?}", result);
+    }
+}
+
+#[test]
+fn test_validator_call_depth() {
+    let code = r#"
+        fn c() -> u64 { return 1 }
+        fn b() -> u64 { return c() + c() }
+        fn a() -> u64 { return b() }
+
+        entry main() {
+            return a() + c()
+        }
+    "#;
+
+    let (module, environment) = prepare_module(code);
+    let config = |max_call_depth| ValidatorConfig {
+        max_call_depth,
+        ..Default::default()
+    };
+    assert!(ModuleValidator::new(&module, &environment).with_config(config(4)).verify().is_ok());
+    let result = ModuleValidator::new(&module, &environment).with_config(config(3)).verify();
+    assert!(matches!(result, Err(ValidatorError::CallDepthExceeded(4, 3))));
+
+    // The depth checked by the validator is known before submitting the module
+    let stats = module.stats().unwrap();
+    assert_eq!(stats.max_call_depth(), Some(4));
+    assert_eq!(stats.chunks[0].max_call_depth, Some(1));
+    assert_eq!(stats.chunks[1].callees, 1);
+}
+
+#[test]
+fn test_strict_determinism() {
+    let code = r#"
+        entry main() {
+            let a: u64[] = [1, 2];
+            if is_same_ptr(a, a) {
+                return 1
+            }
+            return 0
+        }
+    "#;
+
+    let (module, environment) = prepare_module(code);
+    assert!(ModuleValidator::new(&module, &environment).verify().is_ok());
+    let config = ValidatorConfig {
+        strict_determinism: true,
+        ..Default::default()
+    };
+    let result = ModuleValidator::new(&module, &environment).with_config(config.clone()).verify();
+    assert!(matches!(result, Err(ValidatorError::NonDeterministicOperation(0, _))), "{:?}", result);
+
+    let run = |strict_determinism| {
+        let mut vm = VM::with_config(&module, &environment, VMConfig { strict_determinism, ..Default::default() });
+        vm.invoke_entry_chunk(0).unwrap();
+        vm.run()
+    };
+    assert!(run(false).is_ok());
+    let err = run(true).unwrap_err();
+    assert!(matches!(err.inner(), VMError::NonDeterministicOperation(_)), "{}", err);
+
+    // The other natives are accepted
+    let (module, environment) = prepare_module("entry main() { let a: u64[] = [1, 2]; return a.len() as u64 }");
+    assert!(ModuleValidator::new(&module, &environment).with_config(config).verify().is_ok());
+}
+
+// Is the code accepted by the validator with the strict determinism
+fn is_strictly_deterministic(code: &str) -> bool {
+    let (module, environment) = prepare_module(code);
+    let config = ValidatorConfig {
+        strict_determinism: true,
+        ..Default::default()
+    };
+
+    match ModuleValidator::new(&module, &environment).with_config(config).verify() {
+        Ok(()) => true,
+        Err(ValidatorError::NonDeterministicOperation(..)) => false,
+        Err(e) => panic!("{:?}", e)
+    }
+}
+
+#[test]
+fn test_strict_determinism_map_iteration() {
+    // The maps of the VM keep their insertion order
+    assert!(is_strictly_deterministic(r#"
+        entry main() {
+            let m: map<string, u64> = { "b": 2, "a": 1 };
+            let r: u64 = 0;
+            foreach (k, v) in m {
+                r = (r * 10) + v;
+            }
+            foreach e in m.entries() {
+                r += e.1;
+            }
+            return r + m.keys().len() as u64 + m.values().len() as u64
+        }
+    "#));
+
+    // The keys of the storage are in the order of the host
+    assert!(!is_strictly_deterministic(r#"
+        entry main() {
+            let count: u64 = 0;
+            foreach key in Storage::keys("user:") {
+                count += 1;
+            }
+            return count
+        }
+    "#));
+}
+
+#[test]
+fn test_strict_determinism_time() {
+    for native in ["timestamp()", "block_height()", "block_hash()[0] as u64", "tx_hash()[0] as u64"] {
+        let code = format!("entry main() {{ return chain::{} }}", native);
+        assert!(!is_strictly_deterministic(&code), "{}", native);
+    }
+}
+
+#[test]
+fn test_strict_determinism_casts() {
+    // No float type, every cast is an integer truncation or a formatting
+    assert!(is_strictly_deterministic(r#"
+        entry main() {
+            let a: u256 = 1000;
+            let s: string = a as string;
+            let b: u8 = a as u8;
+            let c: u128 = (b as u16 as u32 as u64) as u128;
+            return (c as u64) + s.len() as u64
+        }
+    "#));
+}
+
+#[test]
+fn test_strict_determinism_serialization() {
+    // The values are written field by field with explicit lengths, without any padding
+    assert!(is_strictly_deterministic(r#"
+        entry main() {
+            let value: u256 = 255;
+            let bytes: u8[] = value.to_bytes_be();
+            let json: string = json_stringify([1, 2, 3]);
+            Storage::set("bytes", bytes);
+            emit("serialized", json);
+            return hex_encode(bytes).len() as u64
+        }
+    "#));
+}
+
+#[test]
+fn test_module_features() {
+    let code = r#"
+        entry main() {
+            let a: u64 = 10;
+            return (a +| 5)
+        }
+    "#;
+
+    let (module, environment) = prepare_module(code);
+    assert!(module.features().contains(ModuleFeatures::CHECKED_ARITHMETIC));
+    assert_eq!(module.version(), ModuleVersion::CURRENT);
+    assert!(ModuleValidator::new(&module, &environment).verify().is_ok());
+
+    // A node which doesn't support the feature rejects the module
+    let verify = |module: &Module, config: ValidatorConfig| ModuleValidator::new(module, &environment).with_config(config).verify().map_err(|e| e.to_string());
+    let config = ValidatorConfig {
+        supported_features: ModuleFeatures::ALL.difference(ModuleFeatures::CHECKED_ARITHMETIC),
+        ..Default::default()
+    };
+    assert_eq!(verify(&module, config), Err(ValidatorError::UnsupportedFeatures(ModuleFeatures::CHECKED_ARITHMETIC).to_string()));
+
+    // Unknown features are rejected too
+    let mut newer = module.clone();
+    newer.set_features(ModuleFeatures::from_bits(1 << 40).union(module.features()));
+    assert_eq!(verify(&newer, ValidatorConfig::default()), Err(ValidatorError::UnsupportedFeatures(ModuleFeatures::from_bits(1 << 40)).to_string()));
+
+    // The instructions can't use a feature not declared
+    let mut undeclared = module.clone();
+    undeclared.set_features(ModuleFeatures::NONE);
+    assert_eq!(verify(&undeclared, ValidatorConfig::default()), Err(ValidatorError::UndeclaredFeature(0, OpCode::SaturatingAdd).to_string()));
+
+    // Only the same major version up to the supported one
+    let mut newer = module.clone();
+    newer.set_version(ModuleVersion::new(1, 1, 0));
+    assert_eq!(verify(&newer, ValidatorConfig::default()), Err(ValidatorError::UnsupportedVersion(ModuleVersion::new(1, 1, 0)).to_string()));
+    let config = ValidatorConfig {
+        supported_version: ModuleVersion::new(1, 1, 0),
+        ..Default::default()
+    };
+    assert!(verify(&newer, config.clone()).is_ok());
+    newer.set_version(ModuleVersion::new(2, 0, 0));
+    assert!(verify(&newer, config).is_err());
+}
+
+#[test]
+fn test_const() {
+    let code = r#"
+        const X: u64 = 10;
+
+        entry main() {
+            return X
+        }
+    "#;
+
+    assert_eq!(
+        run_code(code),
+        Value::U64(10)
+    );
+}
+
+#[test]
+fn test_const_add() {
+    let code = r#"
+        const ZERO: u64 = 0
+        const HELLO_WORLD: string = "Hello World"
+
+        entry main() {
+            let message: string = HELLO_WORLD + " " + ZERO
+            return message.len() as u64
+        }
+    "#;
+
+    assert_eq!(
+        run_code(code),
+        Value::U64(13)
+    );
+}
+
+#[test]
+fn test_optional_cast() {
+    let code = r#"
+        struct Test { value: u64 }
+        entry main() {
+            let x: optional<Test> = Test { value: 10 };
+            let y: optional<u64> = x.unwrap().value;
+            let v: u64 = y.unwrap();
+            return v
+        }
+    "#;
+
+    assert_eq!(
+        run_code(code),
+        Value::U64(10)
+    );
+}
+
+
+#[test]
+fn test_optional_unwrap_or() {
+    let code = r#"
+        entry main() {
+            let x: optional<u8> = null;
+            return x.unwrap_or(10) as u64
+        }
+    "#;
+
+    assert_eq!(
+        run_code(code),
+        Value::U64(10)
+    );
+}
+
+#[test]
+fn test_div_by_zero() {
+    let code = r#"
+        entry main() {
+            let x: u64 = 10;
+            let y: u64 = 0;
+            return x / y
+        }
+    "#;
 
     assert!(
         matches!(
-            try_run_code(code, 0),
+            try_run_code(code, 0).as_ref().map_err(VMError::inner),
             Err(VMError::DivisionByZero)
         )
     );
 }
 
 #[test]
-fn test_path() {
+fn test_path() {
+    let code = r#"
+        struct Value {
+            value: string
+        }
+
+        fn (v Value) my_value() -> string {
+            return v.value
+        }
+
+        struct Message {
+            value: Value
+        }
+
+        fn (m Message) to_string() -> string {
+            return m.value.value
+        }
+
+        entry main() {
+            let message: Message = Message { value: Value { value: "Hello World!" } }
+            assert(message.to_string() == "Hello World!")
+            message.value.value += " from path"
+            assert(message.to_string() == "Hello World! from path")
+            return 0
+        }
+    "#;
+
+    assert_eq!(
+        run_code_id(code, 2),
+        Value::U64(0)
+    );
+}
+#[test]
+fn test_dangling_value_before_loop() {
+    let code = r#"
+        entry main() {
+            let a: u64 = 5;
+            a;
+            let sum: u64 = 0;
+            foreach i in [1u8, 2u8] {
+                sum += i as u64;
+            }
+            return sum
+        }
+    "#;
+
+    assert_eq!(run_code(code), Value::U64(3));
+}
+
+#[test]
+fn test_full_optimizations() {
+    let code = r#"
+        entry main() {
+            let a: u64 = 5;
+            a;
+            let sum: u64 = 0;
+            foreach i in [1u8, 2u8] {
+                if i == 2 {
+                    return sum + i as u64
+                }
+                sum += 10u8 as u64;
+            }
+            return 0
+        }
+    "#;
+
+    let tokens = Lexer::new(code).get().unwrap();
+    let env = EnvironmentBuilder::default();
+    let (program, _) = Parser::new(tokens, &env).parse().unwrap();
+
+    let env = env.build();
+    let module = Compiler::new(&program, &env)
+        .with_optimizations(OptimizationLevel::Full)
+        .compile()
+        .unwrap();
+
+    assert_eq!(run_internal(module, &env, 0).unwrap(), Value::U64(12));
+}
+
+#[derive(Default)]
+struct MemoryStorage(std::collections::BTreeMap<Vec<u8>, Vec<u8>>);
+
+impl StorageProvider for MemoryStorage {
+    fn get(&self, key: &[u8]) -> Result<Option<Vec<u8>>, anyhow::Error> {
+        Ok(self.0.get(key).cloned())
+    }
+
+    fn set(&mut self, key: &[u8], value: Vec<u8>) -> Result<(), anyhow::Error> {
+        self.0.insert(key.to_vec(), value);
+        Ok(())
+    }
+
+    fn delete(&mut self, key: &[u8]) -> Result<bool, anyhow::Error> {
+        Ok(self.0.remove(key).is_some())
+    }
+
+    fn iterate_prefix<'a>(&'a self, prefix: &[u8]) -> Result<StorageIterator<'a>, anyhow::Error> {
+        let prefix = prefix.to_vec();
+        Ok(Box::new(self.0.iter()
+            .filter(move |(k, _)| k.starts_with(&prefix))
+            .map(|(k, v)| (k.clone(), v.clone()))
+        ))
+    }
+}
+
+#[test]
+fn test_storage() {
+    let code = r#"
+        entry main() {
+            let count: u64 = Storage::get("counter").unwrap_or(0);
+            Storage::set("counter", count + 1);
+
+            let values: u8[] = [1, 2, 3];
+            Storage::set("user:values", values);
+            Storage::set("user:name", "xelis");
+            assert(Storage::has("user:name"));
+
+            let keys: u32 = 0;
+            foreach key in Storage::keys("user:") {
+                assert(key.starts_with("user:"));
+                keys += 1;
+            }
+            assert(keys == 2);
+
+            let stored: u8[] = Storage::get("user:values").unwrap();
+            assert(stored.len() == 3);
+
+            assert(Storage::delete("user:name"));
+            assert(!Storage::delete("user:name"));
+            assert(Storage::get("missing").is_none());
+
+            return count
+        }
+
+        entry counter() {
+            return Storage::get("counter").unwrap()
+        }
+    "#;
+
+    let (module, environment) = prepare_module(code);
+    let mut storage = MemoryStorage::default();
+    for expected in 0..3 {
+        let mut vm = VM::new(&module, &environment);
+        vm.context_mut().set_storage(&mut storage);
+        vm.invoke_entry_chunk(0).unwrap();
+        assert_eq!(vm.run().unwrap(), ExecutionOutcome::Success(Value::U64(expected).into()));
+        // The deleted entry is refunded
+        assert_eq!(vm.context().gas_refund(), 100);
+        assert!(vm.context().effective_gas_usage() < vm.context().current_gas_usage());
+    }
+
+    // Values are persisted using their encoding
+    let counter = storage.0.get(b"counter".as_slice()).unwrap();
+    assert_eq!(ValueCell::from_bytes(counter, 16).unwrap(), Value::U64(3).into());
+    assert!(storage.0.contains_key(b"user:values".as_slice()));
+    assert!(!storage.0.contains_key(b"user:name".as_slice()));
+
+    // A read-only execution can read the storage but not write in it
+    let mut vm = VM::new(&module, &environment);
+    vm.context_mut().set_storage(&mut storage);
+    vm.context_mut().set_read_only(true);
+    vm.invoke_entry_chunk(1).unwrap();
+    assert_eq!(vm.run().unwrap(), ExecutionOutcome::Success(Value::U64(3).into()));
+
+    let mut vm = VM::new(&module, &environment);
+    vm.context_mut().set_storage(&mut storage);
+    vm.context_mut().set_read_only(true);
+    vm.invoke_entry_chunk(0).unwrap();
+    let err = vm.run().unwrap_err();
+    assert!(matches!(err.inner(), VMError::EnvironmentError(EnvironmentError::ReadOnlyViolation)), "{}", err);
+    assert_eq!(ValueCell::from_bytes(storage.0.get(b"counter".as_slice()).unwrap(), 16).unwrap(), Value::U64(3).into());
+
+    let mut vm = VM::new(&module, &environment);
+    vm.invoke_entry_chunk(0).unwrap();
+    let err = vm.run().unwrap_err();
+    assert!(matches!(err.inner(), VMError::EnvironmentError(EnvironmentError::MissingStorage)));
+}
+
+#[test]
+fn test_gas_introspection() {
+    let code = r#"
+        entry main() {
+            let before: u64 = gas_used();
+            let remaining: u64 = gas_remaining();
+            assert((gas_used() - before) > 0);
+            assert((remaining + gas_used()) > 1000);
+            return remaining
+        }
+    "#;
+
+    let (module, environment) = prepare_module(code);
+    let mut vm = VM::new(&module, &environment);
+    vm.context_mut().set_gas_limit(1000);
+    vm.invoke_entry_chunk(0).unwrap();
+    let remaining = vm.run().unwrap().into_result().unwrap().into_value().unwrap().as_u64().unwrap();
+    assert!(remaining < 1000);
+    assert!(vm.context().remaining_gas() < remaining);
+}
+
+#[test]
+fn test_revert() {
+    let code = r#"
+        fn reset() -> u64 {
+            Storage::delete("balance");
+            revert(0);
+            return 0
+        }
+
+        fn transfer(amount: u64) -> u64 {
+            Storage::set("balance", amount);
+            emit("transfer", amount);
+            if amount > 100 {
+                revert(amount);
+            }
+            return amount
+        }
+
+        entry main(amount: u64) {
+            return transfer(amount)
+        }
+    "#;
+
+    let (module, environment) = prepare_module(code);
+    let mut storage = MemoryStorage::default();
+    let balance = |storage: &MemoryStorage| storage.0.get(b"balance".as_slice())
+        .map(|v| ValueCell::from_bytes(v, 16).unwrap());
+
+    let mut vm = VM::new(&module, &environment);
+    vm.context_mut().set_storage(&mut storage);
+    vm.invoke_entry_chunk_with_args(2, [ValueCell::from(Value::U64(50))].into_iter()).unwrap();
+    assert_eq!(vm.run().unwrap(), ExecutionOutcome::Success(Value::U64(50).into()));
+    assert_eq!(vm.context_mut().take_events().len(), 1);
+
+    // The storage write and the event are rolled back, the gas is still consumed
+    vm.invoke_entry_chunk_with_args(2, [ValueCell::from(Value::U64(500))].into_iter()).unwrap();
+    let outcome = vm.run().unwrap();
+    assert_eq!(outcome, ExecutionOutcome::Reverted(Value::U64(500).into()));
+    assert!(!outcome.is_success());
+    assert!(vm.context().events().is_empty());
+    assert!(vm.context().current_gas_usage() > 0);
+    assert!(!vm.has_pending_execution());
+    assert_eq!(vm.get_stack().count(), 0);
+
+    // The state before the first slice is restored
+    vm.invoke_entry_chunk_with_args(2, [ValueCell::from(Value::U64(200))].into_iter()).unwrap();
+    assert!(matches!(vm.run_for(5).unwrap(), RunResult::Paused));
+    assert_eq!(vm.resume().unwrap(), ExecutionOutcome::Reverted(Value::U64(200).into()));
+
+    // A caught revert only rolls back the invoked chunk
+    assert_eq!(vm.invoke_catching(0).unwrap(), Err(Value::U64(0).into()));
+    assert_eq!(vm.context().gas_refund(), 0);
+    drop(vm);
+
+    assert_eq!(balance(&storage), Some(Value::U64(50).into()));
+
+    // A typed invocation reports the revert as an error
+    let mut vm = VM::new(&module, &environment);
+    vm.context_mut().set_storage(&mut storage);
+    let err = vm.invoke_entry::<u64>("main", vec![Value::U64(101).into()]).unwrap_err();
+    assert!(matches!(err, VMError::Reverted(ref value) if *value == Value::U64(101).into()), "{}", err);
+    drop(vm);
+
+    assert_eq!(balance(&storage), Some(Value::U64(50).into()));
+}
+
+#[test]
+fn test_storage_checkpoints() {
+    let code = r#"
+        fn write(value: u64) -> u64 {
+            Storage::set("value", value);
+            if value == 0 {
+                panic("zero");
+            }
+            return value
+        }
+
+        fn fail() -> u64 {
+            return write(0)
+        }
+
+        fn succeed() -> u64 {
+            return write(7)
+        }
+
+        entry main(value: u64) {
+            write(value);
+            let total: u64 = 10;
+            if value > 100 {
+                revert(value);
+            }
+            return total / value
+        }
+    "#;
+
+    let (module, environment) = prepare_module(code);
+    let mut storage = MemoryStorage::default();
+    let stored = |storage: &MemoryStorage| storage.0.get(b"value".as_slice())
+        .map(|v| ValueCell::from_bytes(v, 16).unwrap());
+
+    let mut vm = VM::new(&module, &environment);
+    vm.context_mut().set_storage(&mut storage);
+    vm.invoke_entry_chunk_with_args(3, [ValueCell::from(Value::U64(5))].into_iter()).unwrap();
+    assert_eq!(vm.run().unwrap(), ExecutionOutcome::Success(Value::U64(2).into()));
+    assert_eq!(vm.context().checkpoints(), 0);
+
+    // A failed execution doesn't keep its writes
+    vm.invoke_entry_chunk_with_args(3, [ValueCell::from(Value::U64(0))].into_iter()).unwrap();
+    assert!(vm.run().is_err());
+    assert_eq!(vm.context().checkpoints(), 0);
+    drop(vm);
+    assert_eq!(stored(&storage), Some(Value::U64(5).into()));
+
+    // A caught panic only rolls back the writes of the invoked chunk
+    let mut vm = VM::new(&module, &environment);
+    vm.context_mut().set_storage(&mut storage);
+    vm.invoke_entry_chunk_with_args(3, [ValueCell::from(Value::U64(200))].into_iter()).unwrap();
+    let written = |vm: &mut VM| vm.context_mut().storage_mut().unwrap().get(b"value").unwrap()
+        .map(|v| ValueCell::from_bytes(&v, 16).unwrap());
+
+    // Paused once the value is written
+    while written(&mut vm) != Some(Value::U64(200).into()) {
+        assert!(matches!(vm.run_for(1).unwrap(), RunResult::Paused));
+    }
+    assert_eq!(vm.context().checkpoints(), 1);
+
+    assert_eq!(vm.invoke_catching(1).unwrap(), Err(Value::String("zero".to_owned()).into()));
+    assert_eq!(written(&mut vm), Some(Value::U64(200).into()));
+    assert_eq!(vm.invoke_catching(2).unwrap(), Ok(Value::U64(7).into()));
+    assert_eq!(written(&mut vm), Some(Value::U64(7).into()));
+    assert_eq!(vm.context().checkpoints(), 1);
+
+    // The committed sub execution is rolled back with the execution reverting
+    assert_eq!(vm.resume().unwrap(), ExecutionOutcome::Reverted(Value::U64(200).into()));
+    assert_eq!(vm.context().checkpoints(), 0);
+    drop(vm);
+    assert_eq!(stored(&storage), Some(Value::U64(5).into()));
+}
+
+#[test]
+fn test_events() {
+    let code = r#"
+        struct Transfer {
+            to: string,
+            amount: u64
+        }
+
+        entry main() {
+            let transfer: Transfer = Transfer { to: "alice", amount: 10 };
+            emit("transfer", transfer);
+            transfer.amount = 20;
+
+            let values: u8[] = [1, 2, 3];
+            emit("values", values);
+            return 0
+        }
+    "#;
+
+    let (module, environment) = prepare_module(code);
+    let mut vm = VM::new(&module, &environment);
+    vm.invoke_entry_chunk(0).unwrap();
+    vm.run().unwrap();
+
+    let events = vm.context_mut().take_events();
+    assert_eq!(events.len(), 2);
+    assert!(vm.context().events().is_empty());
+
+    // The payload is a copy made at the emission
+    assert_eq!(events[0].name, "transfer");
+    match &events[0].data {
+        ValueCell::Struct(fields, _) => {
+            assert_eq!(*fields[0].borrow(), Value::String("alice".to_owned()).into());
+            assert_eq!(*fields[1].borrow(), Value::U64(10).into());
+        },
+        v => panic!("expected a struct, got {:?}", v)
+    }
+    assert_eq!(events[1].name, "values");
+    assert_eq!(events[1].size(), "values".len() + 3);
+
+    // Limits
+    let mut vm = VM::new(&module, &environment);
+    vm.context_mut().set_max_events(1);
+    vm.invoke_entry_chunk(0).unwrap();
+    let err = vm.run().unwrap_err();
+    assert!(matches!(err.inner(), VMError::EnvironmentError(EnvironmentError::EventsLimitReached)));
+
+    let mut vm = VM::new(&module, &environment);
+    vm.context_mut().set_max_events_size(16);
+    vm.invoke_entry_chunk(0).unwrap();
+    let err = vm.run().unwrap_err();
+    assert!(matches!(err.inner(), VMError::EnvironmentError(EnvironmentError::EventsLimitReached)));
+}
+
+#[test]
+fn test_invoke_entry() {
+    let code = r#"
+        struct Point {
+            x: u64,
+            y: u64
+        }
+
+        entry sum(base: u64, points: Point[], label: optional<string>) {
+            let total: u64 = base;
+            foreach point in points {
+                total += point.x * point.y
+            }
+
+            if label.is_some() {
+                total += 1000
+            }
+            return total
+        }
+
+        entry zero() {
+            return 0
+        }
+    "#;
+
+    let (module, environment) = prepare_module(code);
+    let point_type = module.structs()[0].clone();
+    let point = |x: u64, y: u64| Constant::Struct(vec![Value::U64(x).into(), Value::U64(y).into()], point_type.clone());
+    let params = vec![
+        Value::U64(5).into(),
+        Constant::Array(vec![point(2, 3), point(4, 5)]),
+        Constant::Optional(None),
+    ];
+
+    let mut vm = VM::new(&module, &environment);
+    let total: u64 = vm.invoke_entry("sum", params.clone()).unwrap();
+    assert_eq!(total, 5 + 6 + 20);
+
+    let mut vm = VM::new(&module, &environment);
+    let mut labeled = params.clone();
+    labeled[2] = Constant::Optional(Some(Box::new(Value::String("label".to_owned()).into())));
+    let total: Constant = vm.invoke_entry(0, labeled).unwrap();
+    assert_eq!(total, Value::U64(1031).into());
+
+    let mut vm = VM::new(&module, &environment);
+    assert_eq!(vm.invoke_entry::<u64>("zero", Vec::new()).unwrap(), 0);
+
+    let mut vm = VM::new(&module, &environment);
+    let err = vm.invoke_entry::<u64>("missing", Vec::new()).unwrap_err();
+    assert!(matches!(err, VMError::EntryNotFound(ref name) if name == "missing"), "{}", err);
+
+    let err = vm.invoke_entry::<u64>("sum", Vec::new()).unwrap_err();
+    assert!(matches!(err, VMError::InvalidEntryParamsCount(3, 0)), "{}", err);
+
+    let mut invalid = params;
+    invalid[1] = Constant::Array(vec![Value::U64(1).into()]);
+    let err = vm.invoke_entry::<u64>("sum", invalid).unwrap_err();
+    assert!(matches!(err, VMError::InvalidEntryParams(1, _)), "{}", err);
+    assert!(!vm.has_pending_execution());
+}
+
+#[test]
+fn test_abi() {
+    let code = r#"
+        struct Transfer {
+            to: string,
+            amount: u64
+        }
+
+        fn total(amounts: u64[]) -> u64 {
+            let sum: u64 = 0;
+            foreach amount in amounts {
+                sum += amount
+            }
+            return sum
+        }
+
+        entry transfer(to: string, amounts: u64[]) {
+            let transfer: Transfer = Transfer { to: to, amount: total(amounts) };
+            emit("transfer", transfer);
+            emit("count", amounts.len());
+            return 0
+        }
+
+        entry noop() {
+            return 0
+        }
+    "#;
+
+    let (module, environment) = prepare_module(code);
+    // The ABI is kept in the serialized module
+    let module = Module::from_bytes(&module.to_bytes().unwrap()).unwrap();
+    let abi = module.generate_abi(&environment);
+
+    let transfer_id = module.structs()[0].id();
+    assert_eq!(abi.entries, vec![
+        AbiEntry {
+            name: "transfer".to_owned(),
+            chunk_id: 1,
+            parameters: vec![
+                AbiParameter { name: "to".to_owned(), _type: AbiType::String },
+                AbiParameter { name: "amounts".to_owned(), _type: AbiType::Array(Box::new(AbiType::U64)) },
+            ],
+            return_type: AbiType::U64,
+        },
+        AbiEntry {
+            name: "noop".to_owned(),
+            chunk_id: 2,
+            parameters: Vec::new(),
+            return_type: AbiType::U64,
+        },
+    ]);
+    assert_eq!(abi.structs, vec![AbiStruct { id: transfer_id, fields: vec![AbiType::String, AbiType::U64], builtin: false }]);
+    assert_eq!(abi.events, vec![
+        AbiEvent { name: "transfer".to_owned(), data: AbiType::Struct(transfer_id) },
+        AbiEvent { name: "count".to_owned(), data: AbiType::U32 },
+    ]);
+}
+
+#[test]
+fn test_external_call() {
+    let code = r#"
+        fn double(v: u64) -> u64 {
+            return v * 2
+        }
+
+        entry add(a: u64, b: u64) {
+            return double(a) + b
+        }
+    "#;
+    let (library, environment) = prepare_module(code);
+
+    // Caller built by hand as the language has no syntax for it
+    let build = |module_name: &str, export: &str| {
+        let mut module = Module::new();
+        let name = module.add_constant(Value::String(module_name.to_owned()));
+        let export = module.add_constant(Value::String(export.to_owned()));
+        let a = module.add_constant(Value::U64(20));
+        let b = module.add_constant(Value::U64(2));
+
+        let mut chunk = Chunk::new();
+        chunk.emit_opcode(OpCode::Constant);
+        chunk.write_u16(a as u16);
+        chunk.emit_opcode(OpCode::Constant);
+        chunk.write_u16(b as u16);
+        chunk.emit_opcode(OpCode::ExternalCall);
+        chunk.write_u16(name as u16);
+        chunk.write_u16(export as u16);
+        chunk.write_u8(2);
+        chunk.emit_opcode(OpCode::Return);
+        module.add_entry_chunk(chunk);
+        module
+    };
+
+    let run = |module: &Module, gas: Option<u64>| {
+        let mut vm = VM::new(module, &environment);
+        vm.link_module("lib", &library);
+        if let Some(gas) = gas {
+            vm.context_mut().set_gas_limit(gas);
+        }
+        vm.invoke_entry_chunk(0).unwrap();
+        vm.run()
+    };
+
+    // The chunks called by the library are resolved in the library
+    let caller = build("lib", "add");
+    assert_eq!(run(&caller, None).unwrap(), ExecutionOutcome::Success(Value::U64(42).into()));
+
+    let err = run(&build("unknown", "add"), None).unwrap_err();
+    assert!(matches!(err.inner(), VMError::ModuleNotFound(name) if name == "unknown"));
+
+    let err = run(&build("lib", "double"), None).unwrap_err();
+    assert!(matches!(err.inner(), VMError::ExportNotFound(name) if name == "double"));
+
+    // The gas is shared by both modules
+    let err = run(&caller, Some(20)).unwrap_err();
+    assert!(matches!(err.inner(), VMError::NotEnoughGas { .. }));
+
+    // The chunks of an iterator would be resolved in the caller
+    let code = r#"
+        fn is_even(v: u64) -> bool {
+            return (v % 2) == 0
+        }
+
+        pub fn evens() -> Iterator<u64> {
+            let numbers: range<u64> = 0..10;
+            return numbers.iter().filter(is_even)
+        }
+    "#;
+    let (library, environment) = prepare_module(code);
+    let mut caller = Module::new();
+    let name = caller.add_constant(Value::String("lib".to_owned()));
+    let export = caller.add_constant(Value::String("evens".to_owned()));
+
+    let mut chunk = Chunk::new();
+    chunk.emit_opcode(OpCode::ExternalCall);
+    chunk.write_u16(name as u16);
+    chunk.write_u16(export as u16);
+    chunk.write_u8(0);
+    chunk.emit_opcode(OpCode::Return);
+    caller.add_entry_chunk(chunk);
+
+    let mut vm = VM::new(&caller, &environment);
+    vm.link_module("lib", &library);
+    vm.invoke_entry_chunk(0).unwrap();
+    let err = vm.run().unwrap_err();
+    assert!(matches!(err.inner(), VMError::IteratorCallbacksCrossModule), "{}", err);
+}
+
+#[test]
+fn test_debugger_linked_module() {
+    let code = r#"
+        fn double(v: u64) -> u64 {
+            return v * 2
+        }
+
+        entry add(a: u64, b: u64) {
+            return double(a) + b
+        }
+    "#;
+    let (library, environment) = prepare_module(code);
+
+    let mut caller = Module::new();
+    let name = caller.add_constant(Value::String("lib".to_owned()));
+    let export = caller.add_constant(Value::String("add".to_owned()));
+    let a = caller.add_constant(Value::U64(20));
+
+    let mut chunk = Chunk::new();
+    chunk.emit_opcode(OpCode::Constant);
+    chunk.write_u16(a as u16);
+    chunk.emit_opcode(OpCode::Constant);
+    chunk.write_u16(a as u16);
+    chunk.emit_opcode(OpCode::ExternalCall);
+    chunk.write_u16(name as u16);
+    chunk.write_u16(export as u16);
+    chunk.write_u8(2);
+    chunk.emit_opcode(OpCode::Return);
+    caller.add_entry_chunk(chunk);
+
+    let debugger = || {
+        let mut vm = VM::new(&caller, &environment);
+        vm.link_module("lib", &library);
+        vm.invoke_entry_chunk(0).unwrap();
+        Debugger::new(vm)
+    };
+
+    // The chunk 0 of the library doesn't trigger the breakpoint set in the caller
+    let mut debugger_caller = debugger();
+    assert!(debugger_caller.set_breakpoint(None, 0, 0));
+    match debugger_caller.resume().unwrap() {
+        DebugState::Finished(value) => assert_eq!(value, Value::U64(60).into()),
+        state => panic!("unexpected state: {:?}", state)
+    }
+
+    let mut debugger = debugger();
+    assert!(debugger.set_breakpoint(Some("lib"), 0, 0));
+    let frame = match debugger.resume().unwrap() {
+        DebugState::Breakpoint(frame) => frame,
+        state => panic!("unexpected state: {:?}", state)
+    };
+    assert_eq!(frame, StackFrame { module: Some("lib".to_owned()), chunk_id: 0, offset: 0 });
+
+    let frames = debugger.stack_frames();
+    assert_eq!(frames.len(), 3);
+    assert_eq!(frames[1].module.as_deref(), Some("lib"));
+    assert_eq!(frames[2], StackFrame { module: None, chunk_id: 0, offset: 12 });
+
+    // Decoded with the constants of the library
+    let expected = Disassembler::new(&library)
+        .disassemble_instructions(0, library.get_chunk_at(0).unwrap())
+        .unwrap()
+        .remove(0);
+    assert_eq!(debugger.current_instruction(), Some(expected));
+}
+
+#[test]
+fn test_contract_identity() {
+    struct Authorizer {
+        permission: CallPermission,
+        requests: Vec<(Option<String>, String, String, bool)>,
+    }
+
+    impl CallAuthorizer for Authorizer {
+        fn authorize(&mut self, request: CallRequest) -> CallPermission {
+            self.requests.push((request.caller.map(str::to_owned), request.callee.to_owned(), request.export.to_owned(), request.read_only));
+            self.permission
+        }
+    }
+
+    let code = r#"
+        pub fn whoami() -> string {
+            return caller() + ">" + current_contract()
+        }
+
+        pub fn notify() -> u64 {
+            emit("notified", 1);
+            return 1
+        }
+
+        entry who() {
+            return caller().len() as u64
+        }
+    "#;
+    let (library, environment) = prepare_module(code);
+
+    let build = |export: &str| {
+        let mut module = Module::new();
+        let name = module.add_constant(Value::String("lib".to_owned()));
+        let export = module.add_constant(Value::String(export.to_owned()));
+
+        let mut chunk = Chunk::new();
+        chunk.emit_opcode(OpCode::ExternalCall);
+        chunk.write_u16(name as u16);
+        chunk.write_u16(export as u16);
+        chunk.write_u8(0);
+        chunk.emit_opcode(OpCode::Return);
+        module.add_entry_chunk(chunk);
+        module
+    };
+
+    let whoami = build("whoami");
+    let notify = build("notify");
+    let run = |module: &Module, authorizer: Option<&mut Authorizer>| {
+        let mut vm = VM::new(module, &environment);
+        vm.link_module("lib", &library);
+        vm.context_mut().set_contract("main");
+        vm.context_mut().set_caller("alice");
+        if let Some(authorizer) = authorizer {
+            vm.context_mut().set_call_authorizer(authorizer);
+        }
+
+        vm.invoke_entry_chunk(0).unwrap();
+        let result = vm.run();
+        assert_eq!(vm.context().call_depth(), 0);
+        assert!(!vm.context().is_read_only());
+        result
+    };
+
+    // The callee sees the calling contract as its caller
+    assert_eq!(run(&whoami, None).unwrap(), ExecutionOutcome::Success(Value::String("main>lib".to_owned()).into()));
+
+    let mut authorizer = Authorizer { permission: CallPermission::Allowed, requests: Vec::new() };
+    assert!(run(&notify, Some(&mut authorizer)).is_ok());
+    assert_eq!(authorizer.requests, vec![(Some("main".to_owned()), "lib".to_owned(), "notify".to_owned(), false)]);
+
+    // The host can restrict or deny the calls
+    authorizer.permission = CallPermission::ReadOnly;
+    assert!(run(&whoami, Some(&mut authorizer)).is_ok());
+    let err = run(&notify, Some(&mut authorizer)).unwrap_err();
+    assert!(matches!(err.inner(), VMError::EnvironmentError(EnvironmentError::ReadOnlyViolation)), "{}", err);
+
+    authorizer.permission = CallPermission::Denied;
+    let err = run(&whoami, Some(&mut authorizer)).unwrap_err();
+    assert!(matches!(err.inner(), VMError::EnvironmentError(EnvironmentError::CallDenied(name)) if name == "lib"), "{}", err);
+
+    // The contract invoked by the host has the caller given by the host
+    let mut vm = VM::new(&library, &environment);
+    vm.context_mut().set_caller("alice");
+    assert_eq!(vm.invoke_entry::<u64>("who", Vec::new()).unwrap(), "alice".len() as u64);
+
+    let mut vm = VM::new(&library, &environment);
+    let err = vm.invoke_entry::<u64>("who", Vec::new()).unwrap_err();
+    assert!(matches!(err.inner(), VMError::EnvironmentError(EnvironmentError::MissingCaller)), "{}", err);
+}
+
+#[test]
+fn test_public_function() {
+    let code = r#"
+        pub fn scale(value: u64, factor: u64) -> u64 {
+            return value * factor
+        }
+
+        fn hidden() -> u64 {
+            return 1
+        }
+
+        entry main() {
+            return scale(2, 3) + hidden()
+        }
+    "#;
+    let (library, environment) = prepare_module(code);
+
+    // Public functions are exported with their signature but are not entries
+    assert_eq!(library.get_export("scale"), Some(0));
+    assert_eq!(library.get_export("hidden"), None);
+    assert_eq!(library.entry_by_name("scale"), None);
+    assert_eq!(library.entry_by_name("main"), Some(2));
+    assert_eq!(library.get_signature(0), Some(&ChunkSignature::new(
+        vec![("value".to_owned(), Type::U64), ("factor".to_owned(), Type::U64)],
+        Some(Type::U64)
+    )));
+    assert_eq!(library.get_signature(2), Some(&ChunkSignature::new(Vec::new(), Some(Type::U64))));
+
+    let library = Module::from_bytes(&library.to_bytes().unwrap()).unwrap();
+    assert_eq!(library.get_signature(0).and_then(ChunkSignature::return_type), Some(&Type::U64));
+    assert_eq!(library.generate_abi(&environment).entries.len(), 1);
+
+    let mut vm = VM::new(&library, &environment);
+    let err = vm.invoke_entry::<u64>("scale", vec![Value::U64(1).into(), Value::U64(2).into()]).unwrap_err();
+    assert!(matches!(err, VMError::EntryNotFound(ref name) if name == "scale"), "{}", err);
+    assert_eq!(vm.invoke_entry::<u64>("main", Vec::new()).unwrap(), 7);
+
+    // Callable from another module by its name
+    let mut caller = Module::new();
+    let name = caller.add_constant(Value::String("lib".to_owned()));
+    let export = caller.add_constant(Value::String("scale".to_owned()));
+    let factor = caller.add_constant(Value::U64(2));
+    let value = caller.add_constant(Value::U64(20));
+
+    let mut chunk = Chunk::new();
+    chunk.emit_opcode(OpCode::Constant);
+    chunk.write_u16(factor as u16);
+    chunk.emit_opcode(OpCode::Constant);
+    chunk.write_u16(value as u16);
+    chunk.emit_opcode(OpCode::ExternalCall);
+    chunk.write_u16(name as u16);
+    chunk.write_u16(export as u16);
+    chunk.write_u8(2);
+    chunk.emit_opcode(OpCode::Return);
+    caller.add_entry_chunk(chunk);
+
+    let mut vm = VM::new(&caller, &environment);
+    vm.link_module("lib", &library);
+    vm.invoke_entry_chunk(0).unwrap();
+    assert_eq!(vm.run().unwrap(), ExecutionOutcome::Success(Value::U64(40).into()));
+
+    // A method can't be public and an overload can't be exported twice
+    let compile = |code: &str| {
+        let tokens: Vec<_> = Lexer::new(code).collect::<Result<_, _>>().unwrap();
+        let env = EnvironmentBuilder::default();
+        let (program, _) = Parser::with(tokens.into_iter(), &env).parse().map_err(|e| e.kind.to_string())?;
+        Compiler::new(&program, env.environment()).compile().map(|_| ()).map_err(|e| e.to_string())
+    };
+    assert_eq!(compile("pub fn (v u64) double() -> u64 { return v * 2 }"), Err(ParserErrorKind::PublicFunctionCannotHaveForType.to_string()));
+    assert_eq!(compile("pub fn a() {}\npub fn a(b: u64) {}"), Err(CompilerError::DuplicatedExport("a".to_owned()).to_string()));
+    assert_eq!(compile("fn pub() {}\npub fn a() {}"), Ok(()));
+}
+
+#[test]
+fn test_native_closure() {
+    // The closure captures a state of the host
+    let height = std::sync::Arc::new(std::sync::atomic::AtomicU64::new(100));
+    let host_height = height.clone();
+
+    let mut env = EnvironmentBuilder::default();
+    env.register_native_function("block_height", None, vec![], move |_, _, _| {
+        let value = host_height.fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+        Ok(Some(Value::U64(value).into()))
+    }, 5, Some(Type::U64));
+
+    let code = r#"
+        entry main() {
+            return block_height() + block_height()
+        }
+    "#;
+    let tokens: Vec<_> = Lexer::new(code).collect::<Result<_, _>>().unwrap();
+    let (program, _) = Parser::with(tokens.into_iter(), &env).parse().unwrap();
+    let env = env.build();
+    let module = Compiler::new(&program, &env).compile().unwrap();
+
+    assert_eq!(run_internal(module, &env, 0).unwrap(), Value::U64(201));
+    assert_eq!(height.load(std::sync::atomic::Ordering::Relaxed), 102);
+}
+
+#[test]
+fn test_native_context_data() {
+    // Data of the host attached to the context for each execution
+    struct BlockHeight(u64);
+    struct Caller(String);
+
+    let mut env = EnvironmentBuilder::default();
+    env.register_native_function("block_height", None, vec![], |_, _, context| {
+        let height = context.try_get::<BlockHeight>()?;
+        Ok(Some(Value::U64(height.0).into()))
+    }, 5, Some(Type::U64));
+    env.register_native_function("sender", None, vec![], |_, _, context| {
+        let caller = context.try_get::<Caller>()?;
+        Ok(Some(Value::String(caller.0.clone()).into()))
+    }, 5, Some(Type::String));
+
+    let code = r#"
+        entry main() {
+            assert(sender() == "alice");
+            return block_height()
+        }
+    "#;
+    let tokens: Vec<_> = Lexer::new(code).collect::<Result<_, _>>().unwrap();
+    let (program, _) = Parser::with(tokens.into_iter(), &env).parse().unwrap();
+    let env = env.build();
+    let module = Compiler::new(&program, &env).compile().unwrap();
+
+    // The caller is borrowed from the host
+    let caller = Caller("alice".to_owned());
+    let mut vm = VM::new(&module, &env);
+    vm.context_mut().insert(BlockHeight(42));
+    vm.context_mut().insert_ref(&caller);
+    vm.invoke_chunk_id(0).unwrap();
+    assert_eq!(vm.run().unwrap().into_result().unwrap().into_value().unwrap(), Value::U64(42));
+
+    let mut vm = VM::new(&module, &env);
+    vm.invoke_chunk_id(0).unwrap();
+    let err = vm.run().unwrap_err();
+    assert!(matches!(err.inner(), VMError::EnvironmentError(EnvironmentError::MissingData(_))), "{}", err);
+}
+
+#[test]
+fn test_chain_info() {
+    let code = r#"
+        entry main() {
+            let block: u8[] = chain::block_hash();
+            let tx: u8[] = chain::tx_hash();
+            assert(block.len() == 32);
+            assert(tx[0] == 2);
+            return chain::timestamp() + chain::block_height() + block[0] as u64
+        }
+    "#;
+    let (module, environment) = prepare_module(code);
+
+    let mut vm = VM::new(&module, &environment);
+    vm.context_mut().insert(ChainInfo::default()
+        .with_timestamp(1_700_000_000_000)
+        .with_block_height(42)
+        .with_block_hash([1; 32])
+        .with_tx_hash([2; 32])
+    );
+    vm.invoke_entry_chunk(0).unwrap();
+    assert_eq!(vm.run().unwrap(), ExecutionOutcome::Success(Value::U64(1_700_000_000_043).into()));
+
+    // A value not given by the host stops the execution
+    let mut vm = VM::new(&module, &environment);
+    vm.context_mut().insert(ChainInfo::default()
+        .with_block_hash([1; 32])
+        .with_tx_hash([2; 32])
+    );
+    vm.invoke_entry_chunk(0).unwrap();
+    let err = vm.run().unwrap_err();
+    assert!(matches!(err.inner(), VMError::EnvironmentError(EnvironmentError::MissingChainData("timestamp"))), "{}", err);
+
+    // No chain data given by the host
+    let mut vm = VM::new(&module, &environment);
+    vm.invoke_entry_chunk(0).unwrap();
+    let err = vm.run().unwrap_err();
+    assert!(matches!(err.inner(), VMError::EnvironmentError(EnvironmentError::MissingData(_))), "{}", err);
+}
+
+#[test]
+fn test_namespace() {
+    let mut env = EnvironmentBuilder::default();
+    env.namespace("math", |ns| {
+        ns.register_native_function("double", vec![("value", Type::U64)], |_, params, _| {
+            let value = params[0].as_ref().as_u64()?;
+            Ok(Some(Value::U64(value * 2).into()))
+        }, 5, Some(Type::U64));
+
+        ns.namespace("consts", |ns| {
+            ns.register_native_function("ten", vec![], |_, _, _| Ok(Some(Value::U64(10).into())), 1, Some(Type::U64));
+        });
+    });
+
+    // A namespaced function doesn't collide with a global function of the same name
+    env.register_native_function("double", None, vec![("value", Type::U64)], |_, params, _| {
+        let value = params[0].as_ref().as_u64()?;
+        Ok(Some(Value::U64(value + value + 1).into()))
+    }, 5, Some(Type::U64));
+
+    let code = r#"
+        entry main() {
+            let a: u64 = math::double(math::consts::ten());
+            return a + double(1)
+        }
+    "#;
+    let tokens: Vec<_> = Lexer::new(code).collect::<Result<_, _>>().unwrap();
+    let (program, _) = Parser::with(tokens.into_iter(), &env).parse().unwrap();
+    let env = env.build();
+    let module = Compiler::new(&program, &env).compile().unwrap();
+
+    assert_eq!(run_internal(module, &env, 0).unwrap(), Value::U64(23));
+}
+
+#[test]
+fn test_hardcoded_argument_cast() {
+    // The index is cast to the parameter type, not to the type of the elements
+    let code = r#"
+        entry main() {
+            let values: u8[] = [10, 20, 30];
+            values.remove(0);
+            return values.get(1).unwrap() as u64
+        }
+    "#;
+
+    assert_eq!(run_code(code), Value::U64(30));
+}
+
+#[test]
+fn test_function_overloading() {
+    let code = r#"
+        fn foo(a: u64) -> u64 {
+            return a
+        }
+
+        fn foo(a: string) -> u64 {
+            return 100
+        }
+
+        fn foo(a: u64, b: u64) -> u64 {
+            return a + b
+        }
+
+        fn bar(a: u8, b: u8) -> u64 {
+            return 1000
+        }
+
+        fn bar(a: u8, b: u64) -> u64 {
+            return 2000
+        }
+
+        entry main() {
+            let s: string = "a";
+            return foo(1) + foo(s) + foo(2, 3) + bar(1, 2)
+        }
+    "#;
+    // The overload requiring the fewest casts is selected
+    assert_eq!(run_code_id(code, 5), Value::U64(2106));
+
+    // Both overloads require to cast the hardcoded value
+    let code = r#"
+        fn foo(a: u8) -> u64 {
+            return 1
+        }
+
+        fn foo(a: u16) -> u64 {
+            return 2
+        }
+
+        entry main() {
+            return foo(1)
+        }
+    "#;
+    let tokens: Vec<_> = Lexer::new(code).collect::<Result<_, _>>().unwrap();
+    let env = EnvironmentBuilder::default();
+    let result = Parser::with(tokens.into_iter(), &env).parse();
+    assert!(matches!(result.map_err(|e| e.kind), Err(ParserErrorKind::BuilderError(e)) if matches!(*e, BuilderError::AmbiguousFunctionCall)));
+}
+
+#[test]
+fn test_array_instance_overload() {
+    // The array functions are selected, not the ones of its element type
+    let code = r#"
+        entry main() {
+            let words: string[] = ["hello", "world", "!"];
+            assert(words.contains("world"));
+            return words.len() as u64
+        }
+    "#;
+
+    assert_eq!(run_code(code), Value::U64(3));
+}
+
+#[test]
+fn test_declared_primitive_is_copied() {
+    let code = r#"
+        entry main() {
+            let values: u64[] = [5];
+            let a: u64 = values[0];
+            let b: u64 = a;
+            a = 7;
+            b += 1;
+            assert(values[0] == 5);
+
+            let c: u64[] = values;
+            c.push(a);
+            return (values.len() as u64) + b
+        }
+    "#;
+
+    assert_eq!(run_code(code), Value::U64(8));
+}
+
+#[test]
+fn test_impl_block() {
+    let code = r#"
+        struct Rect {
+            width: u64,
+            height: u64
+        }
+
+        impl Rect {
+            fn square(size: u64) -> Rect {
+                return Rect { width: size, height: size }
+            }
+
+            fn area(self) -> u64 {
+                return self.width * self.height
+            }
+
+            fn scale(self, factor: u64) {
+                self.width *= factor;
+                self.height *= factor;
+            }
+        }
+
+        entry main() {
+            let rect: Rect = Rect::square(3);
+            rect.scale(2);
+            return rect.area()
+        }
+    "#;
+
+    assert_eq!(run_code_id(code, 3), Value::U64(36));
+}
+
+#[test]
+fn test_interface() {
+    let code = r#"
+        interface Shape {
+            fn area(self) -> u64
+            fn bigger(self, other: Self) -> bool
+            fn unit() -> Self
+        }
+
+        struct Square {
+            size: u64
+        }
+
+        impl Shape for Square {
+            fn area(self) -> u64 {
+                return self.size * self.size
+            }
+
+            fn bigger(self, other: Self) -> bool {
+                return self.area() > other.area()
+            }
+
+            fn unit() -> Square {
+                return Square { size: 1 }
+            }
+        }
+
+        entry main() {
+            let square: Square = Square { size: 4 };
+            if square.bigger(Square::unit()) {
+                return square.area()
+            }
+            return 0
+        }
+    "#;
+
+    assert_eq!(run_code_id(code, 3), Value::U64(16));
+
+    let parse = |code: &str| {
+        let tokens: Vec<_> = Lexer::new(code).collect::<Result<_, _>>().unwrap();
+        let env = EnvironmentBuilder::default();
+        Parser::with(tokens.into_iter(), &env).parse().map(|_| ()).map_err(|e| e.kind.to_string())
+    };
+
+    // A function of the interface is not implemented
+    let code = r#"
+        interface Shape {
+            fn area(self) -> u64
+        }
+
+        struct Square {
+            size: u64
+        }
+
+        impl Shape for Square {}
+    "#;
+    assert_eq!(parse(code), Err("interface function 'area' is not implemented".to_owned()));
+
+    // The signature doesn't match the interface
+    let code = r#"
+        interface Shape {
+            fn area(self) -> u64
+        }
+
+        struct Square {
+            size: u64
+        }
+
+        impl Shape for Square {
+            fn area(self) -> u32 {
+                return 0
+            }
+        }
+    "#;
+    assert_eq!(parse(code), Err("function 'area' does not match its interface signature".to_owned()));
+}
+
+#[test]
+fn test_enum_impl() {
+    let code = r#"
+        enum Direction {
+            North,
+            East,
+            South,
+            West
+        }
+
+        impl Direction {
+            const COUNT: u8 = 4
+
+            fn opposite(self) -> Direction {
+                if (self == Direction::North) {
+                    return Direction::South
+                } else if (self == Direction::South) {
+                    return Direction::North
+                } else if (self == Direction::East) {
+                    return Direction::West
+                }
+                return Direction::East
+            }
+
+            fn is_vertical(self) -> bool {
+                return (self == Direction::North) || (self == Direction::South)
+            }
+        }
+
+        entry main() {
+            let d: Direction = Direction::East;
+            let total: u64 = Direction::COUNT as u64;
+            if d.opposite().is_vertical() {
+                return 0
+            }
+            if (d.opposite() == Direction::West) {
+                return total
+            }
+            return 1
+        }
+    "#;
+
+    assert_eq!(run_code_id(code, 2), Value::U64(4));
+}
+
+#[test]
+fn test_tuple() {
+    let code = r#"
+        fn div_mod(a: u64, b: u64) -> (u64, u64) {
+            return (a / b, a % b)
+        }
+
+        entry main() {
+            let pair: (u64, string) = (10, "hello");
+            let (q, r) = div_mod(17, 5);
+            let (_, s) = pair;
+            let nested: ((u8, bool), u64) = ((1, true), pair.0);
+            if !nested.0.1 {
+                return 0
+            }
+            return ((q * 100) + (r * 10)) + (s.len() as u64) + (nested.0.0 as u64) + nested.1
+        }
+    "#;
+
+    assert_eq!(run_code_id(code, 1), Value::U64(300 + 20 + 5 + 1 + 10));
+
+    // The number of variables must match the tuple size
+    let code = r#"
+        entry main() {
+            let pair: (u64, u64) = (1, 2);
+            let (a, b, c) = pair;
+            return a
+        }
+    "#;
+    let tokens: Vec<_> = Lexer::new(code).collect::<Result<_, _>>().unwrap();
+    let env = EnvironmentBuilder::default();
+    let result = Parser::with(tokens.into_iter(), &env).parse();
+    assert!(matches!(result.map_err(|e| e.kind), Err(ParserErrorKind::InvalidTupleSize(3))));
+}
+
+#[test]
+fn test_destructuring() {
+    let code = r#"
+        struct Fee {
+            amount: u64,
+            token: string
+        }
+
+        struct Config {
+            owner: string,
+            fee: Fee,
+            limits: u64[],
+            pair: (u64, bool)
+        }
+
+        fn config() -> Config {
+            return Config { owner: "alice", fee: Fee { amount: 10, token: "XEL" }, limits: [1, 2, 3], pair: (4, true) }
+        }
+
+        entry main() {
+            let { owner, fee: { amount, .. }, limits: [low, _, high], pair: (n, ok) } = config();
+            assert(owner == "alice");
+            assert(ok);
+
+            let c: Config = config();
+            let { fee: f, .. } = c;
+            let [first, ..] = c.limits;
+            let [[a, b], _] = [[5, 6], [7, 8]];
+            return (amount + low) + (high + first) + n + f.amount + (a * b)
+        }
+
+        entry unpack() {
+            let values: u64[] = [1, 2, 3];
+            let [a, b] = values;
+            return a + b
+        }
+    "#;
+
+    assert_eq!(run_code_id(code, 1), Value::U64(10 + 1 + 3 + 1 + 4 + 10 + 30));
+
+    // Without `..`, the length is checked during the execution
+    let err = try_run_code(code, 2).unwrap_err();
+    assert!(matches!(err.inner(), VMError::InvalidUnpackLength(2, 3)), "{}", err);
+
+    let parse = |code: &str| {
+        let tokens: Vec<_> = Lexer::new(code).collect::<Result<_, _>>().unwrap();
+        let env = EnvironmentBuilder::default();
+        Parser::with(tokens.into_iter(), &env).parse().map(|_| ()).map_err(|e| e.kind.to_string())
+    };
+    let code = |pattern: &str| format!(r#"
+        struct Point {{
+            x: u64,
+            y: u64
+        }}
+
+        entry main() {{
+            let p: Point = Point {{ x: 1, y: 2 }};
+            let {} = p;
+            return 0
+        }}
+    "#, pattern);
+
+    assert_eq!(parse(&code("{ x, y }")), Ok(()));
+    assert_eq!(parse(&code("{ y: x, .. }")), Ok(()));
+    assert_eq!(parse(&code("{ x }")), Err(ParserErrorKind::MissingFieldsInPattern.to_string()));
+    assert_eq!(parse(&code("{ x, x: y }")), Err(ParserErrorKind::DuplicatedFieldInPattern("x").to_string()));
+    assert_eq!(parse(&code("{ x: a, y: a }")), Err(ParserErrorKind::VariableNameAlreadyUsed("a").to_string()));
+    assert_eq!(parse(&code("{ z, .. }")), Err(ParserErrorKind::UnexpectedVariable("z").to_string()));
+    // The rest pattern must be the last one
+    assert!(parse(&code("{ .., x }")).is_err());
+    assert!(parse(&code("[a, b]")).unwrap_err().contains("expected an array"));
+}
+
+#[test]
+fn test_if_let_while_let() {
+    let code = r#"
+        fn first_or_max(values: u64[]) -> u64 {
+            if let Some(v) = values.first() {
+                return v
+            } else {
+                return 255
+            }
+        }
+
+        entry main() {
+            let values: u64[] = [10, 20, 30];
+            let total: u64 = 0;
+            while let Some(v) = values.pop() {
+                if v == 20 {
+                    continue
+                }
+                total += v;
+            }
+            let after: u64 = 1;
+
+            if let Some(_) = values.first() {
+                return 0
+            }
+
+            let empty: u64[] = [];
+            return total + after + first_or_max([2, 1]) + first_or_max(empty)
+        }
+    "#;
+
+    assert_eq!(run_code_id(code, 1), Value::U64(40 + 1 + 2 + 255));
+
+    // The value must be an optional
+    let code = r#"
+        entry main() {
+            if let Some(v) = 10 {
+                return v
+            }
+            return 0
+        }
+    "#;
+    let tokens: Vec<_> = Lexer::new(code).collect::<Result<_, _>>().unwrap();
+    let env = EnvironmentBuilder::default();
+    let result = Parser::with(tokens.into_iter(), &env).parse();
+    assert!(matches!(result.map_err(|e| e.kind), Err(ParserErrorKind::ExpectedOptional(Type::U64))));
+
+    // The generated statements must keep the spans of the next statements
+    let code = r#"
+        entry main() {
+            let values: u64[] = [1];
+            while let Some(v) = values.pop() {
+                values.len();
+            }
+            if let Some(v) = values.first() {
+                return v
+            }
+            let b: u64 = 0;
+            let c: u64 = 10 / b;
+            return c
+        }
+    "#;
+    let tokens: Vec<_> = Lexer::new(code).collect::<Result<_, _>>().unwrap();
+    let env = EnvironmentBuilder::default();
+    let (program, _) = Parser::with(tokens.into_iter(), &env).parse().unwrap();
+    let environment = env.build();
+
+    let mut compiler = Compiler::new(&program, &environment);
+    compiler.set_debug_info(true);
+    let module = compiler.compile().unwrap();
+    let err = run_internal(module, &environment, 0).unwrap_err();
+    assert!(matches!(err.inner(), VMError::DivisionByZero));
+    assert_eq!(err.backtrace().unwrap().frames()[0].span.map(|span| span.line), Some(11));
+}
+
+#[test]
+fn test_result() {
+    let code = r#"
+        fn parse(value: u64) -> Result<u64, string> {
+            if value == 0 {
+                return Err("zero")
+            }
+            return Ok(value * 2)
+        }
+
+        fn sum(a: u64, b: u64) -> Result<u64, string> {
+            let x: u64 = parse(a)?;
+            return Ok(x + (parse(b)?))
+        }
+
+        entry main() {
+            let ok: Result<u64, string> = sum(1, 2);
+            let err: Result<u64, string> = sum(1, 0);
+            if ok.is_err() {
+                return 0
+            }
+            if err.is_ok() {
+                return 0
+            }
+            assert(err.unwrap_err() == "zero");
+            return ok.unwrap() + err.unwrap_or(100)
+        }
+    "#;
+
+    assert_eq!(run_code_id(code, 2), Value::U64(6 + 100));
+
+    // Unwrapping an error aborts the execution
+    let code = r#"
+        fn fail() -> Result<u8, string> {
+            return Err("failed")
+        }
+
+        entry main() {
+            let value: u8 = fail().unwrap();
+            return value as u64
+        }
+    "#;
+    assert!(try_run_code(code, 1).is_err());
+
+    // The function must return a result with the same error type
+    let code = r#"
+        fn parse() -> Result<u64, string> {
+            return Ok(1)
+        }
+
+        fn forward() -> Result<u64, bool> {
+            return Ok(parse()?)
+        }
+
+        entry main() {
+            return 0
+        }
+    "#;
+    let tokens: Vec<_> = Lexer::new(code).collect::<Result<_, _>>().unwrap();
+    let env = EnvironmentBuilder::default();
+    let result = Parser::with(tokens.into_iter(), &env).parse();
+    assert!(matches!(result.map_err(|e| e.kind), Err(ParserErrorKind::InvalidTryReturnType(_))));
+}
+
+#[test]
+fn test_const_evaluation() {
     let code = r#"
-        struct Value {
-            value: string
+        const FEE: u64 = 100 * 2;
+        const DOUBLE_FEE: u64 = FEE * 2;
+        const SMALL: u8 = 250 +| 10;
+        const FLAGS: u64 = (1 << 4) | 1;
+        const EXPENSIVE: bool = FEE > 100;
+
+        entry main() {
+            if !EXPENSIVE {
+                return 0
+            }
+            return FEE + DOUBLE_FEE + FLAGS + (SMALL as u64)
         }
+    "#;
+    assert_eq!(run_code(code), Value::U64(200 + 400 + 17 + 255));
 
-        fn (v Value) my_value() -> string {
-            return v.value
+    // An overflow can't be evaluated
+    let code = r#"
+        const MAX: u8 = 255 + 1;
+
+        entry main() {
+            return 0
         }
+    "#;
+    let tokens: Vec<_> = Lexer::new(code).collect::<Result<_, _>>().unwrap();
+    let env = EnvironmentBuilder::default();
+    let result = Parser::with(tokens.into_iter(), &env).parse();
+    assert!(matches!(result.map_err(|e| e.kind), Err(ParserErrorKind::InvalidConstantValue)));
+}
 
-        struct Message {
-            value: Value
+#[test]
+fn test_variable_shadowing() {
+    let code = r#"
+        entry main() {
+            let a: u64 = 1;
+            let a: u64 = a + 10;
+            {
+                let a: u64 = 100;
+                a += 1;
+            }
+            return a
         }
+    "#;
 
-        fn (m Message) to_string() -> string {
-            return m.value.value
+    assert_eq!(run_code(code), Value::U64(11));
+}
+
+#[test]
+fn test_parser_warnings() {
+    let code = r#"
+        const LIMIT: u64 = 10
+
+        fn compute(value: u64) -> u64 {
+            let unused: u64 = 5;
+            let LIMIT: u64 = value;
+            let _: u64 = 0;
+            if LIMIT > 0 {
+                return 1
+            } else {
+                return 2
+            }
+            let after: u64 = 3;
+            return after
         }
 
         entry main() {
-            let message: Message = Message { value: Value { value: "Hello World!" } }
-            assert(message.to_string() == "Hello World!")
-            message.value.value += " from path"
-            assert(message.to_string() == "Hello World! from path")
+            let total: u64 = 1;
+            let total: u64 = total + compute(1);
+            return total
+        }
+    "#;
+
+    let tokens: Vec<_> = Lexer::new(code).collect::<Result<_, _>>().unwrap();
+    let env = EnvironmentBuilder::default();
+    let (_, _, warnings) = Parser::with(tokens.into_iter(), &env).parse_with_warnings().unwrap();
+
+    let warnings: Vec<_> = warnings.iter()
+        .map(|w| (w.span.line, w.code, w.message.as_str()))
+        .collect();
+    assert_eq!(warnings, vec![
+        (5, WarningCode::UnusedVariable, "variable 'unused' is never used"),
+        (6, WarningCode::ShadowedIdentifier, "variable 'LIMIT' shadows a constant with the same name"),
+        (13, WarningCode::UnreachableCode, "unreachable statement"),
+        (19, WarningCode::ShadowedIdentifier, "variable 'total' shadows a variable with the same name"),
+    ]);
+}
+
+#[test]
+fn test_parser_error_suggestions() {
+    // Returns the suggestion and the error rendered with its source line
+    let parse = |code: &str| {
+        let tokens: Vec<_> = Lexer::new(code).collect::<Result<_, _>>().unwrap();
+        let env = EnvironmentBuilder::default();
+        let error = Parser::with(tokens.into_iter(), &env).parse().map(|_| ()).unwrap_err();
+        (error.suggestion.clone(), error.with_source(code).to_string())
+    };
+
+    let code = "entry main() {\n    let value: u64 = 10;\n    return valeu\n}";
+    let (suggestion, rendered) = parse(code);
+    assert_eq!(suggestion.as_deref(), Some("value"));
+    assert_eq!(rendered, [
+        "error: unexpected variable name 'valeu'",
+        " --> line 3, column 12",
+        "  |",
+        "3 |     return valeu",
+        "  |            ^^^^^",
+        "  = help: did you mean 'value'?",
+    ].join("\n"));
+
+    let suggest = |code: &str| parse(code).0;
+
+    // Functions
+    let code = r#"
+        fn compute(a: u64) -> u64 {
+            return a
+        }
+
+        entry main() {
+            return comptue(1)
+        }
+    "#;
+    assert_eq!(suggest(code).as_deref(), Some("compute"));
+
+    // Types and struct fields
+    let code = r#"
+        struct Point {
+            x: u64
+        }
+
+        fn origin() -> Pont {
+            return Point { x: 0 }
+        }
+    "#;
+    assert_eq!(suggest(code).as_deref(), Some("Point"));
+
+    let code = r#"
+        struct Point {
+            x: u64
+        }
+
+        entry main() {
+            let point: Point = Point { x: 0 };
+            return point.y
+        }
+    "#;
+    assert_eq!(suggest(code).as_deref(), Some("x"));
+
+    // Enum variants
+    let code = r#"
+        enum Color {
+            Red,
+            Green
+        }
+
+        entry main() {
+            let color: Color = Color::Gren;
+            return 0
+        }
+    "#;
+    assert_eq!(suggest(code).as_deref(), Some("Green"));
+
+    // Nothing close enough
+    let code = r#"
+        entry main() {
+            let value: u64 = 10;
+            return counter
+        }
+    "#;
+    assert_eq!(suggest(code), None);
+}
+
+#[test]
+fn test_parse_lenient() {
+    let code = r#"
+        struct Point {
+            x: u64
+        }
+
+        fn broken(a: u64) -> u64 {
+            let b: u64 = a + "text";
+            let c: u64 = unknown;
+            if a > 0 {
+                let d: bool = a;
+            }
+            return a
+        }
+
+        fn missing_type() -> Unknown {
+            return 0
+        }
+
+        entry main() {
+            let point: Point = Point { x: 10 };
+            return point.x
+        }
+    "#;
+
+    let tokens: Vec<_> = Lexer::new(code).collect::<Result<_, _>>().unwrap();
+    let env = EnvironmentBuilder::default();
+    let partial = Parser::with(tokens.into_iter(), &env).parse_lenient();
+
+    let errors: Vec<_> = partial.errors.iter()
+        .map(|e| e.line)
+        .collect();
+    assert_eq!(errors, vec![7, 8, 10, 15]);
+
+    // The valid statements and declarations are kept
+    let functions = partial.program.functions();
+    assert_eq!(functions.len(), 2);
+    assert_eq!(functions[0].get_statements().len(), 2);
+    assert_eq!(functions[1].get_statements().len(), 2);
+
+    // A valid program gives the same result as parse
+    let code = r#"
+        entry main() {
             return 0
         }
     "#;
+    let tokens: Vec<_> = Lexer::new(code).collect::<Result<_, _>>().unwrap();
+    let partial = Parser::with(tokens.into_iter(), &env).parse_lenient();
+    assert!(partial.errors.is_empty());
+    assert_eq!(partial.program.functions().len(), 1);
+}
+
+#[test]
+fn test_test_functions() {
+    let code = r#"
+        fn add(a: u64, b: u64) -> u64 {
+            return a + b
+        }
+
+        test fn adds() {
+            assert_eq(add(1, 2), 3)
+            assert(add(2, 2) == 4, "2 + 2 must be 4")
+        }
+
+        test fn fails_with_message() {
+            assert(add(1, 1) == 3, "1 + 1 is not 3")
+        }
+
+        test fn fails_not_equal() {
+            assert_eq(add(1, 1), 3)
+        }
+
+        test fn returns_early() {
+            let test: u64 = add(1, 1)
+            if test == 2 {
+                return
+            }
+            panic("unreachable")
+        }
+
+        entry main() {
+            return add(1, 2)
+        }
+    "#;
+
+    let (module, environment) = prepare_module(code);
+    let report = TestRunner::new(&environment).run(&module);
+    let results: Vec<_> = report.results.iter()
+        .map(|r| (r.name.as_str(), r.passed()))
+        .collect();
+    assert_eq!(results, vec![("adds", true), ("fails_with_message", false), ("fails_not_equal", false), ("returns_early", true)]);
+    assert!(report.results.iter().all(|r| r.gas_usage > 0));
+    assert!(report.results[1].failure.as_ref().unwrap().contains("Assertion failed: 1 + 1 is not 3"));
+    assert!(report.results[2].failure.as_ref().unwrap().contains("Assertion failed: 2 != 3"));
+    assert!(!report.is_success());
+    assert_eq!((report.passed(), report.failed()), (2, 2));
+
+    // The entry is still callable and the tests are not exported
+    assert_eq!(run_code_id(code, 5), Value::U64(3));
+    assert_eq!(module.exports().len(), 1);
+
+    // A gas limit is applied to each test
+    let report = TestRunner::new(&environment).with_gas_limit(1).run(&module);
+    assert_eq!(report.failed(), 4);
+
+    // Tests can't be called and have no parameters
+    let parse = |code: &str| {
+        let tokens: Vec<_> = Lexer::new(code).collect::<Result<_, _>>().unwrap();
+        let env = EnvironmentBuilder::default();
+        Parser::with(tokens.into_iter(), &env).parse().map(|_| ()).map_err(|e| e.kind.to_string())
+    };
+    assert_eq!(parse("test fn a() {}\nentry main() { a()\nreturn 0 }"), Err(ParserErrorKind::FunctionIsTest.to_string()));
+    assert_eq!(parse("test fn a(b: u64) {}"), Err(ParserErrorKind::TestFunctionCannotHaveParameters.to_string()));
+}
+
+#[test]
+fn test_coverage() {
+    let code = r#"
+        fn sign(value: u64) -> u64 {
+            if value > 10 {
+                return 1
+            } else {
+                return 0
+            }
+        }
+
+        test fn small() {
+            assert_eq(sign(1), 0)
+        }
+
+        test fn small_again() {
+            assert_eq(sign(2), 0)
+        }
+    "#;
+
+    let tokens: Vec<_> = Lexer::new(code).collect::<Result<_, _>>().unwrap();
+    let env = EnvironmentBuilder::default();
+    let (program, _) = Parser::with(tokens.into_iter(), &env).parse().unwrap();
+    let environment = env.build();
+
+    let mut compiler = Compiler::new(&program, &environment);
+    compiler.set_debug_info(true);
+    let module = compiler.compile().unwrap();
+
+    let mut coverage = Coverage::new();
+    let report = TestRunner::new(&environment).run_with_tracer(&module, &mut coverage);
+    assert!(report.is_success());
+
+    let report = coverage.report(&module).unwrap();
+    assert_eq!(report.lines.get(&3), Some(&2));
+    assert_eq!(report.lines.get(&6), Some(&2));
+    assert_eq!(report.lines.get(&11), Some(&1));
+    assert_eq!(report.uncovered_lines(), vec![4]);
+    assert_eq!(report.covered_lines(), report.lines.len() - 1);
+    assert!(report.to_string().contains("     4 | 0\n"));
+
+    // The hits of several executions are merged
+    let mut merged = Coverage::new();
+    merged.merge(&coverage);
+    merged.merge(&coverage);
+    assert_eq!(merged.report(&module).unwrap().lines.get(&3), Some(&4));
+
+    // The lines can't be known without the debug info
+    let module = Compiler::new(&program, &environment).compile().unwrap();
+    assert!(matches!(coverage.report(&module), Err(CoverageError::NoDebugInfo)));
+}
+
+#[track_caller]
+fn estimate_and_run(code: &str) -> (GasBound, u64) {
+    let (module, environment) = prepare_module(code);
+    let bound = GasEstimator::new(&module, &environment).estimate_entry("main").unwrap();
+
+    let mut vm = VM::new(&module, &environment);
+    vm.invoke_entry_chunk(module.entry_by_name("main").unwrap() as u16).unwrap();
+    vm.run().unwrap();
+
+    (bound, vm.context().current_gas_usage())
+}
+
+#[test]
+fn test_gas_estimation() {
+    // Without branches the bound is exact
+    let (bound, gas) = estimate_and_run(r#"
+        fn double(value: u64) -> u64 {
+            return value * 2
+        }
+
+        entry main() {
+            let a: u64 = 10;
+            let b: u64 = double(a) + 5;
+            return b
+        }
+    "#);
+    assert_eq!(bound, GasBound::Bounded(gas));
+
+    // The worst branch is counted
+    let (bound, gas) = estimate_and_run(r#"
+        entry main() {
+            let a: u64 = 10;
+            if a > 5 {
+                return 0
+            } else {
+                a = a * 2 + a * 3;
+            }
+            return a
+        }
+    "#);
+    assert!(matches!(bound, GasBound::Bounded(n) if n > gas));
+
+    // Loops with a constant bound
+    let (bound, gas) = estimate_and_run(r#"
+        entry main() {
+            let total: u64 = 0;
+            for i: u64 = 0; i < 10; i += 1 {
+                total += i * 2
+            }
+            foreach value in [1, 2, 3] {
+                total += value
+            }
+            foreach value in 0..5 {
+                total += value
+            }
+            return total
+        }
+    "#);
+    assert!(matches!(bound, GasBound::Bounded(n) if n >= gas && n < gas * 2), "{} {}", bound, gas);
+}
+
+#[test]
+fn test_gas_estimation_unbounded() {
+    let code = r#"
+        fn fact(n: u64) -> u64 {
+            if n == 0 {
+                return 1
+            }
+            return n * fact(n - 1)
+        }
+
+        entry recursive() {
+            return fact(5)
+        }
+
+        entry main() {
+            let count: u64 = 0;
+            while count < 5 {
+                count = count + 1
+            }
+            return count
+        }
+
+        entry modified() {
+            let total: u64 = 0;
+            for i: u64 = 0; i < 10; i += 1 {
+                i += 1;
+                total += i
+            }
+            return total
+        }
+    "#;
+
+    let (module, environment) = prepare_module(code);
+    let estimator = GasEstimator::new(&module, &environment);
+    assert_eq!(estimator.estimate_entry("recursive"), Some(GasBound::Unbounded(UnboundedReason::Recursion(0))));
+    assert_eq!(estimator.estimate_entry("unknown"), None);
+
+    // The counter updated in the body isn't a bound
+    assert!(matches!(estimator.estimate_entry("modified"), Some(GasBound::Unbounded(UnboundedReason::Loop { .. }))));
+
+    let main = module.entry_by_name("main").unwrap();
+    let Some(GasBound::Unbounded(UnboundedReason::Loop { chunk, offset })) = estimator.estimate_entry("main") else {
+        panic!("while loop must be unbounded");
+    };
+    assert_eq!(chunk, main);
+
+    // Annotated, the loop is bounded
+    let estimator = GasEstimator::new(&module, &environment)
+        .with_loop_bound(chunk, offset, 5);
+    let Some(GasBound::Bounded(bound)) = estimator.estimate_entry("main") else {
+        panic!("annotated loop must be bounded");
+    };
+    let mut vm = VM::new(&module, &environment);
+    vm.invoke_entry_chunk(main as u16).unwrap();
+    vm.run().unwrap();
+    assert!(bound >= vm.context().current_gas_usage());
+
+    let entries = estimator.estimate_entries();
+    assert_eq!(entries.len(), 3);
+
+    // The memory is charged up to its limit
+    let estimator = estimator.with_memory(2, 1000);
+    assert_eq!(estimator.estimate_entry("main"), Some(GasBound::Bounded(bound + 2000)));
+    let estimator = estimator.with_memory(2, usize::MAX);
+    assert_eq!(estimator.estimate_entry("main"), Some(GasBound::Unbounded(UnboundedReason::Memory)));
+}
+
+#[test]
+fn test_debug_assertions() {
+    let code = r#"
+        #[invariant(balance >= 10)]
+        fn withdraw(balance: u64, amount: u64) -> u64 {
+            require(amount <= 100, "amount too large");
+            assert_eq(amount % 2, 0);
+            balance -= amount;
+            return balance
+        }
+
+        entry valid() {
+            return withdraw(50, 20)
+        }
+
+        entry odd() {
+            return withdraw(50, 21)
+        }
+
+        entry broken() {
+            return withdraw(50, 42)
+        }
+
+        entry low() {
+            return withdraw(5, 0)
+        }
+
+        entry large() {
+            return withdraw(500, 200)
+        }
+    "#;
+
+    let tokens: Vec<_> = Lexer::new(code).collect::<Result<_, _>>().unwrap();
+    let env = EnvironmentBuilder::default();
+    let (program, _) = Parser::with(tokens.into_iter(), &env).parse().unwrap();
+    let environment = env.environment();
+
+    let run = |debug_assertions: bool, name: &str| {
+        let mut compiler = Compiler::new(&program, environment);
+        compiler.set_debug_assertions(debug_assertions);
+        let module = compiler.compile().unwrap();
+
+        let mut vm = VM::new(&module, environment);
+        vm.invoke_entry_chunk(module.entry_by_name(name).unwrap() as u16).unwrap();
+        let result = match vm.run() {
+            Ok(ExecutionOutcome::Success(value)) => Ok(value.into_value().unwrap()),
+            Ok(ExecutionOutcome::Reverted(value)) => Err(format!("reverted with {}", value)),
+            Err(e) => Err(match e.inner() {
+                VMError::EnvironmentError(e) => e.to_string(),
+                e => e.to_string()
+            })
+        };
+        (result, vm.context().current_gas_usage())
+    };
+
+    // The checks cost no gas without the debug assertions
+    let (debug, debug_gas) = run(true, "valid");
+    let (release, release_gas) = run(false, "valid");
+    assert_eq!(debug, Ok(Value::U64(30)));
+    assert_eq!(release, Ok(Value::U64(30)));
+    assert!(release_gas < debug_gas);
+
+    assert_eq!(run(true, "odd").0, Err("Assertion failed: 1 != 0".to_owned()));
+    assert_eq!(run(false, "odd").0, Ok(Value::U64(29)));
+
+    // The invariant is checked at the start and before each return
+    assert_eq!(run(true, "broken").0, Err("Assertion failed: invariant of 'withdraw' failed".to_owned()));
+    assert_eq!(run(false, "broken").0, Ok(Value::U64(8)));
+    assert_eq!(run(true, "low").0, Err("Assertion failed: invariant of 'withdraw' failed".to_owned()));
+
+    // `require` is always checked
+    for debug_assertions in [true, false] {
+        assert_eq!(run(debug_assertions, "large").0, Err("reverted with amount too large".to_owned()));
+    }
+
+    let parse = |code: &str| {
+        let tokens: Vec<_> = Lexer::new(code).collect::<Result<_, _>>().unwrap();
+        Parser::with(tokens.into_iter(), &env).parse().map(|_| ()).map_err(|e| e.kind.to_string())
+    };
+    assert_eq!(parse("#[inline(a)]\nfn f(a: bool) {}"), Err("unknown attribute 'inline'".to_owned()));
+    assert_eq!(parse("#[invariant(a)]\nstruct A { a: bool }"), Err("an attribute must be followed by a function".to_owned()));
+    assert!(parse("#[invariant(a + 1)]\nfn f(a: u64) {}").is_err());
+    assert_eq!(parse("#[invariant(a)]\n#[invariant(!a)]\npub fn f(a: bool) {}"), Ok(()));
+}
+
+#[test]
+fn test_precompile() {
+    // Reverse the input bytes
+    struct Reverse;
+
+    impl xelis_environment::Precompile for Reverse {
+        fn id(&self) -> u16 {
+            7
+        }
+
+        fn base_gas(&self) -> u64 {
+            100
+        }
+
+        fn dynamic_gas(&self, input: &[u8]) -> u64 {
+            input.len() as u64 * 10
+        }
+
+        fn execute(&self, input: &[u8]) -> Result<Vec<u8>, anyhow::Error> {
+            if input.is_empty() {
+                anyhow::bail!("empty input")
+            }
+
+            Ok(input.iter().rev().copied().collect())
+        }
+    }
+
+    let mut env = EnvironmentBuilder::default();
+    env.register_precompile(Reverse);
+    let environment = env.build();
 
+    let build = |precompile: u16, bytes: &[u8]| {
+        let mut module = Module::new();
+        module.set_features(ModuleFeatures::PRECOMPILE);
+
+        let mut chunk = Chunk::new();
+        for byte in bytes {
+            let id = module.add_constant(Value::U8(*byte));
+            chunk.emit_opcode(OpCode::Constant);
+            chunk.write_u16(id as u16);
+        }
+        chunk.emit_opcode(OpCode::NewArray);
+        chunk.write_u8(bytes.len() as u8);
+        chunk.emit_opcode(OpCode::CallPrecompile);
+        chunk.write_u16(precompile);
+        chunk.emit_opcode(OpCode::Return);
+        module.add_entry_chunk(chunk);
+        module
+    };
+
+    let module = build(7, &[1, 2, 3]);
+    assert!(ModuleValidator::new(&module, &environment).verify().is_ok());
     assert_eq!(
-        run_code_id(code, 2),
-        Value::U64(0)
+        GasEstimator::new(&module, &environment).estimate_chunk(0),
+        GasBound::Unbounded(UnboundedReason::Precompile(7))
     );
-}
\ No newline at end of file
+
+    let mut vm = VM::new(&module, &environment);
+    vm.invoke_entry_chunk(0).unwrap();
+    let expected = Constant::Array(vec![Value::U8(3).into(), Value::U8(2).into(), Value::U8(1).into()]);
+    assert_eq!(vm.run().unwrap(), ExecutionOutcome::Success(expected));
+
+    // Base and dynamic gas are charged with the cost of the instructions
+    let instructions = 3 * InstructionTable::new().get_instruction_cost(OpCode::Constant)
+        + InstructionTable::new().get_instruction_cost(OpCode::NewArray)
+        + InstructionTable::new().get_instruction_cost(OpCode::CallPrecompile)
+        + InstructionTable::new().get_instruction_cost(OpCode::Return);
+    assert_eq!(vm.context().current_gas_usage(), instructions + 100 + 30);
+
+    // An error of the precompile stops the execution
+    let module = build(7, &[]);
+    let mut vm = VM::new(&module, &environment);
+    vm.invoke_entry_chunk(0).unwrap();
+    let err = vm.run().unwrap_err();
+    assert!(matches!(err.inner(), VMError::EnvironmentError(EnvironmentError::Any(_))), "{:?}", err);
+
+    // Unknown precompiles are rejected by the validator and the VM
+    let module = build(8, &[1]);
+    let result = ModuleValidator::new(&module, &environment).verify();
+    assert!(matches!(result, Err(ValidatorError::UnknownPrecompile(0, 8))), "{:?}", result);
+
+    let mut vm = VM::new(&module, &environment);
+    vm.invoke_entry_chunk(0).unwrap();
+    let err = vm.run().unwrap_err();
+    assert!(matches!(err.inner(), VMError::UnknownPrecompile(8)), "{:?}", err);
+
+    // The feature must be declared by the module
+    let mut module = build(7, &[1]);
+    module.set_features(ModuleFeatures::NONE);
+    let result = ModuleValidator::new(&module, &environment).verify();
+    assert!(matches!(result, Err(ValidatorError::UndeclaredFeature(0, OpCode::CallPrecompile))), "{:?}", result);
+}
@@ -0,0 +1,243 @@
+use xelis_compiler::{Compiler, OptimizationLevel};
+use xelis_lexer::Lexer;
+use xelis_parser::Parser;
+use super::*;
+
+fn prepare_module(code: &str, level: OptimizationLevel) -> (Module, Environment) {
+    let tokens: Vec<_> = Lexer::new(code).collect::<Result<_, _>>().unwrap();
+    let env = EnvironmentBuilder::default();
+    let (program, _) = Parser::with(tokens.into_iter(), &env).parse().unwrap();
+
+    let env = env.build();
+    let module = Compiler::new(&program, &env)
+        .with_optimizations(level)
+        .compile()
+        .unwrap();
+
+    (module, env)
+}
+
+// Run the entry with and without the JIT, both must return the same value and use the same gas
+// Returns the value, the gas used and the count of chunks compiled
+#[track_caller]
+fn compare_jit(module: &Module, environment: &Environment, entry: u16, gas_limit: u64) -> (Result<Value, String>, u64, usize) {
+    let run = |threshold: Option<u32>| {
+        let mut vm = VM::new(module, environment);
+        vm.context_mut().set_gas_limit(gas_limit);
+        if let Some(threshold) = threshold {
+            vm.set_jit_threshold(threshold);
+        }
+
+        vm.invoke_entry_chunk(entry).unwrap();
+        let value = vm.run()
+            .map(|v| v.into_result().unwrap().into_value().unwrap())
+            .map_err(|e| e.inner().to_string());
+
+        (value, vm.context().current_gas_usage(), vm.jit.compiled_chunks())
+    };
+
+    let (expected, expected_gas, _) = run(None);
+    let (value, gas, compiled) = run(Some(1));
+    assert_eq!(value, expected, "gas limit {}", gas_limit);
+    assert_eq!(gas, expected_gas, "gas limit {}", gas_limit);
+
+    (value, gas, compiled)
+}
+
+// Compare the JIT with the interpreter for each optimization level
+// Returns the value and the count of chunks compiled
+#[track_caller]
+fn run_jit(code: &str, entry: u16) -> (Result<Value, String>, usize) {
+    let mut result = None;
+    for level in [OptimizationLevel::None, OptimizationLevel::Full] {
+        let (module, environment) = prepare_module(code, level);
+        let (value, _, compiled) = compare_jit(&module, &environment, entry, 1_000_000);
+        result = Some((value, compiled));
+    }
+
+    result.unwrap()
+}
+
+#[test]
+fn test_jit_loops() {
+    let code = "entry main() {
+        let total: u64 = 0
+        for i: u64 = 0; i < 100; i += 1 {
+            if (i % 3) == 0 {
+                continue
+            }
+            let j: u64 = 0
+            while j < i {
+                total += j
+                j += 2
+            }
+        }
+        return total
+    }";
+
+    let (value, compiled) = run_jit(code, 0);
+    assert_eq!(value, Ok(Value::U64(53072)));
+    assert_eq!(compiled, 1);
+}
+
+#[test]
+fn test_jit_switch() {
+    // The switch is compiled to a jump table, each case is a basic block
+    let code = "entry main() {
+        let total: u64 = 0
+        for i: u64 = 0; i < 20; i += 1 {
+            switch i % 6 {
+                case 0: total += 1
+                case 1: total += 10
+                case 2: total += 20
+                case 3: total += 100
+                case 4: total += 1000
+                default: total += 10000
+            }
+        }
+        return total
+    }";
+
+    let (value, compiled) = run_jit(code, 0);
+    assert_eq!(value, Ok(Value::U64(33404)));
+    assert_eq!(compiled, 1);
+}
+
+#[test]
+fn test_jit_calls() {
+    let code = "struct Point { x: u64, y: u64 }
+
+    fn add(a: Point, b: Point) -> Point {
+        return Point { x: a.x + b.x, y: a.y + b.y }
+    }
+
+    fn sum(values: u64[]) -> u64 {
+        let total: u64 = 0
+        foreach value in values {
+            total += value
+        }
+        return total
+    }
+
+    entry main() {
+        let p: Point = Point { x: 0, y: 0 }
+        let values: u64[] = [1, 2, 3]
+        for i: u64 = 0; i < 10; i += 1 {
+            p = add(p, Point { x: i, y: sum(values) })
+        }
+        return p.x + p.y
+    }";
+
+    // The calls are resumed in the compiled caller
+    let (value, compiled) = run_jit(code, 2);
+    assert_eq!(value, Ok(Value::U64(105)));
+    assert_eq!(compiled, 3);
+}
+
+#[test]
+fn test_jit_native_call() {
+    // A chunk calling a native function stays interpreted
+    let code = "fn double(values: u64[]) -> u64 {
+        return values.len() as u64 * 2
+    }
+
+    entry main() {
+        let values: u64[] = [1, 2, 3]
+        return double(values)
+    }";
+
+    let (value, compiled) = run_jit(code, 1);
+    assert_eq!(value, Ok(Value::U64(6)));
+    assert_eq!(compiled, 1);
+}
+
+#[test]
+fn test_jit_errors() {
+    let code = "fn div(a: u64, b: u64) -> u64 {
+        return a / b
+    }
+
+    entry main() {
+        let total: u64 = 0
+        for i: u64 = 10; i > 0; i -= 1 {
+            total += div(100, i - 1)
+        }
+        return total
+    }";
+
+    let (value, _) = run_jit(code, 1);
+    assert_eq!(value, Err(VMError::DivisionByZero.to_string()));
+
+    let code = "entry main() {
+        let total: u64 = 0
+        while true {
+            total += 1
+        }
+        return total
+    }";
+
+    let (value, _) = run_jit(code, 0);
+    assert!(value.unwrap_err().contains("gas"));
+}
+
+#[test]
+fn test_jit_gas_failing_block() {
+    // The division fails in the middle of the basic block of the compiled chunk
+    let code = "fn div(a: u64, b: u64) -> u64 {
+        let c: u64 = a + b
+        let d: u64 = c * 2
+        return (d / b) + c
+    }
+
+    entry main() {
+        let total: u64 = 0
+        for i: u64 = 5; i > 0; i -= 1 {
+            total += div(100, i - 1)
+        }
+        return total
+    }";
+
+    for level in [OptimizationLevel::None, OptimizationLevel::Full] {
+        let (module, environment) = prepare_module(code, level);
+        let (value, used, _) = compare_jit(&module, &environment, 1, 1_000_000);
+        assert_eq!(value, Err(VMError::DivisionByZero.to_string()));
+
+        // Each limit runs out of gas at another instruction, before, in or after the block
+        // The division fails as soon as the gas of the instructions before it is available
+        for gas_limit in 0..used + 10 {
+            let (value, gas, _) = compare_jit(&module, &environment, 1, gas_limit);
+            let error = value.unwrap_err();
+            if gas_limit >= used {
+                assert_eq!(error, VMError::DivisionByZero.to_string());
+                assert_eq!(gas, used);
+            } else {
+                assert!(error.starts_with("not enough gas"), "{}", error);
+            }
+        }
+    }
+}
+
+#[test]
+fn test_jit_resume_paused() {
+    let code = "fn add(a: u64, b: u64) -> u64 {
+        return a + b
+    }
+
+    entry main() {
+        let total: u64 = 0
+        for i: u64 = 0; i < 100; i += 1 {
+            total = add(total, i)
+        }
+        return total
+    }";
+
+    let (module, environment) = prepare_module(code, OptimizationLevel::Full);
+    let mut vm = VM::new(&module, &environment);
+    vm.set_jit_threshold(1);
+    vm.invoke_entry_chunk(1).unwrap();
+
+    // Paused in the middle of a basic block, interpreted until the next one
+    assert!(matches!(vm.run_for(7).unwrap(), RunResult::Paused));
+    assert_eq!(vm.resume().unwrap(), ExecutionOutcome::Success(Value::U64(4950).into()));
+    assert_eq!(vm.jit.compiled_chunks(), 2);
+}
@@ -11,12 +11,20 @@ mod op_code;
 /// (Lexer, Parser, Compiler) to produce a valid Module.
 mod full;
 
+/// This module generates random programs and compares their results
+/// between the compiler configurations
+mod differential;
+
+/// This module compares the chunks compiled by the JIT with the interpreter
+#[cfg(feature = "jit")]
+mod jit;
+
 
 #[track_caller]
 fn run_internal(module: Module, environment: &Environment, id: u16) -> Result<Value, VMError> {
     let mut vm = VM::new(&module, environment);
     vm.invoke_chunk_id(id).unwrap();
-    vm.run().map(|v| v.into_value().unwrap())
+    vm.run().map(|v| v.into_result().unwrap().into_value().unwrap())
 }
 
 #[track_caller]
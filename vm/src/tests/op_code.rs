@@ -2,7 +2,7 @@ use super::*;
 
 use xelis_bytecode::{Chunk, Module, OpCode};
 use xelis_environment::EnvironmentError;
-use xelis_types::{Type, Value, ValueError};
+use xelis_types::{EnumVariant, Type, Value, ValueError};
 
 fn try_run(module: Module) -> Result<Value, VMError> {
     let env = EnvironmentBuilder::default().build();
@@ -33,7 +33,7 @@ fn test_pop_empty_stack() {
 
     module.add_chunk(chunk);
 
-    assert!(matches!(try_run(module), Err(VMError::EmptyStack)));
+    assert!(matches!(try_run(module).as_ref().map_err(VMError::inner), Err(VMError::EmptyStack)));
 }
 
 
@@ -69,7 +69,7 @@ fn test_pop_n_out_of_bounds() {
 
     module.add_chunk(chunk);
 
-    assert!(matches!(try_run(module), Err(VMError::StackIndexOutOfBounds)));
+    assert!(matches!(try_run(module).as_ref().map_err(VMError::inner), Err(VMError::StackIndexOutOfBounds)));
 }
 
 #[test]
@@ -204,13 +204,13 @@ fn test_struct() {
     vm.invoke_chunk_id(0).unwrap();
     assert_eq!(
         vm.run().unwrap(),
-        Constant::Struct(
+        ExecutionOutcome::Success(Constant::Struct(
             vec![
                 Value::U8(10).into(),
                 Value::U16(20).into()
             ].into(),
             new_struct
-        )
+        ))
     );
 
     let chunk = module.get_chunk_at_mut(0).unwrap();
@@ -248,7 +248,7 @@ fn test_struct() {
 
     let mut vm = VM::new(&module, &env);
     vm.invoke_chunk_id(0).unwrap();
-    assert_eq!(vm.run().unwrap(), Value::U16(30).into());
+    assert_eq!(vm.run().unwrap(), ExecutionOutcome::Success(Value::U16(30).into()));
 }
 
 #[test]
@@ -285,7 +285,7 @@ fn test_function_call() {
     let env = Environment::new();
     let mut vm = VM::new(&module, &env);
     vm.invoke_chunk_id(0).unwrap();
-    assert_eq!(vm.run().unwrap(), Value::Boolean(true).into());
+    assert_eq!(vm.run().unwrap(), ExecutionOutcome::Success(Value::Boolean(true).into()));
 }
 
 #[test]
@@ -326,7 +326,7 @@ fn test_function_call_on_value() {
     let env = Environment::new();
     let mut vm = VM::new(&module, &env);
     vm.invoke_chunk_id(0).unwrap();
-    assert_eq!(vm.run().unwrap(), Value::U64(10).into());
+    assert_eq!(vm.run().unwrap(), ExecutionOutcome::Success(Value::U64(10).into()));
 }
 
 #[test]
@@ -524,7 +524,7 @@ fn test_bad_program_recursive_infinite() {
 
     // Execute
     module.add_chunk(chunk);
-    assert!(matches!(try_run(module), Err(VMError::CallStackOverflow)));
+    assert!(matches!(try_run(module).as_ref().map_err(VMError::inner), Err(VMError::CallStackOverflow)));
 }
 
 #[test]
@@ -593,5 +593,318 @@ fn test_infinite_map_depth() {
     // Execute
     module.add_chunk(chunk);
 
-    assert!(matches!(try_run(module), Err(VMError::EnvironmentError(EnvironmentError::ValueError(ValueError::MaxDepthReached)))));
-}
\ No newline at end of file
+    assert!(matches!(try_run(module).as_ref().map_err(VMError::inner), Err(VMError::EnvironmentError(EnvironmentError::ValueError(ValueError::MaxDepthReached)))));
+}
+#[test]
+fn test_syscall_argument_is_instance() {
+    let mut module = Module::new();
+    let mut chunk = Chunk::new();
+
+    // let a = []
+    chunk.emit_opcode(OpCode::NewArray);
+    chunk.write_u8(0);
+    chunk.emit_opcode(OpCode::MemorySet);
+    chunk.write_u16(0);
+
+    // a.push(a), the argument is the instance mutably borrowed by the call
+    chunk.emit_opcode(OpCode::MemoryLoad);
+    chunk.write_u16(0);
+    chunk.emit_opcode(OpCode::MemoryLoad);
+    chunk.write_u16(0);
+    chunk.emit_opcode(OpCode::SysCall);
+    chunk.write_u16(1);
+    chunk.write_bool(true);
+    chunk.write_u8(1);
+
+    // return a.len()
+    chunk.emit_opcode(OpCode::MemoryLoad);
+    chunk.write_u16(0);
+    chunk.emit_opcode(OpCode::SysCall);
+    chunk.write_u16(0);
+    chunk.write_bool(true);
+    chunk.write_u8(0);
+    chunk.emit_opcode(OpCode::Return);
+
+    module.add_chunk(chunk);
+
+    assert_eq!(run(module), Value::U32(1));
+}
+
+#[test]
+fn test_arithmetic_errors() {
+    let run_op = |left: Value, right: Value, op: OpCode| {
+        let mut module = Module::new();
+        let mut chunk = Chunk::new();
+        for value in [left, right] {
+            let index = module.add_constant(value);
+            chunk.emit_opcode(OpCode::Constant);
+            chunk.write_u16(index as u16);
+        }
+        chunk.emit_opcode(op);
+        chunk.emit_opcode(OpCode::Return);
+        module.add_chunk(chunk);
+
+        try_run(module)
+    };
+
+    for op in [OpCode::Add, OpCode::Mul] {
+        let result = run_op(Value::U8(200), Value::U8(100), op);
+        assert!(matches!(result.as_ref().map_err(VMError::inner), Err(VMError::ArithmeticOverflow)), "{:?}", op);
+    }
+
+    let result = run_op(Value::U8(2), Value::U32(8), OpCode::Pow);
+    assert!(matches!(result.as_ref().map_err(VMError::inner), Err(VMError::ArithmeticOverflow)));
+
+    let result = run_op(Value::U64(1), Value::U64(2), OpCode::Sub);
+    assert!(matches!(result.as_ref().map_err(VMError::inner), Err(VMError::ArithmeticOverflow)));
+
+    let result = run_op(Value::U64(1), Value::U64(0), OpCode::Mod);
+    assert!(matches!(result.as_ref().map_err(VMError::inner), Err(VMError::DivisionByZero)));
+
+    for (value, op) in [(Value::U8(u8::MAX), OpCode::Inc), (Value::U64(0), OpCode::Dec)] {
+        let mut module = Module::new();
+        let mut chunk = Chunk::new();
+        let index = module.add_constant(value);
+        chunk.emit_opcode(OpCode::Constant);
+        chunk.write_u16(index as u16);
+        chunk.emit_opcode(op);
+        chunk.emit_opcode(OpCode::Return);
+        module.add_chunk(chunk);
+
+        let result = try_run(module);
+        assert!(matches!(result.as_ref().map_err(VMError::inner), Err(VMError::ValueError(ValueError::Overflow))), "{:?}", op);
+    }
+
+    assert_eq!(run_op(Value::U8(200), Value::U8(55), OpCode::Add).unwrap(), Value::U8(255));
+    assert_eq!(run_op(Value::U64(3), Value::U32(4), OpCode::Pow).unwrap(), Value::U64(81));
+}
+
+#[test]
+fn test_validator_invalid_call_and_jump() {
+    let environment = EnvironmentBuilder::default().build();
+
+    let mut module = Module::new();
+    let mut chunk = Chunk::new();
+    chunk.emit_opcode(OpCode::InvokeChunk);
+    chunk.write_u16(1);
+    chunk.write_bool(false);
+    chunk.write_u8(0);
+    chunk.emit_opcode(OpCode::Return);
+    module.add_chunk(chunk);
+
+    let result = ModuleValidator::new(&module, &environment).verify();
+    assert!(matches!(result, Err(ValidatorError::InvalidChunkCall(1))));
+
+    // jump in the middle of the arguments of the jump itself
+    let mut module = Module::new();
+    let mut chunk = Chunk::new();
+    chunk.emit_opcode(OpCode::Jump);
+    chunk.write_u32(2);
+    chunk.emit_opcode(OpCode::Return);
+    module.add_chunk(chunk);
+
+    let result = ModuleValidator::new(&module, &environment).verify();
+    assert!(matches!(result, Err(ValidatorError::InvalidJumpAddress(2))));
+}
+
+#[test]
+fn test_validator_mutual_recursion() {
+    let environment = EnvironmentBuilder::default().build();
+    let invoke = |chunk: &mut Chunk, id: u16| {
+        chunk.emit_opcode(OpCode::InvokeChunk);
+        chunk.write_u16(id);
+        chunk.write_bool(false);
+        chunk.write_u8(0);
+    };
+
+    // 0 calls 1, 1 and 2 call each other
+    let build = |returns: bool| {
+        let mut module = Module::new();
+        for id in [1, 2, 1] {
+            let mut chunk = Chunk::new();
+            if returns && id == 1 {
+                // 2 can return before calling 1
+                chunk.emit_opcode(OpCode::Constant);
+                chunk.write_u16(0);
+                chunk.emit_opcode(OpCode::JumpIfFalse);
+                chunk.write_u32(13);
+            }
+            invoke(&mut chunk, id);
+            chunk.emit_opcode(OpCode::Return);
+            module.add_chunk(chunk);
+        }
+        module.add_constant(Value::Boolean(false));
+        module
+    };
+
+    let module = build(false);
+    let result = ModuleValidator::new(&module, &environment).verify();
+    assert!(matches!(result, Err(ValidatorError::InfiniteRecursion(_))));
+
+    let module = build(true);
+    assert!(ModuleValidator::new(&module, &environment).verify().is_ok());
+    let config = ValidatorConfig {
+        allow_recursion: false,
+        ..Default::default()
+    };
+    let result = ModuleValidator::new(&module, &environment).with_config(config).verify();
+    assert!(matches!(result, Err(ValidatorError::RecursiveCall(_))));
+}
+
+#[test]
+fn test_validator_upgrade() {
+    let environment = EnvironmentBuilder::default().build();
+    let build = |fields: Vec<Vec<Type>>| {
+        let mut module = Module::new();
+        for (id, fields) in fields.into_iter().enumerate() {
+            module.add_struct(StructType::new(id as u16, fields));
+        }
+        let mut chunk = Chunk::new();
+        chunk.emit_opcode(OpCode::Return);
+        module.add_entry_chunk(chunk);
+        module
+    };
+
+    let previous = build(vec![vec![Type::U64, Type::String], vec![Type::Bool]]);
+    let validate = |module: &Module| ModuleValidator::new(module, &environment)
+        .with_previous(&previous)
+        .verify()
+        .map_err(|e| e.to_string());
+
+    // Fields appended
+    let module = build(vec![vec![Type::U64, Type::String, Type::U8], vec![Type::Bool], vec![]]);
+    assert!(validate(&module).is_ok());
+    assert!(module.structs()[0].is_layout_compatible(&previous.structs()[0]));
+
+    // Field changed of type
+    let mut module = build(vec![vec![Type::U64, Type::U8], vec![Type::Bool]]);
+    assert_eq!(validate(&module).unwrap_err(), ValidatorError::IncompatibleStructLayout(0).to_string());
+    module.add_migration(0, 0);
+    assert!(validate(&module).is_ok());
+
+    // Struct removed
+    let module = build(vec![vec![Type::U64, Type::String]]);
+    assert_eq!(validate(&module).unwrap_err(), ValidatorError::IncompatibleStructLayout(1).to_string());
+
+    // The migration must be done by an entry of the module
+    let mut module = build(vec![vec![Type::U64, Type::U8], vec![Type::Bool]]);
+    module.add_chunk(Chunk::new());
+    module.add_migration(0, 1);
+    assert!(matches!(ModuleValidator::new(&module, &environment).verify(), Err(ValidatorError::InvalidMigration(0))));
+}
+
+#[test]
+fn test_validator_config_limits() {
+    let environment = EnvironmentBuilder::default().build();
+
+    let mut module = Module::new();
+    module.add_struct(StructType::new(0, vec![Type::U64, Type::U64]));
+    let mut chunk = Chunk::new();
+    chunk.emit_opcode(OpCode::Constant);
+    chunk.write_u16(0);
+    chunk.emit_opcode(OpCode::Return);
+    module.add_chunk(chunk);
+    module.add_constant(Value::U64(0));
+
+    assert!(ModuleValidator::new(&module, &environment).verify().is_ok());
+
+    let validate = |config: ValidatorConfig| ModuleValidator::new(&module, &environment)
+        .with_config(config)
+        .verify()
+        .map_err(|e| e.to_string());
+
+    let error = validate(ValidatorConfig { max_chunk_size: 3, ..Default::default() }).unwrap_err();
+    assert_eq!(error, ValidatorError::ChunkTooLarge(0).to_string());
+    assert!(validate(ValidatorConfig { max_chunk_size: 4, ..Default::default() }).is_ok());
+
+    let error = validate(ValidatorConfig { max_struct_fields: 1, ..Default::default() }).unwrap_err();
+    assert_eq!(error, "too many struct fields");
+
+    let error = validate(ValidatorConfig { max_constants: 0, ..Default::default() }).unwrap_err();
+    assert_eq!(error, ValidatorError::TooManyConstants.to_string());
+
+    let size = module.to_bytes().unwrap().len();
+    assert!(validate(ValidatorConfig { max_module_size: Some(size), ..Default::default() }).is_ok());
+    let error = validate(ValidatorConfig { max_module_size: Some(size - 1), ..Default::default() }).unwrap_err();
+    assert_eq!(error, ValidatorError::ModuleTooLarge(size).to_string());
+}
+
+#[test]
+fn test_validator_recursive_types() {
+    let environment = EnvironmentBuilder::default().build();
+    // Types refer to each other by their id, so the fields of an inner type are not needed
+    let struct_ref = |id| Type::Struct(StructType::new(id, Vec::new()));
+    let enum_ref = |id| Type::Enum(EnumType::new(id, Vec::new()));
+
+    // A { b: B }, B { a: optional<A> }
+    let mut module = Module::new();
+    module.add_struct(StructType::new(0, vec![struct_ref(1)]));
+    module.add_struct(StructType::new(1, vec![Type::Optional(Box::new(struct_ref(0)))]));
+    let result = ModuleValidator::new(&module, &environment).verify();
+    assert!(matches!(result, Err(ValidatorError::RecursiveStruct(_))));
+
+    // A { values: map<u64, A[]> }
+    let mut module = Module::new();
+    module.add_struct(StructType::new(0, vec![Type::Map(Box::new(Type::U64), Box::new(Type::Array(Box::new(struct_ref(0)))))]));
+    let result = ModuleValidator::new(&module, &environment).verify();
+    assert!(matches!(result, Err(ValidatorError::RecursiveStruct(s)) if s.id() == 0));
+
+    // enum E { Some(A) }, A { e: (u8, E) }
+    let mut module = Module::new();
+    module.add_struct(StructType::new(0, vec![Type::Tuple(vec![Type::U8, enum_ref(0)])]));
+    module.add_enum(EnumType::new(0, vec![EnumVariant::new(Vec::new()), EnumVariant::new(vec![struct_ref(0)])]));
+    let result = ModuleValidator::new(&module, &environment).verify();
+    assert!(matches!(result, Err(ValidatorError::RecursiveStruct(_) | ValidatorError::RecursiveEnum(_))));
+
+    // A { b: B[], e: E }, B { e: optional<E> }, enum E { None, Some(u64) } has no cycle
+    let mut module = Module::new();
+    module.add_struct(StructType::new(0, vec![Type::Array(Box::new(struct_ref(1))), enum_ref(0)]));
+    module.add_struct(StructType::new(1, vec![Type::Optional(Box::new(enum_ref(0)))]));
+    module.add_enum(EnumType::new(0, vec![EnumVariant::new(Vec::new()), EnumVariant::new(vec![Type::U64])]));
+    assert!(ModuleValidator::new(&module, &environment).verify().is_ok());
+}
+
+#[test]
+fn test_compiled_chunk() {
+    // Push 20 instead of the constant 10 of the bytecode
+    fn compiled<'a>(_: &Backend<'a>, stack: &mut Stack<'a>, manager: &mut ChunkManager<'a>, context: &mut Context<'a>) -> Result<InstructionResult, VMError> {
+        context.increase_gas_usage(1)?;
+        manager.set_index(3)?;
+        stack.push_stack(Path::Owned(Value::U8(20).into()))?;
+        Ok(InstructionResult::Break)
+    }
+
+    struct Counter(usize);
+    impl Tracer for Counter {
+        fn on_instruction(&mut self, _: u16, _: usize, _: u8, _: u64) {
+            self.0 += 1;
+        }
+    }
+
+    let mut module = Module::new();
+    let mut chunk = Chunk::new();
+    chunk.emit_opcode(OpCode::Constant);
+    chunk.write_u16(module.add_constant(Value::U8(10)) as u16);
+    module.add_chunk(chunk);
+
+    let env = EnvironmentBuilder::default().build();
+    let mut vm = VM::new(&module, &env);
+    vm.set_compiled_chunk(&module, 0, compiled);
+    vm.invoke_chunk_id(0).unwrap();
+    assert_eq!(vm.run().unwrap(), ExecutionOutcome::Success(Value::U8(20).into()));
+
+    // The chunk is interpreted to be traced or executed with a budget
+    let mut counter = Counter(0);
+    let mut vm = VM::new(&module, &env);
+    vm.set_compiled_chunk(&module, 0, compiled);
+    vm.context_mut().set_tracer(&mut counter);
+    vm.invoke_chunk_id(0).unwrap();
+    assert_eq!(vm.run().unwrap(), ExecutionOutcome::Success(Value::U8(10).into()));
+    drop(vm);
+    assert_eq!(counter.0, 1);
+
+    let mut vm = VM::new(&module, &env);
+    vm.set_compiled_chunk(&module, 0, compiled);
+    vm.invoke_chunk_id(0).unwrap();
+    assert!(matches!(vm.run_for(10).unwrap(), RunResult::Finished(v) if v == Value::U8(10).into()));
+}
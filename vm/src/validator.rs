@@ -1,9 +1,10 @@
+use alloc::{vec, vec::Vec};
 use thiserror::Error;
 use xelis_environment::Environment;
-use xelis_types::{EnumType, EnumVariant, StructType, Type, Value, ValueError, Constant};
-use xelis_bytecode::{Module, OpCode};
+use xelis_types::{EnumType, EnumVariant, HashSet, StructType, Type, Value, ValueError, Constant, ENVIRONMENT_ENUM_ID_START};
+use xelis_bytecode::{Chunk, Module, ModuleFeatures, ModuleVersion, OpCode, SerializerError};
 
-use crate::ChunkReader;
+use crate::{ChunkReader, CALL_STACK_SIZE};
 
 #[derive(Debug, Error)]
 pub enum ValidatorError<'a> {
@@ -15,6 +16,10 @@ pub enum ValidatorError<'a> {
     ConstantTooDeep,
     #[error("too many chunks")]
     TooManyChunks,
+    #[error("chunk {0} is too large")]
+    ChunkTooLarge(u16),
+    #[error("module of {0} bytes is too large")]
+    ModuleTooLarge(usize),
     #[error("too many types")]
     TooManyTypes,
 
@@ -50,20 +55,147 @@ pub enum ValidatorError<'a> {
     ReferenceNotAllowed,
     #[error("map as key not allowed")]
     MapAsKeyNotAllowed,
+    #[error("invalid call to chunk {0}")]
+    InvalidChunkCall(u16),
+    #[error("invalid jump address {0}")]
+    InvalidJumpAddress(u32),
+    #[error("jump table at offset {0} is not followed by its jumps")]
+    InvalidJumpTable(usize),
+    #[error("recursive call in chunk {0}")]
+    RecursiveCall(u16),
+    #[error("chunk {0} can't return without calling itself")]
+    InfiniteRecursion(u16),
+    #[error("call depth {0} is above the maximum of {1}")]
+    CallDepthExceeded(usize, usize),
+    #[error("non deterministic operation in chunk {0}: syscall {1}")]
+    NonDeterministicOperation(u16, u16),
+    #[error("unknown precompile in chunk {0}: precompile {1}")]
+    UnknownPrecompile(u16, u16),
+    #[error("module version {0} is not supported")]
+    UnsupportedVersion(ModuleVersion),
+    #[error("unsupported features {0}")]
+    UnsupportedFeatures(ModuleFeatures),
+    #[error("chunk {0} uses the undeclared feature of {1:?}")]
+    UndeclaredFeature(u16, OpCode),
+    #[error("invalid migration of struct {0}")]
+    InvalidMigration(usize),
+    #[error("layout of struct {0} changed without a migration")]
+    IncompatibleStructLayout(usize),
     #[error(transparent)]
-    ValueError(#[from] ValueError)
+    ValueError(#[from] ValueError),
+    #[error(transparent)]
+    SerializerError(#[from] SerializerError)
+}
+
+// Instruction decoded for the analysis of the calls
+#[derive(Debug)]
+enum Flow {
+    // Continue to the next instruction
+    Next,
+    // Go to the address
+    Jump(u32),
+    // Go to the address or to the next instruction
+    Branch(u32),
+    // Go to the default address or to one of the N jumps following
+    Table(u32, u16),
+    // Invoke a chunk of the module and continue to the next instruction
+    Call(u16),
+    // Give a chunk of the module to a native, which may invoke it later
+    Reference(u16),
+    Return,
+}
+
+// Limits enforced by the validator
+// The counts can't go above the limits of the module format, like 65535 chunks
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ValidatorConfig {
+    // Maximum depth of a constant
+    pub constant_max_depth: usize,
+    // Maximum memory used by all the constants
+    pub constant_max_memory: usize,
+    pub max_constants: usize,
+    pub max_chunks: usize,
+    // Maximum size of the instructions of a chunk, in bytes
+    pub max_chunk_size: usize,
+    pub max_structs: usize,
+    pub max_struct_fields: usize,
+    pub max_enums: usize,
+    pub max_enum_variants: usize,
+    pub max_enum_variant_fields: usize,
+    // Maximum size of the serialized module, None for no limit
+    pub max_module_size: Option<usize>,
+    // Maximum count of chunks in a chain of calls, the entry included
+    pub max_call_depth: usize,
+    // Are the chunks allowed to call themselves, directly or not
+    // A recursion that can never return is always rejected
+    pub allow_recursion: bool,
+    // Reject the calls to the natives which may diverge between hosts
+    // Required when the executions must be replayed by other nodes, like in a consensus
+    pub strict_determinism: bool,
+    // Latest version of the bytecode supported
+    pub supported_version: ModuleVersion,
+    // Features the modules are allowed to use
+    // Restricting it lets a network enable the new opcodes at an activation height
+    pub supported_features: ModuleFeatures,
+}
+
+impl Default for ValidatorConfig {
+    fn default() -> Self {
+        Self {
+            constant_max_depth: 16,
+            constant_max_memory: 1024,
+            max_constants: u16::MAX as usize,
+            max_chunks: u16::MAX as usize,
+            max_chunk_size: u32::MAX as usize,
+            max_structs: u16::MAX as usize,
+            max_struct_fields: u8::MAX as usize,
+            max_enums: u16::MAX as usize,
+            max_enum_variants: u8::MAX as usize,
+            max_enum_variant_fields: u8::MAX as usize,
+            max_module_size: None,
+            max_call_depth: CALL_STACK_SIZE,
+            allow_recursion: true,
+            strict_determinism: false,
+            supported_version: ModuleVersion::CURRENT,
+            supported_features: ModuleFeatures::ALL,
+        }
+    }
 }
 
 pub struct ModuleValidator<'a> {
     module: &'a Module,
     environment: &'a Environment,
-    constant_max_depth: usize,
-    constant_max_memory: usize
+    config: ValidatorConfig,
+    // Version of the module being upgraded
+    previous: Option<&'a Module>,
 }
 
 impl<'a> ModuleValidator<'a> {
     pub fn new(module: &'a Module, environment: &'a Environment) -> Self {
-        Self { module, environment, constant_max_depth: 16, constant_max_memory: 1024 }
+        Self {
+            module,
+            environment,
+            config: ValidatorConfig::default(),
+            previous: None,
+        }
+    }
+
+    // Use other limits than the default ones
+    pub fn with_config(mut self, config: ValidatorConfig) -> Self {
+        self.config = config;
+        self
+    }
+
+    // Verify the module as an upgrade of the previous one
+    // The values stored by the previous module must stay readable
+    pub fn with_previous(mut self, previous: &'a Module) -> Self {
+        self.previous = Some(previous);
+        self
+    }
+
+    // Limits used by the validator
+    pub fn config(&self) -> &ValidatorConfig {
+        &self.config
     }
 
     // Due to the use of ValuePointer, we need to clone each value as we can't keep one reference
@@ -72,13 +204,13 @@ impl<'a> ModuleValidator<'a> {
         let mut memory_usage = 0;
 
         while let Some((value, depth)) = stack.pop() {
-            if depth > self.constant_max_depth {
+            if depth > self.config.constant_max_depth {
                 return Err(ValidatorError::ConstantTooDeep);
             }
 
             // Increase by one for the byte type of the value
             memory_usage += 1;
-            if memory_usage > self.constant_max_memory {
+            if memory_usage > self.config.constant_max_memory {
                 return Err(ValidatorError::TooMuchMemoryUsage);
             }
 
@@ -150,7 +282,7 @@ impl<'a> ModuleValidator<'a> {
                     }
                 },
                 Constant::Default(v) => match v {
-                    Value::Range(left, right, _type) => {
+                    Value::Range(left, right, _type, _) => {
                         if !left.is_number() || !right.is_number() {
                             return Err(ValidatorError::InvalidRange);
                         }
@@ -189,7 +321,7 @@ impl<'a> ModuleValidator<'a> {
         for c in self.module.constants() {
             memory_usage += self.verify_value(&c.0)?;
 
-            if memory_usage > self.constant_max_memory {
+            if memory_usage > self.config.constant_max_memory {
                 return Err(ValidatorError::TooManyConstants);
             }
         }
@@ -200,12 +332,48 @@ impl<'a> ModuleValidator<'a> {
     // Verify all the declared chunks in the module
     // We verify that the opcodes are valid and that the count of arguments are correct
     fn verify_chunks(&self) -> Result<(), ValidatorError<'a>> {
-        for chunk in self.module.chunks() {
+        for (id, chunk) in self.module.chunks().iter().enumerate() {
+            if chunk.get_instructions().len() > self.config.max_chunk_size {
+                return Err(ValidatorError::ChunkTooLarge(id as u16));
+            }
+
             let mut reader = ChunkReader::new(chunk);
             while let Some(instruction) = reader.next_u8() {
                 let op = OpCode::from_byte(instruction)
                     .ok_or(ValidatorError::InvalidOpCode)?;
 
+                if !self.module.features().contains(op.required_feature()) {
+                    return Err(ValidatorError::UndeclaredFeature(id as u16, op));
+                }
+
+                if op == OpCode::SysCall && self.config.strict_determinism {
+                    let syscall = reader.read_u16()
+                        .map_err(|_| ValidatorError::InvalidOpCodeArguments)?;
+
+                    // An unknown syscall is an error at the execution
+                    let deterministic = self.environment.get_functions()
+                        .get(syscall as usize)
+                        .is_none_or(|f| f.is_deterministic());
+
+                    if !deterministic {
+                        return Err(ValidatorError::NonDeterministicOperation(id as u16, syscall));
+                    }
+
+                    reader.advance(op.arguments_bytes() - 2)
+                        .map_err(|_| ValidatorError::InvalidOpCodeArguments)?;
+                    continue;
+                }
+
+                if op == OpCode::CallPrecompile {
+                    let precompile = reader.read_u16()
+                        .map_err(|_| ValidatorError::InvalidOpCodeArguments)?;
+
+                    if self.environment.get_precompile(precompile).is_none() {
+                        return Err(ValidatorError::UnknownPrecompile(id as u16, precompile));
+                    }
+                    continue;
+                }
+
                 reader.advance(op.arguments_bytes())
                     .map_err(|_| ValidatorError::InvalidOpCodeArguments)?;
             }
@@ -214,27 +382,191 @@ impl<'a> ModuleValidator<'a> {
         Ok(())
     }
 
+    // Decode the control flow of a chunk, by offset of its instructions
+    fn decode_flow(&self, chunk: &Chunk) -> Result<Vec<(usize, Flow)>, ValidatorError<'a>> {
+        let mut reader = ChunkReader::new(chunk);
+        let mut instructions = Vec::new();
+        while let Some(instruction) = reader.next_u8() {
+            let offset = reader.index() - 1;
+            let op = OpCode::from_byte(instruction)
+                .ok_or(ValidatorError::InvalidOpCode)?;
+
+            let flow = match op {
+                OpCode::Jump => Flow::Jump(reader.read_u32().map_err(|_| ValidatorError::InvalidOpCodeArguments)?),
+                OpCode::JumpTable => {
+                    let addr = reader.read_u32().map_err(|_| ValidatorError::InvalidOpCodeArguments)?;
+                    reader.advance(2).map_err(|_| ValidatorError::InvalidOpCodeArguments)?;
+                    let len = reader.read_u16().map_err(|_| ValidatorError::InvalidOpCodeArguments)?;
+                    Flow::Table(addr, len)
+                },
+                op if op.is_jump() => Flow::Branch(reader.read_u32().map_err(|_| ValidatorError::InvalidOpCodeArguments)?),
+                OpCode::InvokeChunk => {
+                    let id = reader.read_u16().map_err(|_| ValidatorError::InvalidOpCodeArguments)?;
+                    reader.advance(op.arguments_bytes() - 2)
+                        .map_err(|_| ValidatorError::InvalidOpCodeArguments)?;
+
+                    if id as usize >= self.module.chunks().len() || self.module.is_entry_chunk(id as usize) {
+                        return Err(ValidatorError::InvalidChunkCall(id));
+                    }
+
+                    Flow::Call(id)
+                },
+                OpCode::ChunkId => {
+                    let id = reader.read_u16().map_err(|_| ValidatorError::InvalidOpCodeArguments)?;
+                    if id as usize >= self.module.chunks().len() || self.module.is_entry_chunk(id as usize) {
+                        return Err(ValidatorError::InvalidChunkCall(id));
+                    }
+
+                    Flow::Reference(id)
+                },
+                OpCode::Return => Flow::Return,
+                _ => {
+                    reader.advance(op.arguments_bytes())
+                        .map_err(|_| ValidatorError::InvalidOpCodeArguments)?;
+                    Flow::Next
+                }
+            };
+
+            instructions.push((offset, flow));
+        }
+
+        // A jump must land on an instruction, or at the end of the chunk to return
+        let len = chunk.get_instructions().len();
+        for (i, (offset, flow)) in instructions.iter().enumerate() {
+            // The VM computes the address of the jump of a case from its index
+            if let Flow::Table(_, n) = flow {
+                let jumps = instructions.get(i + 1..i + 1 + *n as usize)
+                    .ok_or(ValidatorError::InvalidJumpTable(*offset))?;
+                if !jumps.iter().all(|(_, flow)| matches!(flow, Flow::Jump(_))) {
+                    return Err(ValidatorError::InvalidJumpTable(*offset));
+                }
+            }
+
+            if let Flow::Jump(addr) | Flow::Branch(addr) | Flow::Table(addr, _) = flow {
+                let addr = *addr as usize;
+                if addr != len && instructions.binary_search_by_key(&addr, |(offset, _)| *offset).is_err() {
+                    return Err(ValidatorError::InvalidJumpAddress(addr as u32));
+                }
+            }
+        }
+
+        Ok(instructions)
+    }
+
+    // Can the chunk reach a return without calling one of the recursive chunks
+    fn can_return(instructions: &[(usize, Flow)], is_recursive: impl Fn(u16) -> bool) -> bool {
+        let index_of = |addr: u32| instructions.binary_search_by_key(&(addr as usize), |(offset, _)| *offset).ok();
+
+        let mut visited: HashSet<usize> = HashSet::default();
+        let mut pending = vec![0];
+        while let Some(index) = pending.pop() {
+            // Going past the last instruction ends the chunk
+            let Some((_, flow)) = instructions.get(index) else {
+                return true
+            };
+
+            if !visited.insert(index) {
+                continue
+            }
+
+            match flow {
+                Flow::Return => return true,
+                Flow::Call(id) if is_recursive(*id) => {},
+                Flow::Next | Flow::Call(_) | Flow::Reference(_) => pending.push(index + 1),
+                // A missing address is the end of the chunk
+                Flow::Jump(addr) => pending.push(index_of(*addr).unwrap_or(instructions.len())),
+                Flow::Branch(addr) => {
+                    pending.push(index + 1);
+                    pending.push(index_of(*addr).unwrap_or(instructions.len()));
+                },
+                Flow::Table(addr, n) => {
+                    pending.extend(index + 1..index + 1 + *n as usize);
+                    pending.push(index_of(*addr).unwrap_or(instructions.len()));
+                }
+            }
+        }
+
+        false
+    }
+
+    // Verify the calls between the chunks
+    // The call graph is split into its strongly connected components, a component
+    // with several chunks or a chunk calling itself is a recursion
+    fn verify_calls(&self) -> Result<(), ValidatorError<'a>> {
+        let flows = self.module.chunks()
+            .iter()
+            .map(|chunk| self.decode_flow(chunk))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let graph: Vec<Vec<u16>> = flows.iter()
+            .map(|instructions| {
+                let mut callees: Vec<u16> = instructions.iter()
+                    .filter_map(|(_, flow)| match flow {
+                        Flow::Call(id) | Flow::Reference(id) => Some(*id),
+                        _ => None
+                    })
+                    .collect();
+                callees.sort_unstable();
+                callees.dedup();
+                callees
+            })
+            .collect();
+
+        let components = strongly_connected_components(&graph);
+        let mut component_of = vec![0; graph.len()];
+        for (i, component) in components.iter().enumerate() {
+            for id in component {
+                component_of[*id] = i;
+            }
+        }
+
+        // Components are sorted with the callees first,
+        // so the depth of each callee is known before its callers
+        let mut depths = vec![0; components.len()];
+        for (i, component) in components.iter().enumerate() {
+            let recursive = component.len() > 1 || graph[component[0]].contains(&(component[0] as u16));
+            if recursive {
+                if !self.config.allow_recursion {
+                    return Err(ValidatorError::RecursiveCall(component[0] as u16));
+                }
+
+                let is_recursive = |id: u16| component_of[id as usize] == i;
+                if !component.iter().any(|id| Self::can_return(&flows[*id], is_recursive)) {
+                    return Err(ValidatorError::InfiniteRecursion(component[0] as u16));
+                }
+            }
+
+            let depth = 1 + component.iter()
+                .flat_map(|id| &graph[*id])
+                .map(|callee| component_of[*callee as usize])
+                .filter(|c| *c != i)
+                .map(|c| depths[c])
+                .max()
+                .unwrap_or(0);
+
+            if depth > self.config.max_call_depth {
+                return Err(ValidatorError::CallDepthExceeded(depth, self.config.max_call_depth));
+            }
+            depths[i] = depth;
+        }
+
+        Ok(())
+    }
+
     // Verify the enums integrity
     fn verify_enums(&self) -> Result<(), ValidatorError<'a>> {
         // No need to check the ids, they are already checked by the Module
         for e in self.module.enums() {
             // Verify the variants
-            if e.variants().len() > u8::MAX as usize {
+            if e.variants().len() > self.config.max_enum_variants.min(u8::MAX as usize) {
                 return Err(ValidatorError::TooManyEnumsVariants);
             }
 
             for variant in e.variants() {
-                if variant.fields().len() > u8::MAX as usize {
+                if variant.fields().len() > self.config.max_enum_variant_fields.min(u8::MAX as usize) {
                     return Err(ValidatorError::TooManyEnumsVariantsFields(variant));
                 }
 
-                for field in variant.fields() {
-                    if let Type::Enum(inner) = field {
-                        if e == inner {
-                            return Err(ValidatorError::RecursiveEnum(e));
-                        }
-                    }
-                }
             }
         }
 
@@ -245,39 +577,128 @@ impl<'a> ModuleValidator<'a> {
     fn verify_structs(&self) -> Result<(), ValidatorError<'a>> {
         // No need to check the ids, they are already checked by the Module
         for s in self.module.structs() {
-            if s.fields().len() > u8::MAX as usize {
+            if s.fields().len() > self.config.max_struct_fields.min(u8::MAX as usize) {
                 return Err(ValidatorError::TooManyStructFields(s));
             }
+        }
+        Ok(())
+    }
 
-            for field in s.fields() {
-                if let Type::Struct(inner) = field {
-                    if s == inner {
-                        return Err(ValidatorError::RecursiveStruct(s));
-                    }
-                }
+    // Verify that no struct or enum contains itself, directly or through other types
+    // A type can't be recursive even behind an array, an optional or a map
+    fn verify_types(&self) -> Result<(), ValidatorError<'a>> {
+        let structs = self.module.structs();
+        let enums = self.module.enums();
+
+        // Nodes of the graph are the structs followed by the enums
+        // An edge goes from a type to each type used by its fields
+        let mut graph = Vec::with_capacity(structs.len() + enums.len());
+        for s in structs {
+            graph.push(self.type_dependencies(s.fields().iter()));
+        }
+
+        for e in enums {
+            graph.push(self.type_dependencies(e.variants().iter().flat_map(EnumVariant::fields)));
+        }
+
+        for component in strongly_connected_components(&graph) {
+            let node = component[0];
+            let recursive = component.len() > 1 || graph[node].contains(&(node as u16));
+            if recursive {
+                return Err(match structs.get_index(node) {
+                    Some(s) => ValidatorError::RecursiveStruct(s),
+                    None => ValidatorError::RecursiveEnum(&enums[node - structs.len()])
+                });
             }
         }
+
+        Ok(())
+    }
+
+    // Nodes of the structs and enums found in the types, inner types included
+    // Types unknown to the module are ignored
+    fn type_dependencies(&self, types: impl Iterator<Item = &'a Type>) -> Vec<u16> {
+        let mut dependencies = Vec::new();
+        let mut stack: Vec<&Type> = types.collect();
+        while let Some(ty) = stack.pop() {
+            match ty {
+                Type::Array(inner) | Type::Optional(inner) | Type::Range(inner) | Type::Iterator(inner) => stack.push(inner),
+                Type::Map(key, value) | Type::Result(key, value) => {
+                    stack.push(key);
+                    stack.push(value);
+                },
+                Type::Tuple(types) => stack.extend(types),
+                Type::Struct(s) => if let Some(index) = self.module.structs().get_index_of(s) {
+                    dependencies.push(index as u16);
+                },
+                Type::Enum(e) => if let Some(index) = self.module.enums().get_index_of(e) {
+                    dependencies.push((self.module.structs().len() + index) as u16);
+                },
+                _ => {}
+            }
+        }
+
+        dependencies.sort_unstable();
+        dependencies.dedup();
+        dependencies
+    }
+
+    // Verify that each migration points to a struct and to an entry of the module
+    fn verify_migrations(&self) -> Result<(), ValidatorError<'a>> {
+        for (struct_index, chunk_id) in self.module.migrations() {
+            if *struct_index >= self.module.structs().len() || !self.module.is_entry_chunk(*chunk_id) {
+                return Err(ValidatorError::InvalidMigration(*struct_index));
+            }
+        }
+
+        Ok(())
+    }
+
+    // Verify that each struct of the previous module is kept with a compatible layout
+    // A struct changed or removed on purpose must be declared in the migrations
+    fn verify_upgrade(&self, previous: &Module) -> Result<(), ValidatorError<'a>> {
+        for (index, old) in previous.structs().iter().enumerate() {
+            let compatible = self.module.get_struct_at(index)
+                .is_some_and(|new| new.is_layout_compatible(old));
+
+            if !compatible && self.module.get_migration(index).is_none() {
+                return Err(ValidatorError::IncompatibleStructLayout(index));
+            }
+        }
+
         Ok(())
     }
 
     // Verify the module integrity and return an error if it's invalid
     pub fn verify(&self) -> Result<(), ValidatorError<'a>> {
+        // A newer module may rely on a behavior unknown to this VM
+        let version = self.module.version();
+        if !version.is_supported_by(&self.config.supported_version) {
+            return Err(ValidatorError::UnsupportedVersion(version));
+        }
+
+        let unsupported = self.module.features().difference(self.config.supported_features);
+        if !unsupported.is_empty() {
+            return Err(ValidatorError::UnsupportedFeatures(unsupported));
+        }
+
         let max = u16::MAX as usize;
 
         // We support max of 65535 constants, chunks, structs and enums
-        if self.module.constants().len() >= max {
+        if self.module.constants().len() >= max || self.module.constants().len() > self.config.max_constants {
             return Err(ValidatorError::TooManyConstants);
         }
 
-        if self.module.chunks().len() >= max {
+        if self.module.chunks().len() >= max || self.module.chunks().len() > self.config.max_chunks {
             return Err(ValidatorError::TooManyChunks);
         }
 
-        if self.module.structs().len() >= max {
+        if self.module.structs().len() >= max || self.module.structs().len() > self.config.max_structs {
             return Err(ValidatorError::TooManyStructs);
         }
 
-        if self.module.enums().len() >= max {
+        // The ids after them are the environment ones
+        if self.module.enums().len() >= ENVIRONMENT_ENUM_ID_START as usize || self.module.enums().len() > self.config.max_enums {
             return Err(ValidatorError::TooManyEnums);
         }
 
@@ -287,11 +708,88 @@ impl<'a> ModuleValidator<'a> {
             return Err(ValidatorError::TooManyTypes);
         }
 
+        if let Some(max_module_size) = self.config.max_module_size {
+            let size = self.module.to_bytes()?.len();
+            if size > max_module_size {
+                return Err(ValidatorError::ModuleTooLarge(size));
+            }
+        }
+
         self.verify_enums()?;
         self.verify_structs()?;
+        self.verify_types()?;
         self.verify_constants()?;
         self.verify_chunks()?;
+        self.verify_calls()?;
+        self.verify_migrations()?;
+
+        if let Some(previous) = self.previous {
+            self.verify_upgrade(previous)?;
+        }
 
         Ok(())
     }
+}
+
+// Tarjan's algorithm, iterative so a long chain of calls can't overflow the stack
+// The components are returned in reverse topological order: a component comes after all the ones it calls
+fn strongly_connected_components(graph: &[Vec<u16>]) -> Vec<Vec<usize>> {
+    let mut index = vec![usize::MAX; graph.len()];
+    let mut low_link = vec![0; graph.len()];
+    let mut on_stack = vec![false; graph.len()];
+    let mut stack = Vec::new();
+    let mut components = Vec::new();
+    let mut next_index = 0;
+
+    for root in 0..graph.len() {
+        if index[root] != usize::MAX {
+            continue
+        }
+
+        // Node with the position of the next edge to visit
+        let mut calls = vec![(root, 0)];
+        index[root] = next_index;
+        low_link[root] = next_index;
+        next_index += 1;
+        stack.push(root);
+        on_stack[root] = true;
+
+        while let Some((node, edge)) = calls.last_mut() {
+            let node = *node;
+            if let Some(callee) = graph[node].get(*edge) {
+                *edge += 1;
+                let callee = *callee as usize;
+                if index[callee] == usize::MAX {
+                    index[callee] = next_index;
+                    low_link[callee] = next_index;
+                    next_index += 1;
+                    stack.push(callee);
+                    on_stack[callee] = true;
+                    calls.push((callee, 0));
+                } else if on_stack[callee] {
+                    low_link[node] = low_link[node].min(index[callee]);
+                }
+                continue
+            }
+
+            calls.pop();
+            if let Some((caller, _)) = calls.last() {
+                low_link[*caller] = low_link[*caller].min(low_link[node]);
+            }
+
+            if low_link[node] == index[node] {
+                let mut component = Vec::new();
+                while let Some(id) = stack.pop() {
+                    on_stack[id] = false;
+                    component.push(id);
+                    if id == node {
+                        break
+                    }
+                }
+                components.push(component);
+            }
+        }
+    }
+
+    components
 }
\ No newline at end of file
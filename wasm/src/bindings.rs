@@ -0,0 +1,25 @@
+use serde::Serialize;
+use serde_wasm_bindgen::Serializer;
+use wasm_bindgen::prelude::*;
+
+// Compile a source code into a serialized module
+#[wasm_bindgen]
+pub fn compile(source: &str) -> Result<Vec<u8>, JsError> {
+    super::compile(source)
+        .map_err(|e| JsError::new(&e))
+}
+
+// Run an entry of a serialized module
+// Returns an object with the `value` returned and the `gas_usage`
+// The integers above 32 bits are converted to BigInt to not lose any precision
+#[wasm_bindgen]
+pub fn run(bytes: &[u8], entry: &str, gas: u64) -> Result<JsValue, JsError> {
+    let execution = super::run(bytes, entry, gas)
+        .map_err(|e| JsError::new(&e))?;
+
+    let serializer = Serializer::new()
+        .serialize_large_number_types_as_bigints(true);
+
+    execution.serialize(&serializer)
+        .map_err(|e| JsError::new(&e.to_string()))
+}
@@ -0,0 +1,106 @@
+// Compile and run programs from a WebAssembly host
+// The functions below are plain Rust, the `wasm-bindgen` feature exposes them to JS
+// so web playgrounds and browser wallets can validate and simulate contracts client-side
+
+#[cfg(feature = "wasm-bindgen")]
+mod bindings;
+
+use xelis_builder::EnvironmentBuilder;
+use xelis_bytecode::Module;
+use xelis_compiler::Compiler;
+use xelis_lexer::Lexer;
+use xelis_parser::Parser;
+use xelis_types::Constant;
+use xelis_vm::{ModuleValidator, VM};
+
+// Result of an execution
+#[derive(Debug)]
+#[cfg_attr(feature = "wasm-bindgen", derive(serde::Serialize))]
+pub struct Execution {
+    pub value: Constant,
+    // The value was given to a revert
+    pub reverted: bool,
+    pub gas_usage: u64,
+}
+
+// Compile a source code into a serialized module
+pub fn compile(source: &str) -> Result<Vec<u8>, String> {
+    let environment = EnvironmentBuilder::default();
+    let tokens = Lexer::new(source).collect::<Result<Vec<_>, _>>()
+        .map_err(|e| e.to_string())?;
+
+    let (program, _) = Parser::with(tokens.into_iter(), &environment)
+        .parse()
+        .map_err(|e| e.with_source(source).to_string())?;
+
+    let module = Compiler::new(&program, environment.environment())
+        .compile()
+        .map_err(|e| e.to_string())?;
+
+    module.to_bytes()
+        .map_err(|e| e.to_string())
+}
+
+// Run an entry of a serialized module by its name
+// The module is validated first as it may come from an untrusted source
+pub fn run(bytes: &[u8], entry: &str, gas_limit: u64) -> Result<Execution, String> {
+    let module = Module::from_bytes(bytes)
+        .map_err(|e| format!("invalid module: {}", e))?;
+
+    let environment = EnvironmentBuilder::default();
+    let environment = environment.environment();
+    ModuleValidator::new(&module, environment)
+        .verify()
+        .map_err(|e| e.to_string())?;
+
+    let id = module.entry_by_name(entry)
+        .ok_or_else(|| format!("entry '{}' not found", entry))?;
+
+    let mut vm = VM::new(&module, environment);
+    vm.context_mut().set_gas_limit(gas_limit);
+    vm.invoke_entry_chunk(id as u16)
+        .map_err(|e| e.to_string())?;
+
+    let outcome = vm.run().map_err(|e| e.to_string())?;
+    Ok(Execution {
+        reverted: !outcome.is_success(),
+        value: outcome.into_result().unwrap_or_else(|value| value),
+        gas_usage: vm.context().current_gas_usage()
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use xelis_types::Value;
+    use super::*;
+
+    const CODE: &str = "entry main() {
+        let values: u64[] = [1, 2, 3]
+        let total: u64 = 0
+        foreach value in values {
+            total += value
+        }
+        return total
+    }";
+
+    #[test]
+    fn test_compile_and_run() {
+        let bytes = compile(CODE).unwrap();
+        let execution = run(&bytes, "main", 1_000_000).unwrap();
+        assert_eq!(execution.value, Constant::Default(Value::U64(6)));
+        assert!(!execution.reverted);
+        assert!(execution.gas_usage > 0);
+
+        assert!(run(&bytes, "main", execution.gas_usage).is_ok());
+        assert!(run(&bytes, "main", execution.gas_usage - 1).is_err());
+        assert_eq!(run(&bytes, "other", 1_000_000).unwrap_err(), "entry 'other' not found");
+    }
+
+    #[test]
+    fn test_errors() {
+        let error = compile("entry main() {\n    return value\n}").unwrap_err();
+        assert!(error.contains("2 |     return value"));
+
+        assert!(run(&[0xFF, 0x00], "main", 1_000_000).unwrap_err().starts_with("invalid module"));
+    }
+}